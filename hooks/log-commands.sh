@@ -2,7 +2,18 @@
 # ContextKeeper Command Logger
 # Logs Bash commands executed by Claude Code for context recovery
 
-LOG_FILE="${CONTEXTKEEPER_LOG:-$HOME/.contextkeeper/command-history.jsonl}"
+CONTEXTKEEPER_DIR="${HOME}/.contextkeeper"
+
+# The old layout keeps the log at the top of the directory until
+# `context-keeper migrate` moves it: no layout_version marker, and a file
+# of that layout still there
+if [ ! -f "$CONTEXTKEEPER_DIR/layout_version" ] \
+    && { [ -f "$CONTEXTKEEPER_DIR/work-state.json" ] || [ -f "$CONTEXTKEEPER_DIR/command-history.jsonl" ]; }; then
+    DEFAULT_LOG="$CONTEXTKEEPER_DIR/command-history.jsonl"
+else
+    DEFAULT_LOG="$CONTEXTKEEPER_DIR/logs/command-history.jsonl"
+fi
+LOG_FILE="${CONTEXTKEEPER_LOG:-$DEFAULT_LOG}"
 LOG_DIR=$(dirname "$LOG_FILE")
 
 # Ensure log directory exists
@@ -22,6 +33,13 @@ if [ -z "$COMMAND" ]; then
     exit 0
 fi
 
+# Other sessions and `context-keeper log` append to the same file; hold
+# their lock over the write and the rotation where flock(1) exists
+exec 9>>"$LOG_FILE.lock"
+if command -v flock >/dev/null 2>&1; then
+    flock -w 5 9
+fi
+
 # Write as JSONL (one JSON object per line)
 jq -n -c \
     --arg ts "$TIMESTAMP" \
@@ -32,8 +50,8 @@ jq -n -c \
 
 # Rotate log if too large (>1MB)
 if [ -f "$LOG_FILE" ] && [ $(stat -f%z "$LOG_FILE" 2>/dev/null || stat -c%s "$LOG_FILE" 2>/dev/null) -gt 1048576 ]; then
-    tail -n 500 "$LOG_FILE" > "$LOG_FILE.tmp"
-    mv "$LOG_FILE.tmp" "$LOG_FILE"
+    tail -n 500 "$LOG_FILE" > "$LOG_FILE.tmp.$$"
+    mv "$LOG_FILE.tmp.$$" "$LOG_FILE"
 fi
 
 exit 0
@@ -2,7 +2,9 @@
 # PreCompact Hook: Save work state before context compression
 #
 # This script is executed by Claude Code before context compression.
-# It saves the current git state so it can be recovered after compression.
+# It runs `context-keeper save-state`, the same command `context-keeper
+# hooks install` registers, so the state lands wherever the data layout
+# of ~/.contextkeeper keeps it (see `context-keeper migrate`).
 #
 # Install in ~/.claude/settings.json:
 # {
@@ -17,50 +19,16 @@
 #   }
 # }
 
-set -e
+# The binary, when it isn't on PATH
+CONTEXT_KEEPER="${CONTEXTKEEPER_BIN:-context-keeper}"
 
-# Configuration
-CONTEXTKEEPER_DIR="${HOME}/.contextkeeper"
-WORK_STATE_FILE="${CONTEXTKEEPER_DIR}/work-state.json"
+if ! command -v "$CONTEXT_KEEPER" >/dev/null 2>&1; then
+    echo "[ContextKeeper] $CONTEXT_KEEPER not found; work state not saved" >&2
+    exit 0
+fi
 
-# Ensure directory exists
-mkdir -p "${CONTEXTKEEPER_DIR}"
-
-# Get current working directory (where Claude Code is running)
-CWD="${PWD}"
-
-# Collect working files from all git repos
-collect_working_files() {
-    find "${CWD}" -maxdepth 3 -name '.git' -type d 2>/dev/null | while read gitdir; do
-        repo=$(dirname "$gitdir")
-        git -C "$repo" diff --name-only 2>/dev/null | while read file; do
-            # Output relative path
-            rel_repo="${repo#${CWD}/}"
-            echo "${rel_repo}/${file}"
-        done
-    done | head -20
-}
-
-# Get timestamp
-TIMESTAMP=$(date -u +"%Y-%m-%dT%H:%M:%SZ")
-
-# Collect files
-FILES_JSON=$(collect_working_files | jq -R -s -c 'split("\n") | map(select(length > 0))')
-
-# Create work state JSON
-cat > "${WORK_STATE_FILE}" << EOF
-{
-  "saved_at": "${TIMESTAMP}",
-  "trigger": "pre_compact",
-  "task_summary": "",
-  "working_files": ${FILES_JSON},
-  "notes": "Auto-saved before context compression",
-  "todos": []
-}
-EOF
-
-# Output message (will be shown to Claude)
-echo "[ContextKeeper] Work state saved before compression:" >&2
-echo "  - Files tracked: $(echo "${FILES_JSON}" | jq 'length')" >&2
-echo "  - Saved at: ${TIMESTAMP}" >&2
-echo "  - Use get_dev_context('minimal') to recover after compression" >&2
+# Claude Code runs the hook in the project directory. --exit-zero keeps a
+# failed save from blocking the compaction; save-state still reports it.
+"$CONTEXT_KEEPER" --cwd "$PWD" save-state --exit-zero >&2
+echo "[ContextKeeper] Use get_dev_context('minimal') to recover after compression" >&2
+exit 0
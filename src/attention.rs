@@ -0,0 +1,194 @@
+//! The few things across all collectors most worth a look, ranked
+//!
+//! Sections come in a fixed order, so a conflict or a stopped rebase can
+//! sit below a screenful of targets. Each issue found in the collected
+//! context gets the score of its kind (`AttentionKind::default_score`,
+//! or `[priorities]`), and minimal and normal level open with the top
+//! few. Equal scores keep the order of the kinds, then of the text, so
+//! the same context always gives the same list. After days away, stale
+//! work state, artifacts and upstreams gain `idle::idle_bonus`.
+
+use crate::collectors::working_files::WorkingFileState;
+use crate::config::{AttentionKind, Config};
+use crate::context::{containers_listed, Context};
+use crate::format::{format_file_counts, format_size};
+use crate::i18n::Language;
+use crate::idle::{idle_bonus, idle_duration, is_staleness};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+
+/// Items shown at minimal and normal level
+pub const MINIMAL_ATTENTION_ITEMS: usize = 3;
+pub const NORMAL_ATTENTION_ITEMS: usize = 5;
+
+/// One issue worth a look, as shown in the attention list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttentionItem {
+    pub kind: AttentionKind,
+    pub score: i64,
+    pub text: String,
+}
+
+/// Score of `kind` under `config`; 0 or less leaves its issues out
+pub fn attention_score(config: &Config, kind: AttentionKind) -> i64 {
+    config
+        .priorities
+        .as_ref()
+        .and_then(|priorities| priorities.get(&kind))
+        .copied()
+        .unwrap_or_else(|| kind.default_score())
+}
+
+fn conflicted(files: usize) -> String {
+    match files {
+        1 => "1 conflicted file".to_string(),
+        n => format!("{} conflicted files", n),
+    }
+}
+
+/// Every issue in `ctx` with a positive score, highest first
+pub fn attention_items(config: &Config, ctx: &Context) -> Vec<AttentionItem> {
+    let mut found: Vec<(AttentionKind, String)> = Vec::new();
+
+    for repo in &ctx.git_repos {
+        if let Some(operation) = &repo.operation {
+            let conflicts = match repo.conflicted_files {
+                0 => String::new(),
+                n => format!(", {}", conflicted(n)),
+            };
+            found.push((
+                AttentionKind::GitOperation,
+                format!("`{}`: {} in progress{}", repo.name(), operation, conflicts),
+            ));
+        } else if repo.conflicted_files > 0 {
+            found.push((
+                AttentionKind::Conflicts,
+                format!("`{}`: {}", repo.name(), conflicted(repo.conflicted_files)),
+            ));
+        } else if repo.is_dirty {
+            found.push((
+                AttentionKind::DirtyRepo,
+                format!(
+                    "`{}` has uncommitted changes ({})",
+                    repo.name(),
+                    format_file_counts(repo, Language::En)
+                ),
+            ));
+        }
+        if repo.behind > 0 {
+            found.push((
+                AttentionKind::BehindUpstream,
+                format!(
+                    "`{}` is {} behind {}",
+                    repo.name(),
+                    match repo.behind {
+                        1 => "1 commit".to_string(),
+                        n => format!("{} commits", n),
+                    },
+                    repo.compared_to.as_deref().unwrap_or("upstream")
+                ),
+            ));
+        }
+    }
+
+    if let Some(resources) = &ctx.resources {
+        for disk in resources.low_disks() {
+            found.push((
+                AttentionKind::LowDisk,
+                format!(
+                    "Only {} free on `{}`",
+                    format_size(disk.free_bytes.unwrap_or_default()),
+                    disk.mount
+                ),
+            ));
+        }
+    }
+
+    for container in ctx.containers.iter().filter(|c| c.is_unhealthy()) {
+        found.push((
+            AttentionKind::UnhealthyContainer,
+            format!("Container `{}` is unhealthy", container.name),
+        ));
+    }
+    if containers_listed(ctx) {
+        let mut down: Vec<&str> = ctx
+            .targets
+            .iter()
+            .map(|t| t.container_name.as_str())
+            .filter(|name| !name.is_empty() && !ctx.containers.iter().any(|c| c.name == *name))
+            .collect();
+        down.sort_unstable();
+        down.dedup();
+        for name in down {
+            found.push((
+                AttentionKind::ContainerDown,
+                format!("Container `{}` isn't running", name),
+            ));
+        }
+    }
+
+    if let Some(selection) = ctx.device_selection.as_ref().filter(|s| s.pinned_missing()) {
+        found.push((
+            AttentionKind::DeviceMissing,
+            format!(
+                "Pinned device `{}` isn't connected",
+                selection.pinned.as_deref().unwrap_or_default()
+            ),
+        ));
+    }
+
+    let stale = ctx
+        .working_file_checks
+        .iter()
+        .filter(|check| {
+            matches!(
+                check.state,
+                WorkingFileState::Missing | WorkingFileState::Unchanged
+            )
+        })
+        .count();
+    if stale > 0 {
+        found.push((
+            AttentionKind::StaleWorkState,
+            format!(
+                "{} of {} saved working files gone or no longer modified",
+                stale,
+                ctx.working_file_checks.len()
+            ),
+        ));
+    }
+
+    for target in &ctx.targets {
+        if target.artifact.as_ref().is_some_and(|a| a.stale) {
+            found.push((
+                AttentionKind::StaleArtifact,
+                format!(
+                    "The `{}` build output is older than the last commit",
+                    target.name
+                ),
+            ));
+        }
+    }
+
+    // What went stale matters more the longer the project sat untouched
+    let bonus = idle_bonus(idle_duration(ctx));
+    let mut items: Vec<AttentionItem> = found
+        .into_iter()
+        .map(|(kind, text)| AttentionItem {
+            kind,
+            score: attention_score(config, kind),
+            text,
+        })
+        .filter(|item| item.score > 0)
+        .map(|mut item| {
+            if is_staleness(item.kind) {
+                item.score += bonus;
+            }
+            item
+        })
+        .collect();
+    items.sort_by(|a, b| {
+        (Reverse(a.score), a.kind, &a.text).cmp(&(Reverse(b.score), b.kind, &b.text))
+    });
+    items
+}
@@ -0,0 +1,196 @@
+//! Diagnostics for the MCP client, as `notifications/message`
+//!
+//! Events logged with target `CLIENT_LOG` (collector timeouts and
+//! warnings, missing tools, an unreadable config, cache hits) go to
+//! stderr like any other, and `ClientLogLayer` also forwards them to the
+//! session connected with `connect`, at or above the level the client
+//! asked for with `logging/setLevel` (info until it asks). Before a
+//! session connects, and in the CLI, they only reach stderr.
+
+use rmcp::model::{LoggingLevel, LoggingMessageNotificationParam};
+use rmcp::service::{Peer, RoleServer};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::oneshot;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::Context as LayerContext;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Target of the events meant for the client too
+pub const CLIENT_LOG: &str = "context_keeper::client";
+
+/// Logger name sent with every message
+pub const CLIENT_LOGGER: &str = "context-keeper";
+
+/// What the sending task is given
+enum Outgoing {
+    Message(LoggingMessageNotificationParam),
+    /// Answered once everything queued before it was sent
+    Flush(oneshot::Sender<()>),
+}
+
+/// Messages waiting for the active session
+static CLIENT: Mutex<Option<UnboundedSender<Outgoing>>> = Mutex::new(None);
+
+/// `rank` of the lowest level the client wants
+static CLIENT_LEVEL: AtomicU8 = AtomicU8::new(rank(LoggingLevel::Info));
+
+/// `level` ordered by severity, debug lowest
+const fn rank(level: LoggingLevel) -> u8 {
+    match level {
+        LoggingLevel::Debug => 0,
+        LoggingLevel::Info => 1,
+        LoggingLevel::Notice => 2,
+        LoggingLevel::Warning => 3,
+        LoggingLevel::Error => 4,
+        LoggingLevel::Critical => 5,
+        LoggingLevel::Alert => 6,
+        LoggingLevel::Emergency => 7,
+    }
+}
+
+/// The MCP level of a tracing level; trace counts as debug
+pub fn mcp_level(level: &Level) -> LoggingLevel {
+    match *level {
+        Level::ERROR => LoggingLevel::Error,
+        Level::WARN => LoggingLevel::Warning,
+        Level::INFO => LoggingLevel::Info,
+        Level::DEBUG | Level::TRACE => LoggingLevel::Debug,
+    }
+}
+
+/// `logging/setLevel`: only messages at `level` or above from now on
+pub fn set_client_level(level: LoggingLevel) {
+    CLIENT_LEVEL.store(rank(level), Ordering::Relaxed);
+}
+
+/// Whether the client wants messages at `level`
+pub fn client_wants(level: LoggingLevel) -> bool {
+    rank(level) >= CLIENT_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Forward the messages to `peer` from now on, in the order they were
+/// logged, replacing an earlier session. Spawns the sending task, so it
+/// must run inside the tokio runtime.
+pub fn connect(peer: Peer<RoleServer>) {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    *CLIENT.lock().unwrap_or_else(|e| e.into_inner()) = Some(tx);
+    tokio::spawn(async move {
+        while let Some(outgoing) = rx.recv().await {
+            match outgoing {
+                Outgoing::Message(message) => {
+                    // Gone or not listening; stderr had it anyway
+                    if peer.notify_logging_message(message).await.is_err() {
+                        break;
+                    }
+                }
+                Outgoing::Flush(done) => {
+                    let _ = done.send(());
+                }
+            }
+        }
+    });
+}
+
+/// Wait until the messages logged so far were sent, so that those about
+/// a tool call reach the client before its result
+pub async fn flush_client_log() {
+    let (done, sent) = oneshot::channel();
+    let queued = CLIENT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .is_some_and(|tx| tx.send(Outgoing::Flush(done)).is_ok());
+    if queued {
+        let _ = sent.await;
+    }
+}
+
+/// The message and other fields of an event, as the notification's data
+#[derive(Default)]
+struct Fields {
+    message: String,
+    others: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        let value = format!("{:?}", value);
+        if field.name() == "message" {
+            self.message = value;
+        } else {
+            self.others
+                .insert(field.name().to_string(), serde_json::Value::from(value));
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.others
+                .insert(field.name().to_string(), serde_json::Value::from(value));
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.others
+            .insert(field.name().to_string(), serde_json::Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.others
+            .insert(field.name().to_string(), serde_json::Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.others
+            .insert(field.name().to_string(), serde_json::Value::from(value));
+    }
+}
+
+/// Sends `CLIENT_LOG` events to the connected session; see `client_log_layer`
+pub struct ClientLogLayer;
+
+/// `ClientLogLayer`, filtered to its target, to add to the registry
+pub fn client_log_layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    ClientLogLayer.with_filter(Targets::new().with_target(CLIENT_LOG, Level::DEBUG))
+}
+
+impl<S: Subscriber> Layer<S> for ClientLogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: LayerContext<'_, S>) {
+        let level = mcp_level(event.metadata().level());
+        if !client_wants(level) {
+            return;
+        }
+        let client = CLIENT.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(tx) = client.as_ref() else {
+            return;
+        };
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        // A bare message stays a string; fields make it an object
+        let data = if fields.others.is_empty() {
+            serde_json::Value::from(fields.message)
+        } else {
+            let mut data = fields.others;
+            data.insert(
+                "message".to_string(),
+                serde_json::Value::from(fields.message),
+            );
+            serde_json::Value::Object(data)
+        };
+        let _ = tx.send(Outgoing::Message(LoggingMessageNotificationParam {
+            level,
+            logger: Some(CLIENT_LOGGER.to_string()),
+            data,
+        }));
+    }
+}
@@ -0,0 +1,163 @@
+//! Timestamps in the collected data checked against the clock
+//!
+//! Lab machines drift, and a work state saved by a machine two hours
+//! ahead looks fresh for two hours. Timestamps more than
+//! `CLOCK_SKEW_TOLERANCE_SECS` ahead of now are reported in the
+//! collector's warnings and the diagnostics, and rendered ages are
+//! clamped so they never run backwards. A history log whose timestamps
+//! step back by more than `HISTORY_BACKSTEP_TOLERANCE_SECS` is reported
+//! too: that usually means lines merged from another log, or a corrupted
+//! one.
+
+use crate::collectors::HistoryEntry;
+use crate::context::Context;
+use crate::format::format_duration_secs;
+use chrono::{DateTime, Utc};
+
+/// How far ahead of now a timestamp may be before it is reported
+pub const CLOCK_SKEW_TOLERANCE_SECS: i64 = 120;
+
+/// How far back one history entry may be from the one before it
+pub const HISTORY_BACKSTEP_TOLERANCE_SECS: i64 = 3600;
+
+/// A timestamp that doesn't fit the clock, and the collector it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClockWarning {
+    pub collector: &'static str,
+    pub message: String,
+}
+
+fn parse(timestamp: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// `time`, or `now` when it is later; for "3m ago" style ages
+pub fn clamp_to_now(time: DateTime<Utc>, now: DateTime<Utc>) -> DateTime<Utc> {
+    time.min(now)
+}
+
+/// Seconds `timestamp` is ahead of `now`, when that is beyond
+/// `CLOCK_SKEW_TOLERANCE_SECS`. Unparsable timestamps are never ahead.
+pub fn future_skew(timestamp: &str, now: DateTime<Utc>) -> Option<i64> {
+    let ahead = (parse(timestamp)? - now).num_seconds();
+    (ahead > CLOCK_SKEW_TOLERANCE_SECS).then_some(ahead)
+}
+
+fn ahead_warning(collector: &'static str, what: &str, ahead: i64) -> ClockWarning {
+    ClockWarning {
+        collector,
+        message: format!(
+            "{} {} in the future — check system clocks",
+            what,
+            format_duration_secs(ahead as u64)
+        ),
+    }
+}
+
+/// Entries, in log order, whose timestamp is more than
+/// `HISTORY_BACKSTEP_TOLERANCE_SECS` before the latest one above them,
+/// with how far back they are. Unparsable timestamps are skipped, and so
+/// are ones ahead of `now`, which `future_skew` reports.
+pub fn history_backsteps(entries: &[HistoryEntry], now: DateTime<Utc>) -> Vec<(usize, i64)> {
+    let mut latest: Option<DateTime<Utc>> = None;
+    let mut steps = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let Some(time) = parse(&entry.timestamp) else {
+            continue;
+        };
+        if future_skew(&entry.timestamp, now).is_some() {
+            continue;
+        }
+        match latest {
+            Some(last) if (last - time).num_seconds() > HISTORY_BACKSTEP_TOLERANCE_SECS => {
+                steps.push((index, (last - time).num_seconds()));
+            }
+            Some(last) if last >= time => {}
+            _ => latest = Some(time),
+        }
+    }
+    steps
+}
+
+/// Every timestamp in `ctx` that doesn't fit the clock: saved state,
+/// history, open files, build results and captured environment ahead of
+/// `ctx.now()`, and history going back in time
+pub fn clock_warnings(ctx: &Context) -> Vec<ClockWarning> {
+    let now = ctx.now();
+    let mut warnings = Vec::new();
+
+    if let Some(ws) = &ctx.work_state {
+        if let Some(ahead) = future_skew(&ws.saved_at, now) {
+            warnings.push(ahead_warning(
+                "work_state",
+                "work state saved_at appears to be",
+                ahead,
+            ));
+        }
+    }
+
+    let ahead: Vec<i64> = ctx
+        .command_history
+        .iter()
+        .filter_map(|entry| future_skew(&entry.timestamp, now))
+        .collect();
+    if let Some(&most) = ahead.iter().max() {
+        let what = match ahead.len() {
+            1 => "1 history entry appears to be".to_string(),
+            n => format!("{} history entries appear to be up to", n),
+        };
+        warnings.push(ahead_warning("history", &what, most));
+    }
+    let backsteps = history_backsteps(&ctx.command_history, now);
+    if let Some(&(index, back)) = backsteps.iter().max_by_key(|(_, back)| *back) {
+        let others = match backsteps.len() {
+            1 => String::new(),
+            n => format!(" ({} such jumps)", n),
+        };
+        warnings.push(ClockWarning {
+            collector: "history",
+            message: format!(
+                "history timestamps jump back {} before `{}`{}; the log may be merged or corrupted",
+                format_duration_secs(back as u64),
+                ctx.command_history[index].command,
+                others
+            ),
+        });
+    }
+
+    if let Some(open_files) = &ctx.open_files {
+        if let Some(ahead) = future_skew(&open_files.updated_at, now) {
+            warnings.push(ahead_warning(
+                "open_files",
+                "open-files.json updated_at appears to be",
+                ahead,
+            ));
+        }
+    }
+
+    let ahead: Vec<i64> = ctx
+        .build_results
+        .iter()
+        .filter_map(|record| future_skew(&record.timestamp, now))
+        .collect();
+    if let Some(&most) = ahead.iter().max() {
+        warnings.push(ahead_warning(
+            "build_results",
+            "a build result appears to be",
+            most,
+        ));
+    }
+
+    if let Some(env) = &ctx.shell_env {
+        if let Some(ahead) = future_skew(&env.timestamp, now) {
+            warnings.push(ahead_warning(
+                "shell_env",
+                "the captured environment appears to be",
+                ahead,
+            ));
+        }
+    }
+    warnings
+}
@@ -0,0 +1,202 @@
+//! ADB and fastboot devices
+//!
+//! `[adb] default_serial` or `default_alias` pins the device to use. The
+//! pinned device is marked `is_default` and listed first; one that isn't
+//! connected is a warning, never a reason to pick another. `ANDROID_SERIAL`
+//! in the environment is reported as well, since bare `adb` follows it.
+
+use crate::collectors::git::write_state_file;
+use crate::collectors::AdbDevice;
+#[cfg(feature = "adb")]
+use crate::collectors::{collector_unavailable, failure_reason, run_program, CommandRunner};
+use crate::config::ProjectContext;
+use crate::context::CollectorRegistry;
+use crate::state::project_state_dir;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The devices from the last collection, saved in `devices.json` for
+/// `context-keeper statusline`, which never runs adb
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeviceSnapshot {
+    pub saved_at: String,
+    pub devices: Vec<AdbDevice>,
+}
+
+pub fn devices_snapshot_path(root: &Path) -> PathBuf {
+    project_state_dir(root).join("devices.json")
+}
+
+pub fn save_devices_snapshot(root: &Path, devices: &[AdbDevice]) {
+    write_state_file(
+        &devices_snapshot_path(root),
+        &DeviceSnapshot {
+            saved_at: chrono::Utc::now().to_rfc3339(),
+            devices: devices.to_vec(),
+        },
+    );
+}
+
+pub fn load_devices_snapshot(root: &Path) -> Option<DeviceSnapshot> {
+    let json = fs::read_to_string(devices_snapshot_path(root)).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// Which device commands should go to
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeviceSelection {
+    /// `default_serial` or `default_alias` as configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned: Option<String>,
+    /// Serial of the connected device `pinned` matched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
+    /// `ANDROID_SERIAL` of the environment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub android_serial: Option<String>,
+}
+
+impl DeviceSelection {
+    /// Pinned but not among the connected devices
+    pub fn pinned_missing(&self) -> bool {
+        self.pinned.is_some() && self.serial.is_none()
+    }
+}
+
+/// Whether `device` is the one `alias` names: its model or codename as
+/// `adb devices -l` reports them, case and `_`/space aside
+fn matches_alias(device: &AdbDevice, alias: &str) -> bool {
+    let normalize = |name: &str| name.replace(' ', "_").to_lowercase();
+    let alias = normalize(alias);
+    [&device.model, &device.codename]
+        .into_iter()
+        .any(|name| !name.is_empty() && normalize(name) == alias)
+}
+
+/// Mark the pinned device (`[adb]`, else `ANDROID_SERIAL`) and move it to
+/// the front. `None` when nothing pins a device.
+pub fn select_default_device(
+    project: &ProjectContext,
+    devices: &mut [AdbDevice],
+) -> Option<DeviceSelection> {
+    let config = project.config.adb.as_ref();
+    let android_serial = std::env::var("ANDROID_SERIAL")
+        .ok()
+        .filter(|serial| !serial.is_empty());
+    let by_serial = config.and_then(|c| c.default_serial.clone());
+    let by_alias = config.and_then(|c| c.default_alias.clone());
+    if by_serial.is_none() && by_alias.is_none() && android_serial.is_none() {
+        return None;
+    }
+
+    let index = match (&by_serial, &by_alias, &android_serial) {
+        (Some(serial), _, _) => devices.iter().position(|d| &d.serial == serial),
+        (None, Some(alias), _) => devices.iter().position(|d| matches_alias(d, alias)),
+        (None, None, Some(serial)) => devices.iter().position(|d| &d.serial == serial),
+        (None, None, None) => None,
+    };
+    let serial = index.map(|index| {
+        devices[index].is_default = true;
+        devices[..=index].rotate_right(1);
+        devices[0].serial.clone()
+    });
+    Some(DeviceSelection {
+        pinned: by_serial.or(by_alias),
+        serial,
+        android_serial,
+    })
+}
+
+/// Adds the `devices` collector, or a stand-in without the `adb` feature
+pub fn register(registry: &mut CollectorRegistry) {
+    #[cfg(feature = "adb")]
+    registry.add_fn("devices", |project| {
+        let mut devices = collect_adb_devices(&*project.runner);
+        let selection = select_default_device(project, &mut devices);
+        save_devices_snapshot(&project.root, &devices);
+        Ok(crate::context::CollectorOutput::Devices(devices, selection))
+    });
+    #[cfg(not(feature = "adb"))]
+    registry.add(crate::context::NotBuilt {
+        name: "devices",
+        feature: "adb",
+    });
+}
+
+#[cfg(feature = "adb")]
+pub fn collect_adb_devices(runner: &dyn CommandRunner) -> Vec<AdbDevice> {
+    let mut devices = Vec::new();
+
+    // Collect ADB devices
+    if let Ok(output) = run_program(runner, "adb", &["devices", "-l"]) {
+        if output.status.success() {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            for line in stdout.lines().skip(1) {
+                // Skip "List of devices attached"
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                if parts.len() >= 2 {
+                    let serial = parts[0].to_string();
+                    let state = parts[1].to_string();
+
+                    // Skip offline devices
+                    if state == "offline" {
+                        continue;
+                    }
+
+                    // `model:Pixel_7 device:panther ...`
+                    let field = |key: &str| {
+                        parts[2..]
+                            .iter()
+                            .find_map(|part| part.strip_prefix(key))
+                            .unwrap_or_default()
+                            .to_string()
+                    };
+                    devices.push(AdbDevice {
+                        serial,
+                        state,
+                        device_type: "adb".to_string(),
+                        model: field("model:"),
+                        codename: field("device:"),
+                        is_default: false,
+                    });
+                }
+            }
+        } else {
+            collector_unavailable(failure_reason("adb devices", &output));
+        }
+    }
+
+    // Collect Fastboot devices
+    if let Ok(output) = run_program(runner, "fastboot", &["devices", "-l"]) {
+        if output.status.success() {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            for line in stdout.lines() {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                if !parts.is_empty() {
+                    let serial = parts[0].to_string();
+                    devices.push(AdbDevice {
+                        serial,
+                        state: "fastboot".to_string(),
+                        device_type: "fastboot".to_string(),
+                        model: String::new(),
+                        codename: String::new(),
+                        is_default: false,
+                    });
+                }
+            }
+        }
+    }
+
+    devices
+}
@@ -0,0 +1,100 @@
+//! Build outputs per target: `ARTIFACT_PATH` in the target .conf, or the
+//! target's entry in `[artifacts]`
+//!
+//! The glob resolves against the project root. The newest matching file
+//! is the target's artifact. It is stale when the repository holding it
+//! (the innermost one with a `.git` between the artifact and the root)
+//! has a commit newer than the file. A glob that matches nothing is "no
+//! artifacts", never an error.
+
+use crate::collectors::git::git_in;
+use crate::collectors::working_files::holding_repo;
+use crate::collectors::BuildTarget;
+use crate::config::ProjectContext;
+use crate::paths::{display_path, project_relative};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Limit on each `git log` for the newest commit
+pub const ARTIFACT_GIT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The newest file an artifact glob matched
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Artifact {
+    /// Relative to the project root
+    pub path: String,
+    /// RFC 3339
+    pub modified_at: String,
+    pub size: u64,
+    /// Older than the newest commit of the repository holding it
+    #[serde(default)]
+    pub stale: bool,
+}
+
+impl Artifact {
+    pub fn file_name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+}
+
+/// Newest file matching `pattern`, with its modification time
+fn newest_match(project: &ProjectContext, pattern: &str) -> Option<(PathBuf, SystemTime, u64)> {
+    let pattern = display_path(&project.resolve(pattern));
+    glob::glob(&pattern)
+        .ok()?
+        .flatten()
+        .filter_map(|path| {
+            let metadata = path.metadata().ok().filter(|m| m.is_file())?;
+            Some((path, metadata.modified().ok()?, metadata.len()))
+        })
+        .max_by_key(|(_, modified, _)| *modified)
+}
+
+/// Commit time of HEAD in `repo`
+fn newest_commit(project: &ProjectContext, repo: &Path) -> Option<SystemTime> {
+    let output = git_in(
+        &*project.runner,
+        repo,
+        &["log", "-1", "--format=%ct"],
+        Some(ARTIFACT_GIT_TIMEOUT),
+    )
+    .ok()
+    .filter(|output| output.status.success())?;
+    let seconds: u64 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .ok()?;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// Fill in `artifact_path` from `[artifacts]` and look up each target's
+/// artifact. One `git log` per repository with artifacts in it.
+pub fn attach_artifacts(project: &ProjectContext, targets: &mut [BuildTarget]) {
+    let configured = project.config.artifacts.as_ref();
+    let mut commits: HashMap<PathBuf, Option<SystemTime>> = HashMap::new();
+    for target in targets.iter_mut() {
+        if let Some(pattern) = configured.and_then(|artifacts| artifacts.get(&target.name)) {
+            target.artifact_path = pattern.clone();
+        }
+        if target.artifact_path.is_empty() {
+            continue;
+        }
+        let Some((path, modified, size)) = newest_match(project, &target.artifact_path) else {
+            continue;
+        };
+        let stale = holding_repo(&project.root, &path).is_some_and(|repo| {
+            commits
+                .entry(repo)
+                .or_insert_with_key(|repo| newest_commit(project, repo))
+                .is_some_and(|commit| commit > modified)
+        });
+        target.artifact = Some(Artifact {
+            path: project_relative(&project.root, &path),
+            modified_at: chrono::DateTime::<chrono::Utc>::from(modified).to_rfc3339(),
+            size,
+            stale,
+        });
+    }
+}
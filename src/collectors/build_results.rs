@@ -0,0 +1,119 @@
+//! Outcome of the builds `context-keeper build-wrap` ran
+//!
+//! The wrapper appends one record per build to the project's
+//! `builds.jsonl`: the target it was matched to, exit code, duration and,
+//! for a failed build, the last lines of its output. The newest record per
+//! target is reported, so the context can say whether the last build of
+//! each one worked.
+
+use crate::config::{Config, ProjectContext};
+use crate::error::ContextKeeperError;
+use crate::state::project_state_dir;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// Records kept in `builds.jsonl`, without `[builds] keep`
+pub const DEFAULT_BUILD_RECORDS: usize = 200;
+
+/// Output lines kept from a failed build, without `[builds] output_lines`
+pub const DEFAULT_BUILD_OUTPUT_LINES: usize = 50;
+
+/// Targets reported, newest build first
+pub const MAX_BUILD_RESULTS: usize = 5;
+
+/// Output lines that name a failure
+static ERROR_LINE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(error|fatal)\b").unwrap());
+
+/// One wrapped build
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildRecord {
+    /// When the build finished, RFC 3339
+    pub timestamp: String,
+    /// Matched or `--target` build target; empty when none matched
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub target: String,
+    pub command: String,
+    /// The command's exit code; 128 + the signal when one killed it
+    pub exit_code: i32,
+    pub duration_secs: u64,
+    /// Last lines of output of a failed build, redacted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub output: Vec<String>,
+}
+
+impl BuildRecord {
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == 0
+    }
+
+    /// Target name, else the command
+    pub fn label(&self) -> &str {
+        if self.target.is_empty() {
+            &self.command
+        } else {
+            &self.target
+        }
+    }
+
+    /// The output line most likely to say what went wrong: the first one
+    /// naming an error (later ones tend to follow from it), else the last
+    pub fn error_line(&self) -> Option<&str> {
+        let mut lines = self.output.iter().map(|line| line.trim());
+        lines
+            .clone()
+            .find(|line| ERROR_LINE.is_match(line))
+            .or_else(|| lines.rfind(|line| !line.is_empty()))
+    }
+}
+
+/// `~/.contextkeeper/projects/<root-hash>/builds.jsonl`
+pub fn builds_log_path(root: &Path) -> PathBuf {
+    project_state_dir(root).join("builds.jsonl")
+}
+
+/// `[builds] keep`
+pub fn build_records_kept(config: &Config) -> usize {
+    config
+        .builds
+        .as_ref()
+        .and_then(|b| b.keep)
+        .unwrap_or(DEFAULT_BUILD_RECORDS)
+}
+
+/// `[builds] output_lines`
+pub fn build_output_lines(config: &Config) -> usize {
+    config
+        .builds
+        .as_ref()
+        .and_then(|b| b.output_lines)
+        .unwrap_or(DEFAULT_BUILD_OUTPUT_LINES)
+}
+
+/// Newest record per target (or per command, for unmatched builds),
+/// newest first
+pub fn collect_build_results(
+    project: &ProjectContext,
+) -> Result<Vec<BuildRecord>, ContextKeeperError> {
+    let path = builds_log_path(&project.root);
+    let file = match fs::File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(ContextKeeperError::state_io(&path, e)),
+    };
+    let mut newest: Vec<BuildRecord> = Vec::new();
+    for line in io::BufReader::new(file).lines().map_while(Result::ok) {
+        let Ok(record) = serde_json::from_str::<BuildRecord>(&line) else {
+            continue;
+        };
+        newest.retain(|r| r.label() != record.label());
+        newest.push(record);
+    }
+    newest.reverse();
+    newest.truncate(MAX_BUILD_RESULTS);
+    Ok(newest)
+}
@@ -0,0 +1,168 @@
+//! TODO/FIXME/XXX comments in the working files, with
+//! `[work_state] scan_todos = true`
+//!
+//! Only the files of `WorkState::working_files` are read, each up to
+//! `MAX_TODO_FILE_SIZE`. A file with a NUL byte is taken for binary and
+//! skipped. A marker counts when it is a whole word after a comment
+//! opener on the same line, so `TODO` in a string or an identifier like
+//! `TODO_LIST` is left alone.
+
+use crate::collectors::CodeTodo;
+use crate::config::{Config, ProjectContext};
+use crate::state::TodoItem;
+use std::collections::HashSet;
+use std::fs;
+use std::io::Read;
+
+/// Files larger than this are skipped
+pub const MAX_TODO_FILE_SIZE: u64 = 512 * 1024;
+
+/// Comments recorded at most, over all files
+pub const MAX_CODE_TODOS: usize = 50;
+
+/// Comment text beyond this many characters is cut
+const MAX_TODO_TEXT: usize = 160;
+
+const MARKERS: &[&str] = &["TODO", "FIXME", "XXX"];
+
+/// Line comment and block comment openers of the usual languages
+const COMMENT_OPENERS: &[&str] = &["//", "#", "/*", "--", ";", "<!--", "%"];
+
+/// `[work_state] scan_todos`
+pub fn scan_todos_enabled(config: &Config) -> bool {
+    config
+        .work_state
+        .as_ref()
+        .and_then(|w| w.scan_todos)
+        .unwrap_or(false)
+}
+
+fn is_word_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+/// The comment from its marker on, when `line` has a marked comment
+pub fn todo_comment(line: &str) -> Option<String> {
+    let bytes = line.as_bytes();
+    let start = MARKERS
+        .iter()
+        .flat_map(|marker| {
+            line.match_indices(marker).filter_map(|(pos, _)| {
+                let end = pos + marker.len();
+                let whole_word = (pos == 0 || !is_word_byte(bytes[pos - 1]))
+                    && bytes.get(end).is_none_or(|b| !is_word_byte(*b));
+                whole_word.then_some(pos)
+            })
+        })
+        .min()?;
+
+    let before = &line[..start];
+    // A `*` continues a block comment
+    let in_comment = before.trim_start().starts_with('*')
+        || COMMENT_OPENERS.iter().any(|opener| before.contains(opener));
+    if !in_comment {
+        return None;
+    }
+
+    let text = line[start..]
+        .trim_end()
+        .trim_end_matches("*/")
+        .trim_end_matches("-->")
+        .trim_end();
+    Some(match text.char_indices().nth(MAX_TODO_TEXT) {
+        Some((cut, _)) => format!("{}…", &text[..cut]),
+        None => text.to_string(),
+    })
+}
+
+/// Contents of `path` as text, `None` for binary, oversized or unreadable
+/// files
+fn read_text_file(path: &std::path::Path) -> Option<String> {
+    let file = fs::File::open(path).ok()?;
+    if !file.metadata().ok()?.is_file() {
+        return None;
+    }
+    let mut bytes = Vec::new();
+    // One byte over the limit tells an oversized file apart
+    file.take(MAX_TODO_FILE_SIZE + 1)
+        .read_to_end(&mut bytes)
+        .ok()?;
+    if bytes.len() as u64 > MAX_TODO_FILE_SIZE || bytes.contains(&0) {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Marked comments in `files` (relative to the project root or absolute),
+/// in file order, files `.contextkeeper-ignore` matches left out. Empty
+/// unless `scan_todos` is on.
+pub fn collect_code_todos(project: &ProjectContext, files: &[String]) -> Vec<CodeTodo> {
+    if !scan_todos_enabled(&project.config) {
+        return Vec::new();
+    }
+    let mut todos = Vec::new();
+    for file in files {
+        let path = project.resolve(file);
+        if project.ignore.is_ignored(&path, false) {
+            continue;
+        }
+        let Some(content) = read_text_file(&path) else {
+            continue;
+        };
+        for (index, line) in content.lines().enumerate() {
+            if todos.len() >= MAX_CODE_TODOS {
+                return todos;
+            }
+            if let Some(text) = todo_comment(line) {
+                todos.push(CodeTodo {
+                    file: file.clone(),
+                    line: index + 1,
+                    text,
+                });
+            }
+        }
+    }
+    todos
+}
+
+/// Lowercase words of two or more characters, markers left out
+fn words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 2)
+        .map(str::to_lowercase)
+        .filter(|word| !MARKERS.iter().any(|m| m.eq_ignore_ascii_case(word)))
+        .collect()
+}
+
+/// Whether `text` reads like one of `todos`: most words of the shorter
+/// of the two appear in the other
+pub fn matches_todo_item(text: &str, todos: &[TodoItem]) -> bool {
+    let comment = words(text);
+    if comment.is_empty() {
+        return false;
+    }
+    todos.iter().any(|todo| {
+        let item = words(&todo.content);
+        let shorter = comment.len().min(item.len());
+        shorter > 0 && comment.intersection(&item).count() * 4 >= shorter * 3
+    })
+}
+
+/// Code TODOs shown in a work state at most
+pub const MAX_SHOWN_CODE_TODOS: usize = 10;
+
+/// `code_todos` worth showing beside the explicit `todos`: those that
+/// don't repeat one, capped at `MAX_SHOWN_CODE_TODOS`, and how many more
+/// there were
+pub fn code_todos_to_show<'a>(
+    code_todos: &'a [CodeTodo],
+    todos: &[TodoItem],
+) -> (Vec<&'a CodeTodo>, usize) {
+    let mut shown: Vec<&CodeTodo> = code_todos
+        .iter()
+        .filter(|todo| !matches_todo_item(&todo.text, todos))
+        .collect();
+    let hidden = shown.len().saturating_sub(MAX_SHOWN_CODE_TODOS);
+    shown.truncate(MAX_SHOWN_CODE_TODOS);
+    (shown, hidden)
+}
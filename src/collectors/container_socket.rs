@@ -0,0 +1,245 @@
+//! Running containers from the podman or docker REST socket
+//!
+//! `GET /containers/json` on the runtime's local socket answers what
+//! `<runtime> ps` does, without spawning the CLI or needing it on PATH.
+//! Both runtimes serve the Docker-compatible endpoint; podman's own
+//! (libpod) listing differs in names, ports and mounts, and is read too.
+//! The socket is asked directly with hyper, not through
+//! `ProjectContext::runner`, so `[containers] backend = "cli"` keeps a
+//! replaying runner in charge. `podman ps --format json` prints the
+//! libpod listing too, so the CLI backend parses it here as well.
+
+#[cfg(all(unix, feature = "containers"))]
+use crate::collectors::block_on;
+use crate::collectors::ContainerInfo;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Limit for connecting, sending the request and reading the reply
+pub const CONTAINER_SOCKET_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Sockets `runtime` listens on by default, in the order they are tried:
+/// the rootless one under `$XDG_RUNTIME_DIR`, then the system-wide one
+pub fn socket_candidates(runtime: &str) -> Vec<PathBuf> {
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from);
+    let program = Path::new(runtime)
+        .file_name()
+        .and_then(|name| name.to_str());
+    match program.unwrap_or(runtime) {
+        "podman" => runtime_dir
+            .map(|dir| dir.join("podman/podman.sock"))
+            .into_iter()
+            .chain([PathBuf::from("/run/podman/podman.sock")])
+            .collect(),
+        "docker" => runtime_dir
+            .map(|dir| dir.join("docker.sock"))
+            .into_iter()
+            .chain([PathBuf::from("/var/run/docker.sock")])
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The first of `socket_candidates` that exists
+pub fn find_socket(runtime: &str) -> Option<PathBuf> {
+    socket_candidates(runtime)
+        .into_iter()
+        .find(|path| path.exists())
+}
+
+/// A published port: `PrivatePort`/`PublicPort` from Docker,
+/// `container_port`/`host_port` from libpod
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ApiPort {
+    #[serde(default, rename = "PrivatePort", alias = "container_port")]
+    pub private_port: u16,
+    #[serde(default, rename = "PublicPort", alias = "host_port")]
+    pub public_port: Option<u16>,
+    #[serde(default, rename = "Type", alias = "protocol")]
+    pub protocol: String,
+    #[serde(default, rename = "IP", alias = "host_ip")]
+    pub ip: String,
+}
+
+/// A mount: an object from Docker, only the destination from libpod
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ApiMount {
+    Detailed {
+        #[serde(default, rename = "Type")]
+        kind: String,
+        #[serde(default, rename = "Source")]
+        source: String,
+        #[serde(default, rename = "Destination")]
+        destination: String,
+    },
+    Destination(String),
+}
+
+impl ApiPort {
+    /// As `ps` shows it: "0.0.0.0:8080->80/tcp", "8080->80/tcp" without a
+    /// host address, "80/tcp" when not published
+    pub fn display(&self) -> String {
+        let protocol = if self.protocol.is_empty() {
+            "tcp"
+        } else {
+            &self.protocol
+        };
+        match (self.public_port, self.ip.as_str()) {
+            (Some(public), "") => format!("{}->{}/{}", public, self.private_port, protocol),
+            (Some(public), ip) => format!("{}:{}->{}/{}", ip, public, self.private_port, protocol),
+            (None, _) => format!("{}/{}", self.private_port, protocol),
+        }
+    }
+}
+
+impl ApiMount {
+    pub fn destination(&self) -> &str {
+        match self {
+            ApiMount::Detailed { destination, .. } => destination,
+            ApiMount::Destination(destination) => destination,
+        }
+    }
+}
+
+/// One entry of `GET /containers/json`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ApiContainer {
+    /// `/name` from Docker and podman's compatible endpoint, `name` from
+    /// libpod
+    #[serde(default, rename = "Names")]
+    pub names: Vec<String>,
+    /// "running", "exited", ...
+    #[serde(default, rename = "State")]
+    pub state: String,
+    /// "Up 2 hours (healthy)", as `ps` shows it; empty from older libpod
+    #[serde(default, rename = "Status")]
+    pub status: String,
+    #[serde(default, rename = "Labels", deserialize_with = "null_as_default")]
+    pub labels: BTreeMap<String, String>,
+    #[serde(default, rename = "Ports", deserialize_with = "null_as_default")]
+    pub ports: Vec<ApiPort>,
+    #[serde(default, rename = "Mounts", deserialize_with = "null_as_default")]
+    pub mounts: Vec<ApiMount>,
+}
+
+/// libpod sends `null` for an empty list or map
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+impl ApiContainer {
+    /// As `<runtime> ps` would have listed it
+    pub fn to_container_info(&self, runtime: &str) -> ContainerInfo {
+        let name = self
+            .names
+            .first()
+            .map(|name| name.trim_start_matches('/').to_string())
+            .unwrap_or_default();
+        let status = if self.status.is_empty() {
+            self.state.clone()
+        } else {
+            self.status.clone()
+        };
+        ContainerInfo {
+            name,
+            status,
+            runtime: runtime.to_string(),
+            labels: self.labels.clone(),
+            ports: self.ports.iter().map(ApiPort::display).collect(),
+            mounts: self
+                .mounts
+                .iter()
+                .map(|mount| mount.destination().to_string())
+                .filter(|destination| !destination.is_empty())
+                .collect(),
+        }
+    }
+}
+
+/// The containers in a `/containers/json` reply
+pub fn parse_containers(body: &[u8], runtime: &str) -> Result<Vec<ContainerInfo>, String> {
+    let listed: Vec<ApiContainer> = serde_json::from_slice(body)
+        .map_err(|e| format!("unexpected /containers/json reply: {}", e))?;
+    Ok(listed
+        .iter()
+        .map(|container| container.to_container_info(runtime))
+        .filter(|container| !container.name.is_empty())
+        .collect())
+}
+
+/// `GET path` on the unix socket `socket`: the status code and the body
+#[cfg(all(unix, feature = "containers"))]
+fn get(socket: &Path, path: &str) -> io::Result<(u16, Vec<u8>)> {
+    use http_body_util::{BodyExt, Empty};
+    use hyper::body::Bytes;
+    use hyper_util::rt::TokioIo;
+
+    let request = hyper::Request::get(path)
+        .header(hyper::header::HOST, "localhost")
+        .header(hyper::header::ACCEPT, "application/json")
+        .body(Empty::<Bytes>::new())
+        .map_err(io::Error::other)?;
+    let exchange = async {
+        let stream = tokio::net::UnixStream::connect(socket).await?;
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .map_err(io::Error::other)?;
+        tokio::spawn(connection);
+        let response = sender
+            .send_request(request)
+            .await
+            .map_err(io::Error::other)?;
+        let status = response.status().as_u16();
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(io::Error::other)?;
+        Ok((status, body.to_bytes().to_vec()))
+    };
+    block_on(async {
+        tokio::time::timeout(CONTAINER_SOCKET_TIMEOUT, exchange)
+            .await
+            .unwrap_or_else(|_| {
+                Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("no reply within {:?}", CONTAINER_SOCKET_TIMEOUT),
+                ))
+            })
+    })?
+}
+
+#[cfg(not(all(unix, feature = "containers")))]
+fn get(_socket: &Path, _path: &str) -> io::Result<(u16, Vec<u8>)> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "unix sockets are not available in this build",
+    ))
+}
+
+/// Running containers from `socket`, or why they couldn't be read
+pub fn list_containers_via_socket(
+    socket: &Path,
+    runtime: &str,
+) -> Result<Vec<ContainerInfo>, String> {
+    let describe = |e: io::Error| format!("{}: {}", socket.display(), e);
+    let (status, body) = get(socket, "/containers/json").map_err(describe)?;
+    if status != 200 {
+        let message = String::from_utf8_lossy(&body);
+        return Err(format!(
+            "{}: /containers/json answered {} {}",
+            socket.display(),
+            status,
+            message.trim()
+        ));
+    }
+    parse_containers(&body, runtime)
+}
@@ -0,0 +1,303 @@
+//! Running containers (podman / docker), and how to run a target's build
+//! in its container
+
+use crate::collectors::container_socket::parse_containers;
+#[cfg(feature = "containers")]
+use crate::collectors::container_socket::{
+    find_socket, list_containers_via_socket, socket_candidates,
+};
+use crate::collectors::entry_point::collect_entry_point;
+use crate::collectors::targets::target_command;
+use crate::collectors::{
+    collector_unavailable, failure_reason, run_program, BuildTarget, CommandRunner, ContainerInfo,
+};
+use crate::commands::hooks::shell_quote;
+use crate::config::{ContainerBackend, ProjectContext};
+use crate::context::CollectorRegistry;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// `containers.runtime`, podman by default
+pub fn container_runtime(project: &ProjectContext) -> &str {
+    project
+        .config
+        .containers
+        .as_ref()
+        .and_then(|c| c.runtime.as_deref())
+        .unwrap_or("podman")
+}
+
+/// `containers.backend`, auto by default
+pub fn container_backend(project: &ProjectContext) -> ContainerBackend {
+    project
+        .config
+        .containers
+        .as_ref()
+        .and_then(|c| c.backend)
+        .unwrap_or_default()
+}
+
+/// Adds the `containers` collector, or a stand-in without the
+/// `containers` feature
+pub fn register(registry: &mut CollectorRegistry) {
+    #[cfg(feature = "containers")]
+    registry.add_fn("containers", |project| {
+        Ok(crate::context::CollectorOutput::Containers(
+            collect_containers(project),
+        ))
+    });
+    #[cfg(not(feature = "containers"))]
+    registry.add(crate::context::NotBuilt {
+        name: "containers",
+        feature: "containers",
+    });
+}
+
+/// Running containers from the backend `containers.backend` picks. Auto
+/// asks the socket and falls back to the CLI when there is none or it
+/// fails; socket never runs the CLI.
+#[cfg(feature = "containers")]
+pub fn collect_containers(project: &ProjectContext) -> Vec<ContainerInfo> {
+    let runtime = container_runtime(project);
+    let backend = container_backend(project);
+    if backend == ContainerBackend::Cli {
+        return list_containers(&*project.runner, runtime);
+    }
+    let listed = match find_socket(runtime) {
+        Some(socket) => list_containers_via_socket(&socket, runtime),
+        None => Err(format!(
+            "no {} socket at {}",
+            runtime,
+            socket_candidates(runtime)
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(" or ")
+        )),
+    };
+    match (listed, backend) {
+        (Ok(containers), _) => containers,
+        (Err(reason), ContainerBackend::Socket) => {
+            collector_unavailable(reason);
+            Vec::new()
+        }
+        (Err(reason), _) => {
+            tracing::debug!(reason, "container socket not used; running the CLI");
+            list_containers(&*project.runner, runtime)
+        }
+    }
+}
+
+/// A line of `docker ps --format '{{json .}}'`: labels, ports and mounts
+/// come joined into strings
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct CliContainer {
+    #[serde(rename = "Names")]
+    names: String,
+    #[serde(rename = "State")]
+    state: String,
+    #[serde(rename = "Status")]
+    status: String,
+    /// "com.docker.compose.project=app,maintainer=me"
+    #[serde(rename = "Labels")]
+    labels: String,
+    /// "0.0.0.0:8080->80/tcp, :::8080->80/tcp"
+    #[serde(rename = "Ports")]
+    ports: String,
+}
+
+impl CliContainer {
+    fn to_container_info(&self, runtime: &str) -> ContainerInfo {
+        let labels: BTreeMap<String, String> = self
+            .labels
+            .split(',')
+            .filter(|label| !label.is_empty())
+            .map(|label| match label.split_once('=') {
+                Some((key, value)) => (key.to_string(), value.to_string()),
+                None => (label.to_string(), String::new()),
+            })
+            .collect();
+        ContainerInfo {
+            // The first of several comma-separated names, as the socket gives
+            name: self.names.split(',').next().unwrap_or_default().to_string(),
+            status: if self.status.is_empty() {
+                self.state.clone()
+            } else {
+                self.status.clone()
+            },
+            runtime: runtime.to_string(),
+            labels,
+            ports: self
+                .ports
+                .split(", ")
+                .filter(|port| !port.is_empty())
+                .map(str::to_string)
+                .collect(),
+            // `Mounts` holds volume names and host paths, not where they
+            // are mounted
+            mounts: Vec::new(),
+        }
+    }
+}
+
+/// The containers in `docker ps --format '{{json .}}'` output, one JSON
+/// object per line
+pub fn parse_cli_containers(stdout: &[u8], runtime: &str) -> Result<Vec<ContainerInfo>, String> {
+    String::from_utf8_lossy(stdout)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str::<CliContainer>(line)
+                .map(|container| container.to_container_info(runtime))
+                .map_err(|e| format!("unexpected {} ps line: {}", runtime, e))
+        })
+        .filter(|container| !matches!(container, Ok(c) if c.name.is_empty()))
+        .collect()
+}
+
+/// Whether `runtime` is podman, which prints the libpod listing for
+/// `ps --format json` rather than docker's one object per line
+fn is_podman(runtime: &str) -> bool {
+    Path::new(runtime)
+        .file_name()
+        .and_then(|name| name.to_str())
+        == Some("podman")
+}
+
+/// Containers `runtime ps` reports through `runner`
+pub fn list_containers(runner: &dyn CommandRunner, runtime: &str) -> Vec<ContainerInfo> {
+    let podman = is_podman(runtime);
+    let format = if podman { "json" } else { "{{json .}}" };
+    let Ok(output) = run_program(runner, runtime, &["ps", "--format", format]) else {
+        return Vec::new();
+    };
+    if !output.status.success() {
+        collector_unavailable(failure_reason(&format!("{} ps", runtime), &output));
+        return Vec::new();
+    }
+    let parsed = if podman {
+        parse_containers(&output.stdout, runtime)
+    } else {
+        parse_cli_containers(&output.stdout, runtime)
+    };
+    parsed.unwrap_or_else(|reason| {
+        collector_unavailable(reason);
+        Vec::new()
+    })
+}
+
+/// Stands in for `{cmd}` when a target has no known build command
+pub const EXEC_COMMAND_PLACEHOLDER: &str = "<command>";
+
+/// `[containers] exec_template`, else `<runtime> exec -i` into a login
+/// shell. No `-t`: the caller has no terminal.
+pub fn exec_template(project: &ProjectContext) -> String {
+    project
+        .config
+        .containers
+        .as_ref()
+        .and_then(|c| c.exec_template.clone())
+        .unwrap_or_else(|| {
+            format!(
+                "{} exec -i {{container}} bash -lc '{{cmd}}'",
+                container_runtime(project)
+            )
+        })
+}
+
+/// `template` with `{container}` and `{cmd}` filled in, each escaped for
+/// the quoting it sits in: `'\''` for a `'` inside single quotes, a
+/// backslash before `\`, `"`, `$` and `` ` `` inside double quotes, and
+/// quoted as a whole word outside quotes
+pub fn fill_exec_template(template: &str, container: &str, cmd: &str) -> String {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Quote {
+        None,
+        Single,
+        Double,
+    }
+    let mut out = String::new();
+    let mut quote = Quote::None;
+    let mut rest = template;
+    while let Some(c) = rest.chars().next() {
+        let placeholder = [("{container}", container), ("{cmd}", cmd)]
+            .into_iter()
+            .find(|(name, _)| rest.starts_with(name));
+        if let Some((name, value)) = placeholder {
+            match quote {
+                Quote::Single => out.push_str(&value.replace('\'', r"'\''")),
+                Quote::Double => {
+                    for c in value.chars() {
+                        if matches!(c, '\\' | '"' | '$' | '`') {
+                            out.push('\\');
+                        }
+                        out.push(c);
+                    }
+                }
+                Quote::None => out.push_str(&shell_quote(value)),
+            }
+            rest = &rest[name.len()..];
+            continue;
+        }
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+        match (quote, c) {
+            (Quote::None, '\'') => quote = Quote::Single,
+            (Quote::None, '"') => quote = Quote::Double,
+            (Quote::Single, '\'') | (Quote::Double, '"') => quote = Quote::None,
+            // An escaped character never opens or closes a quote
+            (Quote::None | Quote::Double, '\\') => {
+                if let Some(next) = rest.chars().next() {
+                    out.push(next);
+                    rest = &rest[next.len_utf8()..];
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Fill in `exec_command` for each target with a container: its build
+/// command (as `export vscode-tasks` writes it) run through the target's
+/// `EXEC_TEMPLATE` or the configured template
+pub fn attach_exec_commands(project: &ProjectContext, targets: &mut [BuildTarget]) {
+    if targets.iter().all(|t| t.container_name.is_empty()) {
+        return;
+    }
+    let (_, entry_point) = collect_entry_point(project);
+    let template = exec_template(project);
+    for target in targets.iter_mut().filter(|t| !t.container_name.is_empty()) {
+        let cmd = target_command(target, entry_point.as_ref(), "make", &project.root)
+            .unwrap_or_else(|| EXEC_COMMAND_PLACEHOLDER.to_string());
+        let template = if target.exec_template.is_empty() {
+            &template
+        } else {
+            &target.exec_template
+        };
+        target.exec_command = fill_exec_template(template, &target.container_name, &cmd);
+    }
+}
+
+/// The entry of `commands` that starts a container, preferring one that
+/// names `container`: a word with "start" in it, or `up`. The
+/// `# description` is dropped.
+pub fn start_command(commands: &[String], container: &str) -> Option<String> {
+    let starts: Vec<&str> = commands
+        .iter()
+        .map(|command| command.split("  #").next().unwrap_or_default().trim())
+        .filter(|command| {
+            command.split_whitespace().skip(1).any(|word| {
+                let word = word.to_ascii_lowercase();
+                word.contains("start") || word == "up"
+            })
+        })
+        .collect();
+    starts
+        .iter()
+        .find(|command| command.contains(container))
+        .or(starts.first())
+        .map(|command| command.to_string())
+}
@@ -0,0 +1,216 @@
+//! User-configured collectors, `[[custom_collectors]]`
+//!
+//! Each entry runs its command as given, in the project root, with only
+//! a few environment variables passed through. The output becomes a
+//! section of its own: text verbatim (capped at `MAX_CUSTOM_OUTPUT`
+//! bytes) or a JSON object as a key/value table. A command that fails,
+//! times out or prints invalid JSON costs a warning in the `custom`
+//! collector's report, never the request.
+
+use crate::collectors::{
+    collector_warning, resolve_program, run_command_timeout, CollectorStats, COLLECTOR_STATS,
+};
+use crate::config::{CustomCollectorConfig, CustomFormat, ProjectContext};
+use crate::format::Level;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Default for `timeout_ms`, the limit other collector subprocesses use
+pub const DEFAULT_CUSTOM_TIMEOUT_MS: u64 = 3000;
+
+/// Text output beyond this many bytes is cut at the last full line
+pub const MAX_CUSTOM_OUTPUT: usize = 4096;
+
+/// Variables a custom command sees, when set; everything else is cleared
+pub const PASSED_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "LANG",
+    "LC_ALL",
+    "TZ",
+    "TMPDIR",
+    // Windows needs these to start most programs
+    "SYSTEMROOT",
+    "WINDIR",
+    "USERPROFILE",
+    "TEMP",
+];
+
+/// Defaults for `levels`
+pub const DEFAULT_CUSTOM_LEVELS: &[&str] = &["normal", "full"];
+
+/// What a custom command printed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "format", rename_all = "lowercase")]
+pub enum CustomOutput {
+    Text { text: String, truncated: bool },
+    Json { value: serde_json::Value },
+}
+
+/// One custom collector's section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomSection {
+    pub name: String,
+    /// Levels the section is shown at: "minimal", "normal", "full"
+    pub levels: Vec<String>,
+    #[serde(flatten)]
+    pub output: CustomOutput,
+}
+
+impl CustomSection {
+    pub fn shown_at(&self, level: Level) -> bool {
+        self.levels.iter().any(|l| level_name(l) == Some(level))
+    }
+}
+
+fn level_name(name: &str) -> Option<Level> {
+    match name {
+        "minimal" => Some(Level::Minimal),
+        "normal" => Some(Level::Normal),
+        "full" => Some(Level::Full),
+        _ => None,
+    }
+}
+
+/// Level names of `config` (the defaults when unset)
+pub fn custom_levels(config: &CustomCollectorConfig) -> Vec<String> {
+    match &config.levels {
+        Some(levels) => levels.clone(),
+        None => DEFAULT_CUSTOM_LEVELS
+            .iter()
+            .map(|l| l.to_string())
+            .collect(),
+    }
+}
+
+/// Whether any configured custom collector shows at `level`
+pub fn custom_collectors_at(configs: &[CustomCollectorConfig], level: Level) -> bool {
+    configs.iter().any(|c| {
+        custom_levels(c)
+            .iter()
+            .any(|l| level_name(l) == Some(level))
+    })
+}
+
+/// `text` cut to at most `max` bytes, at the last newline when there is
+/// one, and whether anything was cut
+pub fn cap_text(text: &str, max: usize) -> (String, bool) {
+    if text.len() <= max {
+        return (text.to_string(), false);
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let cut = &text[..end];
+    let cut = match cut.rfind('\n') {
+        Some(newline) if newline > 0 => &cut[..newline],
+        _ => cut,
+    };
+    (cut.to_string(), true)
+}
+
+/// Program to spawn: paths relative to the project root, bare names on PATH
+fn program_path(project: &ProjectContext, command: &str) -> PathBuf {
+    if Path::new(command).components().count() > 1 {
+        project.resolve(command)
+    } else {
+        resolve_program(command)
+    }
+}
+
+/// Run one entry; warnings go to this thread's `COLLECTOR_STATS`
+pub fn run_custom_collector(
+    project: &ProjectContext,
+    config: &CustomCollectorConfig,
+) -> Option<CustomSection> {
+    let levels = custom_levels(config);
+    for level in levels.iter().filter(|l| level_name(l).is_none()) {
+        collector_warning(format!(
+            "{}: unknown level '{}' (use minimal, normal or full)",
+            config.name, level
+        ));
+    }
+
+    let mut command = std::process::Command::new(program_path(project, &config.command));
+    command
+        .args(config.args.iter().flatten())
+        .current_dir(&project.root)
+        .env_clear()
+        .envs(
+            PASSED_ENV
+                .iter()
+                .filter_map(|name| Some((name, std::env::var_os(name)?))),
+        )
+        .env("CONTEXTKEEPER_ROOT", &project.root);
+    let timeout = Duration::from_millis(config.timeout_ms.unwrap_or(DEFAULT_CUSTOM_TIMEOUT_MS));
+    // Spawn errors and timeouts were already reported by `record_command`
+    let output = run_command_timeout(&mut command, timeout).ok()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .map(|line| format!(": {}", line.trim()))
+            .unwrap_or_default();
+        collector_warning(format!("{}: {}{}", config.name, output.status, reason));
+        return None;
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let output = match config.format.unwrap_or_default() {
+        CustomFormat::Text => {
+            let (text, truncated) = cap_text(stdout.trim_end(), MAX_CUSTOM_OUTPUT);
+            CustomOutput::Text { text, truncated }
+        }
+        CustomFormat::Json => match serde_json::from_str(&stdout) {
+            Ok(value) => CustomOutput::Json { value },
+            Err(e) => {
+                collector_warning(format!("{}: invalid JSON output: {}", config.name, e));
+                return None;
+            }
+        },
+    };
+    Some(CustomSection {
+        name: config.name.clone(),
+        levels,
+        output,
+    })
+}
+
+/// Every `[[custom_collectors]]` entry, run side by side, in config order
+pub fn collect_custom_sections(project: &ProjectContext) -> Vec<CustomSection> {
+    let Some(configs) = &project.config.custom_collectors else {
+        return Vec::new();
+    };
+    let results: Vec<(Option<CustomSection>, CollectorStats)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = configs
+            .iter()
+            .map(|config| {
+                scope.spawn(move || {
+                    let section = run_custom_collector(project, config);
+                    (section, COLLECTOR_STATS.with(|stats| stats.take()))
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap_or_default())
+            .collect()
+    });
+
+    // Counters were kept per thread; the report is this thread's
+    let mut sections = Vec::new();
+    for (section, stats) in results {
+        COLLECTOR_STATS.with(|own| {
+            let mut own = own.borrow_mut();
+            own.subprocesses += stats.subprocesses;
+            own.warnings.extend(stats.warnings);
+        });
+        sections.extend(section);
+    }
+    sections
+}
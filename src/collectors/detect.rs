@@ -0,0 +1,173 @@
+//! Project type detection from marker files
+
+use crate::collectors::project_ignore::ProjectIgnore;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How sure a detection is: a marker only that tool creates, one that is
+/// merely typical, or a language manifest inside a tree that is also
+/// something else
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Confidence {
+    High,
+    Medium,
+    Low,
+}
+
+impl Confidence {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Confidence::High => "high",
+            Confidence::Medium => "medium",
+            Confidence::Low => "low",
+        }
+    }
+}
+
+/// One project type found in a directory, with the path that gave it away
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Detection {
+    pub project_type: String,
+    /// Marker path, relative to the project root
+    pub evidence: String,
+    pub confidence: Confidence,
+}
+
+/// ROS workspace under `root`: `root` itself when `src/*/package.xml`
+/// exists, else the first immediate subdirectory laid out that way, what
+/// `ignore` matches left out. Returns the workspace and one package
+/// manifest in it.
+pub fn find_ros_workspace(root: &Path, ignore: &ProjectIgnore) -> Option<(PathBuf, PathBuf)> {
+    let manifest_in = |dir: &Path| {
+        fs::read_dir(dir.join("src"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| !ignore.is_ignored(path, true))
+            .map(|path| path.join("package.xml"))
+            .find(|pkg_xml| pkg_xml.is_file())
+    };
+    if let Some(manifest) = manifest_in(root) {
+        return Some((root.to_path_buf(), manifest));
+    }
+    let mut subdirs: Vec<PathBuf> = fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_dir()
+                && path.file_name().is_some_and(|n| n != "src")
+                && !ignore.is_ignored(path, true)
+        })
+        .collect();
+    subdirs.sort();
+    subdirs
+        .into_iter()
+        .find_map(|dir| manifest_in(&dir).map(|manifest| (dir, manifest)))
+}
+
+/// Every project type recognized in `root`, most specific first. The
+/// fixed marker paths are always checked; the directories listed to find
+/// a ROS workspace or Yocto layers leave out what `ignore` matches.
+pub fn detect_project_types(root: &Path, ignore: &ProjectIgnore) -> Vec<Detection> {
+    let mut found: Vec<Detection> = Vec::new();
+    let add = |found: &mut Vec<Detection>, project_type: &str, evidence: &Path, confidence| {
+        if !found.iter().any(|d| d.project_type == project_type) {
+            found.push(Detection {
+                project_type: project_type.to_string(),
+                evidence: evidence
+                    .strip_prefix(root)
+                    .unwrap_or(evidence)
+                    .to_string_lossy()
+                    .to_string(),
+                confidence,
+            });
+        }
+    };
+    let first = |paths: &[&str]| paths.iter().map(|p| root.join(p)).find(|p| p.exists());
+
+    // AOSP
+    if let Some(path) = first(&["build/envsetup.sh", "build/make/envsetup.sh"]) {
+        add(&mut found, "aosp", &path, Confidence::High);
+    }
+
+    // Zephyr (west workspace or manifest repository)
+    if let Some(path) = first(&[".west/config"]) {
+        add(&mut found, "zephyr", &path, Confidence::High);
+    } else if let Some(path) = first(&["west.yml"]) {
+        add(&mut found, "zephyr", &path, Confidence::Medium);
+    }
+
+    // ROS/ROS2: colcon/catkin workspace here or in a subdirectory, or a
+    // single package
+    match find_ros_workspace(root, ignore) {
+        Some((dir, manifest)) => {
+            let confidence = if dir == root {
+                Confidence::High
+            } else {
+                Confidence::Medium
+            };
+            add(&mut found, "ros", &manifest, confidence);
+        }
+        None => {
+            if let Some(path) = first(&["package.xml"]) {
+                add(&mut found, "ros", &path, Confidence::Medium);
+            }
+        }
+    }
+
+    // Yocto
+    if let Some(path) = first(&["poky"]) {
+        add(&mut found, "yocto", &path, Confidence::High);
+    }
+    let mut layers: Vec<PathBuf> = fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("meta-"))
+        .map(|entry| entry.path())
+        .filter(|path| !ignore.is_ignored(path, path.is_dir()))
+        .collect();
+    layers.sort();
+    if let Some(path) = layers.first().cloned().or_else(|| first(&["meta"])) {
+        add(&mut found, "yocto", &path, Confidence::Medium);
+    }
+
+    // Buildroot and kernel trees
+    if root.join("Config.in").is_file()
+        && root.join("package").is_dir()
+        && root.join("output").is_dir()
+    {
+        add(
+            &mut found,
+            "buildroot",
+            &root.join("Config.in"),
+            Confidence::High,
+        );
+    }
+    if root.join("Kbuild").is_file()
+        && root.join("arch").is_dir()
+        && root.join("MAINTAINERS").is_file()
+    {
+        add(&mut found, "kernel", &root.join("Kbuild"), Confidence::High);
+    }
+
+    // Language package managers last: AOSP/ROS/Yocto trees often contain these too
+    let inside_other = !found.is_empty();
+    for (project_type, manifest) in [("cargo", "Cargo.toml"), ("node", "package.json")] {
+        if let Some(path) = first(&[manifest]) {
+            let confidence = if inside_other {
+                Confidence::Low
+            } else {
+                Confidence::High
+            };
+            add(&mut found, project_type, &path, confidence);
+        }
+    }
+
+    found
+}
@@ -0,0 +1,163 @@
+//! Size and place of the uncommitted change in each working file, recorded
+//! with the work state (`[work_state] capture_diffs`, on by default)
+//!
+//! One `git diff --numstat HEAD` per repository gives the line counts.
+//! The hunk headers of `git diff -U0 HEAD` for each file give the functions
+//! touched, as git's funcname patterns find them. A file with more than
+//! `MAX_DIFF_SUMMARY_LINES` changed lines keeps its counts but isn't read
+//! for functions. Untracked files have no diff against HEAD and are left
+//! out.
+
+use crate::collectors::git::git_in;
+use crate::collectors::working_files::holding_repo;
+use crate::config::{Config, ProjectContext};
+use crate::paths::{display_path, project_relative};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Changed lines above which a file's functions aren't collected
+pub const MAX_DIFF_SUMMARY_LINES: usize = 2000;
+
+/// Functions kept per file
+pub const MAX_DIFF_FUNCTIONS: usize = 3;
+
+/// Characters kept of a function line
+const MAX_FUNCTION_CHARS: usize = 80;
+
+/// Limit on each `git diff`
+pub const DIFF_SUMMARY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The uncommitted change to one working file at save time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileDiff {
+    /// As in `WorkState::working_files`
+    pub file: String,
+    pub insertions: usize,
+    pub deletions: usize,
+    /// Hunk header context lines, first seen first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub functions: Vec<String>,
+    /// Why the functions are missing, e.g. "binary"
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+}
+
+impl FileDiff {
+    pub fn churn(&self) -> usize {
+        self.insertions + self.deletions
+    }
+}
+
+/// `[work_state] capture_diffs`
+pub fn capture_diffs_enabled(config: &Config) -> bool {
+    config
+        .work_state
+        .as_ref()
+        .and_then(|w| w.capture_diffs)
+        .unwrap_or(true)
+}
+
+/// Functions named by the hunk headers of a `-U0` diff, without repeats
+fn hunk_functions(diff: &str) -> Vec<String> {
+    let mut functions: Vec<String> = Vec::new();
+    for line in diff.lines().filter(|line| line.starts_with("@@")) {
+        // "@@ -12,3 +12,4 @@ int main(void)"
+        let Some(context) = line[2..].split_once("@@").map(|(_, rest)| rest.trim()) else {
+            continue;
+        };
+        if context.is_empty() || functions.iter().any(|f| f == context) {
+            continue;
+        }
+        let function = match context.char_indices().nth(MAX_FUNCTION_CHARS) {
+            Some((cut, _)) => format!("{}…", &context[..cut]),
+            None => context.to_string(),
+        };
+        functions.push(function);
+        if functions.len() == MAX_DIFF_FUNCTIONS {
+            break;
+        }
+    }
+    functions
+}
+
+/// The change to each of `files` against HEAD, in `files` order, for
+/// those with one
+pub fn collect_diff_summary(project: &ProjectContext, files: &[String]) -> Vec<FileDiff> {
+    if !capture_diffs_enabled(&project.config) {
+        return Vec::new();
+    }
+    let runner = &*project.runner;
+    let git = |repo: &Path, args: &[&str]| {
+        git_in(runner, repo, args, Some(DIFF_SUMMARY_TIMEOUT))
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+
+    let mut by_repo: HashMap<PathBuf, Vec<(String, String)>> = HashMap::new();
+    for file in files {
+        let path = project.resolve(file);
+        if let Some(repo) = holding_repo(&project.root, &path) {
+            let in_repo = display_path(path.strip_prefix(&repo).unwrap_or(&path));
+            by_repo
+                .entry(repo)
+                .or_default()
+                .push((file.clone(), in_repo));
+        }
+    }
+
+    let mut diffs: HashMap<String, FileDiff> = HashMap::new();
+    for (repo, entries) in &by_repo {
+        let mut args = vec![
+            "-c",
+            "core.quotepath=off",
+            "diff",
+            "--numstat",
+            "HEAD",
+            "--",
+        ];
+        args.extend(entries.iter().map(|(_, in_repo)| in_repo.as_str()));
+        // No HEAD yet, or git failed: nothing to compare against
+        let Some(numstat) = git(repo, &args) else {
+            continue;
+        };
+        for line in numstat.lines() {
+            let mut fields = line.splitn(3, '\t');
+            let (Some(insertions), Some(deletions), Some(changed)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let Some((file, in_repo)) = entries.iter().find(|(_, in_repo)| in_repo == changed)
+            else {
+                continue;
+            };
+            let mut diff = FileDiff {
+                file: file.clone(),
+                insertions: insertions.parse().unwrap_or(0),
+                deletions: deletions.parse().unwrap_or(0),
+                functions: Vec::new(),
+                note: String::new(),
+            };
+            if insertions == "-" {
+                diff.note = "binary".to_string();
+            } else if diff.churn() > MAX_DIFF_SUMMARY_LINES {
+                diff.note = format!("over {} changed lines", MAX_DIFF_SUMMARY_LINES);
+            } else if let Some(patch) = git(repo, &["diff", "-U0", "HEAD", "--", in_repo]) {
+                diff.functions = hunk_functions(&patch);
+            }
+            diffs.insert(file.clone(), diff);
+        }
+    }
+
+    files
+        .iter()
+        .filter_map(|file| diffs.remove(file))
+        .map(|mut diff| {
+            diff.file = project_relative(&project.root, Path::new(&diff.file));
+            diff
+        })
+        .collect()
+}
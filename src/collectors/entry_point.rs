@@ -0,0 +1,306 @@
+//! Subcommands and usage text of the project's entry point script
+
+use crate::config::ProjectContext;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Most subcommands listed from the entry-point script
+pub const MAX_SUBCOMMANDS: usize = 15;
+
+/// Most lines of usage text kept
+pub const MAX_USAGE_LINES: usize = 25;
+
+/// User-facing interface of `scripts.entry_point`
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct EntryPointInfo {
+    /// How the script is run from the project root, e.g. `./build.sh`
+    pub invocation: String,
+    /// Text printed by the script's `usage()`/`help()` function
+    pub usage: Option<String>,
+    /// (name, description) from `case "$1" in` branches and `cmd_*` functions
+    pub subcommands: Vec<(String, String)>,
+}
+
+impl EntryPointInfo {
+    /// Example command lines: `./build.sh flash  # Flash the device`
+    pub fn commands(&self) -> Vec<String> {
+        self.subcommands
+            .iter()
+            .map(|(name, description)| {
+                if description.is_empty() {
+                    format!("{} {}", self.invocation, name)
+                } else {
+                    format!("{} {}  # {}", self.invocation, name, description)
+                }
+            })
+            .collect()
+    }
+}
+
+/// Example commands for the entry point, and its structure when the script
+/// has a usage function, a `case "$1"` dispatch or `cmd_*` functions.
+/// Scripts with none of those fall back to lines that call other scripts.
+pub fn collect_entry_point(project: &ProjectContext) -> (Vec<String>, Option<EntryPointInfo>) {
+    let Some(entry) = project
+        .config
+        .scripts
+        .as_ref()
+        .and_then(|s| s.entry_point.as_deref())
+    else {
+        return (Vec::new(), None);
+    };
+    let Ok(content) = fs::read_to_string(project.resolve(entry)) else {
+        return (Vec::new(), None);
+    };
+
+    let invocation = if Path::new(entry).is_absolute() || entry.starts_with('.') {
+        entry.to_string()
+    } else {
+        format!("./{}", entry)
+    };
+    match parse_entry_point(&content, &invocation) {
+        Some(info) => (info.commands(), Some(info)),
+        None => (parse_entry_point_commands(&content), None),
+    }
+}
+
+pub fn parse_entry_point(content: &str, invocation: &str) -> Option<EntryPointInfo> {
+    let lines: Vec<&str> = content.lines().collect();
+    let usage = find_usage_text(&lines).map(|usage| {
+        // Show the script as the user would type it
+        [
+            "$(basename \"$0\")",
+            "$(basename $0)",
+            "${0##*/}",
+            "${0}",
+            "$0",
+        ]
+        .iter()
+        .fold(usage, |text, var| text.replace(var, invocation))
+    });
+
+    let mut subcommands = case_subcommands(&lines);
+    for (name, description) in cmd_functions(&lines) {
+        match subcommands.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, existing)) if existing.is_empty() => *existing = description,
+            Some(_) => {}
+            None => subcommands.push((name, description)),
+        }
+    }
+
+    // Usage lines like "  flash   Flash the device" describe the rest
+    if let Some(usage) = &usage {
+        for (name, description) in subcommands.iter_mut().filter(|(_, d)| d.is_empty()) {
+            let described = usage.lines().find_map(|line| {
+                let rest = line.trim_start().strip_prefix(name.as_str())?;
+                rest.starts_with(|c: char| c.is_whitespace())
+                    .then(|| rest.trim().to_string())
+            });
+            if let Some(text) = described {
+                *description = text;
+            }
+        }
+    }
+
+    if usage.is_none() && subcommands.is_empty() {
+        return None;
+    }
+    subcommands.truncate(MAX_SUBCOMMANDS);
+    Some(EntryPointInfo {
+        invocation: invocation.to_string(),
+        usage,
+        subcommands,
+    })
+}
+
+/// Name of a shell function defined on this line (`name() {`,
+/// `function name {`)
+pub fn shell_function_name(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let name = match line.strip_prefix("function ") {
+        Some(rest) => rest
+            .trim_start()
+            .split(|c: char| c == '(' || c.is_whitespace())
+            .next()?,
+        None => {
+            let (name, rest) = line.split_once('(')?;
+            if !rest.trim_start().starts_with(')') {
+                return None;
+            }
+            name.trim_end()
+        }
+    };
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    valid.then_some(name)
+}
+
+/// `# comment` lines directly above line `index`, joined
+pub fn comment_above(lines: &[&str], index: usize) -> String {
+    let mut comments: Vec<&str> = lines[..index]
+        .iter()
+        .rev()
+        .map_while(|line| line.trim().strip_prefix('#'))
+        .filter(|comment| !comment.starts_with('!'))
+        .map(str::trim)
+        .collect();
+    comments.reverse();
+    comments.join(" ")
+}
+
+/// Body of the first `usage`/`help` function: its heredoc, or else its
+/// `echo` lines
+pub fn find_usage_text(lines: &[&str]) -> Option<String> {
+    let start = lines.iter().position(|line| {
+        shell_function_name(line).is_some_and(|name| {
+            let name = name.to_ascii_lowercase();
+            name.ends_with("usage") || name.ends_with("help")
+        })
+    })?;
+    // One-line `usage() { ...; }` has no body to read
+    if lines[start].trim_end().ends_with('}') {
+        return None;
+    }
+
+    let mut text = Vec::new();
+    let mut echoes = Vec::new();
+    let mut heredoc: Option<(String, bool)> = None;
+    for line in &lines[start + 1..] {
+        if let Some((delimiter, strip_tabs)) = &heredoc {
+            let check = if *strip_tabs {
+                line.trim_start_matches('\t')
+            } else {
+                line
+            };
+            if check.trim_end() == delimiter {
+                break;
+            }
+            text.push(if *strip_tabs { check } else { line });
+            continue;
+        }
+        if line.starts_with('}') {
+            break;
+        }
+        if let Some((_, rest)) = line.split_once("<<") {
+            let strip_tabs = rest.starts_with('-');
+            let delimiter = rest
+                .trim_start_matches('-')
+                .split_whitespace()
+                .next()
+                .unwrap_or("")
+                .trim_matches(|c| c == '\'' || c == '"');
+            if !delimiter.is_empty() {
+                heredoc = Some((delimiter.to_string(), strip_tabs));
+            }
+            continue;
+        }
+        if let Some(arg) = line.trim().strip_prefix("echo") {
+            let arg = arg.trim_start_matches(" -e").trim();
+            echoes.push(arg.trim_matches(|c| c == '"' || c == '\''));
+        }
+    }
+
+    let text = if text.is_empty() { echoes } else { text };
+    let text: Vec<&str> = text.into_iter().take(MAX_USAGE_LINES).collect();
+    let text = text.join("\n").trim_end().to_string();
+    (!text.trim().is_empty()).then_some(text)
+}
+
+/// Whether `line` opens a `case` on the first positional argument
+pub fn is_argument_case(line: &str) -> bool {
+    let Some(word) = line
+        .trim()
+        .strip_prefix("case ")
+        .and_then(|rest| rest.trim().strip_suffix(" in"))
+    else {
+        return false;
+    };
+    let word = word.trim().trim_matches('"');
+    word == "$1" || word == "${1}" || word.starts_with("${1:")
+}
+
+/// Branch names of every `case "$1" in`, skipping options and `*)`
+pub fn case_subcommands(lines: &[&str]) -> Vec<(String, String)> {
+    let mut subcommands: Vec<(String, String)> = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        if !is_argument_case(lines[index]) {
+            index += 1;
+            continue;
+        }
+
+        let mut depth = 1;
+        let mut expecting_pattern = true;
+        index += 1;
+        while index < lines.len() && depth > 0 {
+            let line = lines[index].trim();
+            if line.starts_with("case ") && line.ends_with(" in") {
+                depth += 1;
+            } else if line == "esac" || line.starts_with("esac ") || line.starts_with("esac;") {
+                depth -= 1;
+            } else if depth == 1 {
+                if expecting_pattern && !line.is_empty() && !line.starts_with('#') {
+                    if let Some((patterns, rest)) = line.split_once(')') {
+                        expecting_pattern = false;
+                        let description = match rest.split_once('#') {
+                            Some((_, comment)) => comment.trim().to_string(),
+                            None => comment_above(lines, index),
+                        };
+                        let name = patterns
+                            .trim_start_matches('(')
+                            .split('|')
+                            .next()
+                            .unwrap_or("")
+                            .trim()
+                            .trim_matches(|c| c == '"' || c == '\'');
+                        let is_subcommand = !name.is_empty()
+                            && !name.starts_with('-')
+                            && name
+                                .chars()
+                                .all(|c| c.is_ascii_alphanumeric() || "_-.:".contains(c));
+                        if is_subcommand && !subcommands.iter().any(|(n, _)| n == name) {
+                            subcommands.push((name.to_string(), description));
+                        }
+                    }
+                }
+                if line.contains(";;") || line.ends_with(";&") {
+                    expecting_pattern = true;
+                }
+            }
+            index += 1;
+        }
+    }
+    subcommands
+}
+
+/// `cmd_<name>` functions, with the comment above each as its description
+pub fn cmd_functions(lines: &[&str]) -> Vec<(String, String)> {
+    lines
+        .iter()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let name = shell_function_name(line)?.strip_prefix("cmd_")?;
+            (!name.is_empty()).then(|| (name.to_string(), comment_above(lines, index)))
+        })
+        .collect()
+}
+
+/// Fallback for unstructured scripts: lines that run other scripts
+pub fn parse_entry_point_commands(content: &str) -> Vec<String> {
+    let mut commands = Vec::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.contains("./") && line.contains(".sh ") {
+            commands.push(line.to_string());
+        }
+    }
+
+    commands.sort();
+    commands.dedup();
+    commands.truncate(10);
+    commands
+}
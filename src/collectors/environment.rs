@@ -0,0 +1,70 @@
+//! Nix / direnv dev shells
+
+use crate::config::ProjectContext;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Files that mean build tools come from a dev shell, with the command
+/// that enters it, in the order they're preferred
+pub const DEV_SHELL_MARKERS: &[(&str, &str)] = &[
+    ("flake.nix", "nix develop"),
+    ("shell.nix", "nix-shell"),
+    (".envrc", "direnv allow"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NixState {
+    Active,
+    Inactive,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DevEnvironment {
+    /// flake.nix, shell.nix or .envrc in the project root
+    pub marker: String,
+    /// Command that enters the dev shell (`environment.activate` or default)
+    pub activate: String,
+    /// Whether this process runs inside the dev shell
+    pub nix: NixState,
+}
+
+/// Dev-shell marker in `root` and its default activation command
+pub fn find_dev_shell_marker(root: &Path) -> Option<(&'static str, &'static str)> {
+    DEV_SHELL_MARKERS
+        .iter()
+        .copied()
+        .find(|(marker, _)| root.join(marker).is_file())
+}
+
+pub fn collect_dev_environment(project: &ProjectContext) -> Option<DevEnvironment> {
+    let (marker, default_activate) = find_dev_shell_marker(&project.root)?;
+    let activate = project
+        .config
+        .environment
+        .as_ref()
+        .and_then(|e| e.activate.clone())
+        .unwrap_or_else(|| default_activate.to_string());
+
+    // nix develop / nix-shell set IN_NIX_SHELL; direnv sets DIRENV_DIR to
+    // "-<dir>" for the directory whose .envrc it loaded
+    let in_nix_shell = std::env::var_os("IN_NIX_SHELL").is_some_and(|v| !v.is_empty());
+    let direnv_loaded = std::env::var("DIRENV_DIR").is_ok_and(|dir| {
+        let dir = Path::new(dir.trim_start_matches('-'));
+        let root = project
+            .root
+            .canonicalize()
+            .unwrap_or_else(|_| project.root.clone());
+        root.starts_with(dir)
+    });
+
+    Some(DevEnvironment {
+        marker: marker.to_string(),
+        activate,
+        nix: if in_nix_shell || direnv_loaded {
+            NixState::Active
+        } else {
+            NixState::Inactive
+        },
+    })
+}
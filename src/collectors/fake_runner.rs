@@ -0,0 +1,177 @@
+//! A `CommandRunner` that replays canned output instead of spawning
+//!
+//! For tests and embedders: register what `git status`, `podman ps` or
+//! `adb devices -l` print, hand the runner to `ProjectContext::runner`,
+//! and the collectors parse it as if the tools had run. Every call is
+//! recorded. A command nothing was registered for fails as not found,
+//! the way a missing binary does, so unexpected calls show up as
+//! "not installed" in the collector reports.
+//!
+//! A registered command matches when its words appear, in order, in the
+//! program and arguments actually run: `["git", "status"]` matches
+//! `git -C /src status --porcelain=v2`. The first match wins, so register
+//! the specific ones first.
+
+use crate::collectors::{record_run, CommandRunner};
+use std::ffi::OsStr;
+use std::io;
+use std::process::Output;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// What a registered command does when run
+#[derive(Debug, Clone)]
+pub enum FakeResponse {
+    /// Exits with `code`, printing `stdout` and `stderr`
+    Exit {
+        code: i32,
+        stdout: Vec<u8>,
+        stderr: Vec<u8>,
+    },
+    /// Fails to start: the program isn't installed
+    NotFound,
+    /// Takes `delay` before doing the rest. Past the call's timeout it
+    /// fails as timed out once the timeout expires, like a killed child.
+    Slow(Duration, Box<FakeResponse>),
+}
+
+/// Replays `FakeResponse`s; see the module docs
+#[derive(Debug, Default)]
+pub struct FakeRunner {
+    responses: Vec<(Vec<String>, FakeResponse)>,
+    calls: Mutex<Vec<FakeCall>>,
+}
+
+/// A command the collectors ran through a `FakeRunner`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FakeCall {
+    /// Program and arguments, lossily as text
+    pub command: Vec<String>,
+    pub timeout: Option<Duration>,
+}
+
+impl FakeCall {
+    /// The words joined with spaces, e.g. "adb devices -l"
+    pub fn line(&self) -> String {
+        self.command.join(" ")
+    }
+}
+
+#[cfg(unix)]
+fn exit_status(code: i32) -> std::process::ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+    std::process::ExitStatus::from_raw(code << 8)
+}
+
+#[cfg(windows)]
+fn exit_status(code: i32) -> std::process::ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+    std::process::ExitStatus::from_raw(code as u32)
+}
+
+/// Whether `pattern` appears in `command` in order, gaps allowed
+fn is_subsequence(pattern: &[String], command: &[String]) -> bool {
+    let mut words = command.iter();
+    pattern.iter().all(|want| words.any(|word| word == want))
+}
+
+impl FakeRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `command` prints `stdout` and exits 0
+    pub fn on(self, command: &[&str], stdout: &str) -> Self {
+        self.respond(
+            command,
+            FakeResponse::Exit {
+                code: 0,
+                stdout: stdout.as_bytes().to_vec(),
+                stderr: Vec::new(),
+            },
+        )
+    }
+
+    /// `command` prints `stderr` and exits with `code`
+    pub fn on_failure(self, command: &[&str], code: i32, stderr: &str) -> Self {
+        self.respond(
+            command,
+            FakeResponse::Exit {
+                code,
+                stdout: Vec::new(),
+                stderr: stderr.as_bytes().to_vec(),
+            },
+        )
+    }
+
+    /// `command` answers as `FakeResponse` says
+    pub fn respond(mut self, command: &[&str], response: FakeResponse) -> Self {
+        let words = command.iter().map(|word| word.to_string()).collect();
+        self.responses.push((words, response));
+        self
+    }
+
+    /// Every command run so far, in order
+    pub fn calls(&self) -> Vec<FakeCall> {
+        self.calls.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn answer(response: &FakeResponse, timeout: Option<Duration>) -> io::Result<Output> {
+        match response {
+            FakeResponse::Exit {
+                code,
+                stdout,
+                stderr,
+            } => Ok(Output {
+                status: exit_status(*code),
+                stdout: stdout.clone(),
+                stderr: stderr.clone(),
+            }),
+            FakeResponse::NotFound => Err(io::Error::from(io::ErrorKind::NotFound)),
+            FakeResponse::Slow(delay, then) => match timeout {
+                Some(timeout) if timeout < *delay => {
+                    std::thread::sleep(timeout);
+                    Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("timed out after {:?}", timeout),
+                    ))
+                }
+                _ => {
+                    std::thread::sleep(*delay);
+                    Self::answer(then, timeout)
+                }
+            },
+        }
+    }
+}
+
+impl CommandRunner for FakeRunner {
+    fn run(&self, program: &str, args: &[&OsStr], timeout: Option<Duration>) -> io::Result<Output> {
+        let mut command = vec![program.to_string()];
+        command.extend(args.iter().map(|arg| arg.to_string_lossy().into_owned()));
+        self.calls
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(FakeCall {
+                command: command.clone(),
+                timeout,
+            });
+        let result = match self
+            .responses
+            .iter()
+            .find(|(pattern, _)| is_subsequence(pattern, &command))
+        {
+            Some((_, response)) => Self::answer(response, timeout),
+            None => Err(io::Error::from(io::ErrorKind::NotFound)),
+        };
+        record_run(OsStr::new(program), &result);
+        result
+    }
+
+    fn has_program(&self, program: &str) -> bool {
+        self.responses.iter().any(|(pattern, response)| {
+            pattern.first().is_some_and(|p| p == program)
+                && !matches!(response, FakeResponse::NotFound)
+        })
+    }
+}
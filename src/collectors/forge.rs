@@ -0,0 +1,275 @@
+//! GitHub pull requests and GitLab merge requests, through `gh` / `glab`
+//!
+//! Only asked for repos with local work whose remote is on github.com or
+//! a GitLab host, and only when the CLI is installed. A CLI that fails
+//! once (not logged in, rate limited, timed out) is not asked again for
+//! the rest of the collection, so it costs one warning, not one per repo.
+
+use crate::collectors::git::git_in;
+use crate::collectors::{collector_warning, CommandRunner, GitInfo, PullRequest};
+use crate::config::ProjectContext;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::time::Duration;
+
+/// Default for `pull_requests.timeout_ms`
+pub const DEFAULT_PR_TIMEOUT_MS: u64 = 3000;
+
+/// Open PRs kept per repo, the current branch's first
+pub const MAX_PULL_REQUESTS: usize = 3;
+
+/// Where a repository's remote is hosted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Forge {
+    GitHub,
+    GitLab,
+}
+
+impl Forge {
+    pub fn cli(&self) -> &'static str {
+        match self {
+            Forge::GitHub => "gh",
+            Forge::GitLab => "glab",
+        }
+    }
+}
+
+/// Host and path of a remote URL, e.g. `git@github.com:me/tool.git` ->
+/// (`github.com`, `me/tool`)
+pub fn remote_host_and_path(url: &str) -> Option<(String, String)> {
+    let (authority, path) = match url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/')?,
+        // scp-like `user@host:path`
+        None => url.split_once(':')?,
+    };
+    let host = authority.rsplit('@').next()?;
+    let host = host.split(':').next()?;
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    if host.is_empty() || path.is_empty() {
+        return None;
+    }
+    Some((host.to_ascii_lowercase(), path.to_string()))
+}
+
+/// The forge and `--repo` argument for a remote URL: `owner/repo` on
+/// github.com, a full URL for self-hosted GitLab
+pub fn forge_repo(url: &str) -> Option<(Forge, String)> {
+    let (host, path) = remote_host_and_path(url)?;
+    if host == "github.com" {
+        Some((Forge::GitHub, path))
+    } else if host == "gitlab.com" {
+        Some((Forge::GitLab, path))
+    } else if host.contains("gitlab") {
+        Some((Forge::GitLab, format!("https://{}/{}", host, path)))
+    } else {
+        None
+    }
+}
+
+/// `origin`'s URL, else the first remote's
+fn remote_url(runner: &dyn CommandRunner, dir: &Path) -> Option<String> {
+    let output = git_in(
+        runner,
+        dir,
+        &["config", "--get-regexp", r"^remote\..*\.url$"],
+        Some(Duration::from_secs(1)),
+    )
+    .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let remotes = String::from_utf8_lossy(&output.stdout);
+    let urls: Vec<(&str, &str)> = remotes
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .collect();
+    urls.iter()
+        .find(|(key, _)| *key == "remote.origin.url")
+        .or(urls.first())
+        .map(|(_, url)| url.to_string())
+}
+
+/// CI summary from `statusCheckRollup`: failing if anything failed, else
+/// pending if anything hasn't finished, else passing
+fn rollup_checks(rollup: &serde_json::Value) -> Option<String> {
+    let checks = rollup.as_array().filter(|checks| !checks.is_empty())?;
+    let mut pending = false;
+    for check in checks {
+        // CheckRun: status + conclusion; StatusContext: state
+        let outcome = check["conclusion"]
+            .as_str()
+            .filter(|c| !c.is_empty())
+            .or(check["state"].as_str())
+            .unwrap_or("");
+        match outcome {
+            "FAILURE" | "ERROR" | "CANCELLED" | "TIMED_OUT" | "ACTION_REQUIRED"
+            | "STARTUP_FAILURE" => return Some("failing".to_string()),
+            "SUCCESS" | "NEUTRAL" | "SKIPPED" => {}
+            _ => pending = true,
+        }
+    }
+    Some(if pending { "pending" } else { "passing" }.to_string())
+}
+
+/// Parse `gh pr list --json number,title,headRefName,isDraft,reviewDecision,statusCheckRollup`
+pub fn parse_gh_prs(json: &str, current_branch: &str) -> Option<Vec<PullRequest>> {
+    let prs: Vec<serde_json::Value> = serde_json::from_str(json).ok()?;
+    Some(
+        prs.iter()
+            .filter_map(|pr| {
+                let branch = pr["headRefName"].as_str().unwrap_or_default().to_string();
+                let review = match pr["reviewDecision"].as_str() {
+                    Some("APPROVED") => Some("approved"),
+                    Some("CHANGES_REQUESTED") => Some("changes requested"),
+                    Some("REVIEW_REQUIRED") => Some("review required"),
+                    _ => None,
+                };
+                Some(PullRequest {
+                    number: pr["number"].as_u64()?,
+                    title: pr["title"].as_str().unwrap_or_default().to_string(),
+                    current_branch: branch == current_branch,
+                    branch,
+                    draft: pr["isDraft"].as_bool().unwrap_or(false),
+                    review: review.map(str::to_string),
+                    checks: rollup_checks(&pr["statusCheckRollup"]),
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Parse `glab mr list --output json`, GitLab's merge request objects
+pub fn parse_glab_mrs(json: &str, current_branch: &str) -> Option<Vec<PullRequest>> {
+    let mrs: Vec<serde_json::Value> = serde_json::from_str(json).ok()?;
+    Some(
+        mrs.iter()
+            .filter_map(|mr| {
+                let branch = mr["source_branch"].as_str().unwrap_or_default().to_string();
+                let status = mr["detailed_merge_status"].as_str().unwrap_or("");
+                let checks = match mr["head_pipeline"]["status"].as_str() {
+                    Some("success") => Some("passing"),
+                    Some("failed" | "canceled") => Some("failing"),
+                    Some(_) => Some("pending"),
+                    None if status == "ci_still_running" => Some("pending"),
+                    None => None,
+                };
+                Some(PullRequest {
+                    number: mr["iid"].as_u64()?,
+                    title: mr["title"].as_str().unwrap_or_default().to_string(),
+                    current_branch: branch == current_branch,
+                    branch,
+                    draft: mr["draft"].as_bool().unwrap_or(false),
+                    review: (status == "not_approved").then(|| "review required".to_string()),
+                    checks: checks.map(str::to_string),
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Why a CLI call gave no PRs
+enum LookupError {
+    /// It couldn't run or timed out; `record_command` already noted that
+    Spawn(std::io::Error),
+    /// It ran and refused (not logged in, rate limited) or said nothing useful
+    Cli(String),
+}
+
+/// Ask `forge`'s CLI for the user's open PRs in `repo`
+fn query_pull_requests(
+    runner: &dyn CommandRunner,
+    forge: Forge,
+    repo: &str,
+    current_branch: &str,
+    timeout: Duration,
+) -> Result<Vec<PullRequest>, LookupError> {
+    let args: &[&str] = match forge {
+        Forge::GitHub => &[
+            "pr",
+            "list",
+            "--repo",
+            repo,
+            "--author",
+            "@me",
+            "--state",
+            "open",
+            "--limit",
+            "20",
+            "--json",
+            "number,title,headRefName,isDraft,reviewDecision,statusCheckRollup",
+        ],
+        Forge::GitLab => &[
+            "mr",
+            "list",
+            "--repo",
+            repo,
+            "--author",
+            "@me",
+            "--output",
+            "json",
+            "--per-page",
+            "20",
+        ],
+    };
+    let args: Vec<&OsStr> = args.iter().map(OsStr::new).collect();
+    let output = runner
+        .run(forge.cli(), &args, Some(timeout))
+        .map_err(LookupError::Spawn)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let first_line = stderr.lines().next().unwrap_or("failed").trim();
+        return Err(LookupError::Cli(first_line.to_string()));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let prs = match forge {
+        Forge::GitHub => parse_gh_prs(&stdout, current_branch),
+        Forge::GitLab => parse_glab_mrs(&stdout, current_branch),
+    };
+    prs.ok_or_else(|| LookupError::Cli("unexpected output".to_string()))
+}
+
+/// Attach open PRs to the repos with local work (dirty or ahead of their
+/// upstream) or `show_clean`, with `[pull_requests] enabled = true`
+pub fn attach_pull_requests(project: &ProjectContext, repos: &mut [GitInfo]) {
+    let Some(config) = &project.config.pull_requests else {
+        return;
+    };
+    if !config.enabled.unwrap_or(false) {
+        return;
+    }
+    let runner = &*project.runner;
+    let timeout = Duration::from_millis(config.timeout_ms.unwrap_or(DEFAULT_PR_TIMEOUT_MS));
+    let mut given_up: HashSet<Forge> = HashSet::new();
+
+    for repo in repos.iter_mut() {
+        if !repo.is_dirty && repo.ahead == 0 && !repo.show_clean {
+            continue;
+        }
+        let dir = project.root.join(&repo.repo_path);
+        let Some((forge, slug)) = remote_url(runner, &dir).and_then(|url| forge_repo(&url)) else {
+            continue;
+        };
+        if given_up.contains(&forge) || !runner.has_program(forge.cli()) {
+            continue;
+        }
+        match query_pull_requests(runner, forge, &slug, &repo.branch, timeout) {
+            Ok(mut prs) => {
+                // Stable: the current branch's PR first, then gh/glab's order
+                prs.sort_by_key(|pr| !pr.current_branch);
+                prs.truncate(MAX_PULL_REQUESTS);
+                repo.pull_requests = prs;
+            }
+            Err(LookupError::Spawn(e)) => {
+                tracing::warn!(cli = forge.cli(), error = %e, "pull request lookup failed");
+                given_up.insert(forge);
+            }
+            Err(LookupError::Cli(e)) => {
+                tracing::warn!(cli = forge.cli(), error = %e, "pull request lookup failed");
+                collector_warning(format!("{}: {}", forge.cli(), e));
+                given_up.insert(forge);
+            }
+        }
+    }
+}
@@ -0,0 +1,321 @@
+//! Gerrit: the user's open changes for repositories with local work
+//!
+//! Queried over HTTPS with `curl` through the project's `CommandRunner`.
+//! Any failure (offline, timeout, bad credentials) leaves the plain git
+//! view. A token from the config goes to curl in a private netrc file,
+//! never on its command line.
+
+use crate::collectors::git::git_in;
+use crate::collectors::{CommandRunner, GerritChange, GitInfo};
+use crate::config::{GerritConfig, ProjectContext};
+use crate::context::{run_with_deadline, Context};
+use crate::state::contextkeeper_dir;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Default for `gerrit.timeout_ms`
+pub const DEFAULT_GERRIT_TIMEOUT_MS: u64 = 3000;
+
+/// Most changes one query returns
+pub const MAX_GERRIT_CHANGES: usize = 50;
+
+/// `https://host[/prefix]` without a trailing slash
+pub fn gerrit_base_url(host: &str) -> String {
+    let host = host.trim().trim_end_matches('/');
+    if host.contains("://") {
+        host.to_string()
+    } else {
+        format!("https://{}", host)
+    }
+}
+
+/// Host name netrc entries are matched against
+fn url_host(base_url: &str) -> &str {
+    let rest = base_url
+        .split_once("://")
+        .map_or(base_url, |(_, rest)| rest);
+    let authority = rest.split('/').next().unwrap_or(rest);
+    authority.split(':').next().unwrap_or(authority)
+}
+
+/// Gerrit project named by a remote URL: the path after the host, e.g.
+/// `ssh://me@review:29418/platform/build.git` -> `platform/build`
+pub fn project_from_remote_url(url: &str) -> Option<String> {
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/')?.1,
+        // scp-like `host:path`
+        None => url.split_once(':')?.1,
+    };
+    let path = path.trim_matches('/');
+    let path = path.strip_prefix("a/").unwrap_or(path);
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    (!path.is_empty()).then(|| path.to_string())
+}
+
+/// The Gerrit project for a repository: `gerrit.projects`, then the
+/// `projectname` repo writes into each remote, then the remote URL
+pub fn gerrit_project(
+    runner: &dyn CommandRunner,
+    config: &GerritConfig,
+    repo_path: &str,
+    dir: &Path,
+) -> Option<String> {
+    if let Some(project) = config.projects.as_ref().and_then(|p| p.get(repo_path)) {
+        return Some(project.clone());
+    }
+    let output = git_in(
+        runner,
+        dir,
+        &["config", "--get-regexp", r"^remote\..*\.(projectname|url)$"],
+        Some(Duration::from_secs(1)),
+    )
+    .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let remotes = String::from_utf8_lossy(&output.stdout);
+    let mut url_project = None;
+    for line in remotes.lines() {
+        let Some((key, value)) = line.split_once(' ') else {
+            continue;
+        };
+        if key.ends_with(".projectname") {
+            return Some(value.to_string());
+        }
+        if url_project.is_none() {
+            url_project = project_from_remote_url(value);
+        }
+    }
+    url_project
+}
+
+/// Escape a Gerrit query for the `q=` parameter
+fn query_escape(query: &str) -> String {
+    let mut escaped = String::with_capacity(query.len());
+    for byte in query.bytes() {
+        match byte {
+            b' ' => escaped.push('+'),
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' | b':' => {
+                escaped.push(byte as char)
+            }
+            _ => escaped.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    escaped
+}
+
+/// Strongest vote on a label in a DETAILED_LABELS change: the lowest if
+/// any is negative, else the highest
+fn label_vote(change: &serde_json::Value, label: &str) -> Option<i32> {
+    let votes: Vec<i32> = change["labels"][label]["all"]
+        .as_array()?
+        .iter()
+        .filter_map(|vote| vote["value"].as_i64())
+        .map(|value| value as i32)
+        .filter(|&value| value != 0)
+        .collect();
+    match votes.iter().min() {
+        Some(&min) if min < 0 => Some(min),
+        _ => votes.iter().max().copied(),
+    }
+}
+
+/// Parse a `/changes/` response, which starts with Gerrit's `)]}'` guard
+pub fn parse_changes(body: &str) -> Option<Vec<GerritChange>> {
+    let json = body.strip_prefix(")]}'").unwrap_or(body);
+    let changes: Vec<serde_json::Value> = serde_json::from_str(json.trim_start()).ok()?;
+    Some(
+        changes
+            .iter()
+            .filter_map(|change| {
+                Some(GerritChange {
+                    number: change["_number"].as_u64()?,
+                    subject: change["subject"].as_str().unwrap_or_default().to_string(),
+                    project: change["project"].as_str()?.to_string(),
+                    branch: change["branch"].as_str().unwrap_or_default().to_string(),
+                    code_review: label_vote(change, "Code-Review"),
+                    verified: label_vote(change, "Verified"),
+                })
+            })
+            .collect(),
+    )
+}
+
+/// A netrc file only we can read, removed when dropped
+struct NetrcFile(PathBuf);
+
+impl NetrcFile {
+    fn create(host: &str, username: &str, token: &str) -> io::Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = contextkeeper_dir();
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!(
+            "gerrit-{}-{}.netrc",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = NetrcFile(path);
+        let mut out = options.open(&file.0)?;
+        writeln!(
+            out,
+            "machine {} login {} password {}",
+            host, username, token
+        )?;
+        Ok(file)
+    }
+}
+
+impl Drop for NetrcFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// The user's open changes in `projects`, or `None` when Gerrit can't be
+/// reached or refuses the query
+pub fn query_open_changes(
+    runner: &dyn CommandRunner,
+    config: &GerritConfig,
+    projects: &[String],
+) -> Option<Vec<GerritChange>> {
+    let base_url = gerrit_base_url(&config.host);
+    let timeout = Duration::from_millis(config.timeout_ms.unwrap_or(DEFAULT_GERRIT_TIMEOUT_MS));
+    let filter: Vec<String> = projects
+        .iter()
+        .map(|project| format!("project:{}", project))
+        .collect();
+    let query = format!("owner:self status:open ({})", filter.join(" OR "));
+    // `/a/` is the authenticated API; `owner:self` needs it
+    let url = format!(
+        "{}/a/changes/?q={}&o=DETAILED_LABELS&n={}",
+        base_url,
+        query_escape(&query),
+        MAX_GERRIT_CHANGES
+    );
+
+    let netrc = match (&config.username, &config.token) {
+        (Some(username), Some(token)) => {
+            match NetrcFile::create(url_host(&base_url), username, token) {
+                Ok(netrc) => Some(netrc),
+                Err(e) => {
+                    tracing::warn!(error = %e, "cannot write Gerrit credentials");
+                    return None;
+                }
+            }
+        }
+        _ => None,
+    };
+    let max_time = format!("{:.1}", timeout.as_secs_f64());
+    let mut args = vec![
+        "--silent",
+        "--show-error",
+        "--fail",
+        "--max-time",
+        &max_time,
+    ];
+    let netrc_path = netrc.as_ref().map(|n| n.0.to_string_lossy().into_owned());
+    match &netrc_path {
+        Some(path) => args.extend(["--netrc-file", path.as_str()]),
+        None => args.push("--netrc-optional"),
+    }
+    args.push(&url);
+
+    let args: Vec<&std::ffi::OsStr> = args.iter().map(std::ffi::OsStr::new).collect();
+    // curl's own --max-time normally ends it first
+    let output = runner.run("curl", &args, Some(timeout + Duration::from_secs(1)));
+    drop(netrc);
+    match output {
+        Ok(output) if output.status.success() => {
+            let changes = parse_changes(&String::from_utf8_lossy(&output.stdout));
+            if changes.is_none() {
+                tracing::warn!(host = %base_url, "unexpected Gerrit response");
+            }
+            changes
+        }
+        Ok(output) => {
+            tracing::warn!(
+                host = %base_url,
+                error = %String::from_utf8_lossy(&output.stderr).trim(),
+                "Gerrit query failed"
+            );
+            None
+        }
+        Err(e) => {
+            tracing::warn!(host = %base_url, error = %e, "Gerrit query failed");
+            None
+        }
+    }
+}
+
+/// Attach open changes to the repos with local work (dirty or ahead of
+/// their upstream) or `show_clean`. Repos keep no changes when the lookup fails.
+pub fn attach_gerrit_changes(project: &ProjectContext, repos: &mut [GitInfo]) {
+    let Some(config) = &project.config.gerrit else {
+        return;
+    };
+    let runner = &*project.runner;
+
+    // Gerrit project -> indexes of the repos that push to it
+    let mut by_project: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (index, repo) in repos.iter().enumerate() {
+        if !repo.is_dirty && repo.ahead == 0 && !repo.show_clean {
+            continue;
+        }
+        let dir = project.root.join(&repo.repo_path);
+        if let Some(name) = gerrit_project(runner, config, &repo.repo_path, &dir) {
+            by_project.entry(name).or_default().push(index);
+        }
+    }
+    if by_project.is_empty() {
+        return;
+    }
+
+    let projects: Vec<String> = by_project.keys().cloned().collect();
+    let Some(changes) = query_open_changes(runner, config, &projects) else {
+        return;
+    };
+    for change in changes {
+        for &index in by_project.get(&change.project).into_iter().flatten() {
+            repos[index].changes.push(change.clone());
+        }
+    }
+}
+
+/// `attach_gerrit_changes` for `ctx.git_repos` on a detached thread; the
+/// repos stay as collected if it doesn't finish before the deadline
+pub async fn annotate_gerrit(
+    project: &Arc<ProjectContext>,
+    ctx: &mut Context,
+    deadline: Duration,
+    ct: &CancellationToken,
+) {
+    if project.config.gerrit.is_none() || ctx.git_repos.is_empty() {
+        return;
+    }
+    let project = Arc::clone(project);
+    let mut repos = ctx.git_repos.clone();
+    match run_with_deadline(deadline, ct, move || {
+        attach_gerrit_changes(&project, &mut repos);
+        repos
+    })
+    .await
+    {
+        Ok(repos) => ctx.git_repos = repos,
+        Err(status) => {
+            tracing::warn!(status = status.as_str(), "Gerrit lookup abandoned");
+        }
+    }
+}
@@ -0,0 +1,876 @@
+//! Git repositories: discovery and status
+
+use crate::collectors::project_ignore::{ProjectIgnore, PROJECT_IGNORE_FILE};
+use crate::collectors::{
+    collector_warning, not_built, CommandRunner, GitInfo, DEFAULT_COMMAND_TIMEOUT,
+};
+use crate::config::{Config, GitBackend, GitRepoConfig, ProjectContext, UntrackedFiles};
+use crate::paths::{display_path, project_relative};
+use crate::state::{load_work_state_with_hooks, project_state_dir};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// `git -C <repo> <args>`, passing the path through without a UTF-8 round
+/// trip. Without a `timeout` it gets the `DEFAULT_COMMAND_TIMEOUT`.
+pub fn git_in(
+    runner: &dyn CommandRunner,
+    repo: &Path,
+    args: &[&str],
+    timeout: Option<Duration>,
+) -> io::Result<std::process::Output> {
+    let mut full_args = vec![OsStr::new("-C"), repo.as_os_str()];
+    full_args.extend(args.iter().map(OsStr::new));
+    let timeout = timeout.unwrap_or(DEFAULT_COMMAND_TIMEOUT);
+    runner.run("git", &full_args, Some(timeout))
+}
+
+/// Per-repository `git status` settings from `[git]`
+#[derive(Debug, Clone, Copy)]
+pub struct StatusOptions {
+    pub untracked: UntrackedFiles,
+    pub timeout: Option<Duration>,
+    pub backend: GitBackend,
+}
+
+/// Default for `git.status_timeout_ms`
+pub const DEFAULT_STATUS_TIMEOUT_MS: u64 = 3000;
+
+impl Default for StatusOptions {
+    fn default() -> Self {
+        StatusOptions {
+            untracked: UntrackedFiles::default(),
+            timeout: Some(Duration::from_millis(DEFAULT_STATUS_TIMEOUT_MS)),
+            backend: GitBackend::default(),
+        }
+    }
+}
+
+impl StatusOptions {
+    pub fn from_config(config: &Config) -> Self {
+        let git = config.git.as_ref();
+        let timeout_ms = git
+            .and_then(|g| g.status_timeout_ms)
+            .unwrap_or(DEFAULT_STATUS_TIMEOUT_MS);
+        StatusOptions {
+            untracked: git.and_then(|g| g.untracked).unwrap_or_default(),
+            timeout: Some(Duration::from_millis(timeout_ms)),
+            backend: git.and_then(|g| g.backend).unwrap_or_default(),
+        }
+    }
+}
+
+impl StatusOptions {
+    /// These options with `repo`'s `[[git.repos]]` settings on top
+    pub fn for_repo(&self, repo: Option<&GitRepoConfig>) -> Self {
+        StatusOptions {
+            untracked: repo.and_then(|r| r.untracked).unwrap_or(self.untracked),
+            timeout: self.timeout,
+            backend: self.backend,
+        }
+    }
+}
+
+/// `[[git.repos]]` path without `./` in front or `/` behind; `.` for the
+/// root
+fn normalize_repo_pattern(path: &str) -> &str {
+    let path = path.trim_start_matches("./").trim_end_matches('/');
+    if path.is_empty() {
+        "."
+    } else {
+        path
+    }
+}
+
+/// Characters of `pattern` that aren't wildcards, a `[...]` class counting
+/// as one
+fn literal_chars(pattern: &str) -> usize {
+    let mut count = 0;
+    let mut in_class = false;
+    for c in pattern.chars() {
+        match c {
+            ']' if in_class => {
+                in_class = false;
+                count += 1;
+            }
+            _ if in_class => {}
+            '[' => in_class = true,
+            '*' | '?' => {}
+            _ => count += 1,
+        }
+    }
+    count
+}
+
+/// The `[[git.repos]]` entry for the repo at `path` (relative to the
+/// project root, as in `GitInfo::repo_path`). An exact path beats any glob;
+/// between globs the one with more literal characters wins, and on a tie
+/// the entry listed last. A pattern that isn't a valid glob only matches
+/// exactly.
+pub fn repo_override<'a>(config: &'a Config, path: &str) -> Option<&'a GitRepoConfig> {
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+    config
+        .git
+        .as_ref()?
+        .repos
+        .as_ref()?
+        .iter()
+        .enumerate()
+        .filter_map(|(index, repo)| {
+            let pattern = normalize_repo_pattern(&repo.path);
+            if pattern == path {
+                return Some(((true, 0, index), repo));
+            }
+            glob::Pattern::new(pattern)
+                .is_ok_and(|glob| glob.matches_with(path, options))
+                .then(|| ((false, literal_chars(pattern), index), repo))
+        })
+        .max_by_key(|(rank, _)| *rank)
+        .map(|(_, repo)| repo)
+}
+
+/// Whether `[[git.repos]]` leaves `repo` out
+fn skips(repo: Option<&GitRepoConfig>) -> bool {
+    repo.and_then(|r| r.skip).unwrap_or(false)
+}
+
+/// Apply `repo`'s alias, `show_clean` and `base_branch` to `info`. Ahead
+/// and behind stay upstream's when the base branch can't be compared.
+fn apply_repo_override(
+    runner: &dyn CommandRunner,
+    dir: &Path,
+    info: &mut GitInfo,
+    repo: Option<&GitRepoConfig>,
+    timeout: Option<Duration>,
+) {
+    let Some(repo) = repo else {
+        return;
+    };
+    info.alias = repo.alias.clone().filter(|alias| !alias.is_empty());
+    info.show_clean = repo.show_clean.unwrap_or(false);
+    let Some(base) = repo.base_branch.as_deref().filter(|b| !b.is_empty()) else {
+        return;
+    };
+    let range = format!("{}...HEAD", base);
+    let counted = git_in(
+        runner,
+        dir,
+        &["rev-list", "--left-right", "--count", &range],
+        timeout,
+    )
+    .ok()
+    .filter(|output| output.status.success())
+    .and_then(|output| {
+        // "<only in base>\t<only in HEAD>"
+        let counts = String::from_utf8_lossy(&output.stdout).to_string();
+        let (behind, ahead) = counts.trim().split_once('\t')?;
+        Some((ahead.parse().ok()?, behind.parse().ok()?))
+    });
+    match counted {
+        Some((ahead, behind)) => {
+            info.ahead = ahead;
+            info.behind = behind;
+            info.compared_to = Some(base.to_string());
+        }
+        None => tracing::debug!(repo = %dir.display(), base, "base branch not comparable"),
+    }
+}
+
+/// What `git status --porcelain=v2 --branch` reports
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PorcelainStatus {
+    /// `branch.head`; `None` while HEAD is detached
+    pub head: Option<String>,
+    /// `branch.oid`; `None` before the first commit
+    pub oid: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: usize,
+    pub behind: usize,
+    /// Ordinary changes and renames/copies, staged or not
+    pub changed: usize,
+    pub conflicted: usize,
+    pub untracked: usize,
+}
+
+/// Parse `git status --porcelain=v2 --branch` output (newline-terminated,
+/// not `-z`). Lines this doesn't know, like `# stash`, are skipped.
+pub fn parse_porcelain_v2(output: &str) -> PorcelainStatus {
+    let mut status = PorcelainStatus::default();
+    for line in output.lines() {
+        if let Some(header) = line.strip_prefix("# ") {
+            let (key, value) = header.split_once(' ').unwrap_or((header, ""));
+            match key {
+                "branch.oid" if value != "(initial)" => status.oid = Some(value.to_string()),
+                "branch.head" if value != "(detached)" => status.head = Some(value.to_string()),
+                "branch.upstream" => status.upstream = Some(value.to_string()),
+                "branch.ab" => {
+                    for count in value.split_whitespace() {
+                        if let Some(n) = count.strip_prefix('+') {
+                            status.ahead = n.parse().unwrap_or(0);
+                        } else if let Some(n) = count.strip_prefix('-') {
+                            status.behind = n.parse().unwrap_or(0);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        match line.split_once(' ').map(|(kind, _)| kind) {
+            // "1" ordinary change, "2" rename or copy
+            Some("1" | "2") => status.changed += 1,
+            Some("u") => status.conflicted += 1,
+            Some("?") => status.untracked += 1,
+            _ => {}
+        }
+    }
+    status
+}
+
+/// Markers git leaves in the git directory while an operation is stopped
+/// halfway, in the order they are checked
+pub const GIT_OPERATIONS: &[(&str, &str)] = &[
+    ("rebase-merge", "rebase"),
+    ("rebase-apply", "rebase"),
+    ("MERGE_HEAD", "merge"),
+    ("CHERRY_PICK_HEAD", "cherry-pick"),
+    ("REVERT_HEAD", "revert"),
+    ("BISECT_LOG", "bisect"),
+];
+
+/// The git directory of the work tree at `repo_path`: `.git` itself, or
+/// where a `.git` file (worktrees, submodules) points
+fn git_dir(repo_path: &Path) -> Option<PathBuf> {
+    let dot_git = repo_path.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let content = fs::read_to_string(&dot_git).ok()?;
+    let target = content.trim().strip_prefix("gitdir:")?.trim();
+    Some(repo_path.join(target))
+}
+
+/// Rebase, merge, cherry-pick, revert or bisect in progress in `repo_path`,
+/// read from the git directory without running git
+pub fn git_operation(repo_path: &Path) -> Option<&'static str> {
+    let dir = git_dir(repo_path)?;
+    GIT_OPERATIONS
+        .iter()
+        .find(|(marker, _)| dir.join(marker).exists())
+        .map(|(_, operation)| *operation)
+}
+
+/// Collect git info from a single repository path
+///
+/// One `git status --porcelain=v2 --branch` gives the branch, upstream
+/// distance and file counts, and `git log -1` the last commit. If status
+/// runs past `options.timeout`, the repo is still listed, by branch only,
+/// with `status_unavailable` set. With `backend = "gix"` (and the
+/// feature) `git_gix` reads the same without running git.
+pub fn collect_git_info_for_path(
+    runner: &dyn CommandRunner,
+    repo_path: &Path,
+    options: &StatusOptions,
+) -> Option<GitInfo> {
+    #[cfg(feature = "gix-backend")]
+    if options.backend == GitBackend::Gix {
+        return crate::collectors::git_gix::collect_git_info(repo_path, options);
+    }
+    let git = |args: &[&str]| git_in(runner, repo_path, args, options.timeout);
+
+    let mut info = GitInfo {
+        repo_path: display_path(repo_path),
+        // Before `git status`, which may rewrite the index
+        last_activity: repo_activity(repo_path)
+            .map(|time| chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339()),
+        ..Default::default()
+    };
+
+    // Fails outside a work tree, which doubles as the repository check
+    match git(&[
+        "status",
+        "--porcelain=v2",
+        "--branch",
+        options.untracked.as_arg(),
+    ]) {
+        Ok(output) if output.status.success() => {
+            let status = parse_porcelain_v2(&String::from_utf8_lossy(&output.stdout));
+            info.modified_files = status.changed;
+            info.untracked_files = status.untracked;
+            info.conflicted_files = status.conflicted;
+            info.is_dirty =
+                info.modified_files > 0 || info.untracked_files > 0 || info.conflicted_files > 0;
+            info.ahead = status.ahead;
+            info.behind = status.behind;
+            info.branch = match (status.head, status.oid) {
+                (Some(head), _) => head,
+                // Detached HEAD: short hash, like `git describe --always --dirty`
+                (None, Some(oid)) => {
+                    let short: String = oid.chars().take(7).collect();
+                    let tracked_changes = info.modified_files > 0 || info.conflicted_files > 0;
+                    format!("({}{})", short, if tracked_changes { "-dirty" } else { "" })
+                }
+                (None, None) => String::new(),
+            };
+        }
+        Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+            tracing::debug!(repo = %repo_path.display(), error = %e, "git status");
+            // Reading HEAD doesn't scan the tree, so it's still cheap here
+            let output = git(&["branch", "--show-current"]).ok()?;
+            if !output.status.success() {
+                return None;
+            }
+            info.branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            info.status_unavailable = Some("timeout".to_string());
+        }
+        _ => return None,
+    }
+
+    info.operation = git_operation(repo_path).map(str::to_string);
+
+    // Get last commit short hash and message
+    if let Ok(output) = git(&["log", "-1", "--format=%h %s"]) {
+        if output.status.success() {
+            let commit_info = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if commit_info.len() > 50 {
+                info.last_commit_short =
+                    format!("{}...", &commit_info.chars().take(47).collect::<String>());
+            } else {
+                info.last_commit_short = commit_info;
+            }
+        }
+    }
+
+    Some(info)
+}
+
+/// Auto-detect git repositories in subdirectories, as paths relative to
+/// `base_path`, leaving out what `ignore` matches
+pub fn find_git_repos(base_path: &Path, max_depth: usize, ignore: &ProjectIgnore) -> Vec<PathBuf> {
+    let mut repos = Vec::new();
+    find_git_repos_recursive(base_path, base_path, 0, max_depth, ignore, &mut repos);
+    repos.sort();
+    repos
+}
+
+pub fn find_git_repos_recursive(
+    base_path: &Path,
+    current: &Path,
+    depth: usize,
+    max_depth: usize,
+    ignore: &ProjectIgnore,
+    repos: &mut Vec<PathBuf>,
+) {
+    if depth > max_depth {
+        return;
+    }
+
+    // Check if current directory is a git repo
+    let git_dir = current.join(".git");
+    if git_dir.exists() {
+        // Use relative path from base
+        if let Ok(relative) = current.strip_prefix(base_path) {
+            if !relative.as_os_str().is_empty() {
+                repos.push(relative.to_path_buf());
+            }
+        }
+        return; // Don't recurse into git repos
+    }
+
+    // Recurse into subdirectories
+    if let Ok(entries) = fs::read_dir(current) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir()
+                && !skip_scan_dir(&entry.file_name())
+                && !ignore.is_ignored(&path, true)
+            {
+                find_git_repos_recursive(base_path, &path, depth + 1, max_depth, ignore, repos);
+            }
+        }
+    }
+}
+
+/// Hidden directories and common non-repo directories the walk skips.
+/// Only this check is lossy; non-UTF-8 names are still walked.
+pub fn skip_scan_dir(name: &OsStr) -> bool {
+    let name = name.to_string_lossy();
+    name.starts_with('.') || name == "node_modules" || name == "target" || name == "out"
+}
+
+/// Default for `git.repo_cache_max_age_secs`
+pub const DEFAULT_REPO_CACHE_MAX_AGE_SECS: u64 = 3600;
+
+/// An auto-detected repo list saved in `repo-cache.json`, with what it
+/// depends on
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RepoCache {
+    pub root: PathBuf,
+    pub scan_depth: usize,
+    pub saved_at: String,
+    /// The root's and its walked top-level directories' mtimes at the walk
+    pub dir_mtimes: Vec<(PathBuf, SystemTime)>,
+    pub repos: Vec<PathBuf>,
+}
+
+/// Where an auto-detected repo list came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepoWalk {
+    Fresh,
+    Cached { saved_at: String },
+}
+
+pub fn repo_cache_path(root: &Path) -> PathBuf {
+    project_state_dir(root).join("repo-cache.json")
+}
+
+/// Forget the saved repo list so the next discovery walks the tree
+pub fn clear_repo_cache(root: &Path) {
+    let _ = fs::remove_file(repo_cache_path(root));
+}
+
+/// Modification times of `root`, the top-level directories the walk
+/// enters and `.contextkeeper-ignore`; adding or removing a repository near
+/// the top, or editing what is ignored, changes one of them
+pub fn top_level_mtimes(root: &Path, ignore: &ProjectIgnore) -> Vec<(PathBuf, SystemTime)> {
+    let mtime = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    let mut mtimes: Vec<(PathBuf, SystemTime)> = fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            entry.path().is_dir()
+                && !skip_scan_dir(&entry.file_name())
+                && !ignore.is_ignored(&entry.path(), true)
+        })
+        .filter_map(|entry| Some((PathBuf::from(entry.file_name()), mtime(&entry.path())?)))
+        .collect();
+    mtimes.extend(mtime(root).map(|time| (PathBuf::new(), time)));
+    mtimes.extend(
+        ignore
+            .mtime
+            .map(|time| (PathBuf::from(PROJECT_IGNORE_FILE), time)),
+    );
+    mtimes.sort();
+    mtimes
+}
+
+/// Write a state file under `project_state_dir`; failures are only logged
+pub fn write_state_file<T: Serialize>(path: &Path, value: &T) {
+    let result = serde_json::to_string_pretty(value)
+        .map_err(io::Error::other)
+        .and_then(|json| {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(path, json)
+        });
+    if let Err(e) = result {
+        tracing::debug!(path = %path.display(), error = %e, "state file not saved");
+    }
+}
+
+/// `find_git_repos` for the project root, reusing the saved list while it
+/// is younger than `git.repo_cache_max_age_secs` and no top-level directory
+/// changed. Saved repositories that are gone are dropped from it.
+pub fn find_git_repos_cached(
+    project: &ProjectContext,
+    scan_depth: usize,
+) -> (Vec<PathBuf>, RepoWalk) {
+    let root = &project.root;
+    let max_age = project
+        .config
+        .git
+        .as_ref()
+        .and_then(|g| g.repo_cache_max_age_secs)
+        .unwrap_or(DEFAULT_REPO_CACHE_MAX_AGE_SECS);
+    if max_age == 0 {
+        return (
+            find_git_repos(root, scan_depth, &project.ignore),
+            RepoWalk::Fresh,
+        );
+    }
+
+    let path = repo_cache_path(root);
+    let dir_mtimes = top_level_mtimes(root, &project.ignore);
+    let cached = fs::read_to_string(&path)
+        .ok()
+        .and_then(|json| serde_json::from_str::<RepoCache>(&json).ok())
+        .filter(|cache| {
+            let age = chrono::DateTime::parse_from_rfc3339(&cache.saved_at).map(|saved| {
+                (chrono::Utc::now() - saved.with_timezone(&chrono::Utc)).num_seconds()
+            });
+            &cache.root == root
+                && cache.scan_depth == scan_depth
+                && cache.dir_mtimes == dir_mtimes
+                && age.is_ok_and(|age| (0..max_age as i64).contains(&age))
+        });
+
+    if let Some(mut cache) = cached {
+        let before = cache.repos.len();
+        cache
+            .repos
+            .retain(|repo| root.join(repo).join(".git").exists());
+        if cache.repos.len() != before {
+            write_state_file(&path, &cache);
+        }
+        let walk = RepoWalk::Cached {
+            saved_at: cache.saved_at,
+        };
+        return (cache.repos, walk);
+    }
+
+    let repos = find_git_repos(root, scan_depth, &project.ignore);
+    write_state_file(
+        &path,
+        &RepoCache {
+            root: root.clone(),
+            scan_depth,
+            saved_at: chrono::Utc::now().to_rfc3339(),
+            dir_mtimes,
+            repos: repos.clone(),
+        },
+    );
+    (repos, RepoWalk::Fresh)
+}
+
+/// How a repository path came to be checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RepoSource {
+    /// The project root itself
+    Root,
+    /// Listed in `git.paths`
+    Explicit,
+    /// Found by scanning subdirectories
+    AutoDetect,
+}
+
+impl RepoSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepoSource::Root => "root",
+            RepoSource::Explicit => "explicit",
+            RepoSource::AutoDetect => "auto-detect",
+        }
+    }
+}
+
+/// A path the git collector checked, with its status when it is a repository
+#[derive(Debug, Clone, Serialize)]
+pub struct RepoCandidate {
+    pub path: String,
+    pub full_path: String,
+    /// `full_path` as the filesystem sees it, for running git
+    #[serde(skip)]
+    pub dir: PathBuf,
+    pub found_by: RepoSource,
+    pub status: Option<GitInfo>,
+    /// Left out by `skip = true` in `[[git.repos]]`; git wasn't run
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub skipped: bool,
+}
+
+/// Check every repository path the config selects, sorted by path
+pub fn discover_git_repos(project: &ProjectContext) -> Vec<RepoCandidate> {
+    discover_git_repos_with_walk(project).0
+}
+
+/// `discover_git_repos`, also saying where an auto-detected list came from
+/// (`None` when nothing was auto-detected)
+pub fn discover_git_repos_with_walk(
+    project: &ProjectContext,
+) -> (Vec<RepoCandidate>, Option<RepoWalk>) {
+    let (candidates, walk, _) = discover_shortlisted(project, None);
+    (candidates, walk)
+}
+
+/// `discover_git_repos_with_walk`, but with more than `shortlist`
+/// directories to check, only the `shortlist` holding working files or
+/// most recently active (see `RepoRank`) are checked and listed. Also returns how many
+/// repositories were passed over.
+fn discover_shortlisted(
+    project: &ProjectContext,
+    shortlist: Option<usize>,
+) -> (Vec<RepoCandidate>, Option<RepoWalk>, usize) {
+    let config = &project.config;
+    let cwd = &project.root;
+
+    let mut options = StatusOptions::from_config(config);
+    if options.backend == GitBackend::Gix && !cfg!(feature = "gix-backend") {
+        collector_warning(format!(
+            "[git] backend = \"gix\": {}",
+            not_built("gix-backend")
+        ));
+        options.backend = GitBackend::Cli;
+    }
+
+    // First, check if current directory itself is a git repo
+    let root_override = repo_override(config, ".");
+    let root_status = if skips(root_override) {
+        None
+    } else {
+        collect_git_info_for_path(&*project.runner, cwd, &options.for_repo(root_override))
+    };
+    if root_status.is_some() || (skips(root_override) && git_dir(cwd).is_some()) {
+        let status = root_status.map(|mut info| {
+            info.repo_path = ".".to_string();
+            apply_repo_override(
+                &*project.runner,
+                cwd,
+                &mut info,
+                root_override,
+                options.timeout,
+            );
+            info
+        });
+        // If root is a git repo, don't scan subdirectories
+        let root = RepoCandidate {
+            path: ".".to_string(),
+            full_path: display_path(cwd),
+            dir: cwd.clone(),
+            found_by: RepoSource::Root,
+            skipped: status.is_none(),
+            status,
+        };
+        return (vec![root], None, 0);
+    }
+
+    // Get paths from config or auto-detect
+    let git_config = config.git.as_ref();
+    let auto_detect = git_config.and_then(|g| g.auto_detect).unwrap_or(true);
+    let explicit_paths = git_config.and_then(|g| g.paths.clone());
+    let scan_depth = git_config.and_then(|g| g.scan_depth).unwrap_or(2);
+
+    let mut walk = None;
+    let (mut paths_to_check, found_by): (Vec<PathBuf>, _) = if let Some(paths) = explicit_paths {
+        (
+            paths.into_iter().map(PathBuf::from).collect(),
+            RepoSource::Explicit,
+        )
+    } else if auto_detect {
+        let (repos, how) = find_git_repos_cached(project, scan_depth);
+        walk = Some(how);
+        (repos, RepoSource::AutoDetect)
+    } else {
+        (Vec::new(), RepoSource::AutoDetect)
+    };
+
+    let mut passed_over = 0;
+    if let Some(limit) = shortlist.filter(|limit| paths_to_check.len() > *limit) {
+        // Only the cheap signals before running git: no dirty flag yet,
+        // and a repository with a working file goes in whatever its age
+        let working = working_file_paths(project);
+        let mut ranked: Vec<(RepoRank, PathBuf)> = paths_to_check
+            .into_iter()
+            .map(|path| {
+                let relative = project_relative(cwd, &path);
+                (
+                    RepoRank::of(&cwd.join(&path), &relative, None, &working),
+                    path,
+                )
+            })
+            .collect();
+        ranked.sort_by_key(|(rank, _)| Reverse((rank.in_working_files, rank.activity)));
+        passed_over = ranked[limit..]
+            .iter()
+            .filter(|(rank, _)| rank.activity.is_some())
+            .count();
+        paths_to_check = ranked.into_iter().take(limit).map(|(_, p)| p).collect();
+    }
+
+    // Collect info from each path
+    let mut candidates: Vec<RepoCandidate> = paths_to_check
+        .into_iter()
+        .map(|path| {
+            let dir = cwd.join(&path);
+            let path = project_relative(cwd, &path);
+            let repo = repo_override(config, &path);
+            let status = if skips(repo) {
+                None
+            } else {
+                collect_git_info_for_path(&*project.runner, &dir, &options.for_repo(repo)).map(
+                    |mut info| {
+                        info.repo_path = path.clone();
+                        apply_repo_override(
+                            &*project.runner,
+                            &dir,
+                            &mut info,
+                            repo,
+                            options.timeout,
+                        );
+                        info
+                    },
+                )
+            };
+            RepoCandidate {
+                path,
+                full_path: display_path(&dir),
+                dir,
+                found_by,
+                skipped: skips(repo),
+                status,
+            }
+        })
+        .collect();
+
+    // Sort by path for consistent output
+    candidates.sort_by(|a, b| a.path.cmp(&b.path));
+    (candidates, walk, passed_over)
+}
+
+/// The directories `discover_git_repos` checks, without running `git
+/// status` in them: the root when it is inside a work tree, else the
+/// `git.paths` or the (cached) auto-detected repositories
+pub fn known_repo_dirs(project: &ProjectContext) -> Vec<PathBuf> {
+    let root = &project.root;
+    let timeout = StatusOptions::from_config(&project.config).timeout;
+    let in_work_tree = git_dir(root).is_some()
+        || git_in(
+            &*project.runner,
+            root,
+            &["rev-parse", "--is-inside-work-tree"],
+            timeout,
+        )
+        .is_ok_and(|output| output.status.success());
+    if in_work_tree {
+        return vec![root.clone()];
+    }
+
+    let git_config = project.config.git.as_ref();
+    let paths = if let Some(paths) = git_config.and_then(|g| g.paths.clone()) {
+        paths.into_iter().map(PathBuf::from).collect()
+    } else if git_config.and_then(|g| g.auto_detect).unwrap_or(true) {
+        let scan_depth = git_config.and_then(|g| g.scan_depth).unwrap_or(2);
+        find_git_repos_cached(project, scan_depth).0
+    } else {
+        Vec::new()
+    };
+    paths.into_iter().map(|path| root.join(path)).collect()
+}
+
+/// Default for `git.max_repos`
+pub const DEFAULT_MAX_GIT_REPOS: usize = 10;
+
+/// Most repositories shown in the context (`git.max_repos`)
+pub fn max_git_repos(config: &Config) -> usize {
+    config
+        .git
+        .as_ref()
+        .and_then(|g| g.max_repos)
+        .unwrap_or(DEFAULT_MAX_GIT_REPOS)
+}
+
+/// Last time git wrote to the repository at `repo_path`: its index, HEAD
+/// or HEAD reflog, which commits, checkouts, staging and `git status`
+/// refreshes all touch. Stats only; `None` when it isn't a repository.
+pub fn repo_activity(repo_path: &Path) -> Option<SystemTime> {
+    let dir = git_dir(repo_path)?;
+    ["index", "HEAD", "logs/HEAD"]
+        .iter()
+        .filter_map(|name| fs::metadata(dir.join(name)).ok()?.modified().ok())
+        .max()
+}
+
+/// Files of the saved work state and the edit hooks, relative to the
+/// project root
+fn working_file_paths(project: &ProjectContext) -> Vec<String> {
+    load_work_state_with_hooks(&project.root)
+        .ok()
+        .flatten()
+        .map(|state| state.working_files)
+        .unwrap_or_default()
+        .iter()
+        .map(|file| project_relative(&project.root, Path::new(file)))
+        .collect()
+}
+
+/// What decides which repositories make the `git.max_repos` cut: dirty
+/// ones first, then the most recently active, then the ones holding a
+/// working file. The greatest rank is the best; ties keep the path order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RepoRank {
+    /// Changed files or an operation in progress; false until `git status`
+    /// has run
+    pub dirty: bool,
+    /// See `repo_activity`
+    pub activity: Option<SystemTime>,
+    /// A file in the work state's `working_files` is in it
+    pub in_working_files: bool,
+}
+
+impl RepoRank {
+    /// The rank of the repository at `dir`, `path` relative to the root
+    pub fn of(dir: &Path, path: &str, status: Option<&GitInfo>, working: &[String]) -> Self {
+        RepoRank {
+            dirty: status.is_some_and(|s| s.is_dirty || s.operation.is_some()),
+            activity: repo_activity(dir),
+            in_working_files: working.iter().any(|file| {
+                file.strip_prefix(path)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            }),
+        }
+    }
+}
+
+/// The statuses from the last `collect_git_repos`, saved in
+/// `git-status.json` for `context-keeper prompt`, which never runs git
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitSnapshot {
+    pub saved_at: String,
+    pub repos: Vec<GitInfo>,
+}
+
+pub fn git_snapshot_path(root: &Path) -> PathBuf {
+    project_state_dir(root).join("git-status.json")
+}
+
+pub fn load_git_snapshot(root: &Path) -> Option<GitSnapshot> {
+    let json = fs::read_to_string(git_snapshot_path(root)).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// The `git.max_repos` repositories most worth showing (see `RepoRank`),
+/// best first, and how many were left out.
+///
+/// Unless `every_status`, `git status` only runs in the twice `max_repos`
+/// most recently active repositories: a dirty repository git hasn't
+/// touched in a while can miss the cut, but a tree with hundreds of
+/// repositories doesn't cost hundreds of status runs.
+pub fn collect_git_repos(project: &ProjectContext, every_status: bool) -> (Vec<GitInfo>, usize) {
+    let max_repos = max_git_repos(&project.config);
+    let shortlist = (!every_status).then(|| max_repos.saturating_mul(2));
+    let (candidates, _, passed_over) = discover_shortlisted(project, shortlist);
+
+    let working = working_file_paths(project);
+    let mut ranked: Vec<(RepoRank, GitInfo)> = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let status = candidate.status?;
+            let rank = RepoRank::of(&candidate.dir, &candidate.path, Some(&status), &working);
+            Some((rank, status))
+        })
+        .collect();
+    ranked.sort_by_key(|(rank, _)| Reverse(*rank));
+    let omitted = passed_over + ranked.len().saturating_sub(max_repos);
+    let repos: Vec<GitInfo> = ranked
+        .into_iter()
+        .take(max_repos)
+        .map(|(_, info)| info)
+        .collect();
+    write_state_file(
+        &git_snapshot_path(&project.root),
+        &GitSnapshot {
+            saved_at: chrono::Utc::now().to_rfc3339(),
+            repos: repos.clone(),
+        },
+    );
+    (repos, omitted)
+}
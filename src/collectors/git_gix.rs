@@ -0,0 +1,190 @@
+//! `[git] backend = "gix"`: a repository's branch, last commit and status
+//! read in-process with gix instead of running `git`
+//!
+//! Fills the same `GitInfo` the CLI path does, so nothing downstream can
+//! tell them apart. Counts follow `git status --porcelain=v2`: a path
+//! changed in the index and the work tree counts once, a conflicted path
+//! once, and untracked files per `[git] untracked`. Past the status
+//! timeout the scan is interrupted and the repository listed by branch,
+//! like a `git status` that ran too long.
+
+use crate::collectors::git::{git_operation, repo_activity, StatusOptions};
+use crate::collectors::GitInfo;
+use crate::config::UntrackedFiles;
+use crate::paths::display_path;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+/// Paths with changes, counted the way porcelain v2 lists them
+#[derive(Default)]
+struct StatusCounts {
+    changed: HashSet<Vec<u8>>,
+    conflicted: HashSet<Vec<u8>>,
+    untracked: usize,
+}
+
+fn untracked_mode(untracked: UntrackedFiles) -> gix::status::UntrackedFiles {
+    match untracked {
+        UntrackedFiles::All => gix::status::UntrackedFiles::Files,
+        UntrackedFiles::Normal => gix::status::UntrackedFiles::Collapsed,
+        UntrackedFiles::No => gix::status::UntrackedFiles::None,
+    }
+}
+
+/// Sets the flag once `timeout` passes, unless dropped before
+struct Deadline {
+    _cancel: mpsc::Sender<()>,
+}
+
+impl Deadline {
+    fn start(timeout: Option<Duration>, flag: Arc<AtomicBool>) -> Self {
+        let (cancel, cancelled) = mpsc::channel::<()>();
+        if let Some(timeout) = timeout {
+            std::thread::spawn(move || {
+                if let Err(mpsc::RecvTimeoutError::Timeout) = cancelled.recv_timeout(timeout) {
+                    flag.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+        Deadline { _cancel: cancel }
+    }
+}
+
+/// `None` when interrupted at the deadline or the status can't be read
+fn status_counts(
+    repo: &gix::Repository,
+    options: &StatusOptions,
+    interrupt: Arc<AtomicBool>,
+) -> Option<StatusCounts> {
+    use gix::status::index_worktree::Item as WorktreeItem;
+    use gix::status::plumbing::index_as_worktree::EntryStatus;
+
+    let _deadline = Deadline::start(options.timeout, Arc::clone(&interrupt));
+    let items = repo
+        .status(gix::progress::Discard)
+        .ok()?
+        .untracked_files(untracked_mode(options.untracked))
+        .should_interrupt_owned(Arc::clone(&interrupt))
+        .into_iter(None)
+        .ok()?;
+    let mut counts = StatusCounts::default();
+    for item in items {
+        let item = item.ok()?;
+        let path = item.location().to_vec();
+        match item {
+            gix::status::Item::TreeIndex(_) => {
+                counts.changed.insert(path);
+            }
+            gix::status::Item::IndexWorktree(WorktreeItem::Modification { status, .. }) => {
+                match status {
+                    EntryStatus::Conflict { .. } => {
+                        counts.conflicted.insert(path);
+                    }
+                    EntryStatus::Change(_) => {
+                        counts.changed.insert(path);
+                    }
+                    EntryStatus::NeedsUpdate(_) | EntryStatus::IntentToAdd => {}
+                }
+            }
+            gix::status::Item::IndexWorktree(WorktreeItem::DirectoryContents { entry, .. }) => {
+                if entry.status == gix::dir::entry::Status::Untracked {
+                    counts.untracked += 1;
+                }
+            }
+            gix::status::Item::IndexWorktree(WorktreeItem::Rewrite { .. }) => {
+                counts.changed.insert(path);
+            }
+        }
+    }
+    if interrupt.load(Ordering::Relaxed) {
+        return None;
+    }
+    // Conflicted paths are listed as `u`, not as changes too
+    counts
+        .changed
+        .retain(|path| !counts.conflicted.contains(path));
+    Some(counts)
+}
+
+/// Commits on `from` that `hidden` doesn't have
+fn count_unique(repo: &gix::Repository, from: gix::ObjectId, hidden: gix::ObjectId) -> usize {
+    repo.rev_walk([from])
+        .with_hidden([hidden])
+        .all()
+        .map(|walk| walk.filter_map(Result::ok).count())
+        .unwrap_or(0)
+}
+
+/// `collect_git_info_for_path` through gix; `None` outside a work tree
+pub fn collect_git_info(repo_path: &Path, options: &StatusOptions) -> Option<GitInfo> {
+    let repo = gix::discover(repo_path).ok()?;
+    repo.workdir()?;
+
+    let mut info = GitInfo {
+        repo_path: display_path(repo_path),
+        last_activity: repo_activity(repo_path)
+            .map(|time| chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339()),
+        ..Default::default()
+    };
+
+    let head_name = repo.head_name().ok().flatten();
+    let head_id = repo.head_id().ok().map(|id| id.detach());
+
+    match status_counts(&repo, options, Arc::new(AtomicBool::new(false))) {
+        Some(counts) => {
+            info.modified_files = counts.changed.len();
+            info.conflicted_files = counts.conflicted.len();
+            info.untracked_files = counts.untracked;
+            info.is_dirty =
+                info.modified_files > 0 || info.untracked_files > 0 || info.conflicted_files > 0;
+        }
+        None => info.status_unavailable = Some("timeout".to_string()),
+    }
+
+    info.branch = match (&head_name, head_id) {
+        (Some(name), _) => name.shorten().to_string(),
+        // Detached HEAD: short hash, like `git describe --always --dirty`
+        (None, Some(id)) => {
+            let tracked_changes = info.modified_files > 0 || info.conflicted_files > 0;
+            format!(
+                "({}{})",
+                id.to_hex_with_len(7),
+                if tracked_changes { "-dirty" } else { "" }
+            )
+        }
+        (None, None) => String::new(),
+    };
+
+    if let (Some(name), Some(head)) = (&head_name, head_id) {
+        let upstream = repo
+            .branch_remote_tracking_ref_name(name.as_ref(), gix::remote::Direction::Fetch)
+            .and_then(Result::ok)
+            .and_then(|upstream| repo.find_reference(upstream.as_ref()).ok())
+            .and_then(|mut reference| reference.peel_to_id().ok())
+            .map(|id| id.detach());
+        if let Some(upstream) = upstream {
+            info.ahead = count_unique(&repo, head, upstream);
+            info.behind = count_unique(&repo, upstream, head);
+        }
+    }
+
+    info.operation = git_operation(repo_path).map(str::to_string);
+
+    if let Ok(commit) = repo.head_commit() {
+        let summary = commit
+            .message()
+            .map(|message| message.summary().to_string())
+            .unwrap_or_default();
+        let commit_info = format!("{} {}", commit.id().shorten_or_id(), summary);
+        info.last_commit_short = if commit_info.len() > 50 {
+            format!("{}...", commit_info.chars().take(47).collect::<String>())
+        } else {
+            commit_info
+        };
+    }
+
+    Some(info)
+}
@@ -0,0 +1,139 @@
+//! Relevant commands from the hook-written history log
+
+use crate::config::{Config, HistoryConfig};
+use crate::context::CollectorRegistry;
+use crate::layout::default_history_log_path;
+use std::path::PathBuf;
+#[cfg(feature = "history")]
+use {
+    crate::collectors::{collector_capped, collector_disabled, collector_warning, HistoryEntry},
+    crate::error::ContextKeeperError,
+    crate::limits::{bounded_lines, overflow_summary, BoundedLine, Limits},
+    regex::Regex,
+    std::{fs, io},
+};
+
+/// Command history log written by the log-commands hook: `[history]
+/// log_file`, else the layout's default
+pub fn history_log_path(history_config: &HistoryConfig) -> PathBuf {
+    match &history_config.log_file {
+        Some(path) => PathBuf::from(path),
+        None => default_history_log_path(),
+    }
+}
+
+/// The history log for `config`, `[history]` or not
+pub fn command_log_path(config: &Config) -> PathBuf {
+    match &config.history {
+        Some(history_config) => history_log_path(history_config),
+        None => default_history_log_path(),
+    }
+}
+
+/// Adds the `history` collector, or a stand-in without the `history`
+/// feature
+pub fn register(registry: &mut CollectorRegistry) {
+    #[cfg(feature = "history")]
+    registry.add_fn("history", |project| {
+        Ok(crate::context::CollectorOutput::History(
+            collect_command_history(&project.config)?,
+        ))
+    });
+    #[cfg(not(feature = "history"))]
+    registry.add(crate::context::NotBuilt {
+        name: "history",
+        feature: "history",
+    });
+}
+
+#[cfg(feature = "history")]
+pub fn collect_command_history(config: &Config) -> Result<Vec<HistoryEntry>, ContextKeeperError> {
+    let history_config = match &config.history {
+        Some(hc) if hc.enabled.unwrap_or(true) => hc,
+        _ => {
+            collector_disabled();
+            return Ok(Vec::new());
+        }
+    };
+
+    let log_file = history_log_path(history_config);
+
+    let max_entries = history_config.max_entries.unwrap_or(20);
+
+    let default_patterns = vec![
+        r"lunch\s+\S+".to_string(),
+        r"source\s+.*envsetup".to_string(),
+        r"export\s+\w+=".to_string(),
+        r"m\s+\S+".to_string(),
+        r"mm\b".to_string(),
+        r"mma\b".to_string(),
+    ];
+
+    let patterns = history_config.patterns.clone().unwrap_or(default_patterns);
+
+    let compiled_patterns: Vec<Regex> = patterns
+        .iter()
+        .filter_map(|p| match Regex::new(p) {
+            Ok(re) => Some(re),
+            Err(e) => {
+                collector_warning(format!("history pattern {:?}: {}", p, e));
+                None
+            }
+        })
+        .collect();
+
+    let mut entries = Vec::new();
+    let path = log_file.as_path();
+
+    if !path.exists() {
+        return Ok(entries);
+    }
+
+    let file = fs::File::open(path).map_err(|e| ContextKeeperError::state_io(path, e))?;
+    let reader = io::BufReader::new(file);
+    let max_line_bytes = Limits::from_config(config).max_line_bytes;
+    let mut too_long = 0;
+
+    for line in bounded_lines(reader, max_line_bytes) {
+        let line = match line {
+            BoundedLine::Line(line) => line,
+            BoundedLine::TooLong(_) => {
+                too_long += 1;
+                continue;
+            }
+        };
+        // Only the newest entries are kept; drop older ones as the log is read
+        if entries.len() >= max_entries.saturating_mul(2).max(64) {
+            entries.drain(0..entries.len() - max_entries);
+        }
+        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&line) {
+            let command = json["command"].as_str().unwrap_or("");
+            let matches_pattern = compiled_patterns.is_empty()
+                || compiled_patterns.iter().any(|re| re.is_match(command));
+
+            if matches_pattern && !command.is_empty() {
+                entries.push(HistoryEntry {
+                    timestamp: json["timestamp"].as_str().unwrap_or("").to_string(),
+                    command: command.to_string(),
+                });
+            }
+        }
+    }
+
+    if entries.len() > max_entries {
+        entries.drain(0..entries.len() - max_entries);
+    }
+    if too_long > 0 {
+        collector_capped(format!(
+            "history: {}",
+            overflow_summary(
+                too_long,
+                "log line skipped as too long",
+                "log lines skipped as too long",
+                "max_line_bytes"
+            )
+        ));
+    }
+
+    Ok(entries)
+}
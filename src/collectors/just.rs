@@ -0,0 +1,132 @@
+//! Justfile recipes
+
+use crate::config::ProjectContext;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// Most just recipes listed
+pub const MAX_JUST_RECIPES: usize = 20;
+
+/// Names just looks for in a directory
+pub const JUSTFILE_NAMES: [&str; 3] = ["justfile", "Justfile", ".justfile"];
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct JustRecipe {
+    pub name: String,
+    /// Parameters as written, e.g. `target mode='debug' *args`
+    pub parameters: String,
+    /// `# comment` directly above the recipe
+    pub description: String,
+}
+
+impl JustRecipe {
+    /// Invocation with required parameters as placeholders: `just deploy <env>`
+    pub fn command(&self) -> String {
+        let mut command = format!("just {}", self.name);
+        for param in self.parameters.split_whitespace() {
+            let param = param.trim_start_matches('$');
+            if !param.contains('=') && !param.starts_with('*') {
+                command.push_str(&format!(" <{}>", param.trim_start_matches('+')));
+            }
+        }
+        command
+    }
+}
+
+pub fn find_justfile(project: &ProjectContext) -> Option<PathBuf> {
+    let enabled = project
+        .config
+        .scripts
+        .as_ref()
+        .and_then(|s| s.justfile)
+        .unwrap_or(true);
+    if !enabled {
+        return None;
+    }
+    JUSTFILE_NAMES
+        .iter()
+        .map(|name| project.root.join(name))
+        .find(|p| p.is_file())
+}
+
+/// Byte offset of the first `:` outside quotes that isn't part of `:=`
+pub fn find_recipe_colon(line: &str) -> Option<usize> {
+    let mut quote = None;
+    let bytes = line.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        match (quote, b) {
+            (None, b'"' | b'\'' | b'`') => quote = Some(b),
+            (Some(q), _) if q == b => quote = None,
+            (None, b':') => return (bytes.get(i + 1) != Some(&b'=')).then_some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse public recipe headers from a justfile
+pub fn parse_justfile(content: &str) -> Vec<JustRecipe> {
+    const KEYWORDS: [&str; 6] = ["set", "alias", "export", "import", "mod", "unexport"];
+
+    let mut recipes = Vec::new();
+    let mut doc = String::new();
+    let mut private = false;
+
+    for line in content.lines() {
+        // Recipe bodies are indented
+        if line.starts_with([' ', '\t']) || line.trim().is_empty() {
+            if !line.trim().is_empty() {
+                doc.clear();
+            }
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            if !comment.starts_with('!') {
+                doc = comment.trim().to_string();
+            }
+            continue;
+        }
+        if line.starts_with('[') {
+            private |= line.contains("private");
+            continue;
+        }
+
+        let (doc, is_private) = (std::mem::take(&mut doc), std::mem::take(&mut private));
+        let first_word = line.split_whitespace().next().unwrap_or("");
+        if KEYWORDS.contains(&first_word) {
+            continue;
+        }
+        let Some(colon) = find_recipe_colon(line) else {
+            continue;
+        };
+        let header = line[..colon].trim().trim_start_matches('@');
+        let (name, parameters) = header
+            .split_once(char::is_whitespace)
+            .unwrap_or((header, ""));
+        let valid_name = name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if name.is_empty() || !valid_name || name.starts_with('_') || is_private {
+            continue;
+        }
+
+        recipes.push(JustRecipe {
+            name: name.to_string(),
+            parameters: parameters.trim().to_string(),
+            description: doc,
+        });
+        if recipes.len() == MAX_JUST_RECIPES {
+            break;
+        }
+    }
+
+    recipes
+}
+
+pub fn collect_just_recipes(project: &ProjectContext) -> Vec<JustRecipe> {
+    find_justfile(project)
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|content| parse_justfile(&content))
+        .unwrap_or_default()
+}
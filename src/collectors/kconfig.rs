@@ -0,0 +1,128 @@
+//! Kconfig `.config` summary (Buildroot / kernel)
+
+use crate::collectors::packages::project_type_is;
+use crate::collectors::yocto::dir_size_and_mtime;
+use crate::config::{file_mtime, ProjectContext};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Symbols reported when `kconfig.symbols` isn't set
+pub const DEFAULT_BUILDROOT_SYMBOLS: &[&str] = &["BR2_DEFCONFIG", "BR2_ARCH"];
+pub const DEFAULT_KERNEL_SYMBOLS: &[&str] = &["CONFIG_LOCALVERSION"];
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct KconfigInfo {
+    /// `.config` in use, relative to the project root
+    pub config_file: String,
+    pub exists: bool,
+    /// Header comment, e.g. "Linux/arm64 6.6.0 Kernel Configuration"
+    pub header: Option<String>,
+    /// Last change to `.config` (RFC 3339)
+    pub modified: Option<String>,
+    /// Newest build artifact (Buildroot output/images, kernel vmlinux)
+    pub artifact: Option<String>,
+    pub artifact_modified: Option<String>,
+    /// `.config` changed after the last build
+    pub changed_since_build: bool,
+    /// Requested symbols in order; `None` when the symbol isn't in `.config`
+    pub symbols: Vec<(String, Option<String>)>,
+}
+
+/// Symbol values from a `.config`. `# CONFIG_X is not set` reads as "n";
+/// string values keep their quotes off.
+pub fn parse_kconfig(content: &str) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if let Some(name) = line
+            .strip_prefix("# ")
+            .and_then(|rest| rest.strip_suffix(" is not set"))
+        {
+            values.insert(name.to_string(), "n".to_string());
+        } else if let Some((name, value)) = line.split_once('=') {
+            if !line.starts_with('#') {
+                values.insert(name.to_string(), value.trim_matches('"').to_string());
+            }
+        }
+    }
+    values
+}
+
+pub fn collect_kconfig_info(project: &ProjectContext) -> Option<KconfigInfo> {
+    let buildroot = project_type_is(project, "buildroot");
+    if !buildroot && !project_type_is(project, "kernel") && project.config.kconfig.is_none() {
+        return None;
+    }
+    let root = &project.root;
+    // In-tree .config first, then Buildroot's default output directory
+    let config_file = [root.join(".config"), root.join("output/.config")]
+        .into_iter()
+        .find(|p| p.is_file())
+        .unwrap_or_else(|| root.join(".config"));
+    let to_rfc3339 = |time: SystemTime| chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339();
+
+    let content = fs::read_to_string(&config_file).ok();
+    let values = content.as_deref().map(parse_kconfig).unwrap_or_default();
+    let header = content.as_deref().and_then(|c| {
+        c.lines()
+            .filter_map(|line| line.strip_prefix("# "))
+            .find(|line| line.ends_with("Configuration"))
+            .map(str::to_string)
+    });
+
+    let configured = project
+        .config
+        .kconfig
+        .as_ref()
+        .and_then(|k| k.symbols.clone());
+    let names = configured.unwrap_or_else(|| {
+        let defaults = if buildroot {
+            DEFAULT_BUILDROOT_SYMBOLS
+        } else {
+            DEFAULT_KERNEL_SYMBOLS
+        };
+        defaults.iter().map(|s| s.to_string()).collect()
+    });
+    let symbols = names
+        .into_iter()
+        .map(|name| {
+            let value = values.get(&name).cloned();
+            (name, value)
+        })
+        .collect();
+
+    let output = config_file.parent().unwrap_or(root);
+    let images = output.join(if output == root {
+        "output/images"
+    } else {
+        "images"
+    });
+    let (artifact, artifact_mtime) = if images.is_dir() {
+        (Some(images.clone()), dir_size_and_mtime(&images, 1).1)
+    } else {
+        let vmlinux = root.join("vmlinux");
+        let mtime = file_mtime(&vmlinux);
+        (mtime.map(|_| vmlinux), mtime)
+    };
+    let config_mtime = file_mtime(&config_file);
+    let relative = |path: &Path| {
+        path.strip_prefix(root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string()
+    };
+
+    Some(KconfigInfo {
+        config_file: relative(&config_file),
+        exists: content.is_some(),
+        header,
+        modified: config_mtime.map(to_rfc3339),
+        artifact: artifact.as_deref().map(relative),
+        artifact_modified: artifact_mtime.map(to_rfc3339),
+        changed_since_build: matches!((config_mtime, artifact_mtime), (Some(c), Some(a)) if c > a),
+        symbols,
+    })
+}
@@ -0,0 +1,163 @@
+//! Makefile targets
+
+use crate::collectors::BuildTarget;
+use crate::config::ProjectContext;
+use crate::paths::display_path;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Most Makefile targets listed
+pub const MAX_MAKE_TARGETS: usize = 20;
+
+/// Names GNU make looks for, in its search order
+pub const MAKEFILE_NAMES: [&str; 3] = ["GNUmakefile", "makefile", "Makefile"];
+
+/// `scripts.makefile` when set, else the first Makefile in the project root
+pub fn find_makefile(project: &ProjectContext) -> Option<PathBuf> {
+    match project
+        .config
+        .scripts
+        .as_ref()
+        .and_then(|s| s.makefile.as_deref())
+    {
+        Some(path) => Some(project.resolve(path)).filter(|p| p.is_file()),
+        None => MAKEFILE_NAMES
+            .iter()
+            .map(|name| project.root.join(name))
+            .find(|p| p.is_file()),
+    }
+}
+
+/// Rules and `.PHONY` names gathered from a Makefile and its includes
+#[derive(Debug, Default)]
+pub struct MakeRules {
+    /// (target, `## description`, file it was defined in), in file order
+    pub rules: Vec<(String, String, PathBuf)>,
+    pub phony: std::collections::HashSet<String>,
+}
+
+/// A command-like target name: no pattern, variable, path or file extension
+pub fn is_command_target(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Parse top-level rules, following `include` lines when `follow_includes`
+/// (one level deep: included files' own includes are ignored)
+pub fn parse_makefile(path: &Path, follow_includes: bool, out: &mut MakeRules) {
+    let Ok(content) = fs::read_to_string(path) else {
+        return;
+    };
+    let dir = path.parent().unwrap_or(Path::new("."));
+
+    // Join backslash continuations so .PHONY lists can span lines
+    let content = content.replace("\\\r\n", " ").replace("\\\n", " ");
+
+    let mut pending_doc = String::new();
+    let mut in_define = false;
+    for raw in content.lines() {
+        // Recipe lines start with a tab; anything else may be indented with spaces
+        if raw.starts_with('\t') {
+            pending_doc.clear();
+            continue;
+        }
+        let line = raw.trim();
+
+        if in_define {
+            in_define = line != "endef";
+            continue;
+        }
+        if line.starts_with("define ") || line == "define" {
+            in_define = true;
+            continue;
+        }
+        if let Some(doc) = line.strip_prefix("##") {
+            pending_doc = doc.trim().to_string();
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            pending_doc.clear();
+            continue;
+        }
+
+        let include = ["include ", "-include ", "sinclude "]
+            .iter()
+            .find_map(|prefix| line.strip_prefix(prefix));
+        if let Some(files) = include {
+            if follow_includes {
+                for file in files.split_whitespace().filter(|f| !f.contains('$')) {
+                    parse_makefile(&dir.join(file), false, out);
+                }
+            }
+            pending_doc.clear();
+            continue;
+        }
+
+        let doc = std::mem::take(&mut pending_doc);
+        let Some((targets, rest)) = line.split_once(':') else {
+            continue;
+        };
+        // Skip assignments (`A = b:c`, `A := b`) and target-specific variables
+        let (deps, comment) = match rest.split_once("##") {
+            Some((deps, comment)) => (deps, comment.trim().to_string()),
+            None => (rest.split('#').next().unwrap_or(""), String::new()),
+        };
+        if targets.contains('=') || rest.starts_with('=') || deps.contains('=') {
+            continue;
+        }
+
+        let targets: Vec<&str> = targets.split_whitespace().collect();
+        if targets == [".PHONY"] {
+            out.phony
+                .extend(deps.split_whitespace().map(str::to_string));
+            continue;
+        }
+        if !targets.iter().all(|t| is_command_target(t)) {
+            continue;
+        }
+
+        let description = if comment.is_empty() { doc } else { comment };
+        for target in targets {
+            out.rules
+                .push((target.to_string(), description.clone(), path.to_path_buf()));
+        }
+    }
+}
+
+/// Targets from the project's Makefile, `.PHONY` ones first, capped
+pub fn collect_make_targets(project: &ProjectContext) -> Vec<BuildTarget> {
+    let Some(makefile) = find_makefile(project) else {
+        return Vec::new();
+    };
+    let mut parsed = MakeRules::default();
+    parse_makefile(&makefile, true, &mut parsed);
+
+    let (mut phony, other): (Vec<_>, Vec<_>) = parsed
+        .rules
+        .into_iter()
+        .partition(|(name, _, _)| parsed.phony.contains(name));
+    phony.extend(other);
+
+    let mut targets: Vec<BuildTarget> = Vec::new();
+    for (name, description, file) in phony {
+        match targets.iter_mut().find(|t| t.name == name) {
+            // A later rule for the same target may carry the description
+            Some(existing) => {
+                if existing.description.is_empty() {
+                    existing.description = description;
+                }
+            }
+            None => targets.push(BuildTarget {
+                name,
+                source: "makefile".to_string(),
+                source_file: display_path(&file),
+                description,
+                ..Default::default()
+            }),
+        }
+    }
+    targets.truncate(MAX_MAKE_TARGETS);
+    targets
+}
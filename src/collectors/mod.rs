@@ -0,0 +1,519 @@
+//! Collectors, one module per source, and the subprocess helpers they
+//! share. Each collector counts its subprocesses and warnings in
+//! `COLLECTOR_STATS` for its `CollectorReport`. The git, container and
+//! device collectors, and the Gerrit and pull request lookups, spawn
+//! through the project's `CommandRunner`.
+
+pub mod adb;
+pub mod artifacts;
+pub mod build_results;
+pub mod code_todos;
+pub mod container_socket;
+pub mod containers;
+pub mod custom;
+pub mod detect;
+pub mod diff_summary;
+pub mod entry_point;
+pub mod environment;
+pub mod fake_runner;
+pub mod forge;
+pub mod gerrit;
+pub mod git;
+#[cfg(feature = "gix-backend")]
+pub mod git_gix;
+pub mod history;
+pub mod just;
+pub mod kconfig;
+pub mod make;
+pub mod onboarding;
+pub mod open_files;
+pub mod packages;
+pub mod processes;
+pub mod project_ignore;
+pub mod recent_files;
+pub mod remote;
+pub mod resolve_path;
+pub mod resources;
+pub mod ros;
+pub mod shell_env;
+pub mod targets;
+pub mod tmux;
+pub mod validation;
+pub mod verify_files;
+pub mod west;
+pub mod working_files;
+pub mod yocto;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// ============================================================================
+// Collector Data Structures
+// ============================================================================
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct BuildTarget {
+    pub name: String,
+    /// Where the target was found: "conf" or "makefile"
+    pub source: String,
+    /// File the target was parsed from
+    pub source_file: String,
+    pub description: String,
+    pub container_name: String,
+    pub lunch_target: String,
+    pub can_emulator: bool,
+    pub can_flash: bool,
+    /// `TARGET_HINT` from the .conf
+    pub hint: String,
+    /// Problems found by `validate_targets`, e.g. "container missing"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub issues: Vec<String>,
+    /// Glob for the target's build outputs: `ARTIFACT_PATH` or `[artifacts]`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub artifact_path: String,
+    /// Newest file `artifact_path` matched, see `attach_artifacts`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact: Option<artifacts::Artifact>,
+    /// `EXEC_TEMPLATE` from the .conf, ahead of `[containers] exec_template`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub exec_template: String,
+    /// The build run inside `container_name`, see `attach_exec_commands`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub exec_command: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ContainerInfo {
+    pub name: String,
+    pub status: String,
+    pub runtime: String,
+    /// Labels, e.g. `com.docker.compose.project`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// Published ports as `ps` shows them, e.g. "0.0.0.0:8080->80/tcp";
+    /// an unpublished one without the host side, "80/tcp"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ports: Vec<String>,
+    /// Where volumes and bind mounts appear inside the container. The
+    /// docker CLI doesn't list them, so they are empty from it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mounts: Vec<String>,
+}
+
+impl ContainerInfo {
+    /// Health check failing, as reported in the runtime's status column
+    pub fn is_unhealthy(&self) -> bool {
+        self.status.contains("(unhealthy)")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub timestamp: String,
+    pub command: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct GitInfo {
+    pub repo_path: String, // Relative path to the repository
+    pub branch: String,
+    pub is_dirty: bool,
+    pub modified_files: usize,
+    pub untracked_files: usize,
+    pub conflicted_files: usize, // Unmerged paths (merge/rebase conflicts)
+    pub last_commit_short: String,
+    /// Commits ahead of / behind the upstream branch (0 without one), or
+    /// `compared_to` when that is set
+    #[serde(default)]
+    pub ahead: usize,
+    #[serde(default)]
+    pub behind: usize,
+    /// `base_branch` from `[[git.repos]]`, once ahead/behind were counted
+    /// against it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compared_to: Option<String>,
+    /// `alias` from `[[git.repos]]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// `show_clean` from `[[git.repos]]`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub show_clean: bool,
+    /// Operation stopped halfway: "rebase", "merge", "cherry-pick",
+    /// "revert" or "bisect"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation: Option<String>,
+    /// Why the status is missing, e.g. "timeout"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_unavailable: Option<String>,
+    /// Open changes uploaded from this repo, with `[gerrit]` at full level
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<GerritChange>,
+    /// The user's open GitHub PRs / GitLab MRs, with `[pull_requests]`;
+    /// the current branch's first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pull_requests: Vec<PullRequest>,
+    /// When git last wrote the index, HEAD or reflog (RFC 3339), as read
+    /// before `git status` refreshed the index
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_activity: Option<String>,
+}
+
+impl GitInfo {
+    /// The alias when one is set, else the path
+    pub fn name(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.repo_path)
+    }
+}
+
+/// An open GitHub pull request or GitLab merge request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequest {
+    pub number: u64,
+    pub title: String,
+    pub branch: String,
+    /// Opened from the branch the repo has checked out
+    pub current_branch: bool,
+    pub draft: bool,
+    /// "approved", "changes requested" or "review required"
+    pub review: Option<String>,
+    /// CI: "passing", "failing" or "pending"
+    pub checks: Option<String>,
+}
+
+/// One of the user's open changes on Gerrit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GerritChange {
+    pub number: u64,
+    pub subject: String,
+    pub project: String,
+    pub branch: String,
+    /// Strongest Code-Review / Verified vote: the lowest if any is
+    /// negative, else the highest; `None` without votes
+    pub code_review: Option<i32>,
+    pub verified: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdbDevice {
+    pub serial: String,
+    pub state: String,
+    pub device_type: String, // "adb" or "fastboot"
+    /// `model:` of `adb devices -l`, e.g. "Pixel_7"
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub model: String,
+    /// `device:` of `adb devices -l`, e.g. "panther"
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub codename: String,
+    /// The device pinned by `[adb]` or `ANDROID_SERIAL`
+    #[serde(default)]
+    pub is_default: bool,
+}
+
+/// A TODO/FIXME/XXX comment in one of the working files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeTodo {
+    /// As in `WorkState::working_files`
+    pub file: String,
+    /// 1-based
+    pub line: usize,
+    /// The comment from its marker on, e.g. "TODO: retry on EAGAIN"
+    pub text: String,
+}
+
+/// A tmux pane whose current path is under the project root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TmuxPane {
+    pub session: String,
+    pub window: String,
+    /// `pane_current_command`, e.g. "adb" or "zsh"
+    pub command: String,
+    /// Relative to the project root ("." for the root itself)
+    pub path: String,
+}
+
+impl TmuxPane {
+    /// Running a build, a log tail, a container or an emulator rather
+    /// than sitting at a shell prompt
+    pub fn is_long_lived(&self) -> bool {
+        tmux::LONG_LIVED_COMMANDS.contains(&self.command.as_str())
+            || self.command.starts_with("qemu-")
+    }
+}
+
+// ============================================================================
+// Subprocesses and Warnings
+// ============================================================================
+
+/// Per-thread counters filled by `run_command` while a collector runs
+#[derive(Debug, Default)]
+pub struct CollectorStats {
+    pub subprocesses: usize,
+    pub warnings: Vec<String>,
+    /// Summaries of what `[limits]` cut, also in `warnings`
+    pub capped: Vec<String>,
+    /// Why the source couldn't be asked, the first reason given
+    pub unavailable: Option<String>,
+    /// The config turns the collector off
+    pub disabled: bool,
+}
+
+thread_local! {
+    pub static COLLECTOR_STATS: std::cell::RefCell<CollectorStats> = Default::default();
+}
+
+/// Record a warning in the running collector's report
+pub fn collector_warning(warning: String) {
+    tracing::debug!(%warning, "collector warning");
+    COLLECTOR_STATS.with(|stats| stats.borrow_mut().warnings.push(warning));
+}
+
+/// Record that the running collector stopped at a `[limits]` cap, with a
+/// summary of what it left out (see `limits::overflow_summary`)
+pub fn collector_capped(summary: String) {
+    COLLECTOR_STATS.with(|stats| stats.borrow_mut().capped.push(summary.clone()));
+    collector_warning(summary);
+}
+
+/// Record that the running collector's source can't be asked (a tool
+/// missing or failing), for a report that found nothing. The first reason
+/// given is kept.
+pub fn collector_unavailable(reason: String) {
+    COLLECTOR_STATS.with(|stats| {
+        stats.borrow_mut().unavailable.get_or_insert(reason);
+    });
+}
+
+/// "`podman ps` failed: <first line of stderr>", for `collector_unavailable`
+pub fn failure_reason(command: &str, output: &std::process::Output) -> String {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match stderr.lines().map(str::trim).find(|line| !line.is_empty()) {
+        Some(line) => format!("`{}` failed: {}", command, line),
+        None => format!("`{}` failed ({})", command, output.status),
+    }
+}
+
+/// Why a collector left out of this build found nothing: "built without
+/// the `adb` feature"
+pub fn not_built(feature: &str) -> String {
+    format!("built without the `{}` feature", feature)
+}
+
+/// Record that the config turns the running collector off
+pub fn collector_disabled() {
+    COLLECTOR_STATS.with(|stats| stats.borrow_mut().disabled = true);
+}
+
+pub fn record_command(command: &std::process::Command, result: &io::Result<std::process::Output>) {
+    record_run(command.get_program(), result);
+}
+
+/// Count a subprocess of the running collector; a spawn failure becomes
+/// a warning, and a missing program the reason the source is unavailable
+pub fn record_run(program: &OsStr, result: &io::Result<std::process::Output>) {
+    COLLECTOR_STATS.with(|stats| stats.borrow_mut().subprocesses += 1);
+    if let Err(e) = result {
+        if e.kind() == io::ErrorKind::NotFound {
+            let path = Path::new(program);
+            let name = path.file_stem().unwrap_or(program);
+            collector_unavailable(format!("{} not installed", name.to_string_lossy()));
+        }
+        collector_warning(format!("{}: {}", program.to_string_lossy(), e));
+    }
+}
+
+/// Locate `name` on PATH, trying PATHEXT suffixes (`.exe`, `.cmd`, ...) on
+/// Windows. Falls back to the bare name so spawn errors still name the tool.
+pub fn resolve_program(name: &str) -> PathBuf {
+    let extensions: Vec<String> = if cfg!(windows) {
+        std::env::var("PATHEXT")
+            .unwrap_or_else(|_| ".EXE;.CMD;.BAT;.COM".to_string())
+            .split(';')
+            .filter(|ext| !ext.is_empty())
+            .map(|ext| ext.to_ascii_lowercase())
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    if let Some(paths) = std::env::var_os("PATH") {
+        for dir in std::env::split_paths(&paths) {
+            let candidate = dir.join(name);
+            if candidate.is_file() {
+                return candidate;
+            }
+            for ext in &extensions {
+                let candidate = dir.join(format!("{}{}", name, ext));
+                if candidate.is_file() {
+                    return candidate;
+                }
+            }
+        }
+    }
+    PathBuf::from(name)
+}
+
+/// Command line run through the platform shell: `sh -c` or `cmd /C`
+pub fn shell_command(cmd: &str) -> std::process::Command {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut command = std::process::Command::new(shell);
+    command.args([flag, cmd]);
+    command
+}
+
+/// How long a collector subprocess may run when the call names no
+/// timeout of its own
+pub const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Run a collector subprocess, counting it for the collector's report.
+/// It is killed after `DEFAULT_COMMAND_TIMEOUT`.
+pub fn run_command(command: &mut std::process::Command) -> io::Result<std::process::Output> {
+    execute(command, None)
+}
+
+/// Like `run_command`, but kill the child if it runs past `timeout`
+pub fn run_command_timeout(
+    command: &mut std::process::Command,
+    timeout: Duration,
+) -> io::Result<std::process::Output> {
+    execute(command, Some(timeout))
+}
+
+/// Every collector subprocess ends up here: spawn, enforce the timeout
+/// (`DEFAULT_COMMAND_TIMEOUT` when none is given), log and record it
+fn execute(
+    command: &mut std::process::Command,
+    timeout: Option<Duration>,
+) -> io::Result<std::process::Output> {
+    let started = Instant::now();
+    // Moved out whole, since a copy would lose `env_clear`
+    let program = command.get_program().to_os_string();
+    let owned = std::mem::replace(command, std::process::Command::new(&program));
+    let result = output_with_timeout(owned, timeout.unwrap_or(DEFAULT_COMMAND_TIMEOUT));
+    tracing::debug!(
+        program = %program.to_string_lossy(),
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        success = result.as_ref().is_ok_and(|output| output.status.success()),
+        "subprocess"
+    );
+    record_run(&program, &result);
+    result
+}
+
+/// Drive `future` on a single-threaded runtime of its own. Collectors
+/// run on plain threads; called from inside a runtime this moves to a
+/// scoped thread, since a runtime can't block on another.
+pub fn block_on<F>(future: F) -> io::Result<F::Output>
+where
+    F: std::future::Future + Send,
+    F::Output: Send,
+{
+    let run = move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Ok(runtime.block_on(future))
+    };
+    if tokio::runtime::Handle::try_current().is_ok() {
+        std::thread::scope(|scope| {
+            scope
+                .spawn(run)
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::other("runtime thread panicked")))
+        })
+    } else {
+        run()
+    }
+}
+
+/// Spawn `command` through `tokio::process` with stdin closed, killing
+/// it once `timeout` expires
+fn output_with_timeout(
+    command: std::process::Command,
+    timeout: Duration,
+) -> io::Result<std::process::Output> {
+    use std::process::Stdio;
+
+    let mut child = tokio::process::Command::from(command);
+    child
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    block_on(async move {
+        let output = child.spawn()?.wait_with_output();
+        match tokio::time::timeout(timeout, output).await {
+            Ok(output) => output,
+            // Dropping the future kills the child
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("timed out after {:?}", timeout),
+            )),
+        }
+    })?
+}
+
+// ============================================================================
+// Command Runner
+// ============================================================================
+
+/// How collectors run external tools. `ProjectContext::runner` is the
+/// `SystemRunner` unless an embedder or a test swaps in its own, e.g. a
+/// `fake_runner::FakeRunner` replaying captured `podman ps` /
+/// `adb devices -l` output.
+pub trait CommandRunner: Send + Sync + std::fmt::Debug {
+    /// Run `program` with `args` and capture its output. Stdin is closed
+    /// and the child is killed once `timeout` expires, or after
+    /// `DEFAULT_COMMAND_TIMEOUT` without one.
+    fn run(
+        &self,
+        program: &str,
+        args: &[&OsStr],
+        timeout: Option<Duration>,
+    ) -> io::Result<std::process::Output>;
+
+    /// Whether `program` is installed, for tools that are only worth
+    /// asking when present
+    fn has_program(&self, program: &str) -> bool {
+        resolve_program(program).is_file()
+    }
+}
+
+/// Spawns real processes, resolving bare program names on PATH
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(
+        &self,
+        program: &str,
+        args: &[&OsStr],
+        timeout: Option<Duration>,
+    ) -> io::Result<std::process::Output> {
+        let program = if Path::new(program).components().count() > 1 {
+            PathBuf::from(program)
+        } else {
+            resolve_program(program)
+        };
+        execute(std::process::Command::new(program).args(args), timeout)
+    }
+}
+
+/// `runner.run` for arguments that are all plain strings, with the
+/// `DEFAULT_COMMAND_TIMEOUT`
+pub fn run_program(
+    runner: &dyn CommandRunner,
+    program: &str,
+    args: &[&str],
+) -> io::Result<std::process::Output> {
+    let args: Vec<&OsStr> = args.iter().map(OsStr::new).collect();
+    runner.run(program, &args, Some(DEFAULT_COMMAND_TIMEOUT))
+}
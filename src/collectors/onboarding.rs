@@ -0,0 +1,79 @@
+//! What a project without a config file looks like, for the onboarding
+//! section
+//!
+//! Without `contextkeeper.toml` most collectors have nothing to go on, and
+//! the context comes back nearly empty. This collector says what was
+//! found anyway (the project type, the repositories, a container runtime,
+//! the build entry point and target configs `init` would pick), so the
+//! output can say what a config would add and how to write one: `init`,
+//! or the `bootstrap_config` tool with the same detected values.
+
+use crate::collectors::detect::detect_project_types;
+use crate::collectors::git::known_repo_dirs;
+use crate::commands::hooks::shell_quote;
+use crate::commands::init::{
+    default_project_types, detect_container_runtime, find_build_candidates,
+    find_config_dir_candidates,
+};
+use crate::config::ProjectContext;
+use crate::paths::{display_path, project_relative};
+use serde::{Deserialize, Serialize};
+
+/// Repositories named in the onboarding section; the rest are counted
+pub const MAX_ONBOARDING_REPOS: usize = 5;
+
+/// What was detected in a project that has no config file
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Onboarding {
+    /// Project types `init` would propose
+    pub project_types: Vec<String>,
+    /// Git repositories, relative to the project root
+    pub git_repos: Vec<String>,
+    /// `podman` or `docker`, when installed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container_runtime: Option<String>,
+    /// Best build entry point: a script, Makefile, justfile or package.json
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_entry_point: Option<String>,
+    /// Directory with the most target `*.conf` files, and how many
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_dir: Option<(String, usize)>,
+    /// Writes a config from these values
+    pub init_command: String,
+}
+
+/// The onboarding facts for `project`, or `None` when it has a config file
+/// (a broken one included: `check` explains that)
+pub fn collect_onboarding(project: &ProjectContext) -> Option<Onboarding> {
+    if project.config_path.is_some() {
+        return None;
+    }
+    let root = &project.root;
+    let project_types = default_project_types(&detect_project_types(root, &project.ignore));
+    // `init --yes` needs a type; "custom" is what it offers for none
+    let type_flag = if project_types.is_empty() {
+        " --type custom"
+    } else {
+        ""
+    };
+    Some(Onboarding {
+        init_command: format!(
+            "context-keeper --cwd {} init --yes{}",
+            shell_quote(&display_path(root)),
+            type_flag
+        ),
+        project_types,
+        git_repos: known_repo_dirs(project)
+            .iter()
+            .map(|dir| project_relative(root, dir))
+            .collect(),
+        container_runtime: detect_container_runtime().map(str::to_string),
+        build_entry_point: find_build_candidates(root)
+            .into_iter()
+            .next()
+            .map(|candidate| candidate.path),
+        config_dir: find_config_dir_candidates(root, &project.ignore)
+            .into_iter()
+            .next(),
+    })
+}
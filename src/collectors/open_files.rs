@@ -0,0 +1,207 @@
+//! Files open in the editor, as the editor itself reported them
+//!
+//! There is no way to ask an editor from outside, so a small extension, an
+//! editor hook or `context-keeper open-files set` writes the list to the
+//! project's `open-files.json`:
+//!
+//! ```json
+//! {
+//!   "updated_at": "2026-10-14T09:12:00Z",
+//!   "editor": "vscode",
+//!   "files": [
+//!     { "path": "src/hal/audio.cpp", "line": 212, "focused_at": "2026-10-14T09:12:00Z" },
+//!     { "path": "/home/me/aosp/include/audio.h" }
+//!   ]
+//! }
+//! ```
+//!
+//! Paths are relative to the project root or absolute; files outside the
+//! root are dropped. `line` is the 1-based cursor line, and the file with
+//! the newest `focused_at` is the focused one. A list last updated more
+//! than `[editor] max_age_secs` ago (by `updated_at`, else the file's
+//! modification time) is ignored, since the editor has likely moved on.
+
+use crate::config::{Config, ProjectContext};
+use crate::error::ContextKeeperError;
+use crate::paths::{project_relative, OUTSIDE_ROOT_MARKER};
+use crate::sessions::write_atomic;
+use crate::state::project_state_dir;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// How old the list may get, without `[editor] max_age_secs`
+pub const DEFAULT_OPEN_FILES_MAX_AGE_SECS: u64 = 4 * 3600;
+
+/// One file open in the editor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenFile {
+    /// Relative to the project root once loaded
+    pub path: String,
+    /// 1-based cursor line
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    /// When the file last had focus, RFC 3339
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focused_at: Option<String>,
+}
+
+impl OpenFile {
+    /// "src/hal/audio.cpp:212", or just the path without a cursor line
+    pub fn location(&self) -> String {
+        match self.line {
+            Some(line) => format!("{}:{}", self.path, line),
+            None => self.path.clone(),
+        }
+    }
+}
+
+/// The contents of `open-files.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OpenFiles {
+    /// When the list was written, RFC 3339; the file's modification time
+    /// when the writer left it out
+    #[serde(default)]
+    pub updated_at: String,
+    /// Which editor wrote it, e.g. "vscode" or "nvim"
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub editor: String,
+    /// Focused file first once loaded, then the most recently focused
+    #[serde(default)]
+    pub files: Vec<OpenFile>,
+}
+
+impl OpenFiles {
+    /// The file that had focus last, when the writer said which
+    pub fn focused(&self) -> Option<&OpenFile> {
+        self.files.first().filter(|file| file.focused_at.is_some())
+    }
+}
+
+/// `~/.contextkeeper/projects/<root-hash>/open-files.json`
+pub fn open_files_path(root: &Path) -> PathBuf {
+    project_state_dir(root).join("open-files.json")
+}
+
+/// `[editor] max_age_secs`
+pub fn open_files_max_age(config: &Config) -> u64 {
+    config
+        .editor
+        .as_ref()
+        .and_then(|e| e.max_age_secs)
+        .unwrap_or(DEFAULT_OPEN_FILES_MAX_AGE_SECS)
+}
+
+/// `path` relative to the project root, or `None` when it is empty, the
+/// root itself or outside it
+pub fn normalize_open_file_path(root: &Path, path: &str) -> Option<String> {
+    let path = path.trim();
+    if path.is_empty() {
+        return None;
+    }
+    let relative = project_relative(root, Path::new(path));
+    if relative == "." || relative.ends_with(OUTSIDE_ROOT_MARKER) {
+        return None;
+    }
+    Some(relative)
+}
+
+/// Paths made relative and checked, duplicates and line 0 dropped,
+/// unparsable focus times cleared, most recently focused first
+pub fn normalize_open_files(root: &Path, files: Vec<OpenFile>) -> Vec<OpenFile> {
+    let mut out: Vec<OpenFile> = Vec::new();
+    for file in files {
+        let Some(path) = normalize_open_file_path(root, &file.path) else {
+            tracing::debug!(path = %file.path, "open file outside the project root dropped");
+            continue;
+        };
+        if out.iter().any(|f| f.path == path) {
+            continue;
+        }
+        let focused_at = file
+            .focused_at
+            .filter(|at| chrono::DateTime::parse_from_rfc3339(at).is_ok());
+        out.push(OpenFile {
+            path,
+            line: file.line.filter(|&line| line > 0),
+            focused_at,
+        });
+    }
+    // Stable, so unfocused files keep the editor's order
+    out.sort_by_key(|file| {
+        std::cmp::Reverse(
+            file.focused_at
+                .as_deref()
+                .and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok()),
+        )
+    });
+    out
+}
+
+/// Write the list for `root`, replacing the previous one
+pub fn save_open_files(root: &Path, open_files: &OpenFiles) -> Result<(), ContextKeeperError> {
+    let path = open_files_path(root);
+    let json = serde_json::to_string_pretty(open_files).map_err(io::Error::other);
+    json.and_then(|json| {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_atomic(&path, json.as_bytes())
+    })
+    .map_err(|e| ContextKeeperError::state_io(&path, e))
+}
+
+/// `collect_open_files` for a saved work state: its files, or none when
+/// the list is missing, stale or unreadable
+pub fn open_files_now(project: &ProjectContext) -> Vec<OpenFile> {
+    match collect_open_files(project) {
+        Ok(open_files) => open_files.map(|o| o.files).unwrap_or_default(),
+        Err(e) => {
+            tracing::debug!(error = %e, "open files not recorded");
+            Vec::new()
+        }
+    }
+}
+
+/// The files open in the editor, focused first. `None` when nothing wrote
+/// the list, it is older than `[editor] max_age_secs`, or none of its
+/// files are under the project root.
+pub fn collect_open_files(
+    project: &ProjectContext,
+) -> Result<Option<OpenFiles>, ContextKeeperError> {
+    let path = open_files_path(&project.root);
+    let json = match fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(ContextKeeperError::state_io(&path, e)),
+    };
+    let mut open_files: OpenFiles =
+        serde_json::from_str(&json).map_err(|e| ContextKeeperError::Collector {
+            collector: "open_files",
+            message: format!("{}: {}", path.display(), e),
+        })?;
+
+    let updated_at = chrono::DateTime::parse_from_rfc3339(&open_files.updated_at)
+        .map(|at| at.with_timezone(&chrono::Utc))
+        .ok()
+        .or_else(|| {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+            Some(chrono::DateTime::<chrono::Utc>::from(modified))
+        });
+    let Some(updated_at) = updated_at else {
+        return Ok(None);
+    };
+    let age = (chrono::Utc::now() - updated_at).num_seconds();
+    if age > i64::try_from(open_files_max_age(&project.config)).unwrap_or(i64::MAX) {
+        tracing::debug!(age_secs = age, "open-files.json is stale, ignored");
+        return Ok(None);
+    }
+    open_files.updated_at = updated_at.to_rfc3339();
+
+    open_files.files = normalize_open_files(&project.root, open_files.files);
+    if open_files.files.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(open_files))
+}
@@ -0,0 +1,134 @@
+//! Package manifests: package.json scripts and Cargo workspaces
+
+use crate::collectors::{collector_warning, run_command_timeout};
+use crate::config::ProjectContext;
+use crate::error::ContextKeeperError;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// Deadline for `cargo metadata`, which may resolve a large workspace
+pub const CARGO_METADATA_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether `project.type` names `project_type` (alone or in its list)
+pub fn project_type_is(project: &ProjectContext, project_type: &str) -> bool {
+    project
+        .config
+        .project
+        .as_ref()
+        .and_then(|p| p.project_type.as_ref())
+        .is_some_and(|types| types.to_vec().contains(&project_type))
+}
+
+/// Package manager implied by the lockfile next to package.json
+pub fn detect_package_manager(root: &Path) -> &'static str {
+    [
+        ("pnpm-lock.yaml", "pnpm"),
+        ("yarn.lock", "yarn"),
+        ("bun.lockb", "bun"),
+        ("bun.lock", "bun"),
+        ("package-lock.json", "npm"),
+    ]
+    .iter()
+    .find(|(lockfile, _)| root.join(lockfile).exists())
+    .map_or("npm", |(_, manager)| manager)
+}
+
+/// `<manager> run <script>` for each script in package.json
+pub fn collect_package_scripts(project: &ProjectContext) -> Vec<String> {
+    let manifest = project.root.join("package.json");
+    if !manifest.is_file() && !project_type_is(project, "node") {
+        return Vec::new();
+    }
+    let Ok(content) = fs::read_to_string(&manifest) else {
+        return Vec::new();
+    };
+
+    let package: serde_json::Value = match serde_json::from_str(&content) {
+        Ok(package) => package,
+        Err(e) => {
+            collector_warning(format!("package.json: {}", e));
+            return Vec::new();
+        }
+    };
+    let manager = detect_package_manager(&project.root);
+    package
+        .get("scripts")
+        .and_then(|s| s.as_object())
+        .map(|scripts| {
+            scripts
+                .keys()
+                .map(|name| format!("{} run {}", manager, name))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CargoPackage {
+    pub name: String,
+    pub version: String,
+    pub binaries: Vec<String>,
+    pub default_run: Option<String>,
+}
+
+/// Members of the cargo workspace at the project root
+pub fn collect_cargo_workspace(
+    project: &ProjectContext,
+) -> Result<Vec<CargoPackage>, ContextKeeperError> {
+    if !project.root.join("Cargo.toml").is_file() && !project_type_is(project, "cargo") {
+        return Ok(Vec::new());
+    }
+    let error = |message: String| ContextKeeperError::Collector {
+        collector: "cargo",
+        message,
+    };
+
+    let output = match run_command_timeout(
+        std::process::Command::new("cargo")
+            .args(["metadata", "--no-deps", "--format-version", "1"])
+            .current_dir(&project.root),
+        CARGO_METADATA_TIMEOUT,
+    ) {
+        Ok(output) => output,
+        // Not installed: run_command_timeout already warned
+        Err(_) => return Ok(Vec::new()),
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+        return Err(error(format!("cargo metadata failed: {}", reason.trim())));
+    }
+
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| error(format!("cargo metadata: {}", e)))?;
+    let members: Vec<&str> = metadata["workspace_members"]
+        .as_array()
+        .map(|ids| ids.iter().filter_map(|id| id.as_str()).collect())
+        .unwrap_or_default();
+
+    let empty = Vec::new();
+    Ok(metadata["packages"]
+        .as_array()
+        .unwrap_or(&empty)
+        .iter()
+        .filter(|p| p["id"].as_str().is_some_and(|id| members.contains(&id)))
+        .map(|p| CargoPackage {
+            name: p["name"].as_str().unwrap_or_default().to_string(),
+            version: p["version"].as_str().unwrap_or_default().to_string(),
+            binaries: p["targets"]
+                .as_array()
+                .unwrap_or(&empty)
+                .iter()
+                .filter(|t| {
+                    t["kind"]
+                        .as_array()
+                        .is_some_and(|kinds| kinds.iter().any(|k| k == "bin"))
+                })
+                .filter_map(|t| t["name"].as_str().map(str::to_string))
+                .collect(),
+            default_run: p["default_run"].as_str().map(str::to_string),
+        })
+        .collect())
+}
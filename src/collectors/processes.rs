@@ -0,0 +1,363 @@
+//! Builds running on the host: `soong_ui`, `ninja`, `bitbake` and the like
+//!
+//! The process table is read through sysinfo. A process counts when its
+//! command matches one of the patterns (the project type's preset, or
+//! `[processes] patterns`) and it runs under the project root, or inside
+//! a container one of the build targets uses. Of a matching process tree
+//! only the outermost process is reported. Entries the user may not read
+//! (another user's cwd or command line) are left out without a warning.
+
+use crate::collectors::containers::container_runtime;
+use crate::collectors::git::write_state_file;
+use crate::collectors::targets::collect_build_targets;
+use crate::collectors::{collector_unavailable, collector_warning, run_program};
+use crate::config::ProjectContext;
+use crate::paths::display_path;
+use crate::state::project_state_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+
+/// Limit on reading the process table
+pub const PROCESS_SCAN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Patterns for a project without a type or a preset
+pub const DEFAULT_BUILD_PATTERNS: &[&str] =
+    &["soong_ui", "ninja", "bitbake", "colcon", "cargo build"];
+
+/// Build commands per project type
+pub const BUILD_PATTERN_PRESETS: &[(&str, &[&str])] = &[
+    ("aosp", &["soong_ui", "soong_build", "ninja", "kati"]),
+    ("yocto", &["bitbake"]),
+    ("ros", &["colcon"]),
+    ("zephyr", &["west build", "ninja"]),
+    ("buildroot", &["make"]),
+    ("kernel", &["make"]),
+    ("cargo", &["cargo build", "cargo test"]),
+];
+
+/// Command lines longer than this are cut
+const MAX_CMDLINE: usize = 200;
+
+/// A build process found by `collect_build_processes`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildProcess {
+    pub pid: u32,
+    /// The pattern that matched, e.g. "ninja" or "cargo build"
+    pub command: String,
+    /// Full command line, cut at 200 characters
+    pub cmdline: String,
+    /// Relative to the project root; as the process sees it in a container
+    pub cwd: String,
+    /// Container the process runs in, by name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    /// RFC 3339
+    pub started_at: String,
+    /// Over a short sample; 100 is one core
+    pub cpu_percent: f32,
+}
+
+/// The builds from the last `collect_build_processes`, saved in
+/// `processes.json` for `context-keeper statusline`, which never reads the
+/// process table
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BuildSnapshot {
+    pub saved_at: String,
+    pub builds: Vec<BuildProcess>,
+}
+
+pub fn builds_snapshot_path(root: &Path) -> PathBuf {
+    project_state_dir(root).join("processes.json")
+}
+
+pub fn load_builds_snapshot(root: &Path) -> Option<BuildSnapshot> {
+    let json = fs::read_to_string(builds_snapshot_path(root)).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// `[processes] patterns`, else the presets of the configured types,
+/// else `DEFAULT_BUILD_PATTERNS`
+pub fn build_patterns(project: &ProjectContext) -> Vec<String> {
+    if let Some(patterns) = project
+        .config
+        .processes
+        .as_ref()
+        .and_then(|p| p.patterns.clone())
+    {
+        return patterns;
+    }
+    let types = project
+        .config
+        .project
+        .as_ref()
+        .and_then(|p| p.project_type.as_ref())
+        .map(|types| types.to_vec())
+        .unwrap_or_default();
+    let mut patterns: Vec<String> = Vec::new();
+    for (_, preset) in BUILD_PATTERN_PRESETS
+        .iter()
+        .filter(|(name, _)| types.contains(name))
+    {
+        for pattern in *preset {
+            if !patterns.iter().any(|p| p == pattern) {
+                patterns.push(pattern.to_string());
+            }
+        }
+    }
+    if patterns.is_empty() {
+        patterns = DEFAULT_BUILD_PATTERNS
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+    }
+    patterns
+}
+
+/// The pattern `words` (a command line, program names without their
+/// directory) matches: its words in a row, from the program or from the
+/// first argument as for `python3 /usr/bin/bitbake`
+pub fn matching_pattern<'a>(words: &[String], patterns: &'a [String]) -> Option<&'a str> {
+    patterns.iter().map(String::as_str).find(|pattern| {
+        let wanted: Vec<&str> = pattern.split_whitespace().collect();
+        !wanted.is_empty()
+            && (0..2).any(|start| {
+                words.len() >= start + wanted.len()
+                    && words[start..start + wanted.len()]
+                        .iter()
+                        .zip(&wanted)
+                        .all(|(word, want)| word == want)
+            })
+    })
+}
+
+/// Full id of the container a `/proc/<pid>/cgroup` places the process in
+pub fn cgroup_container_id(cgroup: &str) -> Option<String> {
+    cgroup
+        .split(|c: char| !c.is_ascii_hexdigit())
+        .find(|token| token.len() == 64)
+        .map(str::to_string)
+}
+
+/// A process as read from the table, before the container check
+struct Candidate {
+    pid: Pid,
+    command: String,
+    cmdline: String,
+    cwd: String,
+    under_root: bool,
+    container_id: Option<String>,
+    start_time: u64,
+    cpu_percent: f32,
+}
+
+/// Read the process table and keep matching processes. Runs on a thread
+/// of its own, see `PROCESS_SCAN_TIMEOUT`.
+fn scan_processes(root: &Path, patterns: &[String]) -> Vec<Candidate> {
+    let kind = ProcessRefreshKind::nothing()
+        .with_cmd(UpdateKind::OnlyIfNotSet)
+        .with_cwd(UpdateKind::OnlyIfNotSet)
+        .with_cpu();
+    let mut system = System::new();
+    system.refresh_processes_specifics(ProcessesToUpdate::All, true, kind);
+
+    let words_of = |process: &sysinfo::Process| -> Vec<String> {
+        process
+            .cmd()
+            .iter()
+            .enumerate()
+            .map(|(index, arg)| {
+                let arg = arg.to_string_lossy();
+                // Program and interpreted script by file name
+                match index {
+                    0 | 1 => Path::new(arg.as_ref())
+                        .file_name()
+                        .map_or(arg.to_string(), |name| name.to_string_lossy().to_string()),
+                    _ => arg.to_string(),
+                }
+            })
+            .collect()
+    };
+    let matched: HashMap<Pid, &str> = system
+        .processes()
+        .iter()
+        .filter_map(|(pid, process)| Some((*pid, matching_pattern(&words_of(process), patterns)?)))
+        .collect();
+    if matched.is_empty() {
+        return Vec::new();
+    }
+
+    // CPU use is the difference between two samples
+    std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+    let pids: Vec<Pid> = matched.keys().copied().collect();
+    system.refresh_processes_specifics(ProcessesToUpdate::Some(&pids), true, kind);
+
+    let own = sysinfo::get_current_pid().ok();
+    let mut candidates = Vec::new();
+    for (pid, command) in &matched {
+        let Some(process) = system.process(*pid) else {
+            continue;
+        };
+        // ninja under soong_ui is the same build
+        let mut parent = process.parent();
+        let mut nested = false;
+        while let Some(ppid) = parent {
+            if matched.contains_key(&ppid) {
+                nested = true;
+                break;
+            }
+            parent = system.process(ppid).and_then(|p| p.parent());
+        }
+        if nested || Some(*pid) == own {
+            continue;
+        }
+        let container_id = std::fs::read_to_string(format!("/proc/{}/cgroup", pid))
+            .ok()
+            .and_then(|cgroup| cgroup_container_id(&cgroup));
+        let (cwd, under_root) = match process.cwd() {
+            Some(cwd) => match cwd.strip_prefix(root) {
+                Ok(relative) if relative.as_os_str().is_empty() => (".".to_string(), true),
+                Ok(relative) => (display_path(relative), true),
+                // A path inside a container, as the container sees it
+                Err(_) if container_id.is_some() => (display_path(cwd), false),
+                Err(_) => continue,
+            },
+            // Unreadable: another user's process, unless containerized
+            None if container_id.is_some() => (String::new(), false),
+            None => continue,
+        };
+        let cmdline = process
+            .cmd()
+            .iter()
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ");
+        candidates.push(Candidate {
+            pid: *pid,
+            command: command.to_string(),
+            cmdline: match cmdline.char_indices().nth(MAX_CMDLINE) {
+                Some((cut, _)) => format!("{}…", &cmdline[..cut]),
+                None => cmdline,
+            },
+            cwd,
+            under_root,
+            container_id,
+            start_time: process.start_time(),
+            cpu_percent: process.cpu_usage(),
+        });
+    }
+    candidates.sort_by_key(|c| c.start_time);
+    candidates
+}
+
+/// Names of running containers by full id, through `runtime ps`
+fn container_names(project: &ProjectContext) -> HashMap<String, String> {
+    let runtime = container_runtime(project);
+    let Ok(output) = run_program(
+        &*project.runner,
+        runtime,
+        &["ps", "--no-trunc", "--format", "{{.ID}}\t{{.Names}}"],
+    ) else {
+        return HashMap::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (id, name) = line.split_once('\t')?;
+            Some((id.trim().to_string(), name.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Build processes of the project, oldest first. Saves them for the
+/// statusline unless the process table couldn't be read.
+pub fn collect_build_processes(project: &ProjectContext) -> Vec<BuildProcess> {
+    let patterns = build_patterns(project);
+    let builds = if patterns.is_empty() {
+        Vec::new()
+    } else {
+        match scan_build_processes(project, patterns) {
+            Some(builds) => builds,
+            None => {
+                collector_unavailable("the process table can't be read".to_string());
+                return Vec::new();
+            }
+        }
+    };
+    write_state_file(
+        &builds_snapshot_path(&project.root),
+        &BuildSnapshot {
+            saved_at: chrono::Utc::now().to_rfc3339(),
+            builds: builds.clone(),
+        },
+    );
+    builds
+}
+
+/// The matching processes, `None` when the process table wasn't read in
+/// time
+fn scan_build_processes(
+    project: &ProjectContext,
+    patterns: Vec<String>,
+) -> Option<Vec<BuildProcess>> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let root = project.root.clone();
+    std::thread::spawn(move || {
+        let _ = tx.send(scan_processes(&root, &patterns));
+    });
+    let candidates = match rx.recv_timeout(PROCESS_SCAN_TIMEOUT) {
+        Ok(candidates) => candidates,
+        Err(_) => {
+            collector_warning(format!(
+                "process table not read within {}s",
+                PROCESS_SCAN_TIMEOUT.as_secs()
+            ));
+            return None;
+        }
+    };
+
+    // Outside the root, only the targets' containers count
+    let (names, project_containers) = if candidates.iter().any(|c| c.container_id.is_some()) {
+        let wanted: HashSet<String> = collect_build_targets(project)
+            .into_iter()
+            .map(|target| target.container_name)
+            .filter(|name| !name.is_empty())
+            .collect();
+        (container_names(project), wanted)
+    } else {
+        (HashMap::new(), HashSet::new())
+    };
+
+    let builds = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let container = candidate
+                .container_id
+                .as_ref()
+                .and_then(|id| names.get(id))
+                .cloned();
+            let in_project_container = container
+                .as_ref()
+                .is_some_and(|name| project_containers.contains(name));
+            if !candidate.under_root && !in_project_container {
+                return None;
+            }
+            let started_at = chrono::DateTime::from_timestamp(candidate.start_time as i64, 0)
+                .unwrap_or_default()
+                .to_rfc3339();
+            Some(BuildProcess {
+                pid: candidate.pid.as_u32(),
+                command: candidate.command,
+                cmdline: candidate.cmdline,
+                cwd: candidate.cwd,
+                container,
+                started_at,
+                cpu_percent: candidate.cpu_percent,
+            })
+        })
+        .collect();
+    Some(builds)
+}
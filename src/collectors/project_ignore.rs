@@ -0,0 +1,105 @@
+//! `.contextkeeper-ignore`: what the collectors never look at
+//!
+//! One file at the project root, in gitignore syntax and with the `ignore`
+//! crate's matching (a pattern with a slash is anchored at the root, a
+//! trailing slash matches directories only, `!` re-includes). It is read
+//! with the project, and every collector walking the tree skips what it
+//! matches: git repository discovery, `[recent_files]`, project type
+//! detection (the ROS workspace and Yocto layer listings), the ROS package
+//! walk, the Yocto build directory search, the config directory search of
+//! onboarding and `init`, the code TODO scan and the summary's layout.
+//! Paths set explicitly in the config are read regardless. The walkers'
+//! built-in skips (hidden directories, `node_modules`, `target`, `out`)
+//! still apply on top of it.
+
+use crate::config::file_mtime;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Name of the file, looked for at the project root only
+pub const PROJECT_IGNORE_FILE: &str = ".contextkeeper-ignore";
+
+/// The rules of a project's `.contextkeeper-ignore`, empty without one
+#[derive(Debug, Clone)]
+pub struct ProjectIgnore {
+    root: PathBuf,
+    matcher: Gitignore,
+    /// The file, when the project has one
+    pub path: Option<PathBuf>,
+    pub mtime: Option<SystemTime>,
+    /// Why some or all of its lines could not be used
+    pub error: Option<String>,
+}
+
+impl Default for ProjectIgnore {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::new(),
+            matcher: Gitignore::empty(),
+            path: None,
+            mtime: None,
+            error: None,
+        }
+    }
+}
+
+impl ProjectIgnore {
+    /// Read `root`'s `.contextkeeper-ignore`. Lines that don't parse are
+    /// left out and reported in `error`; the others still apply.
+    pub fn load(root: &Path) -> Self {
+        let path = root.join(PROJECT_IGNORE_FILE);
+        if !path.is_file() {
+            return Self {
+                root: root.to_path_buf(),
+                ..Self::default()
+            };
+        }
+        let mut builder = GitignoreBuilder::new(root);
+        let mut error = builder.add(&path).map(|e| e.to_string());
+        let matcher = builder.build().unwrap_or_else(|e| {
+            error = Some(e.to_string());
+            Gitignore::empty()
+        });
+        if let Some(e) = &error {
+            tracing::warn!(path = %path.display(), error = %e, "ignore file partly unusable");
+        }
+        Self {
+            root: root.to_path_buf(),
+            matcher,
+            mtime: file_mtime(&path),
+            path: Some(path),
+            error,
+        }
+    }
+
+    /// Patterns in use, `!` ones included
+    pub fn rules(&self) -> usize {
+        self.matcher.len()
+    }
+
+    /// Whether `path`, or a directory holding it, is ignored. Paths
+    /// outside the project root, and the root itself, never are.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if self.matcher.is_empty() {
+            return false;
+        }
+        let path = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.root.join(path)
+        };
+        match path.strip_prefix(&self.root) {
+            Ok(relative) if !relative.as_os_str().is_empty() => self
+                .matcher
+                .matched_path_or_any_parents(relative, is_dir)
+                .is_ignore(),
+            _ => false,
+        }
+    }
+
+    /// Whether the file on disk is no longer the one these rules came from
+    pub fn changed(&self) -> bool {
+        file_mtime(&self.root.join(PROJECT_IGNORE_FILE)) != self.mtime
+    }
+}
@@ -0,0 +1,152 @@
+//! Files modified lately under `[recent_files] paths`, which git doesn't
+//! see: device trees a vendor tool syncs, tweaks under `out/`
+//!
+//! Each path is walked with the `ignore` walker. `.ignore` files, hidden
+//! entries and `.contextkeeper-ignore` are honored, but `.gitignore`
+//! isn't: the listed paths are often ignored by git, which is why they
+//! are listed. The walk stops after
+//! `MAX_RECENT_WALK_ENTRIES` entries or `RECENT_WALK_TIMEOUT`, whichever
+//! comes first. Files that `git status` already shows in the repository
+//! holding them are left to the git section.
+
+use crate::collectors::working_files::{changed_paths, holding_repo};
+use crate::collectors::{collector_capped, collector_disabled};
+use crate::config::{Config, ProjectContext};
+use crate::limits::{format_count, Limits};
+use crate::paths::{display_path, project_relative};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Files modified this recently count (default `max_age_minutes`)
+pub const DEFAULT_RECENT_MAX_AGE_MINUTES: u64 = 240;
+
+/// Files reported at most (default `max_results`)
+pub const DEFAULT_RECENT_MAX_RESULTS: usize = 20;
+
+/// Entries visited at most, over all paths
+pub const MAX_RECENT_WALK_ENTRIES: usize = 50_000;
+
+/// Time the walk may take, over all paths
+pub const RECENT_WALK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A file modified within `max_age_minutes`, outside what git reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentFile {
+    /// In `project_relative` form
+    pub path: String,
+    /// RFC 3339
+    pub modified_at: String,
+}
+
+/// `[recent_files] paths`, empty without them
+pub fn recent_file_paths(config: &Config) -> &[String] {
+    config
+        .recent_files
+        .as_ref()
+        .and_then(|r| r.paths.as_deref())
+        .unwrap_or_default()
+}
+
+/// Files under `[recent_files] paths` modified within `max_age_minutes`,
+/// newest first, at most `max_results`. Empty, and reported as turned
+/// off, without paths.
+pub fn collect_recent_files(project: &ProjectContext) -> Vec<RecentFile> {
+    let paths = recent_file_paths(&project.config);
+    if paths.is_empty() {
+        collector_disabled();
+        return Vec::new();
+    }
+    let settings = project.config.recent_files.as_ref();
+    let max_age = settings
+        .and_then(|r| r.max_age_minutes)
+        .unwrap_or(DEFAULT_RECENT_MAX_AGE_MINUTES);
+    let max_results = settings
+        .and_then(|r| r.max_results)
+        .unwrap_or(DEFAULT_RECENT_MAX_RESULTS);
+    let cutoff = SystemTime::now()
+        .checked_sub(Duration::from_secs(max_age.saturating_mul(60)))
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+
+    let started = Instant::now();
+    let mut visited = 0;
+    let mut stopped = None;
+    let mut found: Vec<(SystemTime, PathBuf)> = Vec::new();
+    'paths: for dir in paths {
+        let skip = project.ignore.clone();
+        let walker = ignore::WalkBuilder::new(project.resolve(dir))
+            .git_ignore(false)
+            .git_exclude(false)
+            .git_global(false)
+            .filter_entry(move |entry| {
+                !skip.is_ignored(entry.path(), entry.file_type().is_some_and(|t| t.is_dir()))
+            })
+            .build();
+        for entry in walker.flatten() {
+            visited += 1;
+            if visited > MAX_RECENT_WALK_ENTRIES {
+                stopped = Some(format!("{} entries", format_count(MAX_RECENT_WALK_ENTRIES)));
+                break 'paths;
+            }
+            if started.elapsed() > RECENT_WALK_TIMEOUT {
+                stopped = Some(format!("{}s", RECENT_WALK_TIMEOUT.as_secs()));
+                break 'paths;
+            }
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let Some(modified) = entry.metadata().ok().and_then(|m| m.modified().ok()) else {
+                continue;
+            };
+            if modified >= cutoff {
+                found.push((modified, entry.into_path()));
+            }
+        }
+    }
+    if let Some(limit) = stopped {
+        collector_capped(format!(
+            "recent_files: walk stopped after {}; files past it not checked",
+            limit
+        ));
+    }
+
+    // What git shows already, one `git status` per repository holding a file
+    let max_paths = Limits::from_config(&project.config).max_status_lines;
+    let mut changed: HashMap<PathBuf, HashSet<String>> = HashMap::new();
+    found.retain(|(_, path)| {
+        let Some(repo) = holding_repo(&project.root, path) else {
+            return true;
+        };
+        let in_repo = display_path(path.strip_prefix(&repo).unwrap_or(path));
+        let listed = changed.entry(repo.clone()).or_insert_with(|| {
+            changed_paths(&*project.runner, &repo, max_paths)
+                .map(|(paths, _)| paths)
+                .unwrap_or_default()
+        });
+        !(listed.contains(&in_repo)
+            || listed
+                .iter()
+                .any(|dir| dir.ends_with('/') && in_repo.starts_with(dir.as_str())))
+    });
+
+    found.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    let mut seen = HashSet::new();
+    found.retain(|(_, path)| seen.insert(path.clone()));
+    if found.len() > max_results {
+        let more = found.len() - max_results;
+        collector_capped(format!(
+            "recent_files: …and {} more modified {} (over [recent_files] max_results)",
+            format_count(more),
+            if more == 1 { "file" } else { "files" }
+        ));
+        found.truncate(max_results);
+    }
+    found
+        .into_iter()
+        .map(|(modified, path)| RecentFile {
+            path: project_relative(&project.root, &path),
+            modified_at: chrono::DateTime::<chrono::Utc>::from(modified).to_rfc3339(),
+        })
+        .collect()
+}
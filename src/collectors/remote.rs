@@ -0,0 +1,202 @@
+//! Context from a remote build server, with `[remote]`
+//!
+//! Runs `context-keeper context --json --section ...` on the host when it
+//! is installed there; otherwise runs git and the container runtime over
+//! the same connection (no build targets then, as those come from files
+//! on the host). ssh always runs with `BatchMode=yes`, so a missing key
+//! fails instead of asking for a password, and shares one control
+//! connection between calls.
+
+use crate::collectors::containers::{container_runtime, list_containers};
+use crate::collectors::git::{collect_git_info_for_path, StatusOptions};
+use crate::collectors::{
+    collector_warning, run_program, BuildTarget, CommandRunner, ContainerInfo, GitInfo,
+    SystemRunner,
+};
+use crate::commands::hooks::shell_quote;
+use crate::config::{GitBackend, ProjectContext, RemoteConfig};
+use crate::error::EXIT_PARTIAL;
+use crate::format::Section;
+use crate::state::contextkeeper_dir;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// Default for `remote.timeout_ms`
+pub const DEFAULT_REMOTE_TIMEOUT_MS: u64 = 5000;
+
+/// Sections that can be collected remotely, the default `remote.sections`
+pub const REMOTE_SECTIONS: [Section; 3] = [Section::Git, Section::Containers, Section::Targets];
+
+/// What the remote host reported, shown in its sections as "(remote: host)"
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RemoteContext {
+    pub host: String,
+    /// "context-keeper" when the host ran it, "ssh" for the raw commands
+    pub via: String,
+    pub git_repos: Vec<GitInfo>,
+    pub containers: Vec<ContainerInfo>,
+    pub targets: Vec<BuildTarget>,
+}
+
+impl RemoteContext {
+    pub fn item_count(&self) -> usize {
+        self.git_repos.len() + self.containers.len() + self.targets.len()
+    }
+}
+
+/// Runs commands on the remote host through ssh
+#[derive(Debug)]
+pub struct SshRunner {
+    /// Options and destination, before the remote command
+    args: Vec<String>,
+    timeout: Duration,
+}
+
+impl SshRunner {
+    pub fn new(config: &RemoteConfig) -> Self {
+        let timeout = Duration::from_millis(config.timeout_ms.unwrap_or(DEFAULT_REMOTE_TIMEOUT_MS));
+        // Best effort: without the directory ssh just skips multiplexing
+        let _ = std::fs::create_dir_all(contextkeeper_dir());
+        let control_path = contextkeeper_dir().join("ssh-%C");
+        let mut args: Vec<String> = [
+            "BatchMode=yes".to_string(),
+            format!("ConnectTimeout={}", timeout.as_secs().max(1)),
+            "ControlMaster=auto".to_string(),
+            format!("ControlPath={}", control_path.display()),
+            "ControlPersist=60".to_string(),
+        ]
+        .into_iter()
+        .flat_map(|option| ["-o".to_string(), option])
+        .collect();
+        args.extend(config.ssh_args.iter().flatten().cloned());
+        args.push(match &config.user {
+            Some(user) => format!("{}@{}", user, config.host),
+            None => config.host.clone(),
+        });
+        SshRunner { args, timeout }
+    }
+}
+
+impl CommandRunner for SshRunner {
+    fn run(
+        &self,
+        program: &str,
+        args: &[&OsStr],
+        timeout: Option<Duration>,
+    ) -> io::Result<std::process::Output> {
+        // ssh hands the remote shell one string
+        let remote_command: Vec<String> = std::iter::once(shell_quote(program))
+            .chain(args.iter().map(|arg| shell_quote(&arg.to_string_lossy())))
+            .collect();
+        let remote_command = remote_command.join(" ");
+        let mut ssh_args: Vec<&OsStr> = self.args.iter().map(OsStr::new).collect();
+        ssh_args.push(OsStr::new("--"));
+        ssh_args.push(OsStr::new(&remote_command));
+        // Never unbounded: a hung connection must not hold the collection
+        let timeout = self.timeout.max(timeout.unwrap_or_default());
+        SystemRunner.run("ssh", &ssh_args, Some(timeout))
+    }
+
+    fn has_program(&self, program: &str) -> bool {
+        let check = format!("command -v {}", shell_quote(program));
+        run_program(self, "sh", &["-c", &check]).is_ok_and(|output| output.status.success())
+    }
+}
+
+/// `remote.sections` that can be collected remotely
+pub fn remote_sections(config: &RemoteConfig) -> Vec<Section> {
+    match &config.sections {
+        Some(names) => names
+            .iter()
+            .filter_map(|name| Section::parse(name))
+            .filter(|section| REMOTE_SECTIONS.contains(section))
+            .collect(),
+        None => REMOTE_SECTIONS.to_vec(),
+    }
+}
+
+/// First stderr line, for the one warning a failed connection gets
+fn stderr_line(output: &std::process::Output) -> String {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    stderr
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("failed")
+        .trim()
+        .to_string()
+}
+
+/// The remote host's sections, or `None` without `[remote]` or when the
+/// host can't be reached (one warning, no password prompt)
+pub fn collect_remote_context(project: &ProjectContext) -> Option<RemoteContext> {
+    let config = project.config.remote.as_ref()?;
+    let sections = remote_sections(config);
+    if sections.is_empty() {
+        return None;
+    }
+    let ssh = SshRunner::new(config);
+    let root = config.remote_root.as_deref().unwrap_or(".");
+    let names: Vec<&str> = sections.iter().map(Section::name).collect();
+    let mut remote = RemoteContext {
+        host: config.host.clone(),
+        ..Default::default()
+    };
+
+    // Spawn errors and timeouts were already reported by `record_command`
+    let output = run_program(
+        &ssh,
+        "context-keeper",
+        &[
+            "--cwd",
+            root,
+            "context",
+            "--json",
+            "--section",
+            &names.join(","),
+        ],
+    )
+    .ok()?;
+    match output.status.code() {
+        Some(0) | Some(EXIT_PARTIAL) => {
+            let Ok(value) = serde_json::from_slice::<serde_json::Value>(&output.stdout) else {
+                collector_warning(format!(
+                    "remote {}: unexpected context-keeper output",
+                    config.host
+                ));
+                return None;
+            };
+            let section = |name: &str| value.get(name).cloned().unwrap_or_default();
+            remote.via = "context-keeper".to_string();
+            remote.git_repos = serde_json::from_value(section("git")).unwrap_or_default();
+            remote.containers = serde_json::from_value(section("containers")).unwrap_or_default();
+            remote.targets = serde_json::from_value(section("targets")).unwrap_or_default();
+        }
+        // Not installed (or not on the non-login PATH): raw commands
+        Some(127) => {
+            remote.via = "ssh".to_string();
+            if sections.contains(&Section::Git) {
+                // gix can't read a repository over ssh
+                let options = StatusOptions {
+                    backend: GitBackend::Cli,
+                    ..StatusOptions::from_config(&project.config)
+                };
+                if let Some(mut info) = collect_git_info_for_path(&ssh, Path::new(root), &options) {
+                    info.repo_path = ".".to_string();
+                    remote.git_repos.push(info);
+                }
+            }
+            if sections.contains(&Section::Containers) {
+                remote.containers = list_containers(&ssh, container_runtime(project));
+            }
+        }
+        // 255 is ssh's own failure: unreachable host, refused key
+        _ => {
+            collector_warning(format!("remote {}: {}", config.host, stderr_line(&output)));
+            return None;
+        }
+    }
+    Some(remote)
+}
@@ -0,0 +1,317 @@
+//! Which repository owns a path, for the `resolve_path` tool
+//!
+//! The owner is the repository the git collector knows about (the root,
+//! `git.paths` or the cached auto-detected list) that holds the path, or a
+//! repository nested in one of those, such as a submodule, when the path
+//! is inside it. The nested one is found by looking for a `.git` from the
+//! path up to the known repository, since the repo walk never enters a
+//! repository. Paths are grouped by owner and each owner gets one
+//! `git status`, which also gives its branch.
+
+use crate::collectors::git::{git_in, known_repo_dirs, parse_porcelain_v2, StatusOptions};
+use crate::collectors::working_files::describe_status;
+use crate::config::ProjectContext;
+use crate::error::ContextKeeperError;
+use crate::paths::{display_path, project_relative, strip_outside_marker, OUTSIDE_ROOT_MARKER};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Most paths one call resolves
+pub const MAX_RESOLVE_PATHS: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathOwnership {
+    /// In a known repository or one nested in it
+    Owned,
+    /// Under the project root, but in no known repository
+    Unowned,
+    /// Outside the project root
+    OutsideProject,
+}
+
+/// One path as `resolve_path` answers it
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedPath {
+    /// As given
+    pub input: String,
+    /// Relative to the project root; absolute when outside it
+    pub path: String,
+    pub ownership: PathOwnership,
+    /// The owning repository, relative to the project root
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repo: Option<String>,
+    /// The known repository `repo` is nested in, when `repo` isn't known
+    /// itself
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nested_in: Option<String>,
+    /// `repo` has a `.git` file: a submodule or a linked worktree
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub submodule: bool,
+    /// `repo`'s branch, or the short commit in parentheses while detached;
+    /// `None` when git failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// `path` relative to `repo`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repo_path: Option<String>,
+    /// Porcelain v2 `XY` code, e.g. `.M`, `A.`, `??` or `!!` (ignored);
+    /// `None` when git lists nothing for the path
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// The status in words, or why there is none
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub state: String,
+}
+
+/// `XY` codes by path relative to the repository, and the repository's
+/// branch, from `git status --porcelain=v2 --branch -z`
+fn parse_status_z(stdout: &[u8]) -> (HashMap<String, String>, String) {
+    let mut codes = HashMap::new();
+    let mut headers = String::new();
+    let mut entries = stdout.split(|&b| b == 0);
+    while let Some(entry) = entries.next() {
+        let entry = String::from_utf8_lossy(entry);
+        // Fields before the path: "1 XY sub mH mI mW hH hI path",
+        // "2 ... X<score> path" (then the source path), "u ... h3 path"
+        let fields = match entry.split_once(' ').map(|(kind, _)| kind) {
+            Some("#") => {
+                headers.push_str(&entry);
+                headers.push('\n');
+                continue;
+            }
+            Some("1") => 9,
+            Some("2") => 10,
+            Some("u") => 11,
+            Some("?" | "!") => {
+                let (kind, path) = entry.split_at(1);
+                codes.insert(path[1..].to_string(), kind.repeat(2));
+                continue;
+            }
+            _ => continue,
+        };
+        let parts: Vec<&str> = entry.splitn(fields, ' ').collect();
+        if let [_, code, .., path] = parts.as_slice() {
+            if parts.len() == fields {
+                codes.insert(path.to_string(), code.to_string());
+            }
+        }
+        if fields == 10 {
+            entries.next();
+        }
+    }
+    let status = parse_porcelain_v2(&headers);
+    let branch = match (status.head, status.oid) {
+        (Some(head), _) => head,
+        (None, Some(oid)) => format!("({})", oid.chars().take(7).collect::<String>()),
+        (None, None) => String::new(),
+    };
+    (codes, branch)
+}
+
+/// The `!!` of the ignored directory holding `key`, or that `key` is:
+/// `--ignored=matching` lists a wholly ignored directory once, as "dir/"
+fn ignored_dir<'a>(codes: &'a HashMap<String, String>, key: &str) -> Option<&'a String> {
+    let itself = format!("{}/", key);
+    let found = key
+        .match_indices('/')
+        .map(|(i, _)| &key[..=i])
+        .chain([itself.as_str()])
+        .find_map(|dir| codes.get(dir).filter(|code| *code == "!!"));
+    found
+}
+
+/// `path` (relative to the project root) resolved to its owning repository,
+/// without git yet
+fn owner(root: &Path, known: &[String], input: &str, path: String) -> ResolvedPath {
+    let mut resolved = ResolvedPath {
+        input: input.to_string(),
+        path,
+        ownership: PathOwnership::Unowned,
+        repo: None,
+        nested_in: None,
+        submodule: false,
+        branch: None,
+        repo_path: None,
+        status: None,
+        state: String::new(),
+    };
+    if resolved.path.ends_with(OUTSIDE_ROOT_MARKER) {
+        resolved.path = display_path(strip_outside_marker(Path::new(&resolved.path)));
+        resolved.ownership = PathOwnership::OutsideProject;
+        return resolved;
+    }
+
+    let contains = |repo: &str| repo == "." || Path::new(&resolved.path).starts_with(repo);
+    // The deepest known repository, in case one is listed inside another
+    let Some(known_repo) = known
+        .iter()
+        .filter(|repo| contains(repo))
+        .max_by_key(|repo| if *repo == "." { 0 } else { repo.len() })
+    else {
+        return resolved;
+    };
+    let known_dir = root.join(known_repo);
+    let full = root.join(&resolved.path);
+    let repo_dir = full
+        .ancestors()
+        .take_while(|dir| dir.starts_with(&known_dir))
+        .find(|dir| dir.join(".git").exists())
+        .unwrap_or(&known_dir);
+
+    let repo = project_relative(root, repo_dir);
+    resolved.submodule = repo_dir.join(".git").is_file();
+    if &repo != known_repo {
+        resolved.nested_in = Some(known_repo.clone());
+    }
+    resolved.repo_path = Some(match full.strip_prefix(repo_dir).map(display_path) {
+        Ok(inner) if !inner.is_empty() => inner,
+        _ => ".".to_string(),
+    });
+    resolved.repo = Some(repo);
+    resolved.ownership = PathOwnership::Owned;
+    resolved
+}
+
+/// Fill in the branch and the status of the paths `indexes` of `resolved`,
+/// which share the repository `repo`, with one `git status`
+fn add_git_status(
+    project: &ProjectContext,
+    repo: &str,
+    indexes: &[usize],
+    resolved: &mut [ResolvedPath],
+) {
+    let dir = project.resolve(repo);
+    let mut args = vec![
+        "--literal-pathspecs",
+        "status",
+        "--porcelain=v2",
+        "--branch",
+        "-z",
+        "--untracked-files=all",
+        "--ignored=matching",
+        "--",
+    ];
+    args.extend(
+        indexes
+            .iter()
+            .filter_map(|&i| resolved[i].repo_path.as_deref()),
+    );
+    let timeout = StatusOptions::from_config(&project.config).timeout;
+    let output = git_in(&*project.runner, &dir, &args, timeout)
+        .ok()
+        .filter(|output| output.status.success());
+    let Some(output) = output else {
+        for &i in indexes {
+            resolved[i].state = "git status unavailable".to_string();
+        }
+        return;
+    };
+
+    // Porcelain paths are relative to the work tree's top, which is above
+    // `dir` when the project root sits inside a repository
+    let top_prefix = if dir.join(".git").exists() {
+        String::new()
+    } else {
+        git_in(
+            &*project.runner,
+            &dir,
+            &["rev-parse", "--show-prefix"],
+            timeout,
+        )
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default()
+    };
+
+    let (codes, branch) = parse_status_z(&output.stdout);
+    for &i in indexes {
+        let entry = &mut resolved[i];
+        entry.branch = Some(branch.clone());
+        let in_repo = entry.repo_path.clone().unwrap_or_default();
+        let (key, inside) = if in_repo == "." {
+            (
+                top_prefix.trim_end_matches('/').to_string(),
+                top_prefix.clone(),
+            )
+        } else {
+            let key = format!("{}{}", top_prefix, in_repo);
+            let inside = format!("{}/", key);
+            (key, inside)
+        };
+        entry.status = codes
+            .get(&key)
+            .or_else(|| ignored_dir(&codes, &key))
+            .cloned();
+        let full = dir.join(&in_repo);
+        entry.state = match entry.status.as_deref() {
+            Some("!!") => "ignored".to_string(),
+            Some(code) => describe_status(&code.replace('.', " ")),
+            None if !full.exists() => "not on disk".to_string(),
+            None if full.is_dir() => {
+                let changed = codes
+                    .iter()
+                    .filter(|(path, code)| path.starts_with(&inside) && *code != "!!")
+                    .count();
+                match changed {
+                    0 => "directory without changes".to_string(),
+                    1 => "directory, 1 changed path inside".to_string(),
+                    n => format!("directory, {} changed paths inside", n),
+                }
+            }
+            None => describe_status("  "),
+        };
+    }
+}
+
+/// Each of `paths` (relative to the project root or absolute) with its
+/// owning repository, branch and git status. Paths outside the root or
+/// in no known repository are answered as such; only an empty list or
+/// more than `MAX_RESOLVE_PATHS` paths are validation errors.
+pub fn resolve_paths(
+    project: &ProjectContext,
+    paths: &[String],
+) -> Result<Vec<ResolvedPath>, ContextKeeperError> {
+    if paths.is_empty() {
+        return Err(ContextKeeperError::Validation(
+            "no paths to resolve".to_string(),
+        ));
+    }
+    if paths.len() > MAX_RESOLVE_PATHS {
+        return Err(ContextKeeperError::Validation(format!(
+            "{} paths given; at most {} can be resolved at once",
+            paths.len(),
+            MAX_RESOLVE_PATHS
+        )));
+    }
+
+    let root = &project.root;
+    let known: Vec<String> = known_repo_dirs(project)
+        .iter()
+        .map(|dir| project_relative(root, dir))
+        .collect();
+    let mut resolved: Vec<ResolvedPath> = paths
+        .iter()
+        .map(|input| {
+            let path = project_relative(root, Path::new(input.trim()));
+            owner(root, &known, input, path)
+        })
+        .collect();
+
+    let mut by_repo: Vec<(String, Vec<usize>)> = Vec::new();
+    for (i, entry) in resolved.iter().enumerate() {
+        let Some(repo) = &entry.repo else {
+            continue;
+        };
+        match by_repo.iter_mut().find(|(r, _)| r == repo) {
+            Some((_, indexes)) => indexes.push(i),
+            None => by_repo.push((repo.clone(), vec![i])),
+        }
+    }
+    for (repo, indexes) in &by_repo {
+        add_git_status(project, repo, indexes, &mut resolved);
+    }
+    Ok(resolved)
+}
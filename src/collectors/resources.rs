@@ -0,0 +1,271 @@
+//! Free disk space for the project's build directories, RAM and load
+//!
+//! Disk space comes from `statvfs` on the project root and the build
+//! output directories (the project type's preset, or `[resources]
+//! paths`), one entry per filesystem. No subprocess runs. A filesystem
+//! reporting no blocks, or more free than total, as network filesystems
+//! sometimes do, is listed without numbers and never warns. Memory and the
+//! load average come from sysinfo.
+
+use crate::collectors::collector_warning;
+use crate::collectors::packages::project_type_is;
+use crate::collectors::yocto::find_yocto_build_dir;
+use crate::config::ProjectContext;
+use crate::paths::display_path;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Limit on the `statvfs` calls together; a hung NFS mount blocks them
+pub const DISK_CHECK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Free space below which to warn, when no preset or `min_free_gb` applies
+pub const DEFAULT_MIN_FREE_GB: u64 = 10;
+
+/// Directories and free space a project type's builds need
+pub struct ResourcePreset {
+    pub project_type: &'static str,
+    /// Relative to the project root; missing ones are skipped
+    pub paths: &'static [&'static str],
+    pub min_free_gb: u64,
+    /// Shown after the warning, e.g. "AOSP full builds need ~300 GB"
+    pub label: &'static str,
+}
+
+pub const RESOURCE_PRESETS: &[ResourcePreset] = &[
+    ResourcePreset {
+        project_type: "aosp",
+        paths: &["out"],
+        min_free_gb: 300,
+        label: "AOSP full builds",
+    },
+    ResourcePreset {
+        project_type: "yocto",
+        paths: &[],
+        min_free_gb: 100,
+        label: "Yocto builds",
+    },
+    ResourcePreset {
+        project_type: "buildroot",
+        paths: &["output"],
+        min_free_gb: 20,
+        label: "Buildroot builds",
+    },
+    ResourcePreset {
+        project_type: "kernel",
+        paths: &[],
+        min_free_gb: 10,
+        label: "Kernel builds",
+    },
+];
+
+/// Paths checked when no preset names any
+pub const DEFAULT_RESOURCE_PATHS: &[&str] = &["out", "build", "tmp"];
+
+/// One filesystem holding a build directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskSpace {
+    /// First checked path on this filesystem, relative to the project root
+    pub path: String,
+    /// Where the filesystem is mounted
+    pub mount: String,
+    /// `None` when the filesystem didn't report believable numbers
+    pub free_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
+}
+
+/// Host resources relevant to a build
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResourceInfo {
+    pub disks: Vec<DiskSpace>,
+    /// Free space to warn below
+    pub min_free_bytes: u64,
+    /// What needs `min_free_bytes`, e.g. "AOSP full builds"; empty for
+    /// the default threshold
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub requirement: String,
+    pub memory_total_bytes: u64,
+    pub memory_available_bytes: u64,
+    /// 1, 5 and 15 minute averages; `None` where the platform has none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_average: Option<[f64; 3]>,
+    pub cpus: usize,
+}
+
+impl ResourceInfo {
+    /// Filesystems with less than `min_free_bytes` free
+    pub fn low_disks(&self) -> impl Iterator<Item = &DiskSpace> {
+        self.disks.iter().filter(|disk| {
+            disk.free_bytes
+                .is_some_and(|free| free < self.min_free_bytes)
+        })
+    }
+}
+
+/// Free and total bytes of the filesystem holding `path`
+#[cfg(unix)]
+fn statvfs(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is NUL-terminated and `stat` is written on success
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+    let fragment = stat.f_frsize as u64;
+    let total = (stat.f_blocks as u64).checked_mul(fragment)?;
+    let free = (stat.f_bavail as u64).checked_mul(fragment)?;
+    // What some network filesystems report
+    if total == 0 || free > total {
+        return None;
+    }
+    Some((free, total))
+}
+
+#[cfg(not(unix))]
+fn statvfs(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+/// Filesystem id of `path`, to tell filesystems apart
+#[cfg(unix)]
+fn device(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    path.metadata().ok().map(|m| m.dev())
+}
+
+#[cfg(not(unix))]
+fn device(path: &Path) -> Option<u64> {
+    path.components().next().map(|c| {
+        c.as_os_str()
+            .to_string_lossy()
+            .bytes()
+            .fold(0, |id, b| id * 31 + u64::from(b))
+    })
+}
+
+/// Outermost ancestor of `path` on the same filesystem
+fn mount_point(path: &Path) -> PathBuf {
+    let dev = device(path);
+    path.ancestors()
+        .take_while(|dir| device(dir) == dev)
+        .last()
+        .unwrap_or(path)
+        .to_path_buf()
+}
+
+/// Directories to check: the root first, then the preset's or the
+/// configured ones that exist
+fn resource_paths(project: &ProjectContext) -> Vec<PathBuf> {
+    let mut paths = vec![project.root.clone()];
+    let configured = project
+        .config
+        .resources
+        .as_ref()
+        .and_then(|r| r.paths.as_ref());
+    match configured {
+        Some(configured) => paths.extend(configured.iter().map(|p| project.resolve(p))),
+        None => {
+            let presets: Vec<&ResourcePreset> = RESOURCE_PRESETS
+                .iter()
+                .filter(|preset| project_type_is(project, preset.project_type))
+                .collect();
+            let preset_paths: Vec<&str> = presets
+                .iter()
+                .flat_map(|p| p.paths.iter().copied())
+                .collect();
+            if presets.iter().any(|p| p.project_type == "yocto") {
+                if let Some(build_dir) = find_yocto_build_dir(project) {
+                    paths.push(build_dir.join("tmp"));
+                    paths.push(build_dir);
+                }
+            }
+            let defaults = if presets.is_empty() {
+                DEFAULT_RESOURCE_PATHS
+            } else {
+                &preset_paths[..]
+            };
+            paths.extend(defaults.iter().map(|p| project.resolve(p)));
+        }
+    }
+    paths.retain(|path| path.exists());
+    paths
+}
+
+/// `[resources] min_free_gb`, else the largest preset need, else the default
+fn threshold(project: &ProjectContext) -> (u64, String) {
+    if let Some(gb) = project
+        .config
+        .resources
+        .as_ref()
+        .and_then(|r| r.min_free_gb)
+    {
+        return (gb, String::new());
+    }
+    RESOURCE_PRESETS
+        .iter()
+        .filter(|preset| project_type_is(project, preset.project_type))
+        .max_by_key(|preset| preset.min_free_gb)
+        .map_or((DEFAULT_MIN_FREE_GB, String::new()), |preset| {
+            (preset.min_free_gb, preset.label.to_string())
+        })
+}
+
+/// One entry per filesystem under the root and the build directories
+fn collect_disks(root: &Path, paths: Vec<PathBuf>) -> Vec<DiskSpace> {
+    let mut seen = Vec::new();
+    let mut disks = Vec::new();
+    for path in paths {
+        let dev = device(&path);
+        if seen.contains(&dev) {
+            continue;
+        }
+        seen.push(dev);
+        let stats = statvfs(&path);
+        let relative = match path.strip_prefix(root) {
+            Ok(relative) if relative.as_os_str().is_empty() => ".".to_string(),
+            Ok(relative) => display_path(relative),
+            Err(_) => display_path(&path),
+        };
+        disks.push(DiskSpace {
+            path: relative,
+            mount: display_path(&mount_point(&path)),
+            free_bytes: stats.map(|(free, _)| free),
+            total_bytes: stats.map(|(_, total)| total),
+        });
+    }
+    disks
+}
+
+pub fn collect_resources(project: &ProjectContext) -> ResourceInfo {
+    let (min_free_gb, requirement) = threshold(project);
+    let paths = resource_paths(project);
+    let root = project.root.clone();
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(collect_disks(&root, paths));
+    });
+    let disks = rx.recv_timeout(DISK_CHECK_TIMEOUT).unwrap_or_else(|_| {
+        collector_warning(format!(
+            "free disk space not read within {}s",
+            DISK_CHECK_TIMEOUT.as_secs()
+        ));
+        Vec::new()
+    });
+
+    let mut system = sysinfo::System::new();
+    system.refresh_memory();
+    let load = sysinfo::System::load_average();
+    ResourceInfo {
+        disks,
+        min_free_bytes: min_free_gb.saturating_mul(1 << 30),
+        requirement,
+        memory_total_bytes: system.total_memory(),
+        memory_available_bytes: system.available_memory(),
+        load_average: cfg!(unix).then_some([load.one, load.five, load.fifteen]),
+        cpus: std::thread::available_parallelism().map_or(1, |n| n.get()),
+    }
+}
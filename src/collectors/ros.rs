@@ -0,0 +1,183 @@
+//! ROS 2 workspaces
+
+use crate::collectors::detect::find_ros_workspace;
+use crate::collectors::packages::project_type_is;
+use crate::collectors::project_ignore::ProjectIgnore;
+use crate::config::{file_mtime, ProjectContext};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Most ROS packages listed
+pub const MAX_ROS_PACKAGES: usize = 50;
+
+/// Build state of a package, from source mtimes against its install dir
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RosPackageStatus {
+    Built,
+    Stale,
+    NotBuilt,
+}
+
+impl RosPackageStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RosPackageStatus::Built => "built",
+            RosPackageStatus::Stale => "stale",
+            RosPackageStatus::NotBuilt => "not built",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RosPackage {
+    pub name: String,
+    /// ament_cmake, ament_python, catkin, ...
+    pub build_type: String,
+    /// Package directory relative to the workspace root
+    pub path: String,
+    pub status: RosPackageStatus,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RosWorkspace {
+    /// Workspace directory relative to the project root ("" for the root)
+    pub path: String,
+    /// ROS_DISTRO, or the single distro under /opt/ros
+    pub distro: String,
+    pub has_build: bool,
+    pub has_install: bool,
+    pub has_log: bool,
+    /// install/setup.bash is older than some package source
+    pub setup_stale: bool,
+    pub packages: Vec<RosPackage>,
+}
+
+/// Text of the first `<tag>...</tag>` in `xml`
+pub fn xml_tag<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    Some(xml[start..end].trim())
+}
+
+/// Newest mtime of the files under `dir`, honoring .gitignore and friends
+/// and `project_ignore`
+pub fn newest_mtime(dir: &Path, project_ignore: &ProjectIgnore) -> Option<SystemTime> {
+    let skip = project_ignore.clone();
+    ignore::WalkBuilder::new(dir)
+        .filter_entry(move |entry| {
+            !skip.is_ignored(entry.path(), entry.file_type().is_some_and(|t| t.is_dir()))
+        })
+        .build()
+        .flatten()
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+        .max()
+}
+
+pub fn ros_distro() -> String {
+    if let Ok(distro) = std::env::var("ROS_DISTRO") {
+        return distro;
+    }
+    let distros: Vec<String> = fs::read_dir("/opt/ros")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    match distros.as_slice() {
+        [one] => one.clone(),
+        _ => distros.join("/"),
+    }
+}
+
+pub fn collect_ros_workspace(project: &ProjectContext) -> Option<RosWorkspace> {
+    if !project_type_is(project, "ros") {
+        return None;
+    }
+    let root = &project.root;
+    let workspace_dir = find_ros_workspace(root, &project.ignore)
+        .map(|(dir, _)| dir)
+        .unwrap_or_else(|| root.clone());
+    let install = workspace_dir.join("install");
+    let mut workspace = RosWorkspace {
+        path: workspace_dir
+            .strip_prefix(root)
+            .unwrap_or(&workspace_dir)
+            .to_string_lossy()
+            .to_string(),
+        distro: ros_distro(),
+        has_build: workspace_dir.join("build").is_dir(),
+        has_install: install.is_dir(),
+        has_log: workspace_dir.join("log").is_dir(),
+        ..Default::default()
+    };
+
+    // colcon skips packages under a COLCON_IGNORE marker; so do we
+    let skip = project.ignore.clone();
+    let manifests = ignore::WalkBuilder::new(workspace_dir.join("src"))
+        .filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+            !entry.path().join("COLCON_IGNORE").exists() && !skip.is_ignored(entry.path(), is_dir)
+        })
+        .build()
+        .flatten()
+        .filter(|entry| entry.file_name() == "package.xml");
+
+    let mut newest_source = None;
+    for manifest in manifests {
+        let Ok(xml) = fs::read_to_string(manifest.path()) else {
+            continue;
+        };
+        let Some(name) = xml_tag(&xml, "name") else {
+            continue;
+        };
+        let dir = manifest.path().parent().unwrap_or(root);
+        let build_type = xml_tag(&xml, "build_type")
+            .map(str::to_string)
+            .unwrap_or_else(|| {
+                if xml.contains("<buildtool_depend>catkin") {
+                    "catkin".to_string()
+                } else {
+                    "ament_cmake".to_string()
+                }
+            });
+
+        let source_mtime = newest_mtime(dir, &project.ignore);
+        newest_source = newest_source.max(source_mtime);
+        // Isolated (install/<pkg>) or merged (install/share/<pkg>) layout
+        let installed = [install.join(name), install.join("share").join(name)]
+            .into_iter()
+            .find(|p| p.is_dir());
+        let status = match installed {
+            None => RosPackageStatus::NotBuilt,
+            Some(dir) if newest_mtime(&dir, &project.ignore) < source_mtime => {
+                RosPackageStatus::Stale
+            }
+            Some(_) => RosPackageStatus::Built,
+        };
+
+        workspace.packages.push(RosPackage {
+            name: name.to_string(),
+            build_type,
+            path: dir
+                .strip_prefix(root)
+                .unwrap_or(dir)
+                .to_string_lossy()
+                .to_string(),
+            status,
+        });
+        if workspace.packages.len() == MAX_ROS_PACKAGES {
+            break;
+        }
+    }
+
+    workspace.setup_stale = match file_mtime(&install.join("setup.bash")) {
+        Some(setup) => newest_source.is_some_and(|source| source > setup),
+        None => false,
+    };
+    workspace.packages.sort_by(|a, b| a.name.cmp(&b.name));
+    Some(workspace)
+}
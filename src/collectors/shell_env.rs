@@ -0,0 +1,152 @@
+//! Environment variables of the user's terminal, as `context-keeper log`
+//! last recorded them
+//!
+//! `lunch`, `oe-init-build-env` and the like set variables in a shell the
+//! server never sees. A prompt hook running `context-keeper log` appends
+//! the `[env] capture` variables to the history log along with the
+//! command; the newest entry with a working directory under the project
+//! root is the one reported. Values that look like secrets are masked
+//! when they are written.
+
+use crate::collectors::collector_disabled;
+use crate::collectors::history::command_log_path;
+use crate::config::{Config, ProjectContext};
+use crate::error::ContextKeeperError;
+use crate::paths::project_relative;
+use crate::redact::{Redactor, REDACTED};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+/// Variables captured per project type, without `[env] capture`
+pub const ENV_CAPTURE_PRESETS: &[(&str, &[&str])] = &[
+    (
+        "aosp",
+        &[
+            "TARGET_PRODUCT",
+            "TARGET_BUILD_VARIANT",
+            "TARGET_RELEASE",
+            "OUT_DIR",
+            "ANDROID_PRODUCT_OUT",
+        ],
+    ),
+    (
+        "yocto",
+        &[
+            "MACHINE",
+            "DISTRO",
+            "BUILDDIR",
+            "TEMPLATECONF",
+            "BB_ENV_PASSTHROUGH_ADDITIONS",
+        ],
+    ),
+    (
+        "ros",
+        &["ROS_DISTRO", "ROS_DOMAIN_ID", "RMW_IMPLEMENTATION"],
+    ),
+    (
+        "zephyr",
+        &["ZEPHYR_BASE", "BOARD", "ZEPHYR_TOOLCHAIN_VARIANT"],
+    ),
+    ("buildroot", &["BR2_EXTERNAL", "BR2_DL_DIR"]),
+    ("kernel", &["ARCH", "CROSS_COMPILE", "KBUILD_OUTPUT"]),
+];
+
+/// Captured variables from one `context-keeper log` run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapturedEnv {
+    /// When the values were recorded, RFC 3339
+    pub timestamp: String,
+    /// Directory the shell was in, relative to the project root
+    pub cwd: String,
+    /// Set variables by name; unset ones are left out
+    pub vars: BTreeMap<String, String>,
+}
+
+/// `[env] capture`, else the presets of the configured types
+pub fn capture_list(config: &Config) -> Vec<String> {
+    if let Some(capture) = config.env.as_ref().and_then(|e| e.capture.clone()) {
+        return capture;
+    }
+    let types = config
+        .project
+        .as_ref()
+        .and_then(|p| p.project_type.as_ref())
+        .map(|types| types.to_vec())
+        .unwrap_or_default();
+    let mut names: Vec<String> = Vec::new();
+    for (_, preset) in ENV_CAPTURE_PRESETS
+        .iter()
+        .filter(|(name, _)| types.contains(name))
+    {
+        for name in *preset {
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// The set variables of `names` from this process's environment, with
+/// values the redaction patterns catch replaced by `REDACTED`
+pub fn capture_env(config: &Config, names: &[String]) -> BTreeMap<String, String> {
+    let redactor = Redactor::from_config(config);
+    names
+        .iter()
+        .filter_map(|name| {
+            let value = std::env::var(name).ok()?;
+            // Labeled patterns need the name to see a secret
+            let masked = match redactor.redact(&format!("{}={}", name, value)) {
+                Cow::Borrowed(_) => value,
+                Cow::Owned(_) => REDACTED.to_string(),
+            };
+            Some((name.clone(), masked))
+        })
+        .collect()
+}
+
+/// Newest captured environment logged from under the project root
+pub fn collect_captured_env(
+    project: &ProjectContext,
+) -> Result<Option<CapturedEnv>, ContextKeeperError> {
+    if capture_list(&project.config).is_empty() {
+        collector_disabled();
+        return Ok(None);
+    }
+    let path = command_log_path(&project.config);
+    let file = match fs::File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(ContextKeeperError::state_io(&path, e)),
+    };
+    let root = project
+        .root
+        .canonicalize()
+        .unwrap_or_else(|_| project.root.clone());
+    let mut newest = None;
+    for line in io::BufReader::new(file).lines().map_while(Result::ok) {
+        let Ok(entry) = serde_json::from_str::<serde_json::Value>(&line) else {
+            continue;
+        };
+        let Some(vars) = entry.get("env").and_then(|env| env.as_object()) else {
+            continue;
+        };
+        let Ok(relative) = Path::new(entry["cwd"].as_str().unwrap_or_default()).strip_prefix(&root)
+        else {
+            continue;
+        };
+        newest = Some(CapturedEnv {
+            timestamp: entry["timestamp"].as_str().unwrap_or_default().to_string(),
+            cwd: project_relative(&root, relative),
+            vars: vars
+                .iter()
+                .filter_map(|(name, value)| Some((name.clone(), value.as_str()?.to_string())))
+                .collect(),
+        });
+    }
+    Ok(newest)
+}
@@ -0,0 +1,410 @@
+//! Build targets from `.conf` files under `scripts.config_dir`
+
+use crate::collectors::entry_point::EntryPointInfo;
+use crate::collectors::make::collect_make_targets;
+use crate::collectors::{collector_capped, collector_warning, BuildTarget};
+use crate::commands::hooks::shell_quote;
+use crate::config::ProjectContext;
+use crate::limits::{overflow_summary, Limits};
+use crate::paths::display_path;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Target config files selected by each `scripts.config_dir` entry and
+/// `config_pattern`, with the full glob pattern used. The pattern may use
+/// `**` to descend into subdirectories. Empty when no config_dir is set.
+///
+/// Directories are resolved against the config file's directory, and
+/// `\\` is read as `/` in both settings so configs written on Windows work
+/// anywhere. Glob characters in the directory itself are taken literally.
+/// Each pattern selects at most `[limits] max_config_files` files; the
+/// rest are counted in a capped summary and not read.
+pub fn glob_target_files(project: &ProjectContext) -> Vec<(String, Result<Vec<PathBuf>, String>)> {
+    let Some(scripts_config) = project.config.scripts.as_ref() else {
+        return Vec::new();
+    };
+    let Some(config_dirs) = scripts_config.config_dir.as_ref() else {
+        return Vec::new();
+    };
+    let pattern = scripts_config
+        .config_pattern
+        .as_deref()
+        .unwrap_or("*.conf")
+        .replace('\\', "/");
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        case_sensitive: !scripts_config.config_pattern_ignore_case.unwrap_or(false),
+        ..Default::default()
+    };
+    let max_files = Limits::from_config(&project.config).max_config_files;
+
+    config_dirs
+        .to_vec()
+        .into_iter()
+        .map(|dir| {
+            let dir = dir.replace('\\', "/");
+            // "conf/" and "conf" are the same directory; "/" stays the root
+            let dir = match dir.trim_end_matches('/') {
+                "" => dir.as_str(),
+                trimmed => trimmed,
+            };
+            let dir = display_path(&project.resolve_from_config(dir)).replace('\\', "/");
+            let full_pattern = format!(
+                "{}/{}",
+                glob::Pattern::escape(dir.trim_end_matches('/')),
+                pattern
+            );
+            let files = glob::glob_with(&full_pattern, options)
+                .map(|entries| {
+                    let mut entries = entries.flatten().filter(|p| p.is_file());
+                    let files: Vec<PathBuf> = entries.by_ref().take(max_files).collect();
+                    let more = entries.count();
+                    if more > 0 {
+                        collector_capped(format!(
+                            "targets: {}: {}",
+                            full_pattern,
+                            overflow_summary(
+                                more,
+                                "file not read",
+                                "files not read",
+                                "max_config_files"
+                            )
+                        ));
+                    }
+                    files
+                })
+                .map_err(|e| e.to_string());
+            (full_pattern, files)
+        })
+        .collect()
+}
+
+/// Targets from the .conf files, then Makefile targets not already named.
+/// A .conf target named again in a later directory replaces the earlier one.
+pub fn collect_build_targets(project: &ProjectContext) -> Vec<BuildTarget> {
+    let vars = project.script_vars();
+    let mut targets: Vec<BuildTarget> = Vec::new();
+    for (pattern, files) in glob_target_files(project) {
+        let files = match files {
+            Ok(files) if files.is_empty() => {
+                // The usual cause of "no targets": say exactly what was searched
+                collector_warning(format!("targets: {} matched 0 files", pattern));
+                continue;
+            }
+            Ok(files) => files,
+            Err(e) => {
+                collector_warning(format!("targets: invalid pattern {}: {}", pattern, e));
+                continue;
+            }
+        };
+        tracing::debug!(%pattern, matched = files.len(), "target config glob");
+        for target in files.iter().filter_map(|f| parse_config_file(f, &vars)) {
+            if let Some(pos) = targets.iter().position(|t| t.name == target.name) {
+                let earlier = targets.remove(pos);
+                collector_warning(format!(
+                    "target {}: {} overrides {}",
+                    target.name, target.source_file, earlier.source_file
+                ));
+            }
+            targets.push(target);
+        }
+    }
+
+    for target in collect_make_targets(project) {
+        if !targets.iter().any(|t| t.name == target.name) {
+            targets.push(target);
+        }
+    }
+    targets
+}
+
+/// Shell command that builds `target` from the project root, if we know one
+pub fn target_command(
+    target: &BuildTarget,
+    entry_point: Option<&EntryPointInfo>,
+    make: &str,
+    root: &Path,
+) -> Option<String> {
+    match target.source.as_str() {
+        "makefile" => Some(format!("{} {}", make, shell_quote(&target.name))),
+        "west" => {
+            let cache = Path::new(&target.source_file);
+            match cache.parent() {
+                Some(build_dir) if cache.file_name() == Some("CMakeCache.txt".as_ref()) => {
+                    let build_dir = build_dir.strip_prefix(root).unwrap_or(build_dir);
+                    Some(format!(
+                        "west build -d {}",
+                        shell_quote(&display_path(build_dir))
+                    ))
+                }
+                // `build.board` with no build directory yet
+                _ => Some(format!("west build -b {}", shell_quote(&target.name))),
+            }
+        }
+        _ => match entry_point {
+            // `./build.sh build emu` when the script has a build subcommand
+            Some(info) => {
+                let build = info.subcommands.iter().any(|(name, _)| name == "build");
+                Some(format!(
+                    "{}{} {}",
+                    info.invocation,
+                    if build { " build" } else { "" },
+                    shell_quote(&target.name)
+                ))
+            }
+            None if !target.lunch_target.is_empty() => Some(format!(
+                "source build/envsetup.sh && lunch {} && m",
+                shell_quote(&target.lunch_target)
+            )),
+            None => None,
+        },
+    }
+}
+
+/// How deep `source`/`.` includes are followed from a target .conf
+pub const MAX_SOURCE_DEPTH: usize = 4;
+
+pub fn parse_config_file(
+    path: &Path,
+    config_vars: &HashMap<String, String>,
+) -> Option<BuildTarget> {
+    let mut assignments = Vec::new();
+    let mut reader = ShellAssignments {
+        config_vars,
+        assigned: HashMap::new(),
+        visited: Vec::new(),
+        unresolved: Vec::new(),
+    };
+    reader.read(path, 0, &mut assignments)?;
+
+    for name in &reader.unresolved {
+        collector_warning(format!("{}: cannot expand ${}", path.display(), name));
+    }
+
+    let mut target = BuildTarget {
+        source: "conf".to_string(),
+        source_file: display_path(path),
+        ..Default::default()
+    };
+
+    // In file order, includes inline, so later assignments win like in bash
+    for (key, value) in assignments {
+        match key.as_str() {
+            "TARGET_NAME" => target.name = value,
+            "TARGET_DESCRIPTION" => target.description = value,
+            "CONTAINER_NAME" => target.container_name = value,
+            "LUNCH_TARGET" => target.lunch_target = value,
+            "CAN_EMULATOR" => target.can_emulator = value == "true",
+            "CAN_FLASH" => target.can_flash = value == "true",
+            "TARGET_HINT" => target.hint = value,
+            "ARTIFACT_PATH" => target.artifact_path = value,
+            "EXEC_TEMPLATE" => target.exec_template = value,
+            _ => {}
+        }
+    }
+
+    if target.name.is_empty() {
+        target.name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("unknown")
+            .to_string();
+    }
+
+    Some(target)
+}
+
+/// State for reading one target .conf and the files it sources
+pub struct ShellAssignments<'a> {
+    /// `[scripts.vars]` from contextkeeper.toml
+    pub config_vars: &'a HashMap<String, String>,
+    /// Variables assigned so far, visible to later `${VAR}` references
+    pub assigned: HashMap<String, String>,
+    /// Files already read, to break `source` cycles
+    pub visited: Vec<PathBuf>,
+    /// Referenced variables with no value and no default
+    pub unresolved: Vec<String>,
+}
+
+impl ShellAssignments<'_> {
+    /// Variable value: earlier assignments, then `[scripts.vars]`, then the
+    /// process environment
+    pub fn lookup(&self, name: &str) -> Option<String> {
+        self.assigned
+            .get(name)
+            .or_else(|| self.config_vars.get(name))
+            .cloned()
+            .or_else(|| std::env::var(name).ok())
+    }
+
+    /// Collect `KEY=value` assignments from a shell file, following `source`
+    /// and `.` includes relative to the including file.
+    /// Returns `None` only when `path` itself can't be read.
+    pub fn read(
+        &mut self,
+        path: &Path,
+        depth: usize,
+        out: &mut Vec<(String, String)>,
+    ) -> Option<()> {
+        let content = fs::read_to_string(path).ok()?;
+        if let Ok(canonical) = path.canonicalize() {
+            if self.visited.contains(&canonical) {
+                return Some(());
+            }
+            self.visited.push(canonical);
+        }
+
+        // Backslash-newline is removed entirely, as bash does
+        let content = content.replace("\\\r\n", "").replace("\\\n", "");
+        let dir = path.parent().unwrap_or(Path::new("."));
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.starts_with('#') || line.is_empty() {
+                continue;
+            }
+
+            let include = line
+                .strip_prefix("source ")
+                .or_else(|| line.strip_prefix(". "));
+            if let Some(file) = include {
+                let file = parse_shell_word(file.trim(), &mut |_| None, &mut Vec::new());
+                if depth < MAX_SOURCE_DEPTH && !file.is_empty() && !file.contains('$') {
+                    let _ = self.read(&dir.join(file), depth + 1, out);
+                }
+                continue;
+            }
+
+            if let Some((key, raw)) = parse_var_assignment(line) {
+                let mut unresolved = Vec::new();
+                let value = parse_shell_word(raw, &mut |name| self.lookup(name), &mut unresolved);
+                for name in unresolved {
+                    if !self.unresolved.contains(&name) {
+                        self.unresolved.push(name);
+                    }
+                }
+                self.assigned.insert(key.to_string(), value.clone());
+                out.push((key.to_string(), value));
+            }
+        }
+
+        Some(())
+    }
+}
+
+/// Split a shell assignment (`[export ]KEY=value`) into key and raw value
+pub fn parse_var_assignment(line: &str) -> Option<(&str, &str)> {
+    let line = line
+        .strip_prefix("export ")
+        .or_else(|| line.strip_prefix("readonly "))
+        .unwrap_or(line)
+        .trim_start();
+    let (key, value) = line.split_once('=')?;
+
+    let key = key.trim();
+    let is_identifier = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_identifier {
+        return None;
+    }
+
+    Some((key, value.trim_start()))
+}
+
+/// Read one shell word, removing quotes the way bash would: `'...'` is
+/// literal, `"..."` honours `\"`, `\\`, `\$` and `` \` ``, and an unquoted
+/// backslash escapes the next character. Stops at unquoted whitespace or a
+/// `#` comment.
+///
+/// `$VAR`, `${VAR}`, `${VAR:-default}` and `${VAR-default}` are expanded
+/// outside single quotes via `lookup`. Unset variables without a default are
+/// kept as written and their names pushed to `unresolved`.
+pub fn parse_shell_word(
+    input: &str,
+    lookup: &mut dyn FnMut(&str) -> Option<String>,
+    unresolved: &mut Vec<String>,
+) -> String {
+    let mut word = String::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => break,
+            '#' if word.is_empty() => break,
+            '\\' => word.extend(chars.next()),
+            '\'' => word.extend(chars.by_ref().take_while(|&c| c != '\'')),
+            '$' => expand_shell_var(&mut chars, lookup, unresolved, &mut word),
+            '"' => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' if matches!(chars.peek(), Some('"' | '\\' | '$' | '`')) => {
+                            word.extend(chars.next());
+                        }
+                        '$' => expand_shell_var(&mut chars, lookup, unresolved, &mut word),
+                        c => word.push(c),
+                    }
+                }
+            }
+            c => word.push(c),
+        }
+    }
+
+    word
+}
+
+/// Expand the variable reference after a `$` into `word`
+pub fn expand_shell_var(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    lookup: &mut dyn FnMut(&str) -> Option<String>,
+    unresolved: &mut Vec<String>,
+    word: &mut String,
+) {
+    let is_name_char = |c: &char| c.is_ascii_alphanumeric() || *c == '_';
+
+    if chars.next_if_eq(&'{').is_none() {
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(is_name_char) {
+            name.push(c);
+        }
+        if name.is_empty() {
+            word.push('$');
+            return;
+        }
+        match lookup(&name) {
+            Some(value) => word.push_str(&value),
+            None => {
+                word.push('$');
+                word.push_str(&name);
+                unresolved.push(name);
+            }
+        }
+        return;
+    }
+
+    let body: String = chars.by_ref().take_while(|&c| c != '}').collect();
+    let name_len = body.chars().take_while(is_name_char).count();
+    let (name, operator) = body.split_at(name_len);
+    let value = lookup(name);
+    match operator {
+        // ${VAR:-default} also replaces an empty value
+        _ if operator.starts_with(":-") => match value.filter(|v| !v.is_empty()) {
+            Some(value) => word.push_str(&value),
+            None => word.push_str(&operator[2..]),
+        },
+        _ if operator.starts_with('-') => match value {
+            Some(value) => word.push_str(&value),
+            None => word.push_str(&operator[1..]),
+        },
+        "" if value.is_some() => word.push_str(&value.unwrap_or_default()),
+        // Unset, or an operator we don't evaluate (${VAR:+x}, ${VAR#x}, ...)
+        _ => {
+            word.push_str(&format!("${{{}}}", body));
+            unresolved.push(name.to_string());
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! tmux panes working inside the project, with `[tmux] enabled = true`
+
+use crate::collectors::{collector_disabled, collector_unavailable, CommandRunner, TmuxPane};
+use crate::config::ProjectContext;
+use crate::paths::project_relative;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::time::Duration;
+
+/// Limit on `tmux list-panes`
+pub const TMUX_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Foreground commands worth keeping in view at normal level: builds,
+/// log tails, containers and emulators (`qemu-system-*` by prefix)
+pub const LONG_LIVED_COMMANDS: &[&str] = &[
+    "make",
+    "ninja",
+    "m",
+    "mm",
+    "mmm",
+    "soong_ui",
+    "bitbake",
+    "cargo",
+    "gradle",
+    "java",
+    "west",
+    "colcon",
+    "idf.py",
+    "adb",
+    "logcat",
+    "tail",
+    "journalctl",
+    "docker",
+    "podman",
+    "emulator",
+];
+
+/// Parse `list-panes -a -F` lines of tab-separated session, window,
+/// command and path, keeping panes under `root`
+pub fn parse_tmux_panes(output: &str, root: &Path) -> Vec<TmuxPane> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, '\t');
+            let (session, window, command, path) = (
+                fields.next()?,
+                fields.next()?,
+                fields.next()?,
+                fields.next()?,
+            );
+            if !Path::new(path).starts_with(root) {
+                return None;
+            }
+            let relative = project_relative(root, Path::new(path));
+            Some(TmuxPane {
+                session: session.to_string(),
+                window: window.to_string(),
+                command: command.to_string(),
+                path: relative,
+            })
+        })
+        .collect()
+}
+
+/// Panes under the project root. Empty, without a warning, when tmux is
+/// disabled, not installed, or has no server running.
+pub fn collect_tmux_panes(project: &ProjectContext) -> Vec<TmuxPane> {
+    let enabled = project
+        .config
+        .tmux
+        .as_ref()
+        .and_then(|t| t.enabled)
+        .unwrap_or(false);
+    if !enabled {
+        collector_disabled();
+        return Vec::new();
+    }
+    if !project.runner.has_program("tmux") {
+        collector_unavailable("tmux not installed".to_string());
+    }
+    list_tmux_panes(&*project.runner, &project.root)
+}
+
+/// `tmux list-panes -a` covers every session
+pub fn list_tmux_panes(runner: &dyn CommandRunner, root: &Path) -> Vec<TmuxPane> {
+    if !runner.has_program("tmux") {
+        return Vec::new();
+    }
+    let format = "#{session_name}\t#{window_name}\t#{pane_current_command}\t#{pane_current_path}";
+    let args = [
+        OsStr::new("list-panes"),
+        OsStr::new("-a"),
+        OsStr::new("-F"),
+        OsStr::new(format),
+    ];
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    match runner.run("tmux", &args, Some(TMUX_TIMEOUT)) {
+        // Non-zero when no server is running
+        Ok(output) if output.status.success() => {
+            parse_tmux_panes(&String::from_utf8_lossy(&output.stdout), &root)
+        }
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,191 @@
+//! Target validation against containers, images and AOSP lunch targets
+
+use crate::collectors::detect::detect_project_types;
+use crate::collectors::packages::project_type_is;
+use crate::collectors::{resolve_program, run_command_timeout, BuildTarget};
+use crate::config::ProjectContext;
+use crate::context::{run_with_deadline, Context};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Timeout for each container runtime lookup during validation
+pub const VALIDATION_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Build variants `lunch` accepts after the product (and release)
+pub const LUNCH_VARIANTS: &[&str] = &["user", "userdebug", "eng"];
+
+/// Cross-check each target's container and lunch target, recording what's
+/// wrong in `BuildTarget::issues`. A check is skipped entirely when its
+/// tooling (container runtime, AOSP tree) isn't available.
+pub fn validate_targets(project: &ProjectContext, targets: &mut [BuildTarget]) {
+    if targets.iter().any(|t| !t.container_name.is_empty()) {
+        let runtime = project
+            .config
+            .containers
+            .as_ref()
+            .and_then(|c| c.runtime.as_deref())
+            .unwrap_or("podman");
+        let containers = runtime_lines(runtime, &["ps", "-a", "--format", "{{.Names}}"]);
+        let images = runtime_lines(runtime, &["images", "--format", "{{.Repository}}:{{.Tag}}"]);
+        if containers.is_some() || images.is_some() {
+            let containers = containers.unwrap_or_default();
+            let images = images.unwrap_or_default();
+            for target in targets.iter_mut().filter(|t| !t.container_name.is_empty()) {
+                let name = target.container_name.as_str();
+                if !containers.iter().any(|c| c == name)
+                    && !images.iter().any(|image| image_matches(image, name))
+                {
+                    target.issues.push("container missing".to_string());
+                }
+            }
+        }
+    }
+
+    let is_aosp = project_type_is(project, "aosp")
+        || detect_project_types(&project.root, &project.ignore)
+            .iter()
+            .any(|d| d.project_type == "aosp");
+    if is_aosp && targets.iter().any(|t| !t.lunch_target.is_empty()) {
+        if let Some(products) = lunch_products(&project.root) {
+            for target in targets.iter_mut().filter(|t| !t.lunch_target.is_empty()) {
+                if !is_known_lunch_target(&target.lunch_target, &products) {
+                    target.issues.push("unknown lunch target".to_string());
+                }
+            }
+        }
+    }
+}
+
+/// Output lines of a container runtime query; `None` when the runtime is
+/// missing, times out or fails
+pub fn runtime_lines(runtime: &str, args: &[&str]) -> Option<Vec<String>> {
+    let output = run_command_timeout(
+        std::process::Command::new(resolve_program(runtime)).args(args),
+        VALIDATION_TIMEOUT,
+    )
+    .ok()
+    .filter(|output| output.status.success())?;
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect(),
+    )
+}
+
+/// Whether `image` ("repo:tag") is what a target's `CONTAINER_NAME` refers
+/// to, allowing for the tag and registry prefix to be left out
+pub fn image_matches(image: &str, name: &str) -> bool {
+    let repository = image.rsplit_once(':').map_or(image, |(repo, _)| repo);
+    image == name
+        || repository == name
+        || repository.ends_with(&format!("/{}", name))
+        || image.ends_with(&format!("/{}", name))
+}
+
+/// Product names `lunch` knows about. A cached `lunch` listing in
+/// `out/lunch-choices.txt` wins; otherwise `AndroidProducts.mk` files under
+/// build/make/target/product, device/ and vendor/ are read.
+/// `None` when neither source yields any product.
+pub fn lunch_products(root: &Path) -> Option<Vec<String>> {
+    let mut products = Vec::new();
+
+    if let Ok(content) = fs::read_to_string(root.join("out/lunch-choices.txt")) {
+        // One combo per line, possibly numbered as in the old lunch menu
+        for line in content.lines() {
+            if let Some(combo) = line.split_whitespace().last() {
+                products.push(lunch_product(combo).to_string());
+            }
+        }
+    } else {
+        let patterns = [
+            "build/make/target/product/AndroidProducts.mk",
+            "device/*/*/AndroidProducts.mk",
+            "vendor/*/*/AndroidProducts.mk",
+        ];
+        for pattern in patterns {
+            let full_pattern = format!("{}/{}", root.display(), pattern);
+            for path in glob::glob(&full_pattern).into_iter().flatten().flatten() {
+                if let Ok(content) = fs::read_to_string(&path) {
+                    parse_android_products(&content, &mut products);
+                }
+            }
+        }
+    }
+
+    products.sort();
+    products.dedup();
+    (!products.is_empty()).then_some(products)
+}
+
+/// Products named by `PRODUCT_MAKEFILES` and `COMMON_LUNCH_CHOICES`
+pub fn parse_android_products(content: &str, products: &mut Vec<String>) {
+    let joined = content.replace("\\\n", " ");
+    for line in joined.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim_end_matches([':', '+', '?']).trim();
+        for word in value.split_whitespace() {
+            match key {
+                "COMMON_LUNCH_CHOICES" => products.push(lunch_product(word).to_string()),
+                "PRODUCT_MAKEFILES" => {
+                    // Either "$(LOCAL_DIR)/name.mk" or "name:$(LOCAL_DIR)/file.mk"
+                    let name = match word.split_once(':') {
+                        Some((name, _)) if !name.contains('$') => name,
+                        _ => Path::new(word)
+                            .file_stem()
+                            .and_then(|s| s.to_str())
+                            .unwrap_or(""),
+                    };
+                    if !name.is_empty() {
+                        products.push(name.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Product part of a lunch combo ("aosp_arm64-trunk_staging-eng" -> "aosp_arm64")
+pub fn lunch_product(combo: &str) -> &str {
+    combo.split('-').next().unwrap_or(combo)
+}
+
+pub fn is_known_lunch_target(lunch_target: &str, products: &[String]) -> bool {
+    let variant_ok = lunch_target
+        .rsplit_once('-')
+        .is_some_and(|(_, variant)| LUNCH_VARIANTS.contains(&variant));
+    variant_ok && products.iter().any(|p| p == lunch_product(lunch_target))
+}
+
+/// Annotate `ctx.targets` on a detached thread; targets stay unannotated if
+/// the lookups don't finish before the deadline
+pub async fn annotate_targets(
+    project: &Arc<ProjectContext>,
+    ctx: &mut Context,
+    deadline: Duration,
+    ct: &CancellationToken,
+) {
+    if ctx.targets.is_empty() {
+        return;
+    }
+    let project = Arc::clone(project);
+    let mut targets = ctx.targets.clone();
+    match run_with_deadline(deadline, ct, move || {
+        validate_targets(&project, &mut targets);
+        targets
+    })
+    .await
+    {
+        Ok(targets) => ctx.targets = targets,
+        Err(status) => {
+            tracing::warn!(status = status.as_str(), "target validation abandoned");
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! `working_files` passed to `save_work_state`, checked before they're
+//! saved
+//!
+//! A list written by hand or by a model goes wrong in a few ways: a
+//! typo, the `b/` of a diff header, the `:42` of a compiler message, the
+//! project directory's own name in front. Each path is normalized against
+//! the project root, looked up on disk and in `git ls-files` of the
+//! repository holding it, and one that isn't on disk gets the closest
+//! path in that repository's `git status` as a suggestion. Paths that
+//! can't be found are saved anyway, and listed in
+//! `WorkState::unverified_files`.
+
+use crate::collectors::git::git_in;
+use crate::collectors::working_files::{changed_paths, holding_repo, WORKING_FILES_GIT_TIMEOUT};
+use crate::config::ProjectContext;
+use crate::limits::Limits;
+use crate::paths::{project_relative, strip_outside_marker, OUTSIDE_ROOT_MARKER};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileVerdictState {
+    /// On disk and tracked by git
+    Tracked,
+    /// On disk, but git doesn't track it (a new file, or no repository)
+    Untracked,
+    /// Not on disk
+    NotFound,
+    /// Outside the project root
+    OutsideProject,
+}
+
+/// One of the given `working_files`, as it will be saved
+#[derive(Debug, Clone, Serialize)]
+pub struct FileVerdict {
+    /// As given
+    pub input: String,
+    /// Normalized, as saved
+    pub path: String,
+    pub state: FileVerdictState,
+    /// Closest changed path, for a path that isn't there or differs from
+    /// one only in case
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+}
+
+/// `input` as `project_relative` names it, after trying the usual slips
+/// when that isn't on disk: a `file://` scheme, a `:line` or
+/// `:line:column` suffix, the `a/` or `b/` of a diff header and the
+/// project directory's name in front. The first spelling that exists
+/// wins; with none, the plain relative path.
+pub fn normalize_working_path(root: &Path, input: &str) -> String {
+    let trimmed = input.trim();
+    let trimmed = trimmed.strip_prefix("file://").unwrap_or(trimmed);
+    let plain = project_relative(root, Path::new(trimmed));
+    if on_disk(root, &plain) {
+        return plain;
+    }
+
+    let mut bases = vec![trimmed];
+    if let Some(without_line) = strip_line_suffix(trimmed) {
+        bases.push(without_line);
+    }
+    let root_name = root.file_name().and_then(|name| name.to_str());
+    for base in bases {
+        let mut spellings = vec![base];
+        spellings.extend(["a/", "b/"].iter().filter_map(|p| base.strip_prefix(p)));
+        if let Some(rest) = root_name.and_then(|name| {
+            base.strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix('/'))
+        }) {
+            spellings.push(rest);
+        }
+        for spelling in spellings {
+            let path = project_relative(root, Path::new(spelling));
+            if on_disk(root, &path) {
+                return path;
+            }
+        }
+    }
+    plain
+}
+
+/// `path` without a trailing `:12` or `:12:7`
+fn strip_line_suffix(path: &str) -> Option<&str> {
+    let numeric = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let (rest, last) = path.rsplit_once(':')?;
+    if !numeric(last) {
+        return None;
+    }
+    match rest.rsplit_once(':') {
+        Some((before, line)) if numeric(line) => Some(before),
+        _ => Some(rest),
+    }
+}
+
+/// A `project_relative` path, absolute again when it left the root
+fn full_path(root: &Path, path: &str) -> PathBuf {
+    root.join(strip_outside_marker(Path::new(path)))
+}
+
+fn on_disk(root: &Path, path: &str) -> bool {
+    full_path(root, path).exists()
+}
+
+/// Edit distance between `a` and `b`, by characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The candidate `path` most likely meant. One that differs only in case
+/// comes first; otherwise the closest by edit distance, ignoring case,
+/// within a quarter of the path's length (at least one edit). Ties go to
+/// the earlier candidate; `path` itself is never suggested.
+pub fn nearest_path<'a>(path: &str, candidates: &'a [String]) -> Option<&'a str> {
+    let lower = path.to_lowercase();
+    let others = || candidates.iter().filter(|c| c.as_str() != path);
+    if let Some(same) = others().find(|c| c.to_lowercase() == lower) {
+        return Some(same);
+    }
+    let limit = (path.chars().count() / 4).max(1);
+    others()
+        .map(|c| (edit_distance(&lower, &c.to_lowercase()), c))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, c)| c.as_str())
+}
+
+/// Paths of `repo` that `git ls-files` lists among `paths` (relative to
+/// `repo`); empty when git fails
+fn tracked_paths(project: &ProjectContext, repo: &Path, paths: &[String]) -> Vec<String> {
+    let mut args = vec!["ls-files", "-z", "--"];
+    args.extend(paths.iter().map(String::as_str));
+    git_in(
+        &*project.runner,
+        repo,
+        &args,
+        Some(WORKING_FILES_GIT_TIMEOUT),
+    )
+    .ok()
+    .filter(|output| output.status.success())
+    .map(|output| {
+        output
+            .stdout
+            .split(|&b| b == 0)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| String::from_utf8_lossy(entry).into_owned())
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Each of `files` normalized and looked up, in the order given. One `git
+/// ls-files` per repository holding a file on disk, and one `git status`
+/// per repository holding a file that isn't.
+pub fn verify_working_files(project: &ProjectContext, files: &[String]) -> Vec<FileVerdict> {
+    let root = &project.root;
+    let max_paths = Limits::from_config(&project.config).max_status_lines;
+    let mut verdicts: Vec<(FileVerdict, Option<PathBuf>)> = files
+        .iter()
+        .map(|input| {
+            let path = normalize_working_path(root, input);
+            let state = if path.ends_with(OUTSIDE_ROOT_MARKER) {
+                FileVerdictState::OutsideProject
+            } else if on_disk(root, &path) {
+                FileVerdictState::Untracked
+            } else {
+                FileVerdictState::NotFound
+            };
+            let repo = holding_repo(root, &full_path(root, &path));
+            let verdict = FileVerdict {
+                input: input.clone(),
+                path,
+                state,
+                suggestion: None,
+            };
+            (verdict, repo)
+        })
+        .collect();
+
+    // Paths relative to their repository, by repository
+    let mut by_repo: HashMap<PathBuf, Vec<(usize, String)>> = HashMap::new();
+    for (i, (verdict, repo)) in verdicts.iter().enumerate() {
+        let Some(repo) = repo
+            .as_ref()
+            .filter(|_| verdict.state != FileVerdictState::OutsideProject)
+        else {
+            continue;
+        };
+        let within = full_path(root, &verdict.path);
+        if let Ok(relative) = within.strip_prefix(repo) {
+            let relative = relative.to_string_lossy().into_owned();
+            by_repo.entry(repo.clone()).or_default().push((i, relative));
+        }
+    }
+
+    for (repo, entries) in &by_repo {
+        let on_disk: Vec<String> = entries
+            .iter()
+            .filter(|(i, _)| verdicts[*i].0.state == FileVerdictState::Untracked)
+            .map(|(_, relative)| relative.clone())
+            .collect();
+        if !on_disk.is_empty() {
+            let tracked = tracked_paths(project, repo, &on_disk);
+            for (i, relative) in entries {
+                if tracked.contains(relative) {
+                    verdicts[*i].0.state = FileVerdictState::Tracked;
+                }
+            }
+        }
+
+        // Suggestions for what isn't there, or is there only in another
+        // case (a case-insensitive filesystem finds it; git doesn't)
+        let needs_suggestion = |state: FileVerdictState| {
+            matches!(
+                state,
+                FileVerdictState::NotFound | FileVerdictState::Untracked
+            )
+        };
+        if !entries
+            .iter()
+            .any(|(i, _)| needs_suggestion(verdicts[*i].0.state))
+        {
+            continue;
+        }
+        let Some((changed, _)) = changed_paths(&*project.runner, repo, max_paths) else {
+            continue;
+        };
+        let mut candidates: Vec<String> = changed
+            .iter()
+            .filter(|path| !path.ends_with('/'))
+            .map(|path| project_relative(root, &repo.join(path)))
+            .collect();
+        candidates.sort();
+        for (i, _) in entries {
+            let verdict = &mut verdicts[*i].0;
+            let suggestion = match verdict.state {
+                FileVerdictState::NotFound => nearest_path(&verdict.path, &candidates),
+                FileVerdictState::Untracked => candidates
+                    .iter()
+                    .find(|c| {
+                        *c != &verdict.path && c.to_lowercase() == verdict.path.to_lowercase()
+                    })
+                    .map(String::as_str),
+                _ => None,
+            };
+            verdict.suggestion = suggestion.map(str::to_string);
+        }
+    }
+    verdicts.into_iter().map(|(verdict, _)| verdict).collect()
+}
+
+/// The files part of the `save_work_state` reply: "5 saved; 1 path not
+/// found: src/hall/audio.cpp — did you mean src/hal/audio.cpp?"
+pub fn format_file_verdicts(saved: usize, verdicts: &[FileVerdict]) -> String {
+    let mut out = format!("{} saved", saved);
+    let groups = [
+        (
+            FileVerdictState::NotFound,
+            "path not found",
+            "paths not found",
+        ),
+        (
+            FileVerdictState::OutsideProject,
+            "path outside the project",
+            "paths outside the project",
+        ),
+        (
+            FileVerdictState::Untracked,
+            "path not tracked by git",
+            "paths not tracked by git",
+        ),
+    ];
+    for (state, one, many) in groups {
+        let matching: Vec<String> = verdicts
+            .iter()
+            .filter(|v| v.state == state)
+            .map(|v| match &v.suggestion {
+                Some(suggestion) => format!("{} — did you mean {}?", v.path, suggestion),
+                None => v.path.clone(),
+            })
+            .collect();
+        if !matching.is_empty() {
+            out.push_str(&format!(
+                "; {} {}: {}",
+                matching.len(),
+                if matching.len() == 1 { one } else { many },
+                matching.join(", ")
+            ));
+        }
+    }
+    out
+}
@@ -0,0 +1,175 @@
+//! Zephyr / west workspaces and their builds
+
+use crate::collectors::packages::project_type_is;
+use crate::collectors::{run_command_timeout, BuildTarget};
+use crate::config::{file_mtime, ProjectContext};
+use crate::format::format_age;
+use crate::i18n::configured_language;
+use crate::paths::display_path;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Most west builds listed as targets
+pub const MAX_WEST_BUILDS: usize = 10;
+
+/// Timeout for `west config --list`
+pub const WEST_CONFIG_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// West workspace top directory: the project root or its parent, whichever
+/// holds `.west/config`
+pub fn find_west_topdir(root: &Path) -> Option<PathBuf> {
+    [Some(root), root.parent()]
+        .into_iter()
+        .flatten()
+        .find(|dir| dir.join(".west/config").is_file())
+        .map(Path::to_path_buf)
+}
+
+/// `section.key` -> value from west's INI-style config
+pub fn parse_west_config(content: &str) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    let mut section = String::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.trim().to_string();
+        } else if let Some((key, value)) = line.split_once('=') {
+            values.insert(
+                format!("{}.{}", section, key.trim()),
+                value.trim().to_string(),
+            );
+        }
+    }
+    values
+}
+
+/// Merged west config (system, global, workspace) from `west config --list`
+/// when west is installed, else the workspace's `.west/config` alone
+pub fn read_west_config(topdir: &Path) -> HashMap<String, String> {
+    let listed = run_command_timeout(
+        std::process::Command::new("west")
+            .args(["config", "--list"])
+            .current_dir(topdir),
+        WEST_CONFIG_TIMEOUT,
+    )
+    .ok()
+    .filter(|output| output.status.success())
+    .map(|output| parse_west_config_list(&String::from_utf8_lossy(&output.stdout)));
+
+    listed.unwrap_or_else(|| {
+        fs::read_to_string(topdir.join(".west/config"))
+            .map(|c| parse_west_config(&c))
+            .unwrap_or_default()
+    })
+}
+
+/// `west config --list` prints one `section.key=value` per line
+pub fn parse_west_config_list(output: &str) -> HashMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+/// `NAME:TYPE=value` from a CMakeCache.txt
+pub fn cmake_cache_value<'a>(cache: &'a str, name: &str) -> Option<&'a str> {
+    cache.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        let key = key.split(':').next()?;
+        (key == name && !value.is_empty()).then_some(value)
+    })
+}
+
+/// Boards built in `build*/` and `build/*/` under the workspace and the
+/// project, newest first, plus the configured default board, as targets
+pub fn collect_west_targets(project: &ProjectContext) -> Vec<BuildTarget> {
+    let topdir = find_west_topdir(&project.root);
+    let has_manifest = project.root.join("west.yml").is_file();
+    if !project_type_is(project, "zephyr") && topdir.is_none() && !has_manifest {
+        return Vec::new();
+    }
+    let config = topdir.as_deref().map(read_west_config).unwrap_or_default();
+
+    let mut dirs = vec![project.root.clone()];
+    dirs.extend(topdir.filter(|t| *t != project.root));
+    let mut caches: Vec<(SystemTime, PathBuf)> = Vec::new();
+    for dir in &dirs {
+        for pattern in ["build*/CMakeCache.txt", "build/*/CMakeCache.txt"] {
+            let full_pattern = format!("{}/{}", dir.display(), pattern);
+            for path in glob::glob(&full_pattern).into_iter().flatten().flatten() {
+                if !caches.iter().any(|(_, p)| *p == path) {
+                    caches.push((file_mtime(&path).unwrap_or(SystemTime::UNIX_EPOCH), path));
+                }
+            }
+        }
+    }
+    caches.sort_by_key(|(mtime, _)| std::cmp::Reverse(*mtime));
+
+    let mut targets: Vec<BuildTarget> = Vec::new();
+    for (mtime, path) in caches {
+        let Ok(cache) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let Some(board) = cmake_cache_value(&cache, "BOARD")
+            .or_else(|| cmake_cache_value(&cache, "CACHED_BOARD"))
+        else {
+            continue;
+        };
+        // Newest build of a board wins
+        if targets.iter().any(|t| t.name == board) {
+            continue;
+        }
+
+        let build_dir = path.parent().unwrap_or(&path);
+        let build_dir = build_dir.strip_prefix(&project.root).unwrap_or(build_dir);
+        let built = format_age(
+            &chrono::DateTime::<chrono::Utc>::from(mtime).to_rfc3339(),
+            chrono::Utc::now(),
+            configured_language(&project.config),
+        );
+        let description = match cmake_cache_value(&cache, "APPLICATION_SOURCE_DIR") {
+            Some(app) => {
+                let app = Path::new(app);
+                let app = app.strip_prefix(&project.root).unwrap_or(app);
+                format!(
+                    "{} in {}, built {}",
+                    app.display(),
+                    build_dir.display(),
+                    built
+                )
+            }
+            None => format!("{}, built {}", build_dir.display(), built),
+        };
+        targets.push(BuildTarget {
+            name: board.to_string(),
+            source: "west".to_string(),
+            source_file: display_path(&path),
+            description,
+            can_flash: true,
+            ..Default::default()
+        });
+        if targets.len() == MAX_WEST_BUILDS {
+            break;
+        }
+    }
+
+    if let Some(board) = config.get("build.board").filter(|b| !b.is_empty()) {
+        if !targets.iter().any(|t| &t.name == board) {
+            targets.push(BuildTarget {
+                name: board.clone(),
+                source: "west".to_string(),
+                source_file: ".west/config".to_string(),
+                description: "default board (build.board), not built yet".to_string(),
+                can_flash: true,
+                ..Default::default()
+            });
+        }
+    }
+    targets
+}
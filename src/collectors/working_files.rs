@@ -0,0 +1,449 @@
+//! Saved working files checked against the tree as it is now
+//!
+//! A work state can outlive its files: the branch gets merged, a file is
+//! renamed, the change is committed. Each saved file is looked up on disk
+//! and in `git status` of the repository holding it (the innermost one
+//! with a `.git` under the project root). Commits since the save come
+//! from `git log <head>..HEAD`, with the head recorded in
+//! `WorkingRepo::head`, or `git log --since=<saved_at>` for states saved
+//! without one. One `git status` and one `git log` per repository.
+//!
+//! `read_working_file` serves an excerpt of one of these files, or of a
+//! file the project's repositories show as changed, and nothing else.
+
+use crate::collectors::git::git_in;
+use crate::collectors::{collector_capped, CommandRunner};
+use crate::config::ProjectContext;
+use crate::error::ContextKeeperError;
+use crate::limits::{overflow_summary, Limits};
+use crate::paths::{display_path, project_relative, NON_UTF8_MARKER, OUTSIDE_ROOT_MARKER};
+use crate::state::{collect_working_files, load_work_state_with_hooks, WorkState};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Limit on each `git status` and `git log`
+pub const WORKING_FILES_GIT_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkingFileState {
+    /// Gone from disk
+    Missing,
+    /// On disk without changes: committed, stashed or reverted
+    Unchanged,
+    /// Still shows in `git status`
+    Modified,
+    /// On disk, saved from `[recent_files]`: git can't tell
+    NotInGit,
+}
+
+impl WorkingFileState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WorkingFileState::Missing => "missing",
+            WorkingFileState::Unchanged => "unchanged since save",
+            WorkingFileState::Modified => "still modified",
+            WorkingFileState::NotInGit => "not in git",
+        }
+    }
+}
+
+/// One entry of `WorkState::working_files` as found now
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkingFileCheck {
+    /// As saved
+    pub file: String,
+    pub state: WorkingFileState,
+    /// Newest commit since the save that touched the file, abbreviated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+}
+
+/// Abbreviated HEAD of `repo`, empty without one
+pub fn head_commit(runner: &dyn CommandRunner, repo: &Path) -> String {
+    git_in(
+        runner,
+        repo,
+        &["rev-parse", "--short", "HEAD"],
+        Some(WORKING_FILES_GIT_TIMEOUT),
+    )
+    .ok()
+    .filter(|output| output.status.success())
+    .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    .unwrap_or_default()
+}
+
+/// Innermost directory with a `.git` from `path` up to the project root;
+/// `path` itself may be gone
+pub fn holding_repo(root: &Path, path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(root))
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// Paths with changes (staged, unstaged or untracked), relative to `repo`,
+/// and how many went over `max`. An untracked directory is listed with a
+/// trailing `/`. `None` when git failed.
+pub fn changed_paths(
+    runner: &dyn CommandRunner,
+    repo: &Path,
+    max: usize,
+) -> Option<(HashSet<String>, usize)> {
+    let output = git_in(
+        runner,
+        repo,
+        &["status", "--porcelain=v1", "-z", "--untracked-files=normal"],
+        Some(WORKING_FILES_GIT_TIMEOUT),
+    )
+    .ok()
+    .filter(|output| output.status.success())?;
+    let mut paths = HashSet::new();
+    let mut more = 0;
+    let mut entries = output.stdout.split(|&b| b == 0);
+    while let Some(entry) = entries.next() {
+        if entry.len() < 4 {
+            continue;
+        }
+        if paths.len() < max {
+            paths.insert(String::from_utf8_lossy(&entry[3..]).into_owned());
+        } else {
+            more += 1;
+        }
+        // A rename or copy is followed by its source path
+        if matches!(entry[0], b'R' | b'C') {
+            entries.next();
+        }
+    }
+    Some((paths, more))
+}
+
+/// Newest commit touching each path since the save, by path relative to
+/// `repo`
+fn commits_since(
+    runner: &dyn CommandRunner,
+    repo: &Path,
+    head: &str,
+    saved_at: &str,
+) -> HashMap<String, String> {
+    let range = format!("{}..HEAD", head);
+    let since = format!("--since={}", saved_at);
+    let log = |selector: &str| {
+        git_in(
+            runner,
+            repo,
+            &[
+                "-c",
+                "core.quotepath=off",
+                "log",
+                "--format=@%h %ct",
+                "--name-only",
+                selector,
+            ],
+            Some(WORKING_FILES_GIT_TIMEOUT),
+        )
+        .ok()
+        .filter(|output| output.status.success())
+    };
+    // A head that is gone (rebased, garbage collected) falls back to the date
+    let by_range = if head.is_empty() { None } else { log(&range) };
+    // `--since` takes in the second of the save too; the range is exact
+    let (output, saved) = match by_range {
+        Some(output) => (output, i64::MIN),
+        None => match log(&since) {
+            Some(output) => (
+                output,
+                chrono::DateTime::parse_from_rfc3339(saved_at).map_or(0, |t| t.timestamp()),
+            ),
+            None => return HashMap::new(),
+        },
+    };
+    let mut commits = HashMap::new();
+    let mut commit = None;
+    // Newest first, so the first commit seen per path wins
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        match line.strip_prefix('@') {
+            Some(header) => {
+                let (hash, time) = header.split_once(' ').unwrap_or((header, ""));
+                commit = (time.parse::<i64>().unwrap_or(i64::MAX) > saved).then_some(hash);
+            }
+            None if !line.is_empty() => {
+                let Some(commit) = commit else {
+                    continue;
+                };
+                commits
+                    .entry(line.to_string())
+                    .or_insert_with(|| commit.to_string());
+            }
+            None => {}
+        }
+    }
+    commits
+}
+
+/// What became of each of `state`'s working files, in saved order. Files
+/// saved from `[recent_files]` are only checked for being gone, as git
+/// says nothing about them. A name saved with `NON_UTF8_MARKER` no longer
+/// spells the file, so it is not checked rather than called missing.
+pub fn check_working_files(project: &ProjectContext, state: &WorkState) -> Vec<WorkingFileCheck> {
+    struct RepoState {
+        changed: Option<HashSet<String>>,
+        /// `git status` listed more than `max_status_lines` paths
+        capped: bool,
+        commits: HashMap<String, String>,
+    }
+    let runner = &*project.runner;
+    let max_paths = Limits::from_config(&project.config).max_status_lines;
+    let mut repos: HashMap<PathBuf, RepoState> = HashMap::new();
+    state
+        .working_files
+        .iter()
+        .filter(|file| !file.ends_with(NON_UTF8_MARKER))
+        .map(|file| {
+            let path = project.resolve(file);
+            let exists = path.exists();
+            if state.recent_files.contains(file) {
+                return WorkingFileCheck {
+                    file: file.clone(),
+                    state: if exists {
+                        WorkingFileState::NotInGit
+                    } else {
+                        WorkingFileState::Missing
+                    },
+                    commit: None,
+                };
+            }
+            let Some(repo_dir) = holding_repo(&project.root, &path) else {
+                return WorkingFileCheck {
+                    file: file.clone(),
+                    state: if exists {
+                        WorkingFileState::Unchanged
+                    } else {
+                        WorkingFileState::Missing
+                    },
+                    commit: None,
+                };
+            };
+            let repo = repos.entry(repo_dir.clone()).or_insert_with(|| {
+                let repo_path = project_relative(&project.root, &repo_dir);
+                let head = state
+                    .working_repos
+                    .iter()
+                    .find(|repo| repo.path == repo_path)
+                    .map_or("", |repo| repo.head.as_str());
+                let changed = changed_paths(runner, &repo_dir, max_paths);
+                let more = changed.as_ref().map_or(0, |(_, more)| *more);
+                if more > 0 {
+                    collector_capped(format!(
+                        "working_files: {}: {}",
+                        repo_path,
+                        overflow_summary(more, "changed path", "changed paths", "max_status_lines")
+                    ));
+                }
+                RepoState {
+                    changed: changed.map(|(paths, _)| paths),
+                    capped: more > 0,
+                    commits: commits_since(runner, &repo_dir, head, &state.saved_at),
+                }
+            });
+            let in_repo = display_path(path.strip_prefix(&repo_dir).unwrap_or(&path));
+            // Past the cap, a file not listed may still be changed
+            let modified = repo.changed.as_ref().is_some_and(|changed| {
+                repo.capped
+                    || changed.contains(&in_repo)
+                    || changed
+                        .iter()
+                        .any(|dir| dir.ends_with('/') && in_repo.starts_with(dir.as_str()))
+            });
+            WorkingFileCheck {
+                file: file.clone(),
+                state: match (exists, modified) {
+                    (false, _) => WorkingFileState::Missing,
+                    (true, true) => WorkingFileState::Modified,
+                    (true, false) => WorkingFileState::Unchanged,
+                },
+                commit: repo.commits.get(&in_repo).cloned(),
+            }
+        })
+        .collect()
+}
+
+/// Lines in an excerpt when the caller doesn't say, and at most
+pub const DEFAULT_EXCERPT_LINES: usize = 80;
+pub const MAX_EXCERPT_LINES: usize = 400;
+
+/// Bytes read from a working file; lines past them aren't served
+pub const MAX_EXCERPT_READ_BYTES: u64 = 2 * 1024 * 1024;
+
+/// Characters kept of one line
+pub const MAX_EXCERPT_LINE_CHARS: usize = 400;
+
+/// Bytes checked for a NUL to tell a binary file
+const BINARY_SNIFF_BYTES: usize = 8192;
+
+/// Part of a working file, as `read_working_file` found it
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkingFileExcerpt {
+    /// In `project_relative` form
+    pub path: String,
+    /// From `git status`, e.g. "modified, not staged"
+    pub git_status: String,
+    /// 1-based, inclusive; 0 and 0 for an empty file
+    pub first_line: usize,
+    pub last_line: usize,
+    /// Lines in the part that was read
+    pub total_lines: usize,
+    /// The file is longer than `MAX_EXCERPT_READ_BYTES`
+    pub truncated: bool,
+    pub lines: Vec<String>,
+}
+
+/// `git status` of one file in words, from its porcelain v1 `XY` code
+pub fn describe_status(code: &str) -> String {
+    let mut chars = code.chars();
+    let (x, y) = (chars.next().unwrap_or(' '), chars.next().unwrap_or(' '));
+    match (x, y) {
+        (' ', ' ') => "no uncommitted changes".to_string(),
+        ('?', '?') => "untracked".to_string(),
+        ('U', _) | (_, 'U') | ('A', 'A') | ('D', 'D') => "conflicted".to_string(),
+        (' ', _) => "modified, not staged".to_string(),
+        (_, ' ') => "staged".to_string(),
+        _ => "staged, with further unstaged changes".to_string(),
+    }
+}
+
+/// `git status` of `path` in the innermost repository holding it
+fn file_git_status(project: &ProjectContext, path: &Path) -> String {
+    let Some(repo) = holding_repo(&project.root, path) else {
+        return "not in a git repository".to_string();
+    };
+    let in_repo = display_path(path.strip_prefix(&repo).unwrap_or(path));
+    git_in(
+        &*project.runner,
+        &repo,
+        &["status", "--porcelain=v1", "--", &in_repo],
+        Some(WORKING_FILES_GIT_TIMEOUT),
+    )
+    .ok()
+    .filter(|output| output.status.success())
+    .map_or_else(
+        || "git status unavailable".to_string(),
+        |output| {
+            describe_status(
+                String::from_utf8_lossy(&output.stdout)
+                    .get(..2)
+                    .unwrap_or(""),
+            )
+        },
+    )
+}
+
+/// Whether `path` may be served: a saved working file, or changed in one
+/// of the project's repositories. The saved state is checked first, as it
+/// needs no git.
+fn is_working_file(project: &ProjectContext, path: &str) -> Result<bool, ContextKeeperError> {
+    let saved = load_work_state_with_hooks(&project.root)?;
+    if saved.is_some_and(|state| state.working_files.iter().any(|file| file == path)) {
+        return Ok(true);
+    }
+    Ok(collect_working_files(project)
+        .files
+        .iter()
+        .any(|file| file == path))
+}
+
+/// An excerpt of the working file `path` (as the context names it, or any
+/// spelling of the same path): `max_lines` lines from the top, or centered
+/// on `around_line`. Other files, files outside the project root (also
+/// through a symlink) and binary files are refused as validation errors.
+pub fn read_working_file(
+    project: &ProjectContext,
+    path: &str,
+    max_lines: Option<usize>,
+    around_line: Option<usize>,
+) -> Result<WorkingFileExcerpt, ContextKeeperError> {
+    let path = project_relative(&project.root, Path::new(path));
+    if path.ends_with(OUTSIDE_ROOT_MARKER) {
+        return Err(ContextKeeperError::Validation(format!(
+            "{} is outside the project root",
+            path
+        )));
+    }
+    if !is_working_file(project, &path)? {
+        return Err(ContextKeeperError::Validation(format!(
+            "{} is neither a saved working file nor changed in git; only those can be read",
+            path
+        )));
+    }
+    let full = project.resolve(&path);
+    let root = project
+        .root
+        .canonicalize()
+        .unwrap_or_else(|_| project.root.clone());
+    match full.canonicalize() {
+        Ok(real) if real.starts_with(&root) => {}
+        Ok(_) => {
+            return Err(ContextKeeperError::Validation(format!(
+                "{} resolves to a file outside the project root",
+                path
+            )))
+        }
+        Err(e) => return Err(ContextKeeperError::state_io(&full, e)),
+    }
+
+    let file = fs::File::open(&full).map_err(|e| ContextKeeperError::state_io(&full, e))?;
+    let length = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let mut bytes = Vec::new();
+    file.take(MAX_EXCERPT_READ_BYTES)
+        .read_to_end(&mut bytes)
+        .map_err(|e| ContextKeeperError::state_io(&full, e))?;
+    if bytes[..bytes.len().min(BINARY_SNIFF_BYTES)].contains(&0) {
+        return Err(ContextKeeperError::Validation(format!(
+            "{} is a binary file",
+            path
+        )));
+    }
+    let truncated = length > MAX_EXCERPT_READ_BYTES;
+    let text = String::from_utf8_lossy(&bytes);
+    let mut all: Vec<&str> = text.lines().collect();
+    // A line cut off by the read limit isn't shown
+    if truncated && !text.ends_with('\n') {
+        all.pop();
+    }
+
+    let count = max_lines
+        .unwrap_or(DEFAULT_EXCERPT_LINES)
+        .clamp(1, MAX_EXCERPT_LINES);
+    let start = match around_line {
+        Some(line) => line
+            .saturating_sub(1)
+            .saturating_sub(count / 2)
+            .min(all.len().saturating_sub(count)),
+        None => 0,
+    };
+    let end = (start + count).min(all.len());
+    let lines = all[start..end]
+        .iter()
+        .map(
+            |line| match line.char_indices().nth(MAX_EXCERPT_LINE_CHARS) {
+                Some((cut, _)) => format!("{}…", &line[..cut]),
+                None => line.to_string(),
+            },
+        )
+        .collect();
+
+    Ok(WorkingFileExcerpt {
+        git_status: file_git_status(project, &full),
+        first_line: if end > start { start + 1 } else { 0 },
+        last_line: end,
+        total_lines: all.len(),
+        truncated,
+        lines,
+        path,
+    })
+}
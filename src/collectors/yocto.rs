@@ -0,0 +1,195 @@
+//! Yocto build directory: local.conf and layers
+
+use crate::collectors::packages::project_type_is;
+use crate::config::{file_mtime, ProjectContext};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct YoctoInfo {
+    /// Build directory, relative to the project root when inside it
+    pub build_dir: String,
+    pub machine: String,
+    pub distro: String,
+    pub bb_number_threads: String,
+    pub dl_dir: String,
+    /// Layer paths from bblayers.conf
+    pub layers: Vec<String>,
+    /// `oe-init-build-env` sourced for this build directory (BUILDDIR matches)
+    pub env_sourced: bool,
+    /// `tmp/deploy/images/<machine>` (relative like build_dir), when it exists
+    pub deploy_dir: String,
+    pub deploy_size_bytes: u64,
+    /// Newest file in the deploy directory (RFC 3339)
+    pub deploy_modified: Option<String>,
+}
+
+/// Variable values from a BitBake conf file, following its operator precedence:
+/// `=`/`:=` always set, `?=` sets only when unset, `??=` is a weak default
+/// used only when nothing else sets the variable. `+=` appends.
+pub fn parse_bitbake_conf(content: &str) -> HashMap<String, String> {
+    let content = content.replace("\\\r\n", " ").replace("\\\n", " ");
+    let mut values: HashMap<String, String> = HashMap::new();
+    let mut weak: HashMap<String, String> = HashMap::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some(eq) = line.find('=') else {
+            continue;
+        };
+        let (lhs, rhs) = (&line[..eq], line[eq + 1..].trim());
+        let (name, op) = ["??", "?", ":", "+", ".", ""]
+            .iter()
+            .find_map(|op| {
+                lhs.trim_end()
+                    .strip_suffix(op)
+                    .map(|name| (name.trim(), *op))
+            })
+            .unwrap_or((lhs.trim(), ""));
+        let name = name.strip_prefix("export ").unwrap_or(name).trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            // Overrides (MACHINE:pn-foo), flags (VAR[doc]) and the like
+            continue;
+        }
+
+        let value = rhs.trim_matches('"').trim_matches('\'').trim();
+        // Inline python can't be evaluated; skip it rather than show code
+        if value.contains("${@") {
+            continue;
+        }
+        let value = value.to_string();
+        match op {
+            "??" => {
+                weak.insert(name.to_string(), value);
+            }
+            "?" => {
+                values.entry(name.to_string()).or_insert(value);
+            }
+            "+" | "." => {
+                let entry = values.entry(name.to_string()).or_default();
+                if !entry.is_empty() && op == "+" {
+                    entry.push(' ');
+                }
+                entry.push_str(&value);
+            }
+            _ => {
+                values.insert(name.to_string(), value);
+            }
+        }
+    }
+
+    for (name, value) in weak {
+        values.entry(name).or_insert(value);
+    }
+    values
+}
+
+/// `yocto.build_dir` when set, else the most recently used `build*/` with a
+/// conf/local.conf that `.contextkeeper-ignore` doesn't match
+pub fn find_yocto_build_dir(project: &ProjectContext) -> Option<PathBuf> {
+    if let Some(dir) = project
+        .config
+        .yocto
+        .as_ref()
+        .and_then(|y| y.build_dir.as_deref())
+    {
+        return Some(project.resolve(dir)).filter(|d| d.join("conf/local.conf").is_file());
+    }
+
+    let pattern = format!("{}/build*/conf/local.conf", project.root.display());
+    glob::glob(&pattern)
+        .ok()?
+        .flatten()
+        .filter(|conf| !project.ignore.is_ignored(conf, false))
+        .max_by_key(|conf| file_mtime(conf))
+        .and_then(|conf| Some(conf.parent()?.parent()?.to_path_buf()))
+}
+
+/// Total size and newest mtime of the regular files under `dir`
+pub fn dir_size_and_mtime(dir: &Path, depth: usize) -> (u64, Option<SystemTime>) {
+    let mut size = 0;
+    let mut newest = None;
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        // symlink_metadata: deploy dirs are full of links to the real images
+        let Ok(meta) = entry.path().symlink_metadata() else {
+            continue;
+        };
+        if meta.is_file() {
+            size += meta.len();
+            newest = newest.max(meta.modified().ok());
+        } else if meta.is_dir() && depth > 0 {
+            let (sub_size, sub_newest) = dir_size_and_mtime(&entry.path(), depth - 1);
+            size += sub_size;
+            newest = newest.max(sub_newest);
+        }
+    }
+    (size, newest)
+}
+
+pub fn collect_yocto_info(project: &ProjectContext) -> Option<YoctoInfo> {
+    if !project_type_is(project, "yocto") && project.config.yocto.is_none() {
+        return None;
+    }
+    let build_dir = find_yocto_build_dir(project)?;
+    let topdir = build_dir.to_string_lossy().to_string();
+    let expand = |value: &str| value.replace("${TOPDIR}", &topdir);
+
+    let local = fs::read_to_string(build_dir.join("conf/local.conf"))
+        .map(|c| parse_bitbake_conf(&c))
+        .unwrap_or_default();
+    let get = |name: &str| local.get(name).map(|v| expand(v)).unwrap_or_default();
+
+    let layers = fs::read_to_string(build_dir.join("conf/bblayers.conf"))
+        .map(|c| parse_bitbake_conf(&c))
+        .unwrap_or_default()
+        .get("BBLAYERS")
+        .map(|layers| layers.split_whitespace().map(expand).collect())
+        .unwrap_or_default();
+
+    let env_sourced = std::env::var_os("BUILDDIR")
+        .and_then(|dir| PathBuf::from(dir).canonicalize().ok())
+        .is_some_and(|dir| Some(dir) == build_dir.canonicalize().ok());
+
+    let mut info = YoctoInfo {
+        build_dir: build_dir
+            .strip_prefix(&project.root)
+            .unwrap_or(&build_dir)
+            .to_string_lossy()
+            .to_string(),
+        machine: get("MACHINE"),
+        distro: get("DISTRO"),
+        bb_number_threads: get("BB_NUMBER_THREADS"),
+        dl_dir: get("DL_DIR"),
+        layers,
+        env_sourced,
+        ..Default::default()
+    };
+
+    if !info.machine.is_empty() && !info.machine.contains("${") {
+        let tmpdir = local
+            .get("TMPDIR")
+            .map(|t| PathBuf::from(expand(t)))
+            .filter(|t| !t.to_string_lossy().contains("${"))
+            .unwrap_or_else(|| build_dir.join("tmp"));
+        let deploy = tmpdir.join("deploy/images").join(&info.machine);
+        if deploy.is_dir() {
+            let (size, newest) = dir_size_and_mtime(&deploy, 2);
+            info.deploy_dir = deploy
+                .strip_prefix(&project.root)
+                .unwrap_or(&deploy)
+                .to_string_lossy()
+                .to_string();
+            info.deploy_size_bytes = size;
+            info.deploy_modified =
+                newest.map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339());
+        }
+    }
+
+    Some(info)
+}
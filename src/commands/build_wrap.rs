@@ -0,0 +1,212 @@
+//! `context-keeper build-wrap -- <cmd>`: run a build and record how it went
+//!
+//! The command's output passes straight through; the wrapper keeps the
+//! last `[builds] output_lines` lines and, when the command exits, appends
+//! a `BuildRecord` to the project's `builds.jsonl`. The target is
+//! `--target`, else the one the command line builds. The wrapper exits
+//! with the command's exit code, so it can stand in for the command in
+//! scripts and aliases.
+
+use crate::collectors::build_results::{
+    build_output_lines, build_records_kept, builds_log_path, BuildRecord,
+};
+use crate::collectors::entry_point::collect_entry_point;
+use crate::collectors::targets::{collect_build_targets, target_command};
+use crate::collectors::BuildTarget;
+use crate::commands::hooks::shell_quote;
+use crate::commands::log::log_root;
+use crate::config::ProjectContext;
+use crate::redact::Redactor;
+use crate::sessions::{lock_shared_file, write_atomic};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+/// Exit code when the command can't be started, as a shell reports it
+pub const EXIT_NOT_RUN: i32 = 127;
+
+/// `argv` as one shell-quoted command line
+pub fn command_line(argv: &[String]) -> String {
+    let words: Vec<String> = argv.iter().map(|arg| shell_quote(arg)).collect();
+    words.join(" ")
+}
+
+/// The build target `argv` runs: the one whose build command (as `export`
+/// writes it) it is, else the first whose name or lunch target is one of
+/// its arguments
+pub fn match_target(project: &ProjectContext, argv: &[String]) -> String {
+    let targets = collect_build_targets(project);
+    let (_, entry_point) = collect_entry_point(project);
+    let command = command_line(argv);
+    let built_by = |target: &&BuildTarget| {
+        target_command(target, entry_point.as_ref(), "make", &project.root)
+            .is_some_and(|cmd| cmd == command)
+    };
+    let named = |target: &&BuildTarget| {
+        argv.iter()
+            .skip(1)
+            .any(|arg| *arg == target.name || *arg == target.lunch_target)
+    };
+    targets
+        .iter()
+        .find(built_by)
+        .or_else(|| targets.iter().find(named))
+        .map(|target| target.name.clone())
+        .unwrap_or_default()
+}
+
+/// Last `limit` lines seen on either stream
+struct OutputTail {
+    lines: VecDeque<String>,
+    limit: usize,
+}
+
+impl OutputTail {
+    fn push(&mut self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line).trim_end().to_string();
+        if line.is_empty() || self.limit == 0 {
+            return;
+        }
+        if self.lines.len() == self.limit {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+}
+
+/// Copy `from` to `to` as it arrives, feeding complete lines to `tail`.
+/// A carriage return ends a line too, for progress counters.
+fn relay(mut from: impl Read, mut to: impl Write, tail: Arc<Mutex<OutputTail>>) {
+    let mut buf = [0u8; 8192];
+    let mut line: Vec<u8> = Vec::new();
+    while let Ok(n) = from.read(&mut buf) {
+        if n == 0 {
+            break;
+        }
+        let _ = to.write_all(&buf[..n]);
+        let _ = to.flush();
+        for &byte in &buf[..n] {
+            if byte == b'\n' || byte == b'\r' {
+                tail.lock().unwrap().push(&line);
+                line.clear();
+            } else {
+                line.push(byte);
+            }
+        }
+    }
+    tail.lock().unwrap().push(&line);
+}
+
+/// Append `record` to `path`, keeping the newest `keep` records
+fn append_record(path: &Path, record: &BuildRecord, keep: usize) -> io::Result<()> {
+    let _lock = lock_shared_file(path)?;
+    let mut lines: Vec<String> = match fs::File::open(path) {
+        Ok(file) => io::BufReader::new(file)
+            .lines()
+            .collect::<io::Result<_>>()?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    lines.push(serde_json::to_string(record).map_err(io::Error::other)?);
+    let keep = &lines[lines.len().saturating_sub(keep.max(1))..];
+    write_atomic(path, (keep.join("\n") + "\n").as_bytes())
+}
+
+/// Run `argv` in `dir` and record the result; returns the exit code to
+/// pass on
+pub fn run_build_wrap(
+    dir: &Path,
+    config_path: Option<PathBuf>,
+    target: Option<&str>,
+    argv: &[String],
+) -> i32 {
+    let project = ProjectContext::load(log_root(dir), config_path);
+    let tail = Arc::new(Mutex::new(OutputTail {
+        lines: VecDeque::new(),
+        limit: build_output_lines(&project.config),
+    }));
+
+    let started = Instant::now();
+    let mut child = match Command::new(&argv[0])
+        .args(&argv[1..])
+        .current_dir(dir)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("context-keeper: can't run {}: {}", argv[0], e);
+            return EXIT_NOT_RUN;
+        }
+    };
+    // Ctrl-C reaches the build too; outlive it to record the result
+    #[cfg(unix)]
+    // SAFETY: only changes this process's disposition, after the spawn
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_IGN);
+    }
+
+    let stdout = child.stdout.take().map(|out| {
+        let tail = Arc::clone(&tail);
+        thread::spawn(move || relay(out, io::stdout(), tail))
+    });
+    let stderr = child.stderr.take().map(|err| {
+        let tail = Arc::clone(&tail);
+        thread::spawn(move || relay(err, io::stderr(), tail))
+    });
+    let status = child.wait();
+    for relay in stdout.into_iter().chain(stderr) {
+        let _ = relay.join();
+    }
+
+    let exit_code = match status {
+        Ok(status) => match status.code() {
+            Some(code) => code,
+            #[cfg(unix)]
+            None => {
+                use std::os::unix::process::ExitStatusExt;
+                128 + status.signal().unwrap_or_default()
+            }
+            #[cfg(not(unix))]
+            None => 1,
+        },
+        Err(e) => {
+            eprintln!("context-keeper: lost track of {}: {}", argv[0], e);
+            1
+        }
+    };
+
+    let redactor = Redactor::from_config(&project.config);
+    let output = if exit_code == 0 {
+        Vec::new()
+    } else {
+        let tail = tail.lock().unwrap();
+        tail.lines
+            .iter()
+            .map(|line| redactor.redact(line).into_owned())
+            .collect()
+    };
+    let record = BuildRecord {
+        timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        target: match target {
+            Some(target) => target.to_string(),
+            None => match_target(&project, argv),
+        },
+        command: redactor.redact(&command_line(argv)).into_owned(),
+        exit_code,
+        duration_secs: started.elapsed().as_secs(),
+        output,
+    };
+    let path = builds_log_path(&project.root);
+    if let Err(e) = append_record(&path, &record, build_records_kept(&project.config)) {
+        tracing::warn!(path = %path.display(), error = %e, "failed to record the build");
+    }
+    exit_code
+}
@@ -0,0 +1,333 @@
+//! `context-keeper doctor`
+
+use crate::collectors::git::{collect_git_info_for_path, find_git_repos, StatusOptions};
+use crate::collectors::history::history_log_path;
+use crate::collectors::project_ignore::PROJECT_IGNORE_FILE;
+use crate::collectors::targets::glob_target_files;
+use crate::collectors::{run_program, SystemRunner};
+use crate::config::{find_config_file, Config, ProjectContext};
+use crate::layout::{data_layout, legacy_files, DataLayout, LAYOUT_VERSION};
+use crate::output::{print_json, JsonStyle};
+use crate::state::ensure_contextkeeper_dir;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckLevel {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DoctorCheck {
+    pub name: String,
+    pub level: CheckLevel,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl DoctorCheck {
+    pub fn new(name: &str, level: CheckLevel, detail: String, hint: Option<&str>) -> Self {
+        Self {
+            name: name.to_string(),
+            level,
+            detail,
+            hint: hint.map(|h| h.to_string()),
+        }
+    }
+}
+
+/// First line of `<program> <args>` output, if the program runs
+pub fn tool_version(program: &str, args: &[&str]) -> Option<String> {
+    let output = run_program(&SystemRunner, program, args).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Some(stdout.lines().next().unwrap_or("").trim().to_string())
+}
+
+/// Check tool availability. Missing tools fail only when an enabled
+/// collector depends on them.
+pub fn check_tool(program: &str, args: &[&str], required: bool, hint: &str) -> DoctorCheck {
+    match tool_version(program, args) {
+        Some(version) => DoctorCheck::new(program, CheckLevel::Pass, version, None),
+        None => DoctorCheck::new(
+            program,
+            if required {
+                CheckLevel::Fail
+            } else {
+                CheckLevel::Warn
+            },
+            "not found on PATH".to_string(),
+            Some(hint),
+        ),
+    }
+}
+
+pub fn run_doctor_checks(root: &Path, config_path: Option<PathBuf>) -> Vec<DoctorCheck> {
+    let mut checks = Vec::new();
+
+    // Config discovery and parse status
+    let config_path = config_path.or_else(|| find_config_file(root));
+    let config = match &config_path {
+        None => {
+            checks.push(DoctorCheck::new(
+                "config",
+                CheckLevel::Warn,
+                format!("no config file in {}", root.display()),
+                Some("Run `context-keeper init` to create contextkeeper.toml"),
+            ));
+            Config::default()
+        }
+        Some(path) => match Config::load(path) {
+            Ok(config) => {
+                checks.push(DoctorCheck::new(
+                    "config",
+                    CheckLevel::Pass,
+                    path.display().to_string(),
+                    None,
+                ));
+                config
+            }
+            Err(e) => {
+                checks.push(DoctorCheck::new(
+                    "config",
+                    CheckLevel::Fail,
+                    e.to_string(),
+                    Some("Fix the TOML syntax; `context-keeper check` shows the same error"),
+                ));
+                Config::default()
+            }
+        },
+    };
+
+    // External tools
+    let runtime = config
+        .containers
+        .as_ref()
+        .and_then(|c| c.runtime.as_deref());
+    checks.push(check_tool(
+        "git",
+        &["--version"],
+        true,
+        "Install git; the Git collector and working file detection need it",
+    ));
+    for candidate in ["podman", "docker"] {
+        checks.push(check_tool(
+            candidate,
+            &["--version"],
+            runtime == Some(candidate),
+            "Install it or change [containers] runtime in contextkeeper.toml",
+        ));
+    }
+    checks.push(check_tool(
+        "adb",
+        &["version"],
+        false,
+        "Install Android platform-tools to list connected devices",
+    ));
+    checks.push(check_tool(
+        "fastboot",
+        &["--version"],
+        false,
+        "Install Android platform-tools to list fastboot devices",
+    ));
+
+    // Build target configs
+    let project = ProjectContext::load(root.to_path_buf(), config_path);
+    for (pattern, files) in glob_target_files(&project) {
+        checks.push(match files {
+            Ok(files) if !files.is_empty() => DoctorCheck::new(
+                "config_dir",
+                CheckLevel::Pass,
+                format!("{} matches {} file(s)", pattern, files.len()),
+                None,
+            ),
+            Ok(_) => DoctorCheck::new(
+                "config_dir",
+                CheckLevel::Fail,
+                format!("{} matches no files", pattern),
+                Some("Check scripts.config_dir and scripts.config_pattern"),
+            ),
+            Err(e) => DoctorCheck::new(
+                "config_dir",
+                CheckLevel::Fail,
+                format!("{} is not a valid pattern: {}", pattern, e),
+                Some("Fix scripts.config_pattern"),
+            ),
+        });
+    }
+
+    // What the walking collectors skip
+    let ignore = &project.ignore;
+    let rules = match ignore.rules() {
+        1 => "1 rule".to_string(),
+        n => format!("{} rules", n),
+    };
+    checks.push(match (&ignore.path, &ignore.error) {
+        (None, _) => DoctorCheck::new(
+            "ignore_file",
+            CheckLevel::Pass,
+            format!("no {} in {}", PROJECT_IGNORE_FILE, root.display()),
+            None,
+        ),
+        (Some(path), None) => DoctorCheck::new(
+            "ignore_file",
+            CheckLevel::Pass,
+            format!("{} ({})", path.display(), rules),
+            None,
+        ),
+        (Some(_), Some(e)) => DoctorCheck::new(
+            "ignore_file",
+            CheckLevel::Warn,
+            format!("{} usable; {}", rules, e),
+            Some("Fix the patterns; the file uses gitignore syntax"),
+        ),
+    });
+
+    // History log
+    if let Some(history) = config
+        .history
+        .as_ref()
+        .filter(|h| h.enabled.unwrap_or(true))
+    {
+        let path = history_log_path(history);
+        let log_file = path.display().to_string();
+        checks.push(if !path.exists() {
+            DoctorCheck::new(
+                "history_log",
+                CheckLevel::Warn,
+                format!("{} does not exist yet", log_file),
+                Some("Install hooks/log-commands.sh as a PostToolUse hook for Bash"),
+            )
+        } else if fs::OpenOptions::new().append(true).open(&path).is_err() {
+            DoctorCheck::new(
+                "history_log",
+                CheckLevel::Fail,
+                format!("{} is not writable", log_file),
+                Some("Fix the file permissions so the hook can append to it"),
+            )
+        } else {
+            DoctorCheck::new("history_log", CheckLevel::Pass, log_file, None)
+        });
+    }
+
+    // State directory
+    checks.push(match ensure_contextkeeper_dir() {
+        Ok(_) => DoctorCheck::new(
+            "state_dir",
+            CheckLevel::Pass,
+            "~/.contextkeeper is writable".to_string(),
+            None,
+        ),
+        Err(e) => DoctorCheck::new(
+            "state_dir",
+            CheckLevel::Fail,
+            format!("cannot create ~/.contextkeeper: {}", e),
+            Some("Check that HOME is set and writable"),
+        ),
+    });
+
+    // Layout of the state directory
+    checks.push(match data_layout() {
+        Ok(DataLayout::Legacy) => DoctorCheck::new(
+            "layout",
+            CheckLevel::Warn,
+            "~/.contextkeeper uses the old layout".to_string(),
+            Some("Run `context-keeper migrate` (`--dry-run` lists the moves)"),
+        ),
+        Ok(DataLayout::Current) => match legacy_files().as_slice() {
+            [] => DoctorCheck::new(
+                "layout",
+                CheckLevel::Pass,
+                format!("layout {}", LAYOUT_VERSION),
+                None,
+            ),
+            leftover => DoctorCheck::new(
+                "layout",
+                CheckLevel::Warn,
+                format!(
+                    "left over from the old layout and not read: {}",
+                    leftover
+                        .iter()
+                        .map(|path| path.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                Some("Run `context-keeper migrate` to move them"),
+            ),
+        },
+        Err(e) => DoctorCheck::new(
+            "layout",
+            CheckLevel::Fail,
+            e.to_string(),
+            Some("Upgrade context-keeper, or fix ~/.contextkeeper/layout_version"),
+        ),
+    });
+
+    // Git repository
+    checks.push(
+        if collect_git_info_for_path(
+            &*project.runner,
+            root,
+            &StatusOptions::from_config(&project.config),
+        )
+        .is_some()
+        {
+            DoctorCheck::new(
+                "git_repo",
+                CheckLevel::Pass,
+                format!("{} is inside a git repository", root.display()),
+                None,
+            )
+        } else {
+            let found = find_git_repos(root, 2, &project.ignore).len();
+            DoctorCheck::new(
+                "git_repo",
+                if found > 0 {
+                    CheckLevel::Pass
+                } else {
+                    CheckLevel::Warn
+                },
+                format!("not a git repository; {} repo(s) found below", found),
+                (found == 0).then_some("Set [git] paths or increase scan_depth"),
+            )
+        },
+    );
+
+    checks
+}
+
+/// Run the doctor checks; returns false if any check failed
+pub fn run_doctor(root: &Path, config_path: Option<PathBuf>, json: Option<JsonStyle>) -> bool {
+    let checks = run_doctor_checks(root, config_path);
+    let ok = checks.iter().all(|c| c.level != CheckLevel::Fail);
+
+    if let Some(style) = json {
+        let report = serde_json::json!({ "ok": ok, "checks": checks });
+        if let Err(e) = print_json(&report, style) {
+            eprintln!("Error: {}", e);
+        }
+        return ok;
+    }
+
+    println!("\n🩺 ContextKeeper Doctor\n");
+    for check in &checks {
+        let icon = match check.level {
+            CheckLevel::Pass => "✅",
+            CheckLevel::Warn => "⚠️ ",
+            CheckLevel::Fail => "❌",
+        };
+        println!("{} {}: {}", icon, check.name, check.detail);
+        if let Some(hint) = &check.hint {
+            println!("     → {}", hint);
+        }
+    }
+
+    ok
+}
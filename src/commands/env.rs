@@ -0,0 +1,195 @@
+//! `context-keeper env`: what the context knows, as shell variables
+//!
+//! For wrapper scripts: `eval "$(context-keeper env --target qcom-dev)"`
+//! sets the variables of `ENV_VARS`, always all of them, in that order.
+//! Every value is single-quoted. A value that isn't known is unset rather
+//! than set empty, with a comment saying why, so `${CK_DEVICE_SERIAL:?}`
+//! fails loudly instead of passing an empty serial on.
+
+use crate::collectors::BuildTarget;
+use crate::config::ProjectContext;
+use crate::context::{collect_context, tool_deadline, Availability, CollectionPlan, Context};
+use crate::error::ContextKeeperError;
+use crate::format::{section_availability, Section};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+
+/// The variables printed, in order
+pub const ENV_VARS: [&str; 5] = [
+    "CK_PROJECT_NAME",
+    "CK_TARGET_CONTAINER",
+    "CK_TARGET_LUNCH",
+    "CK_DEVICE_SERIAL",
+    "CK_GIT_DIRTY_REPOS",
+];
+
+/// Shell syntax to print the variables in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum EnvFormat {
+    /// `export NAME='value'`, for sh, bash and zsh
+    #[default]
+    Sh,
+    /// `set -gx NAME 'value'`
+    Fish,
+}
+
+/// Flags for `context-keeper env`
+#[derive(Debug, clap::Args)]
+pub struct EnvOptions {
+    /// Build target the CK_TARGET_* variables describe
+    #[arg(long)]
+    pub target: Option<String>,
+    /// Shell syntax
+    #[arg(long, value_enum, default_value_t = EnvFormat::Sh)]
+    pub format: EnvFormat,
+}
+
+/// A variable's value, or why it has none
+pub type EnvValue = Result<String, String>;
+
+/// `value` in single quotes for `format`
+pub fn quote_env_value(value: &str, format: EnvFormat) -> String {
+    match format {
+        EnvFormat::Sh => format!("'{}'", value.replace('\'', r"'\''")),
+        EnvFormat::Fish => format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'")),
+    }
+}
+
+/// One line per variable: an assignment, or an unset with the reason
+pub fn format_env(vars: &[(&str, EnvValue)], format: EnvFormat) -> String {
+    let mut out = String::new();
+    for (name, value) in vars {
+        let line = match (value, format) {
+            (Ok(value), EnvFormat::Sh) => {
+                format!("export {}={}", name, quote_env_value(value, format))
+            }
+            (Ok(value), EnvFormat::Fish) => {
+                format!("set -gx {} {}", name, quote_env_value(value, format))
+            }
+            // A newline in the reason would end the comment
+            (Err(why), EnvFormat::Sh) => format!("unset {}  # {}", name, why.replace('\n', " ")),
+            (Err(why), EnvFormat::Fish) => format!("set -e {}  # {}", name, why.replace('\n', " ")),
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+/// A field of the `--target` target, `what` naming it when it's empty
+fn target_value(
+    target: Option<&BuildTarget>,
+    field: &dyn Fn(&BuildTarget) -> &String,
+    what: &str,
+) -> EnvValue {
+    match target {
+        None => Err("no --target given".to_string()),
+        Some(target) if field(target).is_empty() => {
+            Err(format!("target {} has no {}", target.name, what))
+        }
+        Some(target) => Ok(field(target).clone()),
+    }
+}
+
+/// Why `section` came back empty: `nothing` when it was asked, else
+/// what stopped it, e.g. "devices unavailable: adb not installed"
+fn why_empty(ctx: &Context, section: Section, nothing: &str) -> String {
+    match section_availability(ctx, section) {
+        Some(Availability::Unavailable(reason)) => {
+            format!("{} unavailable: {}", section.name(), reason)
+        }
+        Some(Availability::Disabled) => format!("{} turned off in the config", section.name()),
+        Some(Availability::TimedOut) => format!("{} timed out", section.name()),
+        _ => nothing.to_string(),
+    }
+}
+
+/// The `ENV_VARS` of `ctx`, with `target` for the CK_TARGET_* ones. Fails
+/// listing the known targets when `target` isn't one of them.
+pub fn env_vars(
+    ctx: &Context,
+    target: Option<&str>,
+) -> Result<Vec<(&'static str, EnvValue)>, ContextKeeperError> {
+    let target = match target {
+        Some(name) => match ctx.targets.iter().find(|t| t.name == name) {
+            Some(target) => Some(target),
+            None => {
+                let known: Vec<&str> = ctx.targets.iter().map(|t| t.name.as_str()).collect();
+                return Err(ContextKeeperError::Validation(if known.is_empty() {
+                    format!("unknown target '{}'; no build targets were found", name)
+                } else {
+                    format!(
+                        "unknown target '{}'; known targets: {}",
+                        name,
+                        known.join(", ")
+                    )
+                }));
+            }
+        },
+        None => None,
+    };
+
+    let project_name = if ctx.project_name.is_empty() {
+        Err("no [project] name in the config".to_string())
+    } else {
+        Ok(ctx.project_name.clone())
+    };
+    let container = target_value(target, &|t| &t.container_name, "container");
+    let lunch = target_value(target, &|t| &t.lunch_target, "lunch target");
+
+    // The pinned device, else the only one connected, which bare adb uses
+    let device_serial = match &ctx.device_selection {
+        Some(selection) => match (&selection.serial, &selection.pinned) {
+            (Some(serial), _) => Ok(serial.clone()),
+            (None, Some(pinned)) => Err(format!("pinned device {} is not connected", pinned)),
+            (None, None) => Err(format!(
+                "ANDROID_SERIAL={} is not connected",
+                selection.android_serial.as_deref().unwrap_or_default()
+            )),
+        },
+        None => match ctx.adb_devices.as_slice() {
+            [device] => Ok(device.serial.clone()),
+            [] => Err(why_empty(ctx, Section::Devices, "no device connected")),
+            _ => Err(format!(
+                "{} devices connected and none pinned by [adb] or ANDROID_SERIAL",
+                ctx.adb_devices.len()
+            )),
+        },
+    };
+
+    let dirty_repos = if ctx.git_repos.is_empty() {
+        Err(why_empty(ctx, Section::Git, "no git repositories found"))
+    } else {
+        let dirty: Vec<&str> = ctx
+            .git_repos
+            .iter()
+            .filter(|repo| repo.is_dirty)
+            .map(|repo| repo.repo_path.as_str())
+            .collect();
+        Ok(dirty.join(" "))
+    };
+
+    Ok(ENV_VARS
+        .into_iter()
+        .zip([project_name, container, lunch, device_serial, dirty_repos])
+        .collect())
+}
+
+/// Collect what `ENV_VARS` need and print them
+pub async fn run_env(
+    opts: &EnvOptions,
+    project: Arc<ProjectContext>,
+) -> Result<(), ContextKeeperError> {
+    let sections = [
+        Section::Project,
+        Section::Targets,
+        Section::Devices,
+        Section::Git,
+    ];
+    let plan = CollectionPlan::for_sections(&sections, &project.config);
+    let deadline = tool_deadline(&project.config);
+    let ctx = collect_context(project, plan, deadline, CancellationToken::new()).await;
+    let vars = env_vars(&ctx, opts.target.as_deref())?;
+    print!("{}", format_env(&vars, opts.format));
+    Ok(())
+}
@@ -0,0 +1,587 @@
+//! `context-keeper export`: build targets as editor task definitions, and
+//! the whole context as an HTML page
+//!
+//! `vscode-tasks` merges into an existing `tasks.json`, which VS Code
+//! allows to hold comments and trailing commas. Only the generated tasks
+//! (marked with a `contextkeeper` key) are rewritten; the rest of the file
+//! is copied through unchanged.
+
+use crate::collectors::entry_point::{collect_entry_point, EntryPointInfo};
+use crate::collectors::make::{find_makefile, MAKEFILE_NAMES};
+use crate::collectors::targets::{collect_build_targets, target_command};
+use crate::collectors::west::collect_west_targets;
+use crate::collectors::{BuildTarget, COLLECTOR_STATS};
+use crate::commands::hooks::shell_quote;
+use crate::commands::safe_write::{backup_note, write_file, WriteArgs, Written};
+#[cfg(feature = "watch")]
+use crate::commands::watch::watch_context;
+use crate::config::ProjectContext;
+use crate::context::{annotate_context, collect_context, tool_deadline, CollectionPlan};
+use crate::demo::demo_context;
+use crate::format::html::format_html;
+use crate::format::Level;
+use crate::i18n::configured_language;
+use crate::paths::display_path;
+use crate::redact::Redactor;
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+
+/// Key marking the tasks we generated, replaced on every export
+pub const TASK_MARKER: &str = "contextkeeper";
+
+#[derive(Debug, clap::Args)]
+pub struct VscodeTasksOptions {
+    /// File to merge into (default: .vscode/tasks.json in the project)
+    #[arg(long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+    /// Print the resulting tasks.json instead of writing it
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct HtmlOptions {
+    /// File to write
+    #[arg(long, value_name = "FILE", default_value = "context.html")]
+    pub output: PathBuf,
+    /// Keep running and rewrite the file whenever the context may have changed
+    #[arg(long)]
+    pub watch: bool,
+    /// With --watch, seconds between polls for containers and devices
+    #[arg(long, default_value_t = 10)]
+    pub interval: u64,
+    /// Render the built-in demo context instead of collecting anything
+    #[arg(long, conflicts_with = "watch")]
+    pub demo: bool,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum ExportAction {
+    /// Write build targets and entry-point commands as VS Code tasks
+    VscodeTasks(VscodeTasksOptions),
+    /// Write the full context as a self-contained HTML dashboard
+    Html(HtmlOptions),
+}
+
+fn task(label: String, detail: &str, command: String, source: &str) -> serde_json::Value {
+    let mut task = serde_json::json!({
+        "label": label,
+        "type": "shell",
+        "command": command,
+        "group": "build",
+        "problemMatcher": [],
+    });
+    if !detail.is_empty() {
+        task["detail"] = serde_json::Value::from(detail.trim());
+    }
+    task[TASK_MARKER] = serde_json::json!({ "source": source });
+    task
+}
+
+/// Task definitions for the build targets, then the entry point's
+/// subcommands
+pub fn vscode_tasks(
+    targets: &[BuildTarget],
+    entry_point: Option<&EntryPointInfo>,
+    makefile: Option<&Path>,
+    root: &Path,
+) -> Vec<serde_json::Value> {
+    let make = match makefile {
+        Some(path)
+            if path.parent() == Some(root)
+                && path
+                    .file_name()
+                    .is_some_and(|name| MAKEFILE_NAMES.iter().any(|n| name == *n)) =>
+        {
+            "make".to_string()
+        }
+        Some(path) => {
+            let path = path.strip_prefix(root).unwrap_or(path);
+            format!("make -f {}", shell_quote(&display_path(path)))
+        }
+        None => "make".to_string(),
+    };
+
+    let mut tasks = Vec::new();
+    for target in targets {
+        if let Some(command) = target_command(target, entry_point, &make, root) {
+            tasks.push(task(
+                target.name.clone(),
+                &target.description,
+                command,
+                &target.source,
+            ));
+        }
+    }
+    if let Some(info) = entry_point {
+        let script = Path::new(&info.invocation)
+            .file_name()
+            .map_or(info.invocation.clone(), |f| {
+                f.to_string_lossy().into_owned()
+            });
+        for (name, description) in &info.subcommands {
+            tasks.push(task(
+                format!("{} {}", script, name),
+                description,
+                format!("{} {}", info.invocation, name),
+                "entry_point",
+            ));
+        }
+    }
+    tasks
+}
+
+/// `text` with comments blanked out, byte for byte, so offsets into it
+/// are offsets into `text`
+pub fn blank_comments(text: &str) -> String {
+    let mut bytes = text.as_bytes().to_vec();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => i = string_end(&bytes, i),
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    bytes[i] = b' ';
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let end = text[i + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |e| i + 2 + e + 2);
+                for byte in &mut bytes[i..end] {
+                    if *byte != b'\n' {
+                        *byte = b' ';
+                    }
+                }
+                i = end;
+            }
+            _ => i += 1,
+        }
+    }
+    // Only ASCII bytes outside strings were replaced with spaces
+    String::from_utf8(bytes).expect("blanking keeps UTF-8 valid")
+}
+
+/// Comment-free `text` with trailing commas blanked out too, for serde_json
+pub fn blank_trailing_commas(text: &str) -> String {
+    let mut bytes = text.as_bytes().to_vec();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => i = string_end(&bytes, i),
+            b',' => {
+                let next = skip_whitespace(&bytes, i + 1);
+                if matches!(bytes.get(next), Some(b']' | b'}')) {
+                    bytes[i] = b' ';
+                }
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    // Only ASCII bytes outside strings were replaced with spaces
+    String::from_utf8(bytes).expect("blanking keeps UTF-8 valid")
+}
+
+/// Offset just past the string starting at `start`
+fn string_end(bytes: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+fn skip_whitespace(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+/// Offset just past the JSON value starting at `start`, in stripped text
+fn value_end(bytes: &[u8], start: usize) -> usize {
+    match bytes.get(start) {
+        Some(b'"') => string_end(bytes, start),
+        Some(b'{' | b'[') => {
+            let mut depth = 0;
+            let mut i = start;
+            while i < bytes.len() {
+                match bytes[i] {
+                    b'"' => {
+                        i = string_end(bytes, i);
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return i + 1;
+                        }
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+            bytes.len()
+        }
+        _ => {
+            let mut i = start;
+            while i < bytes.len() && !matches!(bytes[i], b',' | b']' | b'}') {
+                i += 1;
+            }
+            // Back over whitespace before the delimiter
+            while i > start && bytes[i - 1].is_ascii_whitespace() {
+                i -= 1;
+            }
+            i
+        }
+    }
+}
+
+/// The top-level `tasks` array: its brackets and the span of each element
+struct TasksArray {
+    open: usize,
+    close: usize,
+    elements: Vec<(usize, usize)>,
+}
+
+/// Where things are in a tasks.json, as offsets shared by the original
+/// and the stripped text
+struct Layout {
+    tasks: Option<TasksArray>,
+    /// End of the last top-level member, and whether a comma follows it
+    last_member: Option<(usize, bool)>,
+}
+
+/// Find the top-level `tasks` array in a stripped, valid JSON object
+fn layout(stripped: &str) -> Layout {
+    let bytes = stripped.as_bytes();
+    let mut layout = Layout {
+        tasks: None,
+        last_member: None,
+    };
+    let mut i = skip_whitespace(bytes, 0) + 1;
+    loop {
+        i = skip_whitespace(bytes, i);
+        match bytes.get(i) {
+            Some(b'"') => {}
+            Some(b',') => {
+                i += 1;
+                continue;
+            }
+            _ => return layout,
+        }
+        let key_end = string_end(bytes, i);
+        let key: String = serde_json::from_str(&stripped[i..key_end]).unwrap_or_default();
+        // Past the ':'
+        i = skip_whitespace(bytes, key_end) + 1;
+        i = skip_whitespace(bytes, i);
+        let end = value_end(bytes, i);
+        if key == "tasks" && bytes.get(i) == Some(&b'[') {
+            let mut elements = Vec::new();
+            let mut j = i + 1;
+            loop {
+                j = skip_whitespace(bytes, j);
+                match bytes.get(j) {
+                    Some(b']') | None => break,
+                    Some(b',') => j += 1,
+                    Some(_) => {
+                        let element_end = value_end(bytes, j);
+                        elements.push((j, element_end));
+                        j = element_end;
+                    }
+                }
+            }
+            layout.tasks = Some(TasksArray {
+                open: i,
+                close: end - 1,
+                elements,
+            });
+        }
+        let next = skip_whitespace(bytes, end);
+        layout.last_member = Some((end, bytes.get(next) == Some(&b',')));
+        i = end;
+    }
+}
+
+/// Leading whitespace of the line `offset` is on
+fn line_indent(text: &str, offset: usize) -> &str {
+    let line_start = text[..offset].rfind('\n').map_or(0, |n| n + 1);
+    let line = &text[line_start..];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+/// Pretty-print `task` for insertion at `indent`, indenting with tabs
+/// when the file does
+fn task_text(task: &serde_json::Value, indent: &str) -> String {
+    use serde::Serialize;
+    let step: &[u8] = if indent.contains('\t') { b"\t" } else { b"  " };
+    let mut out = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(step);
+    let mut serializer = serde_json::Serializer::with_formatter(&mut out, formatter);
+    task.serialize(&mut serializer).expect("task serializes");
+    String::from_utf8_lossy(&out).replace('\n', &format!("\n{}", indent))
+}
+
+/// Merge `tasks` into the text of a tasks.json: earlier generated tasks
+/// are dropped, the user's tasks are kept as written, and a generated
+/// task whose label a user task already has is skipped. Returns the new
+/// text and the labels skipped.
+pub fn merge_tasks(
+    old: &str,
+    tasks: &[serde_json::Value],
+) -> Result<(String, Vec<String>), String> {
+    if old.trim().is_empty() {
+        let doc = serde_json::json!({ "version": "2.0.0", "tasks": tasks });
+        let text = serde_json::to_string_pretty(&doc).map_err(|e| e.to_string())?;
+        return Ok((text + "\n", Vec::new()));
+    }
+
+    // Same offsets as `old`: `stripped` for finding the tasks, `plain`
+    // for parsing them
+    let stripped = blank_comments(old);
+    let plain = blank_trailing_commas(&stripped);
+    let doc: serde_json::Value =
+        serde_json::from_str(&plain).map_err(|e| format!("not valid JSON ({})", e))?;
+    if !doc.is_object() {
+        return Err("expected the top level to be an object".to_string());
+    }
+    if doc.get("tasks").is_some_and(|t| !t.is_array()) {
+        return Err("expected \"tasks\" to be an array".to_string());
+    }
+    let layout = layout(&stripped);
+
+    let Some(TasksArray {
+        open,
+        close,
+        elements,
+    }) = layout.tasks
+    else {
+        // No tasks array yet: add one after the last member
+        let indent = "  ";
+        let entries: Vec<String> = tasks
+            .iter()
+            .map(|task| format!("\n{0}{0}{1}", indent, task_text(task, "    ")))
+            .collect();
+        let array = format!("\"tasks\": [{}\n{}]", entries.join(","), indent);
+        let text = match layout.last_member {
+            Some((end, true)) => {
+                let at = end + stripped[end..].find(',').map_or(0, |c| c + 1);
+                format!("{}\n{}{}{}", &old[..at], indent, array, &old[at..])
+            }
+            Some((end, false)) => format!("{},\n{}{}{}", &old[..end], indent, array, &old[end..]),
+            None => {
+                let brace = stripped.find('{').unwrap_or(0) + 1;
+                format!("{}\n{}{}\n{}", &old[..brace], indent, array, &old[brace..])
+            }
+        };
+        return Ok((text, Vec::new()));
+    };
+
+    // Each element's chunk runs from after the previous comma to its end,
+    // so comments above a task stay with it
+    let bytes = stripped.as_bytes();
+    let mut kept: Vec<&str> = Vec::new();
+    let mut user_labels: HashSet<String> = HashSet::new();
+    let mut chunk_start = open + 1;
+    let mut first_indent = None;
+    for &(start, end) in &elements {
+        let value: serde_json::Value = serde_json::from_str(&plain[start..end])
+            .map_err(|e| format!("not valid JSON ({})", e))?;
+        first_indent.get_or_insert_with(|| line_indent(old, start).to_string());
+        if value.get(TASK_MARKER).is_none() {
+            if let Some(label) = value["label"].as_str() {
+                user_labels.insert(label.to_string());
+            }
+            kept.push(&old[chunk_start..end]);
+        }
+        let next = skip_whitespace(bytes, end);
+        chunk_start = if bytes.get(next) == Some(&b',') {
+            next + 1
+        } else {
+            end
+        };
+    }
+    let mut trailing = &old[chunk_start..close];
+    let key_indent = line_indent(old, open).to_string();
+    let indent = first_indent.unwrap_or_else(|| {
+        let step = if key_indent.contains('\t') {
+            "\t"
+        } else {
+            "  "
+        };
+        format!("{}{}", key_indent, step)
+    });
+
+    let mut skipped = Vec::new();
+    let mut inner = kept.join(",");
+    for task in tasks {
+        let label = task["label"].as_str().unwrap_or_default();
+        if user_labels.contains(label) {
+            skipped.push(label.to_string());
+            continue;
+        }
+        if !inner.is_empty() {
+            inner.push(',');
+        }
+        inner.push_str(&format!("\n{}{}", indent, task_text(task, &indent)));
+    }
+    let closing;
+    if !trailing.contains('\n') && trailing.trim().is_empty() && !inner.is_empty() {
+        closing = format!("\n{}", key_indent);
+        trailing = &closing;
+    }
+    let text = format!("{}{}{}{}", &old[..open + 1], inner, trailing, &old[close..]);
+    Ok((text, skipped))
+}
+
+pub async fn run_export(
+    action: &ExportAction,
+    project: Arc<ProjectContext>,
+    write: &WriteArgs,
+) -> Result<(), String> {
+    match action {
+        ExportAction::VscodeTasks(opts) => export_vscode_tasks(opts, &project, write),
+        ExportAction::Html(opts) => export_html(opts, project, write).await,
+    }
+}
+
+/// Write `content` to `path` and say so
+fn write_export(path: &Path, content: &str, write: &WriteArgs) -> Result<Written, String> {
+    let written = write_file(path, content, write, false)
+        .map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+    if written != Written::Diff {
+        println!("Wrote {}{}", path.display(), backup_note(&written));
+    }
+    Ok(written)
+}
+
+async fn export_html(
+    opts: &HtmlOptions,
+    project: Arc<ProjectContext>,
+    write: &WriteArgs,
+) -> Result<(), String> {
+    let redactor = Redactor::from_config(&project.config);
+    if opts.demo {
+        let mut context = demo_context(None).map_err(|e| e.to_string())?;
+        context.language = configured_language(&project.config);
+        write_export(
+            &opts.output,
+            &redactor.redact(&format_html(&context)),
+            write,
+        )?;
+        return Ok(());
+    }
+    #[cfg(not(feature = "watch"))]
+    if opts.watch {
+        return Err(crate::collectors::not_built("watch"));
+    }
+    #[cfg(feature = "watch")]
+    if opts.watch {
+        if write.diff {
+            return Err("--diff can't be combined with --watch".to_string());
+        }
+        println!(
+            "Writing {} on every change; Ctrl-C to stop",
+            opts.output.display()
+        );
+        let mut last = String::new();
+        return watch_context(project, Level::Full, opts.interval, |context| {
+            let html = redactor.redact(&format_html(context)).into_owned();
+            // Ages and the update time make most renders differ anyway
+            if html != last {
+                write_file(&opts.output, &html, write, false)?;
+                last = html;
+            }
+            Ok(true)
+        })
+        .await
+        .map_err(|e| e.to_string());
+    }
+
+    let deadline = tool_deadline(&project.config);
+    let ct = CancellationToken::new();
+    let plan = CollectionPlan::for_level(Level::Full, &project.config);
+    let mut context = collect_context(Arc::clone(&project), plan, deadline, ct.clone()).await;
+    annotate_context(&project, &mut context, deadline, &ct).await;
+    write_export(
+        &opts.output,
+        &redactor.redact(&format_html(&context)),
+        write,
+    )?;
+    Ok(())
+}
+
+fn export_vscode_tasks(
+    opts: &VscodeTasksOptions,
+    project: &ProjectContext,
+    write: &WriteArgs,
+) -> Result<(), String> {
+    let path = opts
+        .output
+        .clone()
+        .unwrap_or_else(|| project.root.join(".vscode").join("tasks.json"));
+
+    let mut targets = collect_build_targets(project);
+    targets.extend(collect_west_targets(project));
+    let (_, entry_point) = collect_entry_point(project);
+    let makefile = find_makefile(project);
+    for warning in COLLECTOR_STATS.with(|stats| stats.take()).warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    let tasks = vscode_tasks(
+        &targets,
+        entry_point.as_ref(),
+        makefile.as_deref(),
+        &project.root,
+    );
+
+    let old = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Cannot read {}: {}", path.display(), e)),
+    };
+    let (new, skipped) = merge_tasks(&old, &tasks).map_err(|e| {
+        format!(
+            "{}: {}; fix it by hand, nothing was changed",
+            path.display(),
+            e
+        )
+    })?;
+    for label in &skipped {
+        eprintln!(
+            "Skipped \"{}\": {} already has a task with that label",
+            label,
+            path.display()
+        );
+    }
+
+    if opts.dry_run {
+        print!("{}", new);
+        return Ok(());
+    }
+    if new == old {
+        println!("{} is already up to date", path.display());
+        return Ok(());
+    }
+    let written = write_file(&path, &new, write, false)
+        .map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+    if written != Written::Diff {
+        println!(
+            "Wrote {} tasks to {}{}",
+            tasks.len() - skipped.len(),
+            path.display(),
+            backup_note(&written)
+        );
+    }
+    Ok(())
+}
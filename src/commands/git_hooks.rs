@@ -0,0 +1,236 @@
+//! `context-keeper git-hooks`: refresh the saved work state on commit and
+//! checkout
+//!
+//! Each discovered repository gets a guarded snippet in its `post-commit`
+//! and `post-checkout` hooks. A hook that already exists (husky,
+//! pre-commit, hand-written) keeps its content: the snippet goes right
+//! after the shebang, so a trailing `exec` can't skip it, and uninstall
+//! removes exactly the marked lines.
+
+use crate::collectors::git::{discover_git_repos, git_in};
+use crate::commands::hooks::shell_quote;
+use crate::commands::safe_write::{
+    remove_file_backup_in, write_file_backup_in, WriteArgs, Written,
+};
+use crate::config::ProjectContext;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Hooks the snippet goes into
+pub const GIT_HOOKS: [&str; 2] = ["post-commit", "post-checkout"];
+
+/// First and last line of our snippet
+pub const SNIPPET_START: &str = "# >>> context-keeper: refresh saved work state >>>";
+pub const SNIPPET_END: &str = "# <<< context-keeper <<<";
+
+#[derive(Debug, clap::Args)]
+pub struct GitHooksOptions {
+    /// Print the diff instead of writing the hooks (same as --diff)
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum GitHooksAction {
+    /// Add the post-commit/post-checkout snippet to every discovered repo
+    Install(GitHooksOptions),
+    /// Remove the snippet, and hook files that held nothing else
+    Uninstall(GitHooksOptions),
+}
+
+/// The lines we add to a hook. Backgrounded so the commit doesn't wait.
+/// An `if`, not `&&`: git takes a `post-checkout` hook's status as the
+/// checkout's, so a missing binary must still leave 0 when the snippet is
+/// the hook's last command.
+pub fn hook_snippet(exe: &str, root: &Path) -> String {
+    let exe = shell_quote(exe);
+    format!(
+        "{}\nif [ -x {} ]; then ({} --cwd {} --save-state-auto >/dev/null 2>&1 &); fi\n{}\n",
+        SNIPPET_START,
+        exe,
+        exe,
+        shell_quote(&root.to_string_lossy()),
+        SNIPPET_END
+    )
+}
+
+/// `content` without our snippet
+pub fn remove_snippet(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut inside = false;
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        if trimmed == SNIPPET_START {
+            inside = true;
+        } else if inside && trimmed == SNIPPET_END {
+            inside = false;
+        } else if !inside {
+            out.push_str(line);
+        }
+    }
+    out
+}
+
+/// `content` with `snippet` in place of any earlier copy, right after the
+/// shebang (a new `#!/bin/sh` hook when `content` is empty)
+pub fn insert_snippet(content: &str, snippet: &str) -> String {
+    let content = remove_snippet(content);
+    if content.trim().is_empty() {
+        return format!("#!/bin/sh\n{}", snippet);
+    }
+    match content.split_once('\n') {
+        Some((first, rest)) if first.starts_with("#!") => {
+            format!("{}\n{}{}", first, snippet, rest)
+        }
+        Some(_) => format!("{}{}", snippet, content),
+        // A lone shebang without a newline
+        None => format!("{}\n{}", content, snippet),
+    }
+}
+
+/// Whether a hook is left with nothing but a shebang once the snippet is
+/// gone, i.e. we created it
+fn only_shebang(content: &str) -> bool {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .all(|line| line.starts_with("#!"))
+}
+
+/// Directory git runs `repo`'s hooks from. Honors `core.hooksPath`,
+/// worktrees and repo-managed `.git` links; for husky's generated `_`
+/// directory, the user-editable hooks one level up.
+pub fn hooks_dir(project: &ProjectContext, repo: &Path) -> Option<PathBuf> {
+    let output = git_in(
+        &*project.runner,
+        repo,
+        &["rev-parse", "--git-path", "hooks"],
+        Some(Duration::from_secs(3)),
+    )
+    .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let dir = repo.join(path);
+    match dir.parent() {
+        Some(parent) if dir.file_name() == Some("_".as_ref()) && parent.ends_with(".husky") => {
+            Some(parent.to_path_buf())
+        }
+        _ => Some(dir),
+    }
+}
+
+/// Where backups of `repo`'s hooks go when its hooks directory is part
+/// of the work tree (`core.hooksPath = .husky`): a `.bak` there would
+/// show up in `git status`, so it goes in the git directory instead.
+/// `None` for hooks in the git directory, backed up beside themselves.
+pub fn hook_backup_dir(project: &ProjectContext, repo: &Path, hooks: &Path) -> Option<PathBuf> {
+    let output = git_in(
+        &*project.runner,
+        repo,
+        &["rev-parse", "--absolute-git-dir"],
+        Some(Duration::from_secs(3)),
+    )
+    .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let git_dir = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    let hooks = hooks.canonicalize().unwrap_or_else(|_| repo.join(hooks));
+    let in_git_dir = git_dir
+        .canonicalize()
+        .is_ok_and(|git_dir| hooks.starts_with(git_dir));
+    (!in_git_dir).then(|| git_dir.join("context-keeper").join("hook-backups"))
+}
+
+pub fn run_git_hooks(
+    action: &GitHooksAction,
+    project: &ProjectContext,
+    write: &WriteArgs,
+) -> Result<(), String> {
+    let (opts, install) = match action {
+        GitHooksAction::Install(opts) => (opts, true),
+        GitHooksAction::Uninstall(opts) => (opts, false),
+    };
+    let exe = std::env::current_exe()
+        .and_then(|p| p.canonicalize())
+        .map_err(|e| format!("Cannot locate the context-keeper binary: {}", e))?;
+    let snippet = hook_snippet(&exe.to_string_lossy(), &project.root);
+    let args = write.with_dry_run(opts.dry_run);
+
+    let repos = discover_git_repos(project);
+    if repos.iter().all(|repo| repo.status.is_none()) {
+        return Err(format!(
+            "No git repositories found in {}",
+            project.root.display()
+        ));
+    }
+
+    let mut failed = false;
+    for repo in repos.iter().filter(|repo| repo.status.is_some()) {
+        let Some(dir) = hooks_dir(project, &repo.dir) else {
+            eprintln!("{}: cannot find the hooks directory", repo.path);
+            failed = true;
+            continue;
+        };
+        let backup_dir = hook_backup_dir(project, &repo.dir, &dir);
+        let mut changed: Vec<&str> = Vec::new();
+        let mut backups: Vec<PathBuf> = Vec::new();
+        for hook in GIT_HOOKS {
+            let path = dir.join(hook);
+            let old = match std::fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+                Err(e) => {
+                    eprintln!("Cannot read {}: {}", path.display(), e);
+                    failed = true;
+                    continue;
+                }
+            };
+            let new = if install {
+                insert_snippet(&old, &snippet)
+            } else {
+                remove_snippet(&old)
+            };
+            if new == old {
+                continue;
+            }
+            let result = if !install && only_shebang(&new) {
+                remove_file_backup_in(&path, &args, backup_dir.as_deref())
+            } else {
+                write_file_backup_in(&path, &new, &args, true, backup_dir.as_deref())
+            };
+            match result {
+                Ok(Written::Changed {
+                    backup: Some(backup),
+                }) => {
+                    changed.push(hook);
+                    backups.push(backup);
+                }
+                Ok(Written::Unchanged | Written::Diff) => {}
+                Ok(_) => changed.push(hook),
+                Err(e) => {
+                    eprintln!("Cannot write {}: {}", path.display(), e);
+                    failed = true;
+                }
+            }
+        }
+        if args.diff {
+            continue;
+        }
+        match (changed.is_empty(), install) {
+            (true, _) => println!("{}: already up to date", repo.path),
+            (false, true) => println!("{}: installed {}", repo.path, changed.join(", ")),
+            (false, false) => println!("{}: removed from {}", repo.path, changed.join(", ")),
+        }
+        for backup in &backups {
+            println!("  previous version kept as {}", backup.display());
+        }
+    }
+    if failed {
+        return Err("some hooks were not changed".to_string());
+    }
+    Ok(())
+}
@@ -0,0 +1,233 @@
+//! `context-keeper hooks`: installs the Claude Code hooks and MCP entry
+
+use crate::commands::safe_write::{backup_note, write_file, WriteArgs, Written};
+use crate::state::home_dir;
+use std::io::{self};
+use std::path::{Path, PathBuf};
+
+/// Name of the MCP server entry written into Claude settings
+pub const MCP_SERVER_NAME: &str = "context-keeper";
+
+/// Hook events we register, with their matcher and the subcommand to run
+pub const HOOK_ENTRIES: &[(&str, &str, &str)] = &[
+    ("PreCompact", "*", "save-state"),
+    ("SessionStart", "compact", "context --level minimal"),
+];
+
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum HookScope {
+    /// ~/.claude/settings.json
+    #[default]
+    User,
+    /// .claude/settings.json in the project directory
+    Project,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct HooksOptions {
+    /// Which settings file to edit
+    #[arg(long, value_enum, default_value_t)]
+    pub scope: HookScope,
+    /// Print the diff instead of writing the file (same as --diff)
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum HooksAction {
+    /// Register the MCP server and PreCompact/SessionStart hooks
+    Install(HooksOptions),
+    /// Remove everything `hooks install` added
+    Uninstall(HooksOptions),
+}
+
+pub fn settings_path(scope: HookScope, root: &Path) -> PathBuf {
+    match scope {
+        HookScope::User => home_dir().join(".claude").join("settings.json"),
+        HookScope::Project => root.join(".claude").join("settings.json"),
+    }
+}
+
+/// Quote a path for use in a hook command line when it needs it
+pub fn shell_quote(s: &str) -> String {
+    if !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/._-+:@".contains(c))
+    {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
+
+/// Whether a hook command was written by us, for any binary location
+pub fn is_our_hook_command(command: &str, subcommand: &str) -> bool {
+    let Some(program) = command.strip_suffix(subcommand) else {
+        return false;
+    };
+    let program = program.trim_end().trim_matches('\'');
+    Path::new(program).file_name() == Some(std::ffi::OsStr::new("context-keeper"))
+}
+
+/// Get a child object of `parent`, creating it when missing
+pub fn object_entry<'a>(
+    parent: &'a mut serde_json::Map<String, serde_json::Value>,
+    key: &str,
+    path: &str,
+) -> Result<&'a mut serde_json::Map<String, serde_json::Value>, String> {
+    parent
+        .entry(key)
+        .or_insert_with(|| serde_json::json!({}))
+        .as_object_mut()
+        .ok_or_else(|| format!("expected \"{}\" to be an object", path))
+}
+
+/// Drop the hook entries matching `subcommand` from one event's matcher groups,
+/// removing groups left empty. Groups with other hooks are left in place.
+pub fn remove_our_hooks(groups: &mut Vec<serde_json::Value>, subcommand: &str) {
+    for group in groups.iter_mut() {
+        if let Some(hooks) = group.get_mut("hooks").and_then(|h| h.as_array_mut()) {
+            hooks.retain(|hook| {
+                !hook
+                    .get("command")
+                    .and_then(|c| c.as_str())
+                    .is_some_and(|c| is_our_hook_command(c, subcommand))
+            });
+        }
+    }
+    groups.retain(|group| {
+        group
+            .get("hooks")
+            .and_then(|h| h.as_array())
+            .is_none_or(|hooks| !hooks.is_empty())
+    });
+}
+
+/// Apply install or uninstall to parsed settings
+pub fn apply_hooks(
+    settings: &mut serde_json::Value,
+    exe: &str,
+    install: bool,
+) -> Result<(), String> {
+    let root = settings
+        .as_object_mut()
+        .ok_or("expected the top level to be an object")?;
+
+    let servers = object_entry(root, "mcpServers", "mcpServers")?;
+    if install {
+        servers.insert(
+            MCP_SERVER_NAME.to_string(),
+            serde_json::json!({
+                "type": "stdio",
+                "command": exe,
+                "args": ["serve"],
+            }),
+        );
+    } else {
+        servers.remove(MCP_SERVER_NAME);
+    }
+    if servers.is_empty() {
+        root.remove("mcpServers");
+    }
+
+    let hooks = object_entry(root, "hooks", "hooks")?;
+    for (event, matcher, subcommand) in HOOK_ENTRIES {
+        let groups = hooks
+            .entry(*event)
+            .or_insert_with(|| serde_json::json!([]))
+            .as_array_mut()
+            .ok_or_else(|| format!("expected \"hooks.{}\" to be an array", event))?;
+        if let Some(bad) = groups.iter().position(|g| !g.is_object()) {
+            return Err(format!(
+                "expected \"hooks.{}[{}]\" to be an object",
+                event, bad
+            ));
+        }
+
+        remove_our_hooks(groups, subcommand);
+        if install {
+            groups.push(serde_json::json!({
+                "matcher": matcher,
+                "hooks": [{
+                    "type": "command",
+                    "command": format!("{} {}", shell_quote(exe), subcommand),
+                }],
+            }));
+        }
+        if groups.is_empty() {
+            hooks.remove(*event);
+        }
+    }
+    if hooks.is_empty() {
+        root.remove("hooks");
+    }
+
+    Ok(())
+}
+
+pub fn run_hooks(action: &HooksAction, root: &Path, write: &WriteArgs) -> Result<(), String> {
+    let (opts, install) = match action {
+        HooksAction::Install(opts) => (opts, true),
+        HooksAction::Uninstall(opts) => (opts, false),
+    };
+    let path = settings_path(opts.scope, root);
+
+    let old = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Cannot read {}: {}", path.display(), e)),
+    };
+    let mut settings: serde_json::Value = if old.trim().is_empty() {
+        serde_json::json!({})
+    } else {
+        serde_json::from_str(&old).map_err(|e| {
+            format!(
+                "{} is not valid JSON ({}); fix it by hand, nothing was changed",
+                path.display(),
+                e
+            )
+        })?
+    };
+
+    let exe = std::env::current_exe()
+        .and_then(|p| p.canonicalize())
+        .map_err(|e| format!("Cannot locate the context-keeper binary: {}", e))?;
+    apply_hooks(&mut settings, &exe.to_string_lossy(), install).map_err(|e| {
+        format!(
+            "Unexpected settings layout in {}: {}; nothing was changed",
+            path.display(),
+            e
+        )
+    })?;
+
+    let new = if settings.as_object().is_some_and(|o| o.is_empty()) && old.trim().is_empty() {
+        String::new()
+    } else {
+        serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())? + "\n"
+    };
+
+    if new == old {
+        println!("{} is already up to date", path.display());
+        return Ok(());
+    }
+    let written = write_file(&path, &new, &write.with_dry_run(opts.dry_run), false)
+        .map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+    if written == Written::Diff {
+        return Ok(());
+    }
+
+    if install {
+        println!(
+            "Installed context-keeper into {}{}",
+            path.display(),
+            backup_note(&written)
+        );
+    } else {
+        println!(
+            "Removed context-keeper from {}{}",
+            path.display(),
+            backup_note(&written)
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,1045 @@
+//! `context-keeper init`: the config wizard, and `bootstrap_config`, which
+//! writes the same config from the detected values without asking
+
+use crate::collectors::detect::{detect_project_types, Confidence, Detection};
+use crate::collectors::entry_point::{parse_entry_point, parse_entry_point_commands};
+use crate::collectors::environment::find_dev_shell_marker;
+use crate::collectors::git::skip_scan_dir;
+use crate::collectors::just::{parse_justfile, JustRecipe, JUSTFILE_NAMES};
+use crate::collectors::make::{is_command_target, parse_makefile, MakeRules, MAKEFILE_NAMES};
+use crate::collectors::packages::detect_package_manager;
+use crate::collectors::project_ignore::ProjectIgnore;
+use crate::collectors::resolve_program;
+use crate::commands::safe_write::{backup_note, write_file, WriteArgs, Written};
+use crate::config::find_config_file;
+use crate::error::ContextKeeperError;
+use crate::paths::display_path;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// Project types the wizard and `--type` accept
+pub const PROJECT_TYPES: &[&str] = &[
+    "aosp",
+    "ros",
+    "yocto",
+    "zephyr",
+    "buildroot",
+    "kernel",
+    "cargo",
+    "node",
+    "custom",
+];
+
+/// Types the wizard proposes: everything except low-confidence hits,
+/// unless those are all there is
+pub fn default_project_types(detections: &[Detection]) -> Vec<String> {
+    let confident: Vec<String> = detections
+        .iter()
+        .filter(|d| d.confidence != Confidence::Low)
+        .map(|d| d.project_type.to_string())
+        .collect();
+    if confident.is_empty() {
+        detections
+            .iter()
+            .map(|d| d.project_type.to_string())
+            .collect()
+    } else {
+        confident
+    }
+}
+
+/// Types in `types` that the wizard doesn't know
+pub fn unknown_project_types(types: &[String]) -> Vec<&str> {
+    types
+        .iter()
+        .map(String::as_str)
+        .filter(|t| !PROJECT_TYPES.contains(t))
+        .collect()
+}
+
+/// Split a comma-separated `--type` or prompt answer
+pub fn parse_project_types(answer: &str) -> Vec<String> {
+    let mut types: Vec<String> = Vec::new();
+    for name in answer.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+        if !types.iter().any(|t| t == name) {
+            types.push(name.to_string());
+        }
+    }
+    types
+}
+
+/// Detect available container runtime
+pub fn detect_container_runtime() -> Option<&'static str> {
+    // Check podman first (preferred for rootless)
+    if std::process::Command::new(resolve_program("podman"))
+        .arg("--version")
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+    {
+        return Some("podman");
+    }
+
+    // Check docker
+    if std::process::Command::new(resolve_program("docker"))
+        .arg("--version")
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+    {
+        return Some("docker");
+    }
+
+    None
+}
+
+/// Get project directory name as default project name
+pub fn get_default_project_name(root: &Path) -> Option<String> {
+    root.canonicalize()
+        .ok()
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+}
+
+/// Most commands shown when previewing a build entry point
+pub const MAX_PREVIEW_COMMANDS: usize = 10;
+
+/// Directory levels below the root searched for `.conf` directories
+const CONFIG_DIR_SCAN_DEPTH: usize = 3;
+
+/// What kind of file a build candidate is, which decides what goes into
+/// `[scripts]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CandidateKind {
+    /// Shell script, written as `entry_point`
+    Script,
+    /// Written as `makefile`
+    Makefile,
+    /// Read without any config; nothing is written
+    Justfile,
+    /// Its scripts are read without any config; nothing is written
+    PackageJson,
+}
+
+impl CandidateKind {
+    /// The kind a file is by its name: a script unless it's a Makefile,
+    /// justfile or package.json
+    pub fn of(path: &str) -> Self {
+        let name = Path::new(path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if MAKEFILE_NAMES.contains(&name.as_str()) || name.ends_with(".mk") {
+            CandidateKind::Makefile
+        } else if JUSTFILE_NAMES.contains(&name.as_str()) {
+            CandidateKind::Justfile
+        } else if name == "package.json" {
+            CandidateKind::PackageJson
+        } else {
+            CandidateKind::Script
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CandidateKind::Script => "script",
+            CandidateKind::Makefile => "Makefile",
+            CandidateKind::Justfile => "justfile",
+            CandidateKind::PackageJson => "npm scripts",
+        }
+    }
+}
+
+/// A file the wizard offers as the way the project is built
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildCandidate {
+    /// Relative to the project root, as written to the config
+    pub path: String,
+    pub kind: CandidateKind,
+}
+
+impl BuildCandidate {
+    pub fn new(path: &str) -> Self {
+        BuildCandidate {
+            path: path.to_string(),
+            kind: CandidateKind::of(path),
+        }
+    }
+
+    /// The `(entry_point, makefile)` answers choosing this sets
+    pub fn answers(&self) -> (String, String) {
+        match self.kind {
+            CandidateKind::Script => (self.path.clone(), String::new()),
+            CandidateKind::Makefile => (String::new(), self.path.clone()),
+            CandidateKind::Justfile | CandidateKind::PackageJson => (String::new(), String::new()),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// `*.sh` files directly in `dir`, relative to `root`, names mentioning
+/// "build" first
+fn shell_scripts(root: &Path, dir: &str, executable_only: bool) -> Vec<String> {
+    let mut scripts: Vec<String> = fs::read_dir(root.join(dir))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(".sh"))
+        .filter(|entry| {
+            let path = entry.path();
+            path.is_file() && (!executable_only || is_executable(&path))
+        })
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if dir.is_empty() {
+                name
+            } else {
+                format!("{}/{}", dir, name)
+            }
+        })
+        .collect();
+    scripts.sort_by_key(|path| (!path.contains("build"), path.clone()));
+    scripts
+}
+
+/// Likely build entry points in `root`, best first: executable `*.sh` in
+/// the root, `build/*.sh`, then the Makefile, justfile and package.json
+pub fn find_build_candidates(root: &Path) -> Vec<BuildCandidate> {
+    let mut candidates: Vec<BuildCandidate> = shell_scripts(root, "", true)
+        .into_iter()
+        .chain(shell_scripts(root, "build", false))
+        .map(|path| BuildCandidate::new(&path))
+        .collect();
+    let named = |names: &[&str]| {
+        names
+            .iter()
+            .find(|name| root.join(name).is_file())
+            .map(|name| BuildCandidate::new(name))
+    };
+    candidates.extend(named(&MAKEFILE_NAMES));
+    candidates.extend(named(&JUSTFILE_NAMES));
+    candidates.extend(named(&["package.json"]));
+    candidates
+}
+
+/// Commands the context would list for `candidate`: the entry-point
+/// parser's for a script, else the targets, recipes or npm scripts
+pub fn preview_build_candidate(root: &Path, candidate: &BuildCandidate) -> Vec<String> {
+    let path = root.join(&candidate.path);
+    let mut commands = match candidate.kind {
+        CandidateKind::Script => {
+            let Ok(content) = fs::read_to_string(&path) else {
+                return Vec::new();
+            };
+            let invocation =
+                if Path::new(&candidate.path).is_absolute() || candidate.path.starts_with('.') {
+                    candidate.path.clone()
+                } else {
+                    format!("./{}", candidate.path)
+                };
+            match parse_entry_point(&content, &invocation) {
+                Some(info) => info.commands(),
+                None => parse_entry_point_commands(&content),
+            }
+        }
+        CandidateKind::Makefile => {
+            let mut parsed = MakeRules::default();
+            parse_makefile(&path, true, &mut parsed);
+            let mut commands: Vec<String> = Vec::new();
+            for (name, _, _) in &parsed.rules {
+                let command = format!("make {}", name);
+                if is_command_target(name) && !commands.contains(&command) {
+                    commands.push(command);
+                }
+            }
+            commands
+        }
+        CandidateKind::Justfile => fs::read_to_string(&path)
+            .map(|content| {
+                parse_justfile(&content)
+                    .iter()
+                    .map(JustRecipe::command)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        CandidateKind::PackageJson => {
+            let manager = detect_package_manager(path.parent().unwrap_or(root));
+            fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+                .and_then(|package| package.get("scripts")?.as_object().cloned())
+                .map(|scripts| {
+                    scripts
+                        .keys()
+                        .map(|name| format!("{} run {}", manager, name))
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+    };
+    commands.truncate(MAX_PREVIEW_COMMANDS);
+    commands
+}
+
+fn count_conf_dirs(
+    root: &Path,
+    dir: &Path,
+    depth: usize,
+    ignore: &ProjectIgnore,
+    found: &mut Vec<(String, usize)>,
+) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut confs = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if depth < CONFIG_DIR_SCAN_DEPTH
+                && !skip_scan_dir(&entry.file_name())
+                && !ignore.is_ignored(&path, true)
+            {
+                count_conf_dirs(root, &path, depth + 1, ignore, found);
+            }
+        } else if entry.file_name().to_string_lossy().ends_with(".conf")
+            && !ignore.is_ignored(&path, false)
+        {
+            confs += 1;
+        }
+    }
+    if confs >= 2 {
+        let relative = dir.strip_prefix(root).unwrap_or(dir);
+        let relative = if relative.as_os_str().is_empty() {
+            ".".to_string()
+        } else {
+            display_path(relative)
+        };
+        found.push((relative, confs));
+    }
+}
+
+/// Directories under `root` holding two or more `*.conf` files, with how
+/// many, most first; what `ignore` matches is left out
+pub fn find_config_dir_candidates(root: &Path, ignore: &ProjectIgnore) -> Vec<(String, usize)> {
+    let mut found = Vec::new();
+    count_conf_dirs(root, root, 0, ignore, &mut found);
+    found.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    found
+}
+
+/// The entry in `options` a numbered answer picks; `None` for anything
+/// else
+pub fn numbered_choice<'a, T>(answer: &str, options: &'a [T]) -> Option<&'a T> {
+    answer
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_sub(1))
+        .and_then(|index| options.get(index))
+}
+
+/// Where the wizard's questions go and its answers come from
+pub trait WizardIo {
+    /// Show `question` and read one line; `None` once input has ended
+    fn ask(&mut self, question: &str) -> io::Result<Option<String>>;
+    /// Show a line of output
+    fn say(&mut self, line: &str);
+}
+
+/// The terminal: stdout for questions, stdin for answers
+pub struct TerminalIo;
+
+impl WizardIo for TerminalIo {
+    fn ask(&mut self, question: &str) -> io::Result<Option<String>> {
+        print!("{}", question);
+        io::stdout().flush()?;
+        let mut input = String::new();
+        if io::stdin().lock().read_line(&mut input)? == 0 {
+            // End the unanswered prompt's line
+            println!();
+            return Ok(None);
+        }
+        Ok(Some(input))
+    }
+
+    fn say(&mut self, line: &str) {
+        println!("{}", line);
+    }
+}
+
+/// Error for input that ends mid-wizard; nothing has been written yet
+fn input_ended() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "input ended before the wizard finished; nothing was written",
+    )
+}
+
+/// Prompt user for input with default value
+pub fn prompt(wizard: &mut dyn WizardIo, question: &str, default: &str) -> io::Result<String> {
+    let question = if default.is_empty() {
+        format!("{}: ", question)
+    } else {
+        format!("{} [{}]: ", question, default)
+    };
+    let input = wizard.ask(&question)?.ok_or_else(input_ended)?;
+    let input = input.trim();
+
+    if input.is_empty() {
+        Ok(default.to_string())
+    } else {
+        Ok(input.to_string())
+    }
+}
+
+/// Prompt for yes/no with default, asking again on any other answer
+pub fn prompt_yes_no(wizard: &mut dyn WizardIo, question: &str, default: bool) -> io::Result<bool> {
+    let default_str = if default { "Y/n" } else { "y/N" };
+    loop {
+        let input = wizard
+            .ask(&format!("{} [{}]: ", question, default_str))?
+            .ok_or_else(input_ended)?;
+        match input.trim().to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => wizard.say("Please answer y or n."),
+        }
+    }
+}
+
+/// Generate default history patterns based on project type
+pub fn get_default_history_patterns(project_type: &str) -> Vec<&'static str> {
+    match project_type {
+        "aosp" => vec![
+            r"lunch\s+\S+",
+            r"source.*envsetup",
+            r"export\s+\w+=",
+            r"m\s+\S+",
+            r"mm\b",
+            r"mma\b",
+        ],
+        "ros" => vec![
+            r"source.*setup\.bash",
+            r"colcon\s+build",
+            r"catkin_make",
+            r"ros2\s+run",
+            r"ros2\s+launch",
+            r"roslaunch",
+        ],
+        "yocto" => vec![
+            r"source.*oe-init",
+            r"bitbake\s+\S+",
+            r"MACHINE=",
+            r"devtool\s+\S+",
+        ],
+        "buildroot" | "kernel" => vec![
+            r"make\s+\S*config\b",
+            r"make\s+\S+_defconfig",
+            r"make\s+-j",
+            r"ARCH=\S+",
+            r"CROSS_COMPILE=\S+",
+        ],
+        "zephyr" => vec![
+            r"west\s+(build|flash|debug)\b",
+            r"source.*zephyr-env\.sh",
+            r"export\s+\w+=",
+        ],
+        "cargo" => vec![r"cargo\s+(build|run|test)\b", r"export\s+\w+="],
+        "node" => vec![r"(npm|pnpm|yarn|bun)\s+(run\s+)?\S+", r"export\s+\w+="],
+        _ => vec![r"export\s+\w+=", r"source\s+"],
+    }
+}
+
+/// Flags for `context-keeper init`
+#[derive(Debug, Default, clap::Args)]
+pub struct InitOptions {
+    /// Never prompt; use flags and detected values, failing if something is missing
+    #[arg(long, short)]
+    pub yes: bool,
+    /// Overwrite an existing contextkeeper.toml
+    #[arg(long)]
+    pub force: bool,
+    /// Project name (default: directory name)
+    #[arg(long)]
+    pub name: Option<String>,
+    /// Project type(s), comma-separated: aosp, ros, yocto, zephyr, buildroot, kernel, cargo, node, or custom (default: detected)
+    #[arg(long = "type", value_name = "TYPE")]
+    pub project_type: Option<String>,
+    /// Container runtime: podman, docker, or none (default: detected)
+    #[arg(long)]
+    pub runtime: Option<String>,
+    /// Build script entry point, or a Makefile to read targets from (default: first candidate found)
+    #[arg(long, value_name = "PATH")]
+    pub entry_point: Option<String>,
+    /// Directory containing target *.conf files (default: the one with the most)
+    #[arg(long, value_name = "DIR")]
+    pub config_dir: Option<String>,
+    /// AI hint for this project
+    #[arg(long)]
+    pub hint: Option<String>,
+    /// Command that enters the dev shell (default: from flake.nix/shell.nix/.envrc)
+    #[arg(long, value_name = "CMD")]
+    pub activate: Option<String>,
+}
+
+/// Answers collected by the wizard, interactively or from flags
+#[derive(Debug, Default, Clone)]
+pub struct InitAnswers {
+    pub project_name: String,
+    pub project_types: Vec<String>,
+    pub container_runtime: String,
+    pub entry_point: String,
+    /// Makefile to read targets from; empty to let it be found
+    pub makefile: String,
+    pub config_dir: String,
+    pub ai_hint: String,
+    /// Dev-shell activation command; empty when there's no dev shell
+    pub activate: String,
+}
+
+/// Escape a value for a TOML basic string
+pub fn toml_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Render contextkeeper.toml from the wizard answers
+pub fn generate_config_toml(answers: &InitAnswers) -> String {
+    let mut toml_content = String::new();
+
+    toml_content.push_str("# ContextKeeper Configuration\n");
+    toml_content.push_str("# https://github.com/sat0sh-dev/context-keeper\n\n");
+
+    toml_content.push_str("[project]\n");
+    toml_content.push_str(&format!(
+        "name = \"{}\"\n",
+        toml_escape(&answers.project_name)
+    ));
+    let quoted: Vec<String> = answers
+        .project_types
+        .iter()
+        .map(|t| format!("\"{}\"", toml_escape(t)))
+        .collect();
+    match quoted.as_slice() {
+        [one] => toml_content.push_str(&format!("type = {}\n", one)),
+        _ => toml_content.push_str(&format!("type = [{}]\n", quoted.join(", "))),
+    }
+    toml_content.push('\n');
+
+    if !answers.entry_point.is_empty()
+        || !answers.makefile.is_empty()
+        || !answers.config_dir.is_empty()
+    {
+        toml_content.push_str("[scripts]\n");
+        if !answers.entry_point.is_empty() {
+            toml_content.push_str(&format!(
+                "entry_point = \"{}\"\n",
+                toml_escape(&answers.entry_point)
+            ));
+        }
+        if !answers.makefile.is_empty() {
+            toml_content.push_str(&format!(
+                "makefile = \"{}\"\n",
+                toml_escape(&answers.makefile)
+            ));
+        }
+        if !answers.config_dir.is_empty() {
+            toml_content.push_str(&format!(
+                "config_dir = \"{}\"\n",
+                toml_escape(&answers.config_dir)
+            ));
+            toml_content.push_str("config_pattern = \"*.conf\"\n");
+        }
+        toml_content.push('\n');
+    }
+
+    if answers.container_runtime != "none" {
+        toml_content.push_str("[containers]\n");
+        toml_content.push_str(&format!(
+            "runtime = \"{}\"\n",
+            toml_escape(&answers.container_runtime)
+        ));
+        toml_content.push('\n');
+    }
+
+    if !answers.ai_hint.is_empty() {
+        toml_content.push_str("[hints]\n");
+        toml_content.push_str(&format!(
+            "default = \"{}\"\n",
+            toml_escape(&answers.ai_hint)
+        ));
+        toml_content.push('\n');
+    }
+
+    if !answers.activate.is_empty() {
+        toml_content.push_str("[environment]\n");
+        toml_content.push_str(&format!(
+            "activate = \"{}\"\n",
+            toml_escape(&answers.activate)
+        ));
+        toml_content.push('\n');
+    }
+
+    // History config with type-appropriate patterns
+    toml_content.push_str("[history]\n");
+    toml_content.push_str("enabled = true\n");
+    toml_content.push_str("patterns = [\n");
+    let mut patterns: Vec<&str> = Vec::new();
+    for project_type in &answers.project_types {
+        for pattern in get_default_history_patterns(project_type) {
+            if !patterns.contains(&pattern) {
+                patterns.push(pattern);
+            }
+        }
+    }
+    for pattern in patterns {
+        toml_content.push_str(&format!("    \"{}\",\n", toml_escape(pattern)));
+    }
+    toml_content.push_str("]\n");
+    toml_content.push_str("max_entries = 20\n");
+    toml_content.push('\n');
+
+    // Git config
+    toml_content.push_str("[git]\n");
+    toml_content.push_str("auto_detect = true\n");
+    toml_content.push_str("scan_depth = 2\n");
+
+    toml_content
+}
+
+pub fn default_hint(container_runtime: &str) -> &'static str {
+    if container_runtime != "none" {
+        "Build commands must be executed inside the container."
+    } else {
+        ""
+    }
+}
+
+/// Build answers from flags and detection only (`--yes`). Without
+/// `--entry-point` and `--config-dir`, the best-ranked candidates are used.
+pub fn answers_from_flags(root: &Path, opts: &InitOptions) -> io::Result<InitAnswers> {
+    let missing = |what: &str, flag: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("could not determine {}; pass {}", what, flag),
+        )
+    };
+
+    let project_name = opts
+        .name
+        .clone()
+        .or_else(|| get_default_project_name(root))
+        .filter(|name| !name.trim().is_empty())
+        .ok_or_else(|| missing("project name", "--name"))?;
+    let project_types = match &opts.project_type {
+        Some(answer) => parse_project_types(answer),
+        None => default_project_types(&detect_project_types(root, &ProjectIgnore::load(root))),
+    };
+    if project_types.is_empty() {
+        return Err(missing("project type", "--type"));
+    }
+    let unknown = unknown_project_types(&project_types);
+    if !unknown.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "unknown project type {}; valid types: {}",
+                unknown.join(", "),
+                PROJECT_TYPES.join(", ")
+            ),
+        ));
+    }
+    let container_runtime = opts
+        .runtime
+        .clone()
+        .unwrap_or_else(|| detect_container_runtime().unwrap_or("none").to_string());
+    let ai_hint = opts
+        .hint
+        .clone()
+        .unwrap_or_else(|| default_hint(&container_runtime).to_string());
+
+    let (entry_point, makefile) = match &opts.entry_point {
+        Some(path) => BuildCandidate::new(path).answers(),
+        None => find_build_candidates(root)
+            .first()
+            .map(BuildCandidate::answers)
+            .unwrap_or_default(),
+    };
+    let config_dir = match &opts.config_dir {
+        Some(dir) => dir.clone(),
+        None => find_config_dir_candidates(root, &ProjectIgnore::load(root))
+            .into_iter()
+            .next()
+            .map(|(dir, _)| dir)
+            .unwrap_or_default(),
+    };
+
+    Ok(InitAnswers {
+        project_name,
+        project_types,
+        container_runtime,
+        entry_point,
+        makefile,
+        config_dir,
+        ai_hint,
+        activate: opts.activate.clone().unwrap_or_else(|| {
+            find_dev_shell_marker(root)
+                .map(|(_, activate)| activate.to_string())
+                .unwrap_or_default()
+        }),
+    })
+}
+
+/// Offer the build candidates as a numbered list and preview the commands
+/// the chosen one gives, until one is accepted. Returns the `(entry_point,
+/// makefile)` answers; both empty for "none".
+fn prompt_build_candidate(root: &Path, wizard: &mut dyn WizardIo) -> io::Result<(String, String)> {
+    let candidates = find_build_candidates(root);
+    if candidates.is_empty() {
+        let path = prompt(wizard, "Build script entry point (optional)", "")?;
+        if path.is_empty() {
+            return Ok((String::new(), String::new()));
+        }
+        return Ok(BuildCandidate::new(&path).answers());
+    }
+
+    loop {
+        wizard.say("Found build entry points:");
+        for (number, candidate) in candidates.iter().enumerate() {
+            wizard.say(&format!(
+                "  {}. {} ({})",
+                number + 1,
+                candidate.path,
+                candidate.kind.as_str()
+            ));
+        }
+        let answer = prompt(wizard, "Build entry point (number, path, or none)", "1")?;
+        if answer == "none" {
+            return Ok((String::new(), String::new()));
+        }
+        let candidate = match numbered_choice(&answer, &candidates) {
+            Some(candidate) => candidate.clone(),
+            None if answer.parse::<usize>().is_ok() => {
+                wizard.say(&format!("Pick a number from 1 to {}.", candidates.len()));
+                continue;
+            }
+            None => BuildCandidate::new(&answer),
+        };
+
+        let commands = preview_build_candidate(root, &candidate);
+        if commands.is_empty() {
+            wizard.say(&format!("No commands found in {}.", candidate.path));
+        } else {
+            wizard.say(&format!("Commands found in {}:", candidate.path));
+            for command in &commands {
+                wizard.say(&format!("  {}", command));
+            }
+        }
+        if matches!(
+            candidate.kind,
+            CandidateKind::Justfile | CandidateKind::PackageJson
+        ) {
+            wizard.say("(These are read without any config, so nothing is written for them.)");
+        }
+        if prompt_yes_no(wizard, &format!("Use {}?", candidate.path), true)? {
+            return Ok(candidate.answers());
+        }
+    }
+}
+
+/// Offer the directories holding `.conf` files as a numbered list. Without
+/// any, asks for a path only when `ask_anyway` (an entry point was chosen).
+fn prompt_config_dir(
+    root: &Path,
+    ask_anyway: bool,
+    wizard: &mut dyn WizardIo,
+) -> io::Result<String> {
+    let dirs = find_config_dir_candidates(root, &ProjectIgnore::load(root));
+    if dirs.is_empty() {
+        return if ask_anyway {
+            prompt(wizard, "Config directory (optional)", "")
+        } else {
+            Ok(String::new())
+        };
+    }
+
+    wizard.say("Directories with target .conf files:");
+    for (number, (dir, count)) in dirs.iter().enumerate() {
+        wizard.say(&format!(
+            "  {}. {} ({} .conf files)",
+            number + 1,
+            dir,
+            count
+        ));
+    }
+    loop {
+        let answer = prompt(wizard, "Config directory (number, path, or none)", "1")?;
+        if answer == "none" {
+            return Ok(String::new());
+        }
+        match numbered_choice(&answer, &dirs) {
+            Some((dir, _)) => return Ok(dir.clone()),
+            None if answer.parse::<usize>().is_ok() => {
+                wizard.say(&format!("Pick a number from 1 to {}.", dirs.len()));
+            }
+            None => return Ok(answer),
+        }
+    }
+}
+
+/// Ask for anything not already given as a flag
+pub fn answers_from_prompts(
+    root: &Path,
+    opts: &InitOptions,
+    wizard: &mut dyn WizardIo,
+) -> io::Result<InitAnswers> {
+    // Project name
+    let project_name = match &opts.name {
+        Some(name) => name.clone(),
+        None => {
+            let default_name =
+                get_default_project_name(root).unwrap_or_else(|| "my-project".to_string());
+            loop {
+                let name = prompt(wizard, "Project name", &default_name)?;
+                if !name.is_empty() {
+                    break name;
+                }
+                wizard.say("Project name cannot be empty.");
+            }
+        }
+    };
+
+    // Project type
+    let mut project_types = match &opts.project_type {
+        Some(answer) => parse_project_types(answer),
+        None => {
+            let detections = detect_project_types(root, &ProjectIgnore::load(root));
+            let (question, default) = if detections.is_empty() {
+                (
+                    format!("Project type ({})", PROJECT_TYPES.join("/")),
+                    "custom".to_string(),
+                )
+            } else {
+                wizard.say("Detected project types:");
+                for d in &detections {
+                    wizard.say(&format!(
+                        "  - {} ({}, {} confidence)",
+                        d.project_type,
+                        d.evidence,
+                        d.confidence.as_str()
+                    ));
+                }
+                (
+                    "Project type(s), comma-separated".to_string(),
+                    default_project_types(&detections).join(","),
+                )
+            };
+            loop {
+                let types = parse_project_types(&prompt(wizard, &question, &default)?);
+                let unknown = unknown_project_types(&types);
+                if unknown.is_empty() {
+                    break types;
+                }
+                wizard.say(&format!(
+                    "Unknown project type {}; choose from: {}",
+                    unknown.join(", "),
+                    PROJECT_TYPES.join(", ")
+                ));
+            }
+        }
+    };
+    if project_types.is_empty() {
+        project_types.push("custom".to_string());
+    }
+
+    // Container runtime    // Container runtime
+    let container_runtime = match &opts.runtime {
+        Some(runtime) => runtime.clone(),
+        None => {
+            let detected_runtime = detect_container_runtime();
+            let runtime_hint = detected_runtime
+                .map(|r| format!("detected: {}", r))
+                .unwrap_or_else(|| "podman/docker/none".to_string());
+            prompt(
+                wizard,
+                &format!("Container runtime ({})", runtime_hint),
+                detected_runtime.unwrap_or("none"),
+            )?
+        }
+    };
+
+    // Build scripts (optional)
+    let (entry_point, makefile) = match &opts.entry_point {
+        Some(path) => BuildCandidate::new(path).answers(),
+        None => prompt_build_candidate(root, wizard)?,
+    };
+    let config_dir = match &opts.config_dir {
+        Some(dir) => dir.clone(),
+        None => prompt_config_dir(root, !entry_point.is_empty(), wizard)?,
+    };
+
+    // AI hints
+    let ai_hint = match &opts.hint {
+        Some(hint) => hint.clone(),
+        None => prompt(
+            wizard,
+            "AI hint for this project",
+            default_hint(&container_runtime),
+        )?,
+    };
+
+    // Dev shell (Nix / direnv)
+    let activate = match (&opts.activate, find_dev_shell_marker(root)) {
+        (Some(activate), _) => activate.clone(),
+        (None, Some((marker, activate))) => prompt(
+            wizard,
+            &format!("Command to enter the dev shell (found {})", marker),
+            activate,
+        )?,
+        (None, None) => String::new(),
+    };
+
+    Ok(InitAnswers {
+        project_name,
+        project_types,
+        container_runtime,
+        entry_point,
+        makefile,
+        config_dir,
+        ai_hint,
+        activate,
+    })
+}
+
+/// Run the init wizard on the terminal. Without `--yes`, stdin must be a
+/// terminal: piped input would otherwise write a config of defaults.
+pub fn run_init_wizard(root: &Path, opts: &InitOptions, write: &WriteArgs) -> io::Result<()> {
+    if !opts.yes && !io::stdin().is_terminal() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "stdin is not a terminal; run `context-keeper init --yes` \
+             (with --name, --type, ... as needed) instead",
+        ));
+    }
+    run_init_wizard_with(root, opts, write, &mut TerminalIo)
+}
+
+/// Run the init wizard, asking questions through `wizard`. Nothing is
+/// written unless every question was answered, and with `--diff`
+/// nothing at all, so an existing config isn't a reason to stop.
+pub fn run_init_wizard_with(
+    root: &Path,
+    opts: &InitOptions,
+    write: &WriteArgs,
+    wizard: &mut dyn WizardIo,
+) -> io::Result<()> {
+    let config_path = root.join("contextkeeper.toml");
+
+    if !opts.yes {
+        wizard.say("\n🔧 ContextKeeper Setup Wizard\n");
+    }
+
+    // Check if config already exists
+    if config_path.exists() && !opts.force && !write.diff {
+        if opts.yes {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "contextkeeper.toml already exists; pass --force to overwrite",
+            ));
+        }
+        if !prompt_yes_no(
+            wizard,
+            "contextkeeper.toml already exists. Overwrite?",
+            false,
+        )? {
+            wizard.say("Aborted.");
+            return Ok(());
+        }
+    }
+
+    let answers = if opts.yes {
+        answers_from_flags(root, opts)?
+    } else {
+        answers_from_prompts(root, opts, wizard)?
+    };
+
+    let written = write_file(&config_path, &generate_config_toml(&answers), write, false)?;
+    match &written {
+        Written::Diff => return Ok(()),
+        Written::Unchanged => wizard.say(&format!(
+            "\n✅ {} is already up to date",
+            config_path.display()
+        )),
+        Written::Created => wizard.say(&format!("\n✅ Created {}", config_path.display())),
+        Written::Changed { .. } => wizard.say(&format!(
+            "\n✅ Replaced {}{}",
+            config_path.display(),
+            backup_note(&written)
+        )),
+    }
+    if !opts.yes {
+        wizard.say("\nNext steps:");
+        wizard.say("  1. Review and customize contextkeeper.toml");
+        wizard.say("  2. Test with: context-keeper context");
+        wizard.say("  3. Add to Claude Code: ./install.sh (or see README)");
+    }
+
+    Ok(())
+}
+
+/// Write a config for `root` from the detected values alone, as
+/// `init --yes` without flags would, for the `bootstrap_config` tool. A
+/// project with no recognized type gets `custom`. An existing config file,
+/// under any of its names, is only replaced with `force`, after a backup
+/// (see `safe_write`). Without a `name`, the one `init` would default to
+/// is used. Returns the path written and what was written.
+pub fn bootstrap_config(
+    root: &Path,
+    force: bool,
+    name: Option<String>,
+) -> Result<(PathBuf, String), ContextKeeperError> {
+    let existing = find_config_file(root);
+    if let (Some(path), false) = (&existing, force) {
+        return Err(ContextKeeperError::Validation(format!(
+            "{} already exists; pass force: true to overwrite it",
+            display_path(path)
+        )));
+    }
+    // The tool's parameter, not the `--name` flag the error below names
+    let name = name
+        .or_else(|| get_default_project_name(root))
+        .filter(|name| !name.trim().is_empty())
+        .ok_or_else(|| {
+            ContextKeeperError::Validation(
+                "could not determine the project name; pass name".to_string(),
+            )
+        })?;
+    let opts = InitOptions {
+        yes: true,
+        force,
+        name: Some(name),
+        project_type: default_project_types(&detect_project_types(
+            root,
+            &ProjectIgnore::load(root),
+        ))
+        .is_empty()
+        .then(|| "custom".to_string()),
+        ..Default::default()
+    };
+    let answers = answers_from_flags(root, &opts)
+        .map_err(|e| ContextKeeperError::Validation(e.to_string()))?;
+    let content = generate_config_toml(&answers);
+    let path = existing.unwrap_or_else(|| root.join("contextkeeper.toml"));
+    write_file(&path, &content, &WriteArgs::default(), false)
+        .map_err(|e| ContextKeeperError::state_io(&path, e))?;
+    Ok((path, content))
+}
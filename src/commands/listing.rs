@@ -0,0 +1,192 @@
+//! `context-keeper targets` and `context-keeper repos`
+
+use crate::collectors::git::{
+    clear_repo_cache, discover_git_repos_with_walk, max_git_repos, repo_cache_path, RepoWalk,
+};
+use crate::collectors::make::find_makefile;
+use crate::collectors::targets::{collect_build_targets, glob_target_files};
+use crate::collectors::west::collect_west_targets;
+use crate::collectors::{BuildTarget, COLLECTOR_STATS};
+use crate::config::ProjectContext;
+use crate::format::{format_age, format_git_status};
+use crate::i18n::configured_language;
+use crate::output::{print_list, JsonList, JsonOutput};
+use crate::paths::display_path;
+
+/// The `.conf` and west targets, which `targets --ndjson` prints one per
+/// line
+struct TargetList<'a> {
+    targets: &'a [BuildTarget],
+    west_targets: &'a [BuildTarget],
+}
+
+impl JsonList for TargetList<'_> {
+    type Item = BuildTarget;
+
+    fn json_items(&self) -> impl Iterator<Item = &BuildTarget> {
+        self.targets.iter().chain(self.west_targets)
+    }
+}
+
+/// Print the build targets with the file each came from and the glob used
+pub fn run_targets(project: &ProjectContext, json: Option<JsonOutput>) -> serde_json::Result<()> {
+    let patterns = glob_target_files(project);
+    let makefile = find_makefile(project);
+    let targets = collect_build_targets(project);
+    let west_targets = collect_west_targets(project);
+    let warnings = COLLECTOR_STATS.with(|stats| stats.take()).warnings;
+
+    if let Some(output) = json {
+        let patterns: Vec<serde_json::Value> = patterns
+            .iter()
+            .map(|(pattern, files)| match files {
+                Ok(files) => {
+                    serde_json::json!({ "pattern": pattern, "matched_files": files.len() })
+                }
+                Err(e) => serde_json::json!({ "pattern": pattern, "error": e }),
+            })
+            .collect();
+        let value = serde_json::json!({
+            "patterns": patterns,
+            "makefile": makefile,
+            "targets": targets,
+            "west_targets": west_targets,
+            "warnings": warnings,
+        });
+        let list = TargetList {
+            targets: &targets,
+            west_targets: &west_targets,
+        };
+        return print_list(output, &list, &value, None);
+    }
+
+    let print_target = |target: &BuildTarget| {
+        println!("  {}  {}", target.name, target.source_file);
+        if !target.description.is_empty() {
+            println!("      {}", target.description);
+        }
+    };
+
+    if patterns.is_empty() {
+        println!("No scripts.config_dir configured; no .conf targets");
+    }
+    for (pattern, files) in &patterns {
+        let files = match files {
+            Ok(files) => files,
+            Err(e) => {
+                println!("Pattern: {} (invalid: {})", pattern, e);
+                continue;
+            }
+        };
+        println!("Pattern: {} ({} files)", pattern, files.len());
+        for file in files {
+            match targets.iter().find(|t| t.source_file == display_path(file)) {
+                Some(target) => print_target(target),
+                None => println!("  (unreadable or overridden)  {}", file.display()),
+            }
+        }
+    }
+
+    match &makefile {
+        Some(path) => {
+            let make_targets: Vec<&BuildTarget> =
+                targets.iter().filter(|t| t.source == "makefile").collect();
+            println!(
+                "Makefile: {} ({} targets, duplicates of .conf names dropped)",
+                path.display(),
+                make_targets.len()
+            );
+            make_targets.into_iter().for_each(print_target);
+        }
+        None => println!("No Makefile found"),
+    }
+
+    if !west_targets.is_empty() {
+        println!("West builds: {} boards", west_targets.len());
+        west_targets.iter().for_each(print_target);
+    }
+
+    for warning in &warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    Ok(())
+}
+
+/// Print every repository path the git collector checks and how it was
+/// found. `refresh` walks the tree even when a saved repo list is valid.
+pub fn run_repos(
+    project: &ProjectContext,
+    json: Option<JsonOutput>,
+    refresh: bool,
+) -> serde_json::Result<()> {
+    if refresh {
+        clear_repo_cache(&project.root);
+    }
+    let (candidates, walk) = discover_git_repos_with_walk(project);
+
+    if let Some(output) = json {
+        return print_list(output, &candidates, &candidates, None);
+    }
+
+    match &walk {
+        Some(RepoWalk::Fresh) => println!("Auto-detect: fresh walk"),
+        Some(RepoWalk::Cached { saved_at }) => println!(
+            "Auto-detect: cached list from {} ({})",
+            format_age(
+                saved_at,
+                chrono::Utc::now(),
+                configured_language(&project.config)
+            ),
+            display_path(&repo_cache_path(&project.root))
+        ),
+        None => {}
+    }
+
+    if candidates.is_empty() {
+        let auto_detect = project
+            .config
+            .git
+            .as_ref()
+            .and_then(|g| g.auto_detect)
+            .unwrap_or(true);
+        if auto_detect {
+            println!("No repositories found under {}", project.root.display());
+        } else {
+            println!("git.auto_detect is off and no git.paths are configured");
+        }
+        return Ok(());
+    }
+
+    let lang = configured_language(&project.config);
+    for candidate in &candidates {
+        let status = match &candidate.status {
+            Some(info) => match &info.alias {
+                Some(alias) => format!(
+                    "{}, {} ({})",
+                    info.branch,
+                    format_git_status(info, lang),
+                    alias
+                ),
+                None => format!("{}, {}", info.branch, format_git_status(info, lang)),
+            },
+            None if candidate.skipped => "skipped by [[git.repos]]".to_string(),
+            None => "not a git repository".to_string(),
+        };
+        println!(
+            "{}  {}  [{}]  {}",
+            candidate.path,
+            candidate.full_path,
+            candidate.found_by.as_str(),
+            status
+        );
+    }
+    let repos = candidates.iter().filter(|c| c.status.is_some()).count();
+    let max_repos = max_git_repos(&project.config);
+    if repos > max_repos {
+        println!(
+            "Only {} of {} repositories appear in the context: dirty ones first, then the most recently active ([git] max_repos)",
+            max_repos, repos
+        );
+    }
+    Ok(())
+}
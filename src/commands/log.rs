@@ -0,0 +1,64 @@
+//! `context-keeper log`: record a shell command and the `[env] capture`
+//! variables, from a prompt hook in the user's terminal
+//!
+//! Entries go to the same history log as the Claude Code hook's, with an
+//! `env` object added. The project is the nearest directory at or above
+//! the working directory with a config file, so the hook works anywhere
+//! inside a project.
+
+use crate::collectors::history::command_log_path;
+use crate::collectors::shell_env::{capture_env, capture_list};
+use crate::config::{find_config_file, ProjectContext};
+use crate::paths::display_path;
+use crate::sessions::{lock_shared_file, write_atomic};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// The log is cut back to `LOG_KEEP_LINES` lines past this size, as the
+/// log-commands hook does
+pub const MAX_LOG_SIZE: u64 = 1024 * 1024;
+pub const LOG_KEEP_LINES: usize = 500;
+
+/// Nearest of `dir` and its ancestors with a config file
+pub fn log_root(dir: &Path) -> PathBuf {
+    dir.ancestors()
+        .find(|ancestor| find_config_file(ancestor).is_some())
+        .unwrap_or(dir)
+        .to_path_buf()
+}
+
+/// Keep the last `LOG_KEEP_LINES` lines of an oversized log
+fn rotate_log(path: &Path) -> io::Result<()> {
+    if fs::metadata(path)?.len() <= MAX_LOG_SIZE {
+        return Ok(());
+    }
+    let lines: Vec<String> = io::BufReader::new(fs::File::open(path)?)
+        .lines()
+        .collect::<io::Result<_>>()?;
+    let keep = &lines[lines.len().saturating_sub(LOG_KEEP_LINES)..];
+    write_atomic(path, (keep.join("\n") + "\n").as_bytes())
+}
+
+/// Append one entry for `command` run in `dir`
+pub fn run_log(dir: &Path, config_path: Option<PathBuf>, command: Option<&str>) -> io::Result<()> {
+    let project = ProjectContext::load(log_root(dir), config_path);
+    let names = capture_list(&project.config);
+    let entry = serde_json::json!({
+        "timestamp": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        "command": command.unwrap_or_default(),
+        "cwd": display_path(dir),
+        "env": capture_env(&project.config, &names),
+    });
+
+    let path = command_log_path(&project.config);
+    // Held over the rotation too, so no entry lands in the replaced file
+    let _lock = lock_shared_file(&path)?;
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    writeln!(file, "{}", entry)?;
+    drop(file);
+    rotate_log(&path)
+}
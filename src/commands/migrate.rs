@@ -0,0 +1,305 @@
+//! `context-keeper migrate`: move `~/.contextkeeper` from the first layout
+//! to the current one, see `layout`
+//!
+//! The shared `work-state.json` goes to the state directory of the project
+//! it belongs to: the one it names, else the known root (from the diary,
+//! or the current project) most of its working files are under, else the
+//! one picked on the terminal or given with `--project`. The history log
+//! goes to `logs/`. The `layout_version` marker is written last, under
+//! the locks the writers of both files take, so an interrupted run leaves
+//! the old layout in use and the next run finishes it.
+
+use crate::commands::init::{numbered_choice, prompt, TerminalIo, WizardIo};
+use crate::diary::load_diary;
+use crate::error::ContextKeeperError;
+use crate::layout::{
+    current_history_log_path, data_layout, layout_marker_path, legacy_history_log_path,
+    legacy_work_state_path, read_layout_marker, write_layout_marker, LAYOUT_VERSION,
+};
+use crate::paths::display_path;
+use crate::sessions::{lock_shared_file, write_atomic};
+use crate::state::{project_state_dir, read_work_state, WorkState};
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, clap::Args)]
+pub struct MigrateOptions {
+    /// Project the shared work state belongs to (default: the one it was
+    /// saved for, else the known root its working files are under)
+    #[arg(long, value_name = "ROOT")]
+    pub project: Option<PathBuf>,
+    /// List the moves without making any
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// One step of a migration, in the order they are made
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrationStep {
+    /// The shared work state into `root`'s state directory
+    WorkState {
+        from: PathBuf,
+        to: PathBuf,
+        root: PathBuf,
+    },
+    /// The history log into `logs/`, added to the end of one already there
+    HistoryLog {
+        from: PathBuf,
+        to: PathBuf,
+        append: bool,
+    },
+    /// The `layout_version` marker
+    Marker { path: PathBuf },
+}
+
+impl fmt::Display for MigrationStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationStep::WorkState { from, to, root } => write!(
+                f,
+                "move {} to {} (work state of {})",
+                from.display(),
+                to.display(),
+                root.display()
+            ),
+            MigrationStep::HistoryLog { from, to, append } => write!(
+                f,
+                "{} {} to {}",
+                if *append { "append" } else { "move" },
+                from.display(),
+                to.display()
+            ),
+            MigrationStep::Marker { path } => {
+                write!(f, "write {} (layout {})", path.display(), LAYOUT_VERSION)
+            }
+        }
+    }
+}
+
+/// How many of `state`'s working files are under `root`: absolute ones
+/// inside it, relative ones that exist there
+fn files_under(state: &WorkState, root: &Path) -> usize {
+    state
+        .working_files
+        .iter()
+        .chain(&state.unverified_files)
+        .filter(|file| {
+            let path = Path::new(file.as_str());
+            if path.is_absolute() {
+                path.starts_with(root)
+            } else {
+                root.join(path).exists()
+            }
+        })
+        .count()
+}
+
+/// Project roots the diary has states for, and `current`, once each
+fn known_roots(current: &Path) -> Vec<PathBuf> {
+    let mut roots = vec![current.to_path_buf()];
+    let saved = load_diary().map(|(entries, _)| entries).unwrap_or_default();
+    for state in saved.iter().rev() {
+        let root = PathBuf::from(&state.project_root);
+        if !state.project_root.is_empty() && root.is_dir() && !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+    roots
+}
+
+/// `root` as a project root: an existing directory, made absolute
+fn project_root_arg(root: &Path) -> Result<PathBuf, ContextKeeperError> {
+    if !root.is_dir() {
+        return Err(ContextKeeperError::Validation(format!(
+            "{} is not a directory",
+            root.display()
+        )));
+    }
+    root.canonicalize()
+        .map_err(|e| ContextKeeperError::state_io(root, e))
+}
+
+/// The project `state` belongs to; see the module docs. `wizard` is asked
+/// only when nothing else tells.
+fn choose_project(
+    state: &WorkState,
+    opts: &MigrateOptions,
+    current: &Path,
+    wizard: Option<&mut dyn WizardIo>,
+) -> Result<PathBuf, ContextKeeperError> {
+    if let Some(root) = &opts.project {
+        return project_root_arg(root);
+    }
+    if !state.project_root.is_empty() {
+        return Ok(PathBuf::from(&state.project_root));
+    }
+
+    let roots = known_roots(current);
+    let scores: Vec<usize> = roots.iter().map(|root| files_under(state, root)).collect();
+    let best = scores.iter().copied().max().unwrap_or(0);
+    if best > 0 && scores.iter().filter(|&&score| score == best).count() == 1 {
+        let index = scores.iter().position(|&score| score == best).unwrap_or(0);
+        return Ok(roots[index].clone());
+    }
+
+    let Some(wizard) = wizard else {
+        return Err(ContextKeeperError::Validation(
+            "can't tell which project the saved work state belongs to; \
+             rerun with --project <root>"
+                .to_string(),
+        ));
+    };
+    let io_error = |e: io::Error| ContextKeeperError::state_io(&legacy_work_state_path(), e);
+    wizard.say(&format!(
+        "The saved work state doesn't say which project it is for (task: {}).",
+        if state.task_summary.is_empty() {
+            "none"
+        } else {
+            &state.task_summary
+        }
+    ));
+    wizard.say("Known project roots:");
+    for (number, (root, score)) in roots.iter().zip(&scores).enumerate() {
+        wizard.say(&format!(
+            "  {}. {} ({} working files)",
+            number + 1,
+            display_path(root),
+            score
+        ));
+    }
+    loop {
+        let answer = prompt(wizard, "Project (number or path)", "1").map_err(io_error)?;
+        match numbered_choice(&answer, &roots) {
+            Some(root) => return Ok(root.clone()),
+            None if answer.parse::<usize>().is_ok() => {
+                wizard.say(&format!("Pick a number from 1 to {}.", roots.len()));
+            }
+            None => match project_root_arg(Path::new(&answer)) {
+                Ok(root) => return Ok(root),
+                Err(e) => wizard.say(&e.to_string()),
+            },
+        }
+    }
+}
+
+/// The steps that move `~/.contextkeeper` to the current layout; none when
+/// it is there already. `current` is the project the command runs in.
+pub fn plan_migration(
+    opts: &MigrateOptions,
+    current: &Path,
+    wizard: Option<&mut dyn WizardIo>,
+) -> Result<Vec<MigrationStep>, ContextKeeperError> {
+    // A marker from a newer version, or one that doesn't parse
+    data_layout()?;
+    let migrated = read_layout_marker()? == Some(LAYOUT_VERSION);
+    let mut steps = Vec::new();
+
+    let from = legacy_work_state_path();
+    if let Some(state) = read_work_state(&from)? {
+        let root = choose_project(&state, opts, current, wizard)?;
+        let to = project_state_dir(&root).join("work-state.json");
+        // Before the marker the old file is the one in use, and replaces
+        // whatever a run cut short left behind
+        if migrated && to.exists() {
+            return Err(ContextKeeperError::Validation(format!(
+                "{} is left over from the old layout, and {} is in use; \
+                 remove the one you don't need and run migrate again",
+                from.display(),
+                to.display()
+            )));
+        }
+        steps.push(MigrationStep::WorkState { from, to, root });
+    }
+
+    let from = legacy_history_log_path();
+    if from.exists() {
+        let to = current_history_log_path();
+        let append = to.exists();
+        steps.push(MigrationStep::HistoryLog { from, to, append });
+    }
+
+    if !migrated {
+        steps.push(MigrationStep::Marker {
+            path: layout_marker_path(),
+        });
+    }
+    Ok(steps)
+}
+
+fn create_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) => fs::create_dir_all(dir),
+        None => Ok(()),
+    }
+}
+
+/// Make `step`. Runs under the locks of both old files.
+fn apply_step(step: &MigrationStep) -> Result<(), ContextKeeperError> {
+    match step {
+        MigrationStep::WorkState { from, to, root } => {
+            // Read again: a save may have come in since the plan
+            let Some(mut state) = read_work_state(from)? else {
+                return Ok(());
+            };
+            state.project_root = root.to_string_lossy().to_string();
+            let json = serde_json::to_string_pretty(&state).map_err(io::Error::other);
+            json.and_then(|json| {
+                create_parent(to)?;
+                write_atomic(to, json.as_bytes())
+            })
+            .map_err(|e| ContextKeeperError::state_io(to, e))?;
+            fs::remove_file(from).map_err(|e| ContextKeeperError::state_io(from, e))
+        }
+        MigrationStep::HistoryLog { from, to, append } => {
+            let moved = if *append {
+                fs::read(from).and_then(|lines| {
+                    let mut log = fs::OpenOptions::new().append(true).open(to)?;
+                    log.write_all(&lines)?;
+                    fs::remove_file(from)
+                })
+            } else {
+                create_parent(to).and_then(|_| fs::rename(from, to))
+            };
+            moved.map_err(|e| ContextKeeperError::state_io(to, e))
+        }
+        MigrationStep::Marker { .. } => write_layout_marker(),
+    }
+}
+
+/// Plan the migration and, without `--dry-run`, make it, printing each
+/// step. Asks which project the work state is for only on a terminal.
+pub fn run_migrate(opts: &MigrateOptions, current: &Path) -> Result<(), ContextKeeperError> {
+    let mut terminal = TerminalIo;
+    let wizard: Option<&mut dyn WizardIo> = if io::stdin().is_terminal() {
+        Some(&mut terminal)
+    } else {
+        None
+    };
+    let steps = plan_migration(opts, current, wizard)?;
+    if steps.is_empty() {
+        println!(
+            "~/.contextkeeper is on layout {} already; nothing to move.",
+            LAYOUT_VERSION
+        );
+        return Ok(());
+    }
+    if opts.dry_run {
+        println!("Would:");
+        for step in &steps {
+            println!("  {}", step);
+        }
+        return Ok(());
+    }
+
+    let locks = [legacy_work_state_path(), legacy_history_log_path()]
+        .map(|path| lock_shared_file(&path).map_err(|e| ContextKeeperError::state_io(&path, e)));
+    let _locks = locks.into_iter().collect::<Result<Vec<_>, _>>()?;
+    for step in &steps {
+        apply_step(step)?;
+        println!("  {}", step);
+    }
+    println!("~/.contextkeeper is on layout {} now.", LAYOUT_VERSION);
+    Ok(())
+}
@@ -0,0 +1,19 @@
+//! CLI subcommands that are more than a call into the library
+
+pub mod build_wrap;
+pub mod doctor;
+pub mod env;
+pub mod export;
+pub mod git_hooks;
+pub mod hooks;
+pub mod init;
+pub mod listing;
+pub mod log;
+pub mod migrate;
+pub mod open_files;
+pub mod prompt;
+pub mod safe_write;
+pub mod status;
+pub mod statusline;
+#[cfg(feature = "watch")]
+pub mod watch;
@@ -0,0 +1,107 @@
+//! `context-keeper open-files`: report the files open in the editor
+//!
+//! For editors without an extension that writes `open-files.json`, and for
+//! scripts: an editor command or shell alias calls `open-files set` with
+//! the files it has open, and the next context shows them.
+
+use crate::collectors::open_files::{
+    normalize_open_file_path, open_files_path, save_open_files, OpenFile, OpenFiles,
+};
+use crate::commands::log::log_root;
+use crate::config::ProjectContext;
+use crate::error::ContextKeeperError;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, clap::Subcommand)]
+pub enum OpenFilesAction {
+    /// Replace the list; the first --file is the focused one
+    Set {
+        /// Open file as PATH or PATH:LINE, relative to the current
+        /// directory or absolute; repeat for each file
+        #[arg(long = "file", value_name = "PATH[:LINE]", required = true)]
+        files: Vec<String>,
+        /// Editor the list came from, e.g. vim
+        #[arg(long)]
+        editor: Option<String>,
+    },
+    /// Forget the list, e.g. when the editor closes
+    Clear,
+}
+
+/// "src/a.c:12" as the path and line; a suffix that isn't a line number
+/// stays part of the path
+pub fn parse_file_arg(arg: &str) -> (&str, Option<usize>) {
+    match arg.rsplit_once(':') {
+        Some((path, line)) if !path.is_empty() => match line.parse() {
+            Ok(line) => (path, Some(line)),
+            Err(_) => (arg, None),
+        },
+        _ => (arg, None),
+    }
+}
+
+/// `--file` values as open files under `root`, taken against `dir`
+fn open_files_from_args(
+    root: &Path,
+    dir: &Path,
+    args: &[String],
+) -> Result<Vec<OpenFile>, ContextKeeperError> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut files: Vec<OpenFile> = Vec::new();
+    for (index, arg) in args.iter().enumerate() {
+        let (path, line) = parse_file_arg(arg);
+        if line == Some(0) {
+            return Err(ContextKeeperError::Validation(format!(
+                "{}: lines start at 1",
+                arg
+            )));
+        }
+        let path =
+            normalize_open_file_path(root, &dir.join(path).to_string_lossy()).ok_or_else(|| {
+                ContextKeeperError::Validation(format!(
+                    "{} is not under the project root {}",
+                    path,
+                    root.display()
+                ))
+            })?;
+        if files.iter().any(|f| f.path == path) {
+            continue;
+        }
+        files.push(OpenFile {
+            path,
+            line,
+            focused_at: (index == 0).then(|| now.clone()),
+        });
+    }
+    Ok(files)
+}
+
+pub fn run_open_files(
+    action: &OpenFilesAction,
+    dir: &Path,
+    config_path: Option<PathBuf>,
+) -> Result<(), ContextKeeperError> {
+    let project = ProjectContext::load(log_root(dir), config_path);
+    match action {
+        OpenFilesAction::Set { files, editor } => {
+            let files = open_files_from_args(&project.root, dir, files)?;
+            let open_files = OpenFiles {
+                updated_at: chrono::Utc::now().to_rfc3339(),
+                editor: editor.clone().unwrap_or_default(),
+                files,
+            };
+            save_open_files(&project.root, &open_files)
+        }
+        OpenFilesAction::Clear => {
+            let path = open_files_path(&project.root);
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    Err(ContextKeeperError::state_io(&path, e))
+                }
+                _ => Ok(()),
+            }
+        }
+    }
+}
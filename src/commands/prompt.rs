@@ -0,0 +1,86 @@
+//! `context-keeper prompt`: a status line from saved state only, cheap
+//! enough to run on every prompt render
+
+use crate::collectors::git::{git_snapshot_path, load_git_snapshot};
+use crate::commands::status::{join_segments, project_name, repo_segment, task_segment};
+use crate::config::ProjectContext;
+use crate::redact::redact_output;
+use crate::state::load_work_state_with_hooks;
+use std::path::{Path, PathBuf};
+
+/// Default `prompt` layout; empty segments are dropped
+pub const DEFAULT_PROMPT_FORMAT: &str = "{dirty_repos}{stale} ▸ {task}";
+
+/// Saved repo statuses older than this get the `~` marker
+pub const PROMPT_STALE_SECS: i64 = 300;
+
+/// The nearest of `dir` and its ancestors with saved repo statuses, so
+/// the prompt works from anywhere inside a project
+pub fn prompt_root(dir: &Path) -> PathBuf {
+    dir.ancestors()
+        .find(|ancestor| git_snapshot_path(ancestor).is_file())
+        .unwrap_or(dir)
+        .to_path_buf()
+}
+
+/// The prompt line, or `None` when there is nothing to show (no changed
+/// repos and no task). Reads the work state and the repo statuses saved
+/// by the last collection; never runs a subprocess.
+pub fn prompt_line(project: &ProjectContext, format: Option<&str>) -> Option<String> {
+    let (dirty, conflicts, stale) = match load_git_snapshot(&project.root) {
+        Some(snapshot) => {
+            let dirty = snapshot.repos.iter().filter(|r| r.is_dirty).count();
+            let conflicts: usize = snapshot.repos.iter().map(|r| r.conflicted_files).sum();
+            let stale = chrono::DateTime::parse_from_rfc3339(&snapshot.saved_at)
+                .map(|saved| {
+                    (chrono::Utc::now() - saved.with_timezone(&chrono::Utc)).num_seconds()
+                        > PROMPT_STALE_SECS
+                })
+                .unwrap_or(true);
+            (dirty, conflicts, stale)
+        }
+        None => (0, 0, false),
+    };
+
+    let task = match load_work_state_with_hooks(&project.root) {
+        Ok(state) => state.map(|ws| ws.task_summary).unwrap_or_default(),
+        Err(e) => {
+            tracing::debug!(error = %e, "work state unreadable");
+            String::new()
+        }
+    };
+
+    if dirty == 0 && conflicts == 0 && task.is_empty() {
+        return None;
+    }
+
+    let stale_marker = if stale && (dirty > 0 || conflicts > 0) {
+        "~"
+    } else {
+        ""
+    };
+    Some(match format {
+        Some(format) => format
+            .replace("{project}", &project_name(project))
+            .replace("{dirty_repos}", &dirty.to_string())
+            .replace("{conflicts}", &conflicts.to_string())
+            .replace("{task}", &task)
+            .replace("{stale}", stale_marker),
+        None => join_segments(
+            &DEFAULT_PROMPT_FORMAT
+                .replace("{dirty_repos}", &repo_segment(dirty, conflicts))
+                .replace("{stale}", stale_marker)
+                .replace("{task}", &task_segment(&task)),
+        ),
+    })
+}
+
+/// Print the prompt line. With `when`, print nothing and only report
+/// whether there is anything to show, for a prompt segment's condition.
+pub fn run_prompt(project: &ProjectContext, format: Option<&str>, when: bool) -> bool {
+    let line = prompt_line(project, format);
+    if let (Some(line), false) = (&line, when) {
+        println!("{}", redact_output(&project.config, line));
+    }
+    line.is_some()
+}
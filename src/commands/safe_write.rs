@@ -0,0 +1,450 @@
+//! Writing the user's files: contextkeeper.toml, settings.json, git hooks
+//! and exports
+//!
+//! Every writer goes through `write_file` (or `remove_file`). A symlink
+//! is followed, so the link stays and the file it points at changes. The
+//! new content is written and synced to a temporary file with a name of
+//! its own beside that file, then renamed over it, so an interrupted
+//! write leaves the old file in place. Before a file is replaced for the
+//! first time in a run, it is copied to `<name>.<YYYYmmdd-HHMMSS>.bak`
+//! beside it (or in the directory `write_file_backup_in` names), unless
+//! `--no-backup`; at any point, the old content is in the file or the
+//! backup. With `--diff`, nothing is written: a unified diff of what
+//! would change is printed instead.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// `--diff` and `--no-backup`, for every command that writes a user file
+#[derive(Debug, Clone, Copy, Default, clap::Args)]
+pub struct WriteArgs {
+    /// Print a unified diff of the files a command would change instead
+    /// of writing them
+    #[arg(long, global = true)]
+    pub diff: bool,
+    /// Don't keep a timestamped .bak of a file before changing it
+    #[arg(long, global = true)]
+    pub no_backup: bool,
+}
+
+impl WriteArgs {
+    /// These, with `--diff` also turned on by a command's `--dry-run`
+    pub fn with_dry_run(self, dry_run: bool) -> Self {
+        WriteArgs {
+            diff: self.diff || dry_run,
+            ..self
+        }
+    }
+}
+
+/// What `write_file` or `remove_file` did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Written {
+    /// The file already had the content (or, to remove, didn't exist)
+    Unchanged,
+    /// Printed the diff, wrote nothing
+    Diff,
+    Created,
+    /// Replaced or removed, with the backup made first, if one was
+    Changed {
+        backup: Option<PathBuf>,
+    },
+}
+
+/// Files backed up in this run; each only the first time it is changed
+static BACKED_UP: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+/// A unified diff from `old` to `new`, headed with `path`
+pub fn print_diff(path: &Path, old: &str, new: &str) {
+    let diff = similar::TextDiff::from_lines(old, new);
+    let name = path.display().to_string();
+    print!("{}", diff.unified_diff().header(&name, &name));
+}
+
+/// `path` beside itself with `suffix` appended to the file name
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// The file a write to `path` lands in: the target of a symlink (a
+/// dangling one included), else `path` itself
+fn write_target(path: &Path) -> io::Result<PathBuf> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_symlink() => match path.canonicalize() {
+            Ok(target) => Ok(target),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let link = fs::read_link(path)?;
+                let target = path.parent().unwrap_or(Path::new("")).join(link);
+                if target == path {
+                    return Err(e);
+                }
+                write_target(&target)
+            }
+            Err(e) => Err(e),
+        },
+        _ => Ok(path.to_path_buf()),
+    }
+}
+
+/// Temporary files made by this process so far, for unique names
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Write `content` to a new temporary file beside `path`, synced, with
+/// `path`'s permissions (or mode 755 for a new `executable`). Nothing
+/// else is touched: a write interrupted here leaves `path` as it was.
+fn stage(path: &Path, content: &str, executable: bool) -> io::Result<PathBuf> {
+    loop {
+        let n = TMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let tmp = sibling(path, &format!(".{}-{}.tmp", std::process::id(), n));
+        // Never reuse a file left by an earlier run that had this pid
+        let mut file = match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp)
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        };
+        let written = (|| {
+            file.write_all(content.as_bytes())?;
+            file.sync_all()?;
+            match (fs::metadata(path), executable) {
+                (Ok(meta), _) => fs::set_permissions(&tmp, meta.permissions()),
+                #[cfg(unix)]
+                (Err(_), true) => {
+                    use std::os::unix::fs::PermissionsExt;
+                    fs::set_permissions(&tmp, fs::Permissions::from_mode(0o755))
+                }
+                (Err(_), _) => Ok(()),
+            }
+        })();
+        return match written {
+            Ok(()) => Ok(tmp),
+            Err(e) => {
+                let _ = fs::remove_file(&tmp);
+                Err(e)
+            }
+        };
+    }
+}
+
+/// Copy `path` to a fresh `.bak` beside it, or in `dir`, unless it was
+/// backed up earlier in this run. Never replaces an older backup.
+fn back_up(path: &Path, dir: Option<&Path>) -> io::Result<Option<PathBuf>> {
+    let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let mut backed_up = BACKED_UP.lock().unwrap_or_else(|e| e.into_inner());
+    if backed_up.get_or_insert_with(HashSet::new).contains(&key) {
+        return Ok(None);
+    }
+    let base = match dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)?;
+            dir.join(path.file_name().unwrap_or_default())
+        }
+        None => path.to_path_buf(),
+    };
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+    let mut backup = sibling(&base, &format!(".{}.bak", stamp));
+    let mut n = 1;
+    while backup.exists() {
+        backup = sibling(&base, &format!(".{}-{}.bak", stamp, n));
+        n += 1;
+    }
+    // Keeps the permissions, so a backed-up hook stays executable
+    fs::copy(path, &backup)?;
+    backed_up.get_or_insert_with(HashSet::new).insert(key);
+    Ok(Some(backup))
+}
+
+/// Write `content` to `path` safely (see the module docs). Parent
+/// directories are created. A replaced file keeps its permissions; a new
+/// one gets the defaults, or mode 755 with `executable`.
+pub fn write_file(
+    path: &Path,
+    content: &str,
+    args: &WriteArgs,
+    executable: bool,
+) -> io::Result<Written> {
+    write_file_backup_in(path, content, args, executable, None)
+}
+
+/// `write_file`, keeping the backup in `backup_dir` rather than beside
+/// the file, e.g. for a file in a directory git tracks
+pub fn write_file_backup_in(
+    path: &Path,
+    content: &str,
+    args: &WriteArgs,
+    executable: bool,
+    backup_dir: Option<&Path>,
+) -> io::Result<Written> {
+    let path = &write_target(path)?;
+    let old = match fs::read_to_string(path) {
+        Ok(text) => Some(text),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    if old.as_deref() == Some(content) {
+        return Ok(Written::Unchanged);
+    }
+    if args.diff {
+        print_diff(path, old.as_deref().unwrap_or_default(), content);
+        return Ok(Written::Diff);
+    }
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    // The new content is complete on disk before the old one is touched
+    let tmp = stage(path, content, executable)?;
+    let replaced = (|| {
+        let backup = match (&old, args.no_backup) {
+            (Some(_), false) => back_up(path, backup_dir)?,
+            _ => None,
+        };
+        fs::rename(&tmp, path)?;
+        Ok(backup)
+    })();
+    let backup = match replaced {
+        Ok(backup) => backup,
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+    };
+    Ok(match old {
+        Some(_) => Written::Changed { backup },
+        None => Written::Created,
+    })
+}
+
+/// Remove `path`, keeping its backup (see `write_file`) unless
+/// `--no-backup`; with `--diff`, print its removal instead
+pub fn remove_file(path: &Path, args: &WriteArgs) -> io::Result<Written> {
+    remove_file_backup_in(path, args, None)
+}
+
+/// `remove_file`, keeping the backup in `backup_dir` (see
+/// `write_file_backup_in`)
+pub fn remove_file_backup_in(
+    path: &Path,
+    args: &WriteArgs,
+    backup_dir: Option<&Path>,
+) -> io::Result<Written> {
+    let old = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Written::Unchanged),
+        Err(e) => return Err(e),
+    };
+    if args.diff {
+        print_diff(path, &old, "");
+        return Ok(Written::Diff);
+    }
+    let backup = if args.no_backup {
+        None
+    } else {
+        back_up(path, backup_dir)?
+    };
+    fs::remove_file(path)?;
+    Ok(Written::Changed { backup })
+}
+
+/// " (previous version kept as <backup>)" when a backup was made, for
+/// the messages that report a write
+pub fn backup_note(written: &Written) -> String {
+    match written {
+        Written::Changed {
+            backup: Some(backup),
+        } => format!(" (previous version kept as {})", backup.display()),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files_in(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn creates_replaces_and_backs_up_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sub/contextkeeper.toml");
+        let args = WriteArgs::default();
+
+        assert_eq!(
+            write_file(&path, "a", &args, false).unwrap(),
+            Written::Created
+        );
+        assert_eq!(
+            write_file(&path, "a", &args, false).unwrap(),
+            Written::Unchanged
+        );
+        let Written::Changed {
+            backup: Some(backup),
+        } = write_file(&path, "b", &args, false).unwrap()
+        else {
+            panic!("expected a backup");
+        };
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "a");
+        // Only the first replacement in a run is backed up
+        assert_eq!(
+            write_file(&path, "c", &args, false).unwrap(),
+            Written::Changed { backup: None }
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "c");
+        assert_eq!(files_in(&dir.path().join("sub")).len(), 2, "no tmp left");
+    }
+
+    #[test]
+    fn diff_and_no_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        fs::write(&path, "old").unwrap();
+        let diff = WriteArgs {
+            diff: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            write_file(&path, "new", &diff, false).unwrap(),
+            Written::Diff
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+
+        let no_backup = WriteArgs {
+            no_backup: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            write_file(&path, "new", &no_backup, false).unwrap(),
+            Written::Changed { backup: None }
+        );
+        assert_eq!(files_in(dir.path()), ["settings.json"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn writes_through_a_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let dotfiles = dir.path().join("dotfiles");
+        fs::create_dir(&dotfiles).unwrap();
+        let real = dotfiles.join("settings.json");
+        fs::write(&real, "old").unwrap();
+        let link = dir.path().join("settings.json");
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        let written = write_file(&link, "new", &WriteArgs::default(), false).unwrap();
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&real).unwrap(), "new");
+        let Written::Changed {
+            backup: Some(backup),
+        } = written
+        else {
+            panic!("expected a backup");
+        };
+        assert_eq!(
+            backup.parent(),
+            Some(real.canonicalize().unwrap().parent().unwrap())
+        );
+        assert_eq!(files_in(dir.path()), ["dotfiles", "settings.json"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn creates_the_target_of_a_dangling_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let link = dir.path().join("hook");
+        std::os::unix::fs::symlink("real-hook", &link).unwrap();
+
+        let written = write_file(&link, "#!/bin/sh\n", &WriteArgs::default(), true).unwrap();
+        assert_eq!(written, Written::Created);
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("real-hook")).unwrap(),
+            "#!/bin/sh\n"
+        );
+    }
+
+    #[test]
+    fn interrupted_write_leaves_the_old_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("contextkeeper.toml");
+        fs::write(&path, "old").unwrap();
+
+        // A run that died after staging, before the rename
+        let left = stage(&path, "half-done", false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+
+        // The next run stages under another name and leaves that one alone
+        let next = stage(&path, "new", false).unwrap();
+        assert_ne!(left, next);
+        assert_eq!(fs::read_to_string(&left).unwrap(), "half-done");
+        fs::remove_file(next).unwrap();
+
+        write_file(&path, "new", &WriteArgs::default(), false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&left).unwrap(), "half-done");
+    }
+
+    #[test]
+    fn unwritable_target_is_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("target");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("keep"), "x").unwrap();
+
+        assert!(write_file(&path, "new", &WriteArgs::default(), false).is_err());
+        assert_eq!(files_in(dir.path()), ["target"]);
+        assert_eq!(files_in(&path), ["keep"]);
+    }
+
+    #[test]
+    fn backup_in_another_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let hooks = dir.path().join(".husky");
+        let backups = dir.path().join(".git/context-keeper/hook-backups");
+        fs::create_dir(&hooks).unwrap();
+        let hook = hooks.join("post-commit");
+        fs::write(&hook, "#!/bin/sh\n").unwrap();
+
+        let written = write_file_backup_in(
+            &hook,
+            "#!/bin/sh\necho hi\n",
+            &WriteArgs::default(),
+            true,
+            Some(&backups),
+        )
+        .unwrap();
+        assert_eq!(files_in(&hooks), ["post-commit"]);
+        let Written::Changed {
+            backup: Some(backup),
+        } = written
+        else {
+            panic!("expected a backup");
+        };
+        assert!(backup.starts_with(&backups));
+        assert_eq!(fs::read_to_string(backup).unwrap(), "#!/bin/sh\n");
+
+        remove_file_backup_in(&hook, &WriteArgs::default(), Some(&backups)).unwrap();
+        assert!(files_in(&hooks).is_empty());
+    }
+}
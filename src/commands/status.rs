@@ -0,0 +1,124 @@
+//! `context-keeper status`: one-line summary for shell prompts
+
+use crate::collectors::git::collect_git_repos;
+use crate::commands::init::get_default_project_name;
+use crate::config::ProjectContext;
+use crate::context::{collect_one, CollectorOutput};
+use crate::redact::redact_output;
+use crate::state::load_work_state_with_hooks;
+
+/// Default `status` layout; empty segments are dropped
+pub const DEFAULT_STATUS_FORMAT: &str = "{project} {dirty_repos} ▸ {task} ▸ {devices}";
+
+/// Print a one-line summary for shell prompts and statuslines.
+/// Returns true when something needs attention (conflicts, unhealthy containers).
+pub fn run_status(project: &ProjectContext, all: bool, format: Option<&str>) -> bool {
+    let project_name = project_name(project);
+
+    let (repos, _) = collect_git_repos(project, false);
+    let dirty = repos.iter().filter(|r| r.is_dirty).count();
+    let conflicts: usize = repos.iter().map(|r| r.conflicted_files).sum();
+
+    let task = match load_work_state_with_hooks(&project.root) {
+        Ok(state) => state.map(|ws| ws.task_summary).unwrap_or_default(),
+        Err(e) => {
+            tracing::warn!(error = %e, "work state unreadable");
+            String::new()
+        }
+    };
+
+    // Containers and devices are the slow collectors; only with --all
+    let (containers, devices) = if all {
+        let containers = match collect_one("containers", project) {
+            Some(CollectorOutput::Containers(containers)) => containers,
+            _ => Vec::new(),
+        };
+        let devices = match collect_one("devices", project) {
+            Some(CollectorOutput::Devices(devices, _)) => devices,
+            _ => Vec::new(),
+        };
+        (containers, devices)
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    let unhealthy = containers.iter().filter(|c| c.is_unhealthy()).count();
+
+    let line = match format {
+        Some(format) => format
+            .replace("{project}", &project_name)
+            .replace("{dirty_repos}", &dirty.to_string())
+            .replace("{conflicts}", &conflicts.to_string())
+            .replace("{task}", &task)
+            .replace("{devices}", &devices.len().to_string()),
+        None => {
+            let mut device_segment = device_segment(devices.len());
+            if unhealthy > 0 {
+                device_segment.push_str(&format!(" ⚠{} unhealthy", unhealthy));
+            }
+
+            join_segments(
+                &DEFAULT_STATUS_FORMAT
+                    .replace("{project}", &project_name)
+                    .replace("{dirty_repos}", &repo_segment(dirty, conflicts))
+                    .replace("{task}", &task_segment(&task))
+                    .replace("{devices}", &device_segment),
+            )
+        }
+    };
+
+    println!("{}", redact_output(&project.config, &line));
+    conflicts > 0 || unhealthy > 0
+}
+
+/// `project_name` from config, else the root directory's name
+pub fn project_name(project: &ProjectContext) -> String {
+    project
+        .config
+        .project
+        .as_ref()
+        .and_then(|p| p.name.clone())
+        .or_else(|| get_default_project_name(&project.root))
+        .unwrap_or_default()
+}
+
+/// "⎇2 dirty ✖1 conflicts", or empty when every repo is clean
+pub fn repo_segment(dirty: usize, conflicts: usize) -> String {
+    let mut segment = if dirty > 0 {
+        format!("⎇{} dirty", dirty)
+    } else {
+        String::new()
+    };
+    if conflicts > 0 {
+        segment.push_str(&format!(" ✖{} conflicts", conflicts));
+    }
+    segment
+}
+
+/// "2 devices", or empty without any
+pub fn device_segment(count: usize) -> String {
+    match count {
+        0 => String::new(),
+        1 => "1 device".to_string(),
+        n => format!("{} devices", n),
+    }
+}
+
+/// "task: ..." cut to 40 characters, or empty without a task
+pub fn task_segment(task: &str) -> String {
+    if task.is_empty() {
+        String::new()
+    } else if task.chars().count() > 40 {
+        format!("task: {}…", task.chars().take(39).collect::<String>())
+    } else {
+        format!("task: {}", task)
+    }
+}
+
+/// Drop the empty segments of a " ▸ "-separated line
+pub fn join_segments(line: &str) -> String {
+    line.split(" ▸ ")
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ▸ ")
+}
@@ -0,0 +1,174 @@
+//! `context-keeper statusline`: a Claude Code statusline command
+//!
+//! Claude Code runs the statusline command with a JSON description of the
+//! session on stdin and shows the first line it prints. The line is built
+//! from what the last collection saved (work state, repo statuses, build
+//! processes, devices), so it never runs a subprocess. Payload fields this
+//! doesn't know are ignored, and a payload that doesn't parse is treated as
+//! empty.
+
+use crate::collectors::adb::load_devices_snapshot;
+use crate::collectors::git::load_git_snapshot;
+use crate::collectors::processes::load_builds_snapshot;
+use crate::commands::prompt::{prompt_root, PROMPT_STALE_SECS};
+use crate::commands::status::{
+    device_segment, join_segments, project_name, repo_segment, task_segment,
+};
+use crate::config::ProjectContext;
+use crate::redact::redact_output;
+use crate::state::load_work_state_with_hooks;
+use serde::Deserialize;
+use std::io::{self, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+
+const BOLD: &str = "\x1b[1m";
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// The parts of the statusline payload used here
+#[derive(Debug, Default, Deserialize)]
+pub struct StatuslineInput {
+    pub cwd: Option<String>,
+    pub workspace: Option<StatuslineWorkspace>,
+    /// Claude Code's version; only Claude Code sends it
+    pub version: Option<String>,
+    /// Whether ANSI colors are shown; defaults to true for Claude Code
+    pub colors: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct StatuslineWorkspace {
+    pub current_dir: Option<String>,
+    pub project_dir: Option<String>,
+}
+
+impl StatuslineInput {
+    pub fn parse(input: &str) -> Self {
+        serde_json::from_str(input).unwrap_or_default()
+    }
+
+    /// The session's project directory, else its working directory
+    pub fn dir(&self) -> Option<PathBuf> {
+        let workspace = self.workspace.as_ref();
+        workspace
+            .and_then(|w| w.project_dir.as_deref())
+            .or_else(|| workspace.and_then(|w| w.current_dir.as_deref()))
+            .or(self.cwd.as_deref())
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+    }
+
+    /// `colors` when given, else whether the payload comes from Claude
+    /// Code, which renders ANSI colors. `NO_COLOR` turns them off.
+    pub fn wants_color(&self) -> bool {
+        if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            return false;
+        }
+        self.colors.unwrap_or(self.version.is_some())
+    }
+}
+
+/// Whether an RFC 3339 `saved_at` is within `PROMPT_STALE_SECS`
+fn fresh(saved_at: &str) -> bool {
+    chrono::DateTime::parse_from_rfc3339(saved_at).is_ok_and(|saved| {
+        (chrono::Utc::now() - saved.with_timezone(&chrono::Utc)).num_seconds() <= PROMPT_STALE_SECS
+    })
+}
+
+/// `segment` in `color`, when colors are on and it isn't empty
+fn paint(segment: String, color: &str, colors: bool) -> String {
+    if colors && !segment.is_empty() {
+        format!("{}{}{}", color, segment, RESET)
+    } else {
+        segment
+    }
+}
+
+/// "⚙ ninja", "⚙ soong_ui +2": the oldest running build and how many more
+fn build_segment(commands: &[String]) -> String {
+    match commands {
+        [] => String::new(),
+        [only] => format!("⚙ {}", only),
+        [first, rest @ ..] => format!("⚙ {} +{}", first, rest.len()),
+    }
+}
+
+/// The statusline: project, changed repos, running build, devices and
+/// task. Builds and devices saved more than `PROMPT_STALE_SECS` ago are
+/// left out, and repo counts that old get the `~` marker. Before anything
+/// was collected or saved, the line says so.
+pub fn statusline(project: &ProjectContext, colors: bool) -> String {
+    let git = load_git_snapshot(&project.root);
+    let builds = load_builds_snapshot(&project.root);
+    let devices = load_devices_snapshot(&project.root);
+    let task = match load_work_state_with_hooks(&project.root) {
+        Ok(state) => state.map(|ws| ws.task_summary),
+        Err(e) => {
+            tracing::debug!(error = %e, "work state unreadable");
+            None
+        }
+    };
+
+    let name = paint(project_name(project), BOLD, colors);
+    if git.is_none() && builds.is_none() && devices.is_none() && task.is_none() {
+        return format!("{} ▸ no context collected yet", name);
+    }
+
+    let repos = git
+        .map(|snapshot| {
+            let dirty = snapshot.repos.iter().filter(|r| r.is_dirty).count();
+            let conflicts: usize = snapshot.repos.iter().map(|r| r.conflicted_files).sum();
+            let stale = if !fresh(&snapshot.saved_at) && (dirty > 0 || conflicts > 0) {
+                "~"
+            } else {
+                ""
+            };
+            let color = if conflicts > 0 { RED } else { YELLOW };
+            paint(
+                format!("{}{}", repo_segment(dirty, conflicts), stale),
+                color,
+                colors,
+            )
+        })
+        .unwrap_or_default();
+    let build = builds
+        .filter(|snapshot| fresh(&snapshot.saved_at))
+        .map(|snapshot| {
+            let commands: Vec<String> = snapshot.builds.into_iter().map(|b| b.command).collect();
+            paint(build_segment(&commands), CYAN, colors)
+        })
+        .unwrap_or_default();
+    let devices = devices
+        .filter(|snapshot| fresh(&snapshot.saved_at))
+        .map(|snapshot| device_segment(snapshot.devices.len()))
+        .unwrap_or_default();
+
+    join_segments(&format!(
+        "{} ▸ {} ▸ {} ▸ {} ▸ {}",
+        name,
+        repos,
+        build,
+        devices,
+        task_segment(&task.unwrap_or_default())
+    ))
+}
+
+/// Read the payload from stdin (nothing when stdin is a terminal) and
+/// print the statusline for its project, else for `dir`
+pub fn run_statusline(dir: &Path, config_path: Option<PathBuf>) {
+    let mut payload = String::new();
+    if !io::stdin().is_terminal() {
+        if let Err(e) = io::stdin().read_to_string(&mut payload) {
+            tracing::debug!(error = %e, "statusline payload unreadable");
+        }
+    }
+    let input = StatuslineInput::parse(&payload);
+    let dir = input.dir().unwrap_or_else(|| dir.to_path_buf());
+    let project = ProjectContext::load(prompt_root(&dir), config_path);
+    println!(
+        "{}",
+        redact_output(&project.config, &statusline(&project, input.wants_color()))
+    );
+}
@@ -0,0 +1,171 @@
+//! `context-keeper watch`
+
+use crate::collectors::git::collect_git_repos;
+use crate::collectors::shell_command;
+use crate::config::ProjectContext;
+use crate::context::{annotate_context, collect_context, tool_deadline, CollectionPlan, Context};
+use crate::format::{format_context, Level};
+use crate::redact::Redactor;
+use crate::state::{contextkeeper_dir, work_state_path};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Quiet period before a burst of filesystem events triggers a re-render
+pub const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, clap::Args)]
+pub struct WatchOptions {
+    /// Detail level
+    #[arg(long, short, value_enum, default_value_t = Level::Normal)]
+    pub level: Level,
+    /// Seconds between polls for containers and devices
+    #[arg(long, default_value_t = 10)]
+    pub interval: u64,
+    /// Exit after the first change
+    #[arg(long)]
+    pub once_on_change: bool,
+    /// Run this shell command with the context on stdin instead of printing
+    #[arg(long, value_name = "CMD")]
+    pub exec: Option<String>,
+}
+
+/// Directories whose changes mean the context is stale: each repo's `.git`
+/// (index, HEAD), `~/.contextkeeper` (hook output) and the one holding the
+/// work state
+pub fn watch_paths(project: &ProjectContext) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = collect_git_repos(project, false)
+        .0
+        .iter()
+        .map(|repo| project.resolve(&repo.repo_path).join(".git"))
+        .filter(|path| path.is_dir())
+        .collect();
+
+    let work_state_dir = work_state_path(&project.root)
+        .ok()
+        .and_then(|path| path.parent().map(Path::to_path_buf));
+    for dir in std::iter::once(contextkeeper_dir()).chain(work_state_dir) {
+        if dir.is_dir() && !paths.contains(&dir) {
+            paths.push(dir);
+        }
+    }
+    paths
+}
+
+/// Print the context, or pipe it into `--exec`
+pub fn emit_watch_output(markdown: &str, opts: &WatchOptions) -> io::Result<()> {
+    match &opts.exec {
+        Some(cmd) => {
+            let mut child = shell_command(cmd)
+                .stdin(std::process::Stdio::piped())
+                .spawn()?;
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(markdown.as_bytes())?;
+            }
+            child.wait()?;
+        }
+        None => {
+            // Clear screen and move the cursor home
+            print!("\x1b[2J\x1b[H");
+            println!(
+                "*Updated {}*\n",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+            );
+            println!("{}", markdown);
+            io::stdout().flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Collect the context at `level` now and again whenever git state, the
+/// work-state files, or (every `interval` seconds) containers and devices
+/// may have changed. Each one goes to `on_context` until it returns false
+/// or Ctrl-C.
+pub async fn watch_context<F>(
+    project: Arc<ProjectContext>,
+    level: Level,
+    interval: u64,
+    mut on_context: F,
+) -> notify::Result<()>
+where
+    F: FnMut(&Context) -> io::Result<bool>,
+{
+    use notify::Watcher;
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if event.is_ok() {
+            let _ = tx.send(());
+        }
+    })?;
+    for path in watch_paths(&project) {
+        watcher.watch(&path, notify::RecursiveMode::NonRecursive)?;
+    }
+
+    let deadline = tool_deadline(&project.config);
+    let plan = CollectionPlan::for_level(level, &project.config);
+    let collect = || async {
+        let ct = CancellationToken::new();
+        let mut context = collect_context(Arc::clone(&project), plan, deadline, ct.clone()).await;
+        if level == Level::Full {
+            annotate_context(&project, &mut context, deadline, &ct).await;
+        }
+        context
+    };
+
+    if !on_context(&collect().await)? {
+        return Ok(());
+    }
+
+    let mut poll = tokio::time::interval(Duration::from_secs(interval.max(1)));
+    poll.tick().await; // First tick completes immediately
+
+    loop {
+        tokio::select! {
+            event = rx.recv() => {
+                if event.is_none() {
+                    break;
+                }
+                // Debounce: wait until events stop arriving
+                while let Ok(Some(())) = tokio::time::timeout(WATCH_DEBOUNCE, rx.recv()).await {}
+            }
+            _ = poll.tick() => {}
+            _ = tokio::signal::ctrl_c() => break,
+        }
+
+        if !on_context(&collect().await)? {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Re-render whenever the context changes, see `watch_context`
+pub async fn run_watch(project: Arc<ProjectContext>, opts: WatchOptions) -> notify::Result<()> {
+    let mut last: Option<String> = None;
+    let redactor = Redactor::from_config(&project.config);
+    watch_context(project, opts.level, opts.interval, |context| {
+        let markdown = redactor
+            .redact(&format_context(context, opts.level))
+            .into_owned();
+        match &last {
+            // The first render is only shown, never piped into --exec
+            None if opts.exec.is_some() => {}
+            None => emit_watch_output(&markdown, &opts)?,
+            Some(last) if *last == markdown => return Ok(true),
+            Some(_) => {
+                emit_watch_output(&markdown, &opts)?;
+                if opts.once_on_change {
+                    return Ok(false);
+                }
+            }
+        }
+        last = Some(markdown);
+        Ok(true)
+    })
+    .await
+}
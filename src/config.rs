@@ -0,0 +1,771 @@
+//! `contextkeeper.toml`: config types, discovery and loading
+
+use crate::client_log::CLIENT_LOG;
+use crate::collectors::project_ignore::ProjectIgnore;
+use crate::collectors::{CommandRunner, SystemRunner};
+use crate::diary::parse_since;
+use crate::error::ContextKeeperError;
+use crate::i18n::Language;
+use crate::paths::strip_outside_marker;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+#[derive(Debug, Deserialize, Default)]
+pub struct Config {
+    pub project: Option<ProjectConfig>,
+    pub scripts: Option<ScriptsConfig>,
+    pub containers: Option<ContainersConfig>,
+    pub hints: Option<HintsConfig>,
+    pub history: Option<HistoryConfig>,
+    pub git: Option<GitConfig>,
+    pub server: Option<ServerConfig>,
+    pub logging: Option<LoggingConfig>,
+    pub yocto: Option<YoctoConfig>,
+    pub kconfig: Option<KconfigConfig>,
+    pub environment: Option<EnvironmentConfig>,
+    pub gerrit: Option<GerritConfig>,
+    pub pull_requests: Option<PullRequestsConfig>,
+    pub tmux: Option<TmuxConfig>,
+    pub editor: Option<EditorConfig>,
+    pub remote: Option<RemoteConfig>,
+    pub diary: Option<DiaryConfig>,
+    pub stats: Option<StatsConfig>,
+    pub limits: Option<LimitsConfig>,
+    pub work_state: Option<WorkStateConfig>,
+    pub processes: Option<ProcessesConfig>,
+    pub builds: Option<BuildsConfig>,
+    pub resources: Option<ResourcesConfig>,
+    pub adb: Option<AdbConfig>,
+    pub env: Option<EnvConfig>,
+    pub recent_files: Option<RecentFilesConfig>,
+    /// Attention score by kind of issue, replacing the default; 0 hides it
+    pub priorities: Option<BTreeMap<AttentionKind, i64>>,
+    /// Build output glob by target name, like `ARTIFACT_PATH`
+    pub artifacts: Option<HashMap<String, String>>,
+    pub custom_collectors: Option<Vec<CustomCollectorConfig>>,
+    pub output: Option<OutputConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProjectConfig {
+    pub name: Option<String>,
+    /// One type or a list, e.g. `["yocto", "ros"]`
+    #[serde(rename = "type")]
+    pub project_type: Option<OneOrMany>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScriptsConfig {
+    pub entry_point: Option<String>,
+    /// One directory or a list; later directories win on duplicate names
+    pub config_dir: Option<OneOrMany>,
+    pub config_pattern: Option<String>,
+    /// Match `config_pattern` without regard to case (default: false)
+    pub config_pattern_ignore_case: Option<bool>,
+    /// Makefile to read targets from (default: auto-detect in project root)
+    pub makefile: Option<String>,
+    /// List recipes from a justfile in the project root (default: true)
+    pub justfile: Option<bool>,
+    /// Values for `${VAR}` in target .conf files, ahead of the environment
+    pub vars: Option<HashMap<String, String>>,
+    #[allow(dead_code)]
+    pub extract_vars: Option<Vec<String>>,
+}
+
+/// A config value written either as a single string or as an array
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    pub fn to_vec(&self) -> Vec<&str> {
+        match self {
+            OneOrMany::One(value) => vec![value.as_str()],
+            OneOrMany::Many(values) => values.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContainersConfig {
+    pub runtime: Option<String>,
+    /// Where to read running containers from (default: auto)
+    pub backend: Option<ContainerBackend>,
+    /// How to run a command in a target's container, with `{container}`
+    /// and `{cmd}` (default: `<runtime> exec -i {container} bash -lc '{cmd}'`)
+    pub exec_template: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HintsConfig {
+    pub default: Option<String>,
+    /// Hints keyed by target name, shown with that target
+    pub targets: Option<BTreeMap<String, String>>,
+    /// Hints shown only while a condition is detected
+    pub when: Option<WhenHints>,
+}
+
+/// `[hints.when]`: a hint per condition, and per idle time
+#[derive(Debug, Default, Deserialize)]
+pub struct WhenHints {
+    /// Hints by how long the project has been idle, e.g. `"48h" = "..."`;
+    /// each one passed is shown, shortest first (see `idle`)
+    #[serde(default)]
+    pub idle_over: BTreeMap<IdleThreshold, ConditionalHint>,
+    #[serde(flatten)]
+    pub conditions: BTreeMap<HintCondition, ConditionalHint>,
+}
+
+/// An `idle_over` key: a number and `m`, `h`, `d` or `w`, e.g. `48h`.
+/// Ordered by length, so `90m` comes before `2h`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct IdleThreshold {
+    pub minutes: i64,
+    /// As written in the config
+    pub text: String,
+}
+
+impl<'de> Deserialize<'de> for IdleThreshold {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        let minutes = parse_since(&text)
+            .map_err(serde::de::Error::custom)?
+            .num_minutes();
+        Ok(IdleThreshold { minutes, text })
+    }
+}
+
+/// States a `[hints.when.<condition>]` hint can depend on. Unknown names
+/// are rejected when the config is loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HintCondition {
+    /// A device is in fastboot mode
+    FastbootDevice,
+    /// A device is connected over adb
+    AdbDevice,
+    /// A container named by a target isn't running
+    ContainerDown,
+    /// A repository has unmerged paths
+    Conflicts,
+}
+
+/// Issues ranked for the attention list, as `[priorities]` names them.
+/// Declared from the highest default score down, which also breaks ties.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttentionKind {
+    /// A rebase, merge, cherry-pick, revert or bisect stopped halfway
+    GitOperation,
+    /// A repository has unmerged paths
+    Conflicts,
+    /// A checked filesystem is below `[resources] min_free_gb`
+    LowDisk,
+    /// A container's health check is failing
+    UnhealthyContainer,
+    /// A container named by a target isn't running
+    ContainerDown,
+    /// The `[adb]` device isn't connected
+    DeviceMissing,
+    /// Saved working files are gone or no longer modified
+    StaleWorkState,
+    /// A build artifact is older than the last commit
+    StaleArtifact,
+    /// A branch is behind its upstream
+    BehindUpstream,
+    /// A repository has uncommitted changes
+    DirtyRepo,
+}
+
+impl AttentionKind {
+    pub fn default_score(&self) -> i64 {
+        match self {
+            AttentionKind::GitOperation => 100,
+            AttentionKind::Conflicts => 90,
+            AttentionKind::LowDisk => 80,
+            AttentionKind::UnhealthyContainer => 70,
+            AttentionKind::ContainerDown => 60,
+            AttentionKind::DeviceMissing => 50,
+            AttentionKind::StaleWorkState => 40,
+            AttentionKind::StaleArtifact => 30,
+            AttentionKind::BehindUpstream => 20,
+            AttentionKind::DirtyRepo => 10,
+        }
+    }
+}
+
+/// `fastboot_device = "..."` or `[hints.when.fastboot_device] hint = "..."`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ConditionalHint {
+    Text(String),
+    Table { hint: String },
+}
+
+impl ConditionalHint {
+    pub fn text(&self) -> &str {
+        match self {
+            ConditionalHint::Text(hint) | ConditionalHint::Table { hint } => hint,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryConfig {
+    pub enabled: Option<bool>,
+    pub log_file: Option<String>,
+    pub patterns: Option<Vec<String>>,
+    pub max_entries: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitConfig {
+    /// Explicit list of repository paths to check (relative to project root)
+    pub paths: Option<Vec<String>>,
+    /// Auto-detect git repositories in subdirectories
+    pub auto_detect: Option<bool>,
+    /// Max depth for auto-detection (default: 2)
+    pub scan_depth: Option<usize>,
+    /// Most changed files recorded in a saved work state (default: 20)
+    pub max_working_files: Option<usize>,
+    /// Most repositories in the context, dirty and recently active ones
+    /// first (default: 10)
+    pub max_repos: Option<usize>,
+    /// Reuse the auto-detected repo list for up to this many seconds while
+    /// the top-level directories are unchanged (default: 3600, 0 disables)
+    pub repo_cache_max_age_secs: Option<u64>,
+    /// Which untracked files `git status` looks for (default: "normal")
+    pub untracked: Option<UntrackedFiles>,
+    /// Per-repository limit on `git status` in milliseconds (default: 3000)
+    pub status_timeout_ms: Option<u64>,
+    /// How the status is read (default: "cli")
+    pub backend: Option<GitBackend>,
+    /// `[[git.repos]]`: settings for the repositories a path or glob
+    /// matches, ahead of the ones above
+    pub repos: Option<Vec<GitRepoConfig>>,
+}
+
+/// One `[[git.repos]]` entry. A repository takes the most specific entry
+/// that matches it (see `repo_override`); entries don't combine.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitRepoConfig {
+    /// Repository path relative to the project root, or a glob such as
+    /// `vendor/*` (`*` stops at `/`)
+    pub path: String,
+    /// Overrides `[git] untracked`
+    pub untracked: Option<UntrackedFiles>,
+    /// List the repo below full level and look up its PRs even while it's
+    /// clean (default: false)
+    pub show_clean: Option<bool>,
+    /// Count ahead/behind against this branch instead of the upstream
+    pub base_branch: Option<String>,
+    /// Leave the repo out of the context (default: false)
+    pub skip: Option<bool>,
+    /// Name shown instead of the path
+    pub alias: Option<String>,
+}
+
+/// `git status --untracked-files=<mode>`. Unknown modes are rejected when
+/// the config is loaded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UntrackedFiles {
+    /// Every untracked file, including inside untracked directories
+    All,
+    /// Untracked files and directories, without descending into the latter
+    #[default]
+    Normal,
+    /// Skip untracked files; much faster on very large trees
+    No,
+}
+
+impl UntrackedFiles {
+    pub fn as_arg(&self) -> &'static str {
+        match self {
+            UntrackedFiles::All => "--untracked-files=all",
+            UntrackedFiles::Normal => "--untracked-files=normal",
+            UntrackedFiles::No => "--untracked-files=no",
+        }
+    }
+}
+
+/// `[git] backend`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GitBackend {
+    /// `git status` and `git log`
+    #[default]
+    Cli,
+    /// In-process through gix, with the `gix-backend` feature; the CLI
+    /// without it
+    Gix,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ServerConfig {
+    /// Overall deadline for a single tool call in seconds (default: 10)
+    pub tool_timeout_secs: Option<u64>,
+    /// How long get_dev_context reuses a collected context, in seconds
+    /// (default: 15, 0 disables the cache)
+    pub cache_ttl_secs: Option<u64>,
+    /// Collect the context in the background once the server starts
+    /// (default: true)
+    pub prewarm: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YoctoConfig {
+    /// Build directory with conf/local.conf (default: newest build*/)
+    pub build_dir: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KconfigConfig {
+    /// `.config` symbols to report, e.g. ["CONFIG_ARCH", "BR2_DEFCONFIG"]
+    pub symbols: Option<Vec<String>>,
+}
+
+/// Opt-in Gerrit lookups for repositories with local work
+#[derive(Deserialize)]
+pub struct GerritConfig {
+    /// Server, e.g. "review.example.com" or "https://review.example.com/r"
+    pub host: String,
+    /// HTTP credentials; without them `~/.netrc` is used
+    pub username: Option<String>,
+    pub token: Option<String>,
+    /// Gerrit project per repository path, when the remote doesn't tell
+    pub projects: Option<BTreeMap<String, String>>,
+    /// Limit on the Gerrit query in milliseconds (default: 3000)
+    pub timeout_ms: Option<u64>,
+}
+
+// Hand-written so the token never reaches a log line
+impl std::fmt::Debug for GerritConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GerritConfig")
+            .field("host", &self.host)
+            .field("username", &self.username)
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("projects", &self.projects)
+            .field("timeout_ms", &self.timeout_ms)
+            .finish()
+    }
+}
+
+/// Opt-in GitHub / GitLab lookups through the `gh` and `glab` CLIs
+#[derive(Debug, Deserialize)]
+pub struct PullRequestsConfig {
+    /// Ask `gh` / `glab` for open PRs (default: false)
+    pub enabled: Option<bool>,
+    /// Limit on each CLI call in milliseconds (default: 3000)
+    pub timeout_ms: Option<u64>,
+}
+
+/// Opt-in snapshot of the tmux panes working inside the project
+#[derive(Debug, Deserialize)]
+pub struct TmuxConfig {
+    /// Run `tmux list-panes` (default: false)
+    pub enabled: Option<bool>,
+}
+
+/// Files open in the editor, as `open-files.json` reports them
+#[derive(Debug, Deserialize)]
+pub struct EditorConfig {
+    /// Ignore the list once it is this many seconds old (default: 14400)
+    pub max_age_secs: Option<u64>,
+}
+
+/// A build server whose git, container and target state is collected
+/// over SSH and shown beside the local context
+#[derive(Debug, Deserialize)]
+pub struct RemoteConfig {
+    /// SSH host, e.g. "buildbox" (an alias from ~/.ssh/config works)
+    pub host: String,
+    pub user: Option<String>,
+    /// Project directory on the host (default: the login directory)
+    pub remote_root: Option<String>,
+    /// Extra ssh arguments, e.g. ["-p", "2222"]
+    pub ssh_args: Option<Vec<String>>,
+    /// Sections collected remotely (default: git, containers, targets)
+    pub sections: Option<Vec<String>>,
+    /// Limit on each ssh call in milliseconds (default: 5000)
+    pub timeout_ms: Option<u64>,
+}
+
+/// Secret redaction applied to everything the tool prints, its language
+/// and size
+#[derive(Debug, Default, Deserialize)]
+pub struct OutputConfig {
+    /// Replace credentials and tokens with [REDACTED] (default: true)
+    pub redact: Option<bool>,
+    /// Extra regexes whose matches are redacted
+    pub redact_patterns: Option<Vec<String>>,
+    /// Per pattern (a built-in name, or the regex from `redact_patterns`),
+    /// strings whose presence in a match keeps it, for false positives
+    pub redact_allow: Option<HashMap<String, Vec<String>>>,
+    /// Language of section headings and labels; collected data is shown
+    /// as it came (default: en)
+    pub language: Option<Language>,
+    /// Largest context output in bytes, for clients that cut tool results
+    /// at a fixed size: over it the context is shown a level down (see
+    /// `format::budget`). Unset or 0 for no cap
+    pub max_output_bytes: Option<usize>,
+}
+
+/// A user-configured command whose output becomes a section of its own.
+/// It runs whatever is configured, in the project root, with a cleared
+/// environment apart from PATH, HOME, the locale and a few others.
+#[derive(Debug, Deserialize)]
+pub struct CustomCollectorConfig {
+    /// Section heading, e.g. "Flash queue"
+    pub name: String,
+    /// Program to run: a path relative to the project root, or a name on PATH
+    pub command: String,
+    pub args: Option<Vec<String>>,
+    /// Limit in milliseconds (default: 3000)
+    pub timeout_ms: Option<u64>,
+    /// How to read stdout (default: text)
+    pub format: Option<CustomFormat>,
+    /// Levels the section shows at (default: ["normal", "full"])
+    pub levels: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerBackend {
+    /// The runtime's REST socket when there is one and it answers, else
+    /// the CLI
+    #[default]
+    Auto,
+    /// Only the socket
+    Socket,
+    /// Only `<runtime> ps`
+    Cli,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CustomFormat {
+    /// Shown verbatim, capped in size
+    #[default]
+    Text,
+    /// A JSON object, shown as a key/value table
+    Json,
+}
+
+/// The append-only log of saved work states behind `context-keeper diary`
+#[derive(Debug, Deserialize)]
+pub struct DiaryConfig {
+    /// Days entries are kept; 0 stops recording (default: 30)
+    pub retention_days: Option<u64>,
+}
+
+/// The local record of MCP tool calls behind `context-keeper stats`
+#[derive(Debug, Deserialize)]
+pub struct StatsConfig {
+    /// Record each tool call in ~/.contextkeeper/stats.jsonl (default: true)
+    pub enabled: Option<bool>,
+}
+
+/// Caps on oversized inputs; what goes over one is summarized instead
+#[derive(Debug, Deserialize)]
+pub struct LimitsConfig {
+    /// `.conf` files read per `scripts.config_dir` pattern (default: 1000)
+    pub max_config_files: Option<usize>,
+    /// Paths read from one repository's `git status` or `git diff`
+    /// listing (default: 10000)
+    pub max_status_lines: Option<usize>,
+    /// Longest history log line read, in bytes (default: 65536)
+    pub max_line_bytes: Option<usize>,
+    /// Todos kept in a work state (default: 500)
+    pub max_todos: Option<usize>,
+}
+
+/// Build processes looked for on the host
+#[derive(Debug, Deserialize)]
+pub struct ProcessesConfig {
+    /// Commands that count as a build, e.g. `["ninja", "cargo build"]`
+    /// (default: the project type's preset)
+    pub patterns: Option<Vec<String>>,
+}
+
+/// Results `context-keeper build-wrap` records
+#[derive(Debug, Deserialize)]
+pub struct BuildsConfig {
+    /// Records kept in `builds.jsonl` (default: 200)
+    pub keep: Option<usize>,
+    /// Output lines kept from a failed build (default: 50)
+    pub output_lines: Option<usize>,
+}
+
+/// Terminal environment variables `context-keeper log` records
+#[derive(Debug, Deserialize)]
+pub struct EnvConfig {
+    /// Variable names, e.g. `["TARGET_PRODUCT", "OUT_DIR"]` (default: the
+    /// project type's preset)
+    pub capture: Option<Vec<String>>,
+}
+
+/// Opt-in list of files modified lately where git doesn't look
+#[derive(Debug, Deserialize)]
+pub struct RecentFilesConfig {
+    /// Directories to walk, relative to the project root, e.g.
+    /// `["device/vendor", "out/target/product"]`
+    pub paths: Option<Vec<String>>,
+    /// Files modified this many minutes ago or later count (default: 240)
+    pub max_age_minutes: Option<u64>,
+    /// Files listed at most, newest first (default: 20)
+    pub max_results: Option<usize>,
+}
+
+/// The device adb and fastboot commands should target
+#[derive(Debug, Deserialize)]
+pub struct AdbConfig {
+    /// Serial of the device to use, e.g. "34HAY1CLMN"
+    pub default_serial: Option<String>,
+    /// Model or codename of the device to use, e.g. "Pixel_7" or
+    /// "panther", when the serial changes (emulators, adb over Wi-Fi)
+    pub default_alias: Option<String>,
+}
+
+/// Free disk space checked for the build directories
+#[derive(Debug, Deserialize)]
+pub struct ResourcesConfig {
+    /// Directories to check besides the project root, relative to it
+    /// (default: the project type's preset, else `out`, `build`, `tmp`)
+    pub paths: Option<Vec<String>>,
+    /// Warn below this many GB free (default: the project type's preset,
+    /// else 10)
+    pub min_free_gb: Option<u64>,
+}
+
+/// What goes into a saved work state
+#[derive(Debug, Deserialize)]
+pub struct WorkStateConfig {
+    /// Record TODO/FIXME/XXX comments in the working files (default: false)
+    pub scan_todos: Option<bool>,
+    /// Record line counts and changed functions of the working files
+    /// against HEAD (default: true)
+    pub capture_diffs: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EnvironmentConfig {
+    /// Command that enters the dev shell, e.g. "nix develop .#cross"
+    pub activate: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoggingConfig {
+    /// Also write logs to ~/.contextkeeper/context-keeper.log (rotated daily)
+    pub file: Option<bool>,
+}
+
+/// A project root and the config loaded from it.
+/// Collectors resolve all relative paths against `root` instead of the
+/// process cwd, so one server can describe several projects.
+#[derive(Debug)]
+pub struct ProjectContext {
+    pub root: PathBuf,
+    pub config: Config,
+    /// Config file in use (explicit `--config` or discovered in `root`)
+    pub config_path: Option<PathBuf>,
+    pub config_mtime: Option<SystemTime>,
+    /// Why the config file could not be used, when it exists but is broken
+    pub config_error: Option<ContextKeeperError>,
+    /// `.contextkeeper-ignore`, which every walking collector honors
+    pub ignore: ProjectIgnore,
+    /// Runs the git, container and device tools for the collectors
+    pub runner: Arc<dyn CommandRunner>,
+}
+
+impl Default for ProjectContext {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::new(),
+            config: Config::default(),
+            config_path: None,
+            config_mtime: None,
+            config_error: None,
+            ignore: ProjectIgnore::default(),
+            runner: Arc::new(SystemRunner),
+        }
+    }
+}
+
+impl ProjectContext {
+    /// With `language` (`--lang`) in place of `[output] language`
+    pub fn with_language(mut self, language: Option<Language>) -> Self {
+        if let Some(language) = language {
+            let output = self.config.output.get_or_insert_with(OutputConfig::default);
+            output.language = Some(language);
+        }
+        self
+    }
+
+    /// Load the project at `root`, using `config_path` instead of searching
+    /// the root when given
+    pub fn load(root: PathBuf, config_path: Option<PathBuf>) -> Self {
+        let config_path = config_path.or_else(|| find_config_file(&root));
+        let config_mtime = config_path.as_deref().and_then(file_mtime);
+        let mut config_error = None;
+        let config = match config_path.as_deref().map(Config::load) {
+            Some(Ok(config)) => config,
+            Some(Err(e)) => {
+                config_error = Some(e);
+                Config::default()
+            }
+            None => Config::default(),
+        };
+        Self {
+            ignore: ProjectIgnore::load(&root),
+            root,
+            config,
+            config_path,
+            config_mtime,
+            config_error,
+            runner: Arc::new(SystemRunner),
+        }
+    }
+
+    /// `[scripts.vars]`, used to expand `${VAR}` in target .conf files
+    pub fn script_vars(&self) -> HashMap<String, String> {
+        self.config
+            .scripts
+            .as_ref()
+            .and_then(|s| s.vars.clone())
+            .unwrap_or_default()
+    }
+
+    /// Log a broken config for callers that carry on with the defaults
+    pub fn warn_config_error(&self) {
+        if let Some(e) = &self.config_error {
+            tracing::warn!(target: CLIENT_LOG, error = %e, "config unreadable, using defaults");
+        }
+    }
+
+    /// Whether the config file or `.contextkeeper-ignore` changed on disk
+    /// since they were loaded
+    pub fn config_changed(&self) -> bool {
+        let current = match &self.config_path {
+            Some(path) => file_mtime(path),
+            None => find_config_file(&self.root).as_deref().and_then(file_mtime),
+        };
+        current != self.config_mtime || self.ignore.changed()
+    }
+
+    /// Resolve a config-relative path, or one from `project_relative`,
+    /// against the project root
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        let path = strip_outside_marker(Path::new(path));
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.root.join(path)
+        }
+    }
+
+    /// Resolve `path` against the directory holding the config file, so
+    /// `--config` pointing elsewhere keeps its relative paths meaningful
+    pub fn resolve_from_config(&self, path: &str) -> PathBuf {
+        let base = self
+            .config_path
+            .as_deref()
+            .and_then(Path::parent)
+            .filter(|dir| !dir.as_os_str().is_empty());
+        match base {
+            Some(dir) if Path::new(path).is_relative() => dir.join(path),
+            _ => self.resolve(path),
+        }
+    }
+}
+
+// ============================================================================
+// Config Reader
+// ============================================================================
+
+pub const CONFIG_FILE_NAMES: [&str; 3] = [
+    "contextkeeper.toml",
+    "context-keeper.toml",
+    ".contextkeeper.toml",
+];
+
+/// Locate the first config file present in `root`
+pub fn find_config_file(root: &Path) -> Option<PathBuf> {
+    CONFIG_FILE_NAMES
+        .iter()
+        .map(|name| root.join(name))
+        .find(|path| path.exists())
+}
+
+pub fn file_mtime(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).ok()?.modified().ok()
+}
+
+impl Config {
+    /// Read and parse a single config file
+    pub fn load(path: &Path) -> Result<Config, ContextKeeperError> {
+        let error = |message: String| ContextKeeperError::Config {
+            path: path.to_path_buf(),
+            message,
+        };
+        let content = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        toml::from_str(&content).map_err(|e| error(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn when_hints(toml: &str) -> Result<WhenHints, toml::de::Error> {
+        toml::from_str(toml)
+    }
+
+    #[test]
+    fn idle_thresholds_parse_and_sort_by_length() {
+        let when = when_hints(
+            r#"
+            [idle_over]
+            "7d" = "Rebuild first"
+            "30m" = "Check the device"
+            "48h" = "Fetch upstream"
+            "#,
+        )
+        .unwrap();
+        let thresholds: Vec<_> = when
+            .idle_over
+            .keys()
+            .map(|t| (t.text.as_str(), t.minutes))
+            .collect();
+        assert_eq!(
+            thresholds,
+            [("30m", 30), ("48h", 48 * 60), ("7d", 7 * 24 * 60)]
+        );
+    }
+
+    #[test]
+    fn bad_idle_threshold_is_a_config_error() {
+        for key in ["2日", "é", "soon", "", "-1h"] {
+            let toml = format!("[idle_over]\n{:?} = \"x\"\n", key);
+            let error = when_hints(&toml).expect_err(key);
+            assert!(
+                error.to_string().contains("invalid duration"),
+                "{}: {}",
+                key,
+                error
+            );
+        }
+    }
+}
@@ -0,0 +1,1239 @@
+//! Context aggregation: runs every collector against a deadline and
+//! merges the results into a `Context`
+
+use crate::attention::{attention_items, AttentionItem};
+use crate::client_log::CLIENT_LOG;
+use crate::clock::clock_warnings;
+use crate::collectors::adb::{self, DeviceSelection};
+use crate::collectors::artifacts::attach_artifacts;
+use crate::collectors::build_results::{collect_build_results, BuildRecord};
+use crate::collectors::code_todos::{collect_code_todos, scan_todos_enabled};
+use crate::collectors::containers::{self, attach_exec_commands, start_command};
+use crate::collectors::custom::{collect_custom_sections, custom_collectors_at, CustomSection};
+use crate::collectors::detect::{detect_project_types, Detection};
+use crate::collectors::entry_point::{collect_entry_point, EntryPointInfo};
+use crate::collectors::environment::{collect_dev_environment, DevEnvironment};
+use crate::collectors::forge::attach_pull_requests;
+use crate::collectors::gerrit::annotate_gerrit;
+use crate::collectors::git::collect_git_repos;
+use crate::collectors::history;
+use crate::collectors::just::{collect_just_recipes, JustRecipe};
+use crate::collectors::kconfig::{collect_kconfig_info, KconfigInfo};
+use crate::collectors::onboarding::{collect_onboarding, Onboarding};
+use crate::collectors::open_files::{collect_open_files, OpenFiles};
+use crate::collectors::packages::{collect_cargo_workspace, collect_package_scripts, CargoPackage};
+use crate::collectors::processes::{collect_build_processes, BuildProcess};
+use crate::collectors::recent_files::{collect_recent_files, recent_file_paths, RecentFile};
+use crate::collectors::remote::{collect_remote_context, RemoteContext};
+use crate::collectors::resources::{collect_resources, ResourceInfo};
+use crate::collectors::ros::{collect_ros_workspace, RosWorkspace};
+use crate::collectors::shell_env::{collect_captured_env, CapturedEnv};
+use crate::collectors::targets::collect_build_targets;
+use crate::collectors::tmux::collect_tmux_panes;
+use crate::collectors::validation::annotate_targets;
+use crate::collectors::west::collect_west_targets;
+use crate::collectors::working_files::{check_working_files, WorkingFileCheck};
+use crate::collectors::yocto::{collect_yocto_info, YoctoInfo};
+use crate::collectors::{
+    collector_capped, collector_unavailable, collector_warning, not_built, AdbDevice, BuildTarget,
+    CodeTodo, ContainerInfo, GitInfo, HistoryEntry, TmuxPane, COLLECTOR_STATS,
+};
+use crate::config::{Config, HintCondition, ProjectContext};
+use crate::error::ContextKeeperError;
+use crate::format::{level_sections, Level, Section};
+use crate::i18n::{configured_language, Language};
+use crate::idle::{idle_duration, last_activity, LastActivity};
+use crate::limits::{overflow_summary, Limits};
+use crate::state::{load_work_state_with_hooks, remember_seen, WorkState};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// Default overall deadline for a single tool call
+pub const DEFAULT_TOOL_TIMEOUT_SECS: u64 = 10;
+
+/// Default time the server reuses a collected context
+pub const DEFAULT_CACHE_TTL_SECS: u64 = 15;
+
+/// Outcome of a single collector run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollectorStatus {
+    Ok,
+    TimedOut,
+    Cancelled,
+    Failed,
+}
+
+impl CollectorStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CollectorStatus::Ok => "ok",
+            CollectorStatus::TimedOut => "timed out",
+            CollectorStatus::Cancelled => "cancelled",
+            CollectorStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Whether a collector's part of the context is there, and if not, why:
+/// nothing to find, a tool or file that can't be asked, turned off in the
+/// config, or cut off by the deadline
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", content = "reason", rename_all = "snake_case")]
+pub enum Availability {
+    /// Found something
+    Ok,
+    /// Asked, and found nothing
+    Empty,
+    /// Couldn't be asked, e.g. "podman not installed"
+    Unavailable(String),
+    /// Turned off in the config
+    Disabled,
+    /// Abandoned at the deadline
+    TimedOut,
+}
+
+impl Availability {
+    /// Worth a line of its own where the section would be: not found
+    /// something, and not just found nothing
+    pub fn is_stub(&self) -> bool {
+        !matches!(self, Availability::Ok | Availability::Empty)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectorReport {
+    pub name: &'static str,
+    pub status: CollectorStatus,
+    #[serde(rename = "duration_ms", serialize_with = "serialize_millis")]
+    pub duration: Duration,
+    /// Entries the collector found
+    pub items: usize,
+    /// External commands it ran
+    pub subprocesses: usize,
+    /// Commands that could not be started (tool missing, permissions)
+    pub warnings: Vec<String>,
+    /// What `[limits]` cut, as "…and N more ..." summaries
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub capped: Vec<String>,
+    pub availability: Availability,
+}
+
+impl CollectorReport {
+    /// Report for a collector that never reported back
+    pub fn abandoned(name: &'static str, status: CollectorStatus, duration: Duration) -> Self {
+        CollectorReport {
+            name,
+            status,
+            duration,
+            items: 0,
+            subprocesses: 0,
+            warnings: Vec::new(),
+            capped: Vec::new(),
+            availability: match status {
+                CollectorStatus::TimedOut => Availability::TimedOut,
+                status => Availability::Unavailable(format!("collector {}", status.as_str())),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Context {
+    pub project_name: String,
+    /// Configured type(s), comma-separated
+    pub project_type: String,
+    /// What the directory looks like, whatever the config says
+    pub detected_types: Vec<Detection>,
+    pub targets: Vec<BuildTarget>,
+    pub containers: Vec<ContainerInfo>,
+    pub available_commands: Vec<String>,
+    pub entry_point: Option<EntryPointInfo>,
+    pub just_recipes: Vec<JustRecipe>,
+    pub cargo_workspace: Vec<CargoPackage>,
+    pub yocto: Option<YoctoInfo>,
+    pub kconfig: Option<KconfigInfo>,
+    /// Nix/direnv dev shell the project expects, when it has one
+    pub environment: Option<DevEnvironment>,
+    /// `[env] capture` variables as `context-keeper log` last recorded them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell_env: Option<CapturedEnv>,
+    pub ros: Option<RosWorkspace>,
+    /// What was detected and how to write a config, when there is none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub onboarding: Option<Onboarding>,
+    /// Applicable hints, in display order (see `resolve_hints`)
+    pub hints: Vec<String>,
+    /// Issues across all collectors, highest score first (see
+    /// `attention_items`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attention: Vec<AttentionItem>,
+    /// The newest of the saved work state, the last logged command and git
+    /// writes; `None` without any (see `idle::last_activity`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_activity: Option<LastActivity>,
+    pub command_history: Vec<HistoryEntry>,
+    pub git_repos: Vec<GitInfo>, // Multiple repositories support
+    /// Repositories past `git.max_repos`, not in `git_repos`
+    #[serde(default)]
+    pub git_repos_omitted: usize,
+    /// Files modified lately under `[recent_files] paths` that git doesn't
+    /// show, newest first, at full level
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recent_files: Vec<RecentFile>,
+    pub adb_devices: Vec<AdbDevice>,
+    /// Device pinned by `[adb]` or `ANDROID_SERIAL`, when one is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_selection: Option<DeviceSelection>,
+    /// tmux panes under the project root, with `[tmux] enabled = true`
+    pub terminal_panes: Vec<TmuxPane>,
+    /// Files open in the editor, as `open-files.json` last reported them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_files: Option<OpenFiles>,
+    /// Builds running under the project root or in its containers
+    pub build_processes: Vec<BuildProcess>,
+    /// Last `build-wrap` result per target, newest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub build_results: Vec<BuildRecord>,
+    /// Free disk space of the build directories, RAM and load
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ResourceInfo>,
+    /// Git, container and target state of the `[remote]` build server
+    pub remote: Option<RemoteContext>,
+    /// Output of the `[[custom_collectors]]` commands, in config order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub custom_sections: Vec<CustomSection>,
+    pub work_state: Option<WorkState>, // Saved work state for recovery
+    /// TODO comments in the working files now, at full level with
+    /// `scan_todos`; `None` when not scanned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_todos: Option<Vec<CodeTodo>>,
+    /// What became of the saved working files: missing, unchanged or
+    /// still modified
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub working_file_checks: Vec<WorkingFileCheck>,
+    #[serde(skip_deserializing)]
+    pub reports: Vec<CollectorReport>, // Per-collector outcome of this collection
+    /// Reference time for "3h ago" ages; the wall clock when unset
+    #[serde(skip)]
+    pub now: Option<chrono::DateTime<chrono::Utc>>,
+    /// Age of the cached collection this was served from; `None` when fresh
+    #[serde(skip)]
+    pub cache_age: Option<Duration>,
+    /// Rules in the project's `.contextkeeper-ignore`; `None` without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ignore_rules: Option<usize>,
+    /// Language of the headings and labels it is formatted with
+    #[serde(skip)]
+    pub language: Language,
+}
+
+impl Context {
+    /// Time ages are measured against
+    pub fn now(&self) -> chrono::DateTime<chrono::Utc> {
+        self.now.unwrap_or_else(chrono::Utc::now)
+    }
+
+    /// TODO comments to show: this collection's scan, else the saved ones
+    pub fn code_todos(&self) -> &[CodeTodo] {
+        match (&self.code_todos, &self.work_state) {
+            (Some(todos), _) => todos,
+            (None, Some(state)) => &state.code_todos,
+            (None, None) => &[],
+        }
+    }
+}
+
+pub fn serialize_millis<S: serde::Serializer>(
+    duration: &Duration,
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    s.serialize_u128(duration.as_millis())
+}
+
+/// Result of one collector, applied to the context once it arrives
+pub enum CollectorOutput {
+    ProjectTypes(Vec<Detection>),
+    Targets(Vec<BuildTarget>),
+    WestTargets(Vec<BuildTarget>),
+    Containers(Vec<ContainerInfo>),
+    Commands(Vec<String>, Option<EntryPointInfo>),
+    JustRecipes(Vec<JustRecipe>),
+    PackageScripts(Vec<String>),
+    CargoWorkspace(Vec<CargoPackage>),
+    Yocto(Option<YoctoInfo>),
+    Kconfig(Option<KconfigInfo>),
+    Environment(Option<DevEnvironment>),
+    Ros(Option<RosWorkspace>),
+    History(Vec<HistoryEntry>),
+    /// The repositories shown, and how many more `git.max_repos` left out
+    Git(Vec<GitInfo>, usize),
+    RecentFiles(Vec<RecentFile>),
+    Devices(Vec<AdbDevice>, Option<DeviceSelection>),
+    Terminals(Vec<TmuxPane>),
+    OpenFiles(Option<OpenFiles>),
+    Processes(Vec<BuildProcess>),
+    BuildResults(Vec<BuildRecord>),
+    Resources(ResourceInfo),
+    ShellEnv(Option<CapturedEnv>),
+    Remote(Option<RemoteContext>),
+    Custom(Vec<CustomSection>),
+    /// Boxed: a work state is several times the size of the other outputs
+    WorkState(Option<Box<WorkState>>),
+    CodeTodos(Vec<CodeTodo>),
+    WorkingFiles(Vec<WorkingFileCheck>),
+    Onboarding(Option<Onboarding>),
+    /// From a collector that found nothing to add, like `NotBuilt`
+    Nothing,
+}
+
+impl CollectorOutput {
+    pub fn item_count(&self) -> usize {
+        match self {
+            CollectorOutput::ProjectTypes(detections) => detections.len(),
+            CollectorOutput::Targets(targets) | CollectorOutput::WestTargets(targets) => {
+                targets.len()
+            }
+            CollectorOutput::Containers(containers) => containers.len(),
+            CollectorOutput::Commands(commands, _) => commands.len(),
+            CollectorOutput::JustRecipes(recipes) => recipes.len(),
+            CollectorOutput::PackageScripts(scripts) => scripts.len(),
+            CollectorOutput::CargoWorkspace(packages) => packages.len(),
+            CollectorOutput::Yocto(info) => usize::from(info.is_some()),
+            CollectorOutput::Kconfig(info) => info.as_ref().map_or(0, |i| i.symbols.len()),
+            CollectorOutput::Environment(env) => usize::from(env.is_some()),
+            CollectorOutput::Ros(workspace) => workspace.as_ref().map_or(0, |w| w.packages.len()),
+            CollectorOutput::History(history) => history.len(),
+            CollectorOutput::Git(repos, _) => repos.len(),
+            CollectorOutput::RecentFiles(files) => files.len(),
+            CollectorOutput::Devices(devices, _) => devices.len(),
+            CollectorOutput::Terminals(panes) => panes.len(),
+            CollectorOutput::OpenFiles(open) => open.as_ref().map_or(0, |o| o.files.len()),
+            CollectorOutput::Processes(builds) => builds.len(),
+            CollectorOutput::BuildResults(records) => records.len(),
+            CollectorOutput::Resources(info) => info.disks.len(),
+            CollectorOutput::ShellEnv(env) => env.as_ref().map_or(0, |e| e.vars.len()),
+            CollectorOutput::Remote(remote) => remote.as_ref().map_or(0, RemoteContext::item_count),
+            CollectorOutput::Custom(sections) => sections.len(),
+            CollectorOutput::WorkState(state) => usize::from(state.is_some()),
+            CollectorOutput::CodeTodos(todos) => todos.len(),
+            CollectorOutput::WorkingFiles(checks) => checks.len(),
+            CollectorOutput::Onboarding(onboarding) => usize::from(onboarding.is_some()),
+            CollectorOutput::Nothing => 0,
+        }
+    }
+
+    pub fn apply(self, ctx: &mut Context) {
+        match self {
+            CollectorOutput::ProjectTypes(detections) => ctx.detected_types = detections,
+            // .conf/Makefile targets first, west builds after
+            CollectorOutput::Targets(targets) => {
+                ctx.targets.splice(0..0, targets);
+            }
+            CollectorOutput::WestTargets(targets) => ctx.targets.extend(targets),
+            CollectorOutput::Containers(containers) => ctx.containers = containers,
+            // Entry-point commands go first, recipes and scripts after,
+            // whichever arrives first
+            CollectorOutput::Commands(commands, entry_point) => {
+                ctx.available_commands.splice(0..0, commands);
+                ctx.entry_point = entry_point;
+            }
+            CollectorOutput::JustRecipes(recipes) => {
+                ctx.available_commands
+                    .extend(recipes.iter().map(JustRecipe::command));
+                ctx.just_recipes = recipes;
+            }
+            CollectorOutput::PackageScripts(scripts) => ctx.available_commands.extend(scripts),
+            CollectorOutput::CargoWorkspace(packages) => ctx.cargo_workspace = packages,
+            CollectorOutput::Yocto(info) => ctx.yocto = info,
+            CollectorOutput::Kconfig(info) => ctx.kconfig = info,
+            CollectorOutput::Environment(env) => ctx.environment = env,
+            CollectorOutput::Ros(workspace) => ctx.ros = workspace,
+            CollectorOutput::History(history) => ctx.command_history = history,
+            CollectorOutput::Git(repos, omitted) => {
+                ctx.git_repos = repos;
+                ctx.git_repos_omitted = omitted;
+            }
+            CollectorOutput::RecentFiles(files) => ctx.recent_files = files,
+            CollectorOutput::Devices(devices, selection) => {
+                ctx.adb_devices = devices;
+                ctx.device_selection = selection;
+            }
+            CollectorOutput::Terminals(panes) => ctx.terminal_panes = panes,
+            CollectorOutput::OpenFiles(open) => ctx.open_files = open,
+            CollectorOutput::Processes(builds) => ctx.build_processes = builds,
+            CollectorOutput::BuildResults(records) => ctx.build_results = records,
+            CollectorOutput::Resources(info) => ctx.resources = Some(info),
+            CollectorOutput::ShellEnv(env) => ctx.shell_env = env,
+            CollectorOutput::Remote(remote) => ctx.remote = remote,
+            CollectorOutput::Custom(sections) => ctx.custom_sections = sections,
+            CollectorOutput::WorkState(state) => ctx.work_state = state.map(|state| *state),
+            CollectorOutput::CodeTodos(todos) => ctx.code_todos = Some(todos),
+            CollectorOutput::WorkingFiles(checks) => ctx.working_file_checks = checks,
+            CollectorOutput::Onboarding(onboarding) => ctx.onboarding = onboarding,
+            CollectorOutput::Nothing => {}
+        }
+    }
+}
+
+pub type CollectorFn = fn(&ProjectContext) -> Result<CollectorOutput, ContextKeeperError>;
+
+/// One source of the context, run on a thread of its own
+pub trait Collector: Send + Sync {
+    /// Name in reports, plans and `--section` lookups
+    fn name(&self) -> &'static str;
+    fn collect(&self, project: &ProjectContext) -> Result<CollectorOutput, ContextKeeperError>;
+}
+
+/// A collector that is a plain function
+pub struct FnCollector {
+    pub name: &'static str,
+    pub collect: CollectorFn,
+}
+
+impl Collector for FnCollector {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn collect(&self, project: &ProjectContext) -> Result<CollectorOutput, ContextKeeperError> {
+        (self.collect)(project)
+    }
+}
+
+/// Stands in for a collector left out of the build: its section says
+/// "built without the `<feature>` feature"
+pub struct NotBuilt {
+    pub name: &'static str,
+    pub feature: &'static str,
+}
+
+impl Collector for NotBuilt {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn collect(&self, _project: &ProjectContext) -> Result<CollectorOutput, ContextKeeperError> {
+        collector_unavailable(not_built(self.feature));
+        Ok(CollectorOutput::Nothing)
+    }
+}
+
+/// The collectors, in report order. Those behind a cargo feature add
+/// themselves through their module's `register`, or a `NotBuilt` in their
+/// place, so the rest of the crate never checks which were built.
+#[derive(Default)]
+pub struct CollectorRegistry {
+    collectors: Vec<Box<dyn Collector>>,
+}
+
+impl CollectorRegistry {
+    pub fn add(&mut self, collector: impl Collector + 'static) {
+        debug_assert!(
+            self.position(collector.name()).is_none(),
+            "collector {} registered twice",
+            collector.name()
+        );
+        self.collectors.push(Box::new(collector));
+    }
+
+    pub fn add_fn(&mut self, name: &'static str, collect: CollectorFn) {
+        self.add(FnCollector { name, collect });
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Collector> {
+        self.collectors.iter().map(|collector| &**collector)
+    }
+
+    pub fn len(&self) -> usize {
+        self.collectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.collectors.is_empty()
+    }
+
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.collectors.iter().position(|c| c.name() == name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Collector> {
+        self.position(name).map(|index| &*self.collectors[index])
+    }
+
+    /// Every collector of this build
+    fn builtin() -> Self {
+        let mut registry = CollectorRegistry::default();
+        registry.add_fn("onboarding", |project| {
+            Ok(CollectorOutput::Onboarding(collect_onboarding(project)))
+        });
+        registry.add_fn("project_type", |project| {
+            Ok(CollectorOutput::ProjectTypes(detect_project_types(
+                &project.root,
+                &project.ignore,
+            )))
+        });
+        registry.add_fn("work_state", |project| {
+            let mut state = load_work_state_with_hooks(&project.root)?;
+            if let Some(state) = &state {
+                remember_seen(&project.root, state);
+            }
+            let max_todos = Limits::from_config(&project.config).max_todos;
+            if let Some(state) = state.as_mut().filter(|s| s.todos.len() > max_todos) {
+                let more = state.todos.len() - max_todos;
+                state.todos.truncate(max_todos);
+                collector_capped(format!(
+                    "work_state: {}",
+                    overflow_summary(more, "todo", "todos", "max_todos")
+                ));
+            }
+            Ok(CollectorOutput::WorkState(state.map(Box::new)))
+        });
+        registry.add_fn("code_todos", |project| {
+            // A state that can't be read is the work_state collector's warning
+            let files = load_work_state_with_hooks(&project.root)
+                .ok()
+                .flatten()
+                .map(|state| state.working_files)
+                .unwrap_or_default();
+            Ok(CollectorOutput::CodeTodos(collect_code_todos(
+                project, &files,
+            )))
+        });
+        registry.add_fn("working_files", |project| {
+            let checks = match load_work_state_with_hooks(&project.root) {
+                Ok(Some(state)) => check_working_files(project, &state),
+                _ => Vec::new(),
+            };
+            Ok(CollectorOutput::WorkingFiles(checks))
+        });
+        registry.add_fn("targets", |project| {
+            let mut targets = collect_build_targets(project);
+            attach_artifacts(project, &mut targets);
+            attach_exec_commands(project, &mut targets);
+            Ok(CollectorOutput::Targets(targets))
+        });
+        registry.add_fn("environment", |project| {
+            Ok(CollectorOutput::Environment(collect_dev_environment(
+                project,
+            )))
+        });
+        registry.add_fn("shell_env", |project| {
+            Ok(CollectorOutput::ShellEnv(collect_captured_env(project)?))
+        });
+        registry.add_fn("west", |project| {
+            let mut targets = collect_west_targets(project);
+            attach_artifacts(project, &mut targets);
+            Ok(CollectorOutput::WestTargets(targets))
+        });
+        containers::register(&mut registry);
+        registry.add_fn("commands", |project| {
+            let (commands, entry_point) = collect_entry_point(project);
+            Ok(CollectorOutput::Commands(commands, entry_point))
+        });
+        registry.add_fn("just", |project| {
+            Ok(CollectorOutput::JustRecipes(collect_just_recipes(project)))
+        });
+        registry.add_fn("package_scripts", |project| {
+            Ok(CollectorOutput::PackageScripts(collect_package_scripts(
+                project,
+            )))
+        });
+        registry.add_fn("cargo", |project| {
+            Ok(CollectorOutput::CargoWorkspace(collect_cargo_workspace(
+                project,
+            )?))
+        });
+        registry.add_fn("yocto", |project| {
+            Ok(CollectorOutput::Yocto(collect_yocto_info(project)))
+        });
+        registry.add_fn("kconfig", |project| {
+            Ok(CollectorOutput::Kconfig(collect_kconfig_info(project)))
+        });
+        registry.add_fn("ros", |project| {
+            Ok(CollectorOutput::Ros(collect_ros_workspace(project)))
+        });
+        history::register(&mut registry);
+        registry.add_fn("git", |project| {
+            let every_status = RUNNING_PLAN.with(|plan| plan.get().every_repo_status());
+            let (mut repos, omitted) = collect_git_repos(project, every_status);
+            attach_pull_requests(project, &mut repos);
+            Ok(CollectorOutput::Git(repos, omitted))
+        });
+        registry.add_fn("recent_files", |project| {
+            Ok(CollectorOutput::RecentFiles(collect_recent_files(project)))
+        });
+        adb::register(&mut registry);
+        registry.add_fn("tmux", |project| {
+            Ok(CollectorOutput::Terminals(collect_tmux_panes(project)))
+        });
+        registry.add_fn("open_files", |project| {
+            collect_open_files(project).map(CollectorOutput::OpenFiles)
+        });
+        registry.add_fn("processes", |project| {
+            Ok(CollectorOutput::Processes(collect_build_processes(project)))
+        });
+        registry.add_fn("build_results", |project| {
+            collect_build_results(project).map(CollectorOutput::BuildResults)
+        });
+        registry.add_fn("resources", |project| {
+            Ok(CollectorOutput::Resources(collect_resources(project)))
+        });
+        registry.add_fn("remote", |project| {
+            Ok(CollectorOutput::Remote(collect_remote_context(project)))
+        });
+        registry.add_fn("custom", |project| {
+            Ok(CollectorOutput::Custom(collect_custom_sections(project)))
+        });
+        // A plan is a bit per collector, and `EVERY_REPO_STATUS`
+        assert!(registry.len() < 32);
+        registry
+    }
+}
+
+pub static COLLECTORS: LazyLock<CollectorRegistry> = LazyLock::new(CollectorRegistry::builtin);
+
+/// Run the collector `name` on the current thread, outside any plan, for
+/// commands that want one part of the context; `None` when it failed
+pub fn collect_one(name: &str, project: &ProjectContext) -> Option<CollectorOutput> {
+    let collector = COLLECTORS.get(name)?;
+    let (_, output) = run_collector(collector, project, CollectionPlan::all());
+    output
+}
+
+/// Plan bit for `git status` in every repository, not just the shortlist
+/// `collect_git_repos` ranks by activity. Full level and `--section` ask
+/// for it. In its own bit, a cached context collected with it covers a
+/// plan without it, and not the other way around.
+const EVERY_REPO_STATUS: u32 = 1 << 31;
+
+thread_local! {
+    /// The plan of the collector running on this thread
+    static RUNNING_PLAN: std::cell::Cell<CollectionPlan> =
+        const { std::cell::Cell::new(CollectionPlan(0)) };
+}
+
+/// Collectors one request needs, as a subset of `COLLECTORS`. Skipped
+/// collectors leave their part of the `Context` empty and get no report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollectionPlan(u32);
+
+impl CollectionPlan {
+    /// Every collector, for JSON output and library callers
+    pub fn all() -> Self {
+        CollectionPlan(((1 << COLLECTORS.len()) - 1) | EVERY_REPO_STATUS)
+    }
+
+    /// What the sections shown at `level` need. Custom commands only run
+    /// when one of them is shown at `level`, the TODO scan and the walk for
+    /// recent files at full level.
+    pub fn for_level(level: Level, config: &Config) -> Self {
+        let mut plan = Self::for_sections(level_sections(level), config);
+        if level != Level::Full {
+            plan = plan.without("code_todos").without("recent_files");
+            plan = CollectionPlan(plan.0 & !EVERY_REPO_STATUS);
+        }
+        // Minimal and oneline only show what is running
+        if matches!(level, Level::Oneline | Level::Minimal) {
+            plan = plan.without("build_results");
+        }
+        let configs = config.custom_collectors.as_deref().unwrap_or_default();
+        if custom_collectors_at(configs, level) {
+            plan
+        } else {
+            plan.without("custom")
+        }
+    }
+
+    /// What `sections` need. Hints also pull in whatever the configured
+    /// `[hints.when]` conditions look at.
+    pub fn for_sections(sections: &[Section], config: &Config) -> Self {
+        let mut plan = CollectionPlan(EVERY_REPO_STATUS);
+        for section in sections {
+            plan = plan.with_all(section_collectors(*section));
+        }
+        // No report line for a collector with nothing configured
+        if config.custom_collectors.as_ref().is_none_or(Vec::is_empty) {
+            plan = plan.without("custom");
+        }
+        if !scan_todos_enabled(config) {
+            plan = plan.without("code_todos");
+        }
+        if recent_file_paths(config).is_empty() {
+            plan = plan.without("recent_files");
+        }
+        if sections.contains(&Section::Hints) {
+            // The `adb -s` hint for the pinned device
+            if config
+                .adb
+                .as_ref()
+                .is_some_and(|adb| adb.default_serial.is_some() || adb.default_alias.is_some())
+            {
+                plan = plan.with_all(&["devices"]);
+            }
+            // Build-in-container hints, when .conf targets can name a container
+            if config
+                .scripts
+                .as_ref()
+                .is_some_and(|scripts| scripts.config_dir.is_some())
+            {
+                plan = plan.with_all(&["containers", "commands"]);
+            }
+            let when = config.hints.as_ref().and_then(|h| h.when.as_ref());
+            for condition in when.into_iter().flat_map(|when| when.conditions.keys()) {
+                plan = plan.with_all(match condition {
+                    HintCondition::FastbootDevice | HintCondition::AdbDevice => &["devices"],
+                    HintCondition::ContainerDown => &["targets", "west", "containers"],
+                    HintCondition::Conflicts => &["git"],
+                });
+            }
+            // The signals the idle time is measured from
+            if when.is_some_and(|when| !when.idle_over.is_empty()) {
+                plan = plan.with_all(&["work_state", "history", "git"]);
+            }
+        }
+        plan
+    }
+
+    fn without(self, name: &str) -> Self {
+        let index = COLLECTORS
+            .position(name)
+            .expect("plan names a collector in COLLECTORS");
+        CollectionPlan(self.0 & !(1 << index))
+    }
+
+    fn with_all(self, names: &[&str]) -> Self {
+        names.iter().fold(self, |plan, name| {
+            let index = COLLECTORS
+                .position(name)
+                .expect("plan names a collector in COLLECTORS");
+            CollectionPlan(plan.0 | 1 << index)
+        })
+    }
+
+    /// Whether the git collector runs `git status` in every repository
+    pub fn every_repo_status(&self) -> bool {
+        self.0 & EVERY_REPO_STATUS != 0
+    }
+
+    pub fn includes(&self, index: usize) -> bool {
+        self.0 & (1 << index) != 0
+    }
+
+    /// Whether a context collected under this plan has everything `other` needs
+    pub fn covers(&self, other: &CollectionPlan) -> bool {
+        other.0 & !self.0 == 0
+    }
+}
+
+/// Collectors whose output a section shows
+pub fn section_collectors(section: Section) -> &'static [&'static str] {
+    match section {
+        Section::Project => &["project_type", "kconfig"],
+        Section::WorkState => &["work_state", "code_todos", "working_files"],
+        // Dev-shell hint, each target's TARGET_HINT, and what to set up
+        // without a config
+        Section::Hints => &["environment", "targets", "onboarding"],
+        Section::Targets => &["targets", "west", "remote", "commands"],
+        Section::Containers => &["containers", "remote"],
+        Section::Yocto => &["yocto"],
+        Section::Ros => &["ros"],
+        Section::Commands => &["commands", "just", "package_scripts", "cargo"],
+        Section::History => &["history"],
+        Section::Git => &["git", "remote", "recent_files"],
+        Section::Devices => &["devices"],
+        Section::Terminals => &["tmux", "open_files"],
+        Section::Builds => &["processes", "build_results"],
+        Section::Resources => &["resources"],
+        Section::Environment => &["shell_env"],
+        // Ranks whatever the other sections collected
+        Section::Attention => &[],
+        Section::Custom => &["custom"],
+        Section::Diagnostics => &[],
+    }
+}
+
+/// Run one collector of `plan` on the current thread and measure it
+pub fn run_collector(
+    collector: &dyn Collector,
+    project: &ProjectContext,
+    plan: CollectionPlan,
+) -> (CollectorReport, Option<CollectorOutput>) {
+    COLLECTOR_STATS.with(|stats| stats.take());
+    RUNNING_PLAN.with(|running| running.set(plan));
+    let start = Instant::now();
+    // A broken source leaves the section empty and says why
+    let mut failure = None;
+    let output = collector
+        .collect(project)
+        .map_err(|e| {
+            collector_warning(e.to_string());
+            failure = Some(e.to_string());
+        })
+        .ok();
+    let duration = start.elapsed();
+    let stats = COLLECTOR_STATS.with(|stats| stats.take());
+    let items = output.as_ref().map_or(0, CollectorOutput::item_count);
+    let availability = match (items, failure.or(stats.unavailable)) {
+        (0, Some(reason)) => Availability::Unavailable(reason),
+        (0, None) if stats.disabled => Availability::Disabled,
+        (0, None) => Availability::Empty,
+        _ => Availability::Ok,
+    };
+
+    tracing::debug!(
+        elapsed_ms = duration.as_millis() as u64,
+        subprocesses = stats.subprocesses,
+        "collector finished"
+    );
+    let report = CollectorReport {
+        name: collector.name(),
+        status: CollectorStatus::Ok,
+        duration,
+        items,
+        subprocesses: stats.subprocesses,
+        warnings: stats.warnings,
+        capped: stats.capped,
+        availability,
+    };
+    (report, output)
+}
+
+/// Overall tool deadline from config
+pub fn tool_deadline(config: &Config) -> Duration {
+    let secs = config
+        .server
+        .as_ref()
+        .and_then(|s| s.tool_timeout_secs)
+        .unwrap_or(DEFAULT_TOOL_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
+
+/// How long the server may serve a context from its cache
+pub fn context_cache_ttl(config: &Config) -> Duration {
+    let secs = config
+        .server
+        .as_ref()
+        .and_then(|s| s.cache_ttl_secs)
+        .unwrap_or(DEFAULT_CACHE_TTL_SECS);
+    Duration::from_secs(secs)
+}
+
+/// Run a blocking closure on a detached thread.
+///
+/// A wedged closure (e.g. an NFS stat hanging inside `fs::read_dir`) keeps its
+/// thread, but never blocks the caller or process exit.
+pub fn spawn_detached<T, F>(f: F) -> tokio::sync::oneshot::Receiver<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(f());
+    });
+    rx
+}
+
+/// Run a blocking closure, giving up at the deadline or on cancellation
+pub async fn run_with_deadline<T, F>(
+    deadline: Duration,
+    ct: &CancellationToken,
+    f: F,
+) -> Result<T, CollectorStatus>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    tokio::select! {
+        result = tokio::time::timeout(deadline, spawn_detached(f)) => match result {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(_)) => Err(CollectorStatus::Failed),
+            Err(_) => Err(CollectorStatus::TimedOut),
+        },
+        _ = ct.cancelled() => Err(CollectorStatus::Cancelled),
+    }
+}
+
+/// Collect the context `plan` asks for, abandoning collectors still running
+/// at the deadline or when the request is cancelled. Abandoned collectors
+/// leave their section empty and are recorded in `Context::reports`.
+pub async fn collect_context(
+    project: Arc<ProjectContext>,
+    plan: CollectionPlan,
+    deadline: Duration,
+    ct: CancellationToken,
+) -> Context {
+    let mut ctx = Context::default();
+    let config = &project.config;
+    ctx.language = configured_language(config);
+    ctx.ignore_rules = project
+        .ignore
+        .path
+        .is_some()
+        .then(|| project.ignore.rules());
+
+    if let Some(project) = &config.project {
+        ctx.project_name = project.name.clone().unwrap_or_default();
+        ctx.project_type = project
+            .project_type
+            .as_ref()
+            .map(|types| types.to_vec().join(", "))
+            .unwrap_or_default();
+    }
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    for (index, collector) in COLLECTORS.iter().enumerate() {
+        if !plan.includes(index) {
+            continue;
+        }
+        let tx = tx.clone();
+        let project = Arc::clone(&project);
+        let span = tracing::debug_span!("collector", name = collector.name());
+        std::thread::spawn(move || {
+            let _entered = span.enter();
+            let _ = tx.send((index, run_collector(collector, &project, plan)));
+        });
+    }
+    drop(tx);
+
+    let mut finished: Vec<Option<CollectorReport>> = vec![None; COLLECTORS.len()];
+    let started = Instant::now();
+    let sleep = tokio::time::sleep(deadline);
+    tokio::pin!(sleep);
+
+    // Status given to collectors that never reported back
+    let abandoned = loop {
+        tokio::select! {
+            received = rx.recv() => match received {
+                Some((index, (report, output))) => {
+                    if let Some(output) = output {
+                        output.apply(&mut ctx);
+                    }
+                    finished[index] = Some(report);
+                }
+                // All senders gone: anything unfinished panicked
+                None => break CollectorStatus::Failed,
+            },
+            _ = &mut sleep => break CollectorStatus::TimedOut,
+            _ = ct.cancelled() => break CollectorStatus::Cancelled,
+        }
+    };
+
+    ctx.reports = COLLECTORS
+        .iter()
+        .zip(finished)
+        .enumerate()
+        .filter(|(index, _)| plan.includes(*index))
+        .map(|(_, (collector, report))| {
+            report.unwrap_or_else(|| {
+                let name = collector.name();
+                tracing::warn!(
+                    target: CLIENT_LOG,
+                    collector = name,
+                    status = abandoned.as_str(),
+                    "collector abandoned"
+                );
+                CollectorReport::abandoned(name, abandoned, started.elapsed())
+            })
+        })
+        .collect();
+
+    // Timestamps ahead of the clock or going back in time
+    for warning in clock_warnings(&ctx) {
+        if let Some(report) = ctx.reports.iter_mut().find(|r| r.name == warning.collector) {
+            report.warnings.push(warning.message);
+        }
+    }
+
+    ctx.last_activity = last_activity(&ctx);
+    ctx.hints = resolve_hints(config, &ctx);
+    ctx.attention = attention_items(config, &ctx);
+    ctx
+}
+
+/// Whether a full-level answer showing `shown` needs `annotate_context`
+pub fn needs_annotation(level: Level, shown: &[Section]) -> bool {
+    level == Level::Full && (shown.contains(&Section::Targets) || shown.contains(&Section::Git))
+}
+
+/// Full-level lookups on top of a collection: container and lunch checks
+/// for the targets, then open Gerrit changes for the repos
+pub async fn annotate_context(
+    project: &Arc<ProjectContext>,
+    ctx: &mut Context,
+    deadline: Duration,
+    ct: &CancellationToken,
+) {
+    annotate_targets(project, ctx, deadline, ct).await;
+    annotate_gerrit(project, ctx, deadline, ct).await;
+}
+
+/// Hints that apply to the collected context: the default hint, the
+/// dev-shell hint, the pinned-device hint, how to build in each target
+/// container, then per-target hints (`TARGET_HINT`, then `[hints.targets]`), then
+/// `[hints.when]` hints whose condition holds, then `[hints.when.idle_over]`
+/// hints for the idle time passed
+pub fn resolve_hints(config: &Config, ctx: &Context) -> Vec<String> {
+    let mut hints = Vec::new();
+    let hints_config = config.hints.as_ref();
+
+    if let Some(default) = hints_config.and_then(|h| h.default.as_deref()) {
+        if !default.is_empty() {
+            hints.push(default.to_string());
+        }
+    }
+
+    if let Some(env) = &ctx.environment {
+        hints.push(format!(
+            "Build tools come from the dev shell ({}); run `{}` first or commands fail with \"command not found\"",
+            env.marker, env.activate
+        ));
+    }
+
+    if let Some(DeviceSelection {
+        pinned: Some(_),
+        serial: Some(serial),
+        ..
+    }) = &ctx.device_selection
+    {
+        hints.push(format!(
+            "Always use `adb -s {0}` (`fastboot -s {0}` in fastboot); bare `adb` may reach another device",
+            serial
+        ));
+    }
+
+    hints.extend(container_build_hints(ctx));
+
+    let target_hints = hints_config.and_then(|h| h.targets.as_ref());
+    for target in &ctx.targets {
+        let configured = target_hints.and_then(|t| t.get(&target.name));
+        for hint in [Some(&target.hint), configured].into_iter().flatten() {
+            if !hint.is_empty() {
+                hints.push(format!("{}: {}", target.name, hint));
+            }
+        }
+    }
+    // Hints for targets that weren't found still matter to whoever asked
+    for (name, hint) in target_hints.into_iter().flatten() {
+        if !ctx.targets.iter().any(|t| &t.name == name) {
+            hints.push(format!("{}: {}", name, hint));
+        }
+    }
+
+    let when = hints_config.and_then(|h| h.when.as_ref());
+    for (condition, hint) in when.into_iter().flat_map(|when| &when.conditions) {
+        if hint_condition_holds(*condition, ctx) {
+            hints.push(hint.text().to_string());
+        }
+    }
+    // Every threshold passed, shortest first
+    if let Some(idle) = idle_duration(ctx) {
+        for (threshold, hint) in when.into_iter().flat_map(|when| &when.idle_over) {
+            if idle.num_minutes() >= threshold.minutes {
+                hints.push(hint.text().to_string());
+            }
+        }
+    }
+
+    hints.dedup();
+    hints
+}
+
+/// Whether the running containers were listed, so a container missing
+/// from `ctx.containers` is really down
+pub fn containers_listed(ctx: &Context) -> bool {
+    ctx.reports
+        .iter()
+        .any(|r| r.name == "containers" && r.status == CollectorStatus::Ok && r.warnings.is_empty())
+}
+
+/// One hint per container the targets build in: the exec command of its
+/// first target, or how to start it when it isn't running
+fn container_build_hints(ctx: &Context) -> Vec<String> {
+    let mut hints = Vec::new();
+    let mut seen: Vec<&str> = Vec::new();
+    for target in ctx.targets.iter().filter(|t| !t.exec_command.is_empty()) {
+        let container = target.container_name.as_str();
+        if seen.contains(&container) {
+            continue;
+        }
+        seen.push(container);
+        let running = ctx.containers.iter().any(|c| c.name == container);
+        if !running && containers_listed(ctx) {
+            hints.push(match start_command(&ctx.available_commands, container) {
+                Some(start) => format!(
+                    "Container `{}` isn't running; start it with `{}` before building",
+                    container, start
+                ),
+                None => format!(
+                    "Container `{}` isn't running; start it before building `{}`",
+                    container, target.name
+                ),
+            });
+            continue;
+        }
+        hints.push(format!(
+            "Build `{}` in container `{}` with `{}`",
+            target.name, container, target.exec_command
+        ));
+    }
+    hints
+}
+
+pub fn hint_condition_holds(condition: HintCondition, ctx: &Context) -> bool {
+    match condition {
+        HintCondition::FastbootDevice => {
+            ctx.adb_devices.iter().any(|d| d.device_type == "fastboot")
+        }
+        HintCondition::AdbDevice => ctx
+            .adb_devices
+            .iter()
+            .any(|d| d.device_type == "adb" && d.state == "device"),
+        HintCondition::ContainerDown => ctx.targets.iter().any(|t| {
+            !t.container_name.is_empty()
+                && !ctx.containers.iter().any(|c| c.name == t.container_name)
+        }),
+        HintCondition::Conflicts => ctx.git_repos.iter().any(|r| r.conflicted_files > 0),
+    }
+}
+
+/// Run each collector on its own, `repeat` times, and average the durations.
+///
+/// Collectors run one after another so they don't compete for CPU or disk.
+/// Each run still gets the full deadline; a collector that times out or fails
+/// is reported with that status and not repeated.
+pub async fn time_collectors(
+    project: Arc<ProjectContext>,
+    deadline: Duration,
+    repeat: u32,
+) -> Vec<CollectorReport> {
+    let ct = CancellationToken::new();
+    let mut reports = Vec::with_capacity(COLLECTORS.len());
+
+    for collector in COLLECTORS.iter() {
+        let name = collector.name();
+        let mut total = Duration::ZERO;
+        let mut last = None;
+
+        for run in 0..repeat.max(1) {
+            let project = Arc::clone(&project);
+            let started = Instant::now();
+            match run_with_deadline(deadline, &ct, move || {
+                run_collector(collector, &project, CollectionPlan::all()).0
+            })
+            .await
+            {
+                Ok(report) => {
+                    total += report.duration;
+                    last = Some((report, run + 1));
+                }
+                Err(status) => {
+                    last = Some((
+                        CollectorReport::abandoned(name, status, started.elapsed()),
+                        1,
+                    ));
+                    total = started.elapsed();
+                    break;
+                }
+            }
+        }
+
+        if let Some((mut report, runs)) = last {
+            report.duration = total / runs;
+            reports.push(report);
+        }
+    }
+
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idle::{ActivitySource, LastActivity};
+
+    fn idle_config() -> Config {
+        toml::from_str(
+            r#"
+            [hints.when.idle_over]
+            "30m" = "Check the device is still connected"
+            "48h" = "Fetch upstream before building"
+            "7d" = "Rebuild from clean"
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn idle_for(minutes: i64) -> Context {
+        let now = chrono::Utc::now();
+        Context {
+            now: Some(now),
+            last_activity: Some(LastActivity {
+                at: (now - chrono::Duration::minutes(minutes)).to_rfc3339(),
+                source: ActivitySource::Git,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn idle_hints_for_each_threshold_passed() {
+        let config = idle_config();
+        assert!(resolve_hints(&config, &idle_for(10)).is_empty());
+        assert_eq!(
+            resolve_hints(&config, &idle_for(30)),
+            ["Check the device is still connected"]
+        );
+        assert_eq!(
+            resolve_hints(&config, &idle_for(3 * 24 * 60)),
+            [
+                "Check the device is still connected",
+                "Fetch upstream before building"
+            ]
+        );
+        assert_eq!(resolve_hints(&config, &idle_for(8 * 24 * 60)).len(), 3);
+    }
+
+    #[test]
+    fn no_idle_hints_without_activity() {
+        let ctx = Context {
+            now: Some(chrono::Utc::now()),
+            ..Default::default()
+        };
+        assert!(resolve_hints(&idle_config(), &ctx).is_empty());
+    }
+
+    #[test]
+    fn every_collector_a_section_needs_is_registered() {
+        for section in Section::ALL {
+            for name in section_collectors(section) {
+                assert!(COLLECTORS.get(name).is_some(), "{} for {:?}", name, section);
+            }
+        }
+        // Hint conditions pull in collectors of their own
+        for name in ["devices", "containers", "commands", "history", "git"] {
+            assert!(COLLECTORS.get(name).is_some(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn not_built_collector_is_unavailable() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = ProjectContext::load(dir.path().to_path_buf(), None);
+        let stand_in = NotBuilt {
+            name: "devices",
+            feature: "adb",
+        };
+        let (report, output) = run_collector(&stand_in, &project, CollectionPlan::all());
+        assert_eq!(report.name, "devices");
+        assert!(matches!(output, Some(CollectorOutput::Nothing)));
+        assert_eq!(
+            report.availability,
+            Availability::Unavailable("built without the `adb` feature".to_string())
+        );
+    }
+}
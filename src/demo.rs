@@ -0,0 +1,51 @@
+//! Demo mode: a fixed synthetic `Context` instead of collected state, for
+//! reproducible screenshots, docs and formatter work
+
+use crate::attention::attention_items;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::ContextKeeperError;
+use crate::idle::last_activity;
+use std::fs;
+use std::path::Path;
+
+/// Built-in demo context
+pub const DEMO_CONTEXT: &str = include_str!("demo.json");
+
+/// "Now" for demo output; fixture timestamps are relative to it
+pub const DEMO_EPOCH: &str = "2025-01-15T10:00:00+00:00";
+
+/// Environment variable that puts the MCP server in demo mode
+pub const DEMO_ENV: &str = "CONTEXTKEEPER_DEMO";
+
+/// Whether `CONTEXTKEEPER_DEMO` asks for demo mode
+pub fn demo_enabled() -> bool {
+    std::env::var(DEMO_ENV).is_ok_and(|v| !v.is_empty() && v != "0")
+}
+
+/// The demo context: the built-in fixture, or `file` (same shape as
+/// `context --json`) when given. Ages are measured from `DEMO_EPOCH`.
+pub fn demo_context(file: Option<&Path>) -> Result<Context, ContextKeeperError> {
+    let mut context: Context = match file {
+        Some(path) => {
+            let content =
+                fs::read_to_string(path).map_err(|e| ContextKeeperError::state_io(path, e))?;
+            serde_json::from_str(&content)
+                .map_err(|e| ContextKeeperError::Validation(format!("{}: {}", path.display(), e)))?
+        }
+        None => serde_json::from_str(DEMO_CONTEXT)
+            .map_err(|e| ContextKeeperError::Validation(format!("demo fixture: {}", e)))?,
+    };
+    context.now = chrono::DateTime::parse_from_rfc3339(DEMO_EPOCH)
+        .ok()
+        .map(|epoch| epoch.with_timezone(&chrono::Utc));
+    if context.last_activity.is_none() {
+        context.last_activity = last_activity(&context);
+    }
+    // Ranked with the default scores unless the file brings its own list;
+    // after `now`, which the idle time is measured from
+    if context.attention.is_empty() {
+        context.attention = attention_items(&Config::default(), &context);
+    }
+    Ok(context)
+}
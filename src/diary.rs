@@ -0,0 +1,350 @@
+//! Session diary: every saved work state, one JSON line each
+//!
+//! `save_work_state_to_file` appends to `~/.contextkeeper/diary.jsonl`, so
+//! the week can be reconstructed after `work-state.json` was overwritten.
+//! Entries older than `diary.retention_days` are dropped on the next save.
+//! A line that doesn't parse (a save cut short by a crash) is skipped on
+//! read and dropped with the next pruning.
+
+use crate::config::Config;
+use crate::output::JsonList;
+use crate::state::{contextkeeper_dir, WorkState};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Default for `diary.retention_days`
+pub const DEFAULT_RETENTION_DAYS: u64 = 30;
+
+/// Default for `diary --since` and `get_recent_activity`
+pub const DEFAULT_SINCE: &str = "7d";
+
+pub fn diary_path() -> PathBuf {
+    contextkeeper_dir().join("diary.jsonl")
+}
+
+/// `diary.retention_days`; 0 turns the diary off
+pub fn retention_days(config: &Config) -> u64 {
+    config
+        .diary
+        .as_ref()
+        .and_then(|d| d.retention_days)
+        .unwrap_or(DEFAULT_RETENTION_DAYS)
+}
+
+fn saved_at(state: &WorkState) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&state.saved_at)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Append `state` and prune entries past the retention. Does nothing when
+/// the diary is turned off.
+pub fn append_diary_entry(state: &WorkState, config: &Config) -> io::Result<()> {
+    let retention = retention_days(config);
+    if retention == 0 {
+        return Ok(());
+    }
+    let path = diary_path();
+    let mut line = serde_json::to_string(state).map_err(io::Error::other)?;
+    line.push('\n');
+
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(&path)?;
+    // A partial last line would swallow this entry too
+    if file.seek(SeekFrom::End(0))? > 0 {
+        let mut last = [0u8; 1];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            line.insert(0, '\n');
+        }
+    }
+    // One write, so concurrent saves don't interleave
+    file.write_all(line.as_bytes())?;
+    drop(file);
+
+    let cutoff = Utc::now() - chrono::Duration::days(retention.min(36_500) as i64);
+    prune_diary(&path, cutoff)
+}
+
+/// Rewrite the diary without entries saved before `cutoff`, when the
+/// oldest one is. Reads only the first line otherwise.
+fn prune_diary(path: &Path, cutoff: DateTime<Utc>) -> io::Result<()> {
+    let mut first = String::new();
+    BufReader::new(fs::File::open(path)?).read_line(&mut first)?;
+    let expired = serde_json::from_str::<WorkState>(&first)
+        .ok()
+        .and_then(|state| saved_at(&state))
+        .is_none_or(|time| time < cutoff);
+    if !expired {
+        return Ok(());
+    }
+
+    let content = fs::read_to_string(path)?;
+    let kept: String = content
+        .lines()
+        .filter(|line| {
+            serde_json::from_str::<WorkState>(line)
+                .ok()
+                .and_then(|state| saved_at(&state))
+                .is_some_and(|time| time >= cutoff)
+        })
+        .flat_map(|line| [line, "\n"])
+        .collect();
+    let tmp = path.with_extension("jsonl.tmp");
+    fs::write(&tmp, kept)?;
+    fs::rename(&tmp, path)
+}
+
+/// Diary entries in save order and the number of lines that didn't parse.
+/// No diary yet is an empty one.
+pub fn load_diary() -> io::Result<(Vec<WorkState>, usize)> {
+    let content = match fs::read_to_string(diary_path()) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+        Err(e) => return Err(e),
+    };
+    let mut skipped = 0;
+    let mut entries: Vec<WorkState> = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str::<WorkState>(line) {
+            Ok(state) if saved_at(&state).is_some() => Some(state),
+            _ => {
+                skipped += 1;
+                None
+            }
+        })
+        .collect();
+    // Clocks and concurrent saves can append slightly out of order
+    entries.sort_by_key(saved_at);
+    Ok((entries, skipped))
+}
+
+/// `--since` value: a number and `m`, `h`, `d` or `w`, e.g. `7d`
+pub fn parse_since(value: &str) -> Result<chrono::Duration, String> {
+    let value = value.trim();
+    let invalid = || {
+        format!(
+            "invalid duration '{}'; use a number and m, h, d or w, e.g. 7d",
+            value
+        )
+    };
+    // The unit may be any character the user typed, e.g. `7日`
+    let (split, _) = value.char_indices().next_back().ok_or_else(invalid)?;
+    let (number, unit) = value.split_at(split);
+    let number: i64 = number.parse().map_err(|_| invalid())?;
+    let minutes = match unit {
+        "m" => 1,
+        "h" => 60,
+        "d" => 60 * 24,
+        "w" => 60 * 24 * 7,
+        _ => return Err(invalid()),
+    };
+    number
+        .checked_mul(minutes)
+        .filter(|m| *m >= 0 && *m <= 60 * 24 * 36_500)
+        .map(chrono::Duration::minutes)
+        .ok_or_else(invalid)
+}
+
+/// What happened in one project on one day
+#[derive(Debug, Clone, Serialize)]
+pub struct DiaryProject {
+    /// Empty for states saved before `project_root` was recorded
+    pub project_root: String,
+    pub saves: usize,
+    /// Task summaries in the order they were first saved
+    pub tasks: Vec<String>,
+    /// Repositories with working files in any save
+    pub repos: Vec<String>,
+    /// Todos that became completed since the project's previous save
+    pub completed_todos: Vec<String>,
+    /// Todos that weren't in the project's previous save
+    pub added_todos: Vec<String>,
+    /// Time of the last save, RFC 3339
+    pub last_saved_at: String,
+}
+
+impl DiaryProject {
+    /// Last component of the root, for headings
+    pub fn name(&self) -> String {
+        match Path::new(&self.project_root).file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None if self.project_root.is_empty() => "(unknown project)".to_string(),
+            None => self.project_root.clone(),
+        }
+    }
+}
+
+/// One local calendar day
+#[derive(Debug, Clone, Serialize)]
+pub struct DiaryDay {
+    /// `YYYY-MM-DD` in local time
+    pub date: String,
+    pub projects: Vec<DiaryProject>,
+}
+
+/// Entries since a point in time, grouped by day and project
+#[derive(Debug, Clone, Serialize)]
+pub struct DiaryDigest {
+    pub since: String,
+    pub days: Vec<DiaryDay>,
+    /// Diary lines that didn't parse
+    pub skipped_lines: usize,
+}
+
+impl JsonList for DiaryDigest {
+    type Item = DiaryDay;
+
+    fn json_items(&self) -> impl Iterator<Item = &DiaryDay> {
+        self.days.iter()
+    }
+}
+
+fn push_unique(list: &mut Vec<String>, value: &str) {
+    if !value.is_empty() && !list.iter().any(|v| v == value) {
+        list.push(value.to_string());
+    }
+}
+
+/// Group `entries` (in save order) saved at or after `since`. Todo deltas
+/// compare each entry with the project's previous entry that had todos,
+/// also when that one is older than `since`: saves from the CLI and git
+/// hooks carry no todo list, and shouldn't count as everything removed.
+/// `project_root` limits the digest to one project.
+pub fn build_digest(
+    entries: &[WorkState],
+    skipped_lines: usize,
+    since: DateTime<Utc>,
+    project_root: Option<&str>,
+) -> DiaryDigest {
+    let mut days: Vec<DiaryDay> = Vec::new();
+    let mut last_todos: std::collections::HashMap<&str, &WorkState> =
+        std::collections::HashMap::new();
+
+    for entry in entries {
+        if project_root.is_some_and(|root| root != entry.project_root) {
+            continue;
+        }
+        let Some(time) = saved_at(entry) else {
+            continue;
+        };
+        let previous = last_todos.get(entry.project_root.as_str()).copied();
+        if !entry.todos.is_empty() {
+            last_todos.insert(&entry.project_root, entry);
+        }
+        if time < since {
+            continue;
+        }
+
+        let date = time
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d")
+            .to_string();
+        if days.last().is_none_or(|day| day.date != date) {
+            days.push(DiaryDay {
+                date,
+                projects: Vec::new(),
+            });
+        }
+        let day = days.last_mut().expect("pushed above");
+        let index = match day
+            .projects
+            .iter()
+            .position(|p| p.project_root == entry.project_root)
+        {
+            Some(index) => index,
+            None => {
+                day.projects.push(DiaryProject {
+                    project_root: entry.project_root.clone(),
+                    saves: 0,
+                    tasks: Vec::new(),
+                    repos: Vec::new(),
+                    completed_todos: Vec::new(),
+                    added_todos: Vec::new(),
+                    last_saved_at: String::new(),
+                });
+                day.projects.len() - 1
+            }
+        };
+        let project = &mut day.projects[index];
+        project.saves += 1;
+        project.last_saved_at = entry.saved_at.clone();
+        push_unique(&mut project.tasks, entry.task_summary.trim());
+        for repo in &entry.working_repos {
+            push_unique(&mut project.repos, &repo.path);
+        }
+
+        if entry.todos.is_empty() {
+            continue;
+        }
+        let before = previous.map(|p| p.todos.as_slice()).unwrap_or_default();
+        for todo in &entry.todos {
+            let old = before.iter().find(|t| t.content == todo.content);
+            if old.is_none() {
+                push_unique(&mut project.added_todos, &todo.content);
+            }
+            if todo.status == "completed" && old.is_none_or(|t| t.status != "completed") {
+                push_unique(&mut project.completed_todos, &todo.content);
+            }
+        }
+    }
+
+    DiaryDigest {
+        since: since.to_rfc3339(),
+        days,
+        skipped_lines,
+    }
+}
+
+/// Digest of the diary for the last `since` (e.g. `7d`)
+pub fn recent_activity(since: &str, project_root: Option<&str>) -> Result<DiaryDigest, String> {
+    let since = Utc::now() - parse_since(since)?;
+    let (entries, skipped) =
+        load_diary().map_err(|e| format!("{}: {}", diary_path().display(), e))?;
+    Ok(build_digest(&entries, skipped, since, project_root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_since_units() {
+        assert_eq!(parse_since("30m"), Ok(chrono::Duration::minutes(30)));
+        assert_eq!(parse_since("48h"), Ok(chrono::Duration::hours(48)));
+        assert_eq!(parse_since(" 7d "), Ok(chrono::Duration::days(7)));
+        assert_eq!(parse_since("2w"), Ok(chrono::Duration::weeks(2)));
+    }
+
+    #[test]
+    fn parse_since_rejects_without_panicking() {
+        for value in [
+            "",
+            "d",
+            "7",
+            "7x",
+            "-1d",
+            "7日",
+            "é",
+            "1é",
+            "日日",
+            "99999999999w",
+        ] {
+            let error = parse_since(value).expect_err(value);
+            assert!(
+                error.starts_with("invalid duration"),
+                "{}: {}",
+                value,
+                error
+            );
+        }
+    }
+}
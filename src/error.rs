@@ -0,0 +1,68 @@
+//! Crate error type and the CLI exit codes it maps to
+
+use rmcp::ErrorData as McpError;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Failures that must not look like "nothing to report". The aggregator turns
+/// them into collector warnings; tools map them to MCP errors and the CLI to
+/// exit codes.
+#[derive(Debug, thiserror::Error)]
+pub enum ContextKeeperError {
+    /// Config file unreadable or invalid
+    #[error("{}: {message}", path.display())]
+    Config { path: PathBuf, message: String },
+    /// A collector's source exists but could not be read
+    #[error("{collector}: {message}")]
+    Collector {
+        collector: &'static str,
+        message: String,
+    },
+    /// Reading or writing state under ~/.contextkeeper
+    #[error("{}: {source}", path.display())]
+    StateIo {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// Bad tool parameters or command-line input
+    #[error("{0}")]
+    Validation(String),
+}
+
+impl ContextKeeperError {
+    pub fn state_io(path: &Path, source: io::Error) -> Self {
+        ContextKeeperError::StateIo {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    /// Exit code for the CLI's exit-code contract
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ContextKeeperError::Config { .. } => EXIT_CONFIG,
+            ContextKeeperError::Collector { .. } => EXIT_PARTIAL,
+            ContextKeeperError::StateIo { .. } => EXIT_IO,
+            ContextKeeperError::Validation(_) => EXIT_USAGE,
+        }
+    }
+}
+
+impl From<ContextKeeperError> for McpError {
+    fn from(e: ContextKeeperError) -> Self {
+        match e {
+            ContextKeeperError::Validation(_) => McpError::invalid_params(e.to_string(), None),
+            _ => McpError::internal_error(e.to_string(), None),
+        }
+    }
+}
+
+/// Exit code when some collectors timed out or failed
+pub const EXIT_PARTIAL: i32 = 1;
+/// Exit code for bad arguments (same as clap's own usage errors)
+pub const EXIT_USAGE: i32 = 2;
+/// Exit code when the config file is missing or cannot be parsed
+pub const EXIT_CONFIG: i32 = 3;
+/// Exit code when a file could not be written
+pub const EXIT_IO: i32 = 4;
@@ -0,0 +1,2866 @@
+//! Markdown and JSON output, hierarchical by level: minimal / normal / full
+
+pub mod budget;
+pub mod html;
+pub mod template;
+
+use crate::attention::{MINIMAL_ATTENTION_ITEMS, NORMAL_ATTENTION_ITEMS};
+use crate::clock::{clamp_to_now, clock_warnings};
+use crate::collectors::build_results::BuildRecord;
+use crate::collectors::code_todos::code_todos_to_show;
+use crate::collectors::containers::start_command;
+use crate::collectors::custom::{CustomOutput, MAX_CUSTOM_OUTPUT};
+use crate::collectors::diff_summary::FileDiff;
+use crate::collectors::environment::NixState;
+use crate::collectors::kconfig::KconfigInfo;
+use crate::collectors::onboarding::{Onboarding, MAX_ONBOARDING_REPOS};
+use crate::collectors::open_files::OpenFiles;
+use crate::collectors::project_ignore::PROJECT_IGNORE_FILE;
+use crate::collectors::resolve_path::{PathOwnership, ResolvedPath};
+use crate::collectors::ros::RosPackageStatus;
+use crate::collectors::working_files::{WorkingFileCheck, WorkingFileExcerpt, WorkingFileState};
+use crate::collectors::{AdbDevice, BuildTarget, CodeTodo, GerritChange, GitInfo, PullRequest};
+use crate::commands::status::{device_segment, join_segments, repo_segment, task_segment};
+use crate::context::{
+    containers_listed, section_collectors, Availability, CollectorReport, CollectorStatus, Context,
+};
+use crate::diary::DiaryDigest;
+use crate::i18n::{Label, Language};
+use crate::idle::{format_idle, idle_duration};
+use crate::limits::format_count;
+use crate::state::{WorkState, RESERVED_METADATA_PREFIX};
+use crate::stats::StatsDigest;
+use crate::summary::ProjectSummary;
+
+/// Helper: format git status string
+pub fn format_git_status(git: &GitInfo, lang: Language) -> String {
+    if let Some(reason) = &git.status_unavailable {
+        return format!("status unavailable ({})", reason);
+    }
+    let mut status = format_file_counts(git, lang);
+    if let Some(operation) = &git.operation {
+        status = format!("{} in progress, {}", operation, status);
+    }
+    let of_base = git
+        .compared_to
+        .as_ref()
+        .map(|base| format!(" of {}", base))
+        .unwrap_or_default();
+    if git.ahead > 0 {
+        status.push_str(&format!(", {} ahead{}", git.ahead, of_base));
+    }
+    if git.behind > 0 {
+        status.push_str(&format!(", {} behind{}", git.behind, of_base));
+    }
+    status
+}
+
+pub fn format_file_counts(git: &GitInfo, lang: Language) -> String {
+    if git.conflicted_files > 0 {
+        let mut rest = git.clone();
+        rest.conflicted_files = 0;
+        rest.is_dirty = rest.modified_files > 0 || rest.untracked_files > 0;
+        return if rest.is_dirty {
+            format!(
+                "{} conflicts, {}",
+                git.conflicted_files,
+                format_file_counts(&rest, lang)
+            )
+        } else {
+            format!("{} conflicts", git.conflicted_files)
+        };
+    }
+
+    if git.is_dirty {
+        if git.modified_files > 0 && git.untracked_files > 0 {
+            format!("{}M {}U", git.modified_files, git.untracked_files)
+        } else if git.modified_files > 0 {
+            format!("{}M", git.modified_files)
+        } else {
+            format!("{}U", git.untracked_files)
+        }
+    } else {
+        lang.text(Label::Clean).to_string()
+    }
+}
+
+/// Helper: " (CR +2, V -1)" for a change's votes, or empty without any
+fn format_votes(change: &GerritChange) -> String {
+    let votes: Vec<String> = [("CR", change.code_review), ("V", change.verified)]
+        .into_iter()
+        .filter_map(|(label, vote)| Some(format!("{} {:+}", label, vote?)))
+        .collect();
+    if votes.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", votes.join(", "))
+    }
+}
+
+/// Helper: "draft", "CI failing", "changes requested", as they apply
+fn pr_state(pr: &PullRequest) -> Vec<String> {
+    let mut state: Vec<String> = Vec::new();
+    if pr.draft {
+        state.push("draft".to_string());
+    }
+    if let Some(checks) = &pr.checks {
+        state.push(format!("CI {}", checks));
+    }
+    state.extend(pr.review.clone());
+    state
+}
+
+/// Helper: one-line note naming collectors that did not finish
+pub fn format_partial_note(ctx: &Context) -> String {
+    let abandoned: Vec<String> = ctx
+        .reports
+        .iter()
+        .filter(|r| r.status != CollectorStatus::Ok)
+        .map(|r| format!("{} ({})", r.name, r.status.as_str()))
+        .collect();
+
+    if abandoned.is_empty() {
+        return String::new();
+    }
+
+    format!(
+        "> **{}:** abandoned collectors: {}\n\n",
+        ctx.language.text(Label::PartialContext),
+        abandoned.join(", ")
+    )
+}
+
+/// Warning lines for saved working files that are gone or no longer
+/// modified, naming the commit most of them went into
+pub fn format_working_file_warnings(checks: &[WorkingFileCheck]) -> String {
+    let mut out = String::new();
+    let of_state = |state: WorkingFileState| -> Vec<&WorkingFileCheck> {
+        checks.iter().filter(|check| check.state == state).collect()
+    };
+    let (unchanged, missing) = (
+        of_state(WorkingFileState::Unchanged),
+        of_state(WorkingFileState::Missing),
+    );
+    // The commit that took the most of them
+    let main_commit = |files: &[&WorkingFileCheck]| -> Option<String> {
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+        for commit in files.iter().filter_map(|check| check.commit.as_deref()) {
+            match counts.iter_mut().find(|(c, _)| *c == commit) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((commit, 1)),
+            }
+        }
+        counts
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(commit, _)| commit.to_string())
+    };
+    if !unchanged.is_empty() {
+        out.push_str(&format!(
+            "⚠️ **{} of {} saved files are no longer modified**",
+            unchanged.len(),
+            checks.len()
+        ));
+        match main_commit(&unchanged) {
+            Some(commit) => out.push_str(&format!(
+                " — the work may already be committed; see commit {}\n",
+                commit
+            )),
+            None => out.push_str(" — committed, stashed or reverted\n"),
+        }
+    }
+    if !missing.is_empty() {
+        let names: Vec<&str> = missing.iter().take(3).map(|c| c.file.as_str()).collect();
+        out.push_str(&format!(
+            "⚠️ **{} of {} saved files no longer exist:** {}",
+            missing.len(),
+            checks.len(),
+            names.join(", ")
+        ));
+        if missing.len() > names.len() {
+            out.push_str(&format!(" ({} more)", missing.len() - names.len()));
+        }
+        if let Some(commit) = main_commit(&missing) {
+            out.push_str(&format!(" — see commit {}", commit));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// "+120/-35 in `AudioStreamOut::write`", functions or note after the counts
+fn format_file_diff(diff: &FileDiff) -> String {
+    // Binary files have no line counts
+    if diff.churn() == 0 && !diff.note.is_empty() {
+        return diff.note.clone();
+    }
+    let mut out = format!("+{}/-{}", diff.insertions, diff.deletions);
+    if !diff.functions.is_empty() {
+        let functions: Vec<String> = diff.functions.iter().map(|f| format!("`{}`", f)).collect();
+        out.push_str(&format!(" in {}", functions.join(", ")));
+    } else if !diff.note.is_empty() {
+        out.push_str(&format!(", {}", diff.note));
+    }
+    out
+}
+
+/// Helper: the state's metadata as `key=value, key=value`, without the
+/// reserved `ck:` keys; `None` when that leaves nothing
+fn format_metadata(work_state: &WorkState) -> Option<String> {
+    let pairs: Vec<String> = work_state
+        .metadata
+        .iter()
+        .filter(|(key, _)| !key.starts_with(RESERVED_METADATA_PREFIX))
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    (!pairs.is_empty()).then(|| pairs.join(", "))
+}
+
+/// Helper: format work state section
+/// `code_todos` go under the explicit todos, see `Context::code_todos`;
+/// `checks` annotate the working files
+pub fn format_work_state(
+    work_state: &WorkState,
+    code_todos: &[CodeTodo],
+    checks: &[WorkingFileCheck],
+    lang: Language,
+) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("## {}\n", lang.text(Label::SavedWorkState)));
+    out.push_str(&format_working_file_warnings(checks));
+    out.push_str(&format!(
+        "- **{}:** {}\n",
+        lang.text(Label::SavedAt),
+        work_state.saved_at
+    ));
+
+    if !work_state.task_summary.is_empty() {
+        out.push_str(&format!(
+            "- **{}:** {}\n",
+            lang.text(Label::Task),
+            work_state.task_summary
+        ));
+    }
+    if let Some(metadata) = format_metadata(work_state) {
+        out.push_str(&format!(
+            "- **{}:** {}\n",
+            lang.text(Label::Metadata),
+            metadata
+        ));
+    }
+
+    if !work_state.working_files.is_empty() {
+        out.push_str(&format!("- **{}:**\n", lang.text(Label::WorkingFiles)));
+        for file in &work_state.working_files {
+            let mut notes: Vec<String> = Vec::new();
+            let unverified = work_state.unverified_files.contains(file);
+            if unverified {
+                notes.push("not found when saved".to_string());
+            }
+            if let Some(check) = checks.iter().find(|check| &check.file == file) {
+                // Still missing says nothing new
+                if !(unverified && check.state == WorkingFileState::Missing) {
+                    notes.push(check.state.as_str().to_string());
+                }
+                if let Some(commit) = &check.commit {
+                    notes.push(format!("touched by {}", commit));
+                }
+            }
+            if let Some(diff) = work_state.diff_summary.iter().find(|d| &d.file == file) {
+                notes.push(format_file_diff(diff));
+            }
+            if notes.is_empty() {
+                out.push_str(&format!("  - {}\n", file));
+            } else {
+                out.push_str(&format!("  - {} ({})\n", file, notes.join(", ")));
+            }
+        }
+    }
+
+    if !work_state.notes.is_empty() {
+        out.push_str(&format!(
+            "- **{}:** {}\n",
+            lang.text(Label::Notes),
+            work_state.notes
+        ));
+    }
+
+    if !work_state.open_files.is_empty() {
+        let files: Vec<String> = work_state
+            .open_files
+            .iter()
+            .map(|file| format!("`{}`", file.location()))
+            .collect();
+        out.push_str(&format!(
+            "- **{}:** {}\n",
+            lang.text(Label::OpenInEditor),
+            files.join(", ")
+        ));
+    }
+
+    if !work_state.terminal_panes.is_empty() {
+        out.push_str(&format!("- **{}:**\n", lang.text(Label::RunningInTmux)));
+        for pane in &work_state.terminal_panes {
+            out.push_str(&format!(
+                "  - `{}` in {}:{} (`{}`)\n",
+                pane.command, pane.session, pane.window, pane.path
+            ));
+        }
+    }
+
+    if !work_state.next_steps.is_empty() {
+        out.push_str(&format!("- **{}:**\n", lang.text(Label::NextSteps)));
+        for (index, step) in work_state.next_steps.iter().enumerate() {
+            out.push_str(&format!("  {}. {}\n", index + 1, step));
+        }
+    }
+
+    if !work_state.todos.is_empty() {
+        out.push_str(&format!("- **{}:**\n", lang.text(Label::Todos)));
+        for todo in &work_state.todos {
+            let checkbox = match todo.status.as_str() {
+                "completed" => "[x]",
+                "in_progress" => "[>]",
+                _ => "[ ]",
+            };
+            out.push_str(&format!("  - {} {}\n", checkbox, todo.content));
+        }
+    }
+
+    let (shown, hidden) = code_todos_to_show(code_todos, &work_state.todos);
+    if !shown.is_empty() {
+        out.push_str(&format!("- **{}:**\n", lang.text(Label::CodeTodos)));
+        for todo in shown {
+            out.push_str(&format!(
+                "  - `{}:{}` {}\n",
+                todo.file, todo.line, todo.text
+            ));
+        }
+        if hidden > 0 {
+            out.push_str(&format!("  - ({} more)\n", hidden));
+        }
+    }
+
+    out.push('\n');
+    out
+}
+
+/// One line per path for `resolve_path`: where it belongs and its status,
+/// e.g. "- `vendor/x/a.c`: repo `vendor/x` on `main`, staged (`A.`)"
+pub fn format_resolved_paths(resolved: &[ResolvedPath]) -> String {
+    let mut out = String::new();
+    for entry in resolved {
+        let normalized = if entry.input.trim() == entry.path {
+            String::new()
+        } else {
+            format!(" (given as `{}`)", entry.input)
+        };
+        let answer = match entry.ownership {
+            PathOwnership::OutsideProject => "outside project".to_string(),
+            PathOwnership::Unowned => "unowned, in no known repository".to_string(),
+            PathOwnership::Owned => {
+                let mut answer = format!(
+                    "{} `{}`",
+                    if entry.submodule { "submodule" } else { "repo" },
+                    entry.repo.as_deref().unwrap_or(".")
+                );
+                if let Some(outer) = &entry.nested_in {
+                    answer.push_str(&format!(" inside `{}`", outer));
+                }
+                if let Some(branch) = entry.branch.as_deref().filter(|b| !b.is_empty()) {
+                    answer.push_str(&format!(" on `{}`", branch));
+                }
+                answer.push_str(&format!(", {}", entry.state));
+                if let Some(code) = &entry.status {
+                    answer.push_str(&format!(" (`{}`)", code));
+                }
+                answer
+            }
+        };
+        out.push_str(&format!("- `{}`{}: {}\n", entry.path, normalized, answer));
+    }
+    out
+}
+
+/// An excerpt for `get_working_file`: a header with the git status and
+/// line range, then the numbered lines in a fence longer than any run of
+/// backticks in them
+pub fn format_working_file_excerpt(excerpt: &WorkingFileExcerpt) -> String {
+    let mut out = format!("`{}` ({})", excerpt.path, excerpt.git_status);
+    if excerpt.lines.is_empty() {
+        out.push_str(" is empty\n");
+        return out;
+    }
+    out.push_str(&format!(
+        ", lines {}-{} of {}{}\n",
+        excerpt.first_line,
+        excerpt.last_line,
+        excerpt.total_lines,
+        if excerpt.truncated {
+            " read (the file is longer)"
+        } else {
+            ""
+        }
+    ));
+    let longest_run = excerpt
+        .lines
+        .iter()
+        .flat_map(|line| line.split(|c| c != '`'))
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    let width = excerpt.last_line.to_string().len();
+    out.push_str(&fence);
+    out.push('\n');
+    for (i, line) in excerpt.lines.iter().enumerate() {
+        out.push_str(&format!(
+            "{:>width$} | {}\n",
+            excerpt.first_line + i,
+            line,
+            width = width
+        ));
+    }
+    out.push_str(&fence);
+    out.push('\n');
+    out
+}
+
+/// `get_project_summary`: name and types, build systems, the top-level
+/// layout on one line, the entry point's usage and the static hints
+pub fn format_project_summary(summary: &ProjectSummary) -> String {
+    let mut out = if summary.name.is_empty() {
+        "## Project\n".to_string()
+    } else {
+        format!("## Project: {}\n", summary.name)
+    };
+    if !summary.types.is_empty() {
+        out.push_str(&format!("- **Type:** {}\n", summary.types.join(", ")));
+    }
+    if !summary.detected.is_empty() {
+        let detected: Vec<String> = summary
+            .detected
+            .iter()
+            .map(|d| format!("{} (`{}`)", d.project_type, d.evidence))
+            .collect();
+        out.push_str(&format!("- **Detected:** {}\n", detected.join(", ")));
+    }
+    let mut build: Vec<String> = summary
+        .build_systems
+        .iter()
+        .map(|s| format!("{} (`{}`)", s.name, s.file))
+        .collect();
+    if let Some(entry_point) = &summary.entry_point {
+        build.insert(0, format!("entry point `{}`", entry_point));
+    }
+    if !build.is_empty() {
+        out.push_str(&format!("- **Build:** {}\n", build.join(", ")));
+    }
+    if !summary.layout.is_empty() {
+        let mut layout: Vec<String> = summary
+            .layout
+            .iter()
+            .map(|e| {
+                if e.is_dir {
+                    format!("`{}/` ({})", e.name, e.entries)
+                } else {
+                    format!("`{}`", e.name)
+                }
+            })
+            .collect();
+        if summary.layout_omitted > 0 {
+            layout.push(format!("{} more", summary.layout_omitted));
+        }
+        out.push_str(&format!("- **Layout:** {}\n", layout.join(", ")));
+    }
+    if let Some(usage) = &summary.usage {
+        out.push_str(&format!("\n### Usage\n```\n{}\n```\n", usage));
+    }
+    if !summary.hints.is_empty() {
+        out.push_str("\n### Hints\n");
+        for hint in &summary.hints {
+            out.push_str(&format!("- {}\n", hint));
+        }
+    }
+    out
+}
+
+/// `context-keeper diary` and `get_recent_activity`: one heading per day,
+/// one list entry per project
+pub fn format_diary(digest: &DiaryDigest) -> String {
+    let since = chrono::DateTime::parse_from_rfc3339(&digest.since)
+        .map(|time| {
+            time.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_else(|_| digest.since.clone());
+    let mut out = format!(
+        "## Recent Activity (since {})
+",
+        since
+    );
+    if digest.days.is_empty() {
+        out.push_str("No saved work states in this period.\n");
+    }
+    for day in &digest.days {
+        out.push_str(&format!("\n### {}\n", day.date));
+        for project in &day.projects {
+            let saves = if project.saves == 1 { "save" } else { "saves" };
+            out.push_str(&format!(
+                "- **{}** ({} {})\n",
+                project.name(),
+                project.saves,
+                saves
+            ));
+            for task in &project.tasks {
+                out.push_str(&format!("  - Task: {}\n", task));
+            }
+            if !project.repos.is_empty() {
+                out.push_str(&format!("  - Repos: {}\n", project.repos.join(", ")));
+            }
+            if !project.completed_todos.is_empty() {
+                out.push_str(&format!(
+                    "  - Completed: {}\n",
+                    project.completed_todos.join("; ")
+                ));
+            }
+            if !project.added_todos.is_empty() {
+                out.push_str(&format!(
+                    "  - New todos: {}\n",
+                    project.added_todos.join("; ")
+                ));
+            }
+        }
+    }
+    if digest.skipped_lines > 0 {
+        out.push_str(&format!(
+            "\n_{} unreadable diary line(s) skipped_\n",
+            digest.skipped_lines
+        ));
+    }
+    out
+}
+
+/// `context-keeper stats`: a row per tool, then the get_dev_context levels
+pub fn format_tool_stats(digest: &StatsDigest) -> String {
+    let since = chrono::DateTime::parse_from_rfc3339(&digest.since)
+        .map(|time| {
+            time.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_else(|_| digest.since.clone());
+    let mut out = format!("## Tool Calls (since {})\n", since);
+    if digest.tools.is_empty() {
+        out.push_str("No tool calls recorded in this period.\n");
+    } else {
+        let projects = if digest.projects == 1 {
+            "project"
+        } else {
+            "projects"
+        };
+        out.push_str(&format!("Across {} {}.\n\n", digest.projects, projects));
+        out.push_str("| Tool | Calls | Errors | p50 | p95 | Cache hits | Avg output |\n");
+        out.push_str("|------|-------|--------|-----|-----|------------|------------|\n");
+        for tool in &digest.tools {
+            let hits = match tool.cache_hit_rate() {
+                Some(rate) => format!("{:.0}%", rate * 100.0),
+                None => "-".to_string(),
+            };
+            out.push_str(&format!(
+                "| {} | {} | {} | {}ms | {}ms | {} | {} |\n",
+                tool.tool,
+                tool.calls,
+                tool.errors,
+                tool.p50_ms,
+                tool.p95_ms,
+                hits,
+                format_size(tool.mean_output_bytes as u64)
+            ));
+        }
+        for tool in digest.tools.iter().filter(|t| !t.levels.is_empty()) {
+            let levels: Vec<String> = tool
+                .levels
+                .iter()
+                .map(|(level, calls)| format!("{} {}", level, calls))
+                .collect();
+            out.push_str(&format!(
+                "\n**{} levels:** {}\n",
+                tool.tool,
+                levels.join(", ")
+            ));
+        }
+    }
+    if digest.skipped > 0 {
+        out.push_str(&format!(
+            "\n_{} unreadable stats line(s) skipped_\n",
+            digest.skipped
+        ));
+    }
+    out
+}
+
+/// Output detail level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Level {
+    /// ~30 tokens: one line with the changed repos, task, devices and top issue
+    Oneline,
+    /// ~200 tokens: hint, task, working files, dirty repos
+    Minimal,
+    /// ~400 tokens: adds containers and devices
+    #[default]
+    Normal,
+    /// ~1000 tokens: complete information
+    Full,
+}
+
+impl Level {
+    pub const ALL: [Level; 4] = [Level::Oneline, Level::Minimal, Level::Normal, Level::Full];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Oneline => "oneline",
+            Level::Minimal => "minimal",
+            Level::Normal => "normal",
+            Level::Full => "full",
+        }
+    }
+
+    /// Rough output size, as advertised to MCP clients
+    pub fn token_estimate(self) -> usize {
+        match self {
+            Level::Oneline => 30,
+            Level::Minimal => 200,
+            Level::Normal => 400,
+            Level::Full => 1000,
+        }
+    }
+
+    /// When to ask for this level, for the get_dev_context description
+    pub fn usage(self) -> &'static str {
+        match self {
+            Level::Oneline => "to check in between steps",
+            Level::Minimal => "right after compression",
+            Level::Normal => "for balanced info",
+            Level::Full => "for complete details",
+        }
+    }
+
+    /// Parse a level name, falling back to normal for unknown values
+    pub fn parse(level: &str) -> Self {
+        Level::ALL
+            .into_iter()
+            .find(|l| l.name() == level)
+            .unwrap_or_default()
+    }
+
+    /// The level sections are rendered at. Oneline has no sections of its
+    /// own, so sections asked for with it show as at minimal.
+    fn section_level(self) -> Level {
+        match self {
+            Level::Oneline => Level::Minimal,
+            level => level,
+        }
+    }
+}
+
+/// How `get_dev_context` returns the context
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// The rendered sections, as the CLI prints them
+    #[default]
+    Markdown,
+    /// The sections' data, keyed by section name, as `--section --json`
+    /// prints it
+    Json,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 2] = [OutputFormat::Markdown, OutputFormat::Json];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Markdown => "markdown",
+            OutputFormat::Json => "json",
+        }
+    }
+
+    /// What the format is for, for the get_dev_context description
+    pub fn usage(self) -> &'static str {
+        match self {
+            OutputFormat::Markdown => "to read",
+            OutputFormat::Json => "to pick fields out programmatically",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<OutputFormat> {
+        OutputFormat::ALL.into_iter().find(|f| f.name() == name)
+    }
+}
+
+/// A named piece of the context. The same names are used by the CLI
+/// `--section` flag and the MCP `sections` parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Section {
+    Project,
+    #[value(name = "work_state")]
+    WorkState,
+    Hints,
+    Targets,
+    Containers,
+    Yocto,
+    Ros,
+    Commands,
+    History,
+    Git,
+    Devices,
+    Terminals,
+    Builds,
+    Resources,
+    Environment,
+    Attention,
+    Custom,
+    Diagnostics,
+}
+
+impl Section {
+    pub const ALL: [Section; 18] = [
+        Section::Project,
+        Section::WorkState,
+        Section::Hints,
+        Section::Targets,
+        Section::Containers,
+        Section::Yocto,
+        Section::Ros,
+        Section::Commands,
+        Section::History,
+        Section::Git,
+        Section::Devices,
+        Section::Terminals,
+        Section::Builds,
+        Section::Resources,
+        Section::Environment,
+        Section::Attention,
+        Section::Custom,
+        Section::Diagnostics,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Section::Project => "project",
+            Section::WorkState => "work_state",
+            Section::Hints => "hints",
+            Section::Targets => "targets",
+            Section::Containers => "containers",
+            Section::Yocto => "yocto",
+            Section::Ros => "ros",
+            Section::Commands => "commands",
+            Section::History => "history",
+            Section::Git => "git",
+            Section::Devices => "devices",
+            Section::Terminals => "terminals",
+            Section::Builds => "builds",
+            Section::Resources => "resources",
+            Section::Environment => "environment",
+            Section::Attention => "attention",
+            Section::Custom => "custom",
+            Section::Diagnostics => "diagnostics",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Section> {
+        Section::ALL.into_iter().find(|s| s.name() == name)
+    }
+
+    /// Comma-separated list of valid section names
+    pub fn valid_names() -> String {
+        Section::ALL.map(|s| s.name()).join(", ")
+    }
+}
+
+/// Sections shown at each level, in output order
+pub fn level_sections(level: Level) -> &'static [Section] {
+    match level {
+        Level::Oneline => &[
+            Section::Attention,
+            Section::WorkState,
+            Section::Git,
+            Section::Devices,
+        ],
+        Level::Minimal => &[
+            Section::Diagnostics,
+            Section::Attention,
+            Section::Builds,
+            Section::Resources,
+            Section::Hints,
+            Section::WorkState,
+            Section::Git,
+            Section::Devices,
+            Section::Custom,
+        ],
+        Level::Normal => &[
+            Section::Diagnostics,
+            Section::Attention,
+            Section::Builds,
+            Section::Resources,
+            Section::WorkState,
+            Section::Hints,
+            Section::Git,
+            Section::Containers,
+            Section::Yocto,
+            Section::Ros,
+            Section::Devices,
+            Section::Terminals,
+            Section::Custom,
+        ],
+        Level::Full => &[
+            Section::Project,
+            Section::Builds,
+            Section::Resources,
+            Section::WorkState,
+            Section::Hints,
+            Section::Targets,
+            Section::Environment,
+            Section::Containers,
+            Section::Yocto,
+            Section::Ros,
+            Section::Commands,
+            Section::History,
+            Section::Git,
+            Section::Devices,
+            Section::Terminals,
+            Section::Custom,
+            Section::Diagnostics,
+        ],
+    }
+}
+
+/// Render one section at the given level. Empty sections render as "".
+pub fn format_section(ctx: &Context, section: Section, level: Level) -> String {
+    let level = level.section_level();
+    let out = match section {
+        Section::Project => format_project_section(ctx),
+        Section::WorkState => format_work_state_section(ctx, level),
+        Section::Hints => format_hints_section(ctx, level),
+        Section::Targets => format_targets_section(ctx),
+        Section::Containers => format_containers_section(ctx, level),
+        Section::Yocto => format_yocto_section(ctx, level),
+        Section::Ros => format_ros_section(ctx, level),
+        Section::Commands => format_commands_section(ctx),
+        Section::History => format_history_section(ctx),
+        Section::Git => format_git_section(ctx, level),
+        Section::Devices => format_devices_section(ctx, level),
+        Section::Terminals => format_terminals_section(ctx, level),
+        Section::Builds => format_builds_section(ctx, level),
+        Section::Resources => format_resources_section(ctx, level),
+        Section::Environment => format_environment_section(ctx),
+        Section::Attention => format_attention_section(ctx, level),
+        Section::Custom => format_custom_section(ctx, level),
+        Section::Diagnostics => format_diagnostics_section(ctx, level),
+    };
+    let out = match section {
+        Section::Git | Section::Containers | Section::Targets => {
+            out + &format_remote_part(ctx, section, level)
+        }
+        _ => out,
+    };
+    if out.is_empty() && level != Level::Minimal {
+        return format_availability_stub(ctx, section);
+    }
+    out
+}
+
+/// What `section` has to go on: `Ok` when any of its collectors found
+/// something, else the state of its first collector. `None` for sections
+/// without collectors and when the first one didn't run.
+pub fn section_availability(ctx: &Context, section: Section) -> Option<Availability> {
+    let names = section_collectors(section);
+    let report = |name: &str| ctx.reports.iter().find(|r| r.name == name);
+    let primary = report(names.first()?)?;
+    if names
+        .iter()
+        .filter_map(|name| report(name))
+        .any(|r| r.availability == Availability::Ok)
+    {
+        return Some(Availability::Ok);
+    }
+    Some(primary.availability.clone())
+}
+
+/// `section_availability` of each of `sections` that has one, keyed by
+/// section name, for the JSON output
+pub fn availability_json(ctx: &Context, sections: &[Section]) -> serde_json::Value {
+    let map: serde_json::Map<String, serde_json::Value> = sections
+        .iter()
+        .filter_map(|section| {
+            let availability = section_availability(ctx, *section)?;
+            Some((
+                section.name().to_string(),
+                serde_json::to_value(availability).ok()?,
+            ))
+        })
+        .collect();
+    serde_json::Value::Object(map)
+}
+
+/// Helper: how a section with nothing to show is named in its stub
+fn stub_title(section: Section, lang: Language) -> Option<&'static str> {
+    let label = match section {
+        Section::Project => Label::Project,
+        Section::WorkState => Label::WorkStateTitle,
+        Section::Targets => Label::BuildTargetsTitle,
+        Section::Containers => Label::Containers,
+        Section::Yocto => Label::Yocto,
+        Section::Ros => Label::RosWorkspaceTitle,
+        Section::Commands => Label::Commands,
+        Section::History => Label::CommandHistory,
+        Section::Git => Label::Git,
+        Section::Devices => Label::Devices,
+        Section::Terminals => Label::TerminalSessionsTitle,
+        Section::Builds => Label::Builds,
+        Section::Resources => Label::Resources,
+        Section::Environment => Label::CapturedEnvironment,
+        // Shown another way, or built from the other sections
+        Section::Hints | Section::Attention | Section::Custom | Section::Diagnostics => {
+            return None
+        }
+    };
+    Some(lang.text(label))
+}
+
+/// Helper: one line in place of a section that shows nothing because its
+/// source couldn't be asked, is turned off or ran out of time, so that
+/// reads differently from one with nothing to report
+pub fn format_availability_stub(ctx: &Context, section: Section) -> String {
+    let lang = ctx.language;
+    let Some(title) = stub_title(section, lang) else {
+        return String::new();
+    };
+    let state = match section_availability(ctx, section) {
+        Some(Availability::Unavailable(reason)) => {
+            format!("{} — {}", lang.text(Label::StubUnavailable), reason)
+        }
+        Some(Availability::Disabled) => {
+            let setting = match section {
+                Section::History => " (`[history] enabled`)",
+                Section::Terminals => " (`[tmux] enabled`)",
+                Section::Environment => " (`[env] capture`)",
+                _ => "",
+            };
+            format!("{}{}", lang.text(Label::StubTurnedOff), setting)
+        }
+        Some(Availability::TimedOut) => lang.text(Label::StubTimedOut).to_string(),
+        _ => return String::new(),
+    };
+    format!("**{}:** {}\n\n", title, state)
+}
+
+/// Helper: at minimal level, unavailable sources something shown depends
+/// on: the containers the targets build in, git, a pinned device
+pub fn format_blocking_note(ctx: &Context) -> String {
+    let containers_needed = ctx.targets.iter().any(|t| !t.container_name.is_empty());
+    let needs = [
+        (Section::Git, true, "git", ""),
+        (
+            Section::Containers,
+            containers_needed,
+            "containers",
+            "; targets build in containers",
+        ),
+        (
+            Section::Devices,
+            ctx.device_selection.is_some(),
+            "devices",
+            "; a default device is pinned",
+        ),
+    ];
+    needs
+        .iter()
+        .filter(|(_, needed, _, _)| *needed)
+        .filter_map(
+            |(section, _, what, why)| match section_availability(ctx, *section)? {
+                Availability::Unavailable(reason) => Some(format!(
+                    "> **{}:** {} — {}{}\n\n",
+                    ctx.language.text(Label::Unavailable),
+                    what,
+                    reason,
+                    why
+                )),
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+/// Helper: a section rendered from the `[remote]` host's collection, its
+/// heading marked "(remote: host)"
+fn format_remote_part(ctx: &Context, section: Section, level: Level) -> String {
+    let Some(remote) = &ctx.remote else {
+        return String::new();
+    };
+    let remote_ctx = Context {
+        git_repos: remote.git_repos.clone(),
+        containers: remote.containers.clone(),
+        targets: remote.targets.clone(),
+        now: ctx.now,
+        language: ctx.language,
+        ..Default::default()
+    };
+    let text = format_section(&remote_ctx, section, level);
+    let Some((heading, rest)) = text.split_once('\n') else {
+        return text;
+    };
+    let note = format!("(remote: {})", remote.host);
+    let heading = match heading.strip_prefix("**").and_then(|h| h.split_once(":**")) {
+        // "**Changed repos:** ..." at minimal level
+        Some((label, tail)) => format!("**{} {}:**{}", label, note, tail),
+        None => format!("{} {}", heading, note),
+    };
+    format!("{}\n{}", heading, rest)
+}
+
+pub fn format_project_section(ctx: &Context) -> String {
+    let mut out = String::new();
+    if !ctx.project_name.is_empty() || !ctx.detected_types.is_empty() {
+        out.push_str(&format!("## {}\n", ctx.language.text(Label::Project)));
+        if !ctx.project_name.is_empty() {
+            out.push_str(&format!(
+                "- **{}:** {}\n",
+                ctx.language.text(Label::Name),
+                ctx.project_name
+            ));
+        }
+        if !ctx.project_type.is_empty() {
+            out.push_str(&format!(
+                "- **{}:** {}\n",
+                ctx.language.text(Label::Type),
+                ctx.project_type
+            ));
+        }
+        if !ctx.detected_types.is_empty() {
+            let detected: Vec<String> = ctx
+                .detected_types
+                .iter()
+                .map(|d| {
+                    format!(
+                        "{} (`{}`, {})",
+                        d.project_type,
+                        d.evidence,
+                        d.confidence.as_str()
+                    )
+                })
+                .collect();
+            out.push_str(&format!(
+                "- **{}:** {}\n",
+                ctx.language.text(Label::Detected),
+                detected.join(", ")
+            ));
+        }
+        out.push('\n');
+    }
+    if let Some(kconfig) = &ctx.kconfig {
+        out.push_str(&format_kconfig(kconfig, ctx.now(), ctx.language));
+    }
+    out
+}
+
+/// "Build Configuration" subsection for Buildroot and kernel trees
+pub fn format_kconfig(
+    kconfig: &KconfigInfo,
+    now: chrono::DateTime<chrono::Utc>,
+    lang: Language,
+) -> String {
+    let mut out = format!("### {}\n", lang.text(Label::BuildConfiguration));
+    if !kconfig.exists {
+        out.push_str(&format!(
+            "- **{}:** missing (run a `*_defconfig` or `menuconfig` target)\n\n",
+            kconfig.config_file
+        ));
+        return out;
+    }
+
+    let modified = kconfig
+        .modified
+        .as_deref()
+        .map(|modified| format_age(modified, now, lang))
+        .unwrap_or_default();
+    match &kconfig.header {
+        Some(header) => out.push_str(&format!(
+            "- **{}:** {}, changed {}\n",
+            kconfig.config_file, header, modified
+        )),
+        None => out.push_str(&format!(
+            "- **{}:** changed {}\n",
+            kconfig.config_file, modified
+        )),
+    }
+    match (&kconfig.artifact, &kconfig.artifact_modified) {
+        (Some(artifact), Some(built)) => {
+            let note = if kconfig.changed_since_build {
+                " (.config changed since; rebuild needed)"
+            } else {
+                ""
+            };
+            out.push_str(&format!(
+                "- **{}:** {} {}{}\n",
+                lang.text(Label::LastBuild),
+                artifact,
+                format_age(built, now, lang),
+                note
+            ));
+        }
+        _ => out.push_str(&format!(
+            "- **{}:** none found\n",
+            lang.text(Label::LastBuild)
+        )),
+    }
+    for (name, value) in &kconfig.symbols {
+        let value = match value.as_deref() {
+            Some("n") => "is not set",
+            Some(value) => value,
+            None => "(absent)",
+        };
+        out.push_str(&format!("- `{}`: {}\n", name, value));
+    }
+    out.push('\n');
+    out
+}
+
+pub fn format_work_state_section(ctx: &Context, level: Level) -> String {
+    let ws = match &ctx.work_state {
+        Some(ws) => ws,
+        None => return String::new(),
+    };
+
+    let lang = ctx.language;
+    if level != Level::Minimal {
+        return format_work_state(ws, ctx.code_todos(), &ctx.working_file_checks, lang);
+    }
+
+    // Work state is most important for recovery
+    let mut out = format_working_file_warnings(&ctx.working_file_checks);
+    if !ws.task_summary.is_empty() {
+        out.push_str(&format!(
+            "**{}:** {}\n",
+            lang.text(Label::Task),
+            ws.task_summary
+        ));
+    }
+    if let Some(metadata) = format_metadata(ws) {
+        out.push_str(&format!(
+            "**{}:** {}\n",
+            lang.text(Label::Metadata),
+            metadata
+        ));
+    }
+    if !ws.working_files.is_empty() {
+        let files: Vec<&str> = ws.working_files.iter().map(|s| s.as_str()).collect();
+        out.push_str(&format!(
+            "**{}:** {}\n",
+            lang.text(Label::Files),
+            files.join(", ")
+        ));
+    }
+    // The two biggest changes say where the work is
+    let mut churn: Vec<&FileDiff> = ws.diff_summary.iter().filter(|d| d.churn() > 0).collect();
+    churn.sort_by_key(|d| std::cmp::Reverse(d.churn()));
+    if !churn.is_empty() {
+        let biggest: Vec<String> = churn
+            .iter()
+            .take(2)
+            .map(|d| format!("{} (+{}/-{})", d.file, d.insertions, d.deletions))
+            .collect();
+        out.push_str(&format!(
+            "**{}:** {}\n",
+            lang.text(Label::Churn),
+            biggest.join(", ")
+        ));
+    }
+    if !ws.notes.is_empty() {
+        out.push_str(&format!("**{}:** {}\n", lang.text(Label::Notes), ws.notes));
+    }
+    // The first two are enough to pick up where the plan left off
+    if !ws.next_steps.is_empty() {
+        let steps: Vec<String> = ws
+            .next_steps
+            .iter()
+            .take(2)
+            .enumerate()
+            .map(|(index, step)| format!("{}. {}", index + 1, step))
+            .collect();
+        out.push_str(&format!(
+            "**{}:** {}",
+            lang.text(Label::Next),
+            steps.join(" ")
+        ));
+        if ws.next_steps.len() > 2 {
+            out.push_str(&format!(" ({} more)", ws.next_steps.len() - 2));
+        }
+        out.push('\n');
+    }
+    out.push('\n');
+    out
+}
+
+pub fn format_hints_section(ctx: &Context, level: Level) -> String {
+    let mut warning = match &ctx.environment {
+        Some(env) if level != Level::Minimal && env.nix == NixState::Inactive => format!(
+            "⚠️ **Dev shell not active:** run `{}` ({}) before build commands\n\n",
+            env.activate, env.marker
+        ),
+        _ => String::new(),
+    };
+    // Full level has the artifacts column in the target table instead
+    if level == Level::Normal {
+        let stale: Vec<String> = ctx
+            .targets
+            .iter()
+            .filter_map(|t| Some((t, t.artifact.as_ref().filter(|a| a.stale)?)))
+            .map(|(t, a)| {
+                format!(
+                    "{} ({} built {})",
+                    t.name,
+                    a.file_name(),
+                    format_age(&a.modified_at, ctx.now(), ctx.language)
+                )
+            })
+            .collect();
+        if !stale.is_empty() {
+            warning.push_str(&format!(
+                "⚠️ **Artifacts older than the last commit:** {}\n\n",
+                stale.join("; ")
+            ));
+        }
+    }
+    warning + &format_hint_list(ctx, level)
+}
+
+pub fn format_hint_list(ctx: &Context, level: Level) -> String {
+    // AI hints (critical for remembering build environment)
+    let mut out = match (level, ctx.hints.as_slice()) {
+        (_, []) => return String::new(),
+        (Level::Oneline | Level::Minimal, [hint]) => {
+            return format!("**{}:** {}\n\n", ctx.language.text(Label::Hint), hint)
+        }
+        (Level::Oneline | Level::Minimal, _) => {
+            format!("**{}:**\n", ctx.language.text(Label::Hints))
+        }
+        (Level::Normal, _) => format!("## {}\n", ctx.language.text(Label::AiHints)),
+        (Level::Full, _) => format!("## {}\n", ctx.language.text(Label::AiHintsImportant)),
+    };
+    for hint in &ctx.hints {
+        out.push_str(&format!("- {}\n", hint));
+    }
+    out.push('\n');
+    out
+}
+
+pub fn format_targets_section(ctx: &Context) -> String {
+    let mut out = String::new();
+    if ctx.targets.is_empty() {
+        return out;
+    }
+
+    out.push_str(&format!(
+        "## {}\n\n",
+        ctx.language.text(Label::AvailableBuildTargets)
+    ));
+    // The column only when some target has an artifact glob
+    let artifacts = ctx.targets.iter().any(|t| !t.artifact_path.is_empty());
+    if artifacts {
+        out.push_str("| Target | Description | Container | Lunch Target | Artifacts |\n");
+        out.push_str("|--------|-------------|-----------|--------------|-----------|\n");
+    } else {
+        out.push_str("| Target | Description | Container | Lunch Target |\n");
+        out.push_str("|--------|-------------|-----------|---------------|\n");
+    }
+    for target in &ctx.targets {
+        let description = if target.issues.is_empty() {
+            target.description.clone()
+        } else {
+            format!("{} ⚠ {}", target.description, target.issues.join(", "))
+        };
+        let name = if target.source == "west" {
+            format!("{} (west)", target.name)
+        } else {
+            target.name.clone()
+        };
+        out.push_str(&format!(
+            "| {} | {} | {} | {} |",
+            name,
+            description.trim(),
+            target.container_name,
+            target.lunch_target
+        ));
+        if artifacts {
+            out.push_str(&format!(
+                " {} |",
+                format_artifact(target, ctx.now(), ctx.language)
+            ));
+        }
+        out.push('\n');
+    }
+    out.push('\n');
+
+    let in_containers: Vec<&BuildTarget> = ctx
+        .targets
+        .iter()
+        .filter(|t| !t.exec_command.is_empty())
+        .collect();
+    if !in_containers.is_empty() {
+        out.push_str(&format!(
+            "### {}\n",
+            ctx.language.text(Label::RunningInContainers)
+        ));
+        let listed = containers_listed(ctx);
+        for target in in_containers {
+            let down = listed
+                && !ctx
+                    .containers
+                    .iter()
+                    .any(|c| c.name == target.container_name);
+            out.push_str(&format!("- **{}:** `{}`", target.name, target.exec_command));
+            if down {
+                match start_command(&ctx.available_commands, &target.container_name) {
+                    Some(start) => out.push_str(&format!(
+                        " (container not running; start it with `{}`)",
+                        start
+                    )),
+                    None => out.push_str(" (container not running)"),
+                }
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+
+    out.push_str(&format!(
+        "### {}\n",
+        ctx.language.text(Label::TargetCapabilities)
+    ));
+    for target in &ctx.targets {
+        let caps: Vec<&str> = [
+            if target.can_emulator {
+                Some("emulator")
+            } else {
+                None
+            },
+            if target.can_flash {
+                Some("flash")
+            } else {
+                None
+            },
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        if !caps.is_empty() {
+            out.push_str(&format!("- **{}:** {}\n", target.name, caps.join(", ")));
+        }
+    }
+    out.push('\n');
+    out
+}
+
+pub fn format_containers_section(ctx: &Context, level: Level) -> String {
+    let mut out = String::new();
+    if ctx.containers.is_empty() {
+        return out;
+    }
+
+    out.push_str(&format!(
+        "## {}\n",
+        ctx.language.text(Label::ActiveContainers)
+    ));
+    for container in &ctx.containers {
+        if level == Level::Full {
+            out.push_str(&format!(
+                "- **{}** ({}): {}",
+                container.name, container.runtime, container.status
+            ));
+            if !container.ports.is_empty() {
+                out.push_str(&format!(" [{}]", container.ports.join(", ")));
+            }
+            out.push('\n');
+        } else {
+            out.push_str(&format!("- {} ({})\n", container.name, container.status));
+        }
+    }
+    out.push('\n');
+    out
+}
+
+/// Helper: "built 2h ago (boot.img, 64.0 MB)", "none" without a match,
+/// "" without an artifact glob
+pub fn format_artifact(
+    target: &BuildTarget,
+    now: chrono::DateTime<chrono::Utc>,
+    lang: Language,
+) -> String {
+    match &target.artifact {
+        Some(artifact) => format!(
+            "built {} ({}, {}){}",
+            format_age(&artifact.modified_at, now, lang),
+            artifact.file_name(),
+            format_size(artifact.size),
+            if artifact.stale {
+                ", older than the last commit"
+            } else {
+                ""
+            }
+        ),
+        None if target.artifact_path.is_empty() => String::new(),
+        None => "none".to_string(),
+    }
+}
+
+/// Helper: "1.2 GB" style size
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Helper: "3h ago" style age of an RFC 3339 timestamp at `now`, in `lang`
+pub fn format_age(timestamp: &str, now: chrono::DateTime<chrono::Utc>, lang: Language) -> String {
+    let Ok(time) = chrono::DateTime::parse_from_rfc3339(timestamp) else {
+        return timestamp.to_string();
+    };
+    // A clock running ahead would make the age negative
+    let minutes = (now - clamp_to_now(time.with_timezone(&chrono::Utc), now)).num_minutes();
+    match minutes {
+        m if m < 1 => lang.text(Label::JustNow).to_string(),
+        m if m < 60 => lang.count(Label::MinutesAgo, m),
+        m if m < 60 * 24 => lang.count(Label::HoursAgo, m / 60),
+        m => lang.count(Label::DaysAgo, m / (60 * 24)),
+    }
+}
+
+pub fn format_yocto_section(ctx: &Context, level: Level) -> String {
+    let mut out = String::new();
+    let Some(yocto) = &ctx.yocto else {
+        return out;
+    };
+
+    out.push_str(&format!("## {}\n", ctx.language.text(Label::Yocto)));
+    if yocto.env_sourced {
+        out.push_str(&format!(
+            "- **{}:** {} (environment sourced)\n",
+            ctx.language.text(Label::BuildDir),
+            yocto.build_dir
+        ));
+    } else {
+        out.push_str(&format!(
+            "- **{}:** {} (not sourced: `source oe-init-build-env {}`)\n",
+            ctx.language.text(Label::BuildDir),
+            yocto.build_dir,
+            yocto.build_dir
+        ));
+    }
+    for (name, value) in [("MACHINE", &yocto.machine), ("DISTRO", &yocto.distro)] {
+        if !value.is_empty() {
+            out.push_str(&format!("- **{}:** {}\n", name, value));
+        }
+    }
+    if level == Level::Full {
+        for (name, value) in [
+            ("BB_NUMBER_THREADS", &yocto.bb_number_threads),
+            ("DL_DIR", &yocto.dl_dir),
+        ] {
+            if !value.is_empty() {
+                out.push_str(&format!("- **{}:** {}\n", name, value));
+            }
+        }
+        if !yocto.layers.is_empty() {
+            let names: Vec<&str> = yocto
+                .layers
+                .iter()
+                .map(|l| l.rsplit('/').find(|s| !s.is_empty()).unwrap_or(l))
+                .collect();
+            out.push_str(&format!(
+                "- **{}:** {}\n",
+                ctx.language.text(Label::Layers),
+                names.join(", ")
+            ));
+        }
+    }
+    match &yocto.deploy_modified {
+        Some(modified) => out.push_str(&format!(
+            "- **{}:** {} ({}, {})\n",
+            ctx.language.text(Label::LastBuild),
+            yocto.deploy_dir,
+            format_size(yocto.deploy_size_bytes),
+            format_age(modified, ctx.now(), ctx.language)
+        )),
+        None if level == Level::Full => out.push_str(&format!(
+            "- **{}:** no images deployed\n",
+            ctx.language.text(Label::LastBuild)
+        )),
+        None => {}
+    }
+    out.push('\n');
+    out
+}
+
+pub fn format_ros_section(ctx: &Context, level: Level) -> String {
+    let mut out = String::new();
+    let Some(ros) = &ctx.ros else {
+        return out;
+    };
+
+    out.push_str(&format!("## {}\n", ctx.language.text(Label::RosWorkspace)));
+    if !ros.path.is_empty() {
+        out.push_str(&format!(
+            "- **{}:** {}/\n",
+            ctx.language.text(Label::Path),
+            ros.path
+        ));
+    }
+    if !ros.distro.is_empty() {
+        out.push_str(&format!("- **ROS_DISTRO:** {}\n", ros.distro));
+    }
+    let dirs: Vec<&str> = [
+        ("build/", ros.has_build),
+        ("install/", ros.has_install),
+        ("log/", ros.has_log),
+    ]
+    .iter()
+    .filter(|(_, present)| *present)
+    .map(|(name, _)| *name)
+    .collect();
+    if dirs.is_empty() {
+        out.push_str(&format!(
+            "- **{}:** no (run `colcon build`)\n",
+            ctx.language.text(Label::Built)
+        ));
+    } else if ros.setup_stale {
+        out.push_str(&format!(
+            "- **{}:** {} (install/setup.bash older than sources)\n",
+            ctx.language.text(Label::Built),
+            dirs.join(", ")
+        ));
+    } else {
+        out.push_str(&format!(
+            "- **{}:** {}\n",
+            ctx.language.text(Label::Built),
+            dirs.join(", ")
+        ));
+    }
+
+    let count = |status| ros.packages.iter().filter(|p| p.status == status).count();
+    out.push_str(&format!(
+        "- **{}:** {} ({} built, {} stale, {} not built)\n",
+        ctx.language.text(Label::Packages),
+        ros.packages.len(),
+        count(RosPackageStatus::Built),
+        count(RosPackageStatus::Stale),
+        count(RosPackageStatus::NotBuilt)
+    ));
+
+    if level == Level::Full && !ros.packages.is_empty() {
+        out.push_str("\n| Package | Build Type | Path | Status |\n");
+        out.push_str("|---------|------------|------|--------|\n");
+        for package in &ros.packages {
+            out.push_str(&format!(
+                "| {} | {} | {} | {} |\n",
+                package.name,
+                package.build_type,
+                package.path,
+                package.status.as_str()
+            ));
+        }
+    }
+    out.push('\n');
+    out
+}
+
+pub fn format_commands_section(ctx: &Context) -> String {
+    let mut out = String::new();
+    if !ctx.available_commands.is_empty() {
+        out.push_str(&format!(
+            "## {}\n",
+            ctx.language.text(Label::ExampleCommands)
+        ));
+        out.push_str("```bash\n");
+        for cmd in &ctx.available_commands {
+            out.push_str(&format!("{}\n", cmd));
+        }
+        out.push_str("```\n");
+    }
+
+    if let Some(usage) = ctx.entry_point.as_ref().and_then(|e| e.usage.as_deref()) {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("### {}\n", ctx.language.text(Label::Usage)));
+        out.push_str(&format!("```\n{}\n```\n", usage));
+    }
+
+    if !ctx.just_recipes.is_empty() {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("### {}\n", ctx.language.text(Label::JustRecipes)));
+        out.push_str("| Recipe | Parameters | Description |\n");
+        out.push_str("|--------|------------|-------------|\n");
+        for recipe in &ctx.just_recipes {
+            out.push_str(&format!(
+                "| {} | {} | {} |\n",
+                recipe.name, recipe.parameters, recipe.description
+            ));
+        }
+    }
+
+    if !ctx.cargo_workspace.is_empty() {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("### {}\n", ctx.language.text(Label::Workspace)));
+        out.push_str("| Package | Version | Binaries |\n");
+        out.push_str("|---------|---------|----------|\n");
+        for package in &ctx.cargo_workspace {
+            let binaries: Vec<String> = package
+                .binaries
+                .iter()
+                .map(|bin| {
+                    if package.default_run.as_ref() == Some(bin) {
+                        format!("{} (default-run)", bin)
+                    } else {
+                        bin.clone()
+                    }
+                })
+                .collect();
+            out.push_str(&format!(
+                "| {} | {} | {} |\n",
+                package.name,
+                package.version,
+                binaries.join(", ")
+            ));
+        }
+    }
+    out
+}
+
+pub fn format_history_section(ctx: &Context) -> String {
+    let mut out = String::new();
+    if ctx.command_history.is_empty() {
+        return out;
+    }
+
+    out.push_str(&format!(
+        "## {}\n",
+        ctx.language.text(Label::RecentCommands)
+    ));
+    out.push_str(
+        "These commands were executed in previous sessions (useful after context compression):\n\n",
+    );
+    out.push_str("| Time | Command |\n");
+    out.push_str("|------|--------|\n");
+    for entry in &ctx.command_history {
+        let cmd_display = if entry.command.chars().count() > 80 {
+            let truncated: String = entry.command.chars().take(77).collect();
+            format!("{}...", truncated)
+        } else {
+            entry.command.clone()
+        };
+        let cmd_escaped = cmd_display.replace('|', "\\|");
+        out.push_str(&format!("| {} | `{}` |\n", entry.timestamp, cmd_escaped));
+    }
+    out.push('\n');
+    out
+}
+
+/// Helper: how many repositories `git.max_repos` left out of the table
+fn format_omitted_repos(ctx: &Context) -> String {
+    match ctx.git_repos_omitted {
+        0 => String::new(),
+        omitted => format!(
+            "*…and {} more {}, ranked below these by changes and recent activity (`[git] max_repos`)*\n\n",
+            format_count(omitted),
+            if omitted == 1 { "repository" } else { "repositories" }
+        ),
+    }
+}
+
+/// Helper: the files modified lately outside git, with their age
+fn format_recent_files(ctx: &Context) -> String {
+    if ctx.recent_files.is_empty() {
+        return String::new();
+    }
+    let mut out = format!("**{}:**\n", ctx.language.text(Label::RecentlyModified));
+    for file in &ctx.recent_files {
+        out.push_str(&format!(
+            "- `{}` ({})\n",
+            file.path,
+            format_age(&file.modified_at, ctx.now(), ctx.language)
+        ));
+    }
+    out.push('\n');
+    out
+}
+
+pub fn format_git_section(ctx: &Context, level: Level) -> String {
+    let mut out = String::new();
+
+    if level == Level::Full {
+        // Git information (ALL repositories)
+        if ctx.git_repos.is_empty() {
+            return format_recent_files(ctx);
+        }
+        out.push_str(&format!("## {}\n\n", ctx.language.text(Label::GitStatus)));
+        out.push_str("| Repository | Branch | Status | Last Commit |\n");
+        out.push_str("|------------|--------|--------|-------------|\n");
+
+        for git in &ctx.git_repos {
+            let commit = git.last_commit_short.replace('|', "\\|");
+            out.push_str(&format!(
+                "| {} | {} | {} | {} |\n",
+                git.name(),
+                git.branch,
+                format_git_status(git, ctx.language),
+                commit
+            ));
+        }
+        out.push('\n');
+        out.push_str(&format_omitted_repos(ctx));
+
+        let with_prs: Vec<&GitInfo> = ctx
+            .git_repos
+            .iter()
+            .filter(|r| !r.pull_requests.is_empty())
+            .collect();
+        if !with_prs.is_empty() {
+            out.push_str(&format!(
+                "**{}:**\n",
+                ctx.language.text(Label::OpenPullRequests)
+            ));
+            for git in with_prs {
+                for pr in &git.pull_requests {
+                    let mut about = vec![format!("`{}`", pr.branch)];
+                    if pr.current_branch {
+                        about.push("current branch".to_string());
+                    }
+                    about.extend(pr_state(pr));
+                    out.push_str(&format!(
+                        "- {}: #{} {} ({})\n",
+                        git.name(),
+                        pr.number,
+                        pr.title,
+                        about.join(", ")
+                    ));
+                }
+            }
+            out.push('\n');
+        }
+
+        let with_changes: Vec<&GitInfo> = ctx
+            .git_repos
+            .iter()
+            .filter(|r| !r.changes.is_empty())
+            .collect();
+        if !with_changes.is_empty() {
+            out.push_str(&format!(
+                "**{}:**\n",
+                ctx.language.text(Label::OpenGerritChanges)
+            ));
+            for git in with_changes {
+                for change in &git.changes {
+                    out.push_str(&format!(
+                        "- {}: {} {}{}\n",
+                        git.name(),
+                        change.number,
+                        change.subject,
+                        format_votes(change)
+                    ));
+                }
+            }
+            out.push('\n');
+        }
+        out.push_str(&format_recent_files(ctx));
+        return out;
+    }
+
+    // Show only dirty repos, and the ones `show_clean` lists anyway
+    let dirty_repos: Vec<&GitInfo> = ctx
+        .git_repos
+        .iter()
+        .filter(|r| r.is_dirty || r.operation.is_some() || r.status_unavailable.is_some())
+        .collect();
+    let listed_repos: Vec<&GitInfo> = ctx
+        .git_repos
+        .iter()
+        .filter(|r| r.show_clean || dirty_repos.iter().any(|d| std::ptr::eq(*d, *r)))
+        .collect();
+    // Only the PR for the checked-out branch below full level
+    let current_prs: Vec<(&GitInfo, &PullRequest)> = ctx
+        .git_repos
+        .iter()
+        .flat_map(|r| r.pull_requests.iter().map(move |pr| (r, pr)))
+        .filter(|(_, pr)| pr.current_branch)
+        .collect();
+    if dirty_repos.is_empty()
+        && (level == Level::Minimal || (listed_repos.is_empty() && current_prs.is_empty()))
+    {
+        return out;
+    }
+
+    if level == Level::Minimal {
+        out.push_str(&format!("**{}:** ", ctx.language.text(Label::ChangedRepos)));
+        let repo_strs: Vec<String> = dirty_repos
+            .iter()
+            .map(|r| format!("{} ({})", r.name(), format_git_status(r, ctx.language)))
+            .collect();
+        out.push_str(&repo_strs.join(", "));
+        out.push('\n');
+        return out;
+    }
+
+    out.push_str(&format!(
+        "## {}\n\n",
+        ctx.language.text(Label::GitChangesOnly)
+    ));
+    if !listed_repos.is_empty() {
+        out.push_str("| Repository | Branch | Status |\n");
+        out.push_str("|------------|--------|--------|\n");
+        for git in listed_repos {
+            out.push_str(&format!(
+                "| {} | {} | {} |\n",
+                git.name(),
+                git.branch,
+                format_git_status(git, ctx.language)
+            ));
+        }
+        out.push('\n');
+        out.push_str(&format_omitted_repos(ctx));
+    }
+    if !current_prs.is_empty() {
+        out.push_str(&format!(
+            "**{}:**\n",
+            ctx.language.text(Label::PullRequests)
+        ));
+        for (git, pr) in current_prs {
+            let state = pr_state(pr);
+            let state = if state.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", state.join(", "))
+            };
+            out.push_str(&format!(
+                "- {}: `{}` has open PR #{} {}{}\n",
+                git.name(),
+                pr.branch,
+                pr.number,
+                pr.title,
+                state
+            ));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Warnings about the pinned device and `ANDROID_SERIAL`, for every level
+fn format_device_selection(ctx: &Context) -> String {
+    let Some(selection) = &ctx.device_selection else {
+        return String::new();
+    };
+    let mut out = String::new();
+    if let (Some(pinned), true) = (&selection.pinned, selection.pinned_missing()) {
+        out.push_str(&format!(
+            "⚠️ **Pinned device not connected:** `{}` (`[adb]` in the config); don't fall back to another device\n",
+            pinned
+        ));
+    }
+    if let Some(serial) = &selection.android_serial {
+        let connected = ctx.adb_devices.iter().any(|d| &d.serial == serial);
+        let line = match (&selection.serial, connected) {
+            (_, false) => format!(
+                "⚠️ **ANDROID_SERIAL:** `{}` is set, but no such device is connected; bare `adb` fails",
+                serial
+            ),
+            (Some(pinned), true) if selection.pinned.is_some() && pinned != serial => format!(
+                "⚠️ **ANDROID_SERIAL:** `{}` is set, so bare `adb` reaches it instead of the pinned `{}`",
+                serial, pinned
+            ),
+            _ => format!(
+                "**ANDROID_SERIAL:** `{}` is set, so bare `adb` reaches that device",
+                serial
+            ),
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+/// ", default" for the pinned device, to go inside its parentheses
+fn default_marker(device: &AdbDevice) -> &'static str {
+    if device.is_default {
+        ", default"
+    } else {
+        ""
+    }
+}
+
+pub fn format_devices_section(ctx: &Context, level: Level) -> String {
+    let mut out = format_device_selection(ctx);
+    if !out.is_empty() && (level != Level::Minimal || ctx.adb_devices.is_empty()) {
+        out.push('\n');
+    }
+    if ctx.adb_devices.is_empty() {
+        return out;
+    }
+
+    match level {
+        // Device (one line), the pinned one when connected
+        Level::Oneline | Level::Minimal => {
+            let device = &ctx.adb_devices[0];
+            out.push_str(&format!(
+                "**{}:** {} ({}{})\n",
+                ctx.language.text(Label::Device),
+                device.serial,
+                device.device_type,
+                default_marker(device)
+            ));
+        }
+        Level::Normal => {
+            out.push_str(&format!(
+                "## {}\n",
+                ctx.language.text(Label::ConnectedDevices)
+            ));
+            for device in &ctx.adb_devices {
+                out.push_str(&format!(
+                    "- {} ({}, {}{})\n",
+                    device.serial,
+                    device.state,
+                    device.device_type,
+                    default_marker(device)
+                ));
+            }
+            out.push('\n');
+        }
+        Level::Full => {
+            out.push_str(&format!(
+                "## {}\n",
+                ctx.language.text(Label::ConnectedDevices)
+            ));
+            out.push_str("| Serial | State | Type | Model |\n");
+            out.push_str("|--------|-------|------|-------|\n");
+            for device in &ctx.adb_devices {
+                out.push_str(&format!(
+                    "| {}{} | {} | {} | {} |\n",
+                    device.serial,
+                    if device.is_default { " (default)" } else { "" },
+                    device.state,
+                    device.device_type,
+                    device.model
+                ));
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Helper: time since `timestamp` as "42m" or "2h 5m"
+pub fn format_elapsed(timestamp: &str, now: chrono::DateTime<chrono::Utc>) -> String {
+    let Ok(time) = chrono::DateTime::parse_from_rfc3339(timestamp) else {
+        return timestamp.to_string();
+    };
+    // A clock running ahead would make the age negative
+    let minutes = (now - clamp_to_now(time.with_timezone(&chrono::Utc), now)).num_minutes();
+    match minutes {
+        m if m < 1 => "<1m".to_string(),
+        m if m < 60 => format!("{}m", m),
+        m if m < 60 * 24 => format!("{}h {}m", m / 60, m % 60),
+        m => format!("{}d {}h", m / (60 * 24), m / 60 % 24),
+    }
+}
+
+/// Helper: "45s", "3m 12s" or "1h 5m"
+pub fn format_duration_secs(secs: u64) -> String {
+    match secs {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m {}s", s / 60, s % 60),
+        s => format!("{}h {}m", s / 3600, s / 60 % 60),
+    }
+}
+
+/// "**Last build:** `emu` FAILED 2h ago (`ninja: error: ...`)", with the
+/// captured output at full level
+fn format_build_result(
+    record: &BuildRecord,
+    level: Level,
+    now: chrono::DateTime<chrono::Utc>,
+    lang: Language,
+) -> String {
+    let age = format_age(&record.timestamp, now, lang);
+    let mut out = if record.succeeded() {
+        format!(
+            "✅ **{}:** `{}` succeeded {} in {}\n",
+            lang.text(Label::LastBuild),
+            record.label(),
+            age,
+            format_duration_secs(record.duration_secs)
+        )
+    } else {
+        let mut line = format!(
+            "❌ **{}:** `{}` FAILED {} (exit {}",
+            lang.text(Label::LastBuild),
+            record.label(),
+            age,
+            record.exit_code
+        );
+        if let Some(error) = record.error_line() {
+            let error = match error.char_indices().nth(120) {
+                Some((cut, _)) => format!("{}…", &error[..cut]),
+                None => error.to_string(),
+            };
+            line.push_str(&format!(": `{}`", error));
+        }
+        line + ")\n"
+    };
+    if level == Level::Full && !record.output.is_empty() {
+        out.push_str(&format!("  - `{}`\n\n```\n", record.command));
+        for line in &record.output {
+            out.push_str(line);
+            out.push('\n');
+        }
+        out.push_str("```\n");
+    }
+    out
+}
+
+/// A line per running build, with its command line and directory at
+/// full level, then the last recorded result per target
+pub fn format_builds_section(ctx: &Context, level: Level) -> String {
+    let mut out = String::new();
+    for build in &ctx.build_processes {
+        out.push_str(&format!(
+            "⏳ **{}** running for {} (CPU {:.0}%)",
+            build.command,
+            format_elapsed(&build.started_at, ctx.now()),
+            build.cpu_percent
+        ));
+        if let Some(container) = &build.container {
+            out.push_str(&format!(" in container `{}`", container));
+        }
+        if level == Level::Full {
+            out.push_str(&format!(
+                "\n  - pid {} in `{}`: `{}`",
+                build.pid, build.cwd, build.cmdline
+            ));
+        }
+        out.push('\n');
+    }
+    if level != Level::Minimal {
+        for record in &ctx.build_results {
+            out.push_str(&format_build_result(record, level, ctx.now(), ctx.language));
+        }
+    }
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+/// A warning per filesystem low on space; at full level a table of all
+/// of them with RAM and load below
+pub fn format_resources_section(ctx: &Context, level: Level) -> String {
+    let Some(resources) = &ctx.resources else {
+        return String::new();
+    };
+    let mut out = String::new();
+    let need = format!("{} GB", resources.min_free_bytes >> 30);
+    for disk in resources.low_disks() {
+        out.push_str(&format!(
+            "⚠️ **Low disk space:** only {} free on `{}`",
+            format_size(disk.free_bytes.unwrap_or_default()),
+            disk.mount
+        ));
+        if resources.requirement.is_empty() {
+            out.push_str(&format!(" (warning below {})\n", need));
+        } else {
+            out.push_str(&format!(" — {} need ~{}\n", resources.requirement, need));
+        }
+    }
+    if level == Level::Full && !resources.disks.is_empty() {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("## {}\n", ctx.language.text(Label::Resources)));
+        out.push_str("| Path | Mount | Free | Total |\n|------|-------|------|-------|\n");
+        for disk in &resources.disks {
+            let (free, total) = match (disk.free_bytes, disk.total_bytes) {
+                (Some(free), Some(total)) => (format_size(free), format_size(total)),
+                // Numbers the filesystem didn't report believably
+                _ => ("?".to_string(), "?".to_string()),
+            };
+            out.push_str(&format!(
+                "| `{}` | `{}` | {} | {} |\n",
+                disk.path, disk.mount, free, total
+            ));
+        }
+        out.push_str(&format!(
+            "\nRAM: {} available of {}",
+            format_size(resources.memory_available_bytes),
+            format_size(resources.memory_total_bytes)
+        ));
+        if let Some([one, five, fifteen]) = resources.load_average {
+            out.push_str(&format!(
+                ", load {:.2} {:.2} {:.2} on {} CPUs",
+                one, five, fifteen, resources.cpus
+            ));
+        }
+        out.push('\n');
+    }
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+/// Captured terminal variables, with when they were recorded
+pub fn format_environment_section(ctx: &Context) -> String {
+    let Some(env) = ctx.shell_env.as_ref().filter(|env| !env.vars.is_empty()) else {
+        return String::new();
+    };
+    let mut out = format!(
+        "## {}\nAs set in the terminal {} (`{}`):\n",
+        ctx.language.text(Label::Environment),
+        format_age(&env.timestamp, ctx.now(), ctx.language),
+        env.cwd
+    );
+    for (name, value) in &env.vars {
+        out.push_str(&format!("- `{}={}`\n", name, value));
+    }
+    out.push('\n');
+    out
+}
+
+/// The top-scored issues across all sections (see `attention_items`):
+/// one line at minimal level, a numbered list at the others
+pub fn format_attention_section(ctx: &Context, level: Level) -> String {
+    let limit = match level {
+        Level::Minimal => MINIMAL_ATTENTION_ITEMS,
+        _ => NORMAL_ATTENTION_ITEMS,
+    };
+    let items: Vec<&str> = ctx
+        .attention
+        .iter()
+        .take(limit)
+        .map(|item| item.text.as_str())
+        .collect();
+    if items.is_empty() {
+        return String::new();
+    }
+    if level == Level::Minimal {
+        return format!(
+            "**{}:** {}\n",
+            ctx.language.text(Label::Attention),
+            items.join("; ")
+        );
+    }
+    let mut out = format!("## {}\n", ctx.language.text(Label::Attention));
+    for (i, item) in items.iter().enumerate() {
+        out.push_str(&format!("{}. {}\n", i + 1, item));
+    }
+    out.push('\n');
+    out
+}
+
+/// "**Editor:** 4 files open, focused `src/hal/audio.cpp:212` 3m ago",
+/// with every file at full level
+fn format_open_files(
+    open_files: &OpenFiles,
+    level: Level,
+    now: chrono::DateTime<chrono::Utc>,
+    lang: Language,
+) -> String {
+    let count = open_files.files.len();
+    let mut out = format!(
+        "**{}:** {} file{} open",
+        lang.text(Label::Editor),
+        count,
+        if count == 1 { "" } else { "s" }
+    );
+    if let Some(focused) = open_files.focused() {
+        out.push_str(&format!(", focused `{}`", focused.location()));
+        if let Some(at) = &focused.focused_at {
+            out.push_str(&format!(" {}", format_age(at, now, lang)));
+        }
+    }
+    out.push('\n');
+    if level == Level::Full {
+        for file in &open_files.files {
+            out.push_str(&format!("  - `{}`\n", file.location()));
+        }
+    }
+    out.push('\n');
+    out
+}
+
+/// What the editor has open, then the tmux panes
+pub fn format_terminals_section(ctx: &Context, level: Level) -> String {
+    let mut out = String::new();
+    if level != Level::Minimal {
+        if let Some(open_files) = &ctx.open_files {
+            out.push_str(&format_open_files(
+                open_files,
+                level,
+                ctx.now(),
+                ctx.language,
+            ));
+        }
+    }
+    match level {
+        Level::Oneline | Level::Minimal => {}
+        // Only panes busy with a build, a log tail or an emulator
+        Level::Normal => {
+            let busy: Vec<_> = ctx
+                .terminal_panes
+                .iter()
+                .filter(|pane| pane.is_long_lived())
+                .collect();
+            if busy.is_empty() {
+                return out;
+            }
+            out.push_str(&format!(
+                "## {}\n",
+                ctx.language.text(Label::TerminalSessions)
+            ));
+            for pane in busy {
+                out.push_str(&format!(
+                    "- `{}` in {}:{} (`{}`)\n",
+                    pane.command, pane.session, pane.window, pane.path
+                ));
+            }
+            out.push('\n');
+        }
+        Level::Full => {
+            if ctx.terminal_panes.is_empty() {
+                return out;
+            }
+            out.push_str(&format!(
+                "## {}\n",
+                ctx.language.text(Label::TerminalSessions)
+            ));
+            out.push_str("| Session | Window | Command | Path |\n");
+            out.push_str("|---------|--------|---------|------|\n");
+            for pane in &ctx.terminal_panes {
+                out.push_str(&format!(
+                    "| {} | {} | {} | {} |\n",
+                    pane.session, pane.window, pane.command, pane.path
+                ));
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Key/value rows of a custom collector's JSON: an object's members, an
+/// array's elements by index, or a lone value. Strings are shown bare,
+/// anything else as compact JSON.
+pub fn json_rows(value: &serde_json::Value) -> Vec<(String, String)> {
+    let show = |value: &serde_json::Value| match value {
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    match value {
+        serde_json::Value::Object(map) => map.iter().map(|(k, v)| (k.clone(), show(v))).collect(),
+        serde_json::Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (i.to_string(), show(v)))
+            .collect(),
+        other => vec![("value".to_string(), show(other))],
+    }
+}
+
+/// `[[custom_collectors]]` output shown at `level`, one heading each
+pub fn format_custom_section(ctx: &Context, level: Level) -> String {
+    let mut out = String::new();
+    for section in ctx.custom_sections.iter().filter(|s| s.shown_at(level)) {
+        out.push_str(&format!("## {}\n", section.name));
+        match &section.output {
+            CustomOutput::Text { text, truncated } => {
+                // A fence longer than any backtick run in the output
+                let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+                let fence = "`".repeat(longest.max(2) + 1);
+                out.push_str(&format!("{}\n{}\n{}\n", fence, text, fence));
+                if *truncated {
+                    out.push_str(&format!("*Output cut at {} bytes.*\n", MAX_CUSTOM_OUTPUT));
+                }
+            }
+            CustomOutput::Json { value } => {
+                out.push_str("| Key | Value |\n");
+                out.push_str("|-----|-------|\n");
+                for (key, value) in json_rows(value) {
+                    out.push_str(&format!(
+                        "| {} | {} |\n",
+                        key.replace('|', "\\|"),
+                        value.replace('|', "\\|").replace('\n', " ")
+                    ));
+                }
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Helper: one line per timestamp that doesn't fit the clock
+pub fn format_clock_note(ctx: &Context) -> String {
+    clock_warnings(ctx)
+        .iter()
+        .map(|warning| {
+            format!(
+                "> **{}:** {}\n\n",
+                ctx.language.text(Label::Clock),
+                warning.message
+            )
+        })
+        .collect()
+}
+
+/// Helper: one line per input cut at a `[limits]` cap
+pub fn format_capped_note(ctx: &Context) -> String {
+    ctx.reports
+        .iter()
+        .flat_map(|report| &report.capped)
+        .map(|summary| {
+            format!(
+                "> **{}:** {}\n\n",
+                ctx.language.text(Label::Capped),
+                summary
+            )
+        })
+        .collect()
+}
+
+/// Helper: note that `.contextkeeper-ignore` kept parts of the tree out
+pub fn format_ignore_note(ctx: &Context) -> String {
+    match ctx.ignore_rules {
+        Some(rules) => format!(
+            "> **{}:** `{}` has {} {}; what {} was not scanned\n\n",
+            ctx.language.text(Label::Ignored),
+            PROJECT_IGNORE_FILE,
+            rules,
+            if rules == 1 { "rule" } else { "rules" },
+            if rules == 1 {
+                "it matches"
+            } else {
+                "they match"
+            }
+        ),
+        None => String::new(),
+    }
+}
+
+/// Helper: note that the context came from the server's cache
+pub fn format_cache_note(ctx: &Context) -> String {
+    match ctx.cache_age {
+        Some(age) => format!(
+            "*Cached context from {}s ago; pass force_refresh=true to re-collect.*\n\n",
+            age.as_secs()
+        ),
+        None => String::new(),
+    }
+}
+
+pub fn format_diagnostics_section(ctx: &Context, level: Level) -> String {
+    if level != Level::Full {
+        let blocking = if level == Level::Minimal {
+            format_blocking_note(ctx)
+        } else {
+            String::new()
+        };
+        return format_partial_note(ctx)
+            + &blocking
+            + &format_clock_note(ctx)
+            + &format_capped_note(ctx)
+            + &format_cache_note(ctx);
+    }
+
+    // Diagnostics (only when something was abandoned)
+    let mut out = String::new();
+    if ctx.reports.iter().any(|r| r.status != CollectorStatus::Ok) {
+        out.push_str(&format!("## {}\n", ctx.language.text(Label::Diagnostics)));
+        out.push_str("| Collector | Status | Duration |\n");
+        out.push_str("|-----------|--------|----------|\n");
+        for report in &ctx.reports {
+            out.push_str(&format!(
+                "| {} | {} | {}ms |\n",
+                report.name,
+                report.status.as_str(),
+                report.duration.as_millis()
+            ));
+        }
+        out.push('\n');
+    }
+    out.push_str(&format_clock_note(ctx));
+    out.push_str(&format_capped_note(ctx));
+    out.push_str(&format_ignore_note(ctx));
+    out.push_str(&format_cache_note(ctx));
+    out
+}
+
+/// Per-collector timing table printed by `context --timings`
+pub fn format_timings(reports: &[CollectorReport], repeat: u32) -> String {
+    let mut out = String::new();
+    if repeat > 1 {
+        out.push_str(&format!("## Timings (average of {} runs)\n", repeat));
+    } else {
+        out.push_str("## Timings\n");
+    }
+    out.push_str("| Collector | Status | Duration | Items | Subprocesses | Warnings |\n");
+    out.push_str("|-----------|--------|----------|-------|--------------|----------|\n");
+    for report in reports {
+        out.push_str(&format!(
+            "| {} | {} | {}ms | {} | {} | {} |\n",
+            report.name,
+            report.status.as_str(),
+            report.duration.as_millis(),
+            report.items,
+            report.subprocesses,
+            report.warnings.join("; ")
+        ));
+    }
+    out
+}
+
+/// Concatenate the given sections at a level
+pub fn format_sections(ctx: &Context, sections: &[Section], level: Level) -> String {
+    join_parts(&section_parts(ctx, sections, level))
+}
+
+/// Below this many useful lines, minimal level shows the normal-level
+/// rendering instead
+pub const MIN_USEFUL_LINES: usize = 3;
+
+/// Hints listed when there is nothing else to report
+pub const QUIET_HINTS: usize = 3;
+
+/// Helper: lines of rendered sections that say something, not blank
+/// lines, headings or rules
+fn useful_lines(body: &str) -> usize {
+    body.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && *line != "---")
+        .count()
+}
+
+/// Nothing worth a section: no saved work state, every repository clean,
+/// nothing running or connected, and no attention items. Only what the
+/// level collected is looked at.
+pub fn nothing_notable(ctx: &Context) -> bool {
+    ctx.work_state.is_none()
+        && ctx.attention.is_empty()
+        && ctx.git_repos.iter().all(|repo| {
+            !repo.is_dirty
+                && repo.conflicted_files == 0
+                && repo.operation.is_none()
+                && repo.status_unavailable.is_none()
+        })
+        && ctx.containers.is_empty()
+        && ctx.adb_devices.is_empty()
+        && ctx.build_processes.is_empty()
+        && ctx.build_results.is_empty()
+        && ctx.terminal_panes.is_empty()
+        && ctx.open_files.is_none()
+        && ctx.custom_sections.is_empty()
+        && ctx.remote.is_none()
+}
+
+/// What a fresh or idle project still has to say, so the output reads as
+/// deliberate rather than broken: the project, the clean tree, how to
+/// save a work state and, below full level, the top hints
+pub fn format_nothing_notable(ctx: &Context, level: Level) -> String {
+    let lang = ctx.language;
+    let mut out = format!("## {}\n", lang.text(Label::NothingToReport));
+    let name = if ctx.project_name.is_empty() {
+        "unnamed"
+    } else {
+        ctx.project_name.as_str()
+    };
+    let project_type = if ctx.project_type.is_empty() {
+        let detected: Vec<&str> = ctx
+            .detected_types
+            .iter()
+            .map(|d| d.project_type.as_str())
+            .collect();
+        detected.join(", ")
+    } else {
+        ctx.project_type.clone()
+    };
+    if project_type.is_empty() {
+        out.push_str(&format!("- **{}:** {}\n", lang.text(Label::Project), name));
+    } else {
+        out.push_str(&format!(
+            "- **{}:** {} ({})\n",
+            lang.text(Label::Project),
+            name,
+            project_type
+        ));
+    }
+    match (ctx.git_repos.len(), ctx.git_repos_omitted) {
+        (0, _) => out.push_str(&format!(
+            "- **{}:** no repositories found\n",
+            lang.text(Label::Git)
+        )),
+        (n, omitted) if omitted > 0 => out.push_str(&format!(
+            "- **{}:** working tree clean across the {} most recently active repos; {} more not shown (`[git] max_repos`)\n",
+            lang.text(Label::Git),
+            n,
+            format_count(omitted)
+        )),
+        (1, _) => out.push_str(&format!(
+            "- **{}:** working tree clean\n",
+            lang.text(Label::Git)
+        )),
+        (n, _) => out.push_str(&format!(
+            "- **{}:** working tree clean across {} repos\n",
+            lang.text(Label::Git),
+            n
+        )),
+    }
+    out.push_str(&format!(
+        "- **{}:** none saved — call `save_work_state` to create one\n",
+        lang.text(Label::WorkStateTitle)
+    ));
+    if level != Level::Full {
+        if !ctx.targets.is_empty() {
+            out.push_str(&format!(
+                "- **{}:** {}, listed at level=\"full\"\n",
+                lang.text(Label::BuildTargetsTitle),
+                ctx.targets.len()
+            ));
+        }
+        for hint in ctx.hints.iter().take(QUIET_HINTS) {
+            out.push_str(&format!("- **{}:** {}\n", lang.text(Label::Hint), hint));
+        }
+        if ctx.hints.len() > QUIET_HINTS {
+            out.push_str(&format!(
+                "- ({} more hints at level=\"full\")\n",
+                ctx.hints.len() - QUIET_HINTS
+            ));
+        }
+    }
+    out.push('\n');
+    out
+}
+
+/// Setup help for a project without a config file: what was detected,
+/// what a config would add and the command that writes one
+pub fn format_onboarding(onboarding: &Onboarding, lang: Language) -> String {
+    let mut out = format!("## {}\n", lang.text(Label::GettingStarted));
+    out.push_str("No contextkeeper.toml here, so most sections stay empty. Detected:\n");
+    match onboarding.project_types.as_slice() {
+        [] => out.push_str(&format!(
+            "- **{}:** nothing recognized\n",
+            lang.text(Label::Type)
+        )),
+        types => out.push_str(&format!(
+            "- **{}:** {} (guess)\n",
+            lang.text(Label::Type),
+            types.join(", ")
+        )),
+    }
+    let repos = &onboarding.git_repos;
+    let mut named: Vec<String> = repos
+        .iter()
+        .take(MAX_ONBOARDING_REPOS)
+        .map(|repo| format!("`{}`", repo))
+        .collect();
+    if repos.len() > MAX_ONBOARDING_REPOS {
+        named.push(format!("{} more", repos.len() - MAX_ONBOARDING_REPOS));
+    }
+    match repos.as_slice() {
+        [] => out.push_str(&format!(
+            "- **{}:** no repositories found\n",
+            lang.text(Label::Git)
+        )),
+        [root] if root == "." => out.push_str(&format!(
+            "- **{}:** the project root is a repository\n",
+            lang.text(Label::Git)
+        )),
+        [_] => out.push_str(&format!(
+            "- **{}:** {}\n",
+            lang.text(Label::Git),
+            named.join(", ")
+        )),
+        _ => out.push_str(&format!(
+            "- **{}:** {} repos: {}\n",
+            lang.text(Label::Git),
+            repos.len(),
+            named.join(", ")
+        )),
+    }
+    match &onboarding.container_runtime {
+        Some(runtime) => out.push_str(&format!(
+            "- **{}:** {} installed\n",
+            lang.text(Label::Containers),
+            runtime
+        )),
+        None => out.push_str(&format!(
+            "- **{}:** no podman or docker\n",
+            lang.text(Label::Containers)
+        )),
+    }
+    if let Some(entry_point) = &onboarding.build_entry_point {
+        out.push_str(&format!("- **Build entry point:** `{}`\n", entry_point));
+    }
+    if let Some((dir, confs)) = &onboarding.config_dir {
+        out.push_str(&format!(
+            "- **Target configs:** `{}` ({} .conf files)\n",
+            dir, confs
+        ));
+    }
+
+    out.push_str("\nA config adds:\n");
+    match (&onboarding.build_entry_point, &onboarding.config_dir) {
+        (None, None) => out
+            .push_str("- Build targets and commands, from `[scripts]` entry_point or config_dir\n"),
+        _ => out.push_str("- Build targets and commands from the files above (`[scripts]`)\n"),
+    }
+    if onboarding.container_runtime.is_some() {
+        out.push_str("- Which target builds in which container (`[containers]`)\n");
+    }
+    out.push_str("- The build commands you ran lately (`[history]`)\n");
+    out.push_str("- Instructions shown in every context, e.g. how to build (`[hints]`)\n");
+    out.push_str(&format!(
+        "\nWrite one with `{}`, or call `bootstrap_config` to write it from the values above.\n\n",
+        onboarding.init_command
+    ));
+    out
+}
+
+/// Helper: the sections of `level`, after the onboarding section when the
+/// project has no config
+/// A piece of the rendered context: one section, or the title,
+/// onboarding block or footer around them (`section` is `None` for those).
+/// `budget` cuts output between parts, never inside one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextPart {
+    pub section: Option<Section>,
+    pub text: String,
+}
+
+impl ContextPart {
+    fn new(section: Option<Section>, text: String) -> Self {
+        ContextPart { section, text }
+    }
+}
+
+/// The parts' text, in order
+pub fn join_parts(parts: &[ContextPart]) -> String {
+    parts.iter().map(|part| part.text.as_str()).collect()
+}
+
+fn level_body_parts(ctx: &Context, level: Level) -> Vec<ContextPart> {
+    let onboarding = ctx
+        .onboarding
+        .as_ref()
+        .map(|onboarding| ContextPart::new(None, format_onboarding(onboarding, ctx.language)));
+    onboarding
+        .into_iter()
+        .chain(level_section_parts(ctx, level))
+        .collect()
+}
+
+/// Helper: the sections of `level`. With nothing notable, the
+/// `format_nothing_notable` summary takes the place of the hints, or
+/// opens the full level, which lists every hint itself.
+fn level_section_parts(ctx: &Context, level: Level) -> Vec<ContextPart> {
+    let sections = level_sections(level);
+    if !nothing_notable(ctx) {
+        return section_parts(ctx, sections, level);
+    }
+    if level == Level::Full {
+        let summary = ContextPart::new(None, format_nothing_notable(ctx, level));
+        return std::iter::once(summary)
+            .chain(section_parts(ctx, sections, level))
+            .collect();
+    }
+    sections
+        .iter()
+        .map(|section| match section {
+            Section::Hints => ContextPart::new(Some(*section), format_nothing_notable(ctx, level)),
+            section => ContextPart::new(Some(*section), format_section(ctx, *section, level)),
+        })
+        .collect()
+}
+
+/// `sections` rendered at `level`, one part each
+pub fn section_parts(ctx: &Context, sections: &[Section], level: Level) -> Vec<ContextPart> {
+    sections
+        .iter()
+        .map(|section| ContextPart::new(Some(*section), format_section(ctx, *section, level)))
+        .collect()
+}
+
+/// The parts `format_context` joins for `level`. When the minimal sections
+/// would say almost nothing, what was collected is rendered at normal
+/// level instead.
+pub fn context_parts(ctx: &Context, level: Level) -> Vec<ContextPart> {
+    let (title, body, footer) = match level {
+        Level::Oneline => return vec![ContextPart::new(None, format_oneline(ctx))],
+        Level::Minimal => {
+            let body = level_body_parts(ctx, Level::Minimal);
+            let body = if useful_lines(&join_parts(&body)) < MIN_USEFUL_LINES {
+                level_body_parts(ctx, Level::Normal)
+            } else {
+                body
+            };
+            (
+                Label::ContextRecoveryMinimal,
+                body,
+                "\n---\n*Run `get_dev_context` with level=\"normal\" or \"full\" for more details.*\n",
+            )
+        }
+        Level::Normal => (
+            Label::DevelopmentContext,
+            level_body_parts(ctx, Level::Normal),
+            "---\n*Run `get_dev_context` with level=\"full\" for complete information.*\n",
+        ),
+        Level::Full => (
+            Label::DevelopmentContextFull,
+            level_body_parts(ctx, Level::Full),
+            "",
+        ),
+    };
+    let mut title = format!("# {}\n\n", ctx.language.text(title));
+    if let (Some(activity), Some(idle)) = (&ctx.last_activity, idle_duration(ctx)) {
+        title.push_str(&format!(
+            "*{} {} ({})*\n\n",
+            ctx.language.text(Label::LastActivity),
+            format_idle(idle, ctx.language),
+            activity.source.text(ctx.language)
+        ));
+    }
+    let title = ContextPart::new(None, title);
+    let footer = (!footer.is_empty()).then(|| ContextPart::new(None, footer.to_string()));
+    std::iter::once(title).chain(body).chain(footer).collect()
+}
+
+/// Oneline format (~30 tokens): the `status` segments plus the top
+/// attention item, e.g. "aosp-demo ⎇2 dirty ▸ task: ... ▸ 1 device ▸ ⚠ ..."
+pub fn format_oneline(ctx: &Context) -> String {
+    let dirty = ctx.git_repos.iter().filter(|r| r.is_dirty).count();
+    let conflicts: usize = ctx.git_repos.iter().map(|r| r.conflicted_files).sum();
+    let task = ctx
+        .work_state
+        .as_ref()
+        .map(|ws| ws.task_summary.as_str())
+        .unwrap_or_default();
+    let issue = ctx
+        .attention
+        .first()
+        .map(|item| format!("⚠ {}", item.text))
+        .unwrap_or_default();
+    let line = join_segments(&format!(
+        "{} {} ▸ {} ▸ {} ▸ {}",
+        ctx.project_name,
+        repo_segment(dirty, conflicts),
+        task_segment(task),
+        device_segment(ctx.adb_devices.len()),
+        issue
+    ));
+    line + "\n"
+}
+
+/// Minimal format (~200 tokens) - for recovery after compression
+pub fn format_minimal(ctx: &Context) -> String {
+    join_parts(&context_parts(ctx, Level::Minimal))
+}
+
+/// Normal format (~400 tokens) - balanced info
+pub fn format_normal(ctx: &Context) -> String {
+    join_parts(&context_parts(ctx, Level::Normal))
+}
+
+/// Full format (~1000 tokens) - complete information
+pub fn format_full(ctx: &Context) -> String {
+    join_parts(&context_parts(ctx, Level::Full))
+}
+
+/// Main formatter dispatcher
+pub fn format_context(ctx: &Context, level: Level) -> String {
+    match level {
+        Level::Oneline => format_oneline(ctx),
+        Level::Minimal => format_minimal(ctx),
+        Level::Normal => format_normal(ctx),
+        Level::Full => format_full(ctx),
+    }
+}
+
+/// JSON value of a single section
+pub fn section_json(ctx: &Context, section: Section) -> serde_json::Value {
+    let value = match section {
+        Section::Project => Ok(serde_json::json!({
+            "name": ctx.project_name,
+            "type": ctx.project_type,
+            "detected": ctx.detected_types,
+            "kconfig": ctx.kconfig,
+        })),
+        Section::WorkState => serde_json::to_value(&ctx.work_state).map(|mut value| {
+            // A fresh scan replaces the one saved with the state
+            if let (Some(todos), Some(state)) = (&ctx.code_todos, value.as_object_mut()) {
+                state.insert("code_todos".to_string(), serde_json::json!(todos));
+            }
+            if let (false, Some(state)) =
+                (ctx.working_file_checks.is_empty(), value.as_object_mut())
+            {
+                state.insert(
+                    "working_file_checks".to_string(),
+                    serde_json::json!(ctx.working_file_checks),
+                );
+            }
+            value
+        }),
+        Section::Hints => serde_json::to_value(&ctx.hints),
+        Section::Targets => serde_json::to_value(&ctx.targets),
+        Section::Containers => serde_json::to_value(&ctx.containers),
+        Section::Yocto => serde_json::to_value(&ctx.yocto),
+        Section::Ros => serde_json::to_value(&ctx.ros),
+        Section::Commands => serde_json::to_value(&ctx.available_commands),
+        Section::History => serde_json::to_value(&ctx.command_history),
+        Section::Git => serde_json::to_value(&ctx.git_repos),
+        Section::Devices => serde_json::to_value(&ctx.adb_devices),
+        Section::Terminals => Ok(serde_json::json!({
+            "panes": ctx.terminal_panes,
+            "editor": ctx.open_files,
+        })),
+        Section::Builds => Ok(serde_json::json!({
+            "running": ctx.build_processes,
+            "results": ctx.build_results,
+        })),
+        Section::Resources => serde_json::to_value(&ctx.resources),
+        Section::Environment => serde_json::to_value(&ctx.shell_env),
+        Section::Attention => serde_json::to_value(&ctx.attention),
+        Section::Custom => serde_json::to_value(&ctx.custom_sections),
+        Section::Diagnostics => serde_json::to_value(&ctx.reports),
+    };
+    value.unwrap_or(serde_json::Value::Null)
+}
+
+/// JSON object holding the given sections, keyed by section name
+pub fn sections_json(ctx: &Context, sections: &[Section]) -> serde_json::Value {
+    let map: serde_json::Map<String, serde_json::Value> = sections
+        .iter()
+        .map(|section| (section.name().to_string(), section_json(ctx, *section)))
+        .collect();
+    serde_json::Value::Object(map)
+}
@@ -0,0 +1,179 @@
+//! `max_output_bytes`: context output that fits a client's cap on tool
+//! results
+//!
+//! A client that cuts tool results at a fixed size would cut the context
+//! mid-table. Instead the context already collected is rendered again one
+//! level down (full, normal, minimal) until it fits, under a notice naming
+//! the level it fell to and the sections that were left out. No collector
+//! runs again. Only when even minimal level is too big is it cut, after
+//! the last part (see `ContextPart`) that fits.
+
+use crate::collectors::custom::cap_text;
+use crate::config::Config;
+use crate::context::Context;
+use crate::format::{context_parts, join_parts, section_parts, ContextPart, Level, Section};
+use crate::i18n::Label;
+use crate::limits::format_count;
+
+/// The call's `max_output_bytes`, else `[output] max_output_bytes`; `None`
+/// without a cap. 0 means no cap, so a call can lift the configured one.
+pub fn output_budget(config: &Config, requested: Option<usize>) -> Option<usize> {
+    requested
+        .or_else(|| config.output.as_ref().and_then(|o| o.max_output_bytes))
+        .filter(|&max| max > 0)
+}
+
+/// The next level down, `None` below minimal
+pub fn level_below(level: Level) -> Option<Level> {
+    match level {
+        Level::Full => Some(Level::Normal),
+        Level::Normal => Some(Level::Minimal),
+        Level::Minimal | Level::Oneline => None,
+    }
+}
+
+fn parts_at(ctx: &Context, sections: Option<&[Section]>, level: Level) -> Vec<ContextPart> {
+    match sections {
+        Some(sections) => section_parts(ctx, sections, level),
+        None => context_parts(ctx, level),
+    }
+}
+
+/// Sections of `wanted` with nothing in `parts`
+fn lost_sections(wanted: &[ContextPart], parts: &[ContextPart]) -> Vec<Section> {
+    let mut lost: Vec<Section> = Vec::new();
+    for section in wanted
+        .iter()
+        .filter(|part| !part.text.is_empty())
+        .filter_map(|part| part.section)
+    {
+        let kept = parts
+            .iter()
+            .any(|part| part.section == Some(section) && !part.text.is_empty());
+        if !kept && !lost.contains(&section) {
+            lost.push(section);
+        }
+    }
+    lost
+}
+
+/// What was shown instead of the requested level: a lower level, or
+/// minimal level cut after a section (`None` when no section fit)
+enum Fallback {
+    Level(Level),
+    Cut(Option<Section>),
+}
+
+fn budget_notice(
+    ctx: &Context,
+    requested: Level,
+    size: usize,
+    max: usize,
+    fallback: &Fallback,
+    lost: &[Section],
+) -> String {
+    let mut out = format!(
+        "> **{}:** level=\"{}\" is {} bytes, over max_output_bytes={}",
+        ctx.language.text(Label::OutputCapped),
+        requested.name(),
+        format_count(size),
+        format_count(max)
+    );
+    if matches!(fallback, Fallback::Cut(_)) && level_below(requested).is_some() {
+        out.push_str(", and so is level=\"minimal\"");
+    }
+    match fallback {
+        Fallback::Level(level) => out.push_str(&format!("; this is level=\"{}\"", level.name())),
+        Fallback::Cut(Some(last)) => {
+            out.push_str(&format!("; cut after the {} section", last.name()))
+        }
+        Fallback::Cut(None) => out.push_str("; no section fits"),
+    }
+    if !lost.is_empty() {
+        let names: Vec<&str> = lost.iter().map(|section| section.name()).collect();
+        out.push_str(&format!(". Left out: {}", names.join(", ")));
+    }
+    out.push_str(".\n\n");
+    out
+}
+
+/// The context at `level` (or just `sections` at it) as `finish` turns it
+/// into the tool result, within `max_bytes` of that result: the requested
+/// level when it fits, else the first lower level that does, else minimal
+/// level cut at a part boundary. The notice alone is returned when no part
+/// fits with it, shortened or cut so the result stays within `max_bytes`.
+pub fn format_within_budget<F>(
+    ctx: &Context,
+    sections: Option<&[Section]>,
+    level: Level,
+    max_bytes: usize,
+    finish: F,
+) -> String
+where
+    F: Fn(&str) -> String,
+{
+    let wanted = parts_at(ctx, sections, level);
+    let text = finish(&join_parts(&wanted));
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let size = text.len();
+
+    let mut current = level;
+    while let Some(lower) = level_below(current) {
+        current = lower;
+        let parts = parts_at(ctx, sections, lower);
+        let lost = lost_sections(&wanted, &parts);
+        let notice = budget_notice(ctx, level, size, max_bytes, &Fallback::Level(lower), &lost);
+        let text = finish(&(notice + &join_parts(&parts)));
+        if text.len() <= max_bytes {
+            return text;
+        }
+    }
+
+    // Even the lowest level is over: the longest run of whole parts that
+    // fits
+    let parts = parts_at(ctx, sections, current);
+    for kept in (1..parts.len()).rev() {
+        let shown = &parts[..kept];
+        let last = shown
+            .iter()
+            .rev()
+            .filter(|part| !part.text.is_empty())
+            .find_map(|part| part.section);
+        let lost = lost_sections(&wanted, shown);
+        let notice = budget_notice(ctx, level, size, max_bytes, &Fallback::Cut(last), &lost);
+        let text = finish(&(notice + &join_parts(shown)));
+        if text.len() <= max_bytes {
+            return text;
+        }
+    }
+
+    // Not one part fits beside the notice: the notice alone, then shorter
+    // ones, and at worst the start of the shortest, so the result never
+    // goes over
+    let lost = lost_sections(&wanted, &[]);
+    let notices = [
+        budget_notice(ctx, level, size, max_bytes, &Fallback::Cut(None), &lost),
+        budget_notice(ctx, level, size, max_bytes, &Fallback::Cut(None), &[]),
+        short_notice(ctx, max_bytes),
+    ];
+    let mut shortest = String::new();
+    for notice in &notices {
+        shortest = finish(notice);
+        if shortest.len() <= max_bytes {
+            return shortest;
+        }
+    }
+    cap_text(&shortest, max_bytes).0
+}
+
+/// "> **Output capped:** over max_output_bytes=50.", for a cap too small
+/// for the full notice
+fn short_notice(ctx: &Context, max: usize) -> String {
+    format!(
+        "> **{}:** over max_output_bytes={}.\n",
+        ctx.language.text(Label::OutputCapped),
+        format_count(max)
+    )
+}
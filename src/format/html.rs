@@ -0,0 +1,740 @@
+//! Self-contained HTML dashboard: `context-keeper export html`
+//!
+//! One page from `dashboard.html` with the CSS inline and no scripts or
+//! external assets, so any static server (or a browser opening the file)
+//! shows it as is. Every section is a `<details>` block. Sections without
+//! a table of their own show their Markdown in a `<pre>`.
+
+use crate::collectors::code_todos::code_todos_to_show;
+use crate::collectors::custom::{CustomOutput, MAX_CUSTOM_OUTPUT};
+use crate::collectors::working_files::WorkingFileState;
+use crate::collectors::{ContainerInfo, GitInfo};
+use crate::context::{CollectorStatus, Context};
+use crate::format::{
+    format_age, format_artifact, format_duration_secs, format_elapsed, format_git_status,
+    format_section, format_size, json_rows, level_sections, Level, Section,
+};
+use crate::i18n::Label;
+
+/// Page template; `{{name}}` placeholders are filled by `format_html`
+pub const DASHBOARD_TEMPLATE: &str = include_str!("dashboard.html");
+
+/// Escape text for element content and quoted attributes
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Fill `{{name}}` placeholders in one pass, so text in a value is never
+/// taken for a placeholder. Values are inserted as given; unknown names
+/// are left in place.
+fn render_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let value = after.find("}}").and_then(|end| {
+            let name = &after[..end];
+            let (_, value) = values.iter().find(|(n, _)| *n == name)?;
+            Some((value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                out.push_str(value);
+                rest = &after[end + 2..];
+            }
+            None => {
+                out.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Colored pill; `color` is one of red, yellow, green, grey
+fn badge(color: &str, text: &str) -> String {
+    format!(r#"<span class="badge {}">{}</span>"#, color, escape(text))
+}
+
+fn git_badge(git: &GitInfo) -> String {
+    if git.status_unavailable.is_some() {
+        badge("grey", "unknown")
+    } else if git.conflicted_files > 0 {
+        badge("red", "conflicts")
+    } else if git.is_dirty {
+        badge("yellow", "dirty")
+    } else {
+        badge("green", "clean")
+    }
+}
+
+fn container_badge(container: &ContainerInfo) -> String {
+    if container.is_unhealthy() {
+        badge("red", "unhealthy")
+    } else if container.status.starts_with("Up") {
+        badge("green", "up")
+    } else {
+        badge("grey", "stopped")
+    }
+}
+
+fn status_badge(status: CollectorStatus) -> String {
+    let color = match status {
+        CollectorStatus::Ok => "green",
+        CollectorStatus::TimedOut | CollectorStatus::Cancelled => "yellow",
+        CollectorStatus::Failed => "red",
+    };
+    badge(color, status.as_str())
+}
+
+/// `<table>` with a header row; cells are already HTML
+fn table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut out = String::from("<table>\n<tr>");
+    for header in headers {
+        out.push_str(&format!("<th>{}</th>", escape(header)));
+    }
+    out.push_str("</tr>\n");
+    for row in rows {
+        out.push_str("<tr>");
+        for cell in row {
+            out.push_str(&format!("<td>{}</td>", cell));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</table>\n");
+    out
+}
+
+fn code(text: &str) -> String {
+    format!("<code>{}</code>", escape(text))
+}
+
+/// Repo path, marked with the host for `[remote]` repos
+fn located(path: &str, host: Option<&str>) -> String {
+    match host {
+        Some(host) => format!(
+            "{} <span class=\"muted\">(remote: {})</span>",
+            code(path),
+            escape(host)
+        ),
+        None => code(path),
+    }
+}
+
+fn work_state_html(ctx: &Context) -> Option<String> {
+    let ws = ctx.work_state.as_ref()?;
+    let mut out = String::from("<ul>\n");
+    out.push_str(&format!(
+        "<li><b>Saved:</b> {} ({})</li>\n",
+        escape(&format_age(&ws.saved_at, ctx.now(), ctx.language)),
+        escape(&ws.trigger)
+    ));
+    if !ws.task_summary.is_empty() {
+        out.push_str(&format!(
+            "<li><b>Task:</b> {}</li>\n",
+            escape(&ws.task_summary)
+        ));
+    }
+    if !ws.notes.is_empty() {
+        out.push_str(&format!("<li><b>Notes:</b> {}</li>\n", escape(&ws.notes)));
+    }
+    if !ws.working_files.is_empty() {
+        let files: Vec<String> = ws
+            .working_files
+            .iter()
+            .map(|file| {
+                let check = ctx.working_file_checks.iter().find(|c| &c.file == file);
+                match check.map(|c| c.state) {
+                    Some(WorkingFileState::Missing) => {
+                        format!("{} {}", code(file), badge("red", "missing"))
+                    }
+                    Some(WorkingFileState::Unchanged) => {
+                        let label = match check.and_then(|c| c.commit.as_deref()) {
+                            Some(commit) => format!("committed in {}", commit),
+                            None => "unchanged".to_string(),
+                        };
+                        format!("{} {}", code(file), badge("grey", &label))
+                    }
+                    Some(WorkingFileState::NotInGit) => {
+                        format!("{} {}", code(file), badge("grey", "not in git"))
+                    }
+                    Some(WorkingFileState::Modified) | None => code(file),
+                }
+            })
+            .collect();
+        out.push_str(&format!(
+            "<li><b>Working files:</b> {}</li>\n",
+            files.join(", ")
+        ));
+    }
+    out.push_str("</ul>\n");
+    if !ws.next_steps.is_empty() {
+        out.push_str("<p><b>Next steps</b></p>\n<ol>\n");
+        for step in &ws.next_steps {
+            out.push_str(&format!("<li>{}</li>\n", escape(step)));
+        }
+        out.push_str("</ol>\n");
+    }
+    if !ws.todos.is_empty() {
+        out.push_str("<ul>\n");
+        for todo in &ws.todos {
+            let mark = match todo.status.as_str() {
+                "completed" => badge("green", "done"),
+                "in_progress" => badge("yellow", "in progress"),
+                _ => badge("grey", "pending"),
+            };
+            out.push_str(&format!("<li>{} {}</li>\n", mark, escape(&todo.content)));
+        }
+        out.push_str("</ul>\n");
+    }
+    let (shown, hidden) = code_todos_to_show(ctx.code_todos(), &ws.todos);
+    if !shown.is_empty() {
+        out.push_str("<p><b>Code TODOs</b></p>\n<ul>\n");
+        for todo in shown {
+            out.push_str(&format!(
+                "<li>{} {}</li>\n",
+                code(&format!("{}:{}", todo.file, todo.line)),
+                escape(&todo.text)
+            ));
+        }
+        if hidden > 0 {
+            out.push_str(&format!("<li class=\"muted\">{} more</li>\n", hidden));
+        }
+        out.push_str("</ul>\n");
+    }
+    Some(out)
+}
+
+fn hints_html(ctx: &Context) -> Option<String> {
+    if ctx.hints.is_empty() {
+        return None;
+    }
+    let items: String = ctx
+        .hints
+        .iter()
+        .map(|hint| format!("<li>{}</li>\n", escape(hint)))
+        .collect();
+    Some(format!("<ul>\n{}</ul>\n", items))
+}
+
+fn targets_html(ctx: &Context) -> Option<String> {
+    let remote = ctx.remote.as_ref();
+    let remote_targets = remote.map(|r| r.targets.as_slice()).unwrap_or_default();
+    if ctx.targets.is_empty() && remote_targets.is_empty() {
+        return None;
+    }
+    let local = ctx.targets.iter().map(|t| (t, None));
+    let remote_host = remote.map(|r| r.host.as_str());
+    let artifacts = ctx
+        .targets
+        .iter()
+        .chain(remote_targets)
+        .any(|t| !t.artifact_path.is_empty());
+    let rows: Vec<Vec<String>> = local
+        .chain(remote_targets.iter().map(|t| (t, remote_host)))
+        .map(|(target, host)| {
+            let issues: String = target
+                .issues
+                .iter()
+                .map(|issue| badge("red", issue))
+                .collect();
+            let mut row = vec![
+                located(&target.name, host),
+                escape(&target.description),
+                if target.exec_command.is_empty() {
+                    escape(&target.container_name)
+                } else {
+                    format!(
+                        "{}<br>{}",
+                        escape(&target.container_name),
+                        code(&target.exec_command)
+                    )
+                },
+                escape(&target.lunch_target),
+                format!("{}{}", escape(&target.source_file), issues),
+            ];
+            if artifacts {
+                let text = format_artifact(target, ctx.now(), ctx.language);
+                row.push(match &target.artifact {
+                    Some(artifact) if artifact.stale => badge("yellow", &text),
+                    Some(_) => badge("green", &text),
+                    None if text.is_empty() => String::new(),
+                    None => badge("grey", &text),
+                });
+            }
+            row
+        })
+        .collect();
+    let mut headers = vec!["Target", "Description", "Container", "Lunch", "Source"];
+    if artifacts {
+        headers.push("Artifacts");
+    }
+    Some(table(&headers, &rows))
+}
+
+fn containers_html(ctx: &Context) -> Option<String> {
+    let remote = ctx.remote.as_ref();
+    let remote_containers = remote.map(|r| r.containers.as_slice()).unwrap_or_default();
+    if ctx.containers.is_empty() && remote_containers.is_empty() {
+        return None;
+    }
+    let remote_host = remote.map(|r| r.host.as_str());
+    let rows: Vec<Vec<String>> = ctx
+        .containers
+        .iter()
+        .map(|c| (c, None))
+        .chain(remote_containers.iter().map(|c| (c, remote_host)))
+        .map(|(container, host)| {
+            vec![
+                container_badge(container),
+                located(&container.name, host),
+                escape(&container.status),
+                escape(&container.runtime),
+            ]
+        })
+        .collect();
+    Some(table(&["", "Name", "Status", "Runtime"], &rows))
+}
+
+fn git_html(ctx: &Context) -> Option<String> {
+    let remote = ctx.remote.as_ref();
+    let remote_repos = remote.map(|r| r.git_repos.as_slice()).unwrap_or_default();
+    if ctx.git_repos.is_empty() && remote_repos.is_empty() {
+        return None;
+    }
+    let remote_host = remote.map(|r| r.host.as_str());
+    let rows: Vec<Vec<String>> = ctx
+        .git_repos
+        .iter()
+        .map(|g| (g, None))
+        .chain(remote_repos.iter().map(|g| (g, remote_host)))
+        .map(|(git, host)| {
+            let mut reviews: Vec<String> = git
+                .pull_requests
+                .iter()
+                .map(|pr| format!("#{} {}", pr.number, escape(&pr.title)))
+                .collect();
+            reviews.extend(
+                git.changes
+                    .iter()
+                    .map(|change| format!("{} {}", change.number, escape(&change.subject))),
+            );
+            vec![
+                git_badge(git),
+                located(git.name(), host),
+                code(&git.branch),
+                escape(&format_git_status(git, ctx.language)),
+                code(&git.last_commit_short),
+                reviews.join("<br>"),
+            ]
+        })
+        .collect();
+    Some(table(
+        &[
+            "",
+            "Repository",
+            "Branch",
+            "Status",
+            "Last commit",
+            "Reviews",
+        ],
+        &rows,
+    ))
+}
+
+fn devices_html(ctx: &Context) -> Option<String> {
+    let mut out = String::new();
+    if let Some(selection) = &ctx.device_selection {
+        if let (Some(pinned), true) = (&selection.pinned, selection.pinned_missing()) {
+            out.push_str(&format!(
+                "<p>{} Pinned device {} is not connected</p>\n",
+                badge("red", "missing"),
+                code(pinned)
+            ));
+        }
+        if let Some(serial) = &selection.android_serial {
+            out.push_str(&format!(
+                "<p><code>ANDROID_SERIAL</code> is set to {}</p>\n",
+                code(serial)
+            ));
+        }
+    }
+    if ctx.adb_devices.is_empty() {
+        return (!out.is_empty()).then_some(out);
+    }
+    let rows: Vec<Vec<String>> = ctx
+        .adb_devices
+        .iter()
+        .map(|device| {
+            let color = match device.state.as_str() {
+                "device" | "fastboot" => "green",
+                "unauthorized" | "offline" => "red",
+                _ => "yellow",
+            };
+            let mut serial = code(&device.serial);
+            if device.is_default {
+                serial.push_str(&format!(" {}", badge("grey", "default")));
+            }
+            vec![
+                badge(color, &device.state),
+                serial,
+                escape(&device.device_type),
+                escape(&device.model),
+            ]
+        })
+        .collect();
+    out.push_str(&table(&["", "Serial", "Mode", "Model"], &rows));
+    Some(out)
+}
+
+fn terminals_html(ctx: &Context) -> Option<String> {
+    if ctx.terminal_panes.is_empty() && ctx.open_files.is_none() {
+        return None;
+    }
+    let mut out = String::new();
+    if let Some(open_files) = &ctx.open_files {
+        let rows: Vec<Vec<String>> = open_files
+            .files
+            .iter()
+            .map(|file| {
+                vec![
+                    code(&file.path),
+                    file.line.map(|line| line.to_string()).unwrap_or_default(),
+                    file.focused_at
+                        .as_deref()
+                        .map(|at| escape(&format_age(at, ctx.now(), ctx.language)))
+                        .unwrap_or_default(),
+                ]
+            })
+            .collect();
+        out.push_str(&table(&["Open in editor", "Line", "Focused"], &rows));
+    }
+    let rows: Vec<Vec<String>> = ctx
+        .terminal_panes
+        .iter()
+        .map(|pane| {
+            vec![
+                escape(&pane.session),
+                escape(&pane.window),
+                code(&pane.command),
+                code(&pane.path),
+            ]
+        })
+        .collect();
+    if !rows.is_empty() {
+        out.push_str(&table(&["Session", "Window", "Command", "Path"], &rows));
+    }
+    Some(out)
+}
+
+fn builds_html(ctx: &Context) -> Option<String> {
+    if ctx.build_processes.is_empty() && ctx.build_results.is_empty() {
+        return None;
+    }
+    let mut out = String::new();
+    let rows: Vec<Vec<String>> = ctx
+        .build_processes
+        .iter()
+        .map(|build| {
+            vec![
+                badge("yellow", &build.command),
+                escape(&format_elapsed(&build.started_at, ctx.now())),
+                escape(&format!("{:.0}%", build.cpu_percent)),
+                match &build.container {
+                    Some(container) => {
+                        format!("{} (container {})", code(&build.cwd), code(container))
+                    }
+                    None => code(&build.cwd),
+                },
+                code(&build.cmdline),
+            ]
+        })
+        .collect();
+    if !rows.is_empty() {
+        out.push_str(&table(
+            &["Build", "Running for", "CPU", "Directory", "Command"],
+            &rows,
+        ));
+    }
+    let rows: Vec<Vec<String>> = ctx
+        .build_results
+        .iter()
+        .map(|record| {
+            let result = if record.succeeded() {
+                badge("green", "succeeded")
+            } else {
+                badge("red", &format!("exit {}", record.exit_code))
+            };
+            let output = if record.output.is_empty() {
+                String::new()
+            } else {
+                format!("<pre>{}</pre>", escape(&record.output.join("\n")))
+            };
+            vec![
+                escape(record.label()),
+                result,
+                escape(&format_age(&record.timestamp, ctx.now(), ctx.language)),
+                escape(&format_duration_secs(record.duration_secs)),
+                code(&record.command) + &output,
+            ]
+        })
+        .collect();
+    if !rows.is_empty() {
+        out.push_str(&table(
+            &["Target", "Last build", "When", "Took", "Command"],
+            &rows,
+        ));
+    }
+    Some(out)
+}
+
+/// Filesystems with their free space, low ones flagged, then RAM and load
+fn resources_html(ctx: &Context) -> Option<String> {
+    let resources = ctx.resources.as_ref().filter(|r| !r.disks.is_empty())?;
+    let rows: Vec<Vec<String>> = resources
+        .disks
+        .iter()
+        .map(|disk| {
+            let free = match disk.free_bytes {
+                Some(free) if free < resources.min_free_bytes => badge("red", &format_size(free)),
+                Some(free) => badge("green", &format_size(free)),
+                None => badge("grey", "unknown"),
+            };
+            vec![
+                code(&disk.path),
+                code(&disk.mount),
+                free,
+                escape(&disk.total_bytes.map_or("?".to_string(), format_size)),
+            ]
+        })
+        .collect();
+    let mut out = table(&["Path", "Mount", "Free", "Total"], &rows);
+    let mut host = format!(
+        "RAM: {} available of {}",
+        format_size(resources.memory_available_bytes),
+        format_size(resources.memory_total_bytes)
+    );
+    if let Some([one, five, fifteen]) = resources.load_average {
+        host.push_str(&format!(
+            ", load {:.2} {:.2} {:.2} on {} CPUs",
+            one, five, fifteen, resources.cpus
+        ));
+    }
+    out.push_str(&format!("\n<p>{}</p>", escape(&host)));
+    Some(out)
+}
+
+/// Every collector's report, not only when one failed as in Markdown
+fn diagnostics_html(ctx: &Context) -> Option<String> {
+    if ctx.reports.is_empty() {
+        return None;
+    }
+    let rows: Vec<Vec<String>> = ctx
+        .reports
+        .iter()
+        .map(|report| {
+            vec![
+                status_badge(report.status),
+                code(report.name),
+                format!("{}ms", report.duration.as_millis()),
+                report.items.to_string(),
+                report.subprocesses.to_string(),
+                escape(&report.warnings.join("; ")),
+            ]
+        })
+        .collect();
+    Some(table(
+        &[
+            "Status",
+            "Collector",
+            "Duration",
+            "Items",
+            "Subprocesses",
+            "Warnings",
+        ],
+        &rows,
+    ))
+}
+
+/// A section's full-level Markdown without its heading, and the heading
+fn markdown_html(ctx: &Context, section: Section) -> Option<(String, String)> {
+    let markdown = format_section(ctx, section, Level::Full);
+    let markdown = markdown.trim();
+    if markdown.is_empty() {
+        return None;
+    }
+    let (title, body) = match markdown.split_once('\n') {
+        Some((first, rest)) if first.starts_with("## ") => (first[3..].to_string(), rest),
+        _ => (String::new(), markdown),
+    };
+    Some((title, format!("<pre>{}</pre>\n", escape(body.trim()))))
+}
+
+/// One `<details>` block per custom collector shown at full level
+fn custom_html(ctx: &Context) -> Vec<(String, String, String)> {
+    ctx.custom_sections
+        .iter()
+        .enumerate()
+        .filter(|(_, section)| section.shown_at(Level::Full))
+        .map(|(index, section)| {
+            let body = match &section.output {
+                CustomOutput::Text { text, truncated } => {
+                    let note = if *truncated {
+                        format!(
+                            "<p class=\"muted\">Output cut at {} bytes.</p>\n",
+                            MAX_CUSTOM_OUTPUT
+                        )
+                    } else {
+                        String::new()
+                    };
+                    format!("<pre>{}</pre>\n{}", escape(text), note)
+                }
+                CustomOutput::Json { value } => {
+                    let rows: Vec<Vec<String>> = json_rows(value)
+                        .iter()
+                        .map(|(key, value)| vec![code(key), escape(value)])
+                        .collect();
+                    table(&["Key", "Value"], &rows)
+                }
+            };
+            (format!("custom-{}", index), section.name.clone(), body)
+        })
+        .collect()
+}
+
+/// Id, heading and body of a section's blocks; none when it has nothing
+/// to show
+fn section_html(ctx: &Context, section: Section) -> Vec<(String, String, String)> {
+    let id = section.name().to_string();
+    let title = |label| ctx.language.text(label).to_string();
+    let (title, body) = match section {
+        Section::Custom => return custom_html(ctx),
+        Section::WorkState => (title(Label::SavedWorkState), work_state_html(ctx)),
+        Section::Hints => (title(Label::AiHints), hints_html(ctx)),
+        Section::Targets => (title(Label::BuildTargets), targets_html(ctx)),
+        Section::Containers => (title(Label::Containers), containers_html(ctx)),
+        Section::Git => (title(Label::GitStatus), git_html(ctx)),
+        Section::Devices => (title(Label::Devices), devices_html(ctx)),
+        Section::Terminals => (title(Label::TerminalSessions), terminals_html(ctx)),
+        Section::Builds => (title(Label::Builds), builds_html(ctx)),
+        Section::Resources => (title(Label::Resources), resources_html(ctx)),
+        Section::Diagnostics => (title(Label::Diagnostics), diagnostics_html(ctx)),
+        Section::Project
+        | Section::Yocto
+        | Section::Ros
+        | Section::Commands
+        | Section::History
+        | Section::Environment
+        | Section::Attention => match markdown_html(ctx, section) {
+            Some((title, body)) if title.is_empty() => (id.clone(), Some(body)),
+            Some((title, body)) => (title, Some(body)),
+            None => (String::new(), None),
+        },
+    };
+    body.map(|body| (id, title, body)).into_iter().collect()
+}
+
+/// Counts shown under the title: repos by state, unhealthy containers,
+/// collectors that didn't finish
+fn summary_html(ctx: &Context) -> String {
+    let repos: Vec<&GitInfo> = ctx
+        .git_repos
+        .iter()
+        .chain(ctx.remote.iter().flat_map(|r| r.git_repos.iter()))
+        .collect();
+    let conflicted = repos.iter().filter(|g| g.conflicted_files > 0).count();
+    let dirty = repos
+        .iter()
+        .filter(|g| g.conflicted_files == 0 && g.is_dirty)
+        .count();
+    let clean = repos
+        .iter()
+        .filter(|g| g.status_unavailable.is_none() && !g.is_dirty && g.conflicted_files == 0)
+        .count();
+    let unhealthy = ctx.containers.iter().filter(|c| c.is_unhealthy()).count();
+    let unfinished = ctx
+        .reports
+        .iter()
+        .filter(|r| r.status != CollectorStatus::Ok)
+        .count();
+
+    let mut out = String::new();
+    if conflicted > 0 {
+        out.push_str(&badge("red", &format!("{} with conflicts", conflicted)));
+    }
+    if dirty > 0 {
+        out.push_str(&badge("yellow", &format!("{} dirty", dirty)));
+    }
+    if clean > 0 {
+        out.push_str(&badge("green", &format!("{} clean", clean)));
+    }
+    if unhealthy > 0 {
+        out.push_str(&badge(
+            "red",
+            &format!("{} unhealthy containers", unhealthy),
+        ));
+    }
+    if unfinished > 0 {
+        out.push_str(&badge(
+            "yellow",
+            &format!("{} collectors incomplete", unfinished),
+        ));
+    }
+    out
+}
+
+/// The whole context as one HTML page, with every full-level section
+pub fn format_html(ctx: &Context) -> String {
+    let mut sections = String::new();
+    for section in level_sections(Level::Full) {
+        for (id, title, body) in section_html(ctx, *section) {
+            sections.push_str(&format!(
+                "<details open id=\"{}\">\n<summary>{}</summary>\n{}</details>\n",
+                id,
+                escape(&title),
+                body
+            ));
+        }
+    }
+
+    let generated = ctx
+        .now()
+        .with_timezone(&chrono::Local)
+        .format("%Y-%m-%d %H:%M %:z")
+        .to_string();
+    let updated = ctx.language.text(Label::Updated);
+    let meta = if ctx.project_type.is_empty() {
+        format!("{} {}", updated, generated)
+    } else {
+        format!(
+            "{} · {} {}",
+            ctx.project_type,
+            updated.to_lowercase(),
+            generated
+        )
+    };
+    let title = format!("{} — context-keeper", ctx.project_name);
+    render_template(
+        DASHBOARD_TEMPLATE,
+        &[
+            ("lang", ctx.language.code()),
+            ("title", &escape(&title)),
+            ("meta", &escape(&meta)),
+            ("summary", &summary_html(ctx)),
+            ("sections", &sections),
+        ],
+    )
+}
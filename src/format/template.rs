@@ -0,0 +1,50 @@
+//! `context --template`: the context through a user's Jinja template
+//!
+//! The template sees the fields of the context JSON (`context --json`) as
+//! its variables, redacted the same way: `{{ project_name }}`,
+//! `{% for repo in git_repos %}`, `{{ work_state.task_summary }}`. It is
+//! rendered with minijinja, so the syntax is Jinja2's. Without the
+//! `templates` feature it is an error.
+
+use crate::context::Context;
+use crate::redact::Redactor;
+
+/// `source` rendered against `context`; `name` (the file) is what errors
+/// point at
+pub fn render_template(
+    name: &str,
+    source: &str,
+    context: &Context,
+    redactor: &Redactor,
+) -> Result<String, String> {
+    let mut value = serde_json::to_value(context).map_err(|e| e.to_string())?;
+    redactor.redact_json(&mut value);
+    render(name, source, value)
+}
+
+#[cfg(feature = "templates")]
+fn render(name: &str, source: &str, value: serde_json::Value) -> Result<String, String> {
+    let mut env = minijinja::Environment::new();
+    // Missing fields render empty, like an empty section
+    env.set_undefined_behavior(minijinja::UndefinedBehavior::Lenient);
+    env.set_keep_trailing_newline(true);
+    let describe = |e: minijinja::Error| match e.line() {
+        Some(line) => format!(
+            "{}:{}: {}",
+            name,
+            line,
+            e.detail().unwrap_or(&e.to_string())
+        ),
+        None => format!("{}: {}", name, e),
+    };
+    env.add_template(name, source).map_err(describe)?;
+    let template = env.get_template(name).map_err(describe)?;
+    template
+        .render(minijinja::Value::from_serialize(&value))
+        .map_err(describe)
+}
+
+#[cfg(not(feature = "templates"))]
+fn render(_name: &str, _source: &str, _value: serde_json::Value) -> Result<String, String> {
+    Err(crate::collectors::not_built("templates"))
+}
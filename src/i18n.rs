@@ -0,0 +1,368 @@
+//! Section headings and fixed labels in the output's language
+//!
+//! Formatters look each heading and label up with `Language::text`; what
+//! was collected (names, statuses, commit subjects, hints) is shown as it
+//! came. A language is a `Language` variant and a table like `japanese`:
+//! a `Label` its table leaves out is shown in English. Labels with a count
+//! hold `{n}` and are filled in with `Language::count`.
+
+use crate::config::Config;
+use serde::Deserialize;
+
+/// `[output] language`, or `--lang`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    En,
+    Ja,
+}
+
+/// A heading or fixed label of the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Label {
+    // Titles, one per level
+    ContextRecoveryMinimal,
+    DevelopmentContext,
+    DevelopmentContextFull,
+
+    // Section headings
+    Project,
+    SavedWorkState,
+    BuildConfiguration,
+    AiHints,
+    AiHintsImportant,
+    AvailableBuildTargets,
+    RunningInContainers,
+    TargetCapabilities,
+    ActiveContainers,
+    RosWorkspace,
+    ExampleCommands,
+    Usage,
+    JustRecipes,
+    Workspace,
+    RecentCommands,
+    GitStatus,
+    GitChangesOnly,
+    ConnectedDevices,
+    Resources,
+    Environment,
+    Attention,
+    TerminalSessions,
+    Diagnostics,
+    NothingToReport,
+    GettingStarted,
+
+    // Labels
+    SavedAt,
+    Task,
+    Metadata,
+    WorkingFiles,
+    Files,
+    Churn,
+    Notes,
+    OpenInEditor,
+    RunningInTmux,
+    NextSteps,
+    Next,
+    Todos,
+    CodeTodos,
+    Hint,
+    Hints,
+    Name,
+    Type,
+    Detected,
+    LastBuild,
+    BuildDir,
+    Layers,
+    Path,
+    Built,
+    Packages,
+    OpenPullRequests,
+    OpenGerritChanges,
+    PullRequests,
+    ChangedRepos,
+    RecentlyModified,
+    Device,
+    Editor,
+    PartialContext,
+    Unavailable,
+    Clock,
+    Capped,
+    Ignored,
+    OutputCapped,
+    Updated,
+    LastActivity,
+
+    // Sections in the one-line stubs and the dashboard
+    WorkStateTitle,
+    BuildTargets,
+    BuildTargetsTitle,
+    Containers,
+    Yocto,
+    RosWorkspaceTitle,
+    Commands,
+    CommandHistory,
+    Git,
+    Devices,
+    TerminalSessionsTitle,
+    Builds,
+    CapturedEnvironment,
+
+    // Stub states
+    StubUnavailable,
+    StubTurnedOff,
+    StubTimedOut,
+
+    // Words in git status
+    Clean,
+
+    // Ages, as in "2h ago"
+    JustNow,
+    MinutesAgo,
+    HoursAgo,
+    DaysAgo,
+
+    // Time since the last activity, and what it was
+    IdleUnderAMinute,
+    IdleMinute,
+    IdleMinutes,
+    IdleHour,
+    IdleHours,
+    IdleDay,
+    IdleDays,
+    WorkStateSaved,
+    CommandLogged,
+}
+
+impl Language {
+    /// BCP 47 tag, for the dashboard's `<html lang>`
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::En => "en",
+            Language::Ja => "ja",
+        }
+    }
+
+    /// `label` in this language, else in English
+    pub fn text(self, label: Label) -> &'static str {
+        let translated = match self {
+            Language::En => None,
+            Language::Ja => japanese(label),
+        };
+        translated.unwrap_or_else(|| english(label))
+    }
+
+    /// `label` in this language with `n` in place of its `{n}`
+    pub fn count(self, label: Label, n: i64) -> String {
+        self.text(label).replace("{n}", &n.to_string())
+    }
+}
+
+/// `[output] language`, English without one
+pub fn configured_language(config: &Config) -> Language {
+    config
+        .output
+        .as_ref()
+        .and_then(|output| output.language)
+        .unwrap_or_default()
+}
+
+fn english(label: Label) -> &'static str {
+    match label {
+        Label::ContextRecoveryMinimal => "Context Recovery (Minimal)",
+        Label::DevelopmentContext => "Development Context",
+        Label::DevelopmentContextFull => "Development Context (Full)",
+        Label::Project => "Project",
+        Label::SavedWorkState => "Saved Work State",
+        Label::BuildConfiguration => "Build Configuration",
+        Label::AiHints => "AI Hints",
+        Label::AiHintsImportant => "AI Hints (Important)",
+        Label::AvailableBuildTargets => "Available Build Targets",
+        Label::RunningInContainers => "Running in Containers",
+        Label::TargetCapabilities => "Target Capabilities",
+        Label::ActiveContainers => "Active Containers",
+        Label::RosWorkspace => "ROS Workspace",
+        Label::ExampleCommands => "Example Commands",
+        Label::Usage => "Usage",
+        Label::JustRecipes => "Just Recipes",
+        Label::Workspace => "Workspace",
+        Label::RecentCommands => "Recent Relevant Commands",
+        Label::GitStatus => "Git Status",
+        Label::GitChangesOnly => "Git Status (changes only)",
+        Label::ConnectedDevices => "Connected Devices",
+        Label::Resources => "Resources",
+        Label::Environment => "Environment",
+        Label::Attention => "Attention",
+        Label::TerminalSessions => "Terminal Sessions",
+        Label::Diagnostics => "Diagnostics",
+        Label::NothingToReport => "Nothing to Report",
+        Label::GettingStarted => "Getting Started",
+        Label::SavedAt => "Saved at",
+        Label::Task => "Task",
+        Label::Metadata => "Metadata",
+        Label::WorkingFiles => "Working files",
+        Label::Files => "Files",
+        Label::Churn => "Churn",
+        Label::Notes => "Notes",
+        Label::OpenInEditor => "Open in editor",
+        Label::RunningInTmux => "Running in tmux",
+        Label::NextSteps => "Next steps",
+        Label::Next => "Next",
+        Label::Todos => "Todos",
+        Label::CodeTodos => "Code TODOs",
+        Label::Hint => "Hint",
+        Label::Hints => "Hints",
+        Label::Name => "Name",
+        Label::Type => "Type",
+        Label::Detected => "Detected",
+        Label::LastBuild => "Last build",
+        Label::BuildDir => "Build dir",
+        Label::Layers => "Layers",
+        Label::Path => "Path",
+        Label::Built => "Built",
+        Label::Packages => "Packages",
+        Label::OpenPullRequests => "Open pull requests",
+        Label::OpenGerritChanges => "Open Gerrit changes",
+        Label::PullRequests => "Pull requests",
+        Label::ChangedRepos => "Changed repos",
+        Label::RecentlyModified => "Recently Modified (untracked)",
+        Label::Device => "Device",
+        Label::Editor => "Editor",
+        Label::PartialContext => "Partial context",
+        Label::Unavailable => "Unavailable",
+        Label::Clock => "Clock",
+        Label::Capped => "Capped",
+        Label::Ignored => "Ignored",
+        Label::OutputCapped => "Output capped",
+        Label::Updated => "Updated",
+        Label::LastActivity => "Last activity",
+        Label::WorkStateTitle => "Work state",
+        Label::BuildTargets => "Build Targets",
+        Label::BuildTargetsTitle => "Build targets",
+        Label::Containers => "Containers",
+        Label::Yocto => "Yocto",
+        Label::RosWorkspaceTitle => "ROS workspace",
+        Label::Commands => "Commands",
+        Label::CommandHistory => "Command history",
+        Label::Git => "Git",
+        Label::Devices => "Devices",
+        Label::TerminalSessionsTitle => "Terminal sessions",
+        Label::Builds => "Builds",
+        Label::CapturedEnvironment => "Captured environment",
+        Label::StubUnavailable => "unavailable",
+        Label::StubTurnedOff => "turned off in the config",
+        Label::StubTimedOut => "timed out, not collected",
+        Label::Clean => "clean",
+        Label::JustNow => "just now",
+        Label::MinutesAgo => "{n}m ago",
+        Label::HoursAgo => "{n}h ago",
+        Label::DaysAgo => "{n}d ago",
+        Label::IdleUnderAMinute => "under a minute ago",
+        Label::IdleMinute => "~1 minute ago",
+        Label::IdleMinutes => "~{n} minutes ago",
+        Label::IdleHour => "~1 hour ago",
+        Label::IdleHours => "~{n} hours ago",
+        Label::IdleDay => "~1 day ago",
+        Label::IdleDays => "~{n} days ago",
+        Label::WorkStateSaved => "work state saved",
+        Label::CommandLogged => "command logged",
+    }
+}
+
+fn japanese(label: Label) -> Option<&'static str> {
+    Some(match label {
+        Label::ContextRecoveryMinimal => "コンテキストの復元（最小）",
+        Label::DevelopmentContext => "開発コンテキスト",
+        Label::DevelopmentContextFull => "開発コンテキスト（完全）",
+        Label::Project => "プロジェクト",
+        Label::SavedWorkState => "保存された作業状態",
+        Label::BuildConfiguration => "ビルド設定",
+        Label::AiHints => "AI へのヒント",
+        Label::AiHintsImportant => "AI へのヒント（重要）",
+        Label::AvailableBuildTargets => "ビルドターゲット",
+        Label::RunningInContainers => "コンテナ内で実行",
+        Label::TargetCapabilities => "ターゲットの機能",
+        Label::ActiveContainers => "稼働中のコンテナ",
+        Label::RosWorkspace => "ROS ワークスペース",
+        Label::ExampleCommands => "コマンド例",
+        Label::Usage => "使い方",
+        Label::JustRecipes => "Just レシピ",
+        Label::Workspace => "ワークスペース",
+        Label::RecentCommands => "最近の関連コマンド",
+        Label::GitStatus => "Git の状態",
+        Label::GitChangesOnly => "Git の状態（変更のみ）",
+        Label::ConnectedDevices => "接続中のデバイス",
+        Label::Resources => "リソース",
+        Label::Environment => "環境",
+        Label::Attention => "要確認",
+        Label::TerminalSessions => "ターミナルセッション",
+        Label::Diagnostics => "診断",
+        Label::NothingToReport => "報告事項なし",
+        Label::GettingStarted => "はじめに",
+        Label::SavedAt => "保存日時",
+        Label::Task => "タスク",
+        Label::Metadata => "メタデータ",
+        Label::WorkingFiles => "作業中のファイル",
+        Label::Files => "ファイル",
+        Label::Churn => "変更量",
+        Label::Notes => "メモ",
+        Label::OpenInEditor => "エディタで開いているファイル",
+        Label::RunningInTmux => "tmux で実行中",
+        Label::NextSteps => "次のステップ",
+        Label::Next => "次",
+        Label::Todos => "ToDo",
+        Label::CodeTodos => "コード内の TODO",
+        Label::Hint => "ヒント",
+        Label::Hints => "ヒント",
+        Label::Name => "名前",
+        Label::Type => "種類",
+        Label::Detected => "検出",
+        Label::LastBuild => "前回のビルド",
+        Label::BuildDir => "ビルドディレクトリ",
+        Label::Layers => "レイヤー",
+        Label::Path => "パス",
+        Label::Built => "ビルド",
+        Label::Packages => "パッケージ",
+        Label::OpenPullRequests => "オープン中のプルリクエスト",
+        Label::OpenGerritChanges => "オープン中の Gerrit 変更",
+        Label::PullRequests => "プルリクエスト",
+        Label::ChangedRepos => "変更のあるリポジトリ",
+        Label::RecentlyModified => "最近変更されたファイル（git 管理外）",
+        Label::Device => "デバイス",
+        Label::Editor => "エディタ",
+        Label::PartialContext => "一部のみ",
+        Label::Unavailable => "利用不可",
+        Label::Clock => "時計",
+        Label::Ignored => "除外",
+        Label::OutputCapped => "出力を縮小",
+        Label::Updated => "更新",
+        Label::LastActivity => "最終作業",
+        Label::WorkStateTitle => "作業状態",
+        Label::BuildTargets | Label::BuildTargetsTitle => "ビルドターゲット",
+        Label::Containers => "コンテナ",
+        Label::RosWorkspaceTitle => "ROS ワークスペース",
+        Label::Commands => "コマンド",
+        Label::CommandHistory => "コマンド履歴",
+        Label::Devices => "デバイス",
+        Label::TerminalSessionsTitle => "ターミナルセッション",
+        Label::Builds => "ビルド",
+        Label::CapturedEnvironment => "記録された環境",
+        Label::StubUnavailable => "利用不可",
+        Label::StubTurnedOff => "設定で無効",
+        Label::StubTimedOut => "タイムアウトのため未収集",
+        Label::Clean => "変更なし",
+        Label::JustNow => "たった今",
+        Label::MinutesAgo => "{n}分前",
+        Label::HoursAgo => "{n}時間前",
+        Label::DaysAgo => "{n}日前",
+        Label::IdleUnderAMinute => "1分以内",
+        Label::IdleMinute | Label::IdleMinutes => "約{n}分前",
+        Label::IdleHour | Label::IdleHours => "約{n}時間前",
+        Label::IdleDay | Label::IdleDays => "約{n}日前",
+        Label::WorkStateSaved => "作業状態を保存",
+        Label::CommandLogged => "コマンドを記録",
+        // Same in both, or not translated yet
+        Label::Yocto | Label::Git | Label::Capped => return None,
+    })
+}
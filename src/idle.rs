@@ -0,0 +1,127 @@
+//! How long the project has been left alone
+//!
+//! Coming back after a weekend needs other hints than resuming after a
+//! compaction. The last activity is the newest of three signals the
+//! context collected anyway: the saved work state, the last logged
+//! command, and the last time git wrote to a repository's index, HEAD or
+//! reflog (read before `git status` refreshes the index). Without any of
+//! them there is no idle time, rather than one measured from the epoch.
+//! It drives `[hints.when.idle_over]`, the "Last activity" line under the
+//! title, and the staleness bonus in the attention list.
+
+use crate::config::AttentionKind;
+use crate::context::Context;
+use crate::i18n::{Label, Language};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Where the last activity was seen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivitySource {
+    WorkState,
+    History,
+    Git,
+}
+
+impl ActivitySource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActivitySource::WorkState => "work state saved",
+            ActivitySource::History => "command logged",
+            ActivitySource::Git => "git",
+        }
+    }
+
+    /// `as_str` in `language`
+    pub fn text(&self, language: Language) -> &'static str {
+        match self {
+            ActivitySource::WorkState => language.text(Label::WorkStateSaved),
+            ActivitySource::History => language.text(Label::CommandLogged),
+            ActivitySource::Git => "git",
+        }
+    }
+}
+
+/// The newest activity signal in a context
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LastActivity {
+    /// RFC 3339
+    pub at: String,
+    pub source: ActivitySource,
+}
+
+/// Attention added per idle day to the staleness kinds, up to
+/// `MAX_IDLE_BONUS_DAYS` days
+pub const IDLE_BONUS_PER_DAY: i64 = 10;
+pub const MAX_IDLE_BONUS_DAYS: i64 = 6;
+
+fn parse(timestamp: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+        // A zero or missing time read as a date
+        .filter(|time| time.timestamp() > 0)
+}
+
+/// The newest of the signals in `ctx`, `None` without any
+pub fn last_activity(ctx: &Context) -> Option<LastActivity> {
+    let saved = ctx
+        .work_state
+        .iter()
+        .map(|state| (state.saved_at.as_str(), ActivitySource::WorkState));
+    let logged = ctx
+        .command_history
+        .iter()
+        .map(|entry| (entry.timestamp.as_str(), ActivitySource::History));
+    let git = ctx
+        .git_repos
+        .iter()
+        .filter_map(|repo| repo.last_activity.as_deref())
+        .map(|at| (at, ActivitySource::Git));
+    saved
+        .chain(logged)
+        .chain(git)
+        .filter_map(|(at, source)| Some((parse(at)?, at, source)))
+        .max_by_key(|(time, _, _)| *time)
+        .map(|(_, at, source)| LastActivity {
+            at: at.to_string(),
+            source,
+        })
+}
+
+/// Time since `ctx.last_activity`, never negative; `None` without one
+pub fn idle_duration(ctx: &Context) -> Option<Duration> {
+    let at = parse(&ctx.last_activity.as_ref()?.at)?;
+    Some((ctx.now() - at).max(Duration::zero()))
+}
+
+/// "~3 days ago", "~5 hours ago", "~10 minutes ago" in `language`
+pub fn format_idle(idle: Duration, language: Language) -> String {
+    let plural =
+        |n: i64, one: Label, many: Label| language.count(if n == 1 { one } else { many }, n);
+    match idle.num_minutes() {
+        m if m < 1 => language.text(Label::IdleUnderAMinute).to_string(),
+        m if m < 60 => plural(m, Label::IdleMinute, Label::IdleMinutes),
+        m if m < 60 * 48 => plural(m / 60, Label::IdleHour, Label::IdleHours),
+        m => plural(m / (60 * 24), Label::IdleDay, Label::IdleDays),
+    }
+}
+
+/// Whether `kind` says something may have gone stale while away
+pub fn is_staleness(kind: AttentionKind) -> bool {
+    matches!(
+        kind,
+        AttentionKind::StaleWorkState
+            | AttentionKind::StaleArtifact
+            | AttentionKind::BehindUpstream
+    )
+}
+
+/// What a staleness item gains after `idle`: `IDLE_BONUS_PER_DAY` per
+/// whole day, at most `MAX_IDLE_BONUS_DAYS` of them
+pub fn idle_bonus(idle: Option<Duration>) -> i64 {
+    idle.map_or(0, |idle| {
+        idle.num_days().clamp(0, MAX_IDLE_BONUS_DAYS) * IDLE_BONUS_PER_DAY
+    })
+}
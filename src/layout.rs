@@ -0,0 +1,150 @@
+//! Layout of `~/.contextkeeper`
+//!
+//! The first layout kept one `work-state.json` for every project and the
+//! command history log at the top of the directory. Layout 2 keeps the
+//! work state with the rest of the project's state in
+//! `projects/<root-hash>/`, the history log in `logs/`, and records
+//! itself in `layout_version`. A directory without the marker and with a
+//! file of the first layout stays on the first layout until `context-keeper
+//! migrate` moves it; an empty one starts on the current layout. Either
+//! way every reader and writer uses one layout, never some of each.
+
+use crate::error::ContextKeeperError;
+use crate::sessions::write_atomic;
+use crate::state::{contextkeeper_dir, ensure_contextkeeper_dir};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Layout this build reads and writes
+pub const LAYOUT_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataLayout {
+    /// The first layout, not migrated yet
+    Legacy,
+    /// `LAYOUT_VERSION`
+    Current,
+}
+
+/// `~/.contextkeeper/layout_version`
+pub fn layout_marker_path() -> PathBuf {
+    contextkeeper_dir().join("layout_version")
+}
+
+/// The work state of every project, in the first layout
+pub fn legacy_work_state_path() -> PathBuf {
+    contextkeeper_dir().join("work-state.json")
+}
+
+/// The history log in the first layout
+pub fn legacy_history_log_path() -> PathBuf {
+    contextkeeper_dir().join("command-history.jsonl")
+}
+
+/// The history log in the current layout
+pub fn current_history_log_path() -> PathBuf {
+    contextkeeper_dir()
+        .join("logs")
+        .join("command-history.jsonl")
+}
+
+/// Files of the first layout that are still there
+pub fn legacy_files() -> Vec<PathBuf> {
+    [legacy_work_state_path(), legacy_history_log_path()]
+        .into_iter()
+        .filter(|path| path.exists())
+        .collect()
+}
+
+/// The version `layout_version` holds, `None` without one
+pub fn read_layout_marker() -> Result<Option<u32>, ContextKeeperError> {
+    let path = layout_marker_path();
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(ContextKeeperError::state_io(&path, e)),
+    };
+    content.trim().parse().map(Some).map_err(|_| {
+        ContextKeeperError::state_io(
+            &path,
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{:?} is not a layout version", content.trim()),
+            ),
+        )
+    })
+}
+
+/// The layout `~/.contextkeeper` is on. An error for a marker this build
+/// can't read, such as one a newer version wrote: nothing should be
+/// written to a layout it doesn't know.
+pub fn data_layout() -> Result<DataLayout, ContextKeeperError> {
+    match read_layout_marker()? {
+        Some(version) if version > LAYOUT_VERSION => Err(ContextKeeperError::state_io(
+            &layout_marker_path(),
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "layout {} is newer than this context-keeper understands ({}); upgrade it",
+                    version, LAYOUT_VERSION
+                ),
+            ),
+        )),
+        Some(LAYOUT_VERSION) => Ok(DataLayout::Current),
+        Some(_) => Ok(DataLayout::Legacy),
+        None if legacy_files().is_empty() => Ok(DataLayout::Current),
+        None => Ok(DataLayout::Legacy),
+    }
+}
+
+/// Write the `layout_version` marker
+pub fn write_layout_marker() -> Result<(), ContextKeeperError> {
+    let path = layout_marker_path();
+    ensure_contextkeeper_dir()
+        .and_then(|_| write_atomic(&path, format!("{}\n", LAYOUT_VERSION).as_bytes()))
+        .map_err(|e| ContextKeeperError::state_io(&path, e))
+}
+
+/// `data_layout`, with the marker written when the directory is on the
+/// current layout without one yet, so a later file of the first layout
+/// can't pull it back. Writers call this before writing.
+pub fn pinned_data_layout() -> Result<DataLayout, ContextKeeperError> {
+    let layout = data_layout()?;
+    if layout == DataLayout::Current && !layout_marker_path().exists() {
+        write_layout_marker()?;
+    }
+    Ok(layout)
+}
+
+/// Startup check: pin the layout and warn about a directory that still
+/// needs `migrate`, or that holds files of the first layout it no longer
+/// reads
+pub fn check_data_layout() -> Result<DataLayout, ContextKeeperError> {
+    let layout = pinned_data_layout()?;
+    match layout {
+        DataLayout::Legacy => tracing::warn!(
+            dir = %contextkeeper_dir().display(),
+            "~/.contextkeeper uses the old layout; run `context-keeper migrate` to move it"
+        ),
+        DataLayout::Current => {
+            for path in legacy_files() {
+                tracing::warn!(
+                    path = %path.display(),
+                    "left over from the old layout and no longer read; run `context-keeper migrate`"
+                );
+            }
+        }
+    }
+    Ok(layout)
+}
+
+/// The history log hooks write by default, on the current layout or the
+/// first one. A layout that can't be read counts as the current one; the
+/// writers reject it on their own.
+pub fn default_history_log_path() -> PathBuf {
+    match data_layout() {
+        Ok(DataLayout::Legacy) => legacy_history_log_path(),
+        _ => current_history_log_path(),
+    }
+}
@@ -0,0 +1,38 @@
+//! ContextKeeper: development context for AI agents.
+//!
+//! Collectors read the project's build configuration, containers, git
+//! repositories, devices and shell history. [`collect_context`] runs them
+//! with a deadline, and [`format_context`] renders the result as Markdown at
+//! a chosen detail level. The `context-keeper` binary wraps this in the CLI
+//! and the MCP server ([`server::ContextKeeperService`]).
+
+pub mod attention;
+pub mod client_log;
+pub mod clock;
+pub mod collectors;
+pub mod commands;
+pub mod config;
+pub mod context;
+pub mod demo;
+pub mod diary;
+pub mod error;
+pub mod format;
+pub mod i18n;
+pub mod idle;
+pub mod layout;
+pub mod limits;
+pub mod output;
+pub mod paths;
+pub mod redact;
+pub mod root_drift;
+pub mod server;
+pub mod sessions;
+pub mod state;
+pub mod stats;
+pub mod summary;
+
+pub use config::{Config, ProjectContext};
+pub use context::{collect_context, CollectionPlan, Context};
+pub use error::ContextKeeperError;
+pub use format::{format_context, Level};
+pub use state::WorkState;
@@ -0,0 +1,156 @@
+//! `[limits]`: caps on pathological inputs
+//!
+//! A `.conf` directory with thousands of files, a repository with tens of
+//! thousands of changes, a multi-megabyte history line or a work state
+//! with thousands of pasted todos would otherwise stall collection or
+//! grow memory without bound. Each is cut at its cap where it is parsed.
+//! What went over becomes a summary ("…and 4,820 more changed paths")
+//! in the collector's report, shown in the context, and a warning.
+
+use crate::config::Config;
+use std::io::{self, BufRead};
+
+pub const DEFAULT_MAX_CONFIG_FILES: usize = 1000;
+pub const DEFAULT_MAX_STATUS_LINES: usize = 10_000;
+pub const DEFAULT_MAX_LINE_BYTES: usize = 64 * 1024;
+pub const DEFAULT_MAX_TODOS: usize = 500;
+
+/// `[limits]` with the defaults filled in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub max_config_files: usize,
+    pub max_status_lines: usize,
+    pub max_line_bytes: usize,
+    pub max_todos: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_config_files: DEFAULT_MAX_CONFIG_FILES,
+            max_status_lines: DEFAULT_MAX_STATUS_LINES,
+            max_line_bytes: DEFAULT_MAX_LINE_BYTES,
+            max_todos: DEFAULT_MAX_TODOS,
+        }
+    }
+}
+
+impl Limits {
+    pub fn from_config(config: &Config) -> Self {
+        let limits = config.limits.as_ref();
+        let defaults = Limits::default();
+        Limits {
+            max_config_files: limits
+                .and_then(|l| l.max_config_files)
+                .unwrap_or(defaults.max_config_files),
+            max_status_lines: limits
+                .and_then(|l| l.max_status_lines)
+                .unwrap_or(defaults.max_status_lines),
+            max_line_bytes: limits
+                .and_then(|l| l.max_line_bytes)
+                .unwrap_or(defaults.max_line_bytes),
+            max_todos: limits
+                .and_then(|l| l.max_todos)
+                .unwrap_or(defaults.max_todos),
+        }
+    }
+}
+
+/// `n` with thousands separators: "4,820"
+pub fn format_count(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    out
+}
+
+/// "…and 4,820 more changed paths", for `more` items left out at the cap
+/// `limit`, which names its `[limits]` key; `one` and `many` are the item
+/// in the singular and the plural
+pub fn overflow_summary(more: usize, one: &str, many: &str, limit: &str) -> String {
+    format!(
+        "…and {} more {} (over [limits] {})",
+        format_count(more),
+        if more == 1 { one } else { many },
+        limit
+    )
+}
+
+/// One line from `bounded_lines`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoundedLine {
+    /// Without its line ending
+    Line(String),
+    /// Longer than the cap, with its length; read past, not kept
+    TooLong(usize),
+}
+
+/// Lines of a reader, never holding more than `max_bytes` of one
+#[derive(Debug)]
+pub struct BoundedLines<R> {
+    reader: R,
+    max_bytes: usize,
+}
+
+/// The lines of `reader` as `BufRead::lines` gives them, except that a
+/// line longer than `max_bytes` comes as `TooLong`. Stops at the first
+/// read error, like `lines().map_while(Result::ok)`; invalid UTF-8 is
+/// replaced.
+pub fn bounded_lines<R: BufRead>(reader: R, max_bytes: usize) -> BoundedLines<R> {
+    BoundedLines { reader, max_bytes }
+}
+
+impl<R: BufRead> BoundedLines<R> {
+    fn read_line(&mut self) -> io::Result<Option<BoundedLine>> {
+        let mut line = Vec::new();
+        let mut length = 0;
+        loop {
+            let buffer = self.reader.fill_buf()?;
+            if buffer.is_empty() {
+                break;
+            }
+            let (chunk, done) = match buffer.iter().position(|&b| b == b'\n') {
+                Some(end) => (&buffer[..end], Some(end + 1)),
+                None => (buffer, None),
+            };
+            length += chunk.len();
+            if length <= self.max_bytes {
+                line.extend_from_slice(chunk);
+            } else if !line.is_empty() {
+                line = Vec::new();
+            }
+            let consumed = done.unwrap_or(chunk.len());
+            self.reader.consume(consumed);
+            if done.is_some() {
+                return Ok(Some(self.finish(line, length)));
+            }
+        }
+        if length == 0 {
+            return Ok(None);
+        }
+        Ok(Some(self.finish(line, length)))
+    }
+
+    fn finish(&self, mut line: Vec<u8>, length: usize) -> BoundedLine {
+        if length > self.max_bytes {
+            return BoundedLine::TooLong(length);
+        }
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        BoundedLine::Line(String::from_utf8_lossy(&line).into_owned())
+    }
+}
+
+impl<R: BufRead> Iterator for BoundedLines<R> {
+    type Item = BoundedLine;
+
+    fn next(&mut self) -> Option<BoundedLine> {
+        self.read_line().ok().flatten()
+    }
+}
@@ -1,1596 +1,1208 @@
-use regex::Regex;
-use rmcp::{
-    handler::server::{tool::ToolRouter, wrapper::Parameters},
-    model::*,
-    tool, tool_handler, tool_router,
-    transport::stdio,
-    ErrorData as McpError, ServerHandler, ServiceExt,
+use context_keeper::client_log::client_log_layer;
+use context_keeper::collectors::code_todos::collect_code_todos;
+use context_keeper::collectors::diff_summary::collect_diff_summary;
+use context_keeper::collectors::open_files::open_files_now;
+use context_keeper::collectors::targets::collect_build_targets;
+use context_keeper::collectors::tmux::collect_tmux_panes;
+use context_keeper::collectors::validation::validate_targets;
+use context_keeper::commands::build_wrap::run_build_wrap;
+use context_keeper::commands::doctor::run_doctor;
+use context_keeper::commands::env::{run_env, EnvOptions};
+use context_keeper::commands::export::{run_export, ExportAction};
+use context_keeper::commands::git_hooks::{run_git_hooks, GitHooksAction};
+use context_keeper::commands::hooks::{run_hooks, HooksAction};
+use context_keeper::commands::init::{run_init_wizard, InitOptions};
+use context_keeper::commands::listing::{run_repos, run_targets};
+use context_keeper::commands::log::run_log;
+use context_keeper::commands::migrate::{run_migrate, MigrateOptions};
+use context_keeper::commands::open_files::{run_open_files, OpenFilesAction};
+use context_keeper::commands::prompt::{prompt_root, run_prompt};
+use context_keeper::commands::safe_write::WriteArgs;
+use context_keeper::commands::status::run_status;
+use context_keeper::commands::statusline::run_statusline;
+#[cfg(feature = "watch")]
+use context_keeper::commands::watch::{run_watch, WatchOptions};
+use context_keeper::config::{find_config_file, OneOrMany, CONFIG_FILE_NAMES};
+use context_keeper::context::{
+    annotate_context, needs_annotation, time_collectors, tool_deadline, CollectionPlan,
+    CollectorReport, CollectorStatus,
+};
+use context_keeper::demo::{demo_context, DEMO_ENV};
+use context_keeper::diary::{parse_since, recent_activity, DEFAULT_SINCE};
+use context_keeper::error::{EXIT_CONFIG, EXIT_IO, EXIT_PARTIAL, EXIT_USAGE};
+use context_keeper::format::budget::{format_within_budget, output_budget};
+use context_keeper::format::template::render_template;
+use context_keeper::format::{
+    availability_json, format_diary, format_timings, format_tool_stats, level_sections,
+    sections_json, Section,
 };
-use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use context_keeper::i18n::Language;
+use context_keeper::layout::check_data_layout;
+use context_keeper::output::{print_json, print_list, JsonArgs, JsonStyle, ListJsonArgs};
+use context_keeper::paths::project_dir;
+use context_keeper::redact::Redactor;
+use context_keeper::server::{serve, ContextKeeperService};
+use context_keeper::state::{
+    collect_working_files, contextkeeper_dir, ensure_contextkeeper_dir,
+    refresh_work_state_for_repo, save_work_state_to_file, saved_metadata, WORK_STATE_VERSION,
+};
+use context_keeper::stats::{build_stats_digest, load_tool_calls, stats_path, DEFAULT_STATS_SINCE};
+use context_keeper::{collect_context, Config, Context, Level, ProjectContext, WorkState};
+use regex::Regex;
 use std::fs;
-use std::io::{self, BufRead, Write};
-use std::path::Path;
-
-// ============================================================================
-// Configuration
-// ============================================================================
-
-#[derive(Debug, Deserialize, Default)]
-struct Config {
-    project: Option<ProjectConfig>,
-    scripts: Option<ScriptsConfig>,
-    containers: Option<ContainersConfig>,
-    hints: Option<HintsConfig>,
-    history: Option<HistoryConfig>,
-    git: Option<GitConfig>,
-}
-
-#[derive(Debug, Deserialize)]
-struct ProjectConfig {
-    name: Option<String>,
-    #[serde(rename = "type")]
-    project_type: Option<String>,
-}
-
-#[derive(Debug, Deserialize)]
-struct ScriptsConfig {
-    entry_point: Option<String>,
-    config_dir: Option<String>,
-    config_pattern: Option<String>,
-    #[allow(dead_code)]
-    extract_vars: Option<Vec<String>>,
-}
-
-#[derive(Debug, Deserialize)]
-struct ContainersConfig {
-    runtime: Option<String>,
-}
-
-#[derive(Debug, Deserialize)]
-struct HintsConfig {
-    default: Option<String>,
-}
-
-#[derive(Debug, Deserialize)]
-struct HistoryConfig {
-    enabled: Option<bool>,
-    log_file: Option<String>,
-    patterns: Option<Vec<String>>,
-    max_entries: Option<usize>,
-}
-
-#[derive(Debug, Deserialize)]
-struct GitConfig {
-    /// Explicit list of repository paths to check (relative to project root)
-    paths: Option<Vec<String>>,
-    /// Auto-detect git repositories in subdirectories
-    auto_detect: Option<bool>,
-    /// Max depth for auto-detection (default: 2)
-    scan_depth: Option<usize>,
-}
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
 
 // ============================================================================
-// Collector Data Structures
+// Help and Man Pages
 // ============================================================================
 
-#[derive(Debug, Default, Clone)]
-struct BuildTarget {
-    name: String,
-    description: String,
-    container_name: String,
-    lunch_target: String,
-    can_emulator: bool,
-    can_flash: bool,
+/// Environment variables read by the CLI
+const HELP_ENVIRONMENT: &[(&str, &str)] = &[
+    ("CONTEXTKEEPER_CWD", "Project directory, same as --cwd"),
+    (
+        "RUST_LOG",
+        "Log filter, e.g. context_keeper=debug; overrides -v and -q",
+    ),
+    (
+        DEMO_ENV,
+        "Set to 1 to have the MCP server return the --demo context",
+    ),
+    (
+        "HOME",
+        "Work state, command history and logs are kept in ~/.contextkeeper (%USERPROFILE% on Windows)",
+    ),
+];
+
+fn config_search_help() -> String {
+    format!(
+        "Without --config, the first of {} found in the project directory is used. \
+         Without a config file every collector runs with its defaults.",
+        CONFIG_FILE_NAMES.join(", ")
+    )
 }
 
-#[derive(Debug, Default, Clone)]
-struct ContainerInfo {
-    name: String,
-    status: String,
-    runtime: String,
-}
-
-#[derive(Debug, Clone)]
-struct HistoryEntry {
-    timestamp: String,
-    command: String,
-}
-
-#[derive(Debug, Default, Clone)]
-struct GitInfo {
-    repo_path: String, // Relative path to the repository
-    branch: String,
-    is_dirty: bool,
-    modified_files: usize,
-    untracked_files: usize,
-    last_commit_short: String,
+/// Trailer for the top-level `--help`
+fn after_long_help() -> String {
+    let mut out = format!("Config file:\n  {}\n\nEnvironment:\n", config_search_help());
+    for (name, description) in HELP_ENVIRONMENT {
+        out.push_str(&format!("  {:<19} {}\n", name, description));
+    }
+    out
 }
 
-#[derive(Debug, Clone)]
-struct AdbDevice {
-    serial: String,
-    state: String,
-    device_type: String, // "adb" or "fastboot"
+/// Main man page: the usual clap sections plus ENVIRONMENT and FILES
+fn render_main_man_page(command: &clap::Command, w: &mut dyn Write) -> io::Result<()> {
+    use clap_mangen::roff::{bold, roman, Roff};
+
+    let man = clap_mangen::Man::new(command.clone());
+    man.render_title(w)?;
+    man.render_name_section(w)?;
+    man.render_synopsis_section(w)?;
+    man.render_description_section(w)?;
+    man.render_options_section(w)?;
+    man.render_subcommands_section(w)?;
+
+    let mut roff = Roff::default();
+    roff.control("SH", ["ENVIRONMENT"]);
+    for (name, description) in HELP_ENVIRONMENT {
+        roff.control("TP", []);
+        roff.text([bold(*name)]);
+        roff.text([roman(*description)]);
+    }
+    roff.control("SH", ["FILES"]);
+    roff.text([roman(config_search_help())]);
+    roff.control("TP", []);
+    roff.text([bold("~/.contextkeeper/")]);
+    roff.text([roman(
+        "Saved work state, tracked todos and files, command history and the optional log file",
+    )]);
+    roff.to_writer(w)?;
+
+    man.render_version_section(w)
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-struct TodoItem {
-    content: String,
-    status: String, // "pending", "in_progress", "completed"
+/// Write a page for each visible subcommand, recursing into nested ones
+fn generate_subcommand_man_pages(command: &clap::Command, dir: &Path) -> io::Result<()> {
+    for sub in command.get_subcommands().filter(|s| !s.is_hide_set()) {
+        clap_mangen::Man::new(sub.clone()).generate_to(dir)?;
+        generate_subcommand_man_pages(sub, dir)?;
+    }
+    Ok(())
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-struct WorkState {
-    saved_at: String,
-    trigger: String, // "manual", "pre_compact", "auto"
-    task_summary: String,
-    working_files: Vec<String>,
-    notes: String,
-    todos: Vec<TodoItem>,
-}
+fn run_man(out_dir: Option<&Path>) -> io::Result<()> {
+    use clap::CommandFactory;
 
-// ============================================================================
-// MCP Tool Parameters
-// ============================================================================
+    let mut command = Cli::command().disable_help_subcommand(true);
+    // Fills in subcommand display names (context-keeper-context, ...)
+    command.build();
 
-/// Parameters for get_dev_context tool
-#[derive(Debug, Deserialize, JsonSchema)]
-struct GetDevContextParams {
-    /// Detail level: 'minimal' (~200 tokens), 'normal' (~400 tokens), or 'full' (~1000 tokens). Default: 'normal'
-    level: Option<String>,
+    let Some(dir) = out_dir else {
+        return render_main_man_page(&command, &mut io::stdout());
+    };
+    fs::create_dir_all(dir)?;
+    let mut file = fs::File::create(dir.join("context-keeper.1"))?;
+    render_main_man_page(&command, &mut file)?;
+    generate_subcommand_man_pages(&command, dir)
 }
 
-/// Parameters for save_work_state tool
-#[derive(Debug, Deserialize, JsonSchema)]
-struct SaveWorkStateParams {
-    /// Brief summary of current task (required)
-    task_summary: String,
-    /// List of files currently being worked on (auto-detected from git diff if omitted)
-    working_files: Option<Vec<String>>,
-    /// Additional notes about current progress
-    notes: Option<String>,
-    /// Todo items as JSON array: [{"content": "...", "status": "pending|in_progress|completed"}]
-    todos: Option<String>,
+/// Completes the work-state snapshots saved in ~/.contextkeeper/states/
+/// as the argument of `restore` and `state`
+const ZSH_SNAPSHOT_COMPLETION: &str = r#"
+_context-keeper_snapshots() {
+    local -a snapshots
+    snapshots=(${HOME}/.contextkeeper/states/*.json(N:t:r))
+    _describe -t snapshots 'saved work-state snapshot' snapshots
 }
-
-#[derive(Debug, Default, Clone)]
-struct Context {
-    project_name: String,
-    project_type: String,
-    targets: Vec<BuildTarget>,
-    containers: Vec<ContainerInfo>,
-    available_commands: Vec<String>,
-    hints: String,
-    command_history: Vec<HistoryEntry>,
-    git_repos: Vec<GitInfo>, // Multiple repositories support
-    adb_devices: Vec<AdbDevice>,
-    work_state: Option<WorkState>, // Saved work state for recovery
+"#;
+
+/// Checked first in `_context-keeper`; clap's completion handles the rest
+const ZSH_SNAPSHOT_DISPATCH: &str = r#"
+    if (( CURRENT == 3 )) && [[ ${words[2]} == (restore|state) ]]; then
+        _context-keeper_snapshots
+        return
+    fi
+"#;
+
+/// The clap completion script for `shell`, and for zsh the snapshot names
+fn completion_script(shell: clap_complete::Shell) -> String {
+    use clap::CommandFactory;
+
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut Cli::command(), "context-keeper", &mut script);
+    let script = String::from_utf8_lossy(&script).into_owned();
+    if shell != clap_complete::Shell::Zsh {
+        return script;
+    }
+    let opening = "_context-keeper() {\n";
+    script.replacen(
+        opening,
+        &format!(
+            "{}\n{}{}",
+            ZSH_SNAPSHOT_COMPLETION.trim_start(),
+            opening,
+            ZSH_SNAPSHOT_DISPATCH.trim_start_matches('\n')
+        ),
+        1,
+    )
 }
 
 // ============================================================================
-// BuildScript Collector
+// CLI
 // ============================================================================
 
-fn collect_build_targets(config: &Config) -> Vec<BuildTarget> {
-    let mut targets = Vec::new();
-
-    let scripts_config = match &config.scripts {
-        Some(sc) => sc,
-        None => return targets,
-    };
+#[derive(Debug, clap::Parser)]
+#[command(
+    name = "context-keeper",
+    version,
+    about = "AI-Native Development Context Engine",
+    long_about = None,
+    after_long_help = after_long_help()
+)]
+struct Cli {
+    /// Config file to use instead of searching the project directory
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Project directory (default: current directory)
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        env = "CONTEXTKEEPER_CWD",
+        value_parser = project_dir
+    )]
+    cwd: Option<PathBuf>,
 
-    let config_dir = match &scripts_config.config_dir {
-        Some(dir) => dir.clone(),
-        None => return targets,
-    };
+    /// More log output on stderr (-v info, -vv debug, -vvv trace). RUST_LOG overrides
+    #[arg(long, short, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
 
-    let pattern = scripts_config.config_pattern.as_deref().unwrap_or("*.conf");
+    /// Only log errors
+    #[arg(long, short, global = true)]
+    quiet: bool,
 
-    let full_pattern = format!("{}/{}", config_dir, pattern);
+    /// Language of section headings and labels (default: `[output] language`, else en)
+    #[arg(long, global = true, value_enum, value_name = "LANG")]
+    lang: Option<Language>,
 
-    if let Ok(entries) = glob::glob(&full_pattern) {
-        for entry in entries.flatten() {
-            if let Some(target) = parse_config_file(&entry) {
-                targets.push(target);
-            }
-        }
-    }
+    #[command(flatten)]
+    write: WriteArgs,
 
-    targets
+    #[command(subcommand)]
+    command: Option<CliCommand>,
 }
 
-fn parse_config_file(path: &Path) -> Option<BuildTarget> {
-    let content = fs::read_to_string(path).ok()?;
-    let mut target = BuildTarget::default();
-
-    for line in content.lines() {
-        let line = line.trim();
-        if line.starts_with('#') || line.is_empty() {
-            continue;
-        }
-
-        if let Some((key, value)) = parse_var_assignment(line) {
-            match key.as_str() {
-                "TARGET_NAME" => target.name = value,
-                "TARGET_DESCRIPTION" => target.description = value,
-                "CONTAINER_NAME" => target.container_name = value,
-                "LUNCH_TARGET" => target.lunch_target = value,
-                "CAN_EMULATOR" => target.can_emulator = value == "true",
-                "CAN_FLASH" => target.can_flash = value == "true",
-                _ => {}
-            }
-        }
-    }
-
-    if target.name.is_empty() {
-        target.name = path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("unknown")
-            .to_string();
-    }
-
-    Some(target)
+#[derive(Debug, clap::Subcommand)]
+enum CliCommand {
+    /// Run as MCP server over stdio (default)
+    ///
+    /// Speaks MCP (JSON-RPC) on stdin/stdout and offers the get_dev_context
+    /// and save_work_state tools. Logs go to stderr only. Each call describes
+    /// the project_root it is given, else the client's first root, else the
+    /// directory the server started in (see --cwd).
+    Serve {
+        /// Serve streamable HTTP at ADDR, e.g. 127.0.0.1:8931, on the /mcp
+        /// path, instead of stdio (needs the `http-transport` feature)
+        #[arg(long, value_name = "ADDR")]
+        http: Option<std::net::SocketAddr>,
+    },
+    /// Print the development context
+    ///
+    /// Collects the project state (work state, build targets, containers,
+    /// git repos, devices, history) and prints it as Markdown.
+    ///
+    /// Levels: oneline (~30 tokens) is a single line with the changed repos,
+    /// task, devices and top issue; minimal (~200 tokens) has the hint,
+    /// saved task, working files and dirty repos; normal (~400 tokens) adds containers, devices and all
+    /// hints; full (~1000 tokens) has everything, including targets, commands,
+    /// history and every repo. --section picks exact sections instead.
+    Context {
+        /// Detail level (default: normal, or full with --section)
+        #[arg(long, short, value_enum)]
+        level: Option<Level>,
+        #[command(flatten)]
+        json: JsonArgs,
+        /// Print only these sections, in the given order (repeatable or comma-separated)
+        #[arg(long, short, value_enum, value_delimiter = ',')]
+        section: Vec<Section>,
+        /// Also time each collector on its own and print a table
+        #[arg(long)]
+        timings: bool,
+        /// Average the timings over N runs
+        #[arg(long, value_name = "N", default_value_t = 1, requires = "timings")]
+        repeat: u32,
+        /// Print a fixed synthetic context instead of collecting (for demos and docs)
+        #[arg(long, conflicts_with = "timings")]
+        demo: bool,
+        /// Use this context JSON (as printed by --json) for --demo
+        #[arg(long, value_name = "FILE", requires = "demo")]
+        demo_file: Option<PathBuf>,
+        /// Show the context a level down when it is over N bytes, noting what
+        /// was left out (default: `[output] max_output_bytes`; 0 for no limit)
+        #[arg(long, value_name = "N", conflicts_with = "json")]
+        max_output_bytes: Option<usize>,
+        /// Render the context through this Jinja template instead, with the
+        /// fields of --json as its variables (needs the `templates` feature)
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["json", "section", "timings"])]
+        template: Option<PathBuf>,
+    },
+    /// Create contextkeeper.toml with an interactive wizard (or flags with --yes)
+    ///
+    /// Detects the project type (AOSP, ROS, Yocto) and container runtime,
+    /// asks for anything it cannot detect, and writes contextkeeper.toml in
+    /// the project directory. With --yes nothing is asked: detected values and
+    /// flags are used, and a missing answer is an error.
+    Init(InitOptions),
+    /// Save work state (used by the PreCompact hook)
+    SaveState {
+        /// Brief summary of the current task
+        task: Option<String>,
+        /// Exit 0 even when saving fails (set by the legacy `--save-state`
+        /// spelling, so hooks that predate the exit codes keep working)
+        #[arg(long, hide = true)]
+        exit_zero: bool,
+        /// Only refresh the working files of the repository in the current
+        /// directory, keeping the saved task, notes and todos (used by the
+        /// git hooks; also spelled `--save-state-auto`)
+        #[arg(long, conflicts_with = "task")]
+        auto: bool,
+    },
+    /// Validate the config file
+    Check,
+    /// Diagnose the environment (tools, config, paths)
+    Doctor {
+        #[command(flatten)]
+        json: JsonArgs,
+    },
+    /// Print a one-line summary for shell prompts and statuslines
+    ///
+    /// Exits 1 when something needs attention (conflicts, unhealthy containers).
+    Status {
+        /// Also query containers and devices (slower)
+        #[arg(long)]
+        all: bool,
+        /// Custom layout with {project}, {dirty_repos}, {conflicts}, {task}, {devices}
+        #[arg(long)]
+        format: Option<String>,
+    },
+    /// Print a short line for shell prompts from saved state only
+    ///
+    /// Reads the work state and the repo statuses saved by the last
+    /// collection; never runs git or any other program. A trailing `~`
+    /// marks repo statuses more than 5 minutes old.
+    Prompt {
+        /// Custom layout with {project}, {dirty_repos}, {conflicts}, {task}, {stale}
+        #[arg(long)]
+        format: Option<String>,
+        /// Print nothing; exit 1 when there is nothing to show
+        #[arg(long)]
+        when: bool,
+    },
+    /// Print the Claude Code statusline from saved state only
+    ///
+    /// Set as the `statusLine` command in Claude Code's settings. Reads the
+    /// session JSON on stdin for the project directory, then the work
+    /// state, repo statuses, build processes and devices saved by the last
+    /// collection; never runs git or any other program.
+    Statusline,
+    /// Record a shell command and the `[env] capture` variables
+    ///
+    /// For a prompt hook in your terminal (PROMPT_COMMAND, precmd). Appends
+    /// to the command history log with the current values of the captured
+    /// variables, which the full context shows under "Environment".
+    Log {
+        /// The command just run; omit to record only the variables
+        #[arg(long)]
+        command: Option<String>,
+    },
+    /// Report the files open in the editor
+    ///
+    /// Writes the list an editor extension would write to the project's
+    /// open-files.json. The context shows it beside the terminal sessions,
+    /// and save_work_state records it.
+    OpenFiles {
+        #[command(subcommand)]
+        action: OpenFilesAction,
+    },
+    /// Run a build and record its result for the context
+    ///
+    /// Passes the output through and exits with the build's exit code.
+    /// The result, with the last output lines of a failed build, shows
+    /// under "Builds" at normal and full level.
+    BuildWrap {
+        /// Target to record the build under (default: the target whose
+        /// command or name matches the command line)
+        #[arg(long)]
+        target: Option<String>,
+        /// The build command, after `--`
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Re-render the context whenever it changes
+    #[cfg(feature = "watch")]
+    Watch(WatchOptions),
+    /// List build targets with the file and glob each came from
+    Targets {
+        #[command(flatten)]
+        json: ListJsonArgs,
+    },
+    /// List checked git repositories, how they were found, and their status
+    Repos {
+        #[command(flatten)]
+        json: ListJsonArgs,
+        /// Walk the tree even when the saved repo list is still valid
+        #[arg(long)]
+        refresh: bool,
+    },
+    /// Summarize the saved work states by day and project
+    ///
+    /// Every save (save_work_state, the PreCompact hook, git hooks) is kept
+    /// in ~/.contextkeeper/diary.jsonl for diary.retention_days. Shows the
+    /// tasks, the repositories with working files, and the todos added or
+    /// completed since the project's previous save.
+    Diary {
+        /// How far back, e.g. 12h, 7d, 2w
+        #[arg(long, default_value = DEFAULT_SINCE)]
+        since: String,
+        #[command(flatten)]
+        json: ListJsonArgs,
+    },
+    /// Summarize the MCP tool calls recorded in ~/.contextkeeper/stats.jsonl
+    ///
+    /// Calls per tool, median and 95th percentile durations, cache hit
+    /// rate and get_dev_context levels, across all projects. Turn the
+    /// recording off with `[stats] enabled = false`.
+    Stats {
+        /// How far back, e.g. 12h, 7d, 2w
+        #[arg(long, default_value = DEFAULT_STATS_SINCE)]
+        since: String,
+        #[command(flatten)]
+        json: JsonArgs,
+    },
+    /// Keep the saved work state current from git post-commit/post-checkout hooks
+    GitHooks {
+        #[command(subcommand)]
+        action: GitHooksAction,
+    },
+    /// Export the build targets or the whole context for other tools
+    Export {
+        #[command(subcommand)]
+        action: ExportAction,
+    },
+    /// Print the project, target, device and dirty repos as shell variables
+    ///
+    /// For scripts: `eval "$(context-keeper env --target qcom-dev)"` sets
+    /// CK_PROJECT_NAME, CK_TARGET_CONTAINER, CK_TARGET_LUNCH,
+    /// CK_DEVICE_SERIAL and CK_GIT_DIRTY_REPOS. A value that isn't known is
+    /// unset, with a comment saying why. An unknown --target is an error
+    /// listing the known ones.
+    Env(EnvOptions),
+    /// Move ~/.contextkeeper to the current layout
+    ///
+    /// The first layout kept one work-state.json for every project. This
+    /// moves it into the state directory of the project it belongs to,
+    /// moves the command history log into logs/, and writes the
+    /// layout_version marker. Until then everything keeps using the old
+    /// layout. Running it again does nothing.
+    Migrate(MigrateOptions),
+    /// Wire context-keeper into Claude Code settings
+    Hooks {
+        #[command(subcommand)]
+        action: HooksAction,
+    },
+    /// Print man pages in roff format (for packagers)
+    #[command(hide = true)]
+    Man {
+        /// Write context-keeper.1 and one page per subcommand into DIR
+        /// instead of printing the main page
+        #[arg(long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+    },
+    /// Print a shell completion script
+    Completions {
+        /// Target shell
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
 }
 
-fn parse_var_assignment(line: &str) -> Option<(String, String)> {
-    let parts: Vec<&str> = line.splitn(2, '=').collect();
-    if parts.len() != 2 {
-        return None;
-    }
-
-    let key = parts[0].trim().to_string();
-    let value = parts[1]
-        .trim()
-        .trim_matches('"')
-        .trim_matches('\'')
-        .to_string();
-
-    Some((key, value))
-}
-
-fn parse_entry_point_commands(entry_point: &str) -> Vec<String> {
-    let mut commands = Vec::new();
-
-    if let Ok(content) = fs::read_to_string(entry_point) {
-        for line in content.lines() {
-            let line = line.trim();
-            if line.contains("./") && line.contains(".sh ") {
-                commands.push(line.to_string());
+/// Rewrite the pre-clap flag spellings so existing hook scripts keep working:
+/// `--context [level]` / `-c [level]`, `--save-state [task]` and
+/// `--save-state-auto`. Nothing after `--` is touched, so `build-wrap --
+/// sh -c ...` keeps its command.
+fn normalize_legacy_args(args: Vec<String>) -> Vec<String> {
+    let mut out = Vec::with_capacity(args.len() + 1);
+    let mut iter = args.into_iter().peekable();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--" => {
+                out.push(arg);
+                out.extend(iter);
+                break;
             }
-        }
-    }
-
-    commands.sort();
-    commands.dedup();
-    commands.truncate(10);
-    commands
-}
-
-// ============================================================================
-// Container Collector
-// ============================================================================
-
-fn collect_containers(config: &Config) -> Vec<ContainerInfo> {
-    let mut containers = Vec::new();
-
-    let runtime = config
-        .containers
-        .as_ref()
-        .and_then(|c| c.runtime.as_deref())
-        .unwrap_or("podman");
-
-    if let Ok(output) = std::process::Command::new(runtime)
-        .args(["ps", "--format", "{{.Names}}\\t{{.Status}}"])
-        .output()
-    {
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            for line in stdout.lines() {
-                let parts: Vec<&str> = line.split('\t').collect();
-                if parts.len() >= 2 {
-                    containers.push(ContainerInfo {
-                        name: parts[0].to_string(),
-                        status: parts[1].to_string(),
-                        runtime: runtime.to_string(),
-                    });
+            "--context" | "-c" => {
+                out.push("context".to_string());
+                if let Some(level) =
+                    iter.next_if(|next| matches!(next.as_str(), "minimal" | "normal" | "full"))
+                {
+                    out.push("--level".to_string());
+                    out.push(level);
                 }
             }
+            "--save-state" => {
+                out.push("save-state".to_string());
+                out.push("--exit-zero".to_string());
+            }
+            "--save-state-auto" => {
+                out.push("save-state".to_string());
+                out.push("--auto".to_string());
+            }
+            _ => out.push(arg),
         }
     }
 
-    containers
+    out
 }
 
-// ============================================================================
-// History Collector
-// ============================================================================
-
-fn collect_command_history(config: &Config) -> Vec<HistoryEntry> {
-    let history_config = match &config.history {
-        Some(hc) if hc.enabled.unwrap_or(true) => hc,
-        _ => return Vec::new(),
+/// Validate the config file and report problems.
+/// Returns the exit code: config errors exit 3, warnings exit 1.
+fn run_check(cli: &Cli, root: &Path) -> i32 {
+    let path = match cli.config.clone().or_else(|| find_config_file(root)) {
+        Some(path) => path,
+        None => {
+            eprintln!("No config file found in {}", root.display());
+            return EXIT_CONFIG;
+        }
     };
 
-    let log_file = history_config.log_file.clone().unwrap_or_else(|| {
-        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        format!("{}/.contextkeeper/command-history.jsonl", home)
-    });
-
-    let max_entries = history_config.max_entries.unwrap_or(20);
-
-    let default_patterns = vec![
-        r"lunch\s+\S+".to_string(),
-        r"source\s+.*envsetup".to_string(),
-        r"export\s+\w+=".to_string(),
-        r"m\s+\S+".to_string(),
-        r"mm\b".to_string(),
-        r"mma\b".to_string(),
-    ];
-
-    let patterns = history_config.patterns.clone().unwrap_or(default_patterns);
-
-    let compiled_patterns: Vec<Regex> =
-        patterns.iter().filter_map(|p| Regex::new(p).ok()).collect();
-
-    let mut entries = Vec::new();
-    let path = Path::new(&log_file);
-
-    if !path.exists() {
-        return entries;
-    }
-
-    if let Ok(file) = fs::File::open(path) {
-        let reader = io::BufReader::new(file);
-
-        for line in reader.lines().map_while(Result::ok) {
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&line) {
-                let command = json["command"].as_str().unwrap_or("");
-                let matches_pattern = compiled_patterns.is_empty()
-                    || compiled_patterns.iter().any(|re| re.is_match(command));
-
-                if matches_pattern && !command.is_empty() {
-                    entries.push(HistoryEntry {
-                        timestamp: json["timestamp"].as_str().unwrap_or("").to_string(),
-                        command: command.to_string(),
-                    });
-                }
-            }
+    let config = match Config::load(&path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Config error: {}", e);
+            return e.exit_code();
         }
-    }
-
-    if entries.len() > max_entries {
-        entries.drain(0..entries.len() - max_entries);
-    }
-
-    entries
-}
-
-// ============================================================================
-// Git Collector
-// ============================================================================
-
-/// Collect git info from a single repository path
-fn collect_git_info_for_path(repo_path: &str) -> Option<GitInfo> {
-    let _path = Path::new(repo_path);
-
-    // Check if this path is a git repository
-    let is_git = std::process::Command::new("git")
-        .args(["-C", repo_path, "rev-parse", "--is-inside-work-tree"])
-        .output()
-        .map(|o| o.status.success())
-        .unwrap_or(false);
-
-    if !is_git {
-        return None;
-    }
-
-    let mut info = GitInfo {
-        repo_path: repo_path.to_string(),
-        ..Default::default()
     };
 
-    // Get current branch
-    if let Ok(output) = std::process::Command::new("git")
-        .args(["-C", repo_path, "branch", "--show-current"])
-        .output()
-    {
-        if output.status.success() {
-            info.branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        }
-    }
+    println!("Config OK: {}", path.display());
 
-    // If branch is empty, try to get detached HEAD info
-    if info.branch.is_empty() {
-        if let Ok(output) = std::process::Command::new("git")
-            .args(["-C", repo_path, "describe", "--always", "--dirty"])
-            .output()
-        {
-            if output.status.success() {
-                info.branch = format!("({})", String::from_utf8_lossy(&output.stdout).trim());
+    let project = ProjectContext::load(root.to_path_buf(), Some(path));
+    let mut code = 0;
+    if let Some(scripts) = &config.scripts {
+        if let Some(value) = &scripts.entry_point {
+            if !project.resolve(value).exists() {
+                eprintln!("Warning: scripts.entry_point does not exist: {}", value);
+                code = EXIT_PARTIAL;
             }
         }
-    }
-
-    // Get status (modified and untracked counts)
-    if let Ok(output) = std::process::Command::new("git")
-        .args(["-C", repo_path, "status", "--porcelain"])
-        .output()
-    {
-        if output.status.success() {
-            let status = String::from_utf8_lossy(&output.stdout);
-            for line in status.lines() {
-                if line.starts_with(" M") || line.starts_with("M ") || line.starts_with("MM") {
-                    info.modified_files += 1;
-                } else if line.starts_with("??") {
-                    info.untracked_files += 1;
-                } else if !line.trim().is_empty() {
-                    info.modified_files += 1; // Other changes (added, deleted, etc.)
-                }
+        for value in scripts.config_dir.iter().flat_map(OneOrMany::to_vec) {
+            if !project.resolve_from_config(value).exists() {
+                eprintln!("Warning: scripts.config_dir does not exist: {}", value);
+                code = EXIT_PARTIAL;
             }
-            info.is_dirty = info.modified_files > 0 || info.untracked_files > 0;
         }
     }
-
-    // Get last commit short hash and message
-    if let Ok(output) = std::process::Command::new("git")
-        .args(["-C", repo_path, "log", "-1", "--format=%h %s"])
-        .output()
-    {
-        if output.status.success() {
-            let commit_info = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            if commit_info.len() > 50 {
-                info.last_commit_short =
-                    format!("{}...", &commit_info.chars().take(47).collect::<String>());
-            } else {
-                info.last_commit_short = commit_info;
+    if let Some(history) = &config.history {
+        for pattern in history.patterns.iter().flatten() {
+            if let Err(e) = Regex::new(pattern) {
+                eprintln!("Warning: invalid history pattern {:?}: {}", pattern, e);
+                code = EXIT_PARTIAL;
             }
         }
     }
-
-    Some(info)
-}
-
-/// Auto-detect git repositories in subdirectories
-fn find_git_repos(base_path: &str, max_depth: usize) -> Vec<String> {
-    let mut repos = Vec::new();
-    find_git_repos_recursive(base_path, base_path, 0, max_depth, &mut repos);
-    repos.sort();
-    repos
-}
-
-fn find_git_repos_recursive(
-    base_path: &str,
-    current_path: &str,
-    depth: usize,
-    max_depth: usize,
-    repos: &mut Vec<String>,
-) {
-    if depth > max_depth {
-        return;
-    }
-
-    let current = Path::new(current_path);
-
-    // Check if current directory is a git repo
-    let git_dir = current.join(".git");
-    if git_dir.exists() {
-        // Use relative path from base
-        if let Ok(relative) = current.strip_prefix(base_path) {
-            let rel_str = relative.to_string_lossy().to_string();
-            if !rel_str.is_empty() {
-                repos.push(rel_str);
+    if let Some(git) = &config.git {
+        for repo in git.repos.iter().flatten() {
+            if let Err(e) = glob::Pattern::new(&repo.path) {
+                eprintln!(
+                    "Warning: git.repos path {:?} is not a valid glob ({}); it only matches exactly",
+                    repo.path, e
+                );
+                code = EXIT_PARTIAL;
             }
         }
-        return; // Don't recurse into git repos
     }
-
-    // Recurse into subdirectories
-    if let Ok(entries) = fs::read_dir(current) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_dir() {
-                let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
-                // Skip hidden directories and common non-repo directories
-                if name.starts_with('.')
-                    || name == "node_modules"
-                    || name == "target"
-                    || name == "out"
-                {
-                    continue;
-                }
-                find_git_repos_recursive(
-                    base_path,
-                    path.to_str().unwrap_or(""),
-                    depth + 1,
-                    max_depth,
-                    repos,
+    if let Some(output) = &config.output {
+        for pattern in output.redact_patterns.iter().flatten() {
+            if let Err(e) = Regex::new(pattern) {
+                eprintln!(
+                    "Warning: invalid output.redact_patterns entry {:?}: {}",
+                    pattern, e
                 );
+                code = EXIT_PARTIAL;
             }
         }
     }
-}
 
-/// Collect git info from multiple repositories based on config
-fn collect_git_repos(config: &Config) -> Vec<GitInfo> {
-    let mut repos = Vec::new();
-    let cwd = std::env::current_dir()
-        .map(|p| p.to_string_lossy().to_string())
-        .unwrap_or_else(|_| ".".to_string());
-
-    // First, check if current directory itself is a git repo
-    if let Some(info) = collect_git_info_for_path(&cwd) {
-        let mut info = info;
-        info.repo_path = ".".to_string();
-        repos.push(info);
-        return repos; // If root is a git repo, don't scan subdirectories
+    let mut targets = collect_build_targets(&project);
+    validate_targets(&project, &mut targets);
+    for target in targets.iter().filter(|t| !t.issues.is_empty()) {
+        eprintln!(
+            "Warning: target {}: {} ({})",
+            target.name,
+            target.issues.join(", "),
+            target.source_file
+        );
+        code = EXIT_PARTIAL;
     }
 
-    // Get paths from config or auto-detect
-    let git_config = config.git.as_ref();
-    let auto_detect = git_config.and_then(|g| g.auto_detect).unwrap_or(true);
-    let explicit_paths = git_config.and_then(|g| g.paths.clone());
-    let scan_depth = git_config.and_then(|g| g.scan_depth).unwrap_or(2);
+    code
+}
 
-    let paths_to_check: Vec<String> = if let Some(paths) = explicit_paths {
-        paths
-    } else if auto_detect {
-        find_git_repos(&cwd, scan_depth)
-    } else {
-        Vec::new()
+/// Print `context` through the template at `path`, or exit with the
+/// reason it can't be: unreadable (EXIT_IO) or broken (EXIT_USAGE)
+fn print_template(path: &Path, context: &Context, redactor: &Redactor) {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error: {}: {}", path.display(), e);
+            std::process::exit(EXIT_IO);
+        }
     };
-
-    // Collect info from each path
-    for path in paths_to_check {
-        let full_path = if Path::new(&path).is_absolute() {
-            path.clone()
-        } else {
-            format!("{}/{}", cwd, path)
-        };
-
-        if let Some(mut info) = collect_git_info_for_path(&full_path) {
-            info.repo_path = path;
-            repos.push(info);
+    match render_template(&path.display().to_string(), &source, context, redactor) {
+        Ok(text) => print!("{}", text),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(EXIT_USAGE);
         }
     }
-
-    // Sort by path for consistent output
-    repos.sort_by(|a, b| a.repo_path.cmp(&b.repo_path));
-
-    // Limit to reasonable number
-    repos.truncate(10);
-
-    repos
 }
 
-// ============================================================================
-// ADB/Fastboot Collector
-// ============================================================================
-
-fn collect_adb_devices() -> Vec<AdbDevice> {
-    let mut devices = Vec::new();
-
-    // Collect ADB devices
-    if let Ok(output) = std::process::Command::new("adb")
-        .args(["devices", "-l"])
-        .output()
-    {
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            for line in stdout.lines().skip(1) {
-                // Skip "List of devices attached"
-                let line = line.trim();
-                if line.is_empty() {
-                    continue;
-                }
-
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 2 {
-                    let serial = parts[0].to_string();
-                    let state = parts[1].to_string();
-
-                    // Skip offline devices
-                    if state == "offline" {
-                        continue;
-                    }
-
-                    devices.push(AdbDevice {
-                        serial,
-                        state,
-                        device_type: "adb".to_string(),
-                    });
-                }
-            }
+/// Print a context for `context`: Markdown or JSON, whole or by section,
+/// redacted
+fn print_context(
+    redactor: &Redactor,
+    context: &Context,
+    section: &[Section],
+    level: Level,
+    json: Option<JsonStyle>,
+    max_output: Option<usize>,
+    timings: Option<(&[CollectorReport], u32)>,
+) -> Result<(), serde_json::Error> {
+    if let Some(style) = json {
+        let (mut value, shown) = if section.is_empty() {
+            (serde_json::to_value(context)?, &Section::ALL[..])
+        } else {
+            (sections_json(context, section), section)
+        };
+        if let Some(map) = value.as_object_mut() {
+            map.insert(
+                "availability".to_string(),
+                availability_json(context, shown),
+            );
         }
-    }
-
-    // Collect Fastboot devices
-    if let Ok(output) = std::process::Command::new("fastboot")
-        .args(["devices", "-l"])
-        .output()
-    {
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            for line in stdout.lines() {
-                let line = line.trim();
-                if line.is_empty() {
-                    continue;
-                }
-
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if !parts.is_empty() {
-                    let serial = parts[0].to_string();
-                    devices.push(AdbDevice {
-                        serial,
-                        state: "fastboot".to_string(),
-                        device_type: "fastboot".to_string(),
-                    });
-                }
-            }
+        if let (Some((reports, _)), Some(map)) = (timings, value.as_object_mut()) {
+            map.insert("timings".to_string(), serde_json::to_value(reports)?);
         }
-    }
-
-    devices
-}
-
-// ============================================================================
-// Work State Management
-// ============================================================================
-
-fn get_work_state_path() -> String {
-    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-    format!("{}/.contextkeeper/work-state.json", home)
-}
-
-fn ensure_contextkeeper_dir() -> io::Result<()> {
-    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-    let dir = format!("{}/.contextkeeper", home);
-    fs::create_dir_all(&dir)?;
-    Ok(())
-}
-
-fn save_work_state_to_file(state: &WorkState) -> io::Result<()> {
-    ensure_contextkeeper_dir()?;
-    let path = get_work_state_path();
-    let json = serde_json::to_string_pretty(state).map_err(io::Error::other)?;
-    let mut file = fs::File::create(&path)?;
-    file.write_all(json.as_bytes())?;
-    Ok(())
-}
-
-fn load_work_state_from_file() -> Option<WorkState> {
-    let path = get_work_state_path();
-    if !Path::new(&path).exists() {
-        return None;
-    }
-
-    fs::read_to_string(&path)
-        .ok()
-        .and_then(|content| serde_json::from_str(&content).ok())
-}
-
-/// Load saved todos from TodoWrite hook
-fn load_saved_todos() -> Vec<TodoItem> {
-    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-    let path = format!("{}/.contextkeeper/current-todos.json", home);
-
-    if !Path::new(&path).exists() {
-        return Vec::new();
-    }
-
-    fs::read_to_string(&path)
-        .ok()
-        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
-        .and_then(|json| {
-            json.get("todos").and_then(|t| t.as_array()).map(|arr| {
-                arr.iter()
-                    .filter_map(|item| {
-                        Some(TodoItem {
-                            content: item.get("content")?.as_str()?.to_string(),
-                            status: item.get("status")?.as_str()?.to_string(),
-                        })
-                    })
-                    .collect()
-            })
-        })
-        .unwrap_or_default()
-}
-
-/// Load recently edited files from Edit/Write hook
-fn load_recent_files() -> Vec<String> {
-    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-    let path = format!("{}/.contextkeeper/recent-files.json", home);
-
-    if !Path::new(&path).exists() {
-        return Vec::new();
-    }
-
-    fs::read_to_string(&path)
-        .ok()
-        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
-        .and_then(|json| {
-            json.get("files").and_then(|f| f.as_array()).map(|arr| {
-                arr.iter()
-                    .filter_map(|item| item.get("path")?.as_str().map(|s| s.to_string()))
-                    .collect()
-            })
-        })
-        .unwrap_or_default()
-}
-
-/// Load or construct work state with hook-collected data
-fn load_work_state_with_hooks() -> Option<WorkState> {
-    // First try to load manually saved work state
-    let mut state = load_work_state_from_file().unwrap_or_default();
-
-    // Enhance with hook-collected data
-    let hook_todos = load_saved_todos();
-    let hook_files = load_recent_files();
-
-    // If we have hook data, update the state
-    if !hook_todos.is_empty() {
-        state.todos = hook_todos;
-    }
-
-    if !hook_files.is_empty() && state.working_files.is_empty() {
-        state.working_files = hook_files;
-    }
-
-    // Return None if state is completely empty
-    if state.task_summary.is_empty()
-        && state.todos.is_empty()
-        && state.working_files.is_empty()
-        && state.notes.is_empty()
-    {
-        return None;
-    }
-
-    Some(state)
-}
-
-/// Collect working files from git diff (for PreCompact hook)
-fn collect_working_files() -> Vec<String> {
-    let mut files = Vec::new();
-    let cwd = std::env::current_dir()
-        .map(|p| p.to_string_lossy().to_string())
-        .unwrap_or_else(|_| ".".to_string());
-
-    // Try to get modified files from all git repos
-    if let Ok(output) = std::process::Command::new("bash")
-        .args(["-c", &format!(
-            "cd '{}' && find . -maxdepth 3 -name '.git' -type d 2>/dev/null | while read gitdir; do \
-             repo=$(dirname \"$gitdir\"); \
-             git -C \"$repo\" diff --name-only 2>/dev/null | sed \"s|^|$repo/|\" ; \
-             done | head -20",
-            cwd
-        )])
-        .output()
-    {
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            for line in stdout.lines() {
-                let line = line.trim();
-                if !line.is_empty() {
-                    // Clean up path (remove leading ./)
-                    let clean_path = line.trim_start_matches("./");
-                    files.push(clean_path.to_string());
-                }
-            }
+        redactor.redact_json(&mut value);
+        print_json(&value, style)?;
+    } else {
+        let sections = (!section.is_empty()).then_some(section);
+        let finish = |body: &str| redactor.redact(body).into_owned();
+        let max = max_output.unwrap_or(usize::MAX);
+        let mut text = format_within_budget(context, sections, level, max, finish);
+        // The blank line after a whole context, when it still fits; a
+        // single line stays one
+        if sections.is_none() && level != Level::Oneline && text.len() < max {
+            text.push('\n');
+        }
+        if let Some((reports, repeat)) = timings {
+            text.push_str(&redactor.redact(&format_timings(reports, repeat)));
         }
+        print!("{}", text);
     }
-
-    files
+    Ok(())
 }
 
 // ============================================================================
-// Context Aggregator
+// Logging
 // ============================================================================
 
-fn collect_context(config: &Config) -> Context {
-    let mut ctx = Context::default();
-
-    if let Some(project) = &config.project {
-        ctx.project_name = project.name.clone().unwrap_or_default();
-        ctx.project_type = project.project_type.clone().unwrap_or_default();
-    }
-
-    ctx.targets = collect_build_targets(config);
-    ctx.containers = collect_containers(config);
-
-    if let Some(scripts) = &config.scripts {
-        if let Some(entry) = &scripts.entry_point {
-            ctx.available_commands = parse_entry_point_commands(entry);
+/// Log to stderr (stdout is the MCP protocol channel), optionally to a
+/// daily-rotated file, and to the MCP client once one connects. The
+/// client layer has its own level (`logging/setLevel`), so `-v` and
+/// RUST_LOG filter the first two only. The returned guard must live until
+/// exit so the file writer flushes.
+fn init_logging(
+    verbose: u8,
+    quiet: bool,
+    log_to_file: bool,
+) -> Option<tracing_appender::non_blocking::WorkerGuard> {
+    use tracing_subscriber::prelude::*;
+    use tracing_subscriber::EnvFilter;
+
+    let level = if quiet {
+        "error"
+    } else {
+        match verbose {
+            0 => "warn",
+            1 => "info",
+            2 => "debug",
+            _ => "trace",
         }
-    }
+    };
+    let filter = || {
+        EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| EnvFilter::new(format!("warn,context_keeper={}", level)))
+    };
 
-    if let Some(hints) = &config.hints {
-        ctx.hints = hints.default.clone().unwrap_or_default();
-    }
+    let stderr_layer = tracing_subscriber::fmt::layer()
+        .with_writer(io::stderr)
+        .with_ansi(io::IsTerminal::is_terminal(&io::stderr()))
+        .with_target(false)
+        .with_filter(filter());
+
+    let (file_layer, guard) = if log_to_file && ensure_contextkeeper_dir().is_ok() {
+        let appender = tracing_appender::rolling::daily(contextkeeper_dir(), "context-keeper.log");
+        let (writer, guard) = tracing_appender::non_blocking(appender);
+        let layer = tracing_subscriber::fmt::layer()
+            .with_writer(writer)
+            .with_ansi(false)
+            .with_filter(filter());
+        (Some(layer), Some(guard))
+    } else {
+        (None, None)
+    };
 
-    ctx.command_history = collect_command_history(config);
-    ctx.git_repos = collect_git_repos(config);
-    ctx.adb_devices = collect_adb_devices();
-    ctx.work_state = load_work_state_with_hooks();
-    ctx
+    tracing_subscriber::registry()
+        .with(stderr_layer)
+        .with(file_layer)
+        .with(client_log_layer())
+        .init();
+
+    guard
 }
 
 // ============================================================================
-// Output Formatter (Hierarchical: minimal / normal / full)
+// Main
 // ============================================================================
 
-/// Helper: format git status string
-fn format_git_status(git: &GitInfo) -> String {
-    if git.is_dirty {
-        if git.modified_files > 0 && git.untracked_files > 0 {
-            format!("{}M {}U", git.modified_files, git.untracked_files)
-        } else if git.modified_files > 0 {
-            format!("{}M", git.modified_files)
-        } else {
-            format!("{}U", git.untracked_files)
-        }
-    } else {
-        "clean".to_string()
-    }
-}
-
-/// Helper: format work state section
-fn format_work_state(work_state: &WorkState) -> String {
-    let mut out = String::new();
-    out.push_str("## Saved Work State\n");
-    out.push_str(&format!("- **Saved at:** {}\n", work_state.saved_at));
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    use clap::Parser;
 
-    if !work_state.task_summary.is_empty() {
-        out.push_str(&format!("- **Task:** {}\n", work_state.task_summary));
-    }
+    let cli = Cli::parse_from(normalize_legacy_args(std::env::args().collect()));
+    let root = match &cli.cwd {
+        Some(dir) => dir.clone(),
+        None => std::env::current_dir()?,
+    };
 
-    if !work_state.working_files.is_empty() {
-        out.push_str("- **Working files:**\n");
-        for file in &work_state.working_files {
-            out.push_str(&format!("  - {}\n", file));
+    let log_to_file = cli
+        .config
+        .clone()
+        .or_else(|| find_config_file(&root))
+        .and_then(|path| Config::load(&path).ok())
+        .and_then(|config| config.logging)
+        .and_then(|logging| logging.file)
+        .unwrap_or(false);
+    let _log_guard = init_logging(cli.verbose, cli.quiet, log_to_file);
+    if !matches!(
+        cli.command,
+        Some(CliCommand::Migrate(_) | CliCommand::Man { .. } | CliCommand::Completions { .. })
+    ) {
+        // The writers refuse a layout they can't use on their own
+        if let Err(e) = check_data_layout() {
+            tracing::error!(error = %e, "cannot use ~/.contextkeeper");
         }
     }
 
-    if !work_state.notes.is_empty() {
-        out.push_str(&format!("- **Notes:** {}\n", work_state.notes));
-    }
-
-    if !work_state.todos.is_empty() {
-        out.push_str("- **Todos:**\n");
-        for todo in &work_state.todos {
-            let checkbox = match todo.status.as_str() {
-                "completed" => "[x]",
-                "in_progress" => "[>]",
-                _ => "[ ]",
-            };
-            out.push_str(&format!("  - {} {}\n", checkbox, todo.content));
+    match cli.command {
+        Some(CliCommand::Init(opts)) => {
+            if let Err(e) = run_init_wizard(&root, &opts, &cli.write) {
+                eprintln!("Error: {}", e);
+                // Missing flags, no terminal, input ending early or an
+                // existing config are usage errors
+                let code = match e.kind() {
+                    io::ErrorKind::InvalidInput
+                    | io::ErrorKind::AlreadyExists
+                    | io::ErrorKind::UnexpectedEof => EXIT_USAGE,
+                    _ => EXIT_IO,
+                };
+                std::process::exit(code);
+            }
         }
-    }
-
-    out.push('\n');
-    out
-}
-
-/// Minimal format (~200 tokens) - for recovery after compression
-fn format_minimal(ctx: &Context) -> String {
-    let mut out = String::new();
-
-    out.push_str("# Context Recovery (Minimal)\n\n");
+        Some(CliCommand::Context {
+            level,
+            json,
+            section,
+            timings,
+            repeat,
+            demo,
+            demo_file,
+            max_output_bytes,
+            template,
+        }) => {
+            let json = json.json;
+            // Sections without a level render at full
+            let level = match level {
+                Some(level) => level,
+                None if section.is_empty() => Level::default(),
+                None => Level::Full,
+            };
+            if demo {
+                let mut context = match demo_context(demo_file.as_deref()) {
+                    Ok(context) => context,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(e.exit_code());
+                    }
+                };
+                context.language = cli.lang.unwrap_or_default();
+                let redactor = Redactor::from_config(&Config::default());
+                if let Some(template) = &template {
+                    print_template(template, &context, &redactor);
+                    return Ok(());
+                }
+                let max_output = output_budget(&Config::default(), max_output_bytes);
+                print_context(&redactor, &context, &section, level, json, max_output, None)?;
+                return Ok(());
+            }
 
-    // AI hints (critical for remembering build environment)
-    if !ctx.hints.is_empty() {
-        out.push_str(&format!("**Hint:** {}\n\n", ctx.hints));
-    }
+            let project =
+                Arc::new(ProjectContext::load(root, cli.config.clone()).with_language(cli.lang));
+            if let Some(e) = &project.config_error {
+                eprintln!("Config error: {}", e);
+                std::process::exit(e.exit_code());
+            }
+            let deadline = tool_deadline(&project.config);
+            let ct = CancellationToken::new();
+            // Whole-context JSON has every section; otherwise collect only
+            // what is printed
+            let plan = match (json.is_some() || template.is_some(), section.is_empty()) {
+                (true, true) => CollectionPlan::all(),
+                (_, true) => CollectionPlan::for_level(level, &project.config),
+                (_, false) => CollectionPlan::for_sections(&section, &project.config),
+            };
+            let mut context =
+                collect_context(Arc::clone(&project), plan, deadline, ct.clone()).await;
+            let shown = if section.is_empty() {
+                level_sections(level)
+            } else {
+                &section
+            };
+            if needs_annotation(level, shown) {
+                annotate_context(&project, &mut context, deadline, &ct).await;
+            }
+            let redactor = Redactor::from_config(&project.config);
+            if let Some(template) = &template {
+                print_template(template, &context, &redactor);
+                return Ok(());
+            }
+            let max_output = output_budget(&project.config, max_output_bytes);
+            let timings = if timings {
+                Some(time_collectors(project, deadline, repeat).await)
+            } else {
+                None
+            };
 
-    // Work state is most important for recovery
-    if let Some(ws) = &ctx.work_state {
-        if !ws.task_summary.is_empty() {
-            out.push_str(&format!("**Task:** {}\n", ws.task_summary));
+            print_context(
+                &redactor,
+                &context,
+                &section,
+                level,
+                json,
+                max_output,
+                timings.as_deref().map(|reports| (reports, repeat)),
+            )?;
+
+            if let Some(report) = context
+                .reports
+                .iter()
+                .find(|r| r.status != CollectorStatus::Ok)
+            {
+                eprintln!(
+                    "Partial context: {} collector {}",
+                    report.name,
+                    report.status.as_str()
+                );
+                std::process::exit(EXIT_PARTIAL);
+            }
         }
-        if !ws.working_files.is_empty() {
-            let files: Vec<&str> = ws.working_files.iter().map(|s| s.as_str()).collect();
-            out.push_str(&format!("**Files:** {}\n", files.join(", ")));
+        Some(CliCommand::SaveState { auto: true, .. }) => {
+            // Runs inside a git hook: quiet, quick, never a failing exit
+            let project = ProjectContext::load(root, cli.config.clone()).with_language(cli.lang);
+            let refreshed = match std::env::current_dir() {
+                Ok(repo) => refresh_work_state_for_repo(&project, &repo).map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            if let Err(e) = refreshed {
+                tracing::warn!(error = %e, "failed to refresh work state");
+            }
         }
-        if !ws.notes.is_empty() {
-            out.push_str(&format!("**Notes:** {}\n", ws.notes));
+        Some(CliCommand::SaveState {
+            task, exit_zero, ..
+        }) => {
+            let project =
+                ProjectContext::load(root.clone(), cli.config.clone()).with_language(cli.lang);
+            let working = collect_working_files(&project);
+            let terminal_panes = collect_tmux_panes(&project);
+            let open_files = open_files_now(&project);
+
+            let mut state = WorkState {
+                version: WORK_STATE_VERSION,
+                saved_at: chrono::Utc::now().to_rfc3339(),
+                trigger: "pre_compact".to_string(),
+                task_summary: task.unwrap_or_default(),
+                working_files: working.files,
+                unverified_files: Vec::new(),
+                recent_files: working.recent,
+                notes: String::new(),
+                todos: Vec::new(),
+                next_steps: Vec::new(),
+                project_root: root.to_string_lossy().to_string(),
+                working_repos: working.repos,
+                terminal_panes,
+                open_files,
+                code_todos: Vec::new(),
+                diff_summary: Vec::new(),
+                instance_id: String::new(),
+                notes_updated_at: String::new(),
+                metadata: saved_metadata(&root),
+            };
+            state.code_todos = collect_code_todos(&project, &state.working_files);
+            state.diff_summary = collect_diff_summary(&project, &state.working_files);
+
+            match save_work_state_to_file(&state, &project.config) {
+                Ok(_) => println!(
+                    "Work state saved: {} files tracked",
+                    state.working_files.len()
+                ),
+                Err(e) if exit_zero => tracing::warn!(error = %e, "failed to save work state"),
+                Err(e) => {
+                    eprintln!("Failed to save work state: {}", e);
+                    std::process::exit(e.exit_code());
+                }
+            }
         }
-        out.push('\n');
-    }
-
-    // Show only dirty repos
-    let dirty_repos: Vec<&GitInfo> = ctx.git_repos.iter().filter(|r| r.is_dirty).collect();
-    if !dirty_repos.is_empty() {
-        out.push_str("**Changed repos:** ");
-        let repo_strs: Vec<String> = dirty_repos
-            .iter()
-            .map(|r| format!("{} ({})", r.repo_path, format_git_status(r)))
-            .collect();
-        out.push_str(&repo_strs.join(", "));
-        out.push('\n');
-    }
-
-    // Device (one line)
-    if !ctx.adb_devices.is_empty() {
-        let device = &ctx.adb_devices[0];
-        out.push_str(&format!(
-            "**Device:** {} ({})\n",
-            device.serial, device.device_type
-        ));
-    }
-
-    out.push_str("\n---\n");
-    out.push_str("*Run `get_dev_context` with level=\"normal\" or \"full\" for more details.*\n");
-
-    out
-}
-
-/// Normal format (~400 tokens) - balanced info
-fn format_normal(ctx: &Context) -> String {
-    let mut out = String::new();
-
-    out.push_str("# Development Context\n\n");
-
-    // Work state
-    if let Some(ws) = &ctx.work_state {
-        out.push_str(&format_work_state(ws));
-    }
-
-    // AI Hints
-    if !ctx.hints.is_empty() {
-        out.push_str("## AI Hints\n");
-        out.push_str(&format!("> {}\n\n", ctx.hints));
-    }
-
-    // Git Status (dirty repos only)
-    let dirty_repos: Vec<&GitInfo> = ctx.git_repos.iter().filter(|r| r.is_dirty).collect();
-    if !dirty_repos.is_empty() {
-        out.push_str("## Git Status (changes only)\n\n");
-        out.push_str("| Repository | Branch | Status |\n");
-        out.push_str("|------------|--------|--------|\n");
-        for git in dirty_repos {
-            out.push_str(&format!(
-                "| {} | {} | {} |\n",
-                git.repo_path,
-                git.branch,
-                format_git_status(git)
-            ));
+        Some(CliCommand::Check) => {
+            let code = run_check(&cli, &root);
+            if code != 0 {
+                std::process::exit(code);
+            }
         }
-        out.push('\n');
-    }
-
-    // Active containers
-    if !ctx.containers.is_empty() {
-        out.push_str("## Active Containers\n");
-        for container in &ctx.containers {
-            out.push_str(&format!("- {} ({})\n", container.name, container.status));
+        Some(CliCommand::Doctor { json }) => {
+            if !run_doctor(&root, cli.config.clone(), json.json) {
+                std::process::exit(1);
+            }
         }
-        out.push('\n');
-    }
-
-    // Connected devices
-    if !ctx.adb_devices.is_empty() {
-        out.push_str("## Connected Devices\n");
-        for device in &ctx.adb_devices {
-            out.push_str(&format!(
-                "- {} ({}, {})\n",
-                device.serial, device.state, device.device_type
-            ));
+        Some(CliCommand::Status { all, format }) => {
+            let project = ProjectContext::load(root, cli.config.clone()).with_language(cli.lang);
+            project.warn_config_error();
+            if run_status(&project, all, format.as_deref()) {
+                std::process::exit(1);
+            }
         }
-        out.push('\n');
-    }
-
-    out.push_str("---\n");
-    out.push_str("*Run `get_dev_context` with level=\"full\" for complete information.*\n");
-
-    out
-}
-
-/// Full format (~1000 tokens) - complete information
-fn format_full(ctx: &Context) -> String {
-    let mut out = String::new();
-
-    out.push_str("# Development Context (Full)\n\n");
-
-    // Project info
-    if !ctx.project_name.is_empty() {
-        out.push_str("## Project\n");
-        out.push_str(&format!("- **Name:** {}\n", ctx.project_name));
-        if !ctx.project_type.is_empty() {
-            out.push_str(&format!("- **Type:** {}\n", ctx.project_type));
+        Some(CliCommand::Prompt { format, when }) => {
+            // Config errors are for `doctor`; a prompt stays quiet
+            let project = ProjectContext::load(prompt_root(&root), cli.config.clone())
+                .with_language(cli.lang);
+            if !run_prompt(&project, format.as_deref(), when) && when {
+                std::process::exit(1);
+            }
         }
-        out.push('\n');
-    }
-
-    // Work state
-    if let Some(ws) = &ctx.work_state {
-        out.push_str(&format_work_state(ws));
-    }
-
-    // AI Hints
-    if !ctx.hints.is_empty() {
-        out.push_str("## AI Hints (Important)\n");
-        out.push_str(&format!("> {}\n\n", ctx.hints));
-    }
-
-    // Build targets
-    if !ctx.targets.is_empty() {
-        out.push_str("## Available Build Targets\n\n");
-        out.push_str("| Target | Description | Container | Lunch Target |\n");
-        out.push_str("|--------|-------------|-----------|---------------|\n");
-        for target in &ctx.targets {
-            out.push_str(&format!(
-                "| {} | {} | {} | {} |\n",
-                target.name, target.description, target.container_name, target.lunch_target
-            ));
+        Some(CliCommand::Statusline) => run_statusline(&root, cli.config.clone()),
+        Some(CliCommand::Log { command }) => {
+            // Runs on every prompt: never noisy, never a failing exit
+            if let Err(e) = run_log(&root, cli.config.clone(), command.as_deref()) {
+                tracing::warn!(error = %e, "failed to write the command log");
+            }
         }
-        out.push('\n');
-
-        out.push_str("### Target Capabilities\n");
-        for target in &ctx.targets {
-            let caps: Vec<&str> = [
-                if target.can_emulator {
-                    Some("emulator")
-                } else {
-                    None
-                },
-                if target.can_flash {
-                    Some("flash")
-                } else {
-                    None
-                },
-            ]
-            .into_iter()
-            .flatten()
-            .collect();
-
-            if !caps.is_empty() {
-                out.push_str(&format!("- **{}:** {}\n", target.name, caps.join(", ")));
+        Some(CliCommand::OpenFiles { action }) => {
+            if let Err(e) = run_open_files(&action, &root, cli.config.clone()) {
+                eprintln!("Error: {}", e);
+                std::process::exit(e.exit_code());
             }
         }
-        out.push('\n');
-    }
-
-    // Containers
-    if !ctx.containers.is_empty() {
-        out.push_str("## Active Containers\n");
-        for container in &ctx.containers {
-            out.push_str(&format!(
-                "- **{}** ({}): {}\n",
-                container.name, container.runtime, container.status
-            ));
+        Some(CliCommand::BuildWrap { target, command }) => {
+            let code = run_build_wrap(&root, cli.config.clone(), target.as_deref(), &command);
+            std::process::exit(code);
         }
-        out.push('\n');
-    }
-
-    // Example commands
-    if !ctx.available_commands.is_empty() {
-        out.push_str("## Example Commands\n");
-        out.push_str("```bash\n");
-        for cmd in &ctx.available_commands {
-            out.push_str(&format!("{}\n", cmd));
+        #[cfg(feature = "watch")]
+        Some(CliCommand::Watch(opts)) => {
+            let project =
+                Arc::new(ProjectContext::load(root, cli.config.clone()).with_language(cli.lang));
+            project.warn_config_error();
+            run_watch(project, opts).await?;
         }
-        out.push_str("```\n");
-    }
-
-    // Command history
-    if !ctx.command_history.is_empty() {
-        out.push_str("## Recent Relevant Commands\n");
-        out.push_str(
-            "These commands were executed in previous sessions (useful after context compression):\n\n",
-        );
-        out.push_str("| Time | Command |\n");
-        out.push_str("|------|--------|\n");
-        for entry in &ctx.command_history {
-            let cmd_display = if entry.command.chars().count() > 80 {
-                let truncated: String = entry.command.chars().take(77).collect();
-                format!("{}...", truncated)
-            } else {
-                entry.command.clone()
-            };
-            let cmd_escaped = cmd_display.replace('|', "\\|");
-            out.push_str(&format!("| {} | `{}` |\n", entry.timestamp, cmd_escaped));
+        Some(CliCommand::Targets { json }) => {
+            let project = ProjectContext::load(root, cli.config.clone()).with_language(cli.lang);
+            project.warn_config_error();
+            run_targets(&project, json.output())?;
         }
-        out.push('\n');
-    }
-
-    // Git information (ALL repositories)
-    if !ctx.git_repos.is_empty() {
-        out.push_str("## Git Status\n\n");
-        out.push_str("| Repository | Branch | Status | Last Commit |\n");
-        out.push_str("|------------|--------|--------|-------------|\n");
-
-        for git in &ctx.git_repos {
-            let commit = git.last_commit_short.replace('|', "\\|");
-            out.push_str(&format!(
-                "| {} | {} | {} | {} |\n",
-                git.repo_path,
-                git.branch,
-                format_git_status(git),
-                commit
-            ));
+        Some(CliCommand::Repos { json, refresh }) => {
+            let project = ProjectContext::load(root, cli.config.clone()).with_language(cli.lang);
+            project.warn_config_error();
+            run_repos(&project, json.output(), refresh)?;
         }
-        out.push('\n');
-    }
-
-    // ADB/Fastboot devices
-    if !ctx.adb_devices.is_empty() {
-        out.push_str("## Connected Devices\n");
-        out.push_str("| Serial | State | Type |\n");
-        out.push_str("|--------|-------|------|\n");
-        for device in &ctx.adb_devices {
-            out.push_str(&format!(
-                "| {} | {} | {} |\n",
-                device.serial, device.state, device.device_type
-            ));
+        Some(CliCommand::Diary { since, json }) => {
+            let digest = match recent_activity(&since, None) {
+                Ok(digest) => digest,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let project = ProjectContext::load(root, cli.config.clone()).with_language(cli.lang);
+            project.warn_config_error();
+            let redactor = Redactor::from_config(&project.config);
+            if let Some(output) = json.output() {
+                print_list(output, &digest, &digest, Some(&redactor))?;
+            } else {
+                print!("{}", redactor.redact(&format_diary(&digest)));
+            }
         }
-        out.push('\n');
-    }
-
-    out
-}
-
-/// Main formatter dispatcher
-fn format_context_markdown(ctx: &Context, level: &str) -> String {
-    match level {
-        "minimal" => format_minimal(ctx),
-        "normal" => format_normal(ctx),
-        "full" => format_full(ctx),
-        _ => format_normal(ctx), // Default to normal
-    }
-}
-
-// ============================================================================
-// Config Reader
-// ============================================================================
-
-fn read_config() -> Config {
-    let paths = [
-        "contextkeeper.toml",
-        "context-keeper.toml",
-        ".contextkeeper.toml",
-    ];
-
-    for path in paths {
-        if Path::new(path).exists() {
-            if let Ok(content) = fs::read_to_string(path) {
-                if let Ok(config) = toml::from_str(&content) {
-                    return config;
+        Some(CliCommand::Stats { since, json }) => {
+            let window = match parse_since(&since) {
+                Ok(window) => window,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(EXIT_USAGE);
                 }
+            };
+            let (records, skipped) = match load_tool_calls() {
+                Ok(loaded) => loaded,
+                Err(e) => {
+                    eprintln!("Error: {}: {}", stats_path().display(), e);
+                    std::process::exit(EXIT_IO);
+                }
+            };
+            let digest = build_stats_digest(&records, skipped, chrono::Utc::now() - window);
+            if let Some(style) = json.json {
+                print_json(&digest, style)?;
+            } else {
+                print!("{}", format_tool_stats(&digest));
             }
         }
-    }
-
-    Config::default()
-}
-
-// ============================================================================
-// MCP Server Implementation
-// ============================================================================
-
-#[derive(Clone)]
-pub struct ContextKeeperService {
-    tool_router: ToolRouter<Self>,
-}
-
-impl Default for ContextKeeperService {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-#[tool_router]
-impl ContextKeeperService {
-    pub fn new() -> Self {
-        Self {
-            tool_router: Self::tool_router(),
+        Some(CliCommand::GitHooks { action }) => {
+            let project = ProjectContext::load(root, cli.config.clone()).with_language(cli.lang);
+            project.warn_config_error();
+            if let Err(e) = run_git_hooks(&action, &project, &cli.write) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
         }
-    }
-
-    #[tool(
-        description = "Get development context. Use level='minimal' after compression (~200 tokens), 'normal' for balanced info (~400 tokens), or 'full' for complete details (~1000 tokens). Default is 'normal'."
-    )]
-    async fn get_dev_context(
-        &self,
-        params: Parameters<GetDevContextParams>,
-    ) -> Result<CallToolResult, McpError> {
-        let config = read_config();
-        let context = collect_context(&config);
-        let level_str = params.0.level.as_deref().unwrap_or("normal");
-        let markdown = format_context_markdown(&context, level_str);
-
-        Ok(CallToolResult::success(vec![Content::text(markdown)]))
-    }
-
-    #[tool(
-        description = "Save current work state for recovery after context compression. Call this before compression or at task milestones."
-    )]
-    async fn save_work_state(
-        &self,
-        params: Parameters<SaveWorkStateParams>,
-    ) -> Result<CallToolResult, McpError> {
-        let SaveWorkStateParams {
-            task_summary,
-            working_files,
-            notes,
-            todos,
-        } = params.0;
-
-        // Parse todos if provided
-        let todo_items: Vec<TodoItem> = todos
-            .and_then(|t| serde_json::from_str(&t).ok())
-            .unwrap_or_default();
-
-        // Auto-collect working files if not provided
-        let files = working_files.unwrap_or_else(collect_working_files);
-
-        let state = WorkState {
-            saved_at: chrono::Utc::now().to_rfc3339(),
-            trigger: "manual".to_string(),
-            task_summary,
-            working_files: files,
-            notes: notes.unwrap_or_default(),
-            todos: todo_items,
-        };
-
-        match save_work_state_to_file(&state) {
-            Ok(_) => Ok(CallToolResult::success(vec![Content::text(format!(
-                "Work state saved successfully.\n\n\
-                - Task: {}\n\
-                - Files: {}\n\
-                - Todos: {} items\n\n\
-                This state will be included in `get_dev_context` output after compression.",
-                state.task_summary,
-                state.working_files.len(),
-                state.todos.len()
-            ))])),
-            Err(e) => Ok(CallToolResult::success(vec![Content::text(format!(
-                "Failed to save work state: {}",
-                e
-            ))])),
+        Some(CliCommand::Export { action }) => {
+            let project =
+                Arc::new(ProjectContext::load(root, cli.config.clone()).with_language(cli.lang));
+            project.warn_config_error();
+            if let Err(e) = run_export(&action, project, &cli.write).await {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
         }
-    }
-}
-
-#[tool_handler]
-impl ServerHandler for ContextKeeperService {
-    fn get_info(&self) -> ServerInfo {
-        ServerInfo {
-            protocol_version: ProtocolVersion::LATEST,
-            capabilities: ServerCapabilities::builder().enable_tools().build(),
-            server_info: Implementation {
-                name: "context-keeper".into(),
-                version: env!("CARGO_PKG_VERSION").into(),
-                ..Default::default()
-            },
-            instructions: Some(
-                "ContextKeeper provides development environment context. \
-                 Call get_dev_context to retrieve build targets, containers, \
-                 and recent commands."
-                    .into(),
-            ),
+        Some(CliCommand::Env(opts)) => {
+            let project =
+                Arc::new(ProjectContext::load(root, cli.config.clone()).with_language(cli.lang));
+            project.warn_config_error();
+            if let Err(e) = run_env(&opts, project).await {
+                eprintln!("Error: {}", e);
+                std::process::exit(e.exit_code());
+            }
         }
-    }
-}
-
-// ============================================================================
-// Init Wizard
-// ============================================================================
-
-/// Detect project type based on directory contents
-fn detect_project_type() -> Option<&'static str> {
-    // Check for AOSP
-    if Path::new("build/envsetup.sh").exists() || Path::new("build/make/envsetup.sh").exists() {
-        return Some("aosp");
-    }
-
-    // Check for ROS/ROS2
-    if Path::new("package.xml").exists() {
-        return Some("ros");
-    }
-    if Path::new("src").is_dir() {
-        // Check for colcon/catkin workspace
-        if let Ok(entries) = fs::read_dir("src") {
-            for entry in entries.flatten() {
-                let pkg_xml = entry.path().join("package.xml");
-                if pkg_xml.exists() {
-                    return Some("ros");
-                }
+        Some(CliCommand::Migrate(opts)) => {
+            if let Err(e) = run_migrate(&opts, &root) {
+                eprintln!("Error: {}", e);
+                std::process::exit(e.exit_code());
             }
         }
-    }
-
-    // Check for Yocto
-    if Path::new("meta").is_dir() || Path::new("poky").is_dir() {
-        return Some("yocto");
-    }
-    if let Ok(entries) = fs::read_dir(".") {
-        for entry in entries.flatten() {
-            let name = entry.file_name();
-            if name.to_string_lossy().starts_with("meta-") {
-                return Some("yocto");
+        Some(CliCommand::Hooks { action }) => {
+            if let Err(e) = run_hooks(&action, &root, &cli.write) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
             }
         }
-    }
-
-    None
-}
-
-/// Detect available container runtime
-fn detect_container_runtime() -> Option<&'static str> {
-    // Check podman first (preferred for rootless)
-    if std::process::Command::new("podman")
-        .arg("--version")
-        .output()
-        .map(|o| o.status.success())
-        .unwrap_or(false)
-    {
-        return Some("podman");
-    }
-
-    // Check docker
-    if std::process::Command::new("docker")
-        .arg("--version")
-        .output()
-        .map(|o| o.status.success())
-        .unwrap_or(false)
-    {
-        return Some("docker");
-    }
-
-    None
-}
-
-/// Get current directory name as default project name
-fn get_default_project_name() -> String {
-    std::env::current_dir()
-        .ok()
-        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
-        .unwrap_or_else(|| "my-project".to_string())
-}
-
-/// Prompt user for input with default value
-fn prompt(question: &str, default: &str) -> String {
-    if default.is_empty() {
-        print!("{}: ", question);
-    } else {
-        print!("{} [{}]: ", question, default);
-    }
-    io::stdout().flush().unwrap();
-
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    let input = input.trim();
-
-    if input.is_empty() {
-        default.to_string()
-    } else {
-        input.to_string()
-    }
-}
-
-/// Prompt for yes/no with default
-fn prompt_yes_no(question: &str, default: bool) -> bool {
-    let default_str = if default { "Y/n" } else { "y/N" };
-    print!("{} [{}]: ", question, default_str);
-    io::stdout().flush().unwrap();
-
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    let input = input.trim().to_lowercase();
-
-    match input.as_str() {
-        "y" | "yes" => true,
-        "n" | "no" => false,
-        _ => default,
-    }
-}
-
-/// Generate default history patterns based on project type
-fn get_default_history_patterns(project_type: &str) -> Vec<&'static str> {
-    match project_type {
-        "aosp" => vec![
-            r"lunch\s+\S+",
-            r"source.*envsetup",
-            r"export\s+\w+=",
-            r"m\s+\S+",
-            r"mm\b",
-            r"mma\b",
-        ],
-        "ros" => vec![
-            r"source.*setup\.bash",
-            r"colcon\s+build",
-            r"catkin_make",
-            r"ros2\s+run",
-            r"ros2\s+launch",
-            r"roslaunch",
-        ],
-        "yocto" => vec![
-            r"source.*oe-init",
-            r"bitbake\s+\S+",
-            r"MACHINE=",
-            r"devtool\s+\S+",
-        ],
-        _ => vec![r"export\s+\w+=", r"source\s+"],
-    }
-}
-
-/// Run the init wizard
-fn run_init_wizard() -> io::Result<()> {
-    println!("\n🔧 ContextKeeper Setup Wizard\n");
-
-    // Check if config already exists
-    if Path::new("contextkeeper.toml").exists()
-        && !prompt_yes_no("contextkeeper.toml already exists. Overwrite?", false)
-    {
-        println!("Aborted.");
-        return Ok(());
-    }
-
-    // Project name
-    let default_name = get_default_project_name();
-    let project_name = prompt("Project name", &default_name);
-
-    // Project type
-    let detected_type = detect_project_type();
-    let type_hint = detected_type
-        .map(|t| format!("detected: {}", t))
-        .unwrap_or_else(|| "aosp/ros/yocto/custom".to_string());
-    let project_type = prompt(
-        &format!("Project type ({})", type_hint),
-        detected_type.unwrap_or("custom"),
-    );
-
-    // Container runtime
-    let detected_runtime = detect_container_runtime();
-    let runtime_hint = detected_runtime
-        .map(|r| format!("detected: {}", r))
-        .unwrap_or_else(|| "podman/docker/none".to_string());
-    let container_runtime = prompt(
-        &format!("Container runtime ({})", runtime_hint),
-        detected_runtime.unwrap_or("none"),
-    );
-
-    // Build scripts (optional)
-    let entry_point = prompt("Build script entry point (optional)", "");
-    let config_dir = if !entry_point.is_empty() {
-        prompt("Config directory (optional)", "")
-    } else {
-        String::new()
-    };
-
-    // AI hints
-    let default_hint = if container_runtime != "none" {
-        "Build commands must be executed inside the container."
-    } else {
-        ""
-    };
-    let ai_hint = prompt("AI hint for this project", default_hint);
-
-    // Generate TOML
-    let mut toml_content = String::new();
-
-    toml_content.push_str("# ContextKeeper Configuration\n");
-    toml_content.push_str("# https://github.com/sat0sh-dev/context-keeper\n\n");
-
-    toml_content.push_str("[project]\n");
-    toml_content.push_str(&format!("name = \"{}\"\n", project_name));
-    toml_content.push_str(&format!("type = \"{}\"\n", project_type));
-    toml_content.push('\n');
-
-    if !entry_point.is_empty() {
-        toml_content.push_str("[scripts]\n");
-        toml_content.push_str(&format!("entry_point = \"{}\"\n", entry_point));
-        if !config_dir.is_empty() {
-            toml_content.push_str(&format!("config_dir = \"{}\"\n", config_dir));
-            toml_content.push_str("config_pattern = \"*.conf\"\n");
+        Some(CliCommand::Man { out_dir }) => run_man(out_dir.as_deref())?,
+        Some(CliCommand::Completions { shell }) => {
+            // A reader that stops early (`| head`) just ends the output
+            match io::stdout().write_all(completion_script(shell).as_bytes()) {
+                Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Some(CliCommand::Serve { http }) => {
+            let service = ContextKeeperService::new(root, cli.config.clone(), cli.lang);
+            serve(service, http).await?;
+        }
+        None => {
+            let service = ContextKeeperService::new(root, cli.config.clone(), cli.lang);
+            serve(service, None).await?;
         }
-        toml_content.push('\n');
-    }
-
-    if container_runtime != "none" {
-        toml_content.push_str("[containers]\n");
-        toml_content.push_str(&format!("runtime = \"{}\"\n", container_runtime));
-        toml_content.push('\n');
-    }
-
-    if !ai_hint.is_empty() {
-        toml_content.push_str("[hints]\n");
-        toml_content.push_str(&format!("default = \"{}\"\n", ai_hint));
-        toml_content.push('\n');
-    }
-
-    // History config with type-appropriate patterns
-    toml_content.push_str("[history]\n");
-    toml_content.push_str("enabled = true\n");
-    toml_content.push_str("patterns = [\n");
-    for pattern in get_default_history_patterns(&project_type) {
-        // Escape backslashes for TOML
-        let escaped = pattern.replace('\\', "\\\\");
-        toml_content.push_str(&format!("    \"{}\",\n", escaped));
     }
-    toml_content.push_str("]\n");
-    toml_content.push_str("max_entries = 20\n");
-    toml_content.push('\n');
-
-    // Git config
-    toml_content.push_str("[git]\n");
-    toml_content.push_str("auto_detect = true\n");
-    toml_content.push_str("scan_depth = 2\n");
-
-    // Write file
-    fs::write("contextkeeper.toml", &toml_content)?;
-
-    println!("\n✅ Created contextkeeper.toml");
-    println!("\nNext steps:");
-    println!("  1. Review and customize contextkeeper.toml");
-    println!("  2. Test with: context-keeper --context");
-    println!("  3. Add to Claude Code: ./install.sh (or see README)");
 
     Ok(())
 }
 
-// ============================================================================
-// Main
-// ============================================================================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = std::env::args().collect();
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        let args = std::iter::once("context-keeper")
+            .chain(args.iter().copied())
+            .map(str::to_string)
+            .collect();
+        Cli::try_parse_from(normalize_legacy_args(args))
+    }
+
+    #[test]
+    fn legacy_context_flag_with_and_without_a_level() {
+        for flag in ["--context", "-c"] {
+            let cli = parse(&[flag]).unwrap();
+            assert!(
+                matches!(cli.command, Some(CliCommand::Context { level: None, .. })),
+                "{}",
+                flag
+            );
+            let cli = parse(&[flag, "full"]).unwrap();
+            assert!(
+                matches!(
+                    cli.command,
+                    Some(CliCommand::Context {
+                        level: Some(Level::Full),
+                        ..
+                    })
+                ),
+                "{}",
+                flag
+            );
+        }
+        // Anything else after it is not a level
+        let cli = parse(&["--context", "--json"]).unwrap();
+        let Some(CliCommand::Context { level, json, .. }) = cli.command else {
+            panic!("not context");
+        };
+        assert_eq!(level, None);
+        assert!(json.json.is_some());
+        assert!(parse(&["-c", "huge"]).is_err());
+    }
+
+    #[test]
+    fn legacy_save_state_flags() {
+        let cli = parse(&["--save-state", "fix the audio underrun"]).unwrap();
+        let Some(CliCommand::SaveState {
+            task,
+            exit_zero,
+            auto,
+        }) = cli.command
+        else {
+            panic!("not save-state");
+        };
+        assert_eq!(task.as_deref(), Some("fix the audio underrun"));
+        assert!(exit_zero && !auto);
+
+        let cli = parse(&["--save-state-auto"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(CliCommand::SaveState {
+                task: None,
+                exit_zero: false,
+                auto: true
+            })
+        ));
 
-    // Init wizard mode
-    // Usage: context-keeper init
-    if args.iter().any(|arg| arg == "init") {
-        run_init_wizard()?;
-        return Ok(());
+        // The new spelling keeps the exit codes
+        let cli = parse(&["save-state", "task"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(CliCommand::SaveState {
+                exit_zero: false,
+                ..
+            })
+        ));
     }
 
-    // CLI mode: output context directly
-    // Usage: context-keeper --context [minimal|normal|full]
-    if args.iter().any(|arg| arg == "--context" || arg == "-c") {
-        let config = read_config();
-        let context = collect_context(&config);
-
-        // Check for level argument
-        let level = args
-            .iter()
-            .position(|arg| arg == "--context" || arg == "-c")
-            .and_then(|i| args.get(i + 1))
-            .map(|s| s.as_str())
-            .unwrap_or("normal");
-
-        println!("{}", format_context_markdown(&context, level));
-        return Ok(());
+    #[test]
+    fn nothing_after_a_double_dash_is_rewritten() {
+        let cli = parse(&["build-wrap", "--", "sh", "-c", "--save-state"]).unwrap();
+        let Some(CliCommand::BuildWrap { command, .. }) = cli.command else {
+            panic!("not build-wrap");
+        };
+        assert_eq!(command, ["sh", "-c", "--save-state"]);
+    }
+
+    #[test]
+    fn init_flags() {
+        let cli = parse(&[
+            "init",
+            "--yes",
+            "--name",
+            "pixel",
+            "--type",
+            "aosp,kernel",
+            "--runtime",
+            "podman",
+        ])
+        .unwrap();
+        let Some(CliCommand::Init(opts)) = cli.command else {
+            panic!("not init");
+        };
+        assert!(opts.yes && !opts.force);
+        assert_eq!(opts.name.as_deref(), Some("pixel"));
+        assert_eq!(opts.project_type.as_deref(), Some("aosp,kernel"));
+        assert_eq!(opts.runtime.as_deref(), Some("podman"));
+        assert_eq!(opts.entry_point, None);
+        assert!(parse(&["init", "--name"]).is_err());
     }
 
-    // Save state mode (for PreCompact hook)
-    // Usage: context-keeper --save-state "task description"
-    if let Some(pos) = args.iter().position(|arg| arg == "--save-state") {
-        let task_summary = args.get(pos + 1).cloned().unwrap_or_default();
-        let files = collect_working_files();
-
-        let state = WorkState {
-            saved_at: chrono::Utc::now().to_rfc3339(),
-            trigger: "pre_compact".to_string(),
-            task_summary,
-            working_files: files,
-            notes: String::new(),
-            todos: Vec::new(),
-        };
+    #[test]
+    fn verbose_and_quiet() {
+        assert_eq!(parse(&["-vv", "check"]).unwrap().verbose, 2);
+        // Global, so also after the subcommand
+        let cli = parse(&["check", "--verbose"]).unwrap();
+        assert_eq!(cli.verbose, 1);
+        assert!(!cli.quiet);
+        assert!(parse(&["-q", "check"]).unwrap().quiet);
 
-        match save_work_state_to_file(&state) {
-            Ok(_) => println!(
-                "Work state saved: {} files tracked",
-                state.working_files.len()
-            ),
-            Err(e) => eprintln!("Failed to save work state: {}", e),
-        }
-        return Ok(());
+        let error = parse(&["-v", "--quiet", "check"]).unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::ArgumentConflict);
     }
 
-    // MCP Server mode (default)
-    let service = ContextKeeperService::new();
-    let server = service.serve(stdio()).await?;
-    server.waiting().await?;
-
-    Ok(())
+    #[test]
+    fn no_subcommand_serves() {
+        assert!(parse(&[]).unwrap().command.is_none());
+    }
 }
@@ -0,0 +1,168 @@
+//! JSON output of the CLI commands
+//!
+//! `--json` is pretty-printed for people and `--json=compact` puts the
+//! whole value on one line for pipelines. Commands whose result is a list
+//! (`repos`, `targets`, `diary`) also take `--ndjson`, which writes one
+//! compact object per line as it goes, so a consumer can handle each item
+//! without reading the rest. A reader that goes away early (`| head -1`)
+//! just ends the output.
+
+use crate::redact::Redactor;
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// Layout of `--json`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum JsonStyle {
+    /// Indented, for reading
+    #[default]
+    Pretty,
+    /// On one line
+    Compact,
+}
+
+/// `--json[=STYLE]` of a command whose result is one value
+#[derive(Debug, Clone, Copy, Default, clap::Args)]
+pub struct JsonArgs {
+    /// Print as JSON: pretty (the default) or compact, on one line
+    #[arg(
+        long,
+        value_enum,
+        value_name = "STYLE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "pretty"
+    )]
+    pub json: Option<JsonStyle>,
+}
+
+/// `--json[=STYLE]` and `--ndjson` of a command whose result is a list
+#[derive(Debug, Clone, Copy, Default, clap::Args)]
+pub struct ListJsonArgs {
+    /// Print as JSON: pretty (the default) or compact, on one line
+    #[arg(
+        long,
+        value_enum,
+        value_name = "STYLE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "pretty",
+        conflicts_with = "ndjson"
+    )]
+    pub json: Option<JsonStyle>,
+    /// Print one JSON object per line, each as soon as it is written
+    #[arg(long)]
+    pub ndjson: bool,
+}
+
+/// How a list-shaped result is printed as JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonOutput {
+    /// The whole result as one value
+    Value(JsonStyle),
+    /// One item per line
+    Lines,
+}
+
+impl ListJsonArgs {
+    /// `None` for the text output
+    pub fn output(&self) -> Option<JsonOutput> {
+        if self.ndjson {
+            return Some(JsonOutput::Lines);
+        }
+        self.json.map(JsonOutput::Value)
+    }
+}
+
+/// A result made of items, which `--ndjson` prints one per line
+pub trait JsonList {
+    type Item: Serialize;
+
+    /// The items in output order
+    fn json_items(&self) -> impl Iterator<Item = &Self::Item>;
+}
+
+impl<T: Serialize> JsonList for Vec<T> {
+    type Item = T;
+
+    fn json_items(&self) -> impl Iterator<Item = &T> {
+        self.iter()
+    }
+}
+
+/// A closed stdout ends the output without an error
+fn ignore_broken_pipe(result: serde_json::Result<()>) -> serde_json::Result<()> {
+    match result {
+        Err(e) if e.io_error_kind() == Some(io::ErrorKind::BrokenPipe) => Ok(()),
+        result => result,
+    }
+}
+
+/// `value` in `style`, then a newline
+pub fn write_json<W: Write, T: Serialize + ?Sized>(
+    out: &mut W,
+    value: &T,
+    style: JsonStyle,
+) -> serde_json::Result<()> {
+    match style {
+        JsonStyle::Pretty => serde_json::to_writer_pretty(&mut *out, value)?,
+        JsonStyle::Compact => serde_json::to_writer(&mut *out, value)?,
+    }
+    out.write_all(b"\n").map_err(serde_json::Error::io)
+}
+
+/// Each item of `list` as one compact line, flushed as it is written.
+/// With a `redactor`, values it matches are redacted in each item.
+pub fn write_ndjson<W: Write, L: JsonList + ?Sized>(
+    out: &mut W,
+    list: &L,
+    redactor: Option<&Redactor>,
+) -> serde_json::Result<()> {
+    for item in list.json_items() {
+        match redactor {
+            Some(redactor) => {
+                let mut value = serde_json::to_value(item)?;
+                redactor.redact_json(&mut value);
+                serde_json::to_writer(&mut *out, &value)?;
+            }
+            None => serde_json::to_writer(&mut *out, item)?,
+        }
+        out.write_all(b"\n").map_err(serde_json::Error::io)?;
+        out.flush().map_err(serde_json::Error::io)?;
+    }
+    Ok(())
+}
+
+/// `write_json` to stdout
+pub fn print_json<T: Serialize + ?Sized>(value: &T, style: JsonStyle) -> serde_json::Result<()> {
+    ignore_broken_pipe(write_json(&mut io::stdout().lock(), value, style))
+}
+
+/// `write_ndjson` to stdout
+pub fn print_ndjson<L: JsonList + ?Sized>(
+    list: &L,
+    redactor: Option<&Redactor>,
+) -> serde_json::Result<()> {
+    ignore_broken_pipe(write_ndjson(&mut io::stdout().lock(), list, redactor))
+}
+
+/// `list` as `output` says: the whole `value` (which may say more than
+/// the items, e.g. warnings) in its style, or the items one per line
+pub fn print_list<L: JsonList + ?Sized, T: Serialize + ?Sized>(
+    output: JsonOutput,
+    list: &L,
+    value: &T,
+    redactor: Option<&Redactor>,
+) -> serde_json::Result<()> {
+    match output {
+        JsonOutput::Value(style) => match redactor {
+            Some(redactor) => {
+                let mut value = serde_json::to_value(value)?;
+                redactor.redact_json(&mut value);
+                print_json(&value, style)
+            }
+            None => print_json(value, style),
+        },
+        JsonOutput::Lines => print_ndjson(list, redactor),
+    }
+}
@@ -0,0 +1,110 @@
+//! Lossless path handling with a marked lossy form for display
+
+use std::path::{Component, Path, PathBuf};
+
+/// Appended when a name had to be converted lossily for display
+pub const NON_UTF8_MARKER: &str = " [non-UTF-8]";
+
+/// Appended to a path outside the project root, which is kept absolute
+pub const OUTSIDE_ROOT_MARKER: &str = " [outside project]";
+
+/// `path` for output. Paths that aren't valid UTF-8 keep their replacement
+/// characters and get a marker, so they aren't mistaken for the real name.
+pub fn display_path(path: &Path) -> String {
+    match path.to_str() {
+        Some(path) => path.to_string(),
+        None => format!("{}{}", path.to_string_lossy(), NON_UTF8_MARKER),
+    }
+}
+
+/// Path bytes as git prints them: raw on Unix, '/'-separated UTF-8 elsewhere
+pub fn path_bytes(path: &Path) -> Vec<u8> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes().to_vec()
+    }
+    #[cfg(not(unix))]
+    {
+        path.to_string_lossy().replace('\\', "/").into_bytes()
+    }
+}
+
+/// The path git printed as `bytes`, the inverse of `path_bytes`
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+    }
+    #[cfg(not(unix))]
+    {
+        PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+/// Check that a project directory (`--cwd`, a tool's `project_root`)
+/// exists and can be listed, and make it absolute so the saved work state
+/// and git paths don't depend on where we were started
+pub fn project_dir(value: &str) -> Result<PathBuf, String> {
+    let path = Path::new(value);
+    if !path.is_dir() {
+        return Err(format!("{} is not a directory", value));
+    }
+    std::fs::read_dir(path).map_err(|e| format!("cannot read {}: {}", value, e))?;
+    path.canonicalize()
+        .map_err(|e| format!("cannot resolve {}: {}", value, e))
+}
+
+/// `path` without its `OUTSIDE_ROOT_MARKER`, if it has one
+pub fn strip_outside_marker(path: &Path) -> &Path {
+    match path
+        .to_str()
+        .and_then(|p| p.strip_suffix(OUTSIDE_ROOT_MARKER))
+    {
+        Some(path) => Path::new(path),
+        None => path,
+    }
+}
+
+/// `.` and `..` resolved without touching the filesystem; repeated
+/// separators are already gone in `components`
+fn lexical(path: &Path) -> PathBuf {
+    let mut clean = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match clean.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    clean.pop();
+                }
+                // `/..` is `/`
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => clean.push(".."),
+            },
+            component => clean.push(component),
+        }
+    }
+    clean
+}
+
+/// `path` as output names it: relative to the project `root`, without `./`
+/// or repeated separators, `.` for the root itself. A relative `path` is
+/// taken against `root`. Only the root's symlinks are resolved, so a link
+/// inside the project keeps its own name. A path that leaves the root stays
+/// absolute and ends in `OUTSIDE_ROOT_MARKER`, which
+/// `ProjectContext::resolve` drops again. Applying it to its own output
+/// changes nothing.
+pub fn project_relative(root: &Path, path: &Path) -> String {
+    let joined = lexical(&root.join(strip_outside_marker(path)));
+    let roots = [Some(lexical(root)), root.canonicalize().ok()];
+    for root in roots.iter().flatten() {
+        if let Ok(relative) = joined.strip_prefix(root) {
+            return match display_path(relative) {
+                relative if relative.is_empty() => ".".to_string(),
+                relative => relative,
+            };
+        }
+    }
+    format!("{}{}", display_path(&joined), OUTSIDE_ROOT_MARKER)
+}
@@ -0,0 +1,366 @@
+//! Tool calls that stop being about the project the server started for
+//!
+//! Claude Code can restart the MCP server from another directory than the
+//! one the conversation is about, and every collector then describes the
+//! wrong project without a word. The server records where it started; a
+//! tool call that falls back to the server's default root is checked
+//! against that record, and against the project name the previous call
+//! saw. The reply opens with a warning naming both sides, and the tools
+//! that write (`save_work_state`, `bootstrap_config`) refuse until the
+//! call names its `project_root`. A root the call names, or the client's
+//! first MCP root, was picked on purpose: only the name is checked for the
+//! latter, and nothing for the former.
+
+use crate::config::ProjectContext;
+use crate::error::ContextKeeperError;
+use std::path::{Path, PathBuf};
+
+/// Where the server started
+#[derive(Debug, Clone, Default)]
+pub struct StartedFor {
+    /// The default project root, canonical
+    pub root: PathBuf,
+    /// Working directory at start, when the default root came from it
+    pub cwd: Option<PathBuf>,
+    /// Config file the default project loaded
+    pub config_path: Option<PathBuf>,
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl StartedFor {
+    /// Record the default project as the server starts
+    pub fn capture(project: &ProjectContext) -> Self {
+        let root = canonical(&project.root);
+        let cwd = std::env::current_dir()
+            .ok()
+            .map(|cwd| canonical(&cwd))
+            .filter(|cwd| *cwd == root);
+        Self {
+            root,
+            cwd,
+            config_path: project.config_path.clone(),
+        }
+    }
+}
+
+/// Where a call's project root came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RootSource {
+    /// The call's `project_root`
+    Explicit,
+    /// The client's first MCP root
+    ClientRoot,
+    /// The server's default root, from its cwd or `--root`
+    Default,
+}
+
+/// One way a call's project differs from what the server started for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RootDrift {
+    /// The working directory the default root came from was moved or
+    /// removed; `now` is `None` when it can't be read
+    CwdChanged { was: PathBuf, now: Option<PathBuf> },
+    /// The call fell back to a default root other than the one at start
+    RootChanged { started: PathBuf, now: PathBuf },
+    /// The config file loaded at start is no longer there
+    ConfigGone { path: PathBuf },
+    /// `[project] name` differs from the previous call's
+    NameChanged { was: String, now: String },
+}
+
+impl RootDrift {
+    /// Whether the call's project may not be the one the server served:
+    /// writes are refused for these. A new name alone only warns.
+    pub fn mismatched_root(&self) -> bool {
+        !matches!(self, RootDrift::NameChanged { .. })
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            RootDrift::CwdChanged { was, now } => format!(
+                "the server started in `{}`, which is now {}",
+                was.display(),
+                match now {
+                    Some(now) => format!("`{}`", now.display()),
+                    None => "gone".to_string(),
+                }
+            ),
+            RootDrift::RootChanged { started, now } => format!(
+                "the server started for `{}`, but this call resolved `{}`",
+                started.display(),
+                now.display()
+            ),
+            RootDrift::ConfigGone { path } => format!(
+                "the config `{}` loaded at start no longer exists",
+                path.display()
+            ),
+            RootDrift::NameChanged { was, now } => format!(
+                "the project name changed from `{}` to `{}` since the previous call",
+                was, now
+            ),
+        }
+    }
+}
+
+/// `[project] name`, "none" without one
+pub fn configured_name(project: &ProjectContext) -> String {
+    project
+        .config
+        .project
+        .as_ref()
+        .and_then(|p| p.name.clone())
+        .unwrap_or_else(|| "none".to_string())
+}
+
+/// How `project`, whose root came from `source`, differs from `started`;
+/// `previous_name` is the previous checked call's `configured_name`. The
+/// start directory is only compared with when the root fell back to it,
+/// and the start config only when the call is for the same root.
+pub fn detect_root_drift(
+    started: &StartedFor,
+    project: &ProjectContext,
+    source: RootSource,
+    previous_name: Option<&str>,
+) -> Vec<RootDrift> {
+    let mut drift = Vec::new();
+    if source == RootSource::Explicit {
+        return drift;
+    }
+    let root = canonical(&project.root);
+    if let Some(was) = started
+        .cwd
+        .as_ref()
+        .filter(|_| source == RootSource::Default)
+    {
+        let now = std::env::current_dir().ok().map(|cwd| canonical(&cwd));
+        if now.as_ref() != Some(was) {
+            drift.push(RootDrift::CwdChanged {
+                was: was.clone(),
+                now,
+            });
+        }
+    }
+    if source == RootSource::Default && root != started.root {
+        drift.push(RootDrift::RootChanged {
+            started: started.root.clone(),
+            now: root.clone(),
+        });
+    }
+    if let Some(path) = started
+        .config_path
+        .as_ref()
+        .filter(|path| root == started.root && !path.exists())
+    {
+        drift.push(RootDrift::ConfigGone { path: path.clone() });
+    }
+    let name = configured_name(project);
+    if let Some(was) = previous_name.filter(|was| *was != name) {
+        drift.push(RootDrift::NameChanged {
+            was: was.to_string(),
+            now: name,
+        });
+    }
+    drift
+}
+
+/// The warning a reply opens with, empty without drift
+pub fn format_root_drift(drift: &[RootDrift]) -> String {
+    match drift {
+        [] => String::new(),
+        [one] => format!(
+            "> ⚠️ **Project may have changed:** {}. Pass `project_root`, or restart \
+             the server from the project's directory.\n\n",
+            one.describe()
+        ),
+        many => {
+            let mut out = String::from("> ⚠️ **Project may have changed:**\n");
+            for drift in many {
+                out.push_str(&format!("> - {}\n", drift.describe()));
+            }
+            out.push_str(
+                "> Pass `project_root`, or restart the server from the project's directory.\n\n",
+            );
+            out
+        }
+    }
+}
+
+/// An error for a write (`action`, e.g. "save the work state") under a
+/// root that may be the wrong one; `Ok` when `drift` has none
+pub fn refuse_drifted_write(drift: &[RootDrift], action: &str) -> Result<(), ContextKeeperError> {
+    let reasons: Vec<String> = drift
+        .iter()
+        .filter(|d| d.mismatched_root())
+        .map(RootDrift::describe)
+        .collect();
+    if reasons.is_empty() {
+        return Ok(());
+    }
+    Err(ContextKeeperError::Validation(format!(
+        "refusing to {}: {}. Pass `project_root` naming the project it is for.",
+        action,
+        reasons.join("; ")
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// A project at `dir/name` with `[project] name = name`
+    fn project(dir: &Path, name: &str) -> ProjectContext {
+        let root = dir.join(name);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("contextkeeper.toml"),
+            format!("[project]\nname = \"{}\"\n", name),
+        )
+        .unwrap();
+        ProjectContext::load(root, None)
+    }
+
+    /// Started for `project`, from a working directory that isn't the
+    /// test's when `cwd` is given
+    fn started(project: &ProjectContext, cwd: Option<&Path>) -> StartedFor {
+        StartedFor {
+            root: canonical(&project.root),
+            cwd: cwd.map(Path::to_path_buf),
+            config_path: project.config_path.clone(),
+        }
+    }
+
+    #[test]
+    fn each_drift_on_a_fallback_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = project(dir.path(), "app");
+        let other = project(dir.path(), "other");
+        let start = started(&app, None);
+        let drift =
+            |project, previous| detect_root_drift(&start, project, RootSource::Default, previous);
+        assert_eq!(drift(&app, Some("app")), []);
+        assert_eq!(
+            drift(&other, None),
+            [RootDrift::RootChanged {
+                started: canonical(&app.root),
+                now: canonical(&other.root),
+            }]
+        );
+        assert_eq!(
+            drift(&app, Some("old")),
+            [RootDrift::NameChanged {
+                was: "old".to_string(),
+                now: "app".to_string(),
+            }]
+        );
+
+        let gone = dir.path().join("moved-away");
+        let start = started(&app, Some(&gone));
+        let now = std::env::current_dir().ok().map(|cwd| canonical(&cwd));
+        assert_eq!(
+            detect_root_drift(&start, &app, RootSource::Default, None),
+            [RootDrift::CwdChanged { was: gone, now }]
+        );
+
+        fs::remove_file(app.root.join("contextkeeper.toml")).unwrap();
+        let start = started(&app, None);
+        assert_eq!(
+            detect_root_drift(&start, &app, RootSource::Default, None),
+            [RootDrift::ConfigGone {
+                path: app.config_path.clone().unwrap(),
+            }]
+        );
+    }
+
+    #[test]
+    fn a_chosen_root_is_not_compared_with_the_start() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = project(dir.path(), "app");
+        let other = project(dir.path(), "other");
+        let gone = dir.path().join("moved-away");
+        let start = started(&app, Some(&gone));
+        fs::remove_file(app.root.join("contextkeeper.toml")).unwrap();
+
+        // The client's root: only the name is checked
+        assert_eq!(
+            detect_root_drift(&start, &other, RootSource::ClientRoot, None),
+            []
+        );
+        assert_eq!(
+            detect_root_drift(&start, &other, RootSource::ClientRoot, Some("app")),
+            [RootDrift::NameChanged {
+                was: "app".to_string(),
+                now: "other".to_string(),
+            }]
+        );
+        // Unless it is the start root, whose config is gone
+        assert_eq!(
+            detect_root_drift(&start, &app, RootSource::ClientRoot, None),
+            [RootDrift::ConfigGone {
+                path: app.config_path.clone().unwrap(),
+            }]
+        );
+        // The call's own: nothing
+        for project in [&app, &other] {
+            assert_eq!(
+                detect_root_drift(&start, project, RootSource::Explicit, Some("x")),
+                []
+            );
+        }
+    }
+
+    #[test]
+    fn writes_refused_for_a_mismatched_root() {
+        let name = RootDrift::NameChanged {
+            was: "app".to_string(),
+            now: "other".to_string(),
+        };
+        let root = RootDrift::RootChanged {
+            started: PathBuf::from("/src/app"),
+            now: PathBuf::from("/home/me"),
+        };
+        assert!(refuse_drifted_write(&[], "save the work state").is_ok());
+        assert!(refuse_drifted_write(std::slice::from_ref(&name), "save the work state").is_ok());
+        let drifts = [
+            root,
+            RootDrift::CwdChanged {
+                was: PathBuf::from("/src/app"),
+                now: None,
+            },
+            RootDrift::ConfigGone {
+                path: PathBuf::from("/src/app/contextkeeper.toml"),
+            },
+        ];
+        for drift in &drifts {
+            assert!(drift.mismatched_root());
+            let error = refuse_drifted_write(&[name.clone(), drift.clone()], "write the config")
+                .unwrap_err()
+                .to_string();
+            assert!(
+                error.contains(&format!(
+                    "refusing to write the config: {}.",
+                    drift.describe()
+                )),
+                "{}",
+                error
+            );
+            assert!(!error.contains("project name changed"), "{}", error);
+        }
+        let error = refuse_drifted_write(&drifts, "save the work state")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains(
+                "refusing to save the work state: the server started for `/src/app`, but this \
+                 call resolved `/home/me`; the server started in `/src/app`, which is now gone; \
+                 the config `/src/app/contextkeeper.toml` loaded at start no longer exists. \
+                 Pass `project_root` naming the project it is for."
+            ),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,1426 @@
+//! MCP server: tool parameters and the `ContextKeeperService` handler
+
+use crate::client_log::{
+    connect as connect_client_log, flush_client_log, set_client_level, CLIENT_LOG,
+};
+use crate::collectors::code_todos::collect_code_todos;
+use crate::collectors::diff_summary::collect_diff_summary;
+use crate::collectors::git::clear_repo_cache;
+use crate::collectors::open_files::open_files_now;
+use crate::collectors::resolve_path::resolve_paths;
+use crate::collectors::targets::collect_build_targets;
+use crate::collectors::tmux::collect_tmux_panes;
+use crate::collectors::validation::validate_targets;
+use crate::collectors::verify_files::{
+    format_file_verdicts, verify_working_files, FileVerdictState,
+};
+use crate::collectors::working_files::read_working_file;
+use crate::collectors::BuildTarget;
+use crate::commands::init::bootstrap_config as write_bootstrap_config;
+use crate::config::ProjectContext;
+use crate::context::{
+    annotate_context, collect_context, context_cache_ttl, needs_annotation, run_with_deadline,
+    tool_deadline, Availability, CollectionPlan, CollectorStatus, Context,
+};
+use crate::demo::{demo_context, demo_enabled};
+use crate::diary::{build_digest, diary_path, load_diary, parse_since, DEFAULT_SINCE};
+use crate::error::ContextKeeperError;
+use crate::format::budget::{format_within_budget, output_budget};
+use crate::format::{
+    availability_json, format_context, format_diary, format_project_summary, format_resolved_paths,
+    format_sections, format_working_file_excerpt, level_sections, sections_json, Level,
+    OutputFormat, Section,
+};
+use crate::i18n::{configured_language, Language};
+use crate::limits::{overflow_summary, Limits};
+use crate::paths::{path_from_bytes, project_dir};
+use crate::redact::{redact_output, Redactor};
+use crate::root_drift::{
+    configured_name, detect_root_drift, format_root_drift, refuse_drifted_write, RootDrift,
+    RootSource, StartedFor,
+};
+use crate::sessions::{
+    format_other_sessions, instance_id, other_sessions, record_heartbeat, remove_heartbeat,
+    HEARTBEAT_INTERVAL,
+};
+use crate::state::{
+    apply_metadata_updates, collect_working_files, save_work_state_to_file, saved_metadata,
+    TodoItem, WorkState, WorkingFiles, WORK_STATE_VERSION,
+};
+use crate::stats::RecordedCall;
+use crate::summary::collect_project_summary;
+use rmcp::{
+    handler::server::{tool::ToolRouter, wrapper::Parameters},
+    model::*,
+    service::{NotificationContext, RequestContext},
+    tool, tool_handler, tool_router, ErrorData as McpError, Peer, RoleServer, ServerHandler,
+};
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+// ============================================================================
+// MCP Tool Parameters
+// ============================================================================
+
+/// "'minimal' (~200 tokens) right after compression, ..." from `Level`
+fn describe_levels() -> String {
+    Level::ALL
+        .map(|level| {
+            format!(
+                "'{}' (~{} tokens) {}",
+                level.name(),
+                level.token_estimate(),
+                level.usage()
+            )
+        })
+        .join(", ")
+}
+
+/// "'markdown' to read, ..." from `OutputFormat`
+fn describe_formats() -> String {
+    OutputFormat::ALL
+        .map(|format| format!("'{}' {}", format.name(), format.usage()))
+        .join(", ")
+}
+
+/// Description of get_dev_context, generated so new levels, formats and
+/// sections are advertised as soon as the formatter has them
+pub fn get_dev_context_description() -> String {
+    format!(
+        "Get development context. Use level {}. Default is '{}'. Pass sections to get only \
+         those, in that order: {}. Use format {}. Default is '{}'.",
+        describe_levels(),
+        Level::default().name(),
+        Section::valid_names(),
+        describe_formats(),
+        OutputFormat::default().name()
+    )
+}
+
+/// `level` as a string limited to the `Level` names
+fn level_schema(_: &mut SchemaGenerator) -> Schema {
+    let names = Level::ALL.map(Level::name);
+    json_schema!({
+        "type": ["string", "null"],
+        "enum": names,
+        "description": format!("Detail level: {}. Default: '{}'", describe_levels(), Level::default().name()),
+    })
+}
+
+/// `format` as a string limited to the `OutputFormat` names
+fn format_schema(_: &mut SchemaGenerator) -> Schema {
+    let names = OutputFormat::ALL.map(OutputFormat::name);
+    json_schema!({
+        "type": ["string", "null"],
+        "enum": names,
+        "description": format!(
+            "Output format: {}. Default: '{}'. JSON is never cut to max_output_bytes",
+            describe_formats(),
+            OutputFormat::default().name()
+        ),
+    })
+}
+
+/// `sections` as a list limited to the `Section` names
+fn sections_schema(_: &mut SchemaGenerator) -> Schema {
+    let names = Section::ALL.map(|s| s.name());
+    json_schema!({
+        "type": ["array", "null"],
+        "items": { "type": "string", "enum": names },
+        "description": "Only return these sections, in order",
+    })
+}
+
+/// Parameters for get_dev_context tool
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetDevContextParams {
+    #[serde(default)]
+    #[schemars(schema_with = "level_schema")]
+    pub level: Option<String>,
+    /// Absolute path of the project to describe. Defaults to the client's first root, then the server's cwd
+    pub project_root: Option<String>,
+    #[serde(default)]
+    #[schemars(schema_with = "sections_schema")]
+    pub sections: Option<Vec<String>>,
+    #[serde(default)]
+    #[schemars(schema_with = "format_schema")]
+    pub format: Option<String>,
+    /// Re-collect even when a recent context is cached
+    pub force_refresh: Option<bool>,
+    /// Largest result to return, in bytes. Over it the context is shown one level down (full, normal, minimal) with a note of the sections left out, and cut between sections only when even minimal is over. Default: `[output] max_output_bytes`, else no limit; 0 for no limit
+    pub max_output_bytes: Option<usize>,
+}
+
+/// Parameters for save_work_state tool
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SaveWorkStateParams {
+    /// Brief summary of current task (required)
+    pub task_summary: String,
+    /// List of files currently being worked on (auto-detected from git diff if omitted)
+    pub working_files: Option<Vec<String>>,
+    /// Additional notes about current progress
+    pub notes: Option<String>,
+    /// Todo items as JSON array: [{"content": "...", "status": "pending|in_progress|completed"}]
+    pub todos: Option<String>,
+    /// What to do next, in order, e.g. ["run the failing test", "fix the parser"]. Shown ahead of the todos, the first two even at minimal level
+    pub next_steps: Option<Vec<String>>,
+    /// Absolute path of the project this state belongs to. Defaults to the client's first root, then the server's cwd
+    pub project_root: Option<String>,
+    /// Extra facts to keep with the state, e.g. {"gerrit_change": "I8f3c2a", "jira": "ABC-123"}. Keys not given keep their saved values; null removes a key. Keys: letters, digits, '.', '_', '-', ':' (up to 64; "ck:" is reserved). Values: up to 1024 bytes
+    pub metadata: Option<BTreeMap<String, Option<String>>>,
+}
+
+/// Parameters for refresh_context tool
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RefreshContextParams {
+    /// Absolute path of the project whose cached context to drop. Defaults to the client's first root, then the server's cwd
+    pub project_root: Option<String>,
+}
+
+/// Parameters for get_recent_activity tool
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetRecentActivityParams {
+    /// How far back, as a number and m, h, d or w, e.g. '1d' for yesterday and today. Default: '7d'
+    pub since: Option<String>,
+    /// Absolute path of the project to summarize. Defaults to the client's first root, then the server's cwd
+    pub project_root: Option<String>,
+    /// Include every project instead of just `project_root`. Default: false
+    pub all_projects: Option<bool>,
+}
+
+/// Parameters for get_working_file tool
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetWorkingFileParams {
+    /// File as listed in the context: a saved working file or one changed in git, relative to the project root
+    pub path: String,
+    /// Lines to return. Default: 80, at most 400
+    pub max_lines: Option<usize>,
+    /// Center the excerpt on this 1-based line instead of starting at the top
+    pub around_line: Option<usize>,
+    /// Absolute path of the project the file belongs to. Defaults to the client's first root, then the server's cwd
+    pub project_root: Option<String>,
+}
+
+/// Parameters for resolve_path tool
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ResolvePathParams {
+    /// Paths to look up, relative to the project root or absolute; at most 50
+    pub paths: Vec<String>,
+    /// Absolute path of the project the paths belong to. Defaults to the client's first root, then the server's cwd
+    pub project_root: Option<String>,
+}
+
+/// Parameters for get_project_summary tool
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetProjectSummaryParams {
+    /// Absolute path of the project to summarize. Defaults to the client's first root, then the server's cwd
+    pub project_root: Option<String>,
+}
+
+/// Parameters for validate_config tool
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ValidateConfigParams {
+    /// Absolute path of the project to validate. Defaults to the client's first root, then the server's cwd
+    pub project_root: Option<String>,
+}
+
+/// Parameters for bootstrap_config tool
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct BootstrapConfigParams {
+    /// Replace an existing config file (default: false, which refuses)
+    pub force: Option<bool>,
+    /// Project name for the config. Defaults to the name of the project directory
+    pub name: Option<String>,
+    /// Absolute path of the project to write the config for. Defaults to the client's first root, then the server's cwd
+    pub project_root: Option<String>,
+}
+
+// ============================================================================
+// MCP Server Implementation
+// ============================================================================
+
+/// Value of an ASCII hex digit
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|digit| digit as u8)
+}
+
+/// Convert a `file://` root URI from the client into a local path. A `%`
+/// not followed by two hex digits is kept as it is, and the decoded bytes
+/// need not be UTF-8.
+pub fn file_uri_to_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?;
+    // Drop an optional authority ("file://localhost/path")
+    let path = &rest[rest.find('/')?..];
+
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(high), Some(low)) = (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+                decoded.push((high << 4) | low);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    Some(path_from_bytes(&decoded))
+}
+
+/// Ask the client for its roots and take the first local directory
+pub async fn first_client_root(peer: &Peer<RoleServer>) -> Option<PathBuf> {
+    let supports_roots = peer
+        .peer_info()
+        .map(|info| info.capabilities.roots.is_some())
+        .unwrap_or(false);
+    if !supports_roots {
+        return None;
+    }
+
+    let result = tokio::time::timeout(Duration::from_secs(2), peer.list_roots())
+        .await
+        .ok()?
+        .ok()?;
+    result
+        .roots
+        .iter()
+        .filter_map(|root| file_uri_to_path(&root.uri))
+        .find(|path| path.is_dir())
+}
+
+/// A collected context the server may serve again until its TTL runs out
+#[derive(Clone)]
+pub struct CachedContext {
+    pub collected_at: Instant,
+    /// Project (and so config) it was collected for
+    pub project: Arc<ProjectContext>,
+    pub context: Context,
+    /// Collectors it was collected with; it serves requests this covers
+    pub plan: CollectionPlan,
+    /// Whether `annotate_context` has run on it, for the full level
+    pub annotated: bool,
+}
+
+/// A formatted get_project_summary, good until the config changes
+#[derive(Clone)]
+pub struct CachedSummary {
+    /// Project (and so config) it was made for
+    pub project: Arc<ProjectContext>,
+    pub text: String,
+}
+
+#[derive(Clone)]
+pub struct ContextKeeperService {
+    pub tool_router: ToolRouter<Self>,
+    /// Project used when neither the call nor the client names one
+    pub default_root: PathBuf,
+    /// Explicit config file for the default project (`--config`)
+    pub config_path: Option<PathBuf>,
+    /// `--lang`, over every project's `[output] language`
+    pub language: Option<Language>,
+    /// Loaded projects keyed by root, so two projects never share config
+    pub projects: Arc<Mutex<HashMap<PathBuf, Arc<ProjectContext>>>>,
+    /// Last collected context per project root
+    pub contexts: Arc<RwLock<HashMap<PathBuf, CachedContext>>>,
+    /// Held while a project's context is looked up or collected, so a call
+    /// that arrives mid-collection waits for it instead of collecting again
+    pub collecting: Arc<Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>>,
+    /// Set by the first get_dev_context; a pre-warm that hasn't started
+    /// collecting by then stands down
+    pub tool_called: Arc<AtomicBool>,
+    /// Last get_project_summary per project root
+    pub summaries: Arc<Mutex<HashMap<PathBuf, CachedSummary>>>,
+    /// The default project as the server started, see `root_drift`
+    pub started_for: Arc<StartedFor>,
+    /// `[project] name` of the previous call that didn't name its root
+    pub last_project_name: Arc<Mutex<Option<String>>>,
+}
+
+impl Default for ContextKeeperService {
+    fn default() -> Self {
+        let root = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        Self::new(root, None, None)
+    }
+}
+
+impl ContextKeeperService {
+    /// Resolve the project for a tool call: explicit parameter, then the
+    /// client's first root, then the server's cwd. Also returns which of
+    /// them it came from.
+    pub async fn resolve_project(
+        &self,
+        project_root: Option<&str>,
+        peer: &Peer<RoleServer>,
+    ) -> Result<(Arc<ProjectContext>, RootSource), McpError> {
+        let (root, source) = match project_root {
+            Some(root) if !Path::new(root).is_absolute() => {
+                return Err(McpError::invalid_params(
+                    format!("project_root must be an absolute path, not {}", root),
+                    None,
+                ))
+            }
+            Some(root) => (
+                project_dir(root)
+                    .map_err(|e| McpError::invalid_params(format!("project_root: {}", e), None))?,
+                RootSource::Explicit,
+            ),
+            None => match first_client_root(peer).await {
+                Some(root) => (root, RootSource::ClientRoot),
+                None => (self.default_root.clone(), RootSource::Default),
+            },
+        };
+        Ok((self.load_project(root), source))
+    }
+
+    /// How the project of a call drifted from the one the server started
+    /// for, see `root_drift`. A call that names its `project_root` chose
+    /// it and isn't checked.
+    pub fn root_drift(&self, project: &ProjectContext, source: RootSource) -> Vec<RootDrift> {
+        if source == RootSource::Explicit || demo_enabled() {
+            return Vec::new();
+        }
+        let mut last = self.last_project_name.lock().unwrap();
+        let drift = detect_root_drift(&self.started_for, project, source, last.as_deref());
+        *last = Some(configured_name(project));
+        for d in &drift {
+            tracing::warn!(root = %project.root.display(), "{}", d.describe());
+        }
+        drift
+    }
+
+    /// The loaded project for `root`, reloaded when its config changed
+    pub fn load_project(&self, root: PathBuf) -> Arc<ProjectContext> {
+        let mut projects = self.projects.lock().unwrap();
+        if let Some(project) = projects.get(&root) {
+            if !project.config_changed() {
+                return Arc::clone(project);
+            }
+        }
+
+        let config_path = if root == self.default_root {
+            self.config_path.clone()
+        } else {
+            None
+        };
+        let project =
+            Arc::new(ProjectContext::load(root.clone(), config_path).with_language(self.language));
+        project.warn_config_error();
+        projects.insert(root, Arc::clone(&project));
+        project
+    }
+
+    fn collection_lock(&self, root: &Path) -> Arc<tokio::sync::Mutex<()>> {
+        let mut collecting = self.collecting.lock().unwrap();
+        Arc::clone(collecting.entry(root.to_path_buf()).or_default())
+    }
+
+    /// The project's context for `plan`, from the cache while it is younger
+    /// than `cache_ttl_secs`, was collected under the current config and
+    /// covers `plan`. It is annotated (targets checked, Gerrit asked) once,
+    /// the first time a full-level call needs it.
+    pub async fn cached_context(
+        &self,
+        project: &Arc<ProjectContext>,
+        plan: CollectionPlan,
+        annotate: bool,
+        force_refresh: bool,
+        ct: &CancellationToken,
+    ) -> Context {
+        let lock = self.collection_lock(&project.root);
+        let _collecting = lock.lock().await;
+        self.cached_context_locked(project, plan, annotate, force_refresh, ct)
+            .await
+    }
+
+    /// Collect the default project's full context once after start-up, so
+    /// the session's first get_dev_context is served from the cache. Does
+    /// nothing with `server.prewarm = false`, in demo mode, or once a tool
+    /// call has arrived; a call that arrives mid-collection waits for it.
+    pub async fn prewarm(&self) {
+        let project = self.load_project(self.default_root.clone());
+        let enabled = project
+            .config
+            .server
+            .as_ref()
+            .and_then(|s| s.prewarm)
+            .unwrap_or(true);
+        if !enabled || demo_enabled() || context_cache_ttl(&project.config).is_zero() {
+            return;
+        }
+
+        let lock = self.collection_lock(&project.root);
+        let Ok(_collecting) = lock.try_lock() else {
+            return;
+        };
+        if self.tool_called.load(Ordering::SeqCst) {
+            tracing::debug!("prewarm skipped; a tool call came first");
+            return;
+        }
+        let started = Instant::now();
+        let ct = CancellationToken::new();
+        self.cached_context_locked(&project, CollectionPlan::all(), true, false, &ct)
+            .await;
+        tracing::info!(
+            root = %project.root.display(),
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "prewarm finished"
+        );
+    }
+
+    /// Project roots this server has served, the default one first
+    fn served_roots(&self) -> Vec<PathBuf> {
+        let mut roots = vec![self.default_root.clone()];
+        let projects = self.projects.lock().unwrap();
+        roots.extend(
+            projects
+                .keys()
+                .filter(|r| **r != self.default_root)
+                .cloned(),
+        );
+        roots
+    }
+
+    /// Refresh this instance's heartbeat on every served project each
+    /// `HEARTBEAT_INTERVAL`, so other servers on them know it is active
+    pub async fn heartbeat(&self) {
+        let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+        loop {
+            interval.tick().await;
+            let roots = self.served_roots();
+            let _ = tokio::task::spawn_blocking(move || {
+                for root in roots {
+                    if let Err(e) = record_heartbeat(&root) {
+                        tracing::debug!(root = %root.display(), error = %e, "heartbeat not saved");
+                    }
+                }
+            })
+            .await;
+        }
+    }
+
+    /// Take this instance off the heartbeats, on shutdown
+    pub fn end_heartbeat(&self) {
+        for root in self.served_roots() {
+            if let Err(e) = remove_heartbeat(&root) {
+                tracing::debug!(root = %root.display(), error = %e, "heartbeat not removed");
+            }
+        }
+    }
+
+    async fn cached_context_locked(
+        &self,
+        project: &Arc<ProjectContext>,
+        plan: CollectionPlan,
+        annotate: bool,
+        force_refresh: bool,
+        ct: &CancellationToken,
+    ) -> Context {
+        let ttl = context_cache_ttl(&project.config);
+        let deadline = tool_deadline(&project.config);
+        if force_refresh {
+            clear_repo_cache(&project.root);
+        }
+        let cached = if force_refresh || ttl.is_zero() {
+            None
+        } else {
+            self.contexts
+                .read()
+                .unwrap()
+                .get(&project.root)
+                .filter(|c| {
+                    Arc::ptr_eq(&c.project, project)
+                        && c.collected_at.elapsed() < ttl
+                        && c.plan.covers(&plan)
+                })
+                .cloned()
+        };
+
+        let hit = cached.is_some();
+        let (collected_at, plan, mut context, mut annotated) = match cached {
+            Some(cached) => {
+                tracing::debug!(
+                    target: CLIENT_LOG,
+                    age_secs = cached.collected_at.elapsed().as_secs(),
+                    "context served from cache"
+                );
+                (
+                    cached.collected_at,
+                    cached.plan,
+                    cached.context,
+                    cached.annotated,
+                )
+            }
+            None => {
+                let started = Instant::now();
+                let context =
+                    collect_context(Arc::clone(project), plan, deadline, ct.clone()).await;
+                tracing::debug!(
+                    target: CLIENT_LOG,
+                    elapsed_ms = started.elapsed().as_millis() as u64,
+                    refreshed = force_refresh,
+                    "context collected"
+                );
+                log_degraded(&context);
+                (started, plan, context, false)
+            }
+        };
+        if annotate && !annotated {
+            annotate_context(project, &mut context, deadline, ct).await;
+            annotated = true;
+        }
+
+        // A collection cut short by the deadline is not worth repeating
+        let complete = context.reports.iter().all(|r| {
+            !matches!(
+                r.status,
+                CollectorStatus::TimedOut | CollectorStatus::Cancelled
+            )
+        });
+        if !hit && !ttl.is_zero() && !complete {
+            tracing::debug!(target: CLIENT_LOG, "collection cut short, not cached");
+        }
+        if !ttl.is_zero() && complete {
+            self.contexts.write().unwrap().insert(
+                project.root.clone(),
+                CachedContext {
+                    collected_at,
+                    project: Arc::clone(project),
+                    plan,
+                    context: context.clone(),
+                    annotated,
+                },
+            );
+        }
+
+        if hit {
+            context.cache_age = Some(collected_at.elapsed());
+        }
+        flush_client_log().await;
+        context
+    }
+}
+
+/// Tell the client what a fresh collection missed: a warning per
+/// collector warning (a command that failed to start, a clock off), an
+/// info per section left empty because its tool or input is missing.
+/// Collectors cut off at the deadline were warned about by
+/// `collect_context` already.
+fn log_degraded(context: &Context) {
+    for report in &context.reports {
+        for warning in &report.warnings {
+            tracing::warn!(target: CLIENT_LOG, collector = report.name, "{}", warning);
+        }
+        if let Availability::Unavailable(reason) = &report.availability {
+            tracing::info!(
+                target: CLIENT_LOG,
+                collector = report.name,
+                "section unavailable: {}",
+                reason
+            );
+        }
+    }
+}
+
+#[tool_router]
+impl ContextKeeperService {
+    pub fn new(
+        default_root: PathBuf,
+        config_path: Option<PathBuf>,
+        language: Option<Language>,
+    ) -> Self {
+        let mut tool_router = Self::tool_router();
+        if let Some(route) = tool_router.map.get_mut("get_dev_context") {
+            route.attr.description = Some(get_dev_context_description().into());
+        }
+        let service = Self {
+            tool_router,
+            default_root,
+            config_path,
+            language,
+            projects: Arc::new(Mutex::new(HashMap::new())),
+            contexts: Arc::new(RwLock::new(HashMap::new())),
+            collecting: Arc::new(Mutex::new(HashMap::new())),
+            tool_called: Arc::new(AtomicBool::new(false)),
+            summaries: Arc::new(Mutex::new(HashMap::new())),
+            started_for: Arc::new(StartedFor::default()),
+            last_project_name: Arc::new(Mutex::new(None)),
+        };
+        let started = StartedFor::capture(&service.load_project(service.default_root.clone()));
+        Self {
+            started_for: Arc::new(started),
+            ..service
+        }
+    }
+
+    /// Get development context; the description is replaced with
+    /// `get_dev_context_description` in `new`
+    #[tool(description = "Get development context.")]
+    pub async fn get_dev_context(
+        &self,
+        params: Parameters<GetDevContextParams>,
+        ct: CancellationToken,
+        peer: Peer<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let started = Instant::now();
+        let mut call = RecordedCall::start("get_dev_context", &params.0);
+        let (project, source) = self
+            .resolve_project(params.0.project_root.as_deref(), &peer)
+            .await?;
+        call.project(&project);
+        let drift = self.root_drift(&project, source);
+        tracing::info!(root = %project.root.display(), level = ?params.0.level, "get_dev_context");
+        let level = Level::parse(params.0.level.as_deref().unwrap_or("normal"));
+        call.level(match params.0.sections {
+            Some(_) => "sections",
+            None => level.name(),
+        });
+        let sections = match &params.0.sections {
+            Some(names) => {
+                let mut sections = Vec::new();
+                for name in names {
+                    match Section::parse(name) {
+                        Some(section) => sections.push(section),
+                        None => {
+                            return Err(McpError::invalid_params(
+                                format!(
+                                    "unknown section '{}'; valid sections: {}",
+                                    name,
+                                    Section::valid_names()
+                                ),
+                                None,
+                            ))
+                        }
+                    }
+                }
+                Some(sections)
+            }
+            None => None,
+        };
+        let format = match params.0.format.as_deref() {
+            None => OutputFormat::default(),
+            Some(name) => OutputFormat::parse(name).ok_or_else(|| {
+                McpError::invalid_params(
+                    format!(
+                        "unknown format '{}'; valid formats: {}",
+                        name,
+                        OutputFormat::ALL.map(OutputFormat::name).join(", ")
+                    ),
+                    None,
+                )
+            })?,
+        };
+        let shown = sections.as_deref().unwrap_or(level_sections(level));
+        let plan = match &sections {
+            Some(sections) => CollectionPlan::for_sections(sections, &project.config),
+            None => CollectionPlan::for_level(level, &project.config),
+        };
+        // Container/lunch checks and Gerrit changes only show at full level
+        let annotate = needs_annotation(level, shown);
+        let force_refresh = params.0.force_refresh.unwrap_or(false);
+        let context = if demo_enabled() {
+            let mut context = demo_context(None)?;
+            context.language = configured_language(&project.config);
+            context
+        } else {
+            self.tool_called.store(true, Ordering::SeqCst);
+            let context = self
+                .cached_context(&project, plan, annotate, force_refresh, &ct)
+                .await;
+            call.cache_hit(context.cache_age.is_some());
+            context
+        };
+        // Another window on the same project saves to the same files
+        let notes = if demo_enabled() {
+            String::new()
+        } else {
+            format_root_drift(&drift) + &format_other_sessions(&other_sessions(&project.root))
+        };
+        let redactor = Redactor::from_config(&project.config);
+        let finish = |body: &str| redactor.redact(&(notes.clone() + body)).into_owned();
+        let markdown = if format == OutputFormat::Json {
+            let mut value = sections_json(&context, shown);
+            if let Some(map) = value.as_object_mut() {
+                map.insert(
+                    "availability".to_string(),
+                    availability_json(&context, shown),
+                );
+                if !notes.is_empty() {
+                    map.insert("notes".to_string(), notes.trim().into());
+                }
+            }
+            redactor.redact_json(&mut value);
+            serde_json::to_string_pretty(&value).unwrap_or_default()
+        } else {
+            match output_budget(&project.config, params.0.max_output_bytes) {
+                Some(max) => {
+                    format_within_budget(&context, sections.as_deref(), level, max, finish)
+                }
+                None => finish(&match &sections {
+                    Some(sections) => format_sections(&context, sections, level),
+                    None => format_context(&context, level),
+                }),
+            }
+        };
+
+        tracing::info!(
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            bytes = markdown.len(),
+            "get_dev_context finished"
+        );
+        call.output(&markdown);
+        Ok(CallToolResult::success(vec![Content::text(markdown)]))
+    }
+
+    #[tool(
+        description = "Save current work state for recovery after context compression. Call this before compression or at task milestones."
+    )]
+    pub async fn save_work_state(
+        &self,
+        params: Parameters<SaveWorkStateParams>,
+        ct: CancellationToken,
+        peer: Peer<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let mut call = RecordedCall::start("save_work_state", &params.0);
+        let SaveWorkStateParams {
+            task_summary,
+            working_files,
+            notes,
+            todos,
+            next_steps,
+            project_root,
+            metadata,
+        } = params.0;
+
+        let (project, source) = self.resolve_project(project_root.as_deref(), &peer).await?;
+        call.project(&project);
+        tracing::info!(root = %project.root.display(), "save_work_state");
+        let drift = self.root_drift(&project, source);
+        refuse_drifted_write(&drift, "save the work state")?;
+
+        // Parse todos if provided
+        let mut todo_items: Vec<TodoItem> = match todos {
+            Some(todos) => serde_json::from_str(&todos).map_err(|e| {
+                ContextKeeperError::Validation(format!(
+                    "todos is not a JSON array of {{content, status}}: {}",
+                    e
+                ))
+            })?,
+            None => Vec::new(),
+        };
+        let max_todos = Limits::from_config(&project.config).max_todos;
+        let todos_note = if todo_items.len() > max_todos {
+            let dropped = todo_items.len() - max_todos;
+            todo_items.truncate(max_todos);
+            format!(
+                "\n\nNote: {}.",
+                overflow_summary(
+                    dropped,
+                    "todo was not saved",
+                    "todos were not saved",
+                    "max_todos"
+                )
+            )
+        } else {
+            String::new()
+        };
+
+        let mut kept_metadata = saved_metadata(&project.root);
+        if let Some(updates) = metadata {
+            let redactor = Redactor::from_config(&project.config);
+            apply_metadata_updates(&mut kept_metadata, updates, |value| {
+                redactor.redact(value).into_owned()
+            })?;
+        }
+
+        // Auto-collect working files if not provided
+        let deadline = tool_deadline(&project.config);
+        let mut files_note = String::new();
+        let mut verdicts = None;
+        let working = match working_files {
+            Some(files) => {
+                let project = Arc::clone(&project);
+                let given = files.clone();
+                match run_with_deadline(deadline, &ct, move || {
+                    verify_working_files(&project, &given)
+                })
+                .await
+                {
+                    Ok(checked) => {
+                        let mut seen = HashSet::new();
+                        let files = checked
+                            .iter()
+                            .map(|v| v.path.clone())
+                            .filter(|path| seen.insert(path.clone()))
+                            .collect();
+                        verdicts = Some(checked);
+                        WorkingFiles {
+                            files,
+                            ..Default::default()
+                        }
+                    }
+                    Err(status) => {
+                        tracing::warn!(status = status.as_str(), "working file check abandoned");
+                        files_note = format!(
+                            "\n\nNote: checking the working files {}; saved as given.",
+                            status.as_str()
+                        );
+                        WorkingFiles {
+                            files,
+                            ..Default::default()
+                        }
+                    }
+                }
+            }
+            None => {
+                let project = Arc::clone(&project);
+                match run_with_deadline(deadline, &ct, move || collect_working_files(&project))
+                    .await
+                {
+                    Ok(working) => working,
+                    Err(status) => {
+                        tracing::warn!(
+                            status = status.as_str(),
+                            "working file detection abandoned"
+                        );
+                        files_note = format!(
+                            "\n\nNote: working file detection {}; saved without files.",
+                            status.as_str()
+                        );
+                        WorkingFiles::default()
+                    }
+                }
+            }
+        };
+
+        // What was running, best effort
+        let terminal_panes = {
+            let project = Arc::clone(&project);
+            run_with_deadline(deadline, &ct, move || collect_tmux_panes(&project))
+                .await
+                .unwrap_or_default()
+        };
+
+        // What the editor reported having open; a file read, no deadline
+        let open_files = open_files_now(&project);
+
+        // TODO comments in those files, with `scan_todos`
+        let code_todos = {
+            let project = Arc::clone(&project);
+            let files = working.files.clone();
+            run_with_deadline(deadline, &ct, move || collect_code_todos(&project, &files))
+                .await
+                .unwrap_or_default()
+        };
+
+        // How much of each changed, and where, with `capture_diffs`
+        let diff_summary = {
+            let project = Arc::clone(&project);
+            let files = working.files.clone();
+            run_with_deadline(deadline, &ct, move || {
+                collect_diff_summary(&project, &files)
+            })
+            .await
+            .unwrap_or_default()
+        };
+
+        let state = WorkState {
+            version: WORK_STATE_VERSION,
+            saved_at: chrono::Utc::now().to_rfc3339(),
+            trigger: "manual".to_string(),
+            task_summary,
+            working_files: working.files,
+            recent_files: working.recent,
+            unverified_files: verdicts
+                .iter()
+                .flatten()
+                .filter(|v| v.state == FileVerdictState::NotFound)
+                .map(|v| v.path.clone())
+                .collect(),
+            notes: notes.unwrap_or_default(),
+            todos: todo_items,
+            next_steps: next_steps
+                .unwrap_or_default()
+                .into_iter()
+                .map(|step| step.trim().to_string())
+                .filter(|step| !step.is_empty())
+                .collect(),
+            project_root: project.root.to_string_lossy().to_string(),
+            working_repos: working.repos,
+            terminal_panes,
+            open_files,
+            code_todos,
+            diff_summary,
+            instance_id: instance_id().to_string(),
+            notes_updated_at: String::new(),
+            metadata: kept_metadata,
+        };
+
+        match save_work_state_to_file(&state, &project.config) {
+            Ok(_) => {
+                // The next get_dev_context must show the new state
+                self.contexts.write().unwrap().clear();
+                let reply = format!(
+                    "Work state saved successfully.\n\n\
+                - Task: {}\n\
+                - Files: {}\n\
+                - Todos: {} items\n\
+                - Next steps: {}\n\
+                - Metadata: {}\n\n\
+                This state will be included in `get_dev_context` output after compression.{}{}",
+                    state.task_summary,
+                    match &verdicts {
+                        Some(verdicts) => format_file_verdicts(state.working_files.len(), verdicts),
+                        None => state.working_files.len().to_string(),
+                    },
+                    state.todos.len(),
+                    match state.next_steps.first() {
+                        Some(first) => format!("{} (first: {})", state.next_steps.len(), first),
+                        None => "none".to_string(),
+                    },
+                    match state.metadata.len() {
+                        0 => "none".to_string(),
+                        _ => state
+                            .metadata
+                            .keys()
+                            .cloned()
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    },
+                    todos_note,
+                    files_note
+                );
+                let reply = format_root_drift(&drift) + &redact_output(&project.config, &reply);
+                call.output(&reply);
+                Ok(CallToolResult::success(vec![Content::text(reply)]))
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "failed to save work state");
+                Err(e.into())
+            }
+        }
+    }
+
+    #[tool(
+        description = "Drop the cached development context so the next get_dev_context collects fresh data. Use after changing branches, containers or devices."
+    )]
+    pub async fn refresh_context(
+        &self,
+        params: Parameters<RefreshContextParams>,
+        peer: Peer<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let mut call = RecordedCall::start("refresh_context", &params.0);
+        let (project, source) = self
+            .resolve_project(params.0.project_root.as_deref(), &peer)
+            .await?;
+        call.project(&project);
+        let drift = self.root_drift(&project, source);
+        tracing::info!(root = %project.root.display(), "refresh_context");
+        let dropped = self.contexts.write().unwrap().remove(&project.root);
+        let text = match dropped {
+            Some(cached) => format!(
+                "Dropped the cached context for {} ({}s old).",
+                project.root.display(),
+                cached.collected_at.elapsed().as_secs()
+            ),
+            None => format!("No cached context for {}.", project.root.display()),
+        };
+        let text = format_root_drift(&drift) + &text;
+        call.output(&text);
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+
+    #[tool(
+        description = "Summarize past saved work states by day: tasks, repos touched, todos added and completed. Use to answer what was done yesterday or this week, e.g. at the start of a fresh session."
+    )]
+    pub async fn get_recent_activity(
+        &self,
+        params: Parameters<GetRecentActivityParams>,
+        peer: Peer<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let mut call = RecordedCall::start("get_recent_activity", &params.0);
+        let (project, source) = self
+            .resolve_project(params.0.project_root.as_deref(), &peer)
+            .await?;
+        call.project(&project);
+        let drift = self.root_drift(&project, source);
+        let since = params.0.since.as_deref().unwrap_or(DEFAULT_SINCE);
+        tracing::info!(root = %project.root.display(), since, "get_recent_activity");
+        let window = parse_since(since).map_err(|e| McpError::invalid_params(e, None))?;
+        let (entries, skipped) = load_diary()
+            .map_err(|e| McpError::from(ContextKeeperError::state_io(&diary_path(), e)))?;
+        let root = project.root.to_string_lossy();
+        let only = (!params.0.all_projects.unwrap_or(false)).then_some(root.as_ref());
+        let digest = build_digest(&entries, skipped, chrono::Utc::now() - window, only);
+        let text = redact_output(&project.config, &format_diary(&digest));
+        let text = format_root_drift(&drift) + &text;
+        call.output(&text);
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+
+    #[tool(
+        description = "Read part of a file being worked on: one of the saved working files or a file with uncommitted changes, as named in get_dev_context. Returns numbered lines with the file's git status. Other files are refused; use it to look at mid-edit files right after recovery."
+    )]
+    pub async fn get_working_file(
+        &self,
+        params: Parameters<GetWorkingFileParams>,
+        ct: CancellationToken,
+        peer: Peer<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let mut call = RecordedCall::start("get_working_file", &params.0);
+        let GetWorkingFileParams {
+            path,
+            max_lines,
+            around_line,
+            project_root,
+        } = params.0;
+        let (project, source) = self.resolve_project(project_root.as_deref(), &peer).await?;
+        call.project(&project);
+        let drift = self.root_drift(&project, source);
+        tracing::info!(root = %project.root.display(), path, "get_working_file");
+
+        let deadline = tool_deadline(&project.config);
+        let reader = Arc::clone(&project);
+        let excerpt = run_with_deadline(deadline, &ct, move || {
+            read_working_file(&reader, &path, max_lines, around_line)
+        })
+        .await
+        .map_err(|status| {
+            McpError::internal_error(format!("reading the file {}", status.as_str()), None)
+        })??;
+        let text = redact_output(&project.config, &format_working_file_excerpt(&excerpt));
+        let text = format_root_drift(&drift) + &text;
+        call.output(&text);
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+
+    #[tool(
+        description = "Say which repository owns each path: the repo (or the submodule or nested repo inside it), its branch, the file's git status code and the path relative to the project root. Paths in no known repo are answered as unowned, paths outside the root as outside project. Use instead of running git to place a file from the context."
+    )]
+    pub async fn resolve_path(
+        &self,
+        params: Parameters<ResolvePathParams>,
+        ct: CancellationToken,
+        peer: Peer<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let mut call = RecordedCall::start("resolve_path", &params.0);
+        let ResolvePathParams {
+            paths,
+            project_root,
+        } = params.0;
+        let (project, source) = self.resolve_project(project_root.as_deref(), &peer).await?;
+        call.project(&project);
+        let drift = self.root_drift(&project, source);
+        tracing::info!(root = %project.root.display(), paths = paths.len(), "resolve_path");
+
+        let deadline = tool_deadline(&project.config);
+        let resolver = Arc::clone(&project);
+        let resolved = run_with_deadline(deadline, &ct, move || resolve_paths(&resolver, &paths))
+            .await
+            .map_err(|status| {
+                McpError::internal_error(format!("resolving the paths {}", status.as_str()), None)
+            })??;
+        let text = redact_output(&project.config, &format_resolved_paths(&resolved));
+        let text = format_root_drift(&drift) + &text;
+        call.output(&text);
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+
+    #[tool(
+        description = "Say what the project is: name and type, build systems, top-level directories, the build script's usage and the project hints. No live state (git, containers, devices), so it is cheap; call it first in a new session, then get_dev_context for the current state."
+    )]
+    pub async fn get_project_summary(
+        &self,
+        params: Parameters<GetProjectSummaryParams>,
+        ct: CancellationToken,
+        peer: Peer<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let mut call = RecordedCall::start("get_project_summary", &params.0);
+        let (project, source) = self
+            .resolve_project(params.0.project_root.as_deref(), &peer)
+            .await?;
+        call.project(&project);
+        let drift = self.root_drift(&project, source);
+        tracing::info!(root = %project.root.display(), "get_project_summary");
+
+        let cached = self
+            .summaries
+            .lock()
+            .unwrap()
+            .get(&project.root)
+            .filter(|cached| Arc::ptr_eq(&cached.project, &project))
+            .map(|cached| cached.text.clone());
+        call.cache_hit(cached.is_some());
+        if let Some(text) = cached {
+            let text = format_root_drift(&drift) + &text;
+            call.output(&text);
+            return Ok(CallToolResult::success(vec![Content::text(text)]));
+        }
+
+        let deadline = tool_deadline(&project.config);
+        let summarized = Arc::clone(&project);
+        let summary =
+            run_with_deadline(deadline, &ct, move || collect_project_summary(&summarized))
+                .await
+                .map_err(|status| {
+                    McpError::internal_error(
+                        format!("summarizing the project {}", status.as_str()),
+                        None,
+                    )
+                })?;
+        let text = redact_output(&project.config, &format_project_summary(&summary));
+        self.summaries.lock().unwrap().insert(
+            project.root.clone(),
+            CachedSummary {
+                project: Arc::clone(&project),
+                text: text.clone(),
+            },
+        );
+        let text = format_root_drift(&drift) + &text;
+        call.output(&text);
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+
+    #[tool(
+        description = "Validate the project config and cross-check build targets against existing containers/images and AOSP lunch targets. Use when a suggested target fails to start or lunch."
+    )]
+    pub async fn validate_config(
+        &self,
+        params: Parameters<ValidateConfigParams>,
+        ct: CancellationToken,
+        peer: Peer<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let mut call = RecordedCall::start("validate_config", &params.0);
+        let (project, source) = self
+            .resolve_project(params.0.project_root.as_deref(), &peer)
+            .await?;
+        call.project(&project);
+        let drift = self.root_drift(&project, source);
+        tracing::info!(root = %project.root.display(), "validate_config");
+
+        let mut out = match (&project.config_path, &project.config_error) {
+            (Some(path), None) => format!("Config OK: {}\n", path.display()),
+            (Some(_), Some(e)) => format!("Config error: {}\n", e),
+            (None, _) => "No config file found; using defaults\n".to_string(),
+        };
+
+        let deadline = tool_deadline(&project.config);
+        let checked = Arc::clone(&project);
+        match run_with_deadline(deadline, &ct, move || {
+            let mut targets = collect_build_targets(&checked);
+            validate_targets(&checked, &mut targets);
+            targets
+        })
+        .await
+        {
+            Ok(targets) => {
+                let invalid: Vec<&BuildTarget> =
+                    targets.iter().filter(|t| !t.issues.is_empty()).collect();
+                out.push_str(&format!(
+                    "Targets: {} checked, {} with problems\n",
+                    targets.len(),
+                    invalid.len()
+                ));
+                for target in invalid {
+                    out.push_str(&format!(
+                        "- {}: {} ({})\n",
+                        target.name,
+                        target.issues.join(", "),
+                        target.source_file
+                    ));
+                }
+            }
+            Err(status) => {
+                out.push_str(&format!("Target validation {}\n", status.as_str()));
+            }
+        }
+
+        let out = format_root_drift(&drift) + &out;
+        call.output(&out);
+        Ok(CallToolResult::success(vec![Content::text(out)]))
+    }
+
+    #[tool(
+        description = "Write a minimal contextkeeper.toml for a project that has none, from what was detected: project type, build entry point, target config directory, container runtime and history patterns. Only when the user asks to set the project up; an existing config is refused unless force is true. Returns the path written and its content."
+    )]
+    pub async fn bootstrap_config(
+        &self,
+        params: Parameters<BootstrapConfigParams>,
+        ct: CancellationToken,
+        peer: Peer<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let mut call = RecordedCall::start("bootstrap_config", &params.0);
+        let BootstrapConfigParams {
+            force,
+            name,
+            project_root,
+        } = params.0;
+        let (project, source) = self.resolve_project(project_root.as_deref(), &peer).await?;
+        call.project(&project);
+        let drift = self.root_drift(&project, source);
+        refuse_drifted_write(&drift, "write the config")?;
+        tracing::info!(root = %project.root.display(), "bootstrap_config");
+
+        let deadline = tool_deadline(&project.config);
+        let root = project.root.clone();
+        let force = force.unwrap_or(false);
+        let (path, content) = run_with_deadline(deadline, &ct, move || {
+            write_bootstrap_config(&root, force, name)
+        })
+        .await
+        .map_err(|status| {
+            McpError::internal_error(format!("writing the config {}", status.as_str()), None)
+        })??;
+        // The next calls must read the new config
+        self.contexts.write().unwrap().remove(&project.root);
+        self.summaries.lock().unwrap().remove(&project.root);
+
+        let text = format!(
+            "Wrote {}:\n\n```toml\n{}```\n\nget_dev_context uses it from the next call. \
+             Review it, then add hints, target configs or containers as needed.",
+            path.display(),
+            content
+        );
+        let text = redact_output(&project.config, &text);
+        let text = format_root_drift(&drift) + &text;
+        call.output(&text);
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+}
+
+#[tool_handler]
+impl ServerHandler for ContextKeeperService {
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            protocol_version: ProtocolVersion::LATEST,
+            capabilities: ServerCapabilities::builder()
+                .enable_tools()
+                .enable_logging()
+                .build(),
+            server_info: Implementation {
+                name: "context-keeper".into(),
+                version: env!("CARGO_PKG_VERSION").into(),
+                ..Default::default()
+            },
+            instructions: Some(
+                "ContextKeeper provides development environment context. \
+                 Start a new session with get_project_summary to learn what \
+                 the project is and how it builds. Use get_dev_context for \
+                 live state: saved work, git status, build targets, \
+                 containers, devices and recent commands."
+                    .into(),
+            ),
+        }
+    }
+
+    async fn on_initialized(&self, context: NotificationContext<RoleServer>) {
+        tracing::info!("client initialized");
+        connect_client_log(context.peer);
+        // Prewarm loaded these before the client could hear about them
+        for project in self.projects.lock().unwrap().values() {
+            project.warn_config_error();
+        }
+    }
+
+    async fn set_level(
+        &self,
+        request: SetLevelRequestParams,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        tracing::debug!(level = ?request.level, "client log level set");
+        set_client_level(request.level);
+        Ok(())
+    }
+}
+
+/// Path `serve --http` answers MCP on
+pub const HTTP_MCP_PATH: &str = "/mcp";
+
+/// Serve `service` until the client hangs up (stdio) or Ctrl-C (`http`),
+/// pre-warming the cache and keeping the heartbeat meanwhile. Over HTTP
+/// every session shares the one service, and so its cache.
+pub async fn serve(
+    service: ContextKeeperService,
+    http: Option<std::net::SocketAddr>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let heartbeat = service.clone();
+    let shutdown = service.clone();
+    tokio::spawn(async move { heartbeat.heartbeat().await });
+    let served = match http {
+        Some(addr) => serve_http(service, addr).await,
+        None => serve_stdio(service).await,
+    };
+    shutdown.end_heartbeat();
+    served
+}
+
+#[cfg(feature = "stdio-server")]
+async fn serve_stdio(service: ContextKeeperService) -> Result<(), Box<dyn std::error::Error>> {
+    use rmcp::ServiceExt;
+
+    let prewarm = service.clone();
+    let server = service.serve(rmcp::transport::stdio()).await?;
+    tokio::spawn(async move { prewarm.prewarm().await });
+    server.waiting().await?;
+    Ok(())
+}
+
+#[cfg(not(feature = "stdio-server"))]
+async fn serve_stdio(_service: ContextKeeperService) -> Result<(), Box<dyn std::error::Error>> {
+    Err(crate::collectors::not_built("stdio-server").into())
+}
+
+#[cfg(feature = "http-transport")]
+async fn serve_http(
+    service: ContextKeeperService,
+    addr: std::net::SocketAddr,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use rmcp::transport::streamable_http_server::{
+        session::local::LocalSessionManager, StreamableHttpServerConfig, StreamableHttpService,
+    };
+
+    let ct = tokio_util::sync::CancellationToken::new();
+    let sessions = {
+        let service = service.clone();
+        StreamableHttpService::new(
+            move || Ok(service.clone()),
+            Arc::new(LocalSessionManager::default()),
+            StreamableHttpServerConfig {
+                cancellation_token: ct.child_token(),
+                ..Default::default()
+            },
+        )
+    };
+    let router = axum::Router::new().nest_service(HTTP_MCP_PATH, sessions);
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    // On stderr, with the port the OS picked for `:0`
+    eprintln!(
+        "Serving MCP on http://{}{}",
+        listener.local_addr()?,
+        HTTP_MCP_PATH
+    );
+    tokio::spawn(async move { service.prewarm().await });
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async move {
+            let _ = tokio::signal::ctrl_c().await;
+            ct.cancel();
+        })
+        .await?;
+    Ok(())
+}
+
+#[cfg(not(feature = "http-transport"))]
+async fn serve_http(
+    _service: ContextKeeperService,
+    _addr: std::net::SocketAddr,
+) -> Result<(), Box<dyn std::error::Error>> {
+    Err(crate::collectors::not_built("http-transport").into())
+}
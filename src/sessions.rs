@@ -0,0 +1,211 @@
+//! Other context-keeper servers on the same project, and the locking that
+//! keeps them from overwriting each other's saves
+//!
+//! Two Claude Code windows on one project run two servers sharing
+//! `~/.contextkeeper`. Each server has an instance id, stamped on the work
+//! states and todos it saves, and refreshes a heartbeat in the project's
+//! `instances.json` every `HEARTBEAT_INTERVAL`. A save merges in the todos
+//! of the other live instances (see `save_work_state_to_file`), and
+//! get_dev_context says when another one is active. Shared files are
+//! written under an advisory lock on a `.lock` file beside them; the shell
+//! hooks take the same lock with flock(1).
+
+use crate::state::project_state_dir;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// How often a server refreshes its heartbeat
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A heartbeat older than this belongs to a server that is gone
+pub const SESSION_STALE_AFTER: Duration = Duration::from_secs(120);
+
+/// One running server, as its heartbeat records it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionEntry {
+    pub instance_id: String,
+    pub pid: u32,
+    /// RFC 3339
+    pub started_at: String,
+    /// RFC 3339, refreshed every `HEARTBEAT_INTERVAL`
+    pub last_seen: String,
+}
+
+fn instance() -> &'static (String, String) {
+    static INSTANCE: OnceLock<(String, String)> = OnceLock::new();
+    INSTANCE.get_or_init(|| {
+        let now = chrono::Utc::now();
+        (
+            format!("{}-{:x}", std::process::id(), now.timestamp_millis()),
+            now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        )
+    })
+}
+
+/// This process's instance id: its pid and start time, so a reused pid
+/// gets a new one
+pub fn instance_id() -> &'static str {
+    &instance().0
+}
+
+/// `path` with `suffix` added to the file name, e.g. `work-state.json.lock`
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Exclusive lock on `path`'s `.lock` file, held until the returned file is
+/// dropped. Only writers that take it are kept out.
+pub fn lock_shared_file(path: &Path) -> io::Result<fs::File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let lock = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(sibling(path, ".lock"))?;
+    lock.lock()?;
+    Ok(lock)
+}
+
+/// Replace `path` with `contents` in one rename, so a reader never sees a
+/// half-written file
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp = sibling(path, &format!(".{}.tmp", std::process::id()));
+    let written = fs::File::create(&tmp)
+        .and_then(|mut file| file.write_all(contents).and_then(|_| file.sync_all()));
+    match written.and_then(|_| fs::rename(&tmp, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
+/// `~/.contextkeeper/projects/<root-hash>/instances.json`
+pub fn instances_path(root: &Path) -> PathBuf {
+    project_state_dir(root).join("instances.json")
+}
+
+fn read_sessions(path: &Path) -> Vec<SessionEntry> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Whether `pid` is a running process; always true where that can't be
+/// asked
+fn process_alive(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        // Signal 0 only checks; EPERM means it runs as another user
+        // SAFETY: kill(2) with signal 0 sends nothing
+        let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+        result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        true
+    }
+}
+
+/// Seconds since `entry` last refreshed its heartbeat, `None` when the
+/// timestamp doesn't parse
+fn seconds_since_seen(entry: &SessionEntry, now: chrono::DateTime<chrono::Utc>) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(&entry.last_seen)
+        .ok()
+        .map(|seen| {
+            (now - seen.with_timezone(&chrono::Utc))
+                .num_seconds()
+                .max(0)
+        })
+}
+
+fn is_live(entry: &SessionEntry, now: chrono::DateTime<chrono::Utc>) -> bool {
+    seconds_since_seen(entry, now)
+        .is_some_and(|seconds| seconds <= SESSION_STALE_AFTER.as_secs() as i64)
+        && process_alive(entry.pid)
+}
+
+/// Rewrite `root`'s heartbeats under the lock: drop stale ones and this
+/// instance's, then add this instance's back when `alive`
+fn update_heartbeat(root: &Path, alive: bool) -> io::Result<()> {
+    let path = instances_path(root);
+    let _lock = lock_shared_file(&path)?;
+    let now = chrono::Utc::now();
+    let mut sessions: Vec<SessionEntry> = read_sessions(&path)
+        .into_iter()
+        .filter(|entry| entry.instance_id != instance_id() && is_live(entry, now))
+        .collect();
+    if alive {
+        sessions.push(SessionEntry {
+            instance_id: instance_id().to_string(),
+            pid: std::process::id(),
+            started_at: instance().1.clone(),
+            last_seen: now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        });
+    }
+    let json = serde_json::to_string_pretty(&sessions).map_err(io::Error::other)?;
+    write_atomic(&path, json.as_bytes())
+}
+
+/// Mark this instance as active on `root`
+pub fn record_heartbeat(root: &Path) -> io::Result<()> {
+    update_heartbeat(root, true)
+}
+
+/// Take this instance off `root`'s heartbeats, on shutdown
+pub fn remove_heartbeat(root: &Path) -> io::Result<()> {
+    update_heartbeat(root, false)
+}
+
+/// Live instances on `root` other than this one, oldest first
+pub fn other_sessions(root: &Path) -> Vec<SessionEntry> {
+    let now = chrono::Utc::now();
+    let mut sessions: Vec<SessionEntry> = read_sessions(&instances_path(root))
+        .into_iter()
+        .filter(|entry| entry.instance_id != instance_id() && is_live(entry, now))
+        .collect();
+    sessions.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+    sessions
+}
+
+/// "> Another session is active on this project (pid 12345, last seen 30s
+/// ago)", empty without one
+pub fn format_other_sessions(sessions: &[SessionEntry]) -> String {
+    let now = chrono::Utc::now();
+    let described: Vec<String> = sessions
+        .iter()
+        .map(|entry| {
+            format!(
+                "pid {}, last seen {}s ago",
+                entry.pid,
+                seconds_since_seen(entry, now).unwrap_or_default()
+            )
+        })
+        .collect();
+    match described.len() {
+        0 => String::new(),
+        1 => format!(
+            "> Another session is active on this project ({}). Saves merge \
+             its todos instead of replacing them.\n\n",
+            described[0]
+        ),
+        n => format!(
+            "> {} other sessions are active on this project ({}). Saves merge \
+             their todos instead of replacing them.\n\n",
+            n,
+            described.join("; ")
+        ),
+    }
+}
@@ -0,0 +1,913 @@
+//! Saved work state for recovery after context compression
+
+use crate::collectors::code_todos::collect_code_todos;
+use crate::collectors::diff_summary::{collect_diff_summary, FileDiff};
+use crate::collectors::git::{discover_git_repos, git_in, RepoSource};
+use crate::collectors::open_files::{open_files_now, OpenFile};
+use crate::collectors::recent_files::collect_recent_files;
+use crate::collectors::working_files::head_commit;
+use crate::collectors::{CodeTodo, CommandRunner, TmuxPane};
+use crate::config::{Config, ProjectContext};
+use crate::diary::{append_diary_entry, diary_path};
+use crate::error::ContextKeeperError;
+use crate::layout::{data_layout, legacy_work_state_path, pinned_data_layout, DataLayout};
+use crate::limits::Limits;
+use crate::paths::{path_bytes, path_from_bytes, project_relative};
+use crate::sessions::{instance_id, lock_shared_file, write_atomic};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TodoItem {
+    pub content: String,
+    pub status: String, // "pending", "in_progress", "completed"
+    /// Instance that saved it, see `sessions`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub instance_id: String,
+    /// When the item was added or its status last changed (RFC 3339);
+    /// empty in older files, which count as their `saved_at`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub updated_at: String,
+}
+
+/// Version of the work-state file format. 2 added `next_steps`, 3
+/// `diff_summary`, 4 `unverified_files`, 5 `metadata`, 6 the todos'
+/// `updated_at` and `notes_updated_at`; files without a version are 1.
+pub const WORK_STATE_VERSION: u32 = 6;
+
+fn unversioned() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct WorkState {
+    #[serde(default = "unversioned")]
+    pub version: u32,
+    pub saved_at: String,
+    pub trigger: String, // "manual", "pre_compact", "auto", "git_hook"
+    pub task_summary: String,
+    pub working_files: Vec<String>,
+    /// `working_files` given explicitly that weren't on disk at save time
+    /// (see `verify_working_files`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unverified_files: Vec<String>,
+    /// `working_files` found by modification time under `[recent_files]
+    /// paths`, not by git
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recent_files: Vec<String>,
+    pub notes: String,
+    pub todos: Vec<TodoItem>,
+    /// What to do next, in order; shown ahead of the todos
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub next_steps: Vec<String>,
+    /// Project root the state was saved for (empty in older files)
+    #[serde(default)]
+    pub project_root: String,
+    /// Repositories `working_files` were collected from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub working_repos: Vec<WorkingRepo>,
+    /// tmux panes under the project root when the state was saved
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub terminal_panes: Vec<TmuxPane>,
+    /// Files open in the editor at save time, focused first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub open_files: Vec<OpenFile>,
+    /// TODO comments in `working_files` at save time, with `scan_todos`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub code_todos: Vec<CodeTodo>,
+    /// Change to each of `working_files` against HEAD at save time, with
+    /// `capture_diffs`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diff_summary: Vec<FileDiff>,
+    /// Instance that saved it, see `sessions`; empty in older files
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub instance_id: String,
+    /// When `notes` last changed (RFC 3339); empty in older files
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes_updated_at: String,
+    /// Facts a team wants kept with the state, such as the Gerrit change
+    /// or the ticket; see `apply_metadata_updates`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl WorkState {
+    /// `working_files` and `working_repos` paths in `project_relative`
+    /// form, without duplicates. States saved before this held whatever
+    /// the caller passed, such as `./repo//file` or absolute paths.
+    pub fn normalize_paths(&mut self, root: &Path) {
+        let mut seen = HashSet::new();
+        self.working_files = self
+            .working_files
+            .iter()
+            .map(|file| project_relative(root, Path::new(file)))
+            .filter(|file| seen.insert(file.clone()))
+            .collect();
+        for file in self
+            .unverified_files
+            .iter_mut()
+            .chain(self.recent_files.iter_mut())
+        {
+            *file = project_relative(root, Path::new(file));
+        }
+        for repo in &mut self.working_repos {
+            repo.path = project_relative(root, Path::new(&repo.path));
+        }
+    }
+}
+
+/// Metadata keys starting with this are context-keeper's own (schema
+/// hints and the like); callers can't set or remove them
+pub const RESERVED_METADATA_PREFIX: &str = "ck:";
+
+/// Most metadata keys in one state
+pub const MAX_METADATA_KEYS: usize = 32;
+
+/// Longest metadata key, in characters
+pub const MAX_METADATA_KEY_CHARS: usize = 64;
+
+/// Longest metadata value, in bytes
+pub const MAX_METADATA_VALUE_BYTES: usize = 1024;
+
+/// Why a caller can't use `key`, if it can't: empty, too long, a
+/// character other than letters, digits, `.`, `_`, `-` and `:`, or the
+/// reserved prefix
+fn metadata_key_error(key: &str) -> Option<String> {
+    if key.is_empty() {
+        return Some("metadata keys can't be empty".to_string());
+    }
+    if key.chars().count() > MAX_METADATA_KEY_CHARS {
+        return Some(format!(
+            "metadata key {:?} is longer than {} characters",
+            key, MAX_METADATA_KEY_CHARS
+        ));
+    }
+    if let Some(c) = key
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | ':')))
+    {
+        return Some(format!(
+            "metadata key {:?} has {:?}; use letters, digits, '.', '_', '-' and ':'",
+            key, c
+        ));
+    }
+    if key.starts_with(RESERVED_METADATA_PREFIX) {
+        return Some(format!(
+            "metadata key {:?}: the {:?} prefix is reserved",
+            key, RESERVED_METADATA_PREFIX
+        ));
+    }
+    None
+}
+
+/// `metadata` with `updates` applied: a value sets its key, `None`
+/// removes it, keys not mentioned stay. Values go through `redact` before
+/// they are kept. Nothing changes when a key or value is invalid or the
+/// result holds more than `MAX_METADATA_KEYS` keys.
+pub fn apply_metadata_updates(
+    metadata: &mut BTreeMap<String, String>,
+    updates: BTreeMap<String, Option<String>>,
+    redact: impl Fn(&str) -> String,
+) -> Result<(), ContextKeeperError> {
+    let mut updated = metadata.clone();
+    for (key, value) in updates {
+        if let Some(error) = metadata_key_error(&key) {
+            return Err(ContextKeeperError::Validation(error));
+        }
+        match value {
+            Some(value) if value.len() > MAX_METADATA_VALUE_BYTES => {
+                return Err(ContextKeeperError::Validation(format!(
+                    "metadata value for {:?} is {} bytes; at most {} are kept",
+                    key,
+                    value.len(),
+                    MAX_METADATA_VALUE_BYTES
+                )));
+            }
+            Some(value) => {
+                updated.insert(key, redact(value.trim()));
+            }
+            None => {
+                updated.remove(&key);
+            }
+        }
+    }
+    if updated.len() > MAX_METADATA_KEYS {
+        return Err(ContextKeeperError::Validation(format!(
+            "{} metadata keys; at most {} can be kept",
+            updated.len(),
+            MAX_METADATA_KEYS
+        )));
+    }
+    *metadata = updated;
+    Ok(())
+}
+
+/// The metadata of the state saved for `root`, which a new save starts
+/// from; empty without one
+pub fn saved_metadata(root: &Path) -> BTreeMap<String, String> {
+    let saved = load_work_state_from_file(root);
+    let root = root.to_string_lossy();
+    saved
+        .ok()
+        .flatten()
+        .filter(|s| s.project_root.is_empty() || s.project_root == root)
+        .map(|s| s.metadata)
+        .unwrap_or_default()
+}
+
+/// A repository that contributed to `WorkState::working_files`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkingRepo {
+    /// Path relative to the project root, as in the git section
+    pub path: String,
+    pub found_by: RepoSource,
+    /// Number of files taken from this repository
+    pub files: usize,
+    /// Abbreviated HEAD at save time, to tell the commits made since
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub head: String,
+}
+
+/// User home directory: HOME on Unix, the profile folder on Windows.
+/// Falls back to the current directory when there is none.
+pub fn home_dir() -> PathBuf {
+    dirs::home_dir().unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// `~/.contextkeeper`, shared with the hook scripts
+pub fn contextkeeper_dir() -> PathBuf {
+    contextkeeper_dir_in(&home_dir())
+}
+
+/// `.contextkeeper` under `home`
+pub fn contextkeeper_dir_in(home: &Path) -> PathBuf {
+    home.join(".contextkeeper")
+}
+
+/// FNV-1a: stable across builds, unlike std's hasher
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The `<root-hash>` naming a project root under `~/.contextkeeper/projects`
+pub fn project_hash(root: &Path) -> String {
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    format!("{:016x}", fnv1a(&path_bytes(&root)))
+}
+
+/// `~/.contextkeeper/projects/<root-hash>`: state kept for one project root
+pub fn project_state_dir(root: &Path) -> PathBuf {
+    project_state_dir_in(&home_dir(), root)
+}
+
+/// `project_state_dir` with `home` as the home directory
+pub fn project_state_dir_in(home: &Path, root: &Path) -> PathBuf {
+    contextkeeper_dir_in(home)
+        .join("projects")
+        .join(project_hash(root))
+}
+
+/// Where `root`'s work state is kept: `projects/<root-hash>/` on the
+/// current layout, the one file shared by every project on the first
+pub fn work_state_path(root: &Path) -> Result<PathBuf, ContextKeeperError> {
+    Ok(match data_layout()? {
+        DataLayout::Legacy => legacy_work_state_path(),
+        DataLayout::Current => project_state_dir(root).join("work-state.json"),
+    })
+}
+
+pub fn ensure_contextkeeper_dir() -> io::Result<()> {
+    fs::create_dir_all(contextkeeper_dir())
+}
+
+/// How long the todos of another instance that this save doesn't repeat
+/// are kept after they last changed
+pub const FOREIGN_TODO_MAX_AGE: chrono::Duration = chrono::Duration::hours(24);
+
+/// The todos and notes this process last read or wrote for a project
+/// root. A save compares with it to tell the items it changed from the
+/// ones it only passes on as they were when it read them.
+#[derive(Debug, Default, Clone)]
+struct SeenState {
+    /// content -> (status, updated_at)
+    todos: BTreeMap<String, (String, String)>,
+    notes: String,
+    notes_updated_at: String,
+}
+
+static SEEN: std::sync::Mutex<BTreeMap<String, SeenState>> = std::sync::Mutex::new(BTreeMap::new());
+
+/// Note that this process showed `state` as `root`'s work state, so a
+/// later save can tell what it changed (see `merge_saved_state`)
+pub fn remember_seen(root: &Path, state: &WorkState) {
+    let todos = state
+        .todos
+        .iter()
+        .map(|todo| {
+            let updated_at = or_saved_at(&todo.updated_at, &state.saved_at);
+            (todo.content.clone(), (todo.status.clone(), updated_at))
+        })
+        .collect();
+    let seen = SeenState {
+        todos,
+        notes: state.notes.clone(),
+        notes_updated_at: or_saved_at(&state.notes_updated_at, &state.saved_at),
+    };
+    SEEN.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(root.to_string_lossy().into_owned(), seen);
+}
+
+fn seen_state(root: &str) -> SeenState {
+    SEEN.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(root)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// `stamp`, or `saved_at` for items from files older than the stamps
+fn or_saved_at(stamp: &str, saved_at: &str) -> String {
+    if stamp.is_empty() { saved_at } else { stamp }.to_string()
+}
+
+/// RFC 3339 stamps compared as times; unparsable ones are oldest
+fn is_newer(stamp: &str, than: &str) -> bool {
+    let parse = |s: &str| chrono::DateTime::parse_from_rfc3339(s).ok();
+    parse(stamp) > parse(than)
+}
+
+/// Merge `saved`, the state on disk, into `state`, item by item, the
+/// newer change winning whoever is live:
+///
+/// - a todo in both keeps the status changed last; a status `state` only
+///   passes on, as this process read it, isn't a change
+/// - a todo only on disk stays if another instance saved it within
+///   `FOREIGN_TODO_MAX_AGE`; one of this instance's that `state` leaves
+///   out was removed
+/// - the notes are `state`'s if it changed them, else the newer ones
+///
+/// Everything else is `state`'s, as the newer save. `seen` is what this
+/// process last read or wrote (see `SeenState`).
+fn merge_saved_state(state: &mut WorkState, saved: WorkState, seen: &SeenState) {
+    let now = state.saved_at.clone();
+    // Stamp this save's items: unchanged ones keep their time
+    for todo in &mut state.todos {
+        let on_disk = saved.todos.iter().find(|t| t.content == todo.content);
+        todo.updated_at = match (seen.todos.get(&todo.content), on_disk) {
+            // Passed on as this process last read or wrote it
+            (Some((status, updated_at)), _) if *status == todo.status => updated_at.clone(),
+            // New here, and no different from the disk
+            (None, Some(t)) if t.status == todo.status => {
+                or_saved_at(&t.updated_at, &saved.saved_at)
+            }
+            _ => now.clone(),
+        };
+    }
+    if saved.project_root != state.project_root {
+        if !state.notes.is_empty() {
+            state.notes_updated_at = now;
+        }
+        return;
+    }
+
+    let cutoff = chrono::DateTime::parse_from_rfc3339(&now)
+        .map(|now| (now - FOREIGN_TODO_MAX_AGE).to_rfc3339())
+        .unwrap_or_default();
+    for mut todo in saved.todos {
+        if todo.instance_id.is_empty() {
+            todo.instance_id = saved.instance_id.clone();
+        }
+        todo.updated_at = or_saved_at(&todo.updated_at, &saved.saved_at);
+        match state.todos.iter_mut().find(|t| t.content == todo.content) {
+            Some(ours) if is_newer(&todo.updated_at, &ours.updated_at) => *ours = todo,
+            Some(_) => {}
+            None if todo.instance_id != state.instance_id
+                && is_newer(&todo.updated_at, &cutoff) =>
+            {
+                state.todos.push(todo);
+            }
+            None => {}
+        }
+    }
+
+    let saved_notes_at = or_saved_at(&saved.notes_updated_at, &saved.saved_at);
+    let changed_notes = !state.notes.is_empty() && state.notes != seen.notes;
+    if changed_notes {
+        state.notes_updated_at = now;
+    } else if state.notes.is_empty() || is_newer(&saved_notes_at, &seen.notes_updated_at) {
+        state.notes = saved.notes;
+        state.notes_updated_at = saved_notes_at;
+    } else {
+        state.notes_updated_at = seen.notes_updated_at.clone();
+    }
+}
+
+/// Write `state` as the current work state and add it to the diary. A
+/// diary that can't be written costs a warning, not the save.
+///
+/// Saves hold a lock on the file and replace it in one rename, merging
+/// what other instances saved item by item, see `merge_saved_state`.
+pub fn save_work_state_to_file(
+    state: &WorkState,
+    config: &Config,
+) -> Result<(), ContextKeeperError> {
+    ensure_contextkeeper_dir()
+        .map_err(|e| ContextKeeperError::state_io(&contextkeeper_dir(), e))?;
+    if state.project_root.is_empty() {
+        return Err(ContextKeeperError::Validation(
+            "a work state can't be saved without its project root".to_string(),
+        ));
+    }
+    pinned_data_layout()?;
+    let mut state = state.clone();
+    if state.instance_id.is_empty() {
+        state.instance_id = instance_id().to_string();
+    }
+    for todo in state.todos.iter_mut().filter(|t| t.instance_id.is_empty()) {
+        todo.instance_id = state.instance_id.clone();
+    }
+    let root = PathBuf::from(&state.project_root);
+    let (path, _lock) = loop {
+        let path = work_state_path(&root)?;
+        let lock = lock_shared_file(&path).map_err(|e| ContextKeeperError::state_io(&path, e))?;
+        // `migrate` may have moved the file while this waited for the lock
+        if work_state_path(&root)? == path {
+            break (path, lock);
+        }
+    };
+    state.normalize_paths(&root);
+    // An unreadable file is replaced, as before
+    let saved = read_work_state(&path).ok().flatten().unwrap_or_default();
+    let seen = seen_state(&state.project_root);
+    merge_saved_state(&mut state, saved, &seen);
+    let json = serde_json::to_string_pretty(&state).map_err(io::Error::other);
+    json.and_then(|json| write_atomic(&path, json.as_bytes()))
+        .map_err(|e| ContextKeeperError::state_io(&path, e))?;
+    remember_seen(&root, &state);
+    if let Err(e) = append_diary_entry(&state, config) {
+        tracing::warn!(path = %diary_path().display(), error = %e, "failed to update the diary");
+    }
+    Ok(())
+}
+
+/// The work state in `path`, `None` when there is no file
+pub fn read_work_state(path: &Path) -> Result<Option<WorkState>, ContextKeeperError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(path).map_err(|e| ContextKeeperError::state_io(path, e))?;
+    serde_json::from_str(&content).map(Some).map_err(|e| {
+        ContextKeeperError::state_io(path, io::Error::new(io::ErrorKind::InvalidData, e))
+    })
+}
+
+/// Work state saved for `root`, `None` when nothing was saved yet. On the
+/// first layout the file is shared, so it may belong to another project.
+pub fn load_work_state_from_file(root: &Path) -> Result<Option<WorkState>, ContextKeeperError> {
+    read_work_state(&work_state_path(root)?)
+}
+
+/// Load saved todos from TodoWrite hook
+pub fn load_saved_todos() -> Vec<TodoItem> {
+    let path = contextkeeper_dir().join("current-todos.json");
+
+    if !path.exists() {
+        return Vec::new();
+    }
+
+    fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        .and_then(|json| {
+            json.get("todos").and_then(|t| t.as_array()).map(|arr| {
+                arr.iter()
+                    .filter_map(|item| {
+                        Some(TodoItem {
+                            content: item.get("content")?.as_str()?.to_string(),
+                            status: item.get("status")?.as_str()?.to_string(),
+                            instance_id: String::new(),
+                            updated_at: String::new(),
+                        })
+                    })
+                    .collect()
+            })
+        })
+        .unwrap_or_default()
+}
+
+/// Load recently edited files from Edit/Write hook
+pub fn load_recent_files() -> Vec<String> {
+    let path = contextkeeper_dir().join("recent-files.json");
+
+    if !path.exists() {
+        return Vec::new();
+    }
+
+    fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        .and_then(|json| {
+            json.get("files").and_then(|f| f.as_array()).map(|arr| {
+                arr.iter()
+                    .filter_map(|item| item.get("path")?.as_str().map(|s| s.to_string()))
+                    .collect()
+            })
+        })
+        .unwrap_or_default()
+}
+
+/// Load or construct work state with hook-collected data
+pub fn load_work_state_with_hooks(root: &Path) -> Result<Option<WorkState>, ContextKeeperError> {
+    // First try to load manually saved work state, ignoring one saved for another project
+    let root_str = root.to_string_lossy();
+    let mut state = load_work_state_from_file(root)?
+        .filter(|s| s.project_root.is_empty() || s.project_root == root_str)
+        .unwrap_or_default();
+
+    // Enhance with hook-collected data
+    let hook_todos = load_saved_todos();
+    let hook_files = load_recent_files();
+
+    // If we have hook data, update the state
+    if !hook_todos.is_empty() {
+        state.todos = hook_todos;
+    }
+
+    if !hook_files.is_empty() && state.working_files.is_empty() {
+        state.working_files = hook_files;
+    }
+    state.normalize_paths(root);
+
+    // Return None if state is completely empty
+    if state.task_summary.is_empty()
+        && state.todos.is_empty()
+        && state.next_steps.is_empty()
+        && state.working_files.is_empty()
+        && state.notes.is_empty()
+    {
+        return Ok(None);
+    }
+
+    Ok(Some(state))
+}
+
+/// Default cap on files recorded in a saved work state
+pub const DEFAULT_MAX_WORKING_FILES: usize = 20;
+
+/// Limit for each `git diff` run while saving work state
+pub const GIT_DIFF_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Changed files across the project's repositories
+#[derive(Debug, Default)]
+pub struct WorkingFiles {
+    pub files: Vec<String>,
+    pub repos: Vec<WorkingRepo>,
+    /// Those of `files` from `[recent_files]`, not from git
+    pub recent: Vec<String>,
+}
+
+/// Unstaged then staged changes in one repository, without duplicates,
+/// at most `max` of them (`[limits] max_status_lines`).
+/// `-z` output is verbatim: no `core.quotepath` escaping, raw bytes.
+pub fn git_changed_files(runner: &dyn CommandRunner, repo_path: &Path, max: usize) -> Vec<Vec<u8>> {
+    let mut files: Vec<Vec<u8>> = Vec::new();
+    let mut seen: HashSet<Vec<u8>> = HashSet::new();
+    for diff_args in [
+        &["diff", "--name-only", "-z"][..],
+        &["diff", "--cached", "--name-only", "-z"],
+    ] {
+        let Ok(output) = git_in(runner, repo_path, diff_args, Some(GIT_DIFF_TIMEOUT)) else {
+            continue;
+        };
+        if !output.status.success() {
+            continue;
+        }
+        for name in output.stdout.split(|&b| b == 0) {
+            if files.len() >= max {
+                return files;
+            }
+            if !name.is_empty() && seen.insert(name.to_vec()) {
+                files.push(name.to_vec());
+            }
+        }
+    }
+    files
+}
+
+/// Collect working files from git diff (for PreCompact hook). Uses the same
+/// repositories as the git section, so `git.paths` and `scan_depth` apply.
+/// Files from `[recent_files]` fill what `max_working_files` leaves.
+pub fn collect_working_files(project: &ProjectContext) -> WorkingFiles {
+    let max_files = project
+        .config
+        .git
+        .as_ref()
+        .and_then(|g| g.max_working_files)
+        .unwrap_or(DEFAULT_MAX_WORKING_FILES);
+    let max_changed = Limits::from_config(&project.config).max_status_lines;
+
+    let mut working = WorkingFiles::default();
+    for candidate in discover_git_repos(project) {
+        if working.files.len() >= max_files {
+            break;
+        }
+        if candidate.status.is_none() {
+            continue;
+        }
+
+        let changed = git_changed_files(&*project.runner, &candidate.dir, max_changed);
+        let take = changed.len().min(max_files - working.files.len());
+        if take == 0 {
+            continue;
+        }
+
+        working
+            .files
+            .extend(changed.into_iter().take(take).map(|file| {
+                project_relative(&project.root, &candidate.dir.join(path_from_bytes(&file)))
+            }));
+        let head = candidate
+            .status
+            .as_ref()
+            .and_then(|status| status.last_commit_short.split_whitespace().next())
+            .unwrap_or_default()
+            .to_string();
+        working.repos.push(WorkingRepo {
+            path: candidate.path,
+            found_by: candidate.found_by,
+            files: take,
+            head,
+        });
+    }
+
+    // Then what changed where git doesn't look
+    let recent = if working.files.len() < max_files {
+        collect_recent_files(project)
+    } else {
+        Vec::new()
+    };
+    for file in recent {
+        if working.files.len() >= max_files {
+            break;
+        }
+        if !working.files.contains(&file.path) {
+            working.files.push(file.path.clone());
+            working.recent.push(file.path);
+        }
+    }
+
+    working
+}
+
+/// Refresh the saved work state after a commit or checkout in `repo_dir`
+/// (`save-state --auto`, run from the git hooks). Only that repository's
+/// working files, its `working_repos` entry and `saved_at` change, and
+/// only its `git diff` runs; the task, notes, next steps and todos are kept. Returns
+/// false, leaving the file alone, when the saved state belongs to another
+/// project or `repo_dir` is outside the project.
+pub fn refresh_work_state_for_repo(
+    project: &ProjectContext,
+    repo_dir: &Path,
+) -> Result<bool, ContextKeeperError> {
+    let root_str = project.root.to_string_lossy().to_string();
+    let mut state = match load_work_state_from_file(&project.root)? {
+        Some(state) if state.project_root.is_empty() || state.project_root == root_str => state,
+        Some(_) => return Ok(false),
+        None => WorkState::default(),
+    };
+    let repo_dir = repo_dir
+        .canonicalize()
+        .unwrap_or_else(|_| repo_dir.to_path_buf());
+    let Ok(relative) = repo_dir.strip_prefix(&project.root) else {
+        return Ok(false);
+    };
+    let repo_path = project_relative(&project.root, relative);
+
+    // Files from the other repositories stay as they were saved
+    let others: Vec<String> = state
+        .working_repos
+        .iter()
+        .filter(|repo| repo.path != repo_path && repo.path != ".")
+        .map(|repo| format!("{}/", repo.path))
+        .collect();
+    // Files found outside git stay too
+    let recent = state.recent_files.clone();
+    let belongs_here = |file: &String| {
+        if recent.contains(file) {
+            false
+        } else if repo_path == "." {
+            !others
+                .iter()
+                .any(|prefix| file.starts_with(prefix.as_str()))
+        } else {
+            file.starts_with(&format!("{}/", repo_path))
+        }
+    };
+    state.working_files.retain(|file| !belongs_here(file));
+    state.unverified_files.retain(|file| !belongs_here(file));
+
+    let max_files = project
+        .config
+        .git
+        .as_ref()
+        .and_then(|g| g.max_working_files)
+        .unwrap_or(DEFAULT_MAX_WORKING_FILES);
+    let max_changed = Limits::from_config(&project.config).max_status_lines;
+    let changed = git_changed_files(&*project.runner, &repo_dir, max_changed);
+    let take = changed
+        .len()
+        .min(max_files.saturating_sub(state.working_files.len()));
+    state.working_files.extend(
+        changed
+            .into_iter()
+            .take(take)
+            .map(|file| project_relative(&project.root, &repo_dir.join(path_from_bytes(&file)))),
+    );
+
+    let found_by = match state.working_repos.iter().find(|r| r.path == repo_path) {
+        Some(repo) => repo.found_by,
+        None if repo_path == "." => RepoSource::Root,
+        None => {
+            let explicit = project
+                .config
+                .git
+                .as_ref()
+                .and_then(|g| g.paths.as_ref())
+                .is_some_and(|paths| {
+                    paths
+                        .iter()
+                        .any(|p| project_relative(&project.root, Path::new(p)) == repo_path)
+                });
+            if explicit {
+                RepoSource::Explicit
+            } else {
+                RepoSource::AutoDetect
+            }
+        }
+    };
+    state.working_repos.retain(|repo| repo.path != repo_path);
+    if take > 0 {
+        state.working_repos.push(WorkingRepo {
+            path: repo_path,
+            found_by,
+            files: take,
+            head: head_commit(&*project.runner, &repo_dir),
+        });
+    }
+
+    state.version = WORK_STATE_VERSION;
+    state.saved_at = chrono::Utc::now().to_rfc3339();
+    state.trigger = "git_hook".to_string();
+    state.project_root = root_str;
+    state.open_files = open_files_now(project);
+    state.code_todos = collect_code_todos(project, &state.working_files);
+    state.diff_summary = collect_diff_summary(project, &state.working_files);
+    save_work_state_to_file(&state, &project.config)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOT: &str = "/src/project";
+
+    fn todo(content: &str, status: &str, instance: &str, updated_at: &str) -> TodoItem {
+        TodoItem {
+            content: content.to_string(),
+            status: status.to_string(),
+            instance_id: instance.to_string(),
+            updated_at: updated_at.to_string(),
+        }
+    }
+
+    fn state(instance: &str, saved_at: &str, todos: Vec<TodoItem>) -> WorkState {
+        WorkState {
+            saved_at: saved_at.to_string(),
+            project_root: ROOT.to_string(),
+            instance_id: instance.to_string(),
+            todos,
+            ..Default::default()
+        }
+    }
+
+    fn statuses(state: &WorkState) -> Vec<(&str, &str)> {
+        let mut todos: Vec<_> = state
+            .todos
+            .iter()
+            .map(|t| (t.content.as_str(), t.status.as_str()))
+            .collect();
+        todos.sort();
+        todos
+    }
+
+    const T1: &str = "2026-03-01T10:00:00+00:00";
+    const T2: &str = "2026-03-01T11:00:00+00:00";
+    const T3: &str = "2026-03-01T12:00:00+00:00";
+
+    #[test]
+    fn newer_status_change_wins_either_way() {
+        let saved = state("a", T2, vec![todo("parse", "completed", "a", T2)]);
+        // Passed on as read at T1: not a change
+        let seen = SeenState {
+            todos: [("parse".into(), ("pending".into(), T1.into()))].into(),
+            ..Default::default()
+        };
+        let mut ours = state("b", T3, vec![todo("parse", "pending", "b", "")]);
+        merge_saved_state(&mut ours, saved.clone(), &seen);
+        assert_eq!(statuses(&ours), [("parse", "completed")]);
+
+        // Changed here after reading: newer than the disk
+        let mut ours = state("b", T3, vec![todo("parse", "in_progress", "b", "")]);
+        merge_saved_state(&mut ours, saved, &seen);
+        assert_eq!(statuses(&ours), [("parse", "in_progress")]);
+        assert_eq!(ours.todos[0].updated_at, T3);
+    }
+
+    #[test]
+    fn other_instances_keep_their_todos_own_removals_stick() {
+        let saved = state(
+            "a",
+            T2,
+            vec![
+                todo("theirs", "pending", "a", T2),
+                todo("mine, done with", "pending", "b", T1),
+            ],
+        );
+        let mut ours = state("b", T3, vec![todo("new", "pending", "", "")]);
+        merge_saved_state(&mut ours, saved, &SeenState::default());
+        assert_eq!(statuses(&ours), [("new", "pending"), ("theirs", "pending")]);
+    }
+
+    #[test]
+    fn old_todos_of_other_instances_expire() {
+        let day_later = "2026-03-02T10:00:01+00:00";
+        let saved = state("a", T1, vec![todo("stale", "pending", "a", T1)]);
+        let mut ours = state("b", day_later, Vec::new());
+        merge_saved_state(&mut ours, saved, &SeenState::default());
+        assert!(ours.todos.is_empty());
+    }
+
+    #[test]
+    fn unstamped_todos_count_as_their_save() {
+        // A version 5 file: no per-item stamps
+        let saved = state("a", T2, vec![todo("parse", "completed", "", "")]);
+        let seen = SeenState {
+            todos: [("parse".into(), ("pending".into(), T1.into()))].into(),
+            ..Default::default()
+        };
+        let mut ours = state("b", T3, vec![todo("parse", "pending", "b", "")]);
+        merge_saved_state(&mut ours, saved, &seen);
+        assert_eq!(statuses(&ours), [("parse", "completed")]);
+        assert_eq!(ours.todos[0].instance_id, "a");
+    }
+
+    #[test]
+    fn notes_new_ones_win_empty_keeps_saved() {
+        let mut saved = state("a", T2, Vec::new());
+        saved.notes = "theirs".to_string();
+
+        let mut ours = state("b", T3, Vec::new());
+        merge_saved_state(&mut ours, saved.clone(), &SeenState::default());
+        assert_eq!(
+            (ours.notes.as_str(), ours.notes_updated_at.as_str()),
+            ("theirs", T2)
+        );
+
+        let mut ours = state("b", T3, Vec::new());
+        ours.notes = "mine".to_string();
+        merge_saved_state(&mut ours, saved.clone(), &SeenState::default());
+        assert_eq!(
+            (ours.notes.as_str(), ours.notes_updated_at.as_str()),
+            ("mine", T3)
+        );
+
+        // Notes passed on as read earlier lose to newer ones on disk
+        let seen = SeenState {
+            notes: "old".to_string(),
+            notes_updated_at: T1.to_string(),
+            ..Default::default()
+        };
+        let mut ours = state("b", T3, Vec::new());
+        ours.notes = "old".to_string();
+        merge_saved_state(&mut ours, saved, &seen);
+        assert_eq!(ours.notes, "theirs");
+    }
+
+    #[test]
+    fn another_projects_state_is_not_merged() {
+        let mut saved = state("a", T2, vec![todo("theirs", "pending", "a", T2)]);
+        saved.project_root = "/src/other".to_string();
+        let mut ours = state("b", T3, Vec::new());
+        merge_saved_state(&mut ours, saved, &SeenState::default());
+        assert!(ours.todos.is_empty());
+    }
+}
@@ -0,0 +1,293 @@
+//! Local statistics of MCP tool calls
+//!
+//! Each tool call appends one JSON line to `~/.contextkeeper/stats.jsonl`:
+//! the tool, the level, hashes of the parameters and the project root, how
+//! long it took, how much it returned and whether the context cache
+//! answered it. Nothing else identifies the project, and nothing leaves
+//! the machine. `[stats] enabled = false` turns it off. Recording happens
+//! on a blocking thread after the call and never fails it; `context-keeper
+//! stats` sums the file up.
+
+use crate::config::{Config, ProjectContext};
+use crate::state::{contextkeeper_dir, fnv1a, project_hash};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// Default for `stats --since`
+pub const DEFAULT_STATS_SINCE: &str = "30d";
+
+/// Size at which the oldest half of `stats.jsonl` is dropped
+pub const MAX_STATS_SIZE: u64 = 4 * 1024 * 1024;
+
+pub fn stats_path() -> PathBuf {
+    contextkeeper_dir().join("stats.jsonl")
+}
+
+/// `[stats] enabled`
+pub fn stats_enabled(config: &Config) -> bool {
+    config
+        .stats
+        .as_ref()
+        .and_then(|s| s.enabled)
+        .unwrap_or(true)
+}
+
+/// One line of `stats.jsonl`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallRecord {
+    /// When the call finished, RFC 3339
+    pub timestamp: String,
+    pub tool: String,
+    /// `project_hash` of the root the call was for
+    pub project: String,
+    /// get_dev_context's level, or "sections" when it was given sections
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<String>,
+    /// FNV-1a of the parameters, telling repeated calls apart without
+    /// storing paths or task text
+    pub params: String,
+    pub duration_ms: u64,
+    /// Bytes of text returned; 0 for a failed call
+    pub output_bytes: usize,
+    /// Whether the context cache answered; `None` for uncached tools
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_hit: Option<bool>,
+    /// False when the call returned an error
+    pub ok: bool,
+}
+
+/// Append `record`, dropping the oldest half of the file first once it
+/// is over `MAX_STATS_SIZE`
+pub fn append_tool_call(record: &ToolCallRecord) -> io::Result<()> {
+    let path = stats_path();
+    if fs::metadata(&path).is_ok_and(|m| m.len() > MAX_STATS_SIZE) {
+        let content = fs::read_to_string(&path)?;
+        let lines: Vec<&str> = content.lines().collect();
+        let kept: String = lines[lines.len() / 2..]
+            .iter()
+            .flat_map(|line| [*line, "\n"])
+            .collect();
+        let tmp = path.with_extension("jsonl.tmp");
+        fs::write(&tmp, kept)?;
+        fs::rename(&tmp, &path)?;
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut line = serde_json::to_string(record).map_err(io::Error::other)?;
+    line.push('\n');
+    // One write, so concurrent servers don't interleave
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?
+        .write_all(line.as_bytes())
+}
+
+/// A tool call being measured. Dropping it records the call, so a call
+/// that returns early with an error is recorded too (as not `ok`).
+#[derive(Debug)]
+pub struct RecordedCall {
+    tool: &'static str,
+    params: String,
+    started: Instant,
+    /// Project hash, when the project was resolved and has stats enabled
+    project: Option<String>,
+    level: Option<String>,
+    cache_hit: Option<bool>,
+    output_bytes: Option<usize>,
+}
+
+impl RecordedCall {
+    pub fn start(tool: &'static str, params: &impl Debug) -> Self {
+        RecordedCall {
+            tool,
+            params: format!("{:016x}", fnv1a(format!("{:?}", params).as_bytes())),
+            started: Instant::now(),
+            project: None,
+            level: None,
+            cache_hit: None,
+            output_bytes: None,
+        }
+    }
+
+    /// The project the call is for; without one, nothing is recorded
+    pub fn project(&mut self, project: &ProjectContext) {
+        self.project = stats_enabled(&project.config).then(|| project_hash(&project.root));
+    }
+
+    pub fn level(&mut self, level: &str) {
+        self.level = Some(level.to_string());
+    }
+
+    pub fn cache_hit(&mut self, hit: bool) {
+        self.cache_hit = Some(hit);
+    }
+
+    /// The text the call returns, marking it successful
+    pub fn output(&mut self, text: &str) {
+        self.output_bytes = Some(text.len());
+    }
+}
+
+impl Drop for RecordedCall {
+    fn drop(&mut self) {
+        let Some(project) = self.project.take() else {
+            return;
+        };
+        let record = ToolCallRecord {
+            timestamp: Utc::now().to_rfc3339(),
+            tool: self.tool.to_string(),
+            project,
+            level: self.level.take(),
+            params: std::mem::take(&mut self.params),
+            duration_ms: self.started.elapsed().as_millis() as u64,
+            output_bytes: self.output_bytes.unwrap_or(0),
+            cache_hit: self.cache_hit,
+            ok: self.output_bytes.is_some(),
+        };
+        let write = move || {
+            if let Err(e) = append_tool_call(&record) {
+                tracing::debug!(error = %e, "tool call statistics not recorded");
+            }
+        };
+        // The reply must not wait on the disk
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => drop(handle.spawn_blocking(write)),
+            Err(_) => write(),
+        }
+    }
+}
+
+/// Records in file order and the number of lines that didn't parse. No
+/// file yet is an empty one.
+pub fn load_tool_calls() -> io::Result<(Vec<ToolCallRecord>, usize)> {
+    let content = match fs::read_to_string(stats_path()) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+        Err(e) => return Err(e),
+    };
+    let mut skipped = 0;
+    let records = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(record) => Some(record),
+            Err(_) => {
+                skipped += 1;
+                None
+            }
+        })
+        .collect();
+    Ok((records, skipped))
+}
+
+/// Calls of one tool in the period
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ToolStats {
+    pub tool: String,
+    pub calls: usize,
+    pub errors: usize,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    /// Calls by level, for get_dev_context
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub levels: BTreeMap<String, usize>,
+    /// Calls that went through the context cache, and how many it answered
+    pub cache_lookups: usize,
+    pub cache_hits: usize,
+    pub mean_output_bytes: usize,
+}
+
+impl ToolStats {
+    /// Share of cache lookups answered from the cache, when there were any
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        (self.cache_lookups > 0).then(|| self.cache_hits as f64 / self.cache_lookups as f64)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsDigest {
+    /// Start of the period, RFC 3339
+    pub since: String,
+    /// Projects with calls in the period
+    pub projects: usize,
+    /// Most called tool first
+    pub tools: Vec<ToolStats>,
+    /// Lines of `stats.jsonl` that didn't parse
+    pub skipped: usize,
+}
+
+/// Nearest-rank percentile of sorted `values`
+fn percentile(values: &[u64], p: f64) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+    let rank = ((p * values.len() as f64).ceil() as usize).clamp(1, values.len());
+    values[rank - 1]
+}
+
+/// Per-tool statistics of the calls at or after `since`
+pub fn build_stats_digest(
+    records: &[ToolCallRecord],
+    skipped: usize,
+    since: DateTime<Utc>,
+) -> StatsDigest {
+    let mut by_tool: BTreeMap<&str, Vec<&ToolCallRecord>> = BTreeMap::new();
+    let mut projects: Vec<&str> = Vec::new();
+    for record in records {
+        let in_period = DateTime::parse_from_rfc3339(&record.timestamp)
+            .is_ok_and(|time| time.with_timezone(&Utc) >= since);
+        if !in_period {
+            continue;
+        }
+        by_tool.entry(&record.tool).or_default().push(record);
+        if !projects.contains(&record.project.as_str()) {
+            projects.push(&record.project);
+        }
+    }
+
+    let mut tools: Vec<ToolStats> = by_tool
+        .into_iter()
+        .map(|(tool, calls)| {
+            let mut durations: Vec<u64> = calls.iter().map(|c| c.duration_ms).collect();
+            durations.sort_unstable();
+            let mut stats = ToolStats {
+                tool: tool.to_string(),
+                calls: calls.len(),
+                p50_ms: percentile(&durations, 0.5),
+                p95_ms: percentile(&durations, 0.95),
+                mean_output_bytes: calls.iter().map(|c| c.output_bytes).sum::<usize>()
+                    / calls.len(),
+                ..ToolStats::default()
+            };
+            for call in &calls {
+                if !call.ok {
+                    stats.errors += 1;
+                }
+                if let Some(level) = &call.level {
+                    *stats.levels.entry(level.clone()).or_default() += 1;
+                }
+                if let Some(hit) = call.cache_hit {
+                    stats.cache_lookups += 1;
+                    stats.cache_hits += usize::from(hit);
+                }
+            }
+            stats
+        })
+        .collect();
+    tools.sort_by(|a, b| b.calls.cmp(&a.calls).then_with(|| a.tool.cmp(&b.tool)));
+
+    StatsDigest {
+        since: since.to_rfc3339(),
+        projects: projects.len(),
+        tools,
+        skipped,
+    }
+}
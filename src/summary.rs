@@ -0,0 +1,181 @@
+//! What the project is, without any live state
+//!
+//! `get_project_summary` answers "what is this project" from the config,
+//! the type detectors, the build files in the root, the first level of the
+//! directory tree and the entry point's usage text. Nothing here runs git,
+//! a container runtime or adb, so the server keeps the result until the
+//! config file changes.
+
+use crate::collectors::detect::{detect_project_types, Detection};
+use crate::collectors::entry_point::collect_entry_point;
+use crate::collectors::just::find_justfile;
+use crate::collectors::make::find_makefile;
+use crate::collectors::packages::detect_package_manager;
+use crate::config::ProjectContext;
+use crate::paths::project_relative;
+use serde::Serialize;
+use std::fs;
+
+/// Top-level entries listed; the rest are counted
+pub const MAX_LAYOUT_ENTRIES: usize = 20;
+
+/// Lines of entry-point usage text kept
+pub const MAX_SUMMARY_USAGE_LINES: usize = 12;
+
+/// Build files recognized in the project root besides the Makefile,
+/// justfile and package.json, first match per build system
+const BUILD_FILES: &[(&str, &str)] = &[
+    ("Cargo.toml", "cargo"),
+    ("CMakeLists.txt", "cmake"),
+    ("meson.build", "meson"),
+    ("build.gradle", "gradle"),
+    ("build.gradle.kts", "gradle"),
+    ("pom.xml", "maven"),
+    ("MODULE.bazel", "bazel"),
+    ("WORKSPACE", "bazel"),
+    ("west.yml", "west"),
+    ("Android.bp", "soong"),
+    ("go.mod", "go"),
+    ("pyproject.toml", "python"),
+];
+
+/// A build system and the file it was recognized by
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildSystem {
+    pub name: String,
+    /// Relative to the project root
+    pub file: String,
+}
+
+/// One entry directly under the project root
+#[derive(Debug, Clone, Serialize)]
+pub struct LayoutEntry {
+    pub name: String,
+    pub is_dir: bool,
+    /// Entries inside a directory; 0 for files and unreadable directories
+    pub entries: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProjectSummary {
+    pub name: String,
+    /// `[project] type`
+    pub types: Vec<String>,
+    pub detected: Vec<Detection>,
+    pub build_systems: Vec<BuildSystem>,
+    /// Directories first, then files, by name, at most `MAX_LAYOUT_ENTRIES`
+    pub layout: Vec<LayoutEntry>,
+    /// Top-level entries left out of `layout`
+    pub layout_omitted: usize,
+    /// How the entry point is run, e.g. `./build.sh`
+    pub entry_point: Option<String>,
+    /// Its usage text, at most `MAX_SUMMARY_USAGE_LINES` lines
+    pub usage: Option<String>,
+    /// `[hints] default`, then `[hints.targets]`; conditional hints depend
+    /// on live state and are left to get_dev_context
+    pub hints: Vec<String>,
+}
+
+fn build_systems(project: &ProjectContext) -> Vec<BuildSystem> {
+    let root = &project.root;
+    let mut systems: Vec<BuildSystem> = Vec::new();
+    let mut found = |name: &str, file: String| {
+        if !systems.iter().any(|s| s.name == name) {
+            systems.push(BuildSystem {
+                name: name.to_string(),
+                file,
+            });
+        }
+    };
+    if let Some(makefile) = find_makefile(project) {
+        found("make", project_relative(root, &makefile));
+    }
+    if let Some(justfile) = find_justfile(project) {
+        found("just", project_relative(root, &justfile));
+    }
+    if root.join("package.json").is_file() {
+        found(detect_package_manager(root), "package.json".to_string());
+    }
+    for (file, name) in BUILD_FILES {
+        if root.join(file).is_file() {
+            found(name, file.to_string());
+        }
+    }
+    systems
+}
+
+/// First level of the tree, hidden and ignored entries left out. Returns
+/// the entries kept and how many were dropped.
+fn layout(project: &ProjectContext) -> (Vec<LayoutEntry>, usize) {
+    let mut entries: Vec<LayoutEntry> = fs::read_dir(&project.root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            if project.ignore.is_ignored(&entry.path(), is_dir) {
+                return None;
+            }
+            let entries = if is_dir {
+                fs::read_dir(entry.path()).map_or(0, |dir| dir.count())
+            } else {
+                0
+            };
+            Some(LayoutEntry {
+                name,
+                is_dir,
+                entries,
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+    let omitted = entries.len().saturating_sub(MAX_LAYOUT_ENTRIES);
+    entries.truncate(MAX_LAYOUT_ENTRIES);
+    (entries, omitted)
+}
+
+pub fn collect_project_summary(project: &ProjectContext) -> ProjectSummary {
+    let config = &project.config;
+    let mut summary = ProjectSummary::default();
+
+    if let Some(project) = &config.project {
+        summary.name = project.name.clone().unwrap_or_default();
+        summary.types = project
+            .project_type
+            .as_ref()
+            .map(|types| types.to_vec().into_iter().map(str::to_string).collect())
+            .unwrap_or_default();
+    }
+    summary.detected = detect_project_types(&project.root, &project.ignore);
+    summary.build_systems = build_systems(project);
+    (summary.layout, summary.layout_omitted) = layout(project);
+
+    if let (_, Some(info)) = collect_entry_point(project) {
+        summary.usage = info.usage.map(|usage| {
+            let lines: Vec<&str> = usage.lines().collect();
+            if lines.len() > MAX_SUMMARY_USAGE_LINES {
+                format!(
+                    "{}\n…",
+                    lines[..MAX_SUMMARY_USAGE_LINES].join("\n").trim_end()
+                )
+            } else {
+                usage
+            }
+        });
+        summary.entry_point = Some(info.invocation);
+    }
+
+    if let Some(hints) = &config.hints {
+        summary
+            .hints
+            .extend(hints.default.iter().filter(|h| !h.is_empty()).cloned());
+        for (name, hint) in hints.targets.iter().flatten() {
+            summary.hints.push(format!("{}: {}", name, hint));
+        }
+    }
+    summary
+}
@@ -0,0 +1,224 @@
+//! The attention list on synthetic contexts with competing issues
+
+use context_keeper::attention::{attention_items, AttentionItem};
+use context_keeper::collectors::artifacts::Artifact;
+use context_keeper::collectors::working_files::{WorkingFileCheck, WorkingFileState};
+use context_keeper::collectors::{BuildTarget, ContainerInfo, GitInfo};
+use context_keeper::config::{AttentionKind, Config};
+use context_keeper::context::{CollectorReport, CollectorStatus};
+use context_keeper::format::{format_attention_section, Level};
+use context_keeper::Context;
+use std::time::Duration;
+
+fn repo(path: &str) -> GitInfo {
+    GitInfo {
+        repo_path: path.to_string(),
+        branch: "main".to_string(),
+        ..GitInfo::default()
+    }
+}
+
+fn dirty(path: &str, modified: usize) -> GitInfo {
+    GitInfo {
+        is_dirty: true,
+        modified_files: modified,
+        ..repo(path)
+    }
+}
+
+fn container(name: &str, status: &str) -> ContainerInfo {
+    ContainerInfo {
+        name: name.to_string(),
+        status: status.to_string(),
+        runtime: "podman".to_string(),
+        ..ContainerInfo::default()
+    }
+}
+
+fn target(name: &str, container_name: &str, stale: bool) -> BuildTarget {
+    BuildTarget {
+        name: name.to_string(),
+        container_name: container_name.to_string(),
+        artifact: Some(Artifact {
+            path: format!("out/{}.img", name),
+            modified_at: "2026-03-01T12:00:00Z".to_string(),
+            size: 1024,
+            stale,
+        }),
+        ..BuildTarget::default()
+    }
+}
+
+fn check(file: &str, state: WorkingFileState) -> WorkingFileCheck {
+    WorkingFileCheck {
+        file: file.to_string(),
+        state,
+        commit: None,
+    }
+}
+
+/// One of each issue but low disk and a missing device
+fn competing() -> Context {
+    let mut rebasing = dirty("frameworks/av", 1);
+    rebasing.operation = Some("rebase".to_string());
+    rebasing.conflicted_files = 2;
+    let mut conflicted = repo("vendor/qcom");
+    conflicted.conflicted_files = 1;
+    let mut behind = dirty("kernel", 3);
+    behind.behind = 4;
+    behind.compared_to = Some("origin/main".to_string());
+
+    let mut listed = CollectorReport::abandoned("containers", CollectorStatus::Ok, Duration::ZERO);
+    listed.items = 2;
+    Context {
+        now: Some("2026-03-02T12:00:00Z".parse().unwrap()),
+        git_repos: vec![behind, conflicted, rebasing],
+        containers: vec![
+            container("builder", "Up 2 hours (unhealthy)"),
+            container("db", "Up 2 hours"),
+        ],
+        targets: vec![
+            target("image", "builder", true),
+            target("flash", "flasher", false),
+        ],
+        working_file_checks: vec![
+            check("src/a.c", WorkingFileState::Missing),
+            check("src/b.c", WorkingFileState::Modified),
+        ],
+        reports: vec![listed],
+        ..Context::default()
+    }
+}
+
+fn kinds(items: &[AttentionItem]) -> Vec<AttentionKind> {
+    items.iter().map(|item| item.kind).collect()
+}
+
+fn texts(items: &[AttentionItem]) -> Vec<&str> {
+    items.iter().map(|item| item.text.as_str()).collect()
+}
+
+#[test]
+fn competing_issues_in_default_order() {
+    let items = attention_items(&Config::default(), &competing());
+    assert_eq!(
+        texts(&items),
+        [
+            "`frameworks/av`: rebase in progress, 2 conflicted files",
+            "`vendor/qcom`: 1 conflicted file",
+            "Container `builder` is unhealthy",
+            "Container `flasher` isn't running",
+            "1 of 2 saved working files gone or no longer modified",
+            "The `image` build output is older than the last commit",
+            "`kernel` is 4 commits behind origin/main",
+            "`kernel` has uncommitted changes (3M)",
+        ]
+    );
+    let scores: Vec<i64> = items.iter().map(|item| item.score).collect();
+    assert_eq!(scores, [100, 90, 70, 60, 40, 30, 20, 10]);
+}
+
+#[test]
+fn down_containers_only_from_a_clean_listing() {
+    let mut ctx = competing();
+    ctx.reports[0]
+        .warnings
+        .push("podman: permission denied".to_string());
+    let items = attention_items(&Config::default(), &ctx);
+    assert!(!kinds(&items).contains(&AttentionKind::ContainerDown));
+
+    ctx.reports.clear();
+    let items = attention_items(&Config::default(), &ctx);
+    assert!(!kinds(&items).contains(&AttentionKind::ContainerDown));
+    // Unhealthy comes from the containers themselves
+    assert!(kinds(&items).contains(&AttentionKind::UnhealthyContainer));
+}
+
+#[test]
+fn equal_scores_break_ties_by_kind_then_text() {
+    let config: Config =
+        toml::from_str("[priorities]\ndirty_repo = 50\nbehind_upstream = 50\nconflicts = 50\n")
+            .unwrap();
+    let mut ctx = Context {
+        git_repos: vec![
+            dirty("zeta", 1),
+            dirty("alpha", 2),
+            GitInfo {
+                behind: 1,
+                ..repo("mid")
+            },
+            GitInfo {
+                conflicted_files: 3,
+                ..repo("omega")
+            },
+        ],
+        ..Context::default()
+    };
+    let expected = [
+        "`omega`: 3 conflicted files",
+        "`mid` is 1 commit behind upstream",
+        "`alpha` has uncommitted changes (2M)",
+        "`zeta` has uncommitted changes (1M)",
+    ];
+    assert_eq!(texts(&attention_items(&config, &ctx)), expected);
+
+    // Whatever order the collectors found them in
+    for _ in 0..ctx.git_repos.len() {
+        ctx.git_repos.rotate_left(1);
+        assert_eq!(texts(&attention_items(&config, &ctx)), expected);
+    }
+    ctx.git_repos.reverse();
+    assert_eq!(texts(&attention_items(&config, &ctx)), expected);
+}
+
+#[test]
+fn priorities_reorder_and_drop_kinds() {
+    let config: Config = toml::from_str(
+        r#"
+        [priorities]
+        dirty_repo = 200
+        conflicts = 0
+        stale_artifact = -5
+        "#,
+    )
+    .unwrap();
+    let items = attention_items(&config, &competing());
+    assert_eq!(items[0].kind, AttentionKind::DirtyRepo);
+    assert_eq!(items[0].score, 200);
+    assert_eq!(items[1].kind, AttentionKind::GitOperation);
+    let kinds = kinds(&items);
+    assert!(!kinds.contains(&AttentionKind::Conflicts), "{:?}", kinds);
+    assert!(
+        !kinds.contains(&AttentionKind::StaleArtifact),
+        "{:?}",
+        kinds
+    );
+
+    let unknown = toml::from_str::<Config>("[priorities]\nrebase = 10\n");
+    assert!(unknown.is_err());
+}
+
+#[test]
+fn top_items_per_level() {
+    let mut ctx = competing();
+    ctx.attention = attention_items(&Config::default(), &ctx);
+
+    assert_eq!(
+        format_attention_section(&ctx, Level::Minimal),
+        "**Attention:** `frameworks/av`: rebase in progress, 2 conflicted files; \
+         `vendor/qcom`: 1 conflicted file; Container `builder` is unhealthy\n"
+    );
+    assert_eq!(
+        format_attention_section(&ctx, Level::Normal),
+        "## Attention\n\
+         1. `frameworks/av`: rebase in progress, 2 conflicted files\n\
+         2. `vendor/qcom`: 1 conflicted file\n\
+         3. Container `builder` is unhealthy\n\
+         4. Container `flasher` isn't running\n\
+         5. 1 of 2 saved working files gone or no longer modified\n\n"
+    );
+
+    ctx.attention.clear();
+    assert_eq!(format_attention_section(&ctx, Level::Minimal), "");
+    assert_eq!(format_attention_section(&ctx, Level::Normal), "");
+}
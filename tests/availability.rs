@@ -0,0 +1,280 @@
+//! Each availability state: how a collector's report comes by it, and how
+//! a section with nothing to show renders it at each level and in JSON
+
+mod common;
+
+use common::{stderr, stdout, Sandbox};
+use context_keeper::collectors::{collector_disabled, collector_unavailable, BuildTarget};
+use context_keeper::context::{
+    run_collector, Availability, CollectionPlan, CollectorOutput, CollectorReport, CollectorStatus,
+    FnCollector,
+};
+use context_keeper::error::ContextKeeperError;
+use context_keeper::format::{
+    format_blocking_note, format_section, section_availability, Level, Section,
+};
+use context_keeper::{Context, ProjectContext};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// The availability `collect` leaves on its report
+fn availability(
+    collect: fn(&ProjectContext) -> Result<CollectorOutput, ContextKeeperError>,
+) -> Availability {
+    let dir = tempfile::tempdir().unwrap();
+    let project = ProjectContext::load(dir.path().to_path_buf(), None);
+    let collector = FnCollector {
+        name: "package_scripts",
+        collect,
+    };
+    let (report, _) = run_collector(&collector, &project, CollectionPlan::all());
+    report.availability
+}
+
+fn scripts(names: &[&str]) -> CollectorOutput {
+    CollectorOutput::PackageScripts(names.iter().map(|n| n.to_string()).collect())
+}
+
+#[test]
+fn states_from_what_the_collector_saw() {
+    assert_eq!(availability(|_| Ok(scripts(&["build"]))), Availability::Ok);
+    assert_eq!(availability(|_| Ok(scripts(&[]))), Availability::Empty);
+    assert_eq!(
+        availability(|_| {
+            collector_unavailable("npm not installed".to_string());
+            collector_unavailable("a later reason".to_string());
+            Ok(scripts(&[]))
+        }),
+        Availability::Unavailable("npm not installed".to_string())
+    );
+    // What was found counts for more than a tool that failed on the way
+    assert_eq!(
+        availability(|_| {
+            collector_unavailable("npm not installed".to_string());
+            Ok(scripts(&["build"]))
+        }),
+        Availability::Ok
+    );
+    assert_eq!(
+        availability(|_| {
+            collector_disabled();
+            Ok(scripts(&[]))
+        }),
+        Availability::Disabled
+    );
+    assert_eq!(
+        availability(|_| {
+            collector_disabled();
+            collector_unavailable("npm not installed".to_string());
+            Ok(scripts(&[]))
+        }),
+        Availability::Unavailable("npm not installed".to_string())
+    );
+    assert_eq!(
+        availability(|_| Err(ContextKeeperError::Collector {
+            collector: "package_scripts",
+            message: "package.json: expected value at line 1".to_string(),
+        })),
+        Availability::Unavailable(
+            "package_scripts: package.json: expected value at line 1".to_string()
+        )
+    );
+
+    // Collectors that never reported back
+    let abandoned = |status| CollectorReport::abandoned("git", status, Duration::ZERO).availability;
+    assert_eq!(abandoned(CollectorStatus::TimedOut), Availability::TimedOut);
+    assert_eq!(
+        abandoned(CollectorStatus::Cancelled),
+        Availability::Unavailable("collector cancelled".to_string())
+    );
+    assert_eq!(
+        abandoned(CollectorStatus::Failed),
+        Availability::Unavailable("collector failed".to_string())
+    );
+}
+
+#[test]
+fn states_serialize_with_their_reason() {
+    for (availability, value) in [
+        (Availability::Ok, json!({"state": "ok"})),
+        (Availability::Empty, json!({"state": "empty"})),
+        (
+            Availability::Unavailable("podman not installed".to_string()),
+            json!({"state": "unavailable", "reason": "podman not installed"}),
+        ),
+        (Availability::Disabled, json!({"state": "disabled"})),
+        (Availability::TimedOut, json!({"state": "timed_out"})),
+    ] {
+        assert_eq!(serde_json::to_value(&availability).unwrap(), value);
+        assert_eq!(
+            availability.is_stub(),
+            !matches!(availability, Availability::Ok | Availability::Empty)
+        );
+    }
+}
+
+/// A finished report for `name` in `availability`
+fn report(name: &'static str, availability: Availability) -> CollectorReport {
+    CollectorReport {
+        status: CollectorStatus::Ok,
+        availability,
+        ..CollectorReport::abandoned(name, CollectorStatus::Ok, Duration::ZERO)
+    }
+}
+
+fn context(reports: Vec<CollectorReport>) -> Context {
+    Context {
+        project_name: "firmware".to_string(),
+        now: Some("2026-03-02T12:00:00Z".parse().unwrap()),
+        reports,
+        ..Context::default()
+    }
+}
+
+fn unavailable(reason: &str) -> Availability {
+    Availability::Unavailable(reason.to_string())
+}
+
+#[test]
+fn stubs_below_minimal_level() {
+    let ctx = context(vec![
+        report("containers", unavailable("podman not installed")),
+        report("history", Availability::Disabled),
+        report("tmux", Availability::Disabled),
+        report("shell_env", Availability::Disabled),
+        report("git", Availability::TimedOut),
+        report("yocto", Availability::Empty),
+        report("processes", unavailable("the process table can't be read")),
+    ]);
+    for (section, stub) in [
+        (
+            Section::Containers,
+            "**Containers:** unavailable — podman not installed\n\n",
+        ),
+        (
+            Section::History,
+            "**Command history:** turned off in the config (`[history] enabled`)\n\n",
+        ),
+        (
+            Section::Terminals,
+            "**Terminal sessions:** turned off in the config (`[tmux] enabled`)\n\n",
+        ),
+        (
+            Section::Environment,
+            "**Captured environment:** turned off in the config (`[env] capture`)\n\n",
+        ),
+        (Section::Git, "**Git:** timed out, not collected\n\n"),
+        (
+            Section::Builds,
+            "**Builds:** unavailable — the process table can't be read\n\n",
+        ),
+        // Nothing found is left out, as is a section without a report
+        (Section::Yocto, ""),
+        (Section::Ros, ""),
+    ] {
+        for level in [Level::Normal, Level::Full] {
+            assert_eq!(format_section(&ctx, section, level), stub, "{:?}", section);
+        }
+        assert_eq!(format_section(&ctx, section, Level::Minimal), "");
+    }
+}
+
+#[test]
+fn a_section_is_ok_when_any_collector_found_something() {
+    let mut ctx = context(vec![
+        report("tmux", Availability::Disabled),
+        report("open_files", Availability::Ok),
+    ]);
+    assert_eq!(
+        section_availability(&ctx, Section::Terminals),
+        Some(Availability::Ok)
+    );
+    // Otherwise the first collector's state
+    ctx.reports[1].availability = Availability::Empty;
+    assert_eq!(
+        section_availability(&ctx, Section::Terminals),
+        Some(Availability::Disabled)
+    );
+    // A first collector that didn't run, or no collectors at all
+    assert_eq!(section_availability(&ctx, Section::Containers), None);
+    assert_eq!(section_availability(&ctx, Section::Attention), None);
+}
+
+#[test]
+fn minimal_names_only_what_blocks_a_known_need() {
+    let mut ctx = context(vec![
+        report("containers", unavailable("podman not installed")),
+        report("devices", unavailable("adb not installed")),
+    ]);
+    assert_eq!(format_blocking_note(&ctx), "");
+
+    ctx.targets = vec![BuildTarget {
+        name: "build".to_string(),
+        container_name: "aosp-builder".to_string(),
+        ..BuildTarget::default()
+    }];
+    let note =
+        "> **Unavailable:** containers — podman not installed; targets build in containers\n\n";
+    assert_eq!(format_blocking_note(&ctx), note);
+    let minimal = format_section(&ctx, Section::Diagnostics, Level::Minimal);
+    assert!(minimal.contains(note), "{}", minimal);
+    assert!(!format_section(&ctx, Section::Diagnostics, Level::Normal).contains("Unavailable"));
+
+    // Git is always needed
+    ctx.targets.clear();
+    ctx.reports
+        .push(report("git", unavailable("git not installed")));
+    assert_eq!(
+        format_blocking_note(&ctx),
+        "> **Unavailable:** git — git not installed\n\n"
+    );
+    // A timed-out source isn't named here
+    ctx.reports.last_mut().unwrap().availability = Availability::TimedOut;
+    assert_eq!(format_blocking_note(&ctx), "");
+}
+
+#[cfg(feature = "adb")]
+#[test]
+fn minimal_names_adb_for_a_pinned_device() {
+    let mut ctx = context(vec![report("devices", unavailable("adb not installed"))]);
+    ctx.device_selection = Some(serde_json::from_value(json!({"pinned": "pixel"})).unwrap());
+    assert_eq!(
+        format_blocking_note(&ctx),
+        "> **Unavailable:** devices — adb not installed; a default device is pinned\n\n"
+    );
+}
+
+#[test]
+fn availability_through_the_cli() {
+    let sandbox = Sandbox::new();
+    sandbox.write("contextkeeper.toml", "[tmux]\nenabled = false\n");
+    let output = sandbox.run(&["context", "--level", "full", "--json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let value: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let map = &value["availability"];
+    let history = if cfg!(feature = "history") {
+        json!({"state": "disabled"})
+    } else {
+        json!({"state": "unavailable", "reason": "built without the `history` feature"})
+    };
+    assert_eq!(map["history"], history, "{}", map);
+    assert_eq!(map["terminals"], json!({"state": "disabled"}), "{}", map);
+    assert_eq!(map["environment"], json!({"state": "disabled"}), "{}", map);
+    assert_eq!(map["yocto"], json!({"state": "empty"}), "{}", map);
+    // Each report carries its own
+    let reports = value["reports"].as_array().unwrap();
+    assert!(reports
+        .iter()
+        .all(|r| r["availability"]["state"].is_string()));
+
+    let output = sandbox.run(&["context", "--level", "normal"]);
+    let text = stdout(&output);
+    assert!(!text.contains("**Yocto:**"), "{}", text);
+    let output = sandbox.run(&["context", "--level", "full"]);
+    let text = stdout(&output);
+    assert!(
+        text.contains("**Terminal sessions:** turned off in the config (`[tmux] enabled`)\n"),
+        "{}",
+        text
+    );
+}
@@ -0,0 +1,113 @@
+//! Which cached contexts the server serves again: one collected for more
+//! collectors serves a request for fewer, never the other way round
+
+use context_keeper::collectors::fake_runner::FakeRunner;
+use context_keeper::context::CollectionPlan;
+use context_keeper::format::{Level, Section};
+use context_keeper::server::ContextKeeperService;
+use context_keeper::ProjectContext;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+
+const CONFIG: &str = "[containers]\nruntime = \"podman\"\n";
+
+/// A project whose commands all go to a fresh `FakeRunner`
+fn project(dir: &tempfile::TempDir) -> (Arc<ProjectContext>, Arc<FakeRunner>) {
+    std::fs::write(dir.path().join("contextkeeper.toml"), CONFIG).unwrap();
+    let runner = Arc::new(FakeRunner::new().on(&["podman", "ps", "--format", "json"], "[]"));
+    let mut project = ProjectContext::load(dir.path().to_path_buf(), None);
+    assert!(project.config_error.is_none());
+    project.runner = runner.clone();
+    (Arc::new(project), runner)
+}
+
+fn plan(level: Level, project: &ProjectContext) -> CollectionPlan {
+    CollectionPlan::for_level(level, &project.config)
+}
+
+#[test]
+fn wider_plans_cover_narrower_ones() {
+    let dir = tempfile::tempdir().unwrap();
+    let (project, _) = project(&dir);
+    let [oneline, minimal, normal, full] = Level::ALL.map(|level| plan(level, &project));
+    for narrower in [oneline, minimal, normal, full] {
+        assert!(CollectionPlan::all().covers(&narrower));
+    }
+    assert!(full.covers(&normal) && normal.covers(&minimal) && minimal.covers(&oneline));
+    assert!(!minimal.covers(&full) && !normal.covers(&full) && !minimal.covers(&normal));
+    // Full level still leaves out what nothing configured asks for
+    assert!(!full.covers(&CollectionPlan::all()));
+
+    let git = CollectionPlan::for_sections(&[Section::Git], &project.config);
+    assert!(full.covers(&git));
+    assert!(!git.covers(&minimal));
+}
+
+#[tokio::test]
+async fn cached_full_serves_minimal() {
+    let dir = tempfile::tempdir().unwrap();
+    let (project, runner) = project(&dir);
+    let service = ContextKeeperService::new(project.root.clone(), None, None);
+    let ct = CancellationToken::new();
+
+    let first = service
+        .cached_context(&project, plan(Level::Full, &project), false, false, &ct)
+        .await;
+    assert!(first.cache_age.is_none());
+    let calls = runner.calls().len();
+    assert!(calls > 0);
+
+    for level in [Level::Minimal, Level::Oneline, Level::Normal, Level::Full] {
+        let again = service
+            .cached_context(&project, plan(level, &project), false, false, &ct)
+            .await;
+        assert!(
+            again.cache_age.is_some(),
+            "{} collected again",
+            level.name()
+        );
+    }
+    assert_eq!(runner.calls().len(), calls);
+}
+
+#[cfg(feature = "containers")]
+#[tokio::test]
+async fn cached_minimal_does_not_serve_full() {
+    let dir = tempfile::tempdir().unwrap();
+    let (project, runner) = project(&dir);
+    let service = ContextKeeperService::new(project.root.clone(), None, None);
+    let ct = CancellationToken::new();
+
+    let minimal = service
+        .cached_context(&project, plan(Level::Minimal, &project), false, false, &ct)
+        .await;
+    let podman = || {
+        runner
+            .calls()
+            .iter()
+            .any(|c| c.line().starts_with("podman ps"))
+    };
+    assert!(!podman());
+    let full = service
+        .cached_context(&project, plan(Level::Full, &project), false, false, &ct)
+        .await;
+    assert!(full.cache_age.is_none());
+    assert!(podman());
+    // Collected again for the collectors minimal left out
+    let names = |ctx: &context_keeper::Context| -> Vec<&'static str> {
+        ctx.reports.iter().map(|r| r.name).collect()
+    };
+    let (minimal, full) = (names(&minimal), names(&full));
+    assert!(
+        full.contains(&"containers") && !minimal.contains(&"containers"),
+        "{:?} {:?}",
+        minimal,
+        full
+    );
+
+    // The full collection replaced it, so minimal is now served from it
+    let minimal = service
+        .cached_context(&project, plan(Level::Minimal, &project), false, false, &ct)
+        .await;
+    assert!(minimal.cache_age.is_some());
+}
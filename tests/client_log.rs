@@ -0,0 +1,211 @@
+//! MCP logging notifications, over an in-memory transport to a server
+//! whose tools are replayed by `FakeRunner`: what a collector timeout
+//! sends, and what `logging/setLevel` filters
+#![cfg(feature = "adb")]
+
+use context_keeper::client_log::{client_log_layer, set_client_level};
+use context_keeper::collectors::fake_runner::{FakeResponse, FakeRunner};
+use context_keeper::server::ContextKeeperService;
+use context_keeper::ProjectContext;
+use rmcp::model::LoggingLevel;
+use rmcp::ServiceExt;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::{Arc, Once};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream, ReadHalf, WriteHalf};
+use tracing_subscriber::layer::SubscriberExt;
+
+/// The client's log level and session are the process's; one test at a
+/// time
+static SERIAL: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+fn install_client_log() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let subscriber = tracing_subscriber::registry().with(client_log_layer());
+        tracing::subscriber::set_global_default(subscriber).unwrap();
+    });
+}
+
+/// The client end of the transport, speaking JSON-RPC lines
+struct MockClient {
+    reader: BufReader<ReadHalf<DuplexStream>>,
+    writer: WriteHalf<DuplexStream>,
+    next_id: u64,
+}
+
+impl MockClient {
+    async fn send(&mut self, message: Value) {
+        let line = format!("{}\n", message);
+        self.writer.write_all(line.as_bytes()).await.unwrap();
+        self.writer.flush().await.unwrap();
+    }
+
+    /// The response to `method`, and the log messages sent before it
+    async fn request(&mut self, method: &str, params: Value) -> (Value, Vec<Value>) {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}))
+            .await;
+        let mut messages = Vec::new();
+        loop {
+            let mut line = String::new();
+            let read =
+                tokio::time::timeout(Duration::from_secs(20), self.reader.read_line(&mut line))
+                    .await
+                    .unwrap_or_else(|_| panic!("no response to {}", method))
+                    .unwrap();
+            assert!(read > 0, "server hung up waiting for {}", method);
+            let message: Value = serde_json::from_str(&line).unwrap();
+            if message["id"] == json!(id) {
+                return (message, messages);
+            }
+            if message["method"] == "notifications/message" {
+                messages.push(message["params"].clone());
+            }
+        }
+    }
+
+    async fn context(&mut self, arguments: Value) -> Vec<Value> {
+        let (response, messages) = self
+            .request(
+                "tools/call",
+                json!({"name": "get_dev_context", "arguments": arguments}),
+            )
+            .await;
+        assert!(response.get("result").is_some(), "{}", response);
+        messages
+    }
+}
+
+/// Serve the project at `root`, its commands replayed by `runner`, and
+/// complete the handshake
+async fn connect(root: &Path, runner: FakeRunner) -> MockClient {
+    install_client_log();
+    set_client_level(LoggingLevel::Info);
+    let service = ContextKeeperService::new(root.to_path_buf(), None, None);
+    let mut project = ProjectContext::load(root.to_path_buf(), None);
+    project.runner = Arc::new(runner);
+    service
+        .projects
+        .lock()
+        .unwrap()
+        .insert(root.to_path_buf(), Arc::new(project));
+
+    let (server_io, client_io) = tokio::io::duplex(1 << 20);
+    tokio::spawn(async move {
+        let server = service.serve(server_io).await.unwrap();
+        let _ = server.waiting().await;
+    });
+    let (reader, writer) = tokio::io::split(client_io);
+    let mut client = MockClient {
+        reader: BufReader::new(reader),
+        writer,
+        next_id: 1,
+    };
+    let (response, _) = client
+        .request(
+            "initialize",
+            json!({
+                "protocolVersion": "2024-11-05",
+                "capabilities": {},
+                "clientInfo": {"name": "mock", "version": "1"}
+            }),
+        )
+        .await;
+    assert!(
+        response["result"]["capabilities"]["logging"].is_object(),
+        "{}",
+        response
+    );
+    client
+        .send(json!({"jsonrpc": "2.0", "method": "notifications/initialized", "params": {}}))
+        .await;
+    client.request("ping", json!({})).await;
+    client
+}
+
+fn levels(messages: &[Value]) -> Vec<&str> {
+    messages
+        .iter()
+        .map(|m| m["level"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn a_collector_timeout_warns_once() {
+    let _serial = SERIAL.lock().await;
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("contextkeeper.toml"),
+        "[project]\nname = \"hung\"\n\n[server]\ntool_timeout_secs = 1\n",
+    )
+    .unwrap();
+    let runner = FakeRunner::new().respond(
+        &["adb", "devices"],
+        FakeResponse::Slow(Duration::from_secs(60), Box::new(FakeResponse::NotFound)),
+    );
+    let mut client = connect(dir.path(), runner).await;
+
+    let messages = client.context(json!({"level": "normal"})).await;
+    let about_devices: Vec<&Value> = messages
+        .iter()
+        .filter(|m| m["data"]["collector"] == "devices")
+        .collect();
+    assert_eq!(about_devices.len(), 1, "{:#?}", messages);
+    assert_eq!(
+        about_devices[0],
+        &json!({
+            "level": "warning",
+            "logger": "context-keeper",
+            "data": {"collector": "devices", "status": "timed out", "message": "collector abandoned"},
+        })
+    );
+    // Nothing else timed out
+    let abandoned = messages
+        .iter()
+        .filter(|m| m["data"]["message"] == "collector abandoned")
+        .count();
+    assert_eq!(abandoned, 1, "{:#?}", messages);
+}
+
+#[tokio::test]
+async fn set_level_filters_debug() {
+    let _serial = SERIAL.lock().await;
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("contextkeeper.toml"),
+        "[project]\nname = \"quiet\"\n",
+    )
+    .unwrap();
+    // Every tool missing: warnings and infos, but no timeouts
+    let mut client = connect(dir.path(), FakeRunner::new()).await;
+
+    // Info by default: no debug
+    let messages = client.context(json!({})).await;
+    assert!(!messages.is_empty());
+    assert!(!levels(&messages).contains(&"debug"), "{:#?}", messages);
+
+    client
+        .request("logging/setLevel", json!({"level": "debug"}))
+        .await;
+    let messages = client.context(json!({})).await;
+    assert_eq!(levels(&messages), ["debug"], "{:#?}", messages);
+    assert_eq!(messages[0]["data"]["message"], "context served from cache");
+
+    client
+        .request("logging/setLevel", json!({"level": "info"}))
+        .await;
+    assert_eq!(client.context(json!({})).await, Vec::<Value>::new());
+
+    // Above warning, a fresh collection sends nothing either
+    client
+        .request("logging/setLevel", json!({"level": "error"}))
+        .await;
+    assert_eq!(
+        client.context(json!({"force_refresh": true})).await,
+        Vec::<Value>::new()
+    );
+    set_client_level(LoggingLevel::Info);
+}
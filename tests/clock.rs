@@ -0,0 +1,198 @@
+//! Timestamps ahead of the clock and history running back, on crafted
+//! timestamp sets
+
+use chrono::{DateTime, Duration, Utc};
+use context_keeper::clock::{
+    clamp_to_now, clock_warnings, future_skew, history_backsteps, ClockWarning,
+};
+use context_keeper::collectors::HistoryEntry;
+use context_keeper::format::{format_age, format_clock_note};
+use context_keeper::i18n::Language;
+use context_keeper::state::WorkState;
+use context_keeper::Context;
+use serde_json::json;
+
+fn now() -> DateTime<Utc> {
+    "2026-03-02T12:00:00Z".parse().unwrap()
+}
+
+/// `now()` moved by `secs`, as RFC 3339
+fn offset(secs: i64) -> String {
+    (now() + Duration::seconds(secs)).to_rfc3339()
+}
+
+fn history(timestamps: &[&str]) -> Vec<HistoryEntry> {
+    timestamps
+        .iter()
+        .enumerate()
+        .map(|(i, timestamp)| HistoryEntry {
+            timestamp: timestamp.to_string(),
+            command: format!("cmd{}", i),
+        })
+        .collect()
+}
+
+fn messages(warnings: &[ClockWarning]) -> Vec<(&str, &str)> {
+    warnings
+        .iter()
+        .map(|w| (w.collector, w.message.as_str()))
+        .collect()
+}
+
+#[test]
+fn future_beyond_the_tolerance() {
+    for (timestamp, skew) in [
+        (offset(-86_400), None),
+        (offset(0), None),
+        (offset(120), None),
+        (offset(121), Some(121)),
+        (offset(7200), Some(7200)),
+        // Another offset is the same instant
+        ("2026-03-02T23:00:00+09:00".to_string(), Some(7200)),
+        ("2026-03-02T12:01:00+00:00".to_string(), None),
+        ("not a time".to_string(), None),
+        (String::new(), None),
+    ] {
+        assert_eq!(future_skew(&timestamp, now()), skew, "{}", timestamp);
+    }
+}
+
+#[test]
+fn future_times_are_clamped_for_ages() {
+    let ahead = now() + Duration::hours(2);
+    assert_eq!(clamp_to_now(ahead, now()), now());
+    let behind = now() - Duration::minutes(5);
+    assert_eq!(clamp_to_now(behind, now()), behind);
+    assert_eq!(format_age(&offset(7200), now(), Language::En), "just now");
+    assert_eq!(format_age(&offset(-300), now(), Language::En), "5m ago");
+}
+
+/// `history_backsteps` of entries at these offsets from `now()`
+fn backsteps(offsets: &[i64]) -> Vec<(usize, i64)> {
+    let timestamps: Vec<String> = offsets.iter().map(|&secs| offset(secs)).collect();
+    let entries = history(&timestamps.iter().map(String::as_str).collect::<Vec<_>>());
+    history_backsteps(&entries, now())
+}
+
+#[test]
+fn history_running_back() {
+    // In order, or back by no more than an hour
+    assert_eq!(backsteps(&[-7200, -3600, -60]), []);
+    assert_eq!(backsteps(&[-3600, -7200]), []);
+    assert_eq!(backsteps(&[-600, -4000, -300]), []);
+    // Back more than an hour from the latest before it, not just the
+    // entry before it
+    assert_eq!(backsteps(&[-600, -4201, -4300]), [(1, 3601), (2, 3700)]);
+    assert_eq!(
+        backsteps(&[-9000, -60, -9000, -50, -3700]),
+        [(2, 8940), (4, 3650)]
+    );
+    // Entries ahead of the clock don't count, nor unparsable ones
+    assert_eq!(backsteps(&[86_400, -60, -120]), []);
+    let entries = history(&[&offset(-60), "garbage", &offset(-4000)]);
+    assert_eq!(history_backsteps(&entries, now()), [(2, 3940)]);
+}
+
+/// A context at `now()` with every checked timestamp set
+fn context(saved_at: String, history_at: &[String], others_at: String) -> Context {
+    Context {
+        now: Some(now()),
+        work_state: Some(WorkState {
+            saved_at,
+            task_summary: "flash the board".to_string(),
+            ..WorkState::default()
+        }),
+        command_history: history(&history_at.iter().map(String::as_str).collect::<Vec<_>>()),
+        open_files: Some(
+            serde_json::from_value(json!({"updated_at": others_at, "files": []})).unwrap(),
+        ),
+        build_results: vec![serde_json::from_value(json!({
+            "timestamp": others_at, "command": "make", "exit_code": 0, "duration_secs": 3,
+        }))
+        .unwrap()],
+        shell_env: Some(
+            serde_json::from_value(json!({"timestamp": others_at, "cwd": ".", "vars": {}}))
+                .unwrap(),
+        ),
+        ..Context::default()
+    }
+}
+
+#[test]
+fn consistent_timestamps_raise_nothing() {
+    let ctx = context(
+        offset(-3600),
+        &[offset(-3000), offset(-2000), offset(90)],
+        offset(100),
+    );
+    assert_eq!(clock_warnings(&ctx), []);
+    assert_eq!(format_clock_note(&ctx), "");
+}
+
+#[test]
+fn every_skewed_source_is_reported() {
+    let ctx = context(
+        offset(7200),
+        &[offset(-60), offset(600), offset(-7300), offset(3 * 86_400)],
+        offset(300),
+    );
+    assert_eq!(
+        messages(&clock_warnings(&ctx)),
+        [
+            (
+                "work_state",
+                "work state saved_at appears to be 2h 0m in the future — check system clocks"
+            ),
+            (
+                "history",
+                "2 history entries appear to be up to 72h 0m in the future — check system clocks"
+            ),
+            (
+                "history",
+                "history timestamps jump back 2h 0m before `cmd2`; the log may be merged or corrupted"
+            ),
+            (
+                "open_files",
+                "open-files.json updated_at appears to be 5m 0s in the future — check system clocks"
+            ),
+            (
+                "build_results",
+                "a build result appears to be 5m 0s in the future — check system clocks"
+            ),
+            (
+                "shell_env",
+                "the captured environment appears to be 5m 0s in the future — check system clocks"
+            ),
+        ]
+    );
+    let note = format_clock_note(&ctx);
+    assert_eq!(note.matches("> **Clock:** ").count(), 6, "{}", note);
+}
+
+#[test]
+fn one_entry_and_the_largest_jump() {
+    let ctx = context(
+        offset(-60),
+        &[
+            offset(-60),
+            offset(-4000),
+            offset(-20_000),
+            offset(-5000),
+            offset(200),
+        ],
+        offset(0),
+    );
+    assert_eq!(
+        messages(&clock_warnings(&ctx)),
+        [
+            (
+                "history",
+                "1 history entry appears to be 3m 20s in the future — check system clocks"
+            ),
+            (
+                "history",
+                "history timestamps jump back 5h 32m before `cmd2` (3 such jumps); the log may be merged or corrupted"
+            ),
+        ]
+    );
+}
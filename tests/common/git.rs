@@ -0,0 +1,38 @@
+//! Building fixture repositories with the git CLI
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// `git <args>` in `dir` with a fixed identity and branch name; panics
+/// when it fails
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(["-c", "user.name=t", "-c", "user.email=t@example.com"])
+        .args([
+            "-c",
+            "init.defaultBranch=main",
+            "-c",
+            "commit.gpgsign=false",
+        ])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {:?}: {:?}", args, output);
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// Whether git is installed; tests that need it pass without it
+pub fn has_git() -> bool {
+    Command::new("git").arg("--version").output().is_ok()
+}
+
+/// Write `text` to `file` in `dir` and commit everything
+pub fn commit(dir: &Path, file: &str, text: &str, message: &str) {
+    let path = dir.join(file);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, text).unwrap();
+    git(dir, &["add", "-A"]);
+    git(dir, &["commit", "-qm", message]);
+}
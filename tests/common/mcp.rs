@@ -0,0 +1,112 @@
+//! A minimal MCP client speaking JSON-RPC lines to `context-keeper serve`
+
+use super::Sandbox;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Stdio};
+
+pub struct McpClient {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+    /// Notifications received while waiting for responses
+    pub notifications: Vec<Value>,
+    /// Every line the server wrote to stdout
+    pub lines: Vec<String>,
+}
+
+impl McpClient {
+    /// Start `serve` in the sandbox and complete the handshake
+    pub fn start(sandbox: &Sandbox, extra_args: &[&str]) -> Self {
+        let mut args = vec!["serve"];
+        args.extend_from_slice(extra_args);
+        let mut child = sandbox
+            .command(&args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let mut client = McpClient {
+            stdin: child.stdin.take(),
+            stdout: BufReader::new(child.stdout.take().unwrap()),
+            child,
+            next_id: 1,
+            notifications: Vec::new(),
+            lines: Vec::new(),
+        };
+        client.request(
+            "initialize",
+            json!({
+                "protocolVersion": "2024-11-05",
+                "capabilities": {},
+                "clientInfo": {"name": "test", "version": "1"}
+            }),
+        );
+        client.notify("notifications/initialized", json!({}));
+        client
+    }
+
+    pub fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({"jsonrpc": "2.0", "method": method, "params": params}));
+    }
+
+    fn send(&mut self, message: Value) {
+        let stdin = self.stdin.as_mut().unwrap();
+        writeln!(stdin, "{}", message).unwrap();
+        stdin.flush().unwrap();
+    }
+
+    /// Send a request and wait for its response, collecting the
+    /// notifications that come first
+    pub fn request(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}));
+        loop {
+            let mut line = String::new();
+            let read = self.stdout.read_line(&mut line).unwrap();
+            assert!(read > 0, "server closed stdout waiting for {}", method);
+            self.lines.push(line.clone());
+            let message: Value = serde_json::from_str(&line)
+                .unwrap_or_else(|e| panic!("not a JSON-RPC frame ({}): {:?}", e, line));
+            if message.get("id") == Some(&json!(id)) {
+                return message;
+            }
+            if message.get("method").is_some() && message.get("id").is_none() {
+                self.notifications.push(message);
+            }
+        }
+    }
+
+    /// `tools/call`, returning the JSON-RPC response
+    pub fn call(&mut self, tool: &str, arguments: Value) -> Value {
+        self.request("tools/call", json!({"name": tool, "arguments": arguments}))
+    }
+
+    /// The text of a successful tool call
+    pub fn call_text(&mut self, tool: &str, arguments: Value) -> String {
+        let response = self.call(tool, arguments);
+        let result = response
+            .get("result")
+            .unwrap_or_else(|| panic!("{} failed: {}", tool, response));
+        assert_ne!(result["isError"], json!(true), "{}: {}", tool, result);
+        result["content"][0]["text"].as_str().unwrap().to_string()
+    }
+
+    /// Close stdin and wait for the server to exit
+    pub fn close(mut self) -> std::process::ExitStatus {
+        drop(self.stdin.take());
+        self.child.wait().unwrap()
+    }
+}
+
+impl Drop for McpClient {
+    fn drop(&mut self) {
+        if self.stdin.is_some() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Running the built binary against a throwaway HOME and project
+
+#![allow(dead_code)]
+
+pub mod git;
+pub mod mcp;
+pub mod snapshot;
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A temporary HOME and project directory for one test
+pub struct Sandbox {
+    pub home: tempfile::TempDir,
+    pub project: tempfile::TempDir,
+}
+
+impl Sandbox {
+    pub fn new() -> Self {
+        Sandbox {
+            home: tempfile::tempdir().unwrap(),
+            project: tempfile::tempdir().unwrap(),
+        }
+    }
+
+    pub fn home(&self) -> &Path {
+        self.home.path()
+    }
+
+    pub fn root(&self) -> &Path {
+        self.project.path()
+    }
+
+    /// Write `content` to `path` under the project, creating directories
+    pub fn write(&self, path: &str, content: &str) -> PathBuf {
+        let path = self.root().join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    /// `context-keeper <args>` in the project directory, with this HOME
+    /// and none of the caller's context-keeper settings
+    pub fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_context-keeper"));
+        command
+            .args(args)
+            .current_dir(self.root())
+            .env("HOME", self.home())
+            .env("USERPROFILE", self.home())
+            .env_remove("RUST_LOG")
+            .env_remove("ANDROID_SERIAL")
+            .env_remove("CLAUDE_PROJECT_DIR");
+        for (key, _) in std::env::vars_os() {
+            if key.to_string_lossy().starts_with("CONTEXTKEEPER_") {
+                command.env_remove(key);
+            }
+        }
+        command
+    }
+
+    pub fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+
+    /// The saved work state of the project, parsed
+    pub fn work_state(&self) -> Option<serde_json::Value> {
+        let projects = self.home().join(".contextkeeper/projects");
+        std::fs::read_dir(projects)
+            .ok()?
+            .flatten()
+            .map(|entry| entry.path().join("work-state.json"))
+            .find(|path| path.exists())
+            .map(|path| serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap())
+    }
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
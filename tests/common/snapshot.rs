@@ -0,0 +1,25 @@
+//! Output compared with the files in tests/snapshots. Run with
+//! `UPDATE_SNAPSHOTS=1` to rewrite them after a deliberate change.
+
+use std::fs;
+use std::path::PathBuf;
+
+/// Compare `actual` with tests/snapshots/`file`
+pub fn assert_snapshot(file: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(file);
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {} (run with UPDATE_SNAPSHOTS=1)", path.display(), e));
+    assert!(
+        actual == expected,
+        "{} differs; run with UPDATE_SNAPSHOTS=1 and review the diff\n--- expected\n{}\n--- actual\n{}",
+        path.display(),
+        expected,
+        actual
+    );
+}
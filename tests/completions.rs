@@ -0,0 +1,88 @@
+//! `context-keeper completions <shell>`
+
+mod common;
+
+use common::{stdout, Sandbox};
+
+/// The subcommands `--help` lists, without `help` itself
+fn subcommands(sandbox: &Sandbox) -> Vec<String> {
+    let help = stdout(&sandbox.run(&["--help"]));
+    let commands = help
+        .split_once("Commands:\n")
+        .and_then(|(_, rest)| rest.split_once("\n\n"))
+        .map(|(commands, _)| commands)
+        .expect("a Commands: list");
+    commands
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|name| *name != "help")
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn every_script_names_every_subcommand() {
+    let sandbox = Sandbox::new();
+    let names = subcommands(&sandbox);
+    assert!(names.contains(&"context".to_string()), "{:?}", names);
+    for shell in ["bash", "zsh", "fish"] {
+        let output = sandbox.run(&["completions", shell]);
+        assert!(output.status.success(), "{}", shell);
+        let script = stdout(&output);
+        assert!(!script.trim().is_empty(), "{}", shell);
+        for name in &names {
+            assert!(script.contains(name.as_str()), "{} lacks {}", shell, name);
+        }
+        // Levels and sections come from their enums
+        for value in ["oneline", "minimal", "work_state", "diagnostics"] {
+            assert!(script.contains(value), "{} lacks {}", shell, value);
+        }
+    }
+}
+
+#[test]
+fn zsh_completes_saved_snapshots() {
+    let sandbox = Sandbox::new();
+    let script = stdout(&sandbox.run(&["completions", "zsh"]));
+    assert!(script.starts_with("#compdef context-keeper\n"));
+    assert!(
+        script.contains("${HOME}/.contextkeeper/states/*.json(N:t:r)"),
+        "{}",
+        script
+    );
+    let function = script
+        .split_once("_context-keeper() {\n")
+        .map(|(_, body)| body)
+        .expect("the completion function");
+    assert!(
+        function
+            .trim_start()
+            .starts_with("if (( CURRENT == 3 )) && [[ ${words[2]} == (restore|state) ]]"),
+        "{}",
+        function
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn stopping_early_is_not_an_error() {
+    use std::io::Read;
+    use std::process::Stdio;
+
+    let sandbox = Sandbox::new();
+    let mut child = sandbox
+        .command(&["completions", "bash"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut first = [0u8; 16];
+    child.stdout.take().unwrap().read_exact(&mut first).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(
+        output.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
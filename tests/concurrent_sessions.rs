@@ -0,0 +1,127 @@
+//! Two servers saving the work state of one project
+
+mod common;
+
+use common::mcp::McpClient;
+use common::Sandbox;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+fn todos(state: &Value) -> BTreeMap<String, String> {
+    state["todos"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| {
+            let field = |key: &str| t[key].as_str().unwrap().to_string();
+            (field("content"), field("status"))
+        })
+        .collect()
+}
+
+fn save(client: &mut McpClient, todos: Value) {
+    client.call_text(
+        "save_work_state",
+        json!({"task_summary": "parser work", "todos": todos.to_string()}),
+    );
+}
+
+#[test]
+fn two_writers_lose_no_todos() {
+    let sandbox = Sandbox::new();
+    let writers: Vec<_> = ["a", "b"]
+        .into_iter()
+        .map(|name| {
+            let mut client = McpClient::start(&sandbox, &[]);
+            std::thread::spawn(move || {
+                let mut items = Vec::new();
+                for i in 0..10 {
+                    items.push(json!({"content": format!("{} {}", name, i), "status": "pending"}));
+                    save(&mut client, json!(items));
+                }
+                client.close();
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    let saved = todos(&sandbox.work_state().unwrap());
+    for name in ["a", "b"] {
+        for i in 0..10 {
+            assert!(
+                saved.contains_key(&format!("{} {}", name, i)),
+                "{} {} lost: {:?}",
+                name,
+                i,
+                saved
+            );
+        }
+    }
+}
+
+#[test]
+fn a_finished_session_keeps_its_todos() {
+    let sandbox = Sandbox::new();
+    let mut first = McpClient::start(&sandbox, &[]);
+    save(
+        &mut first,
+        json!([{"content": "fix the parser", "status": "in_progress"}]),
+    );
+    first.close();
+
+    // The first server is gone; its todo still counts
+    let mut second = McpClient::start(&sandbox, &[]);
+    save(
+        &mut second,
+        json!([{"content": "write the docs", "status": "pending"}]),
+    );
+    second.close();
+
+    let saved = todos(&sandbox.work_state().unwrap());
+    assert_eq!(saved["fix the parser"], "in_progress");
+    assert_eq!(saved["write the docs"], "pending");
+}
+
+#[test]
+fn passing_a_todo_on_does_not_undo_a_status_change() {
+    let sandbox = Sandbox::new();
+    let mut a = McpClient::start(&sandbox, &[]);
+    let mut b = McpClient::start(&sandbox, &[]);
+    let pending = json!([{"content": "fix the parser", "status": "pending"}]);
+    save(&mut a, pending.clone());
+    // b reads the state, with the todo still pending
+    b.call_text(
+        "get_dev_context",
+        json!({"level": "minimal", "force_refresh": true}),
+    );
+    save(
+        &mut a,
+        json!([{"content": "fix the parser", "status": "completed"}]),
+    );
+    // b saves what it read, along with its own
+    save(
+        &mut b,
+        json!([
+            {"content": "fix the parser", "status": "pending"},
+            {"content": "write the docs", "status": "in_progress"}
+        ]),
+    );
+    let saved = todos(&sandbox.work_state().unwrap());
+    assert_eq!(saved["fix the parser"], "completed");
+    assert_eq!(saved["write the docs"], "in_progress");
+
+    // A change b makes after that is newer, and wins
+    save(
+        &mut b,
+        json!([
+            {"content": "fix the parser", "status": "in_progress"},
+            {"content": "write the docs", "status": "in_progress"}
+        ]),
+    );
+    let saved = todos(&sandbox.work_state().unwrap());
+    assert_eq!(saved["fix the parser"], "in_progress");
+    a.close();
+    b.close();
+}
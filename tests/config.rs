@@ -0,0 +1,229 @@
+//! How the config file, `[[git.repos]]` entries and CLI overrides combine
+
+use context_keeper::collectors::git::{repo_override, StatusOptions};
+use context_keeper::config::{Config, UntrackedFiles};
+use context_keeper::i18n::Language;
+use context_keeper::limits::{Limits, DEFAULT_MAX_LINE_BYTES};
+use context_keeper::ProjectContext;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn config(toml: &str) -> Config {
+    toml::from_str(toml).unwrap()
+}
+
+#[test]
+fn repo_entry_overrides_git_defaults() {
+    let config = config(
+        r#"
+        [git]
+        untracked = "no"
+        status_timeout_ms = 500
+
+        [[git.repos]]
+        path = "vendor/*"
+        untracked = "all"
+        "#,
+    );
+    let base = StatusOptions::from_config(&config);
+    assert_eq!(base.untracked, UntrackedFiles::No);
+    assert_eq!(base.timeout, Some(Duration::from_millis(500)));
+
+    let vendor = base.for_repo(repo_override(&config, "vendor/qcom"));
+    assert_eq!(vendor.untracked, UntrackedFiles::All);
+    assert_eq!(vendor.timeout, base.timeout);
+
+    let other = base.for_repo(repo_override(&config, "frameworks/av"));
+    assert_eq!(other.untracked, UntrackedFiles::No);
+}
+
+#[test]
+fn unset_values_fall_back_to_defaults() {
+    let empty = StatusOptions::from_config(&Config::default());
+    assert_eq!(empty.untracked, UntrackedFiles::Normal);
+    assert_eq!(empty.timeout, StatusOptions::default().timeout);
+
+    let limits = Limits::from_config(&config("[limits]\nmax_todos = 5\n"));
+    assert_eq!(limits.max_todos, 5);
+    assert_eq!(limits.max_line_bytes, DEFAULT_MAX_LINE_BYTES);
+}
+
+#[test]
+fn lang_flag_wins_over_the_config() {
+    let project = tempfile::tempdir().unwrap();
+    fs::write(
+        project.path().join("contextkeeper.toml"),
+        "[output]\nlanguage = \"ja\"\n",
+    )
+    .unwrap();
+    let loaded = ProjectContext::load(project.path().to_path_buf(), None);
+    let configured = loaded.config.output.as_ref().and_then(|o| o.language);
+    assert_eq!(configured, Some(Language::Ja));
+
+    let overridden = loaded.with_language(Some(Language::En));
+    let language = overridden.config.output.as_ref().and_then(|o| o.language);
+    assert_eq!(language, Some(Language::En));
+}
+
+#[test]
+fn first_config_name_wins_and_config_flag_beats_them() {
+    let project = tempfile::tempdir().unwrap();
+    let root = project.path().to_path_buf();
+    fs::write(
+        root.join(".contextkeeper.toml"),
+        "[project]\nname = \"hidden\"\n",
+    )
+    .unwrap();
+    fs::write(
+        root.join("contextkeeper.toml"),
+        "[project]\nname = \"plain\"\n",
+    )
+    .unwrap();
+    let name =
+        |project: &ProjectContext| project.config.project.as_ref().and_then(|p| p.name.clone());
+    assert_eq!(
+        name(&ProjectContext::load(root.clone(), None)).as_deref(),
+        Some("plain")
+    );
+
+    let elsewhere = tempfile::tempdir().unwrap();
+    let explicit = elsewhere.path().join("team.toml");
+    fs::write(&explicit, "[project]\nname = \"team\"\n").unwrap();
+    let loaded = ProjectContext::load(root, Some(explicit.clone()));
+    assert_eq!(name(&loaded).as_deref(), Some("team"));
+    // Relative paths in it resolve next to it, not in the project
+    assert_eq!(
+        loaded.resolve_from_config("scripts/env.sh"),
+        elsewhere.path().join("scripts/env.sh")
+    );
+    assert_eq!(
+        loaded.resolve("scripts/env.sh"),
+        loaded.root.join("scripts/env.sh")
+    );
+}
+
+#[test]
+fn broken_config_loads_defaults_and_keeps_the_error() {
+    let project = tempfile::tempdir().unwrap();
+    fs::write(
+        project.path().join("contextkeeper.toml"),
+        "[git]\nuntracked = \"some\"\n",
+    )
+    .unwrap();
+    let loaded = ProjectContext::load(project.path().to_path_buf(), None);
+    let error = loaded.config_error.as_ref().expect("an error");
+    assert!(error.to_string().contains("untracked"), "{}", error);
+    assert!(loaded.config.git.is_none());
+    assert_eq!(
+        loaded.config_path,
+        Some(PathBuf::from(project.path()).join("contextkeeper.toml"))
+    );
+}
+
+/// The alias of the `[[git.repos]]` entry `path` resolves to
+fn matched<'a>(config: &'a Config, path: &str) -> Option<&'a str> {
+    repo_override(config, path).and_then(|repo| repo.alias.as_deref())
+}
+
+#[test]
+fn most_specific_overlapping_entry_wins() {
+    let config = config(
+        r#"
+        [[git.repos]]
+        path = "vendor/qcom/*"
+        alias = "qcom-any"
+
+        [[git.repos]]
+        path = "vendor/qcom/audio"
+        alias = "exact"
+
+        [[git.repos]]
+        path = "vendor/*/audio"
+        alias = "any-audio"
+
+        [[git.repos]]
+        path = "vendor/*"
+        alias = "vendor"
+
+        [[git.repos]]
+        path = "vendor/qcom/cam?ra"
+        alias = "camera"
+        "#,
+    );
+    // An exact path beats every glob, wherever it is listed
+    assert_eq!(matched(&config, "vendor/qcom/audio"), Some("exact"));
+    // Between globs, more literal characters win
+    assert_eq!(matched(&config, "vendor/qcom/camera"), Some("camera"));
+    assert_eq!(matched(&config, "vendor/qcom/video"), Some("qcom-any"));
+    assert_eq!(matched(&config, "vendor/nxp/audio"), Some("any-audio"));
+    // `*` stops at `/`
+    assert_eq!(matched(&config, "vendor/nxp"), Some("vendor"));
+    assert_eq!(matched(&config, "vendor/nxp/video"), None);
+    assert_eq!(matched(&config, "frameworks/av"), None);
+}
+
+#[test]
+fn equally_specific_globs_go_to_the_last_entry() {
+    let overlapping = config(
+        r#"
+        [[git.repos]]
+        path = "kernel/*"
+        alias = "first"
+
+        [[git.repos]]
+        path = "*/common"
+        alias = "second"
+
+        [[git.repos]]
+        path = "kernel/[a-z]ommon"
+        alias = "class"
+        "#,
+    );
+    // "kernel/" and "/common" are 7 literals each, and "kernel/" + class
+    // + "ommon" is 13
+    assert_eq!(matched(&overlapping, "kernel/common"), Some("class"));
+    assert_eq!(matched(&overlapping, "kernel/msm"), Some("first"));
+    assert_eq!(matched(&overlapping, "device/common"), Some("second"));
+
+    let tied = config(
+        r#"
+        [[git.repos]]
+        path = "kernel/*"
+        alias = "first"
+
+        [[git.repos]]
+        path = "*/common"
+        alias = "second"
+        "#,
+    );
+    // The same answer on every load
+    for _ in 0..3 {
+        assert_eq!(matched(&tied, "kernel/common"), Some("second"));
+    }
+}
+
+#[test]
+fn entry_paths_are_normalized_and_bad_globs_match_exactly() {
+    let config = config(
+        r#"
+        [[git.repos]]
+        path = "./docs/"
+        alias = "docs"
+        skip = true
+
+        [[git.repos]]
+        path = "./"
+        alias = "root"
+
+        [[git.repos]]
+        path = "tools/[unclosed"
+        alias = "literal"
+        "#,
+    );
+    assert_eq!(matched(&config, "docs"), Some("docs"));
+    assert_eq!(repo_override(&config, "docs").unwrap().skip, Some(true));
+    assert_eq!(matched(&config, "."), Some("root"));
+    assert_eq!(matched(&config, "tools/[unclosed"), Some("literal"));
+    assert_eq!(matched(&config, "tools/u"), None);
+}
@@ -0,0 +1,133 @@
+//! The container socket backend against a fake REST server on a unix
+//! socket, replying with recorded `/containers/json` bodies
+#![cfg(all(unix, feature = "containers"))]
+
+use context_keeper::collectors::container_socket::{list_containers_via_socket, parse_containers};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixListener;
+use std::path::Path;
+use std::thread::JoinHandle;
+
+fn fixture(name: &str) -> Vec<u8> {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path, e))
+}
+
+/// Answer one request on `socket` with `status` and `body`, sent in
+/// chunks the way podman and docker do; gives back the request line
+fn serve_once(socket: &Path, status: &'static str, body: Vec<u8>) -> JoinHandle<String> {
+    let listener = UnixListener::bind(socket).unwrap();
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            if header.trim().is_empty() {
+                break;
+            }
+        }
+        let mut stream = reader.into_inner();
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n",
+            status
+        )
+        .unwrap();
+        for chunk in body.chunks(100) {
+            write!(stream, "{:x}\r\n", chunk.len()).unwrap();
+            stream.write_all(chunk).unwrap();
+            stream.write_all(b"\r\n").unwrap();
+        }
+        stream.write_all(b"0\r\n\r\n").unwrap();
+        request_line.trim_end().to_string()
+    })
+}
+
+#[test]
+fn docker_listing_over_the_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("docker.sock");
+    let server = serve_once(&socket, "200 OK", fixture("docker_containers.json"));
+
+    let containers = list_containers_via_socket(&socket, "docker").unwrap();
+
+    assert_eq!(server.join().unwrap(), "GET /containers/json HTTP/1.1");
+    assert_eq!(containers.len(), 1);
+    let db = &containers[0];
+    assert_eq!(db.name, "shop-db-1", "the leading / is dropped");
+    assert_eq!(db.status, "Up 3 hours (healthy)");
+    assert_eq!(db.runtime, "docker");
+    assert_eq!(db.labels["com.docker.compose.project"], "shop");
+    assert_eq!(db.ports, ["0.0.0.0:5432->5432/tcp", "9187/tcp"]);
+    assert_eq!(db.mounts, ["/var/lib/postgresql/data"]);
+}
+
+#[test]
+fn libpod_listing_with_null_fields() {
+    let containers = parse_containers(&fixture("podman_ps.json"), "podman").unwrap();
+    assert_eq!(containers.len(), 2);
+    assert_eq!(containers[0].mounts, ["/src/aosp", "/ccache"]);
+    assert!(containers[0].ports.is_empty());
+    assert!(containers[1].labels.is_empty());
+    assert!(containers[1].mounts.is_empty());
+    assert_eq!(containers[1].ports, ["127.0.0.1:8000->8000/tcp"]);
+}
+
+#[test]
+fn error_reply_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("podman.sock");
+    let server = serve_once(
+        &socket,
+        "500 Internal Server Error",
+        br#"{"cause":"database is locked","message":"database is locked","response":500}"#.to_vec(),
+    );
+
+    let reason = list_containers_via_socket(&socket, "podman").unwrap_err();
+    server.join().unwrap();
+
+    assert!(
+        reason.contains("/containers/json answered 500") && reason.contains("database is locked"),
+        "{}",
+        reason
+    );
+}
+
+#[test]
+fn missing_socket_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("absent.sock");
+    let reason = list_containers_via_socket(&socket, "podman").unwrap_err();
+    assert!(
+        reason.starts_with(&socket.display().to_string()),
+        "{}",
+        reason
+    );
+}
+
+#[test]
+fn garbage_body_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("podman.sock");
+    let server = serve_once(&socket, "200 OK", b"not json".to_vec());
+    let reason = list_containers_via_socket(&socket, "podman").unwrap_err();
+    server.join().unwrap();
+    assert!(
+        reason.starts_with("unexpected /containers/json reply"),
+        "{}",
+        reason
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn socket_works_inside_a_runtime() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("docker.sock");
+    let server = serve_once(&socket, "200 OK", fixture("docker_containers.json"));
+    let containers = list_containers_via_socket(&socket, "docker").unwrap();
+    server.join().unwrap();
+    assert_eq!(containers[0].name, "shop-db-1");
+}
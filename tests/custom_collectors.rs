@@ -0,0 +1,216 @@
+//! `[[custom_collectors]]` running scripts from a fixture project
+#![cfg(unix)]
+
+mod common;
+
+use common::{stderr, stdout, Sandbox};
+use context_keeper::collectors::custom::{
+    cap_text, collect_custom_sections, CustomOutput, CustomSection, MAX_CUSTOM_OUTPUT,
+};
+use context_keeper::collectors::COLLECTOR_STATS;
+use context_keeper::ProjectContext;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// An executable `scripts/<name>` in `root`
+fn script(root: &Path, name: &str, body: &str) {
+    let path = root.join("scripts").join(name);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, format!("#!/bin/sh\n{}", body)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// The sections and collector warnings for the config in `root`, from a
+/// fresh thread so the warnings are only these
+fn sections(root: &Path, toml: &str) -> (Vec<CustomSection>, Vec<String>) {
+    std::fs::write(root.join("contextkeeper.toml"), toml).unwrap();
+    let project = ProjectContext::load(root.to_path_buf(), None);
+    assert!(project.config_error.is_none(), "{:?}", project.config_error);
+    std::thread::spawn(move || {
+        let sections = collect_custom_sections(&project);
+        let warnings = COLLECTOR_STATS.with(|stats| stats.borrow().warnings.clone());
+        (sections, warnings)
+    })
+    .join()
+    .unwrap()
+}
+
+fn text(section: &CustomSection) -> (&str, bool) {
+    match &section.output {
+        CustomOutput::Text { text, truncated } => (text, *truncated),
+        CustomOutput::Json { .. } => panic!("{} is JSON", section.name),
+    }
+}
+
+#[test]
+fn text_and_json_output_in_config_order() {
+    let dir = tempfile::tempdir().unwrap();
+    script(
+        dir.path(),
+        "queue.sh",
+        "echo \"slot 1: $1\"\necho 'slot 2: idle'\n",
+    );
+    script(
+        dir.path(),
+        "license.sh",
+        "echo '{\"server\": \"up\", \"seats\": 3}'\n",
+    );
+    let (sections, warnings) = sections(
+        dir.path(),
+        r#"
+        [[custom_collectors]]
+        name = "Flash queue"
+        command = "scripts/queue.sh"
+        args = ["rpi4"]
+
+        [[custom_collectors]]
+        name = "License"
+        command = "./scripts/license.sh"
+        format = "json"
+        levels = ["full"]
+        "#,
+    );
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(sections.len(), 2);
+    assert_eq!(sections[0].name, "Flash queue");
+    assert_eq!(text(&sections[0]), ("slot 1: rpi4\nslot 2: idle", false));
+    assert_eq!(sections[0].levels, ["normal", "full"]);
+    match &sections[1].output {
+        CustomOutput::Json { value } => {
+            assert_eq!(value, &serde_json::json!({"server": "up", "seats": 3}))
+        }
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn failures_are_warnings_not_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    script(
+        dir.path(),
+        "down.sh",
+        "echo 'queue server down' >&2\nexit 3\n",
+    );
+    script(dir.path(), "bad.sh", "echo 'not json'\n");
+    script(dir.path(), "slow.sh", "sleep 5\n");
+    script(dir.path(), "ok.sh", "echo fine\n");
+    let started = Instant::now();
+    let (sections, warnings) = sections(
+        dir.path(),
+        r#"
+        [[custom_collectors]]
+        name = "Down"
+        command = "scripts/down.sh"
+
+        [[custom_collectors]]
+        name = "Bad"
+        command = "scripts/bad.sh"
+        format = "json"
+
+        [[custom_collectors]]
+        name = "Slow"
+        command = "scripts/slow.sh"
+        timeout_ms = 200
+
+        [[custom_collectors]]
+        name = "Missing"
+        command = "scripts/missing.sh"
+
+        [[custom_collectors]]
+        name = "Ok"
+        command = "scripts/ok.sh"
+        levels = ["full", "everything"]
+        "#,
+    );
+    assert!(
+        started.elapsed() < Duration::from_secs(4),
+        "waited on slow.sh"
+    );
+    let names: Vec<&str> = sections.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, ["Ok"]);
+
+    let has = |text: &str| warnings.iter().any(|w| w.contains(text));
+    assert!(
+        has("Down: exit status: 3: queue server down"),
+        "{:?}",
+        warnings
+    );
+    assert!(has("Bad: invalid JSON output"), "{:?}", warnings);
+    assert!(has("slow.sh: timed out after 200ms"), "{:?}", warnings);
+    assert!(has("missing.sh"), "{:?}", warnings);
+    assert!(has("Ok: unknown level 'everything'"), "{:?}", warnings);
+}
+
+#[test]
+fn long_output_is_cut_at_a_line() {
+    let dir = tempfile::tempdir().unwrap();
+    script(
+        dir.path(),
+        "long.sh",
+        "i=0\nwhile [ $i -lt 1000 ]; do echo \"line $i\"; i=$((i+1)); done\n",
+    );
+    let (sections, _) = sections(
+        dir.path(),
+        "[[custom_collectors]]\nname = \"Long\"\ncommand = \"scripts/long.sh\"\n",
+    );
+    let (text, truncated) = text(&sections[0]);
+    assert!(truncated);
+    assert!(text.len() <= MAX_CUSTOM_OUTPUT);
+    assert!(text.starts_with("line 0\n") && text.lines().last().unwrap().starts_with("line "));
+    assert_eq!(cap_text("a\nbc\ndef", 6), ("a\nbc".to_string(), true));
+    assert_eq!(cap_text("abc", 3), ("abc".to_string(), false));
+}
+
+#[test]
+fn runs_in_the_root_with_a_minimal_environment() {
+    let sandbox = Sandbox::new();
+    script(
+        sandbox.root(),
+        "where.sh",
+        "echo \"cwd=$(pwd)\"\necho \"root=$CONTEXTKEEPER_ROOT\"\necho \"token=${DEPLOY_TOKEN:-unset}\"\n",
+    );
+    script(sandbox.root(), "table.sh", "echo '{\"queue\": \"a|b\"}'\n");
+    sandbox.write(
+        "contextkeeper.toml",
+        r#"
+        [[custom_collectors]]
+        name = "Where"
+        command = "scripts/where.sh"
+
+        [[custom_collectors]]
+        name = "Table"
+        command = "scripts/table.sh"
+        format = "json"
+        "#,
+    );
+    let elsewhere = tempfile::tempdir().unwrap();
+    let root = sandbox.root().to_str().unwrap();
+    let output = sandbox
+        .command(&["--cwd", root, "context"])
+        .current_dir(elsewhere.path())
+        .env("DEPLOY_TOKEN", "hunter2")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let text = stdout(&output);
+    let cwd = sandbox.root().canonicalize().unwrap();
+    assert!(
+        text.contains(&format!(
+            "## Where\n```\ncwd={}\nroot={}\ntoken=unset\n```",
+            cwd.display(),
+            root
+        )),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains("## Table\n| Key | Value |\n|-----|-------|\n| queue | a\\|b |"),
+        "{}",
+        text
+    );
+
+    // Not asked for at minimal
+    let output = sandbox.run(&["context", "--level", "minimal"]);
+    assert!(!stdout(&output).contains("## Where"));
+}
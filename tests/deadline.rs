@@ -0,0 +1,106 @@
+//! A collector that never returns: the tool deadline and cancellation
+//! abandon it and the rest of the context still comes back
+#![cfg(feature = "adb")]
+
+use context_keeper::collectors::fake_runner::{FakeResponse, FakeRunner};
+use context_keeper::context::CollectorStatus;
+use context_keeper::{collect_context, format_context, CollectionPlan, Level, ProjectContext};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// A project whose `adb devices -l` sleeps for a minute
+fn hung_adb(dir: &tempfile::TempDir) -> Arc<ProjectContext> {
+    std::fs::write(
+        dir.path().join("contextkeeper.toml"),
+        "[project]\nname = \"hung\"\n",
+    )
+    .unwrap();
+    let mut project = ProjectContext::load(dir.path().to_path_buf(), None);
+    project.runner = Arc::new(FakeRunner::new().respond(
+        &["adb", "devices"],
+        FakeResponse::Slow(
+            Duration::from_secs(60),
+            Box::new(FakeResponse::Exit {
+                code: 0,
+                stdout: b"List of devices attached\n".to_vec(),
+                stderr: Vec::new(),
+            }),
+        ),
+    ));
+    Arc::new(project)
+}
+
+fn status_of(ctx: &context_keeper::Context, name: &str) -> CollectorStatus {
+    ctx.reports
+        .iter()
+        .find(|r| r.name == name)
+        .unwrap_or_else(|| panic!("no {} report", name))
+        .status
+}
+
+#[tokio::test]
+async fn hung_collector_is_abandoned_at_the_deadline() {
+    let dir = tempfile::tempdir().unwrap();
+    let started = Instant::now();
+    let ctx = collect_context(
+        hung_adb(&dir),
+        CollectionPlan::all(),
+        Duration::from_millis(500),
+        CancellationToken::new(),
+    )
+    .await;
+    assert!(
+        started.elapsed() < Duration::from_secs(5),
+        "{:?}",
+        started.elapsed()
+    );
+
+    assert_eq!(status_of(&ctx, "devices"), CollectorStatus::TimedOut);
+    let others: Vec<_> = ctx
+        .reports
+        .iter()
+        .filter(|r| r.status != CollectorStatus::Ok)
+        .map(|r| r.name)
+        .collect();
+    assert_eq!(others, ["devices"]);
+    assert!(ctx.adb_devices.is_empty());
+
+    // Every level renders without the section and says what was left out
+    for level in [Level::Minimal, Level::Normal] {
+        let text = format_context(&ctx, level);
+        assert!(
+            text.contains("abandoned collectors: devices (timed out)"),
+            "{}: {}",
+            level.name(),
+            text
+        );
+    }
+    let full = format_context(&ctx, Level::Full);
+    assert!(full.contains("| devices | timed out |"), "{}", full);
+}
+
+#[tokio::test]
+async fn cancelling_abandons_what_is_still_running() {
+    let dir = tempfile::tempdir().unwrap();
+    let ct = CancellationToken::new();
+    let cancel = ct.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        cancel.cancel();
+    });
+    let started = Instant::now();
+    let ctx = collect_context(
+        hung_adb(&dir),
+        CollectionPlan::all(),
+        Duration::from_secs(60),
+        ct,
+    )
+    .await;
+    assert!(
+        started.elapsed() < Duration::from_secs(5),
+        "{:?}",
+        started.elapsed()
+    );
+    assert_eq!(status_of(&ctx, "devices"), CollectorStatus::Cancelled);
+}
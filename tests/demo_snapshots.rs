@@ -0,0 +1,71 @@
+//! The demo context at each level and in each language, and as the HTML
+//! dashboard, against the files in tests/snapshots.
+//! Run with `UPDATE_SNAPSHOTS=1` to rewrite them after a deliberate change.
+
+mod common;
+
+use common::snapshot::assert_snapshot;
+use context_keeper::demo::demo_context;
+use context_keeper::format::html::format_html;
+use context_keeper::format::{format_context, Level};
+use context_keeper::i18n::Language;
+
+#[test]
+fn demo_context_at_each_level() {
+    for language in [Language::En, Language::Ja] {
+        let mut ctx = demo_context(None).unwrap();
+        ctx.language = language;
+        for level in [Level::Minimal, Level::Normal, Level::Full] {
+            let name = match language {
+                Language::En => format!("demo-{}", level.name()),
+                _ => format!("demo-{}-{}", level.name(), language.code()),
+            };
+            assert_snapshot(&format!("{}.md", name), &format_context(&ctx, level));
+        }
+    }
+}
+
+#[test]
+fn japanese_demo_has_no_english_ages() {
+    let mut ctx = demo_context(None).unwrap();
+    ctx.language = Language::Ja;
+    let text = format_context(&ctx, Level::Full);
+    for english in [" ago", "just now", "minutes", "command logged"] {
+        assert!(!text.contains(english), "{:?} in\n{}", english, text);
+    }
+}
+
+/// The dashboard, with the generation time (shown in the local time zone)
+/// replaced by `{generated}`
+fn dashboard(language: Language) -> String {
+    let mut ctx = demo_context(None).unwrap();
+    ctx.language = language;
+    let generated = ctx
+        .now()
+        .with_timezone(&chrono::Local)
+        .format("%Y-%m-%d %H:%M %:z")
+        .to_string();
+    let html = format_html(&ctx);
+    assert!(html.contains(&generated), "no generation time in\n{}", html);
+    html.replace(&generated, "{generated}")
+}
+
+#[test]
+fn demo_dashboard() {
+    assert_snapshot("demo-dashboard.html", &dashboard(Language::En));
+    assert_snapshot("demo-dashboard-ja.html", &dashboard(Language::Ja));
+}
+
+#[test]
+fn dashboard_is_self_contained() {
+    let html = dashboard(Language::En);
+    assert!(html.starts_with("<!DOCTYPE html>"), "{}", html);
+    assert!(html.trim_end().ends_with("</html>"));
+    for external in ["<link", "<script src", "src=\"http", "url(http", "@import"] {
+        assert!(!html.contains(external), "{:?} in\n{}", external, html);
+    }
+    assert_eq!(
+        html.matches("<details").count(),
+        html.matches("</details>").count()
+    );
+}
@@ -0,0 +1,121 @@
+//! A project with nothing to report, at each level, against the files in
+//! tests/snapshots
+
+mod common;
+
+use common::snapshot::assert_snapshot;
+use common::{stderr, stdout, Sandbox};
+use context_keeper::collectors::{BuildTarget, GitInfo};
+use context_keeper::format::{format_context, nothing_notable, Level};
+use context_keeper::Context;
+
+fn at(now: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    Some(now.parse().unwrap())
+}
+
+/// A fresh directory: no repository, type, state or hints
+fn empty() -> Context {
+    Context {
+        project_name: "empty".to_string(),
+        now: at("2026-03-02T12:00:00Z"),
+        ..Context::default()
+    }
+}
+
+fn clean_repo(path: &str) -> GitInfo {
+    GitInfo {
+        repo_path: path.to_string(),
+        branch: "main".to_string(),
+        last_commit_short: "9a8b7c6".to_string(),
+        ..GitInfo::default()
+    }
+}
+
+/// A typed project between tasks: clean repositories, targets and more
+/// hints than are listed below full level
+fn idle() -> Context {
+    Context {
+        project_name: "firmware".to_string(),
+        project_type: "zephyr".to_string(),
+        targets: ["build", "flash"]
+            .map(|name| BuildTarget {
+                name: name.to_string(),
+                source: "conf".to_string(),
+                source_file: "contextkeeper.toml".to_string(),
+                ..BuildTarget::default()
+            })
+            .to_vec(),
+        git_repos: vec![clean_repo("."), clean_repo("modules/hal")],
+        hints: (1..=5).map(|n| format!("Hint number {}", n)).collect(),
+        now: at("2026-03-02T12:00:00Z"),
+        ..Context::default()
+    }
+}
+
+#[test]
+fn empty_project_at_each_level() {
+    for (name, ctx) in [("empty", empty()), ("idle", idle())] {
+        assert!(nothing_notable(&ctx), "{}", name);
+        for level in Level::ALL {
+            let text = format_context(&ctx, level);
+            assert!(!text.trim().is_empty(), "{} at {}", name, level.name());
+            assert_snapshot(&format!("{}-{}.md", name, level.name()), &text);
+        }
+    }
+}
+
+#[test]
+fn something_notable_renders_the_sections() {
+    let mut ctx = idle();
+    ctx.git_repos[1].is_dirty = true;
+    ctx.git_repos[1].modified_files = 2;
+    assert!(!nothing_notable(&ctx));
+    for level in Level::ALL {
+        let text = format_context(&ctx, level);
+        assert!(!text.contains("Nothing to Report"), "{}", text);
+    }
+}
+
+#[test]
+fn sparse_minimal_falls_back_to_normal() {
+    // A dirty repository and nothing else says too little at minimal
+    let mut ctx = empty();
+    ctx.git_repos = vec![GitInfo {
+        is_dirty: true,
+        modified_files: 1,
+        ..clean_repo(".")
+    }];
+    let minimal = format_context(&ctx, Level::Minimal);
+    let normal = format_context(&ctx, Level::Normal);
+    let body = |text: &str| {
+        let (_, rest) = text.split_once("\n\n").unwrap();
+        let (body, _) = rest.rsplit_once("---\n").unwrap();
+        body.trim_end().to_string()
+    };
+    assert!(minimal.starts_with("# Context Recovery (Minimal)\n"));
+    assert_eq!(body(&minimal), body(&normal));
+}
+
+#[test]
+fn empty_directory_through_the_cli() {
+    let sandbox = Sandbox::new();
+    sandbox.write("contextkeeper.toml", "[project]\nname = \"empty\"\n");
+    for level in ["minimal", "normal", "full"] {
+        let output = sandbox.run(&["context", "--level", level]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        let text = stdout(&output);
+        assert!(
+            text.contains(
+                "## Nothing to Report\n\
+                 - **Project:** empty\n\
+                 - **Git:** no repositories found\n\
+                 - **Work state:** none saved — call `save_work_state` to create one\n"
+            ),
+            "{}: {}",
+            level,
+            text
+        );
+    }
+    let output = sandbox.run(&["context", "--level", "oneline"]);
+    assert_eq!(stdout(&output), "empty\n");
+}
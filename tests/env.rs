@@ -0,0 +1,235 @@
+//! `context-keeper env`: the variable set and its quoting, against the
+//! files in tests/snapshots, and values read back through a shell
+
+mod common;
+
+use common::snapshot::assert_snapshot;
+use common::{stderr, stdout, Sandbox};
+use context_keeper::collectors::{AdbDevice, BuildTarget, GitInfo};
+use context_keeper::commands::env::{
+    env_vars, format_env, quote_env_value, EnvFormat, EnvValue, ENV_VARS,
+};
+use context_keeper::Context;
+use std::process::Command;
+
+/// Values a careless quoting would break on
+const CONTAINER: &str = "it's a \"builder\" $HOME `id` \\n";
+const LUNCH: &str = "aosp_cf-userdebug\nsecond line";
+
+fn target(name: &str, container: &str, lunch: &str) -> BuildTarget {
+    BuildTarget {
+        name: name.to_string(),
+        container_name: container.to_string(),
+        lunch_target: lunch.to_string(),
+        ..BuildTarget::default()
+    }
+}
+
+fn repo(path: &str, dirty: bool) -> GitInfo {
+    GitInfo {
+        repo_path: path.to_string(),
+        is_dirty: dirty,
+        ..GitInfo::default()
+    }
+}
+
+fn device(serial: &str) -> AdbDevice {
+    serde_json::from_value(
+        serde_json::json!({"serial": serial, "state": "device", "device_type": "adb"}),
+    )
+    .unwrap()
+}
+
+/// Everything known: a target with a container and lunch target, one
+/// device connected, dirty repositories
+fn known() -> Context {
+    Context {
+        project_name: "o'brien firmware".to_string(),
+        targets: vec![target("qcom-dev", CONTAINER, LUNCH), target("bare", "", "")],
+        adb_devices: vec![device("emulator-5554")],
+        git_repos: vec![
+            repo(".", true),
+            repo("vendor/hal", false),
+            repo("kernel wip", true),
+        ],
+        ..Context::default()
+    }
+}
+
+fn vars(ctx: &Context, target: Option<&str>) -> Vec<(&'static str, EnvValue)> {
+    env_vars(ctx, target).unwrap()
+}
+
+#[test]
+fn the_variable_set_is_stable() {
+    assert_eq!(
+        ENV_VARS,
+        [
+            "CK_PROJECT_NAME",
+            "CK_TARGET_CONTAINER",
+            "CK_TARGET_LUNCH",
+            "CK_DEVICE_SERIAL",
+            "CK_GIT_DIRTY_REPOS",
+        ]
+    );
+    // Every variable, in that order, whatever is known
+    for ctx in [known(), Context::default()] {
+        for target in [None, Some("qcom-dev"), Some("bare")] {
+            if ctx.targets.is_empty() && target.is_some() {
+                continue;
+            }
+            let names: Vec<&str> = vars(&ctx, target).iter().map(|(name, _)| *name).collect();
+            assert_eq!(names, ENV_VARS);
+        }
+    }
+}
+
+#[test]
+fn golden_output() {
+    for (file, ctx, target) in [
+        ("env-known", known(), Some("qcom-dev")),
+        ("env-bare-target", known(), Some("bare")),
+        ("env-nothing-known", Context::default(), None),
+    ] {
+        let vars = vars(&ctx, target);
+        assert_snapshot(&format!("{}.sh", file), &format_env(&vars, EnvFormat::Sh));
+        assert_snapshot(
+            &format!("{}.fish", file),
+            &format_env(&vars, EnvFormat::Fish),
+        );
+    }
+}
+
+#[test]
+fn single_quotes_escaped() {
+    for (value, sh, fish) in [
+        ("plain", "'plain'", "'plain'"),
+        ("", "''", "''"),
+        ("it's", r"'it'\''s'", r"'it\'s'"),
+        (r"a\b", r"'a\b'", r"'a\\b'"),
+        (r"\'", r"'\'\'''", r"'\\\''"),
+        (
+            "$HOME `id` \"x\"",
+            "'$HOME `id` \"x\"'",
+            "'$HOME `id` \"x\"'",
+        ),
+    ] {
+        assert_eq!(quote_env_value(value, EnvFormat::Sh), sh, "{:?}", value);
+        assert_eq!(quote_env_value(value, EnvFormat::Fish), fish, "{:?}", value);
+    }
+}
+
+#[test]
+fn devices_and_reasons() {
+    let mut ctx = known();
+    let serial = |ctx: &Context| vars(ctx, None)[3].1.clone();
+    assert_eq!(serial(&ctx), Ok("emulator-5554".to_string()));
+    ctx.adb_devices.push(device("1A2B3C"));
+    assert_eq!(
+        serial(&ctx),
+        Err("2 devices connected and none pinned by [adb] or ANDROID_SERIAL".to_string())
+    );
+    ctx.device_selection = Some(
+        serde_json::from_value(serde_json::json!({"pinned": "pixel", "serial": "1A2B3C"})).unwrap(),
+    );
+    assert_eq!(serial(&ctx), Ok("1A2B3C".to_string()));
+    ctx.device_selection =
+        Some(serde_json::from_value(serde_json::json!({"pinned": "pixel"})).unwrap());
+    assert_eq!(
+        serial(&ctx),
+        Err("pinned device pixel is not connected".to_string())
+    );
+
+    // Clean repositories are an empty list, not an unset variable
+    ctx.git_repos = vec![repo(".", false)];
+    assert_eq!(vars(&ctx, None)[4].1, Ok(String::new()));
+}
+
+#[test]
+fn eval_reads_the_values_back() {
+    let ctx = known();
+    let script = format_env(&vars(&ctx, Some("qcom-dev")), EnvFormat::Sh);
+    // Set beforehand, so an unset shows
+    let script = format!(
+        "CK_DEVICE_SERIAL=stale\n{}\nprintf '%s\\0' \"$CK_PROJECT_NAME\" \"$CK_TARGET_CONTAINER\" \
+         \"$CK_TARGET_LUNCH\" \"${{CK_DEVICE_SERIAL-unset}}\" \"$CK_GIT_DIRTY_REPOS\"",
+        script
+    );
+    let output = Command::new("sh").arg("-c").arg(&script).output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let values: Vec<String> = stdout(&output)
+        .split_terminator('\0')
+        .map(str::to_string)
+        .collect();
+    assert_eq!(
+        values,
+        [
+            "o'brien firmware",
+            CONTAINER,
+            LUNCH,
+            "emulator-5554",
+            ". kernel wip"
+        ]
+    );
+
+    let mut ctx = ctx;
+    ctx.adb_devices.clear();
+    let script = format!(
+        "CK_DEVICE_SERIAL=stale\n{}\nprintf %s \"${{CK_DEVICE_SERIAL-unset}}\"",
+        format_env(&vars(&ctx, None), EnvFormat::Sh)
+    );
+    let output = Command::new("sh").arg("-c").arg(&script).output().unwrap();
+    assert_eq!(stdout(&output), "unset");
+}
+
+#[test]
+fn unknown_target_through_the_cli() {
+    let sandbox = Sandbox::new();
+    sandbox.write(
+        "contextkeeper.toml",
+        "[project]\nname = \"firmware\"\n\n[scripts]\nconfig_dir = [\"configs\"]\n",
+    );
+    sandbox.write(
+        "configs/qcom-dev.conf",
+        "TARGET_NAME=qcom-dev\nCONTAINER_NAME=aosp-builder\nLUNCH_TARGET=qcom-userdebug\n",
+    );
+    sandbox.write("configs/emu.conf", "TARGET_NAME=emu\n");
+
+    let output = sandbox.run(&["env", "--target", "qcom-devv"]);
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("unknown target 'qcom-devv'; known targets: "),
+        "{}",
+        stderr(&output)
+    );
+    for name in ["qcom-dev", "emu"] {
+        assert!(stderr(&output).contains(name), "{}", stderr(&output));
+    }
+    assert_eq!(stdout(&output), "");
+
+    let output = sandbox.run(&["env", "--target", "qcom-dev"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let text = stdout(&output);
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), ENV_VARS.len(), "{}", text);
+    assert_eq!(
+        lines[..3],
+        [
+            "export CK_PROJECT_NAME='firmware'",
+            "export CK_TARGET_CONTAINER='aosp-builder'",
+            "export CK_TARGET_LUNCH='qcom-userdebug'",
+        ]
+    );
+    assert_eq!(
+        lines[4],
+        "unset CK_GIT_DIRTY_REPOS  # no git repositories found"
+    );
+
+    let output = sandbox.run(&["env", "--target", "emu", "--format", "fish"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).contains("set -e CK_TARGET_CONTAINER  # target emu has no container\n"),
+        "{}",
+        stdout(&output)
+    );
+}
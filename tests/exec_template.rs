@@ -0,0 +1,116 @@
+//! `{container}` and `{cmd}` in exec templates, escaped for the quotes
+//! around them, and run through a real shell
+
+use context_keeper::collectors::containers::fill_exec_template;
+
+/// Values with every character the quoting rules care about
+const AWKWARD: &[&str] = &[
+    "plain",
+    "with space",
+    "it's",
+    r#"say "hi""#,
+    "$HOME and ${USER}",
+    "`date` and $(id)",
+    r"back\slash",
+    r#"'"'\''mixed"'"#,
+    "",
+    "make -j8 && echo 'done; really'",
+];
+
+#[test]
+fn each_quoting_context() {
+    for (template, container, cmd, expected) in [
+        (
+            "podman exec -i {container} bash -lc '{cmd}'",
+            "dev",
+            "make",
+            "podman exec -i dev bash -lc 'make'",
+        ),
+        (
+            "podman exec -i {container} bash -lc '{cmd}'",
+            "dev box",
+            "echo 'hi' && make",
+            r"podman exec -i 'dev box' bash -lc 'echo '\''hi'\'' && make'",
+        ),
+        (
+            r#"docker exec {container} sh -c "{cmd}""#,
+            "dev",
+            r#"echo "$PATH" `pwd` \n"#,
+            r#"docker exec dev sh -c "echo \"\$PATH\" \`pwd\` \\n""#,
+        ),
+        (
+            "ssh lab {container} -- {cmd}",
+            "it's",
+            "make clean",
+            r"ssh lab 'it'\''s' -- 'make clean'",
+        ),
+        // An escaped quote doesn't open quoting, so the value is quoted as a word
+        (r"echo \' {cmd}", "dev", "a'b", r"echo \' 'a'\''b'"),
+        // A quote inside double quotes is literal and doesn't nest
+        (
+            r#"sh -c "echo '{cmd}'""#,
+            "dev",
+            r#"a"b"#,
+            r#"sh -c "echo 'a\"b'""#,
+        ),
+        // Unknown braces and repeats
+        ("{other} {cmd} {cmd}", "dev", "x", "{other} x x"),
+    ] {
+        assert_eq!(
+            fill_exec_template(template, container, cmd),
+            expected,
+            "{} with {:?} / {:?}",
+            template,
+            container,
+            cmd
+        );
+    }
+}
+
+#[cfg(unix)]
+fn sh(script: &str) -> String {
+    let output = std::process::Command::new("sh")
+        .args(["-c", script])
+        .env("HOME", "/home/nobody")
+        .env("USER", "nobody")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}: {:?}", script, output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Whatever the value, the shell gets it back as one word, unexpanded
+#[cfg(unix)]
+#[test]
+fn shell_sees_the_values_verbatim() {
+    let template = r#"printf '[%s]\n' {container} '{cmd}' "{cmd}" x{cmd}"#;
+    for container in AWKWARD.iter().filter(|c| !c.is_empty()) {
+        for cmd in AWKWARD {
+            let line = fill_exec_template(template, container, cmd);
+            assert_eq!(
+                sh(&line),
+                format!("[{}]\n[{}]\n[{}]\n[x{}]\n", container, cmd, cmd, cmd),
+                "{}",
+                line
+            );
+        }
+    }
+}
+
+/// A command run through `sh -c '{cmd}'` does what it does when run
+/// directly
+#[cfg(unix)]
+#[test]
+fn nested_shell_runs_the_command_as_written() {
+    for cmd in [
+        r#"echo "it's $HOME""#,
+        "printf '%s\\n' 'a b' \"c'd\"",
+        "for w in one 'two three'; do echo \"<$w>\"; done",
+        r#"echo `echo nested` $(echo subst) \$literal"#,
+    ] {
+        for template in [r"sh -c '{cmd}'", r#"sh -c "{cmd}""#, "sh -c {cmd}"] {
+            let line = fill_exec_template(template, "unused", cmd);
+            assert_eq!(sh(&line), sh(cmd), "{}", line);
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Exit codes of the CLI (see `context_keeper::error`)
+
+mod common;
+
+use common::{stderr, stdout, Sandbox};
+use context_keeper::error::{EXIT_CONFIG, EXIT_IO, EXIT_PARTIAL, EXIT_USAGE};
+
+/// Leave HOME unable to hold ~/.contextkeeper: read-only, or for root
+/// (who writes there anyway) a file in its place
+fn read_only_home(sandbox: &Sandbox) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let home = sandbox.home();
+        std::fs::set_permissions(home, std::fs::Permissions::from_mode(0o555)).unwrap();
+        if std::fs::create_dir(home.join(".probe")).is_err() {
+            return;
+        }
+        std::fs::remove_dir(home.join(".probe")).unwrap();
+        std::fs::set_permissions(home, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    std::fs::write(sandbox.home().join(".contextkeeper"), "").unwrap();
+}
+
+fn configured() -> Sandbox {
+    let sandbox = Sandbox::new();
+    sandbox.write("contextkeeper.toml", "[project]\nname = \"codes\"\n");
+    sandbox
+}
+
+#[test]
+fn bad_idle_threshold_exits_with_config_error() {
+    let sandbox = Sandbox::new();
+    sandbox.write(
+        "contextkeeper.toml",
+        "[hints.when.idle_over]\n\"2日\" = \"Back from the weekend\"\n",
+    );
+    for args in [&["check"][..], &["context"]] {
+        let output = sandbox.run(args);
+        assert_eq!(output.status.code(), Some(EXIT_CONFIG), "{:?}", args);
+        assert!(
+            stderr(&output).contains("invalid duration '2日'"),
+            "{}",
+            stderr(&output)
+        );
+    }
+}
+
+#[test]
+fn unparsable_config_exits_with_config_error() {
+    let sandbox = Sandbox::new();
+    sandbox.write("contextkeeper.toml", "[project\nname = 1\n");
+    for args in [&["check"][..], &["context"], &["context", "--json"]] {
+        let output = sandbox.run(args);
+        assert_eq!(output.status.code(), Some(EXIT_CONFIG), "{:?}", args);
+        // Scripts capture stdout; the error is on stderr only
+        assert!(output.stdout.is_empty(), "{:?}: {}", args, stdout(&output));
+        assert!(
+            stderr(&output).contains("Config error:"),
+            "{}",
+            stderr(&output)
+        );
+    }
+}
+
+#[test]
+fn check_without_a_config_exits_with_config_error() {
+    let output = Sandbox::new().run(&["check"]);
+    assert_eq!(output.status.code(), Some(EXIT_CONFIG));
+    assert!(stderr(&output).contains("No config file found"));
+}
+
+#[test]
+fn check_warnings_are_partial() {
+    let sandbox = Sandbox::new();
+    sandbox.write(
+        "contextkeeper.toml",
+        "[scripts]\nentry_point = \"build.sh\"\n",
+    );
+    let output = sandbox.run(&["check"]);
+    assert_eq!(output.status.code(), Some(EXIT_PARTIAL));
+    assert!(stderr(&output).contains("scripts.entry_point does not exist"));
+
+    sandbox.write("build.sh", "#!/bin/sh\n");
+    assert_eq!(sandbox.run(&["check"]).status.code(), Some(0));
+}
+
+#[test]
+fn save_state_to_a_read_only_home_is_an_io_error() {
+    let sandbox = configured();
+    read_only_home(&sandbox);
+    let output = sandbox.run(&["save-state", "fix the build"]);
+    assert_eq!(output.status.code(), Some(EXIT_IO), "{}", stderr(&output));
+    assert!(output.stdout.is_empty(), "{}", stdout(&output));
+    assert!(stderr(&output).contains("Failed to save work state"));
+}
+
+#[test]
+fn legacy_save_state_exits_zero_without_the_failure_message() {
+    let sandbox = configured();
+    read_only_home(&sandbox);
+    let output = sandbox.run(&["--save-state", "fix the build"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(output.stdout.is_empty(), "{}", stdout(&output));
+    assert!(
+        !stderr(&output).contains("Failed to save work state"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn context_still_prints_with_a_read_only_home() {
+    let sandbox = configured();
+    read_only_home(&sandbox);
+    let output = sandbox.run(&["context"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(stdout(&output).contains("codes"));
+}
+
+#[test]
+fn usage_errors() {
+    let sandbox = configured();
+    let output = sandbox.run(&["context", "--no-such-flag"]);
+    assert_eq!(output.status.code(), Some(EXIT_USAGE));
+    assert!(output.stdout.is_empty());
+
+    // The config is already there
+    let output = sandbox.run(&["init", "--yes"]);
+    assert_eq!(
+        output.status.code(),
+        Some(EXIT_USAGE),
+        "{}",
+        stderr(&output)
+    );
+    assert!(output.stdout.is_empty(), "{}", stdout(&output));
+}
+
+#[test]
+fn success_is_zero() {
+    let sandbox = configured();
+    for args in [&["context"][..], &["check"], &["save-state", "done"]] {
+        let output = sandbox.run(args);
+        assert_eq!(
+            output.status.code(),
+            Some(0),
+            "{:?}: {}",
+            args,
+            stderr(&output)
+        );
+    }
+}
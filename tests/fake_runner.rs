@@ -0,0 +1,282 @@
+//! Collectors against recorded tool output, through `FakeRunner`
+
+use context_keeper::collectors::containers::list_containers;
+use context_keeper::collectors::fake_runner::{FakeResponse, FakeRunner};
+use context_keeper::collectors::git::{collect_git_info_for_path, StatusOptions};
+use context_keeper::collectors::{run_program, COLLECTOR_STATS, DEFAULT_COMMAND_TIMEOUT};
+use std::time::{Duration, Instant};
+
+fn fixture(name: &str) -> String {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e))
+}
+
+/// Run `f` on a fresh thread, so it starts with empty `COLLECTOR_STATS`,
+/// and return what it gave back with the reason the source was unavailable
+fn on_collector_thread<T: Send + 'static>(
+    f: impl FnOnce() -> T + Send + 'static,
+) -> (T, Option<String>) {
+    std::thread::spawn(move || {
+        let value = f();
+        let unavailable = COLLECTOR_STATS.with(|stats| stats.borrow().unavailable.clone());
+        (value, unavailable)
+    })
+    .join()
+    .unwrap()
+}
+
+#[cfg(feature = "adb")]
+#[test]
+fn adb_devices_from_recorded_output() {
+    use context_keeper::collectors::adb::collect_adb_devices;
+
+    let runner = FakeRunner::new()
+        .on(&["adb", "devices", "-l"], &fixture("adb_devices_l.txt"))
+        .on(
+            &["fastboot", "devices", "-l"],
+            &fixture("fastboot_devices_l.txt"),
+        );
+    let devices = collect_adb_devices(&runner);
+
+    let summary: Vec<_> = devices
+        .iter()
+        .map(|d| (d.serial.as_str(), d.state.as_str(), d.model.as_str()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("emulator-5554", "device", "sdk_gphone64_x86_64"),
+            ("1A2B3C4D5E", "device", "Pixel_7"),
+            ("R58N12ABCDE", "unauthorized", ""),
+            ("9B061FFBA00123", "fastboot", ""),
+        ]
+    );
+    assert_eq!(devices[1].codename, "panther");
+    assert_eq!(devices[3].device_type, "fastboot");
+
+    let lines: Vec<_> = runner.calls().iter().map(|c| c.line()).collect();
+    assert_eq!(lines, ["adb devices -l", "fastboot devices -l"]);
+    assert!(runner
+        .calls()
+        .iter()
+        .all(|c| c.timeout == Some(DEFAULT_COMMAND_TIMEOUT)));
+}
+
+#[cfg(feature = "adb")]
+#[test]
+fn adb_missing_is_unavailable() {
+    use context_keeper::collectors::adb::collect_adb_devices;
+
+    let (devices, unavailable) = on_collector_thread(|| collect_adb_devices(&FakeRunner::new()));
+    assert!(devices.is_empty());
+    assert_eq!(unavailable.as_deref(), Some("adb not installed"));
+}
+
+#[cfg(feature = "adb")]
+#[test]
+fn adb_server_failure_is_unavailable() {
+    use context_keeper::collectors::adb::collect_adb_devices;
+
+    let (devices, unavailable) = on_collector_thread(|| {
+        let runner = FakeRunner::new().on_failure(
+            &["adb", "devices"],
+            1,
+            "* daemon not running; starting now at tcp:5037\nerror: cannot connect to daemon\n",
+        );
+        collect_adb_devices(&runner)
+    });
+    assert!(devices.is_empty());
+    assert_eq!(
+        unavailable.as_deref(),
+        Some("`adb devices` failed: * daemon not running; starting now at tcp:5037")
+    );
+}
+
+#[test]
+fn podman_ps_from_recorded_output() {
+    let runner = FakeRunner::new().on(
+        &["podman", "ps", "--format", "json"],
+        &fixture("podman_ps.json"),
+    );
+    let containers = list_containers(&runner, "podman");
+
+    let summary: Vec<_> = containers
+        .iter()
+        .map(|c| (c.name.as_str(), c.status.as_str(), c.runtime.as_str()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("aosp-build", "Up 3 hours", "podman"),
+            ("yocto-kirkstone", "Up 12 minutes (healthy)", "podman"),
+        ]
+    );
+    assert_eq!(containers[0].labels["project"], "aosp");
+    assert_eq!(containers[0].mounts, ["/src/aosp", "/ccache"]);
+    assert!(containers[0].ports.is_empty(), "null Ports is no ports");
+    assert!(containers[1].labels.is_empty(), "null Labels is no labels");
+    assert_eq!(containers[1].ports, ["127.0.0.1:8000->8000/tcp"]);
+    assert_eq!(
+        runner.calls()[0].timeout,
+        Some(DEFAULT_COMMAND_TIMEOUT),
+        "run_program always sets a timeout"
+    );
+}
+
+#[test]
+fn docker_ps_from_recorded_output() {
+    let runner = FakeRunner::new().on(
+        &["docker", "ps", "--format", "{{json .}}"],
+        &fixture("docker_ps.ndjson"),
+    );
+    let containers = list_containers(&runner, "docker");
+
+    assert_eq!(containers.len(), 2);
+    let db = &containers[0];
+    assert_eq!(
+        (db.name.as_str(), db.status.as_str(), db.runtime.as_str()),
+        ("shop-db-1", "Up 3 hours (healthy)", "docker")
+    );
+    assert_eq!(db.labels["com.docker.compose.project"], "shop");
+    assert_eq!(db.labels["com.docker.compose.service"], "db");
+    assert_eq!(db.ports, ["0.0.0.0:5432->5432/tcp", ":::5432->5432/tcp"]);
+    assert!(db.mounts.is_empty(), "the CLI gives no mount destinations");
+    let scratch = &containers[1];
+    assert_eq!(scratch.name, "scratch");
+    assert!(scratch.labels.is_empty() && scratch.ports.is_empty());
+}
+
+#[test]
+fn unparseable_ps_output_is_unavailable() {
+    let (containers, unavailable) = on_collector_thread(|| {
+        let runner = FakeRunner::new().on(&["docker", "ps"], "shop-db-1\tUp 3 hours\n");
+        list_containers(&runner, "docker")
+    });
+    assert!(containers.is_empty());
+    assert!(
+        unavailable
+            .as_deref()
+            .is_some_and(|reason| reason.starts_with("unexpected docker ps line")),
+        "{:?}",
+        unavailable
+    );
+}
+
+#[test]
+fn podman_failure_is_unavailable() {
+    let (containers, unavailable) = on_collector_thread(|| {
+        let runner = FakeRunner::new().on_failure(
+            &["podman", "ps"],
+            125,
+            "Error: cannot connect to Podman socket\n",
+        );
+        list_containers(&runner, "podman")
+    });
+    assert!(containers.is_empty());
+    assert_eq!(
+        unavailable.as_deref(),
+        Some("`podman ps` failed: Error: cannot connect to Podman socket")
+    );
+}
+
+#[test]
+fn git_status_from_recorded_output() {
+    let repo = tempfile::tempdir().unwrap();
+    let runner = FakeRunner::new()
+        .on(&["git", "status"], &fixture("git_status_porcelain_v2.txt"))
+        .on(&["git", "log", "-1"], "8f2c1d7 Add sign-in form\n");
+    let info = collect_git_info_for_path(
+        &runner,
+        repo.path(),
+        &StatusOptions {
+            untracked: Default::default(),
+            timeout: Some(Duration::from_secs(5)),
+            backend: Default::default(),
+        },
+    )
+    .expect("a repository");
+
+    assert_eq!(info.branch, "feature/login");
+    assert_eq!((info.ahead, info.behind), (2, 1));
+    assert_eq!(info.modified_files, 3);
+    assert_eq!(info.conflicted_files, 1);
+    assert_eq!(info.untracked_files, 2);
+    assert!(info.is_dirty);
+    assert_eq!(info.last_commit_short, "8f2c1d7 Add sign-in form");
+
+    let calls = runner.calls();
+    let repo_arg = repo.path().to_string_lossy();
+    assert_eq!(calls[0].command[..3], ["git", "-C", &*repo_arg]);
+    assert_eq!(calls[0].timeout, Some(Duration::from_secs(5)));
+}
+
+#[test]
+fn git_outside_a_repository_is_none() {
+    let repo = tempfile::tempdir().unwrap();
+    let runner = FakeRunner::new().on_failure(
+        &["git", "status"],
+        128,
+        "fatal: not a git repository (or any of the parent directories): .git\n",
+    );
+    let options = StatusOptions {
+        untracked: Default::default(),
+        timeout: None,
+        backend: Default::default(),
+    };
+    assert!(collect_git_info_for_path(&runner, repo.path(), &options).is_none());
+    assert_eq!(
+        runner.calls()[0].timeout,
+        Some(DEFAULT_COMMAND_TIMEOUT),
+        "git_in falls back to the default timeout"
+    );
+}
+
+#[test]
+fn git_status_timeout_keeps_the_branch() {
+    let repo = tempfile::tempdir().unwrap();
+    let runner = FakeRunner::new()
+        .respond(
+            &["git", "status"],
+            FakeResponse::Slow(Duration::from_secs(60), Box::new(FakeResponse::NotFound)),
+        )
+        .on(&["git", "branch", "--show-current"], "main\n")
+        .on(&["git", "log", "-1"], "abc1234 Initial commit\n");
+    let options = StatusOptions {
+        untracked: Default::default(),
+        timeout: Some(Duration::from_millis(50)),
+        backend: Default::default(),
+    };
+    let info = collect_git_info_for_path(&runner, repo.path(), &options).expect("listed");
+    assert_eq!(info.branch, "main");
+    assert_eq!(info.status_unavailable.as_deref(), Some("timeout"));
+}
+
+#[test]
+fn system_runner_kills_a_child_past_its_timeout() {
+    use context_keeper::collectors::{CommandRunner, SystemRunner};
+    use std::ffi::OsStr;
+
+    if !SystemRunner.has_program("sleep") {
+        return;
+    }
+    let started = Instant::now();
+    let result = SystemRunner.run(
+        "sleep",
+        &[OsStr::new("30")],
+        Some(Duration::from_millis(200)),
+    );
+    assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
+
+#[tokio::test]
+async fn system_runner_works_inside_a_runtime() {
+    use context_keeper::collectors::{CommandRunner, SystemRunner};
+
+    if !SystemRunner.has_program("git") {
+        return;
+    }
+    let output = run_program(&SystemRunner, "git", &["--version"]).unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("git version"));
+}
@@ -0,0 +1,198 @@
+//! What each cargo feature adds, or what a build without it says instead.
+//! Run under `--no-default-features --features minimal` as well as the
+//! defaults; every test here holds in both.
+
+mod common;
+
+use common::{stderr, stdout, Sandbox};
+
+/// `availability` of `section` from `context --section <section> --json`
+fn availability(sandbox: &Sandbox, section: &str) -> serde_json::Value {
+    let output = sandbox.run(&["context", "--section", section, "--json"]);
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .unwrap_or_else(|e| panic!("{}: {}", e, stdout(&output)));
+    value["availability"][section].clone()
+}
+
+fn not_built_reason(feature: &str) -> serde_json::Value {
+    serde_json::json!({
+        "state": "unavailable",
+        "reason": format!("built without the `{}` feature", feature),
+    })
+}
+
+#[test]
+fn each_optional_collector_is_there_or_says_why_not() {
+    let sandbox = Sandbox::new();
+    sandbox.write("contextkeeper.toml", "[history]\nenabled = true\n");
+    for (section, feature, built) in [
+        ("containers", "containers", cfg!(feature = "containers")),
+        ("devices", "adb", cfg!(feature = "adb")),
+        ("history", "history", cfg!(feature = "history")),
+    ] {
+        let availability = availability(&sandbox, section);
+        if built {
+            assert_ne!(availability, not_built_reason(feature), "{}", section);
+        } else {
+            assert_eq!(availability, not_built_reason(feature), "{}", section);
+        }
+    }
+}
+
+#[test]
+fn left_out_collector_shows_a_stub() {
+    let sandbox = Sandbox::new();
+    let text = stdout(&sandbox.run(&["context", "--level", "full"]));
+    let says_not_built = text.contains("built without the `adb` feature");
+    assert_eq!(says_not_built, !cfg!(feature = "adb"), "{}", text);
+}
+
+#[test]
+fn template_renders_or_is_refused() {
+    let sandbox = Sandbox::new();
+    let template = sandbox.write(
+        "context.j2",
+        "{{ project_name }}: {% for repo in git_repos %}{{ repo.branch }} {% endfor %}\n",
+    );
+    let output = sandbox.run(&[
+        "context",
+        "--demo",
+        "--template",
+        template.to_str().unwrap(),
+    ]);
+    if cfg!(feature = "templates") {
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert!(
+            stdout(&output).starts_with("aosp-demo: "),
+            "{}",
+            stdout(&output)
+        );
+    } else {
+        assert_eq!(output.status.code(), Some(2));
+        assert!(
+            stderr(&output).contains("built without the `templates` feature"),
+            "{}",
+            stderr(&output)
+        );
+    }
+}
+
+#[cfg(not(feature = "http-transport"))]
+#[test]
+fn http_serve_needs_the_feature() {
+    let sandbox = Sandbox::new();
+    let output = sandbox.run(&["serve", "--http", "127.0.0.1:0"]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("built without the `http-transport` feature"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[cfg(not(feature = "gix-backend"))]
+#[test]
+fn gix_backend_falls_back_to_the_cli_with_a_warning() {
+    let sandbox = Sandbox::new();
+    sandbox.write("contextkeeper.toml", "[git]\nbackend = \"gix\"\n");
+    let output = sandbox.run(&["context", "--json"]);
+    assert!(output.status.success() || output.status.code() == Some(1));
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let git = value["reports"]
+        .as_array()
+        .and_then(|all| all.iter().find(|c| c["name"] == "git"))
+        .cloned()
+        .unwrap_or_else(|| panic!("{}", stdout(&output)));
+    assert_eq!(
+        git["warnings"],
+        serde_json::json!(["[git] backend = \"gix\": built without the `gix-backend` feature"])
+    );
+}
+
+#[cfg(feature = "http-transport")]
+#[test]
+fn http_serve_answers_initialize() {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let sandbox = Sandbox::new();
+    let mut child = sandbox
+        .command(&["serve", "--http", "127.0.0.1:0"])
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let mut addr = None;
+    let mut line = String::new();
+    while stderr.read_line(&mut line).unwrap() > 0 {
+        if let Some(rest) = line.trim().strip_prefix("Serving MCP on http://") {
+            addr = rest.strip_suffix("/mcp").map(str::to_string);
+            break;
+        }
+        line.clear();
+    }
+    let addr = addr.expect("the address it listens on");
+
+    let body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": {
+            "protocolVersion": "2024-11-05",
+            "capabilities": {},
+            "clientInfo": {"name": "test", "version": "1"}
+        }
+    })
+    .to_string();
+    let mut stream = std::net::TcpStream::connect(&addr).unwrap();
+    stream
+        .set_read_timeout(Some(std::time::Duration::from_secs(10)))
+        .unwrap();
+    write!(
+        stream,
+        "POST /mcp HTTP/1.1\r\nHost: {addr}\r\nContent-Type: application/json\r\n\
+         Accept: application/json, text/event-stream\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )
+    .unwrap();
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.contains("serverInfo"), "{}", response);
+}
+
+#[cfg(feature = "stdio-server")]
+#[test]
+fn stdio_server_answers_a_tool_call() {
+    let sandbox = Sandbox::new();
+    let mut client = common::mcp::McpClient::start(&sandbox, &[]);
+    let text = client.call_text("get_dev_context", serde_json::json!({ "level": "minimal" }));
+    assert!(!text.is_empty());
+    client.close();
+}
+
+#[test]
+fn unreadable_template_is_an_io_error() {
+    let sandbox = Sandbox::new();
+    let output = sandbox.run(&["context", "--demo", "--template", "missing.j2"]);
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+}
+
+#[cfg(feature = "templates")]
+#[test]
+fn broken_template_names_the_line() {
+    let sandbox = Sandbox::new();
+    sandbox.write("broken.j2", "{{ project_name }}\n{% if x %}\n");
+    let output = sandbox.run(&["context", "--demo", "--template", "broken.j2"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).starts_with("Error: broken.j2:2: "),
+        "{}",
+        stderr(&output)
+    );
+}
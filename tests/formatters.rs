@@ -0,0 +1,141 @@
+//! The small formatters the sections are built from
+
+use chrono::{DateTime, Utc};
+use context_keeper::collectors::GitInfo;
+use context_keeper::format::{
+    format_age, format_duration_secs, format_elapsed, format_file_counts, format_git_status,
+    format_size, json_rows, Level, Section,
+};
+use context_keeper::i18n::Language;
+
+fn now() -> DateTime<Utc> {
+    "2026-03-02T12:00:00Z".parse().unwrap()
+}
+
+fn repo(modified: usize, untracked: usize, conflicted: usize) -> GitInfo {
+    GitInfo {
+        repo_path: "frameworks/av".to_string(),
+        branch: "main".to_string(),
+        is_dirty: modified + untracked + conflicted > 0,
+        modified_files: modified,
+        untracked_files: untracked,
+        conflicted_files: conflicted,
+        ..GitInfo::default()
+    }
+}
+
+#[test]
+fn file_counts() {
+    let en = Language::default();
+    assert_eq!(format_file_counts(&repo(0, 0, 0), en), "clean");
+    assert_eq!(format_file_counts(&repo(2, 0, 0), en), "2M");
+    assert_eq!(format_file_counts(&repo(0, 3, 0), en), "3U");
+    assert_eq!(format_file_counts(&repo(2, 1, 0), en), "2M 1U");
+    assert_eq!(format_file_counts(&repo(0, 0, 1), en), "1 conflicts");
+    assert_eq!(format_file_counts(&repo(2, 1, 1), en), "1 conflicts, 2M 1U");
+}
+
+#[test]
+fn git_status_with_operation_and_upstream() {
+    let en = Language::default();
+    let mut git = repo(0, 0, 2);
+    git.operation = Some("rebase".to_string());
+    git.ahead = 3;
+    git.behind = 1;
+    assert_eq!(
+        format_git_status(&git, en),
+        "rebase in progress, 2 conflicts, 3 ahead, 1 behind"
+    );
+
+    git.compared_to = Some("main".to_string());
+    git.operation = None;
+    assert_eq!(
+        format_git_status(&git, en),
+        "2 conflicts, 3 ahead of main, 1 behind of main"
+    );
+
+    git.status_unavailable = Some("timeout".to_string());
+    assert_eq!(format_git_status(&git, en), "status unavailable (timeout)");
+}
+
+#[test]
+fn sizes() {
+    assert_eq!(format_size(0), "0 B");
+    assert_eq!(format_size(1023), "1023 B");
+    assert_eq!(format_size(1024), "1.0 KB");
+    assert_eq!(format_size(1536 * 1024), "1.5 MB");
+    assert_eq!(format_size(3 * 1024u64.pow(4)), "3.0 TB");
+    assert_eq!(format_size(5 * 1024u64.pow(5)), "5120.0 TB");
+}
+
+#[test]
+fn ages_and_elapsed_times() {
+    let en = Language::En;
+    for (timestamp, age, elapsed) in [
+        ("2026-03-02T11:59:30Z", "just now", "<1m"),
+        ("2026-03-02T11:15:00Z", "45m ago", "45m"),
+        ("2026-03-02T09:30:00Z", "2h ago", "2h 30m"),
+        ("2026-02-27T06:00:00Z", "3d ago", "3d 6h"),
+        // Clocks ahead of ours count as now
+        ("2026-03-02T13:00:00Z", "just now", "<1m"),
+        // Another offset is the same instant
+        ("2026-03-02T20:15:00+09:00", "45m ago", "45m"),
+    ] {
+        assert_eq!(format_age(timestamp, now(), en), age, "{}", timestamp);
+        assert_eq!(format_elapsed(timestamp, now()), elapsed, "{}", timestamp);
+    }
+    assert_eq!(format_age("yesterday", now(), en), "yesterday");
+    assert_eq!(format_elapsed("", now()), "");
+}
+
+#[test]
+fn ages_in_japanese() {
+    let ja = Language::Ja;
+    for (timestamp, age) in [
+        ("2026-03-02T11:59:30Z", "たった今"),
+        ("2026-03-02T11:15:00Z", "45分前"),
+        ("2026-03-02T09:30:00Z", "2時間前"),
+        ("2026-02-27T06:00:00Z", "3日前"),
+    ] {
+        assert_eq!(format_age(timestamp, now(), ja), age, "{}", timestamp);
+    }
+}
+
+#[test]
+fn durations() {
+    assert_eq!(format_duration_secs(0), "0s");
+    assert_eq!(format_duration_secs(59), "59s");
+    assert_eq!(format_duration_secs(61), "1m 1s");
+    assert_eq!(format_duration_secs(3600), "1h 0m");
+    assert_eq!(format_duration_secs(90061), "25h 1m");
+}
+
+#[test]
+fn custom_section_rows() {
+    let rows = json_rows(&serde_json::json!({"board": "rpi4", "jobs": 8}));
+    assert_eq!(
+        rows,
+        [
+            ("board".to_string(), "rpi4".to_string()),
+            ("jobs".to_string(), "8".to_string())
+        ]
+    );
+    let rows = json_rows(&serde_json::json!(["a", null]));
+    assert_eq!(rows[1], ("1".to_string(), "null".to_string()));
+    assert_eq!(
+        json_rows(&serde_json::json!(true)),
+        [("value".to_string(), "true".to_string())]
+    );
+}
+
+#[test]
+fn level_and_section_names_round_trip() {
+    for level in Level::ALL {
+        assert_eq!(Level::parse(level.name()), level);
+    }
+    assert_eq!(Level::parse("verbose"), Level::Normal);
+    for section in Section::ALL {
+        assert_eq!(Section::parse(section.name()), Some(section));
+    }
+    assert_eq!(Section::parse("Git"), None);
+}
@@ -0,0 +1,164 @@
+//! The gix backend reads the same repositories as `git status` does
+#![cfg(feature = "gix-backend")]
+
+mod common;
+
+use common::git::{commit, git, has_git};
+use context_keeper::collectors::git::{collect_git_info_for_path, StatusOptions};
+use context_keeper::collectors::SystemRunner;
+use context_keeper::config::{GitBackend, UntrackedFiles};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Both backends' view of `repo`, without the fields only the CLI path
+/// fills in
+fn both(repo: &Path, untracked: UntrackedFiles) -> (serde_json::Value, serde_json::Value) {
+    let read = |backend| {
+        let options = StatusOptions {
+            untracked,
+            backend,
+            ..StatusOptions::default()
+        };
+        let info = collect_git_info_for_path(&SystemRunner, repo, &options).expect("a repository");
+        let mut value = serde_json::to_value(info).unwrap();
+        value.as_object_mut().unwrap().remove("last_activity");
+        value
+    };
+    (read(GitBackend::Cli), read(GitBackend::Gix))
+}
+
+fn assert_same(repo: &Path, untracked: UntrackedFiles) {
+    let (cli, gix) = both(repo, untracked);
+    assert_eq!(cli, gix);
+}
+
+#[test]
+fn clean_repository() {
+    if !has_git() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    git(dir.path(), &["init", "-q"]);
+    commit(dir.path(), "a.txt", "one\n", "First commit");
+    assert_same(dir.path(), UntrackedFiles::Normal);
+}
+
+#[test]
+fn modified_renamed_and_untracked() {
+    if !has_git() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    git(root, &["init", "-q"]);
+    commit(root, "a.txt", "one\n", "First commit");
+    fs::write(root.join("b.txt"), "bee\n").unwrap();
+    git(root, &["add", "b.txt"]);
+    git(
+        root,
+        &[
+            "commit",
+            "-qm",
+            "A commit message long enough to be cut short by both",
+        ],
+    );
+
+    fs::write(root.join("a.txt"), "one\ntwo\n").unwrap();
+    git(root, &["mv", "b.txt", "c.txt"]);
+    fs::write(root.join("new.txt"), "new\n").unwrap();
+    fs::create_dir_all(root.join("build/out")).unwrap();
+    fs::write(root.join("build/out/x.o"), "x").unwrap();
+    fs::write(root.join("build/y.o"), "y").unwrap();
+
+    for untracked in [
+        UntrackedFiles::Normal,
+        UntrackedFiles::All,
+        UntrackedFiles::No,
+    ] {
+        assert_same(root, untracked);
+    }
+    let (_, gix) = both(root, UntrackedFiles::All);
+    assert_eq!(gix["modified_files"], 2);
+    assert_eq!(gix["untracked_files"], 3);
+}
+
+#[test]
+fn merge_conflict() {
+    if !has_git() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    git(root, &["init", "-q"]);
+    commit(root, "a.txt", "base\n", "Base");
+    git(root, &["checkout", "-qb", "other"]);
+    commit(root, "a.txt", "theirs\n", "Theirs");
+    git(root, &["checkout", "-q", "main"]);
+    commit(root, "a.txt", "ours\n", "Ours");
+    let merge = Command::new("git")
+        .args(["-c", "user.name=t", "-c", "user.email=t@example.com"])
+        .args(["merge", "-q", "other"])
+        .current_dir(root)
+        .output()
+        .unwrap();
+    assert!(!merge.status.success());
+
+    assert_same(root, UntrackedFiles::Normal);
+    let (_, gix) = both(root, UntrackedFiles::Normal);
+    assert_eq!(gix["conflicted_files"], 1);
+    assert_eq!(gix["operation"], "merge");
+}
+
+#[test]
+fn detached_head() {
+    if !has_git() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    git(root, &["init", "-q"]);
+    commit(root, "a.txt", "one\n", "First");
+    commit(root, "a.txt", "two\n", "Second");
+    git(root, &["checkout", "-q", "--detach", "HEAD~1"]);
+    assert_same(root, UntrackedFiles::Normal);
+
+    fs::write(root.join("a.txt"), "dirty\n").unwrap();
+    assert_same(root, UntrackedFiles::Normal);
+}
+
+#[test]
+fn ahead_and_behind_upstream() {
+    if !has_git() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let upstream = dir.path().join("upstream");
+    let clone = dir.path().join("clone");
+    fs::create_dir(&upstream).unwrap();
+    git(&upstream, &["init", "-q"]);
+    commit(&upstream, "a.txt", "one\n", "First");
+    git(dir.path(), &["clone", "-q", "upstream", "clone"]);
+
+    commit(&upstream, "a.txt", "two\n", "Upstream moved");
+    commit(&clone, "b.txt", "local\n", "Local one");
+    commit(&clone, "c.txt", "local\n", "Local two");
+    git(&clone, &["fetch", "-q"]);
+
+    assert_same(&clone, UntrackedFiles::Normal);
+    let (_, gix) = both(&clone, UntrackedFiles::Normal);
+    assert_eq!(
+        (gix["ahead"].as_u64(), gix["behind"].as_u64()),
+        (Some(2), Some(1))
+    );
+}
+
+#[test]
+fn outside_a_repository_is_none() {
+    let dir = tempfile::tempdir().unwrap();
+    let options = StatusOptions {
+        backend: GitBackend::Gix,
+        ..StatusOptions::default()
+    };
+    assert!(collect_git_info_for_path(&SystemRunner, dir.path(), &options).is_none());
+}
@@ -0,0 +1,103 @@
+//! `context-keeper git-hooks` against real repositories
+
+mod common;
+
+use common::git::{git, has_git};
+use common::{stdout, Sandbox};
+
+#[test]
+fn tracked_hooks_path_gets_no_backup_in_the_work_tree() {
+    if !has_git() {
+        return;
+    }
+    let sandbox = Sandbox::new();
+    let root = sandbox.root();
+    git(root, &["init", "-q"]);
+    git(root, &["config", "core.hooksPath", ".husky"]);
+    sandbox.write(".husky/post-checkout", "#!/bin/sh\necho checked out\n");
+    git(root, &["add", "-A"]);
+    git(root, &["commit", "-qm", "hooks"]);
+
+    let output = sandbox.run(&["git-hooks", "install"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(
+        stdout(&output).contains("hook-backups"),
+        "{}",
+        stdout(&output)
+    );
+
+    // Only the hooks themselves changed
+    let status = git(root, &["status", "--porcelain", "--untracked-files=all"]);
+    let mut changed: Vec<&str> = status.lines().map(|line| &line[3..]).collect();
+    changed.sort();
+    assert_eq!(changed, [".husky/post-checkout", ".husky/post-commit"]);
+
+    let backups = root.join(".git/context-keeper/hook-backups");
+    let names: Vec<_> = std::fs::read_dir(backups)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names.len(), 1);
+    assert!(names[0].starts_with("post-checkout."), "{:?}", names);
+}
+
+#[test]
+fn default_hooks_are_backed_up_beside_themselves() {
+    if !has_git() {
+        return;
+    }
+    let sandbox = Sandbox::new();
+    let root = sandbox.root();
+    git(root, &["init", "-q"]);
+    sandbox.write(".git/hooks/post-commit", "#!/bin/sh\necho committed\n");
+
+    let output = sandbox.run(&["git-hooks", "install"]);
+    assert!(output.status.success(), "{:?}", output);
+    let hooks: Vec<_> = std::fs::read_dir(root.join(".git/hooks"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with("post-commit."))
+        .collect();
+    assert_eq!(hooks.len(), 1, "{:?}", hooks);
+    assert!(!root.join(".git/context-keeper").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn a_missing_binary_leaves_the_checkout_succeeding() {
+    use context_keeper::commands::git_hooks::{hook_snippet, insert_snippet};
+    use std::os::unix::fs::PermissionsExt;
+
+    if !has_git() {
+        return;
+    }
+    let sandbox = Sandbox::new();
+    let root = sandbox.root();
+    git(root, &["init", "-q"]);
+    common::git::commit(root, "a.c", "int a;\n", "init");
+    git(root, &["branch", "other"]);
+
+    // The hook install writes, for a binary that has since gone
+    let gone = sandbox.home().join("gone/context-keeper");
+    let hook = root.join(".git/hooks/post-checkout");
+    std::fs::create_dir_all(hook.parent().unwrap()).unwrap();
+    std::fs::write(
+        &hook,
+        insert_snippet("", &hook_snippet(gone.to_str().unwrap(), root)),
+    )
+    .unwrap();
+    std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let status = std::process::Command::new("sh")
+        .arg(&hook)
+        .current_dir(root)
+        .status()
+        .unwrap();
+    assert!(status.success(), "{:?}", status);
+    let output = std::process::Command::new("git")
+        .args(["checkout", "-q", "other"])
+        .current_dir(root)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+}
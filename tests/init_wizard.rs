@@ -0,0 +1,175 @@
+//! The init wizard's questions and answers, scripted through `WizardIo`
+
+use context_keeper::commands::init::{run_init_wizard_with, InitOptions, WizardIo};
+use context_keeper::commands::safe_write::WriteArgs;
+use context_keeper::config::Config;
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Answers given in order, then end of input; records what was asked and
+/// said
+#[derive(Default)]
+struct Scripted {
+    answers: VecDeque<&'static str>,
+    questions: Vec<String>,
+    said: Vec<String>,
+}
+
+impl Scripted {
+    fn new(answers: &[&'static str]) -> Self {
+        Scripted {
+            answers: answers.iter().copied().collect(),
+            ..Scripted::default()
+        }
+    }
+
+    fn said(&self, text: &str) -> bool {
+        self.said.iter().any(|line| line.contains(text))
+    }
+}
+
+impl WizardIo for Scripted {
+    fn ask(&mut self, question: &str) -> io::Result<Option<String>> {
+        self.questions.push(question.to_string());
+        Ok(self.answers.pop_front().map(|a| format!("{}\n", a)))
+    }
+
+    fn say(&mut self, line: &str) {
+        self.said.push(line.to_string());
+    }
+}
+
+fn run(root: &Path, opts: &InitOptions, wizard: &mut Scripted) -> io::Result<()> {
+    run_init_wizard_with(root, opts, &WriteArgs::default(), wizard)
+}
+
+fn written(root: &Path) -> Config {
+    toml::from_str(&fs::read_to_string(root.join("contextkeeper.toml")).unwrap()).unwrap()
+}
+
+#[test]
+fn every_question_answered_writes_the_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut wizard = Scripted::new(&["demo", "cargo", "none", "", "Run cargo test"]);
+    run(dir.path(), &InitOptions::default(), &mut wizard).unwrap();
+
+    assert!(wizard.questions[0].starts_with("Project name ["));
+    assert!(wizard.questions[1].starts_with("Project type ("));
+    assert_eq!(wizard.questions.len(), 5, "{:?}", wizard.questions);
+    let config = written(dir.path());
+    let project = config.project.unwrap();
+    assert_eq!(project.name.as_deref(), Some("demo"));
+    let text = fs::read_to_string(dir.path().join("contextkeeper.toml")).unwrap();
+    assert!(text.contains("type = \"cargo\""), "{}", text);
+    assert!(text.contains("default = \"Run cargo test\""), "{}", text);
+    assert!(!text.contains("[containers]"), "{}", text);
+    assert!(wizard.said("Created"));
+}
+
+#[test]
+fn blank_answers_take_the_defaults() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut wizard = Scripted::new(&["", "", "none", "", ""]);
+    run(dir.path(), &InitOptions::default(), &mut wizard).unwrap();
+    let config = written(dir.path());
+    let name = dir.path().file_name().unwrap().to_str().unwrap();
+    assert_eq!(config.project.unwrap().name.as_deref(), Some(name));
+    let text = fs::read_to_string(dir.path().join("contextkeeper.toml")).unwrap();
+    assert!(text.contains("type = \"custom\""), "{}", text);
+}
+
+#[test]
+fn unknown_project_type_is_asked_again() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut wizard = Scripted::new(&["demo", "cargo,rust", "node", "none", "", ""]);
+    run(dir.path(), &InitOptions::default(), &mut wizard).unwrap();
+    assert!(
+        wizard.said("Unknown project type rust; choose from: aosp, "),
+        "{:?}",
+        wizard.said
+    );
+    assert_eq!(wizard.questions[1], wizard.questions[2]);
+    let text = fs::read_to_string(dir.path().join("contextkeeper.toml")).unwrap();
+    assert!(text.contains("type = \"node\""), "{}", text);
+}
+
+#[test]
+fn input_ending_midway_writes_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    for answers in [&[][..], &["demo"], &["demo", "cargo", "none", ""]] {
+        let mut wizard = Scripted::new(answers);
+        let error = run(dir.path(), &InitOptions::default(), &mut wizard).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof, "{:?}", answers);
+        assert!(error.to_string().contains("nothing was written"));
+        assert!(!dir.path().join("contextkeeper.toml").exists());
+    }
+}
+
+#[test]
+fn existing_config_is_kept_unless_confirmed() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("contextkeeper.toml");
+    fs::write(&path, "[project]\nname = \"old\"\n").unwrap();
+
+    let mut wizard = Scripted::new(&["maybe", ""]);
+    run(dir.path(), &InitOptions::default(), &mut wizard).unwrap();
+    assert!(wizard.questions[0].contains("Overwrite? [y/N]"));
+    assert!(wizard.said("Please answer y or n."));
+    assert!(wizard.said("Aborted."));
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "[project]\nname = \"old\"\n"
+    );
+
+    let opts = InitOptions {
+        name: Some("new".to_string()),
+        project_type: Some("custom".to_string()),
+        runtime: Some("none".to_string()),
+        hint: Some(String::new()),
+        ..InitOptions::default()
+    };
+    let mut wizard = Scripted::new(&["yes", ""]);
+    run(dir.path(), &opts, &mut wizard).unwrap();
+    assert_eq!(
+        written(dir.path()).project.unwrap().name.as_deref(),
+        Some("new")
+    );
+    assert!(wizard.said("Replaced"));
+}
+
+#[test]
+fn build_candidates_by_number_with_a_preview() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("Makefile"),
+        "build:\n\techo build\nflash:\n\techo flash\n",
+    )
+    .unwrap();
+    let opts = InitOptions {
+        name: Some("demo".to_string()),
+        project_type: Some("custom".to_string()),
+        runtime: Some("none".to_string()),
+        hint: Some(String::new()),
+        ..InitOptions::default()
+    };
+    // Out of range, then declined after the preview, then taken
+    let mut wizard = Scripted::new(&["3", "1", "n", "", "y"]);
+    run(dir.path(), &opts, &mut wizard).unwrap();
+    assert!(wizard.said("  1. Makefile (Makefile)"), "{:?}", wizard.said);
+    assert!(wizard.said("Pick a number from 1 to 1."));
+    assert!(wizard.said("Commands found in Makefile:"));
+    let preview: Vec<&str> = wizard
+        .said
+        .iter()
+        .skip_while(|line| *line != "Commands found in Makefile:")
+        .skip(1)
+        .take(2)
+        .map(String::as_str)
+        .collect();
+    assert_eq!(preview, ["  make build", "  make flash"]);
+    assert_eq!(wizard.questions.len(), 5, "{:?}", wizard.questions);
+    let text = fs::read_to_string(dir.path().join("contextkeeper.toml")).unwrap();
+    assert!(text.contains("makefile = \"Makefile\""), "{}", text);
+}
@@ -0,0 +1,257 @@
+//! `[limits]`: each cap on an oversized input, the bound it keeps and the
+//! summary of what went over
+
+mod common;
+
+use common::git::{commit, git, has_git};
+use common::{stderr, stdout, Sandbox};
+use context_keeper::collectors::targets::glob_target_files;
+use context_keeper::collectors::working_files::{check_working_files, WorkingFileState};
+use context_keeper::collectors::COLLECTOR_STATS;
+use context_keeper::limits::{bounded_lines, format_count, overflow_summary, BoundedLine};
+use context_keeper::state::WorkState;
+use context_keeper::ProjectContext;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::Cursor;
+
+/// `collect` on a fresh thread, with the capped summaries it recorded
+fn capped<T: Send + 'static>(collect: impl FnOnce() -> T + Send + 'static) -> (T, Vec<String>) {
+    std::thread::spawn(move || {
+        let result = collect();
+        let (capped, warnings) = COLLECTOR_STATS.with(|stats| {
+            let stats = stats.borrow();
+            (stats.capped.clone(), stats.warnings.clone())
+        });
+        // Every summary is a warning too
+        for summary in &capped {
+            assert!(warnings.contains(summary), "{:?}", warnings);
+        }
+        (result, capped)
+    })
+    .join()
+    .unwrap()
+}
+
+#[test]
+fn summaries() {
+    for (n, text) in [
+        (0, "0"),
+        (7, "7"),
+        (999, "999"),
+        (1000, "1,000"),
+        (4820, "4,820"),
+        (80_000, "80,000"),
+        (1_234_567, "1,234,567"),
+    ] {
+        assert_eq!(format_count(n), text);
+    }
+    assert_eq!(
+        overflow_summary(
+            4820,
+            "untracked file",
+            "untracked files",
+            "max_status_lines"
+        ),
+        "…and 4,820 more untracked files (over [limits] max_status_lines)"
+    );
+    assert_eq!(
+        overflow_summary(1, "todo", "todos", "max_todos"),
+        "…and 1 more todo (over [limits] max_todos)"
+    );
+}
+
+#[test]
+fn long_lines_are_read_past_not_kept() {
+    let huge = "x".repeat(2 * 1024 * 1024);
+    let input = format!(
+        "first\r\n{}\nsecond\n{}\n\n{}last",
+        "y".repeat(16),
+        huge,
+        "z".repeat(17)
+    );
+    let lines: Vec<BoundedLine> = bounded_lines(Cursor::new(input), 16).collect();
+    assert_eq!(
+        lines,
+        [
+            BoundedLine::Line("first".to_string()),
+            // Exactly the cap is kept
+            BoundedLine::Line("y".repeat(16)),
+            BoundedLine::Line("second".to_string()),
+            BoundedLine::TooLong(2 * 1024 * 1024),
+            BoundedLine::Line(String::new()),
+            // The last line needs no newline
+            BoundedLine::TooLong(21),
+        ]
+    );
+
+    // Across reads of a small buffer too
+    let reader = std::io::BufReader::with_capacity(4, Cursor::new("abcdefghij\nab\n"));
+    let lines: Vec<BoundedLine> = bounded_lines(reader, 5).collect();
+    assert_eq!(
+        lines,
+        [
+            BoundedLine::TooLong(10),
+            BoundedLine::Line("ab".to_string())
+        ]
+    );
+    assert_eq!(bounded_lines(Cursor::new(""), 5).count(), 0);
+}
+
+#[test]
+fn config_files_per_pattern() {
+    let sandbox = Sandbox::new();
+    sandbox.write(
+        "contextkeeper.toml",
+        "[scripts]\nconfig_dir = [\"configs\"]\n\n[limits]\nmax_config_files = 10\n",
+    );
+    for i in 0..250 {
+        sandbox.write(&format!("configs/board{:03}.conf", i), "TARGET_NAME=x\n");
+    }
+    let project = ProjectContext::load(sandbox.root().to_path_buf(), None);
+    let (globbed, summaries) = capped(move || glob_target_files(&project));
+    let files: Vec<usize> = globbed
+        .iter()
+        .map(|(_, files)| files.as_ref().unwrap().len())
+        .collect();
+    assert_eq!(files, [10]);
+    let pattern = &globbed[0].0;
+    assert_eq!(
+        summaries,
+        [format!(
+            "targets: {}: …and 240 more files not read (over [limits] max_config_files)",
+            pattern
+        )]
+    );
+}
+
+#[cfg(feature = "history")]
+#[test]
+fn history_lines_over_the_byte_cap() {
+    use context_keeper::collectors::history::collect_command_history;
+    use context_keeper::config::Config;
+
+    let sandbox = Sandbox::new();
+    let log = sandbox.home().join("history.jsonl");
+    let entry = |command: &str| json!({"timestamp": "2026-03-02T12:00:00Z", "command": command});
+    let mut text = String::new();
+    for command in ["make one", "make two"] {
+        text.push_str(&format!("{}\n", entry(command)));
+    }
+    // 2 MB lines, and one just over 1 KiB
+    for _ in 0..3 {
+        text.push_str(&format!(
+            "{}\n",
+            entry(&format!("make {}", "x".repeat(2 << 20)))
+        ));
+    }
+    text.push_str(&format!(
+        "{}\n",
+        entry(&format!("make {}", "y".repeat(1024)))
+    ));
+    text.push_str(&format!("{}\n", entry("make three")));
+    fs::write(&log, text).unwrap();
+
+    let config: Config = toml::from_str(&format!(
+        "[history]\nenabled = true\nlog_file = {:?}\npatterns = [\"make\"]\n\n[limits]\nmax_line_bytes = 1024\n",
+        log.to_str().unwrap()
+    ))
+    .unwrap();
+    let (entries, summaries) = capped(move || collect_command_history(&config).unwrap());
+    let commands: Vec<&str> = entries.iter().map(|e| e.command.as_str()).collect();
+    assert_eq!(commands, ["make one", "make two", "make three"]);
+    assert_eq!(
+        summaries,
+        ["history: …and 4 more log lines skipped as too long (over [limits] max_line_bytes)"]
+    );
+}
+
+#[test]
+fn changed_paths_over_the_status_cap() {
+    if !has_git() {
+        return;
+    }
+    let sandbox = Sandbox::new();
+    sandbox.write("contextkeeper.toml", "[limits]\nmax_status_lines = 10\n");
+    let repo = sandbox.root().join("app");
+    fs::create_dir(&repo).unwrap();
+    git(&repo, &["init", "-q"]);
+    for i in 0..40 {
+        fs::write(repo.join(format!("f{:02}.c", i)), "int x;\n").unwrap();
+    }
+    commit(&repo, "README", "app\n", "init");
+    for i in 0..40 {
+        fs::write(repo.join(format!("f{:02}.c", i)), "int y;\n").unwrap();
+    }
+
+    // The save keeps at most the cap of working files
+    let output = sandbox.run(&["save-state", "many files"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let state: WorkState = serde_json::from_value(sandbox.work_state().unwrap()).unwrap();
+    assert_eq!(state.working_files.len(), 10, "{:?}", state.working_files);
+
+    // Past the cap, a saved file git didn't list still counts as changed
+    let project = ProjectContext::load(sandbox.root().to_path_buf(), None);
+    let (checks, summaries) = capped(move || check_working_files(&project, &state));
+    assert_eq!(checks.len(), 10);
+    assert!(
+        checks.iter().all(|c| c.state == WorkingFileState::Modified),
+        "{:?}",
+        checks
+    );
+    assert_eq!(
+        summaries,
+        ["working_files: app: …and 30 more changed paths (over [limits] max_status_lines)"]
+    );
+}
+
+#[cfg(feature = "stdio-server")]
+#[test]
+fn todos_over_the_cap() {
+    use common::mcp::McpClient;
+
+    let sandbox = Sandbox::new();
+    sandbox.write("contextkeeper.toml", "[limits]\nmax_todos = 25\n");
+    let todos: Vec<Value> = (0..10_000)
+        .map(|i| json!({"content": format!("item {}", i), "status": "pending"}))
+        .collect();
+    let mut client = McpClient::start(&sandbox, &[]);
+    let reply = client.call_text(
+        "save_work_state",
+        json!({"task_summary": "pasted list", "todos": Value::from(todos).to_string()}),
+    );
+    client.close();
+    assert!(
+        reply
+            .ends_with("\n\nNote: …and 9,975 more todos were not saved (over [limits] max_todos)."),
+        "{}",
+        reply
+    );
+    let saved = sandbox.work_state().unwrap();
+    assert_eq!(saved["todos"].as_array().unwrap().len(), 25);
+
+    // A state saved under a higher cap is cut when it is loaded
+    sandbox.write("contextkeeper.toml", "[limits]\nmax_todos = 5\n");
+    let output = sandbox.run(&["context", "--level", "full", "--json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let value: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(value["work_state"]["todos"].as_array().unwrap().len(), 5);
+    let report = value["reports"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|r| r["name"] == "work_state")
+        .unwrap();
+    assert_eq!(
+        report["capped"],
+        json!(["work_state: …and 20 more todos (over [limits] max_todos)"])
+    );
+
+    let output = sandbox.run(&["context", "--level", "full"]);
+    assert!(
+        stdout(&output)
+            .contains("> **Capped:** work_state: …and 20 more todos (over [limits] max_todos)\n"),
+        "{}",
+        stdout(&output)
+    );
+}
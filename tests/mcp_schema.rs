@@ -0,0 +1,138 @@
+//! The get_dev_context schema advertised over tools/list against what the
+//! formatter accepts
+#![cfg(feature = "stdio-server")]
+
+mod common;
+
+use common::mcp::McpClient;
+use common::Sandbox;
+use context_keeper::demo::demo_context;
+use context_keeper::format::{format_context, Level, OutputFormat, Section};
+use serde_json::{json, Value};
+
+/// The get_dev_context entry of tools/list
+fn advertised(client: &mut McpClient) -> Value {
+    let tools = client.request("tools/list", json!({}));
+    tools["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|tool| tool["name"] == "get_dev_context")
+        .cloned()
+        .expect("get_dev_context is listed")
+}
+
+fn names(values: &Value) -> Vec<String> {
+    values
+        .as_array()
+        .unwrap_or_else(|| panic!("not an enum: {}", values))
+        .iter()
+        .map(|v| v.as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn schema_enums_match_the_formatter() {
+    let sandbox = Sandbox::new();
+    let mut client = McpClient::start(&sandbox, &[]);
+    let tool = advertised(&mut client);
+    let properties = &tool["inputSchema"]["properties"];
+
+    let levels = names(&properties["level"]["enum"]);
+    assert_eq!(levels, Level::ALL.map(|l| l.name().to_string()));
+    let formats = names(&properties["format"]["enum"]);
+    assert_eq!(formats, OutputFormat::ALL.map(|f| f.name().to_string()));
+    let sections = names(&properties["sections"]["items"]["enum"]);
+    assert_eq!(sections, Section::ALL.map(|s| s.name().to_string()));
+
+    // Each advertised name reaches its own arm of the dispatcher rather
+    // than a fallback
+    let demo = demo_context(None).unwrap();
+    let mut rendered = Vec::new();
+    for name in &levels {
+        let level = Level::parse(name);
+        assert_eq!(level.name(), name);
+        let text = format_context(&demo, level);
+        assert!(
+            !rendered.contains(&text),
+            "{} renders like another level",
+            name
+        );
+        rendered.push(text);
+    }
+    for name in &formats {
+        assert_eq!(
+            OutputFormat::parse(name).map(OutputFormat::name),
+            Some(&**name)
+        );
+    }
+    for name in &sections {
+        assert_eq!(Section::parse(name).map(|s| s.name()), Some(&**name));
+    }
+
+    let description = tool["description"].as_str().unwrap();
+    for name in levels.iter().chain(&formats) {
+        assert!(
+            description.contains(&format!("'{}'", name)),
+            "{}",
+            description
+        );
+    }
+    client.close();
+}
+
+#[test]
+fn every_advertised_value_is_accepted() {
+    let sandbox = Sandbox::new();
+    let mut client = McpClient::start(&sandbox, &[]);
+    let tool = advertised(&mut client);
+    let properties = &tool["inputSchema"]["properties"];
+
+    for level in names(&properties["level"]["enum"]) {
+        for format in names(&properties["format"]["enum"]) {
+            let text = client.call_text(
+                "get_dev_context",
+                json!({ "level": level, "format": format }),
+            );
+            if format == "json" {
+                let value: Value = serde_json::from_str(&text)
+                    .unwrap_or_else(|e| panic!("{} {}: {}: {}", level, format, e, text));
+                assert!(value["availability"].is_object(), "{}", text);
+            } else if level == "oneline" {
+                assert_eq!(text.lines().count(), 1, "{:?}", text);
+            }
+        }
+    }
+    client.close();
+}
+
+#[test]
+fn unknown_format_is_invalid_params() {
+    let sandbox = Sandbox::new();
+    let mut client = McpClient::start(&sandbox, &[]);
+    let response = client.call("get_dev_context", json!({ "format": "yaml" }));
+    assert_eq!(response["error"]["code"], -32602, "{}", response);
+    assert!(
+        response["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("valid formats: markdown, json"),
+        "{}",
+        response
+    );
+    client.close();
+}
+
+#[test]
+fn json_format_holds_the_sections_asked_for() {
+    let sandbox = Sandbox::new();
+    let mut client = McpClient::start(&sandbox, &[]);
+    let text = client.call_text(
+        "get_dev_context",
+        json!({ "sections": ["project", "git"], "format": "json" }),
+    );
+    let value: Value = serde_json::from_str(&text).unwrap();
+    let keys: Vec<&String> = value.as_object().unwrap().keys().collect();
+    assert_eq!(keys, ["project", "git", "availability"]);
+    client.close();
+}
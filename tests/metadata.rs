@@ -0,0 +1,259 @@
+//! Work-state metadata: per-key updates and their checks, and the map's
+//! round trip through save, load, `--json` export and `--demo-file` import
+
+mod common;
+
+use context_keeper::error::ContextKeeperError;
+use context_keeper::format::format_work_state;
+use context_keeper::i18n::Language;
+use context_keeper::state::{
+    apply_metadata_updates, WorkState, MAX_METADATA_KEYS, MAX_METADATA_VALUE_BYTES,
+};
+use std::collections::BTreeMap;
+
+fn map(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+fn updates(pairs: &[(&str, Option<&str>)]) -> BTreeMap<String, Option<String>> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.map(str::to_string)))
+        .collect()
+}
+
+/// `metadata` after `changes`, redacting nothing
+fn apply(
+    metadata: &mut BTreeMap<String, String>,
+    changes: &[(&str, Option<&str>)],
+) -> Result<(), String> {
+    apply_metadata_updates(metadata, updates(changes), str::to_string).map_err(|e| match e {
+        ContextKeeperError::Validation(message) => message,
+        other => panic!("{}", other),
+    })
+}
+
+#[test]
+fn set_remove_and_keep() {
+    let mut metadata = map(&[("gerrit_change", "I8f3c2a"), ("jira", "ABC-123")]);
+    apply(
+        &mut metadata,
+        &[
+            ("jira", None),
+            ("emulator.snapshot", Some("  boot-ok \n")),
+            ("missing", None),
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        metadata,
+        map(&[
+            ("emulator.snapshot", "boot-ok"),
+            ("gerrit_change", "I8f3c2a")
+        ])
+    );
+    apply(&mut metadata, &[("gerrit_change", Some("I9000"))]).unwrap();
+    assert_eq!(metadata["gerrit_change"], "I9000");
+    apply(&mut metadata, &[]).unwrap();
+    assert_eq!(metadata.len(), 2);
+}
+
+#[test]
+fn keys_are_checked() {
+    let long = "k".repeat(65);
+    for (key, error) in [
+        ("", "metadata keys can't be empty"),
+        (long.as_str(), "is longer than 64 characters"),
+        (
+            "my key",
+            "metadata key \"my key\" has ' '; use letters, digits, '.', '_', '-' and ':'",
+        ),
+        ("ticket/id", "has '/'"),
+        (
+            "ck:schema",
+            "metadata key \"ck:schema\": the \"ck:\" prefix is reserved",
+        ),
+    ] {
+        let mut metadata = map(&[("jira", "ABC-123")]);
+        let message = apply(&mut metadata, &[("a", Some("1")), (key, Some("x"))]).unwrap_err();
+        assert!(message.contains(error), "{:?}: {}", key, message);
+        // Nothing applied, not even the valid key
+        assert_eq!(metadata, map(&[("jira", "ABC-123")]));
+    }
+    // The reserved keys can't be removed either
+    let mut metadata = map(&[("ck:schema", "2")]);
+    assert!(apply(&mut metadata, &[("ck:schema", None)]).is_err());
+    assert_eq!(metadata.len(), 1);
+
+    let mut metadata = BTreeMap::new();
+    let key = "k".repeat(64);
+    apply(
+        &mut metadata,
+        &[(&key, Some("x")), ("team:a.b_c-D9", Some("y"))],
+    )
+    .unwrap();
+    assert_eq!(metadata.len(), 2);
+}
+
+#[test]
+fn values_and_keys_are_capped() {
+    let mut metadata = BTreeMap::new();
+    let fits = "v".repeat(MAX_METADATA_VALUE_BYTES);
+    apply(&mut metadata, &[("fits", Some(&fits))]).unwrap();
+    let over = "é".repeat(MAX_METADATA_VALUE_BYTES / 2 + 1);
+    assert_eq!(
+        apply(&mut metadata, &[("over", Some(&over))]).unwrap_err(),
+        "metadata value for \"over\" is 1026 bytes; at most 1024 are kept"
+    );
+
+    let mut metadata: BTreeMap<String, String> = (1..MAX_METADATA_KEYS)
+        .map(|i| (format!("key{}", i), "x".to_string()))
+        .collect();
+    apply(&mut metadata, &[("last", Some("x"))]).unwrap();
+    assert_eq!(metadata.len(), MAX_METADATA_KEYS);
+    assert_eq!(
+        apply(&mut metadata, &[("one_more", Some("x"))]).unwrap_err(),
+        "33 metadata keys; at most 32 can be kept"
+    );
+    // Removing one makes room in the same update
+    apply(&mut metadata, &[("last", None), ("one_more", Some("x"))]).unwrap();
+    assert!(metadata.contains_key("one_more"));
+}
+
+#[test]
+fn values_are_redacted_before_they_are_kept() {
+    let mut metadata = BTreeMap::new();
+    apply_metadata_updates(
+        &mut metadata,
+        updates(&[("token", Some("ghp_secret")), ("jira", Some("ABC-123"))]),
+        |value| value.replace("ghp_secret", "[REDACTED]"),
+    )
+    .unwrap();
+    assert_eq!(
+        metadata,
+        map(&[("jira", "ABC-123"), ("token", "[REDACTED]")])
+    );
+}
+
+#[test]
+fn state_files_round_trip() {
+    let state = WorkState {
+        task_summary: "audio".to_string(),
+        metadata: map(&[("jira", "ABC-123"), ("ck:schema", "2")]),
+        ..WorkState::default()
+    };
+    let json = serde_json::to_value(&state).unwrap();
+    assert_eq!(
+        json["metadata"],
+        serde_json::json!({"ck:schema": "2", "jira": "ABC-123"})
+    );
+    let loaded: WorkState = serde_json::from_value(json).unwrap();
+    assert_eq!(loaded.metadata, state.metadata);
+
+    // Left out when empty, and empty in files from before it
+    let empty = serde_json::to_value(WorkState::default()).unwrap();
+    assert!(empty.get("metadata").is_none(), "{}", empty);
+    let old: WorkState = serde_json::from_str(
+        r#"{"version": 4, "saved_at": "2026-03-02T12:00:00Z", "trigger": "manual",
+            "task_summary": "old", "working_files": [], "notes": "", "todos": []}"#,
+    )
+    .unwrap();
+    assert!(old.metadata.is_empty());
+}
+
+#[test]
+fn shown_without_reserved_keys() {
+    let mut state = WorkState {
+        task_summary: "audio".to_string(),
+        metadata: map(&[("jira", "ABC-123"), ("ck:schema", "2"), ("gerrit", "I8f3")]),
+        ..WorkState::default()
+    };
+    let text = format_work_state(&state, &[], &[], Language::En);
+    assert!(
+        text.contains("- **Task:** audio\n- **Metadata:** gerrit=I8f3, jira=ABC-123\n"),
+        "{}",
+        text
+    );
+    state.metadata = map(&[("ck:schema", "2")]);
+    let text = format_work_state(&state, &[], &[], Language::En);
+    assert!(!text.contains("Metadata"), "{}", text);
+}
+
+#[cfg(feature = "stdio-server")]
+#[test]
+fn through_save_load_export_and_import() {
+    use common::mcp::McpClient;
+    use common::{stderr, stdout, Sandbox};
+    use serde_json::{json, Value};
+
+    let sandbox = Sandbox::new();
+    sandbox.write(
+        "contextkeeper.toml",
+        "[output]\nredact_patterns = [\"hunter[0-9]+\"]\n",
+    );
+    let mut client = McpClient::start(&sandbox, &[]);
+    let reply = client.call_text(
+        "save_work_state",
+        json!({
+            "task_summary": "audio",
+            "metadata": {"gerrit_change": "I8f3c2a", "jira": "ABC-123", "password": "hunter22"},
+        }),
+    );
+    assert!(
+        reply.contains("- Metadata: gerrit_change, jira, password\n"),
+        "{}",
+        reply
+    );
+    client.call_text(
+        "save_work_state",
+        json!({"task_summary": "audio", "metadata": {"jira": null, "emulator": "snap1"}}),
+    );
+    // A rejected update saves nothing
+    let response = client.call(
+        "save_work_state",
+        json!({"task_summary": "lost", "metadata": {"ck:schema": "9"}}),
+    );
+    assert_eq!(response["error"]["code"], -32602, "{}", response);
+    client.close();
+
+    let expected = json!({
+        "emulator": "snap1",
+        "gerrit_change": "I8f3c2a",
+        "password": "[REDACTED]",
+    });
+    let saved = sandbox.work_state().unwrap();
+    assert_eq!(saved["metadata"], expected);
+    assert_eq!(saved["task_summary"], "audio");
+
+    // The PreCompact hook's save keeps it
+    let output = sandbox.run(&["save-state", "compacting"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(sandbox.work_state().unwrap()["metadata"], expected);
+
+    // Export, then import the export
+    let output = sandbox.run(&["context", "--json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let exported: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(exported["work_state"]["metadata"], expected);
+    let file = sandbox.write("export.json", &stdout(&output));
+    let from_file = |args: &[&str]| {
+        let mut all = vec!["context", "--demo", "--demo-file", file.to_str().unwrap()];
+        all.extend(args);
+        let output = sandbox.run(&all);
+        assert!(output.status.success(), "{}", stderr(&output));
+        stdout(&output)
+    };
+    let imported: Value = serde_json::from_str(&from_file(&["--json"])).unwrap();
+    assert_eq!(imported["work_state"]["metadata"], expected);
+    let text = from_file(&["--level", "normal"]);
+    assert!(
+        text.contains(
+            "- **Metadata:** emulator=snap1, gerrit_change=I8f3c2a, password=[REDACTED]\n"
+        ),
+        "{}",
+        text
+    );
+}
@@ -0,0 +1,97 @@
+//! File and directory names that aren't UTF-8 (latin-1 on an old NAS
+//! share): nothing vanishes, and lossy names carry the marker
+#![cfg(unix)]
+
+mod common;
+
+use common::git::{git, has_git};
+use common::{stderr, stdout, Sandbox};
+use context_keeper::paths::{display_path, path_bytes, path_from_bytes, NON_UTF8_MARKER};
+use serde_json::Value;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// `r\xe9po/caf\xe9.txt` and `r\xe9po/plain.txt`, committed and then
+/// modified; `None` where the filesystem refuses such names
+fn latin1_fixture(sandbox: &Sandbox) -> Option<PathBuf> {
+    sandbox.write("contextkeeper.toml", "[project]\nname = \"latin1\"\n");
+    let repo = sandbox.root().join(OsStr::from_bytes(b"r\xe9po"));
+    std::fs::create_dir(&repo).ok()?;
+    let cafe = repo.join(OsStr::from_bytes(b"caf\xe9.txt"));
+    std::fs::write(&cafe, "1\n").ok()?;
+    std::fs::write(repo.join("plain.txt"), "1\n").unwrap();
+    git(&repo, &["init", "-q"]);
+    git(&repo, &["add", "-A"]);
+    git(&repo, &["commit", "-qm", "latin-1"]);
+    std::fs::write(&cafe, "2\n").unwrap();
+    std::fs::write(repo.join("plain.txt"), "2\n").unwrap();
+    Some(repo)
+}
+
+#[test]
+fn display_marks_lossy_names_and_bytes_round_trip() {
+    let path = Path::new(OsStr::from_bytes(b"dir/caf\xe9.txt"));
+    assert_eq!(
+        display_path(path),
+        format!("dir/caf\u{fffd}.txt{}", NON_UTF8_MARKER)
+    );
+    assert_eq!(display_path(Path::new("dir/café.txt")), "dir/café.txt");
+    assert_eq!(path_bytes(path), b"dir/caf\xe9.txt");
+    assert_eq!(path_from_bytes(&path_bytes(path)), path);
+}
+
+#[test]
+fn repo_in_a_non_utf8_directory_is_listed() {
+    if !has_git() {
+        return;
+    }
+    let sandbox = Sandbox::new();
+    if latin1_fixture(&sandbox).is_none() {
+        return;
+    }
+    let output = sandbox.run(&["repos", "--json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let repos: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let repos = repos.as_array().unwrap();
+    assert_eq!(repos.len(), 1, "{:?}", repos);
+    assert_eq!(
+        repos[0]["path"],
+        format!("r\u{fffd}po{}", NON_UTF8_MARKER).as_str()
+    );
+    assert_eq!(repos[0]["status"]["modified_files"], 2);
+}
+
+#[test]
+fn changed_non_utf8_files_are_saved_and_not_called_missing() {
+    if !has_git() {
+        return;
+    }
+    let sandbox = Sandbox::new();
+    if latin1_fixture(&sandbox).is_none() {
+        return;
+    }
+    let output = sandbox.run(&["save-state", "latin-1"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let state = sandbox.work_state().unwrap();
+    let mut files: Vec<&str> = state["working_files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| f.as_str().unwrap())
+        .collect();
+    files.sort();
+    assert_eq!(
+        files,
+        [
+            format!("r\u{fffd}po/caf\u{fffd}.txt{}", NON_UTF8_MARKER),
+            format!("r\u{fffd}po/plain.txt{}", NON_UTF8_MARKER),
+        ]
+    );
+
+    let output = sandbox.run(&["context", "--level", "full"]);
+    let text = stdout(&output);
+    assert!(text.contains("| 2M |"), "{}", text);
+    assert!(!text.contains("(missing)"), "{}", text);
+    assert!(!text.contains("no longer exist"), "{}", text);
+}
@@ -0,0 +1,139 @@
+//! Running from a directory other than the project: config_dir resolves
+//! against the config file, never the process cwd
+
+mod common;
+
+use common::{stderr, stdout, Sandbox};
+use serde_json::Value;
+use std::path::Path;
+
+/// `targets --json` with `args`, run from `cwd`
+fn targets_from(sandbox: &Sandbox, cwd: &Path, args: &[&str]) -> Value {
+    let mut full = args.to_vec();
+    full.extend(["targets", "--json"]);
+    let output = sandbox.command(&full).current_dir(cwd).output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    serde_json::from_str(&stdout(&output)).unwrap()
+}
+
+fn names(targets: &Value) -> Vec<&str> {
+    let mut names: Vec<&str> = targets["targets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap())
+        .collect();
+    names.sort();
+    names
+}
+
+/// `path` with '/' separators, as patterns show it
+fn slashed(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+#[test]
+fn cwd_flag_from_elsewhere_finds_the_project_targets() {
+    let sandbox = Sandbox::new();
+    sandbox.write(
+        "contextkeeper.toml",
+        "[scripts]\nconfig_dir = [\"configs/boards/\", \"configs\\\\variants\"]\n",
+    );
+    sandbox.write("configs/boards/rpi4.conf", "TARGET_NAME=rpi4\n");
+    sandbox.write("configs/variants/imx8.conf", "TARGET_NAME=imx8\n");
+    let root = sandbox.root().to_str().unwrap();
+
+    // From HOME, and from a subdirectory of the project itself
+    let subdir = sandbox
+        .write("configs/boards/notes.txt", "")
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    for cwd in [sandbox.home(), &subdir] {
+        let value = targets_from(&sandbox, cwd, &["--cwd", root]);
+        assert_eq!(names(&value), ["imx8", "rpi4"], "from {}", cwd.display());
+        let patterns: Vec<&str> = value["patterns"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["pattern"].as_str().unwrap())
+            .collect();
+        let root = slashed(sandbox.root());
+        assert_eq!(
+            patterns,
+            [
+                format!("{}/configs/boards/*.conf", root),
+                format!("{}/configs/variants/*.conf", root),
+            ]
+        );
+    }
+}
+
+#[test]
+fn config_flag_resolves_next_to_the_config() {
+    let sandbox = Sandbox::new();
+    let elsewhere = tempfile::tempdir().unwrap();
+    let team = elsewhere.path().join("team.toml");
+    std::fs::write(&team, "[scripts]\nconfig_dir = \"boards\"\n").unwrap();
+    std::fs::create_dir(elsewhere.path().join("boards")).unwrap();
+    std::fs::write(
+        elsewhere.path().join("boards/shared.conf"),
+        "TARGET_NAME=shared\n",
+    )
+    .unwrap();
+    // The same relative directory in the project is not the one meant
+    sandbox.write("boards/local.conf", "TARGET_NAME=local\n");
+
+    let value = targets_from(
+        &sandbox,
+        sandbox.home(),
+        &[
+            "--cwd",
+            sandbox.root().to_str().unwrap(),
+            "--config",
+            team.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(names(&value), ["shared"]);
+    assert_eq!(
+        value["patterns"][0]["pattern"],
+        format!("{}/boards/*.conf", slashed(elsewhere.path())).as_str()
+    );
+}
+
+#[test]
+fn empty_match_warning_names_the_resolved_pattern() {
+    let sandbox = Sandbox::new();
+    sandbox.write(
+        "contextkeeper.toml",
+        "[scripts]\nconfig_dir = \"configs\"\nconfig_pattern = \"*.cfg\"\n",
+    );
+    sandbox.write("configs/rpi4.conf", "TARGET_NAME=rpi4\n");
+    let value = targets_from(
+        &sandbox,
+        sandbox.home(),
+        &["--cwd", sandbox.root().to_str().unwrap()],
+    );
+    assert!(names(&value).is_empty());
+    assert_eq!(value["patterns"][0]["matched_files"], 0);
+    let expected = format!("{}/configs/*.cfg matched 0 files", slashed(sandbox.root()));
+    let warnings: Vec<&str> = value["warnings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|w| w.as_str().unwrap())
+        .collect();
+    assert!(
+        warnings.iter().any(|w| w.ends_with(&expected)),
+        "{:?}",
+        warnings
+    );
+
+    // The text listing says the same
+    let output = sandbox
+        .command(&["--cwd", sandbox.root().to_str().unwrap(), "targets"])
+        .current_dir(sandbox.home())
+        .output()
+        .unwrap();
+    assert!(stdout(&output).contains(&format!("{}/configs/*.cfg", slashed(sandbox.root()))));
+}
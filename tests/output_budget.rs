@@ -0,0 +1,122 @@
+//! `max_output_bytes`: the level each cap falls to, and that nothing goes
+//! over it
+
+mod common;
+
+use common::Sandbox;
+use context_keeper::demo::demo_context;
+use context_keeper::format::budget::format_within_budget;
+use context_keeper::format::{context_parts, format_context, Level};
+use context_keeper::Context;
+
+fn within(ctx: &Context, level: Level, max: usize) -> String {
+    format_within_budget(ctx, None, level, max, str::to_string)
+}
+
+/// The size at which `level` is shown, notice included, when `requested`
+/// is over the cap
+fn fallback_size(ctx: &Context, requested: Level, level: Level) -> usize {
+    let mut max = format_context(ctx, level).len();
+    while !within(ctx, requested, max).contains(&format!("this is level=\"{}\"", level.name())) {
+        max += 1;
+    }
+    max
+}
+
+#[test]
+fn never_over_the_cap() {
+    let ctx = demo_context(None).unwrap();
+    let full = format_context(&ctx, Level::Full).len();
+    for level in Level::ALL {
+        for max in (0..=full + 1).step_by(7).chain([1, 2, full - 1, full]) {
+            let text = within(&ctx, level, max);
+            assert!(
+                text.len() <= max,
+                "{} at {}: {} bytes",
+                level.name(),
+                max,
+                text.len()
+            );
+        }
+    }
+}
+
+#[test]
+fn each_level_at_its_threshold() {
+    let ctx = demo_context(None).unwrap();
+    let full = format_context(&ctx, Level::Full);
+
+    // Exactly the full size is shown as is, a byte less falls a level
+    assert_eq!(within(&ctx, Level::Full, full.len()), full);
+    let below = within(&ctx, Level::Full, full.len() - 1);
+    assert!(below.contains("this is level=\"normal\""), "{}", below);
+
+    let normal = fallback_size(&ctx, Level::Full, Level::Normal);
+    assert!(within(&ctx, Level::Full, normal).ends_with(&format_context(&ctx, Level::Normal)));
+    let minimal = within(&ctx, Level::Full, normal - 1);
+    assert!(minimal.contains("this is level=\"minimal\""), "{}", minimal);
+    assert!(minimal.ends_with(&format_context(&ctx, Level::Minimal)));
+
+    let lowest = fallback_size(&ctx, Level::Full, Level::Minimal);
+    let cut = within(&ctx, Level::Full, lowest - 1);
+    assert!(
+        cut.contains(", and so is level=\"minimal\"; cut after the "),
+        "{}",
+        cut
+    );
+}
+
+#[test]
+fn minimal_is_cut_between_parts() {
+    let ctx = demo_context(None).unwrap();
+    let parts = context_parts(&ctx, Level::Minimal);
+    let minimal = format_context(&ctx, Level::Minimal);
+    for max in (0..minimal.len()).step_by(11) {
+        let text = within(&ctx, Level::Minimal, max);
+        assert!(text.len() <= max, "{} bytes at {}", text.len(), max);
+        // The notice alone when no part fits
+        let Some((_, body)) = text
+            .split_once(".\n\n")
+            .filter(|(_, body)| !body.is_empty())
+        else {
+            continue;
+        };
+        let mut joined = String::new();
+        let whole = parts.iter().any(|part| {
+            joined.push_str(&part.text);
+            joined == body
+        });
+        assert!(whole, "cut inside a part at {}: {:?}", max, body);
+    }
+}
+
+#[test]
+fn a_cap_below_the_notice_shortens_it() {
+    let ctx = demo_context(None).unwrap();
+    let text = within(&ctx, Level::Full, 50);
+    assert_eq!(text, "> **Output capped:** over max_output_bytes=50.\n");
+    let text = within(&ctx, Level::Full, 20);
+    assert_eq!(text, "> **Output capped:**");
+}
+
+#[test]
+fn cli_output_stays_within_the_cap() {
+    let sandbox = Sandbox::new();
+    for max in ["50", "300", "1000"] {
+        let output = sandbox.run(&[
+            "context",
+            "--demo",
+            "--level",
+            "full",
+            "--max-output-bytes",
+            max,
+        ]);
+        assert!(output.status.success());
+        assert!(
+            output.stdout.len() <= max.parse().unwrap(),
+            "{}: {} bytes",
+            max,
+            output.stdout.len()
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! The parsers behind the collectors, on hand-written input
+
+use context_keeper::collectors::forge::{parse_gh_prs, parse_glab_mrs};
+use context_keeper::collectors::gerrit::parse_changes;
+use context_keeper::collectors::just::parse_justfile;
+use context_keeper::collectors::kconfig::parse_kconfig;
+use context_keeper::collectors::west::{parse_west_config, parse_west_config_list};
+use context_keeper::collectors::yocto::parse_bitbake_conf;
+
+#[test]
+fn kconfig_values_and_unset_symbols() {
+    let values = parse_kconfig(
+        "#\n# Automatically generated file; DO NOT EDIT.\n#\nCONFIG_ARM64=y\n\
+         CONFIG_LOCALVERSION=\"-rt\"\n# CONFIG_DEBUG_INFO is not set\n#CONFIG_X=y\n",
+    );
+    assert_eq!(values["CONFIG_ARM64"], "y");
+    assert_eq!(values["CONFIG_LOCALVERSION"], "-rt");
+    assert_eq!(values["CONFIG_DEBUG_INFO"], "n");
+    assert!(!values.contains_key("#CONFIG_X"));
+    assert_eq!(values.len(), 3);
+}
+
+#[test]
+fn bitbake_operators_in_precedence_order() {
+    let values = parse_bitbake_conf(
+        r#"
+MACHINE ??= "qemux86-64"
+MACHINE ?= "raspberrypi4"
+DISTRO ?= "poky"
+DISTRO = "mydistro"
+IMAGE_INSTALL += "vim"
+IMAGE_INSTALL += "htop"
+TMPDIR := "/build/tmp"
+export SSTATE_DIR = "/cache/sstate"
+PREFERRED_PROVIDER_virtual/kernel = "linux-yocto"
+MACHINE:pn-foo = "ignored"
+DATE = "${@time.strftime('%Y')}"
+EXTRA = "one \
+two"
+"#,
+    );
+    assert_eq!(values["MACHINE"], "raspberrypi4");
+    assert_eq!(values["DISTRO"], "mydistro");
+    assert_eq!(values["IMAGE_INSTALL"], "vim htop");
+    assert_eq!(values["TMPDIR"], "/build/tmp");
+    assert_eq!(values["SSTATE_DIR"], "/cache/sstate");
+    assert_eq!(values["EXTRA"], "one  two");
+    assert!(!values.contains_key("DATE"));
+    assert!(!values.keys().any(|k| k.contains('/') || k.contains(':')));
+}
+
+#[test]
+fn bitbake_weak_default_only_when_nothing_else_sets_it() {
+    let values = parse_bitbake_conf(
+        "BB_NUMBER_THREADS ??= \"4\"\nPARALLEL_MAKE ??= \"-j 4\"\nPARALLEL_MAKE = \"-j 16\"\n",
+    );
+    assert_eq!(values["BB_NUMBER_THREADS"], "4");
+    assert_eq!(values["PARALLEL_MAKE"], "-j 16");
+}
+
+#[test]
+fn west_config_sections_and_list() {
+    let values = parse_west_config(
+        "[manifest]\npath = zephyr\nfile = west.yml\n\n; comment\n[build]\nboard = nrf52840dk_nrf52840\n",
+    );
+    assert_eq!(values["manifest.path"], "zephyr");
+    assert_eq!(values["manifest.file"], "west.yml");
+    assert_eq!(values["build.board"], "nrf52840dk_nrf52840");
+
+    let listed =
+        parse_west_config_list("manifest.path=zephyr\nbuild.board = qemu_x86\nnot a value\n");
+    assert_eq!(listed["build.board"], "qemu_x86");
+    assert_eq!(listed.len(), 2);
+}
+
+#[test]
+fn justfile_public_recipes_with_docs() {
+    let recipes = parse_justfile(
+        r#"set shell := ["bash", "-c"]
+alias b := build
+version := "1.0"
+
+# Build the image
+build target mode='debug' *args:
+    make {{target}}
+
+_helper:
+    echo hidden
+
+[private]
+secret:
+    echo hidden
+
+@test:
+    cargo test
+"#,
+    );
+    let names: Vec<_> = recipes
+        .iter()
+        .map(|r| {
+            (
+                r.name.as_str(),
+                r.parameters.as_str(),
+                r.description.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        names,
+        [
+            ("build", "target mode='debug' *args", "Build the image"),
+            ("test", "", "")
+        ]
+    );
+}
+
+#[test]
+fn gerrit_changes_behind_the_xssi_guard() {
+    let body = r#")]}'
+[{"_number": 4211, "subject": "audio: fix underrun", "project": "platform/frameworks/av",
+  "branch": "main",
+  "labels": {"Code-Review": {"all": [{"value": 2}, {"value": -1}]},
+             "Verified": {"all": [{"value": 1}, {"value": 0}]}}},
+ {"subject": "no number"}]"#;
+    let changes = parse_changes(body).unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].number, 4211);
+    assert_eq!(changes[0].code_review, Some(-1));
+    assert_eq!(changes[0].verified, Some(1));
+    assert!(parse_changes(")]}'\nnot json").is_none());
+}
+
+#[test]
+fn github_and_gitlab_review_and_ci_states() {
+    let prs = parse_gh_prs(
+        r#"[{"number": 142, "title": "Fix login", "headRefName": "feature/x", "isDraft": false,
+             "reviewDecision": "CHANGES_REQUESTED",
+             "statusCheckRollup": [{"conclusion": "SUCCESS"}, {"conclusion": "FAILURE"}]},
+            {"number": 7, "title": "WIP", "headRefName": "wip", "isDraft": true,
+             "statusCheckRollup": [{"status": "IN_PROGRESS", "conclusion": ""}]}]"#,
+        "feature/x",
+    )
+    .unwrap();
+    assert!(prs[0].current_branch && !prs[1].current_branch);
+    assert_eq!(prs[0].review.as_deref(), Some("changes requested"));
+    assert_eq!(prs[0].checks.as_deref(), Some("failing"));
+    assert_eq!(prs[1].checks.as_deref(), Some("pending"));
+    assert!(prs[1].draft);
+
+    let mrs = parse_glab_mrs(
+        r#"[{"iid": 9, "title": "Bump", "source_branch": "bump", "draft": false,
+             "detailed_merge_status": "not_approved", "head_pipeline": {"status": "success"}}]"#,
+        "main",
+    )
+    .unwrap();
+    assert_eq!(mrs[0].number, 9);
+    assert_eq!(mrs[0].review.as_deref(), Some("review required"));
+    assert_eq!(mrs[0].checks.as_deref(), Some("passing"));
+}
@@ -0,0 +1,299 @@
+//! The path helpers against a fake home, and the CLI keeping its state
+//! under the HOME it is given
+
+mod common;
+
+use common::Sandbox;
+use context_keeper::paths::{
+    path_bytes, path_from_bytes, project_dir, project_relative, OUTSIDE_ROOT_MARKER,
+};
+use context_keeper::state::{contextkeeper_dir_in, project_hash, project_state_dir_in, WorkState};
+use context_keeper::ProjectContext;
+use std::path::{Component, Path, PathBuf};
+
+#[test]
+fn state_dirs_are_under_the_given_home() {
+    let home = tempfile::tempdir().unwrap();
+    let project = tempfile::tempdir().unwrap();
+    let data = contextkeeper_dir_in(home.path());
+    assert_eq!(data, home.path().join(".contextkeeper"));
+
+    let state = project_state_dir_in(home.path(), project.path());
+    assert_eq!(state.parent(), Some(data.join("projects").as_path()));
+    let hash = state.file_name().unwrap().to_str().unwrap();
+    assert_eq!(hash.len(), 16);
+    assert!(hash.chars().all(|c| c.is_ascii_hexdigit()), "{}", hash);
+    assert_eq!(hash, project_hash(project.path()));
+}
+
+#[test]
+fn one_project_one_hash_however_it_is_spelled() {
+    let project = tempfile::tempdir().unwrap();
+    let root = project.path();
+    std::fs::create_dir(root.join("sub")).unwrap();
+    let hash = project_hash(root);
+    assert_eq!(project_hash(&root.join(".")), hash);
+    assert_eq!(project_hash(&root.join("sub").join("..")), hash);
+    assert_ne!(project_hash(&root.join("sub")), hash);
+
+    #[cfg(unix)]
+    {
+        let links = tempfile::tempdir().unwrap();
+        let link = links.path().join("via-link");
+        std::os::unix::fs::symlink(root, &link).unwrap();
+        assert_eq!(project_hash(&link), hash);
+    }
+}
+
+#[test]
+fn path_bytes_round_trip() {
+    for path in ["src/main.rs", "a b/ü.txt", "/abs/path", "."] {
+        let bytes = path_bytes(Path::new(path));
+        assert_eq!(path_from_bytes(&bytes), Path::new(path), "{}", path);
+    }
+}
+
+#[test]
+fn project_dir_is_an_existing_absolute_directory() {
+    let project = tempfile::tempdir().unwrap();
+    let file = project.path().join("file.txt");
+    std::fs::write(&file, "").unwrap();
+
+    let resolved = project_dir(project.path().to_str().unwrap()).unwrap();
+    assert!(resolved.is_absolute());
+    assert_eq!(resolved, project.path().canonicalize().unwrap());
+    assert!(project_dir(".").unwrap().is_absolute());
+
+    let error = project_dir(file.to_str().unwrap()).unwrap_err();
+    assert!(error.ends_with("is not a directory"), "{}", error);
+    assert!(project_dir(project.path().join("missing").to_str().unwrap()).is_err());
+}
+
+#[test]
+fn cli_keeps_its_state_under_home() {
+    let sandbox = Sandbox::new();
+    sandbox.write("contextkeeper.toml", "[project]\nname = \"paths\"\n");
+    let output = sandbox.run(&["save-state", "check the paths"]);
+    assert!(output.status.success(), "{}", common::stderr(&output));
+
+    let state = project_state_dir_in(sandbox.home(), sandbox.root()).join("work-state.json");
+    assert!(state.is_file(), "no {}", state.display());
+    // Nothing else at the top of HOME
+    let entries: Vec<_> = std::fs::read_dir(sandbox.home())
+        .unwrap()
+        .flatten()
+        .map(|entry| entry.file_name())
+        .collect();
+    assert_eq!(entries, [".contextkeeper"]);
+}
+
+/// Xorshift, so the generated cases are the same on every run
+struct Cases(u64);
+
+impl Cases {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+const SEGMENTS: &[&str] = &["src", "a b", "ü", "x.c", ".hidden", "v1.2", "日本", "-dash"];
+
+/// `relative` spelled with `./`, doubled separators and `dir/..` detours
+fn respell(cases: &mut Cases, relative: &str) -> String {
+    let mut out = String::new();
+    for part in relative.split('/') {
+        match cases.below(4) {
+            0 => out.push_str("./"),
+            // Not in front, which would make it absolute
+            1 if !out.is_empty() => out.push('/'),
+            2 => out.push_str(&format!("{}/../", cases.pick(SEGMENTS))),
+            _ => {}
+        }
+        out.push_str(part);
+        out.push('/');
+    }
+    out.pop();
+    out
+}
+
+/// Nothing to clean up in an emitted path inside the root
+fn assert_clean(emitted: &str) {
+    assert!(
+        !emitted.starts_with("./") && !emitted.contains("//"),
+        "{:?}",
+        emitted
+    );
+    assert!(!Path::new(emitted).is_absolute(), "{:?}", emitted);
+    assert!(
+        Path::new(emitted)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_))),
+        "{:?}",
+        emitted
+    );
+}
+
+#[test]
+fn emitted_paths_are_clean_idempotent_and_reach_the_file() {
+    let project = tempfile::tempdir().unwrap();
+    let canonical = project.path().canonicalize().unwrap();
+    // The root as given may be a symlink; paths come spelled through it
+    // or through where it points
+    #[cfg(unix)]
+    let (_links, root) = {
+        let links = tempfile::tempdir().unwrap();
+        let link = links.path().join("root-link");
+        std::os::unix::fs::symlink(project.path(), &link).unwrap();
+        (links, link)
+    };
+    #[cfg(not(unix))]
+    let root = project.path().to_path_buf();
+    let root = root.as_path();
+    let spellings_of_root = [root.to_path_buf(), canonical.clone()];
+
+    let mut cases = Cases(0x9e37_79b9_7f4a_7c15);
+    let mut checked = 0;
+    for _ in 0..200 {
+        let depth = 1 + cases.below(4);
+        let relative: Vec<&str> = (0..depth).map(|_| cases.pick(SEGMENTS)).collect();
+        let relative = relative.join("/");
+        let file = root.join(&relative);
+        // A segment may already be a file from an earlier case
+        if std::fs::create_dir_all(file.parent().unwrap()).is_err() || file.is_dir() {
+            continue;
+        }
+        std::fs::write(&file, "").unwrap();
+
+        let spelled = respell(&mut cases, &relative);
+        let base = &spellings_of_root[cases.below(spellings_of_root.len())];
+        for path in [PathBuf::from(&spelled), base.join(&spelled)] {
+            let emitted = project_relative(root, &path);
+            assert_clean(&emitted);
+            assert_eq!(emitted, relative, "{}", path.display());
+            assert_eq!(project_relative(root, Path::new(&emitted)), emitted);
+            assert_eq!(
+                root.join(&emitted).canonicalize().unwrap(),
+                file.canonicalize().unwrap(),
+                "{}",
+                path.display()
+            );
+        }
+        checked += 1;
+    }
+    assert!(checked > 100, "only {} cases", checked);
+    assert_eq!(project_relative(root, root), ".");
+    assert_eq!(project_relative(root, Path::new(".")), ".");
+}
+
+#[test]
+fn paths_outside_the_root_stay_absolute_and_resolve_back() {
+    let project = tempfile::tempdir().unwrap();
+    let outside = tempfile::tempdir().unwrap();
+    let loaded = ProjectContext::load(project.path().to_path_buf(), None);
+    let mut cases = Cases(0xd1b5_4a32_d192_ed03);
+    for _ in 0..50 {
+        let name = format!("{}/{}", cases.pick(SEGMENTS), cases.pick(SEGMENTS));
+        let file = outside.path().join(&name);
+        if std::fs::create_dir_all(file.parent().unwrap()).is_err() || file.is_dir() {
+            continue;
+        }
+        std::fs::write(&file, "").unwrap();
+
+        let spelled = outside.path().join(respell(&mut cases, &name));
+        let emitted = project_relative(project.path(), &spelled);
+        assert!(emitted.ends_with(OUTSIDE_ROOT_MARKER), "{}", emitted);
+        assert_eq!(
+            project_relative(project.path(), Path::new(&emitted)),
+            emitted
+        );
+        assert_eq!(loaded.resolve(&emitted), file, "{}", emitted);
+    }
+    // `..` out of the root is outside too
+    let up = project_relative(project.path(), Path::new("../elsewhere/x.c"));
+    let parent = project.path().parent().unwrap();
+    assert_eq!(
+        up,
+        format!(
+            "{}{}",
+            parent.join("elsewhere/x.c").display(),
+            OUTSIDE_ROOT_MARKER
+        )
+    );
+}
+
+#[test]
+fn old_saved_forms_are_migrated_on_load() {
+    let project = tempfile::tempdir().unwrap();
+    let root = project.path();
+    let mut state: WorkState = serde_json::from_value(serde_json::json!({
+        "saved_at": "2026-03-02T12:00:00Z",
+        "trigger": "manual",
+        "task_summary": "old paths",
+        "working_files": [
+            "./repo//src/main.c",
+            "repo/src/main.c",
+            root.join("repo/./lib.c").to_str().unwrap(),
+        ],
+        "recent_files": ["docs//notes.md"],
+        "notes": "",
+        "todos": [],
+    }))
+    .unwrap();
+    state.normalize_paths(root);
+    assert_eq!(state.working_files, ["repo/src/main.c", "repo/lib.c"]);
+    assert_eq!(state.recent_files, ["docs/notes.md"]);
+    let before = state.working_files.clone();
+    state.normalize_paths(root);
+    assert_eq!(state.working_files, before);
+}
+
+#[test]
+fn the_pre_compact_script_saves_where_the_layout_says() {
+    let sandbox = Sandbox::new();
+    let script = Path::new(env!("CARGO_MANIFEST_DIR")).join("hooks/pre-compact-save.sh");
+    let data = sandbox.home().join(".contextkeeper");
+    std::fs::create_dir_all(&data).unwrap();
+    std::fs::write(data.join("layout_version"), "2\n").unwrap();
+
+    let output = std::process::Command::new("bash")
+        .arg(&script)
+        .current_dir(sandbox.root())
+        .env("HOME", sandbox.home())
+        .env("CONTEXTKEEPER_BIN", env!("CARGO_BIN_EXE_context-keeper"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", common::stderr(&output));
+    let state = sandbox.work_state().unwrap();
+    assert_eq!(state["trigger"], "pre_compact");
+    // Only the current layout is written
+    assert!(!data.join("work-state.json").exists());
+
+    // A missing binary skips the save without failing the compaction
+    let output = std::process::Command::new("bash")
+        .arg(&script)
+        .current_dir(sandbox.root())
+        .env("HOME", sandbox.home())
+        .env(
+            "CONTEXTKEEPER_BIN",
+            sandbox.home().join("gone/context-keeper"),
+        )
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(
+        common::stderr(&output).contains("not found; work state not saved"),
+        "{}",
+        common::stderr(&output)
+    );
+}
@@ -0,0 +1,91 @@
+//! `git status --porcelain=v2 --branch` output: renames and copies,
+//! every kind of conflict, and the branch headers
+
+use context_keeper::collectors::fake_runner::FakeRunner;
+use context_keeper::collectors::git::{
+    collect_git_info_for_path, parse_porcelain_v2, PorcelainStatus, StatusOptions,
+};
+
+fn fixture(name: &str) -> String {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e))
+}
+
+#[test]
+fn renames_copies_and_ordinary_changes() {
+    let status = parse_porcelain_v2(&fixture("git_status_v2_renames.txt"));
+    assert_eq!(
+        status,
+        PorcelainStatus {
+            head: Some("main".to_string()),
+            oid: Some("4e1f0c3b2a1908f7e6d5c4b3a29180f7e6d5c4b3".to_string()),
+            upstream: Some("origin/main".to_string()),
+            ahead: 0,
+            behind: 3,
+            // Three renames or copies, a quoted name, a submodule and a
+            // deletion; the ignored directory isn't a change
+            changed: 6,
+            conflicted: 0,
+            untracked: 0,
+        }
+    );
+}
+
+#[test]
+fn every_conflict_kind_on_a_detached_head() {
+    let status = parse_porcelain_v2(&fixture("git_status_v2_conflicts.txt"));
+    assert_eq!(status.head, None);
+    assert_eq!(
+        status.oid.as_deref(),
+        Some("9a8b7c6d5e4f30211f0e9d8c7b6a5f4e3d2c1b0a")
+    );
+    assert_eq!(status.upstream, None);
+    // DD, AU, UD, UA, DU, AA and UU
+    assert_eq!(status.conflicted, 7);
+    assert_eq!(status.changed, 1);
+    assert_eq!((status.ahead, status.behind), (0, 0));
+}
+
+#[test]
+fn branch_headers_before_the_first_commit_and_without_an_upstream_count() {
+    let initial = parse_porcelain_v2(&fixture("git_status_v2_initial.txt"));
+    assert_eq!(initial.oid, None);
+    assert_eq!(initial.head.as_deref(), Some("main"));
+    assert_eq!((initial.changed, initial.untracked), (1, 1));
+
+    // An upstream that is gone has no `branch.ab` line
+    let gone = parse_porcelain_v2(&fixture("git_status_v2_upstream_gone.txt"));
+    assert_eq!(gone.upstream.as_deref(), Some("origin/topic"));
+    assert_eq!((gone.ahead, gone.behind), (0, 0));
+    assert_eq!((gone.changed, gone.conflicted, gone.untracked), (0, 0, 0));
+}
+
+#[test]
+fn unknown_and_malformed_lines_are_skipped() {
+    let status = parse_porcelain_v2("# stash 4\n# branch.ab +x -2\n\n?\n1\nwarning: something\n");
+    assert_eq!(status.behind, 2);
+    assert_eq!(status.ahead, 0);
+    assert_eq!((status.changed, status.untracked), (0, 0));
+}
+
+#[test]
+fn detached_head_shows_the_short_oid() {
+    let repo = tempfile::tempdir().unwrap();
+    for (output, branch) in [
+        (fixture("git_status_v2_conflicts.txt"), "(9a8b7c6-dirty)"),
+        (
+            "# branch.oid 9a8b7c6d5e4f30211f0e9d8c7b6a5f4e3d2c1b0a\n\
+             # branch.head (detached)\n? notes.txt\n"
+                .to_string(),
+            "(9a8b7c6)",
+        ),
+        (fixture("git_status_v2_initial.txt"), "main"),
+    ] {
+        let runner = FakeRunner::new()
+            .on(&["git", "status"], &output)
+            .on(&["git", "log", "-1"], "9a8b7c6 Merge\n");
+        let info = collect_git_info_for_path(&runner, repo.path(), &StatusOptions::default())
+            .expect("a repository");
+        assert_eq!(info.branch, branch);
+    }
+}
@@ -0,0 +1,98 @@
+//! The start-up pre-warm and a tool call racing it collect once between
+//! them
+
+use context_keeper::collectors::fake_runner::{FakeResponse, FakeRunner};
+use context_keeper::context::CollectionPlan;
+use context_keeper::format::Level;
+use context_keeper::server::ContextKeeperService;
+use context_keeper::ProjectContext;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+const STATUS: &str = "# branch.oid 8f2c1d7e4b5a69a0c3e1f2d4b6a8c0e2f4a6b8d0\n# branch.head main\n";
+
+/// A service whose default project's commands go to a `FakeRunner` where
+/// `git status` takes `delay`
+fn service(
+    config: &str,
+    delay: Duration,
+) -> (tempfile::TempDir, ContextKeeperService, Arc<FakeRunner>) {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join(".git")).unwrap();
+    std::fs::write(dir.path().join("contextkeeper.toml"), config).unwrap();
+    let runner = Arc::new(FakeRunner::new().respond(
+        &["git", "status"],
+        FakeResponse::Slow(
+            delay,
+            Box::new(FakeResponse::Exit {
+                code: 0,
+                stdout: STATUS.as_bytes().to_vec(),
+                stderr: Vec::new(),
+            }),
+        ),
+    ));
+    let mut project = ProjectContext::load(dir.path().to_path_buf(), None);
+    project.runner = runner.clone();
+    let service = ContextKeeperService::new(project.root.clone(), None, None);
+    service
+        .projects
+        .lock()
+        .unwrap()
+        .insert(project.root.clone(), Arc::new(project));
+    (dir, service, runner)
+}
+
+fn status_calls(runner: &FakeRunner) -> usize {
+    runner
+        .calls()
+        .iter()
+        .filter(|call| call.line().contains(" status --porcelain=v2"))
+        .count()
+}
+
+/// What get_dev_context does on a call: mark that a tool was called, then
+/// ask the cache
+async fn tool_call(service: &ContextKeeperService) -> context_keeper::Context {
+    let project = service.load_project(service.default_root.clone());
+    service.tool_called.store(true, Ordering::SeqCst);
+    let plan = CollectionPlan::for_level(Level::Normal, &project.config);
+    service
+        .cached_context(&project, plan, false, false, &CancellationToken::new())
+        .await
+}
+
+#[tokio::test]
+async fn tool_call_during_prewarm_waits_for_it() {
+    let (_dir, service, runner) = service("", Duration::from_millis(300));
+    let prewarm = service.clone();
+    let prewarming = tokio::spawn(async move { prewarm.prewarm().await });
+    // Let the pre-warm take the lock and start git status
+    while status_calls(&runner) == 0 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    let context = tool_call(&service).await;
+    assert!(context.cache_age.is_some(), "collected a second time");
+    assert_eq!(context.git_repos[0].branch, "main");
+    prewarming.await.unwrap();
+    assert_eq!(status_calls(&runner), 1, "{:?}", runner.calls());
+}
+
+#[tokio::test]
+async fn prewarm_after_a_tool_call_does_nothing() {
+    let (_dir, service, runner) = service("", Duration::ZERO);
+    let context = tool_call(&service).await;
+    assert!(context.cache_age.is_none());
+    service.prewarm().await;
+    assert_eq!(status_calls(&runner), 1, "{:?}", runner.calls());
+}
+
+#[tokio::test]
+async fn prewarm_can_be_turned_off() {
+    let (_dir, service, runner) = service("[server]\nprewarm = false\n", Duration::ZERO);
+    service.prewarm().await;
+    assert!(runner.calls().is_empty(), "{:?}", runner.calls());
+    assert!(tool_call(&service).await.cache_age.is_none());
+}
@@ -0,0 +1,362 @@
+//! `.contextkeeper-ignore`: its matching, and an ignored directory left
+//! out by every collector that walks the tree, each checked against the
+//! same tree without the file
+
+mod common;
+
+use common::{stderr, stdout, Sandbox};
+use context_keeper::collectors::code_todos::collect_code_todos;
+use context_keeper::collectors::detect::{detect_project_types, find_ros_workspace};
+use context_keeper::collectors::git::{find_git_repos, top_level_mtimes};
+use context_keeper::collectors::project_ignore::{ProjectIgnore, PROJECT_IGNORE_FILE};
+use context_keeper::collectors::recent_files::collect_recent_files;
+use context_keeper::collectors::ros::collect_ros_workspace;
+use context_keeper::collectors::yocto::find_yocto_build_dir;
+use context_keeper::commands::init::find_config_dir_candidates;
+use context_keeper::summary::collect_project_summary;
+use context_keeper::ProjectContext;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// A project directory holding `files`, with `contextkeeper.toml` set to
+/// `config`
+fn tree(config: &str, files: &[(&str, &str)]) -> (tempfile::TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("project");
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join("contextkeeper.toml"), config).unwrap();
+    for (path, content) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    (dir, root)
+}
+
+fn ignore_with(root: &Path, rules: &str) {
+    fs::write(root.join(PROJECT_IGNORE_FILE), rules).unwrap();
+}
+
+fn load(root: &Path) -> ProjectContext {
+    ProjectContext::load(root.to_path_buf(), None)
+}
+
+#[test]
+fn gitignore_matching() {
+    let (_dir, root) = tree("", &[]);
+    let none = ProjectIgnore::load(&root);
+    assert_eq!((none.path.as_ref(), none.rules()), (None, 0));
+    assert!(!none.is_ignored(&root.join("vendor"), true));
+
+    ignore_with(
+        &root,
+        "# dumps\nvendor/\n/prebuilts\n*.key\n!keep.key\nsecrets/**/private\n",
+    );
+    let ignore = ProjectIgnore::load(&root);
+    assert_eq!(ignore.path, Some(root.join(PROJECT_IGNORE_FILE)));
+    assert_eq!(ignore.rules(), 5);
+    assert_eq!(ignore.error, None);
+    let ignored = |path: &str, is_dir| ignore.is_ignored(&root.join(path), is_dir);
+    // A trailing slash matches directories anywhere, and what they hold
+    assert!(ignored("vendor", true));
+    assert!(ignored("hal/vendor", true));
+    assert!(ignored("vendor/blob/x.bin", false));
+    assert!(!ignored("vendor", false));
+    // A leading slash only at the root
+    assert!(ignored("prebuilts", true));
+    assert!(!ignored("device/prebuilts", true));
+    // `!` re-includes
+    assert!(ignored("certs/release.key", false));
+    assert!(!ignored("certs/keep.key", false));
+    assert!(ignored("secrets/a/b/private", true));
+    // Relative paths are the root's; the root and what is outside it never
+    // are ignored
+    assert!(ignore.is_ignored(Path::new("vendor"), true));
+    assert!(!ignore.is_ignored(&root, true));
+    assert!(!ignore.is_ignored(Path::new("/elsewhere/vendor"), true));
+}
+
+#[test]
+fn a_bad_pattern_leaves_the_others() {
+    let (_dir, root) = tree("", &[]);
+    ignore_with(&root, "vendor/\nhal{b\nout-*/\n");
+    let ignore = ProjectIgnore::load(&root);
+    assert!(ignore
+        .error
+        .as_deref()
+        .is_some_and(|e| e.contains("line 2: error parsing glob 'hal{b'")));
+    assert!(ignore.is_ignored(&root.join("vendor"), true));
+    assert!(ignore.is_ignored(&root.join("out-arm"), true));
+}
+
+#[test]
+fn editing_the_file_reloads_the_project() {
+    let (_dir, root) = tree("", &[]);
+    let project = load(&root);
+    assert!(!project.config_changed());
+    ignore_with(&root, "vendor/\n");
+    assert!(project.config_changed());
+
+    let project = load(&root);
+    assert!(!project.config_changed());
+    // Seen even when the edit keeps the size
+    let file = fs::File::options()
+        .write(true)
+        .open(root.join(PROJECT_IGNORE_FILE))
+        .unwrap();
+    file.set_modified(SystemTime::now() + Duration::from_secs(60))
+        .unwrap();
+    assert!(project.config_changed());
+    fs::remove_file(root.join(PROJECT_IGNORE_FILE)).unwrap();
+    assert!(load(&root).ignore.path.is_none());
+}
+
+#[test]
+fn git_discovery() {
+    let (_dir, root) = tree(
+        "",
+        &[
+            ("app/.git/HEAD", ""),
+            ("vendor/blob/.git/HEAD", ""),
+            ("hal/vendor/drv/.git/HEAD", ""),
+        ],
+    );
+    let found = |root: &Path| find_git_repos(root, 3, &ProjectIgnore::load(root));
+    assert_eq!(
+        found(&root),
+        [
+            PathBuf::from("app"),
+            PathBuf::from("hal/vendor/drv"),
+            PathBuf::from("vendor/blob")
+        ]
+    );
+    ignore_with(&root, "vendor/\n");
+    assert_eq!(found(&root), [PathBuf::from("app")]);
+
+    // The repo cache's fingerprint leaves the directory out and holds the
+    // file, so editing it invalidates the cache
+    let names: Vec<PathBuf> = top_level_mtimes(&root, &ProjectIgnore::load(&root))
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert!(
+        names.contains(&PathBuf::from(PROJECT_IGNORE_FILE)),
+        "{:?}",
+        names
+    );
+    assert!(names.contains(&PathBuf::from("app")), "{:?}", names);
+    assert!(!names.contains(&PathBuf::from("vendor")), "{:?}", names);
+}
+
+#[test]
+fn recent_files() {
+    let (_dir, root) = tree(
+        "[recent_files]\npaths = [\"device\"]\n",
+        &[("device/board.dts", ""), ("device/vendor/blob.bin", "")],
+    );
+    let paths = |root: &Path| -> Vec<String> {
+        collect_recent_files(&load(root))
+            .into_iter()
+            .map(|f| f.path)
+            .collect()
+    };
+    let mut all = paths(&root);
+    all.sort();
+    assert_eq!(all, ["device/board.dts", "device/vendor/blob.bin"]);
+    ignore_with(&root, "vendor/\n");
+    assert_eq!(paths(&root), ["device/board.dts"]);
+}
+
+#[test]
+fn type_detection() {
+    let (_dir, root) = tree(
+        "",
+        &[
+            (
+                "rosws/src/nav/package.xml",
+                "<package><name>nav</name></package>",
+            ),
+            ("meta-secret/conf/layer.conf", ""),
+        ],
+    );
+    let types = |root: &Path| -> Vec<String> {
+        detect_project_types(root, &ProjectIgnore::load(root))
+            .into_iter()
+            .map(|d| d.project_type)
+            .collect()
+    };
+    let found = types(&root);
+    assert!(found.contains(&"ros".to_string()), "{:?}", found);
+    assert!(found.contains(&"yocto".to_string()), "{:?}", found);
+    assert!(find_ros_workspace(&root, &ProjectIgnore::default()).is_some());
+
+    ignore_with(&root, "/rosws\nmeta-secret/\n");
+    assert_eq!(types(&root), Vec::<String>::new());
+    assert_eq!(find_ros_workspace(&root, &ProjectIgnore::load(&root)), None);
+}
+
+#[test]
+fn ros_package_walk() {
+    let (_dir, root) = tree(
+        "[project]\ntype = \"ros\"\n",
+        &[
+            ("src/nav/package.xml", "<package><name>nav</name></package>"),
+            (
+                "src/vendor/lidar/package.xml",
+                "<package><name>lidar</name></package>",
+            ),
+        ],
+    );
+    let packages = |root: &Path| -> Vec<String> {
+        let mut names: Vec<String> = collect_ros_workspace(&load(root))
+            .unwrap()
+            .packages
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        names.sort();
+        names
+    };
+    assert_eq!(packages(&root), ["lidar", "nav"]);
+    ignore_with(&root, "vendor/\n");
+    assert_eq!(packages(&root), ["nav"]);
+}
+
+#[test]
+fn yocto_build_dir() {
+    let (_dir, root) = tree(
+        "",
+        &[
+            ("build/conf/local.conf", ""),
+            ("build-old/conf/local.conf", ""),
+        ],
+    );
+    // The other one most recently used
+    let file = fs::File::options()
+        .write(true)
+        .open(root.join("build/conf/local.conf"))
+        .unwrap();
+    file.set_modified(SystemTime::now() - Duration::from_secs(3600))
+        .unwrap();
+    assert_eq!(
+        find_yocto_build_dir(&load(&root)),
+        Some(root.join("build-old"))
+    );
+    ignore_with(&root, "build-old/\n");
+    assert_eq!(find_yocto_build_dir(&load(&root)), Some(root.join("build")));
+}
+
+#[test]
+fn config_dir_search() {
+    let (_dir, root) = tree(
+        "",
+        &[
+            ("configs/a.conf", ""),
+            ("configs/b.conf", ""),
+            ("vendor/dump/1.conf", ""),
+            ("vendor/dump/2.conf", ""),
+            ("vendor/dump/3.conf", ""),
+        ],
+    );
+    let candidates = |root: &Path| find_config_dir_candidates(root, &ProjectIgnore::load(root));
+    assert_eq!(
+        candidates(&root),
+        [("vendor/dump".to_string(), 3), ("configs".to_string(), 2)]
+    );
+    ignore_with(&root, "vendor/\n");
+    assert_eq!(candidates(&root), [("configs".to_string(), 2)]);
+}
+
+#[test]
+fn code_todo_scan() {
+    let (_dir, root) = tree(
+        "[work_state]\nscan_todos = true\n",
+        &[
+            ("src/audio.c", "// TODO: retry on EAGAIN\n"),
+            ("vendor/blob.c", "// FIXME: not ours\n"),
+        ],
+    );
+    let files = ["src/audio.c".to_string(), "vendor/blob.c".to_string()];
+    let scanned = |root: &Path| -> Vec<String> {
+        collect_code_todos(&load(root), &files)
+            .into_iter()
+            .map(|todo| todo.file)
+            .collect()
+    };
+    assert_eq!(scanned(&root), ["src/audio.c", "vendor/blob.c"]);
+    ignore_with(&root, "vendor/\n");
+    assert_eq!(scanned(&root), ["src/audio.c"]);
+}
+
+#[test]
+fn summary_layout() {
+    let (_dir, root) = tree("", &[("src/main.c", ""), ("vendor/blob.bin", "")]);
+    let layout = |root: &Path| -> Vec<String> {
+        collect_project_summary(&load(root))
+            .layout
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect()
+    };
+    let shown = layout(&root);
+    assert!(shown.contains(&"vendor".to_string()), "{:?}", shown);
+    ignore_with(&root, "vendor/\n");
+    let shown = layout(&root);
+    assert!(shown.contains(&"src".to_string()), "{:?}", shown);
+    assert!(!shown.contains(&"vendor".to_string()), "{:?}", shown);
+}
+
+fn ignore_check(sandbox: &Sandbox) -> Value {
+    let output = sandbox.run(&["doctor", "--json"]);
+    let report: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    report["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|check| check["name"] == "ignore_file")
+        .cloned()
+        .unwrap()
+}
+
+#[test]
+fn reported_by_doctor_and_the_diagnostics() {
+    let sandbox = Sandbox::new();
+    sandbox.write("contextkeeper.toml", "[project]\nname = \"firmware\"\n");
+    let check = ignore_check(&sandbox);
+    assert_eq!(check["level"], "pass");
+    assert!(
+        check["detail"]
+            .as_str()
+            .unwrap()
+            .starts_with("no .contextkeeper-ignore in "),
+        "{}",
+        check
+    );
+
+    let file = sandbox.write(PROJECT_IGNORE_FILE, "vendor/\n/prebuilts\n");
+    let check = ignore_check(&sandbox);
+    assert_eq!(check["level"], "pass");
+    assert_eq!(check["detail"], format!("{} (2 rules)", file.display()));
+
+    let output = sandbox.run(&["context", "--level", "full"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output)
+            .contains("`.contextkeeper-ignore` has 2 rules; what they match was not scanned"),
+        "{}",
+        stdout(&output)
+    );
+
+    sandbox.write(PROJECT_IGNORE_FILE, "vendor/\nhal{b\n");
+    let check = ignore_check(&sandbox);
+    assert_eq!(check["level"], "warn");
+    let detail = check["detail"].as_str().unwrap();
+    assert!(detail.starts_with("1 rule usable; "), "{}", detail);
+    assert!(
+        detail.contains("line 2: error parsing glob 'hal{b'"),
+        "{}",
+        detail
+    );
+}
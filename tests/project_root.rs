@@ -0,0 +1,106 @@
+//! `project_root` as the MCP tools take it, and the client's root URIs
+
+mod common;
+
+use common::mcp::McpClient;
+use common::Sandbox;
+use context_keeper::server::file_uri_to_path;
+use serde_json::json;
+use std::path::PathBuf;
+
+const INVALID_PARAMS: i64 = -32602;
+
+fn error_of(response: &serde_json::Value) -> (i64, String) {
+    let error = &response["error"];
+    (
+        error["code"]
+            .as_i64()
+            .unwrap_or_else(|| panic!("no error: {}", response)),
+        error["message"].as_str().unwrap().to_string(),
+    )
+}
+
+#[test]
+fn bad_project_roots_are_invalid_params() {
+    let sandbox = Sandbox::new();
+    let file = sandbox.write("notes.txt", "not a directory");
+    let missing = sandbox.root().join("missing");
+    let cases = [
+        (missing.to_string_lossy().into_owned(), "is not a directory"),
+        (file.to_string_lossy().into_owned(), "is not a directory"),
+        ("relative/dir".to_string(), "must be an absolute path"),
+    ];
+    let mut client = McpClient::start(&sandbox, &[]);
+    for (tool, args) in [
+        ("get_dev_context", json!({})),
+        ("save_work_state", json!({"task_summary": "x"})),
+        ("bootstrap_config", json!({})),
+    ] {
+        for (root, expected) in &cases {
+            let mut args = args.clone();
+            args["project_root"] = json!(root);
+            let response = client.call(tool, args);
+            let (code, message) = error_of(&response);
+            assert_eq!(code, INVALID_PARAMS, "{} {}: {}", tool, root, message);
+            assert!(message.contains(expected), "{} {}: {}", tool, root, message);
+        }
+    }
+    assert!(!missing.exists(), "nothing created for a bad root");
+    client.close();
+}
+
+#[test]
+fn a_valid_project_root_is_used() {
+    let sandbox = Sandbox::new();
+    let other = tempfile::tempdir().unwrap();
+    let mut client = McpClient::start(&sandbox, &[]);
+    let root = other.path().to_string_lossy().into_owned();
+    let text = client.call_text(
+        "bootstrap_config",
+        json!({"project_root": root, "name": "firmware"}),
+    );
+    assert!(text.contains("Wrote"), "{}", text);
+    let config = std::fs::read_to_string(other.path().join("contextkeeper.toml")).unwrap();
+    assert!(config.contains("name = \"firmware\""), "{}", config);
+    assert!(!sandbox.root().join("contextkeeper.toml").exists());
+    client.close();
+}
+
+#[test]
+fn root_uris_are_decoded() {
+    let path = |uri| file_uri_to_path(uri);
+    assert_eq!(path("file:///src/app"), Some(PathBuf::from("/src/app")));
+    assert_eq!(
+        path("file://localhost/src/my%20app"),
+        Some(PathBuf::from("/src/my app"))
+    );
+    assert_eq!(path("file:///a%2Fb%2f"), Some(PathBuf::from("/a/b/")));
+    assert_eq!(path("file:///caf%C3%A9"), Some(PathBuf::from("/café")));
+    assert_eq!(path("https://example.com/x"), None);
+    assert_eq!(path("file://host-only"), None);
+
+    // A `%` without two hex digits after it stays, whatever follows
+    for (uri, kept) in [
+        ("file:///a%aé", "/a%aé"),
+        ("file:///a%é", "/a%é"),
+        ("file:///a%+1", "/a%+1"),
+        ("file:///a%-f", "/a%-f"),
+        ("file:///a%g1", "/a%g1"),
+        ("file:///a%4", "/a%4"),
+        ("file:///a%", "/a%"),
+    ] {
+        assert_eq!(path(uri), Some(PathBuf::from(kept)), "{}", uri);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn a_root_that_is_not_utf8_is_kept() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    assert_eq!(
+        file_uri_to_path("file:///src/caf%E9"),
+        Some(PathBuf::from(OsStr::from_bytes(b"/src/caf\xe9")))
+    );
+}
@@ -0,0 +1,226 @@
+//! Which repositories make the `[git] max_repos` cut, on fixture trees
+//! whose index, HEAD and reflog times are set
+
+mod common;
+
+use common::git::{commit, git, has_git};
+use common::{stderr, stdout, Sandbox};
+use context_keeper::collectors::git::{repo_activity, GitSnapshot, RepoRank};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// `days` after 2021-01-01
+fn day(days: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(1_609_459_200 + days * 86_400)
+}
+
+fn set_mtime(path: &Path, time: SystemTime) {
+    fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(time)
+        .unwrap();
+}
+
+/// A clean repository at `dir` that git last wrote to at `active`. The
+/// work tree is dated before that and the index refreshed, so `git status`
+/// has nothing to rewrite and leaves the times alone.
+fn repo_active_at(dir: &Path, active: SystemTime) {
+    fs::create_dir_all(dir).unwrap();
+    git(dir, &["init", "-q"]);
+    commit(dir, "x.c", "int x;\n", "init");
+    set_mtime(&dir.join("x.c"), day(0) - Duration::from_secs(86_400));
+    git(dir, &["update-index", "-q", "--refresh"]);
+    for file in ["index", "HEAD", "logs/HEAD"] {
+        set_mtime(&dir.join(".git").join(file), active);
+    }
+}
+
+#[test]
+fn activity_is_the_latest_git_write() {
+    if !has_git() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let repo = dir.path().join("app");
+    repo_active_at(&repo, day(3));
+    assert_eq!(repo_activity(&repo), Some(day(3)));
+    set_mtime(&repo.join(".git/logs/HEAD"), day(7));
+    assert_eq!(repo_activity(&repo), Some(day(7)));
+    set_mtime(&repo.join(".git/index"), day(9));
+    assert_eq!(repo_activity(&repo), Some(day(9)));
+    assert_eq!(repo_activity(dir.path()), None);
+}
+
+#[test]
+fn dirty_then_active_then_working_files() {
+    let rank = |dirty, activity, in_working_files| RepoRank {
+        dirty,
+        activity,
+        in_working_files,
+    };
+    let mut ranks = vec![
+        rank(false, None, true),
+        rank(false, Some(day(1)), true),
+        rank(false, Some(day(5)), false),
+        rank(true, None, false),
+        rank(false, Some(day(1)), false),
+        rank(true, Some(day(1)), false),
+    ];
+    ranks.sort_by_key(|r| std::cmp::Reverse(*r));
+    assert_eq!(
+        ranks,
+        [
+            rank(true, Some(day(1)), false),
+            rank(true, None, false),
+            rank(false, Some(day(5)), false),
+            rank(false, Some(day(1)), true),
+            rank(false, Some(day(1)), false),
+            rank(false, None, true),
+        ]
+    );
+
+    // A working file is in the repository when it is under its path
+    let dir = tempfile::tempdir().unwrap();
+    let working = |files: &[&str]| -> Vec<String> { files.iter().map(|f| f.to_string()).collect() };
+    let holds = |path: &str, files: &[&str]| {
+        RepoRank::of(dir.path(), path, None, &working(files)).in_working_files
+    };
+    assert!(holds("app", &["app/src/main.c"]));
+    assert!(holds("app", &["lib/x.c", "app"]));
+    assert!(!holds("app", &["app2/main.c", "lib/app/x.c"]));
+    assert!(!holds("app", &[]));
+}
+
+const REPOS: [(&str, u64); 6] = [
+    ("art", 1),
+    ("bionic", 5),
+    ("build", 3),
+    ("device", 2),
+    ("frameworks", 4),
+    ("kernel", 0),
+];
+
+/// Six repositories by their last activity, `kernel` the oldest but with
+/// an untracked file, and `[git] max_repos = 2`
+fn tree() -> Sandbox {
+    let sandbox = Sandbox::new();
+    sandbox.write("contextkeeper.toml", "[git]\nmax_repos = 2\n");
+    for (name, days) in REPOS {
+        repo_active_at(&sandbox.root().join(name), day(days));
+    }
+    sandbox.write("kernel/new.c", "int n;\n");
+    sandbox
+}
+
+/// The repositories `context --level <level>` showed, best first, from
+/// the `git-status.json` it saved, and its output
+fn shown(sandbox: &Sandbox, level: &str) -> (Vec<String>, String) {
+    let output = sandbox.run(&["context", "--level", level]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let projects = sandbox.home().join(".contextkeeper/projects");
+    let path = fs::read_dir(projects)
+        .unwrap()
+        .flatten()
+        .map(|entry| entry.path().join("git-status.json"))
+        .find(|path| path.exists())
+        .unwrap();
+    let snapshot: GitSnapshot = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+    let names = snapshot.repos.into_iter().map(|r| r.repo_path).collect();
+    (names, stdout(&output))
+}
+
+#[test]
+fn dirty_and_recent_repositories_make_the_cut() {
+    if !has_git() {
+        return;
+    }
+    let sandbox = tree();
+
+    // Every repository is checked for the whole context
+    let output = sandbox.run(&["context", "--json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let value: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let repos: Vec<&str> = value["git_repos"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["repo_path"].as_str().unwrap())
+        .collect();
+    assert_eq!(repos, ["kernel", "bionic"]);
+    assert_eq!(value["git_repos_omitted"], json!(4));
+
+    let (names, text) = shown(&sandbox, "full");
+    assert_eq!(names, ["kernel", "bionic"], "{}", text);
+    assert!(
+        text.contains("*…and 4 more repositories, ranked below these by changes and recent activity (`[git] max_repos`)*\n"),
+        "{}",
+        text
+    );
+
+    // Below full level only the most recently active are checked, so the
+    // old dirty one doesn't make it
+    let (names, text) = shown(&sandbox, "normal");
+    assert_eq!(names, ["bionic", "frameworks"], "{}", text);
+    assert!(
+        text.contains(
+            "across the 2 most recently active repos; 4 more not shown (`[git] max_repos`)"
+        ),
+        "{}",
+        text
+    );
+
+    // The times are still the fixture's
+    for (name, days) in REPOS {
+        assert_eq!(repo_activity(&sandbox.root().join(name)), Some(day(days)));
+    }
+}
+
+#[cfg(feature = "stdio-server")]
+#[test]
+fn a_working_file_shortlists_its_repository() {
+    use common::mcp::McpClient;
+
+    if !has_git() {
+        return;
+    }
+    let sandbox = tree();
+    let mut client = McpClient::start(&sandbox, &[]);
+    client.call_text(
+        "save_work_state",
+        json!({"task_summary": "kernel config", "working_files": ["kernel/new.c"]}),
+    );
+    client.close();
+
+    let (names, text) = shown(&sandbox, "normal");
+    assert_eq!(names, ["kernel", "bionic"], "{}", text);
+}
+
+#[cfg(feature = "stdio-server")]
+#[test]
+fn a_working_file_breaks_a_tie() {
+    use common::mcp::McpClient;
+
+    if !has_git() {
+        return;
+    }
+    let sandbox = Sandbox::new();
+    sandbox.write("contextkeeper.toml", "[git]\nmax_repos = 1\n");
+    for (name, _) in REPOS {
+        repo_active_at(&sandbox.root().join(name), day(2));
+    }
+    // Ties keep the path order
+    assert_eq!(shown(&sandbox, "full").0, ["art"]);
+
+    let mut client = McpClient::start(&sandbox, &[]);
+    client.call_text(
+        "save_work_state",
+        json!({"task_summary": "frameworks", "working_files": ["frameworks/x.c"]}),
+    );
+    client.close();
+    assert_eq!(shown(&sandbox, "full").0, ["frameworks"]);
+    assert_eq!(shown(&sandbox, "normal").0, ["frameworks"]);
+}
@@ -0,0 +1,260 @@
+//! `resolve_path` on fixture trees with nested repositories and submodules
+
+mod common;
+
+use common::git::{commit, git, has_git};
+use common::Sandbox;
+use context_keeper::collectors::resolve_path::{resolve_paths, PathOwnership, ResolvedPath};
+use context_keeper::ProjectContext;
+use std::fs;
+use std::path::Path;
+
+/// A work tree at `root` holding:
+/// - `src/a.c` committed, then modified, and an untracked `notes.txt`;
+/// - `build/`, ignored, with `build/out.o`;
+/// - `vendor/lib`, a repository of its own (not a submodule) with a
+///   staged `x.c`;
+/// - `modules/sub`, a submodule with its commit checked out detached.
+fn superproject(root: &Path, upstream: &Path) {
+    git(upstream, &["init", "-q"]);
+    commit(upstream, "s.c", "int s;\n", "sub");
+
+    git(root, &["init", "-q"]);
+    fs::write(root.join(".gitignore"), "build/\nvendor/\n").unwrap();
+    commit(root, "src/a.c", "int a;\n", "a");
+    git(
+        root,
+        &[
+            "-c",
+            "protocol.file.allow=always",
+            "submodule",
+            "add",
+            "-q",
+            upstream.to_str().unwrap(),
+            "modules/sub",
+        ],
+    );
+    git(root, &["commit", "-qm", "add sub"]);
+    git(&root.join("modules/sub"), &["checkout", "-q", "--detach"]);
+    fs::write(root.join("src/a.c"), "int a = 1;\n").unwrap();
+    fs::write(root.join("notes.txt"), "todo\n").unwrap();
+    fs::create_dir_all(root.join("build")).unwrap();
+    fs::write(root.join("build/out.o"), "\x7fELF").unwrap();
+
+    let vendor = root.join("vendor/lib");
+    fs::create_dir_all(&vendor).unwrap();
+    git(&vendor, &["init", "-q"]);
+    commit(&vendor, "README", "lib\n", "lib");
+    fs::write(vendor.join("x.c"), "int x;\n").unwrap();
+    git(&vendor, &["add", "x.c"]);
+}
+
+fn resolve(root: &Path, paths: &[&str]) -> Vec<ResolvedPath> {
+    let project = ProjectContext::load(root.to_path_buf(), None);
+    let paths: Vec<String> = paths.iter().map(|p| p.to_string()).collect();
+    resolve_paths(&project, &paths).unwrap()
+}
+
+/// (repo, nested_in, submodule, repo_path, status, state) of `entry`
+fn answer(entry: &ResolvedPath) -> (&str, &str, bool, &str, &str, &str) {
+    (
+        entry.repo.as_deref().unwrap_or_default(),
+        entry.nested_in.as_deref().unwrap_or_default(),
+        entry.submodule,
+        entry.repo_path.as_deref().unwrap_or_default(),
+        entry.status.as_deref().unwrap_or_default(),
+        &entry.state,
+    )
+}
+
+#[test]
+fn nested_repositories_and_submodule_boundaries() {
+    if !has_git() {
+        return;
+    }
+    let sandbox = Sandbox::new();
+    let upstream = tempfile::tempdir().unwrap();
+    superproject(sandbox.root(), upstream.path());
+
+    let resolved = resolve(
+        sandbox.root(),
+        &[
+            "src/a.c",
+            "notes.txt",
+            "build/out.o",
+            "vendor/lib/x.c",
+            "vendor/lib/README",
+            "vendor/lib",
+            "vendor",
+            "modules/sub/s.c",
+            "modules/sub",
+            "modules",
+        ],
+    );
+    let answers: Vec<_> = resolved.iter().map(answer).collect();
+    assert_eq!(
+        answers,
+        [
+            (".", "", false, "src/a.c", ".M", "modified, not staged"),
+            (".", "", false, "notes.txt", "??", "untracked"),
+            (".", "", false, "build/out.o", "!!", "ignored"),
+            // The nested repository owns what is inside it, not the one
+            // around it that ignores it
+            ("vendor/lib", ".", false, "x.c", "A.", "staged"),
+            (
+                "vendor/lib",
+                ".",
+                false,
+                "README",
+                "",
+                "no uncommitted changes"
+            ),
+            (
+                "vendor/lib",
+                ".",
+                false,
+                ".",
+                "",
+                "directory, 1 changed path inside"
+            ),
+            (".", "", false, "vendor", "!!", "ignored"),
+            (
+                "modules/sub",
+                ".",
+                true,
+                "s.c",
+                "",
+                "no uncommitted changes"
+            ),
+            // The submodule's own directory is its root, its parent the
+            // superproject's
+            (
+                "modules/sub",
+                ".",
+                true,
+                ".",
+                "",
+                "directory without changes"
+            ),
+            (".", "", false, "modules", "", "directory without changes"),
+        ]
+    );
+    assert!(resolved
+        .iter()
+        .all(|entry| entry.ownership == PathOwnership::Owned));
+
+    let branches: Vec<&str> = resolved
+        .iter()
+        .map(|entry| entry.branch.as_deref().unwrap())
+        .collect();
+    assert_eq!(branches[..7], ["main"; 7]);
+    let head = git(
+        &sandbox.root().join("modules/sub"),
+        &["rev-parse", "--short=7", "HEAD"],
+    );
+    assert_eq!(branches[7], format!("({})", head.trim()));
+    assert_eq!(branches[8], branches[7]);
+    assert_eq!(branches[9], "main");
+}
+
+#[test]
+fn spellings_are_normalized_and_outside_paths_answered() {
+    if !has_git() {
+        return;
+    }
+    let sandbox = Sandbox::new();
+    let upstream = tempfile::tempdir().unwrap();
+    superproject(sandbox.root(), upstream.path());
+    let absolute = sandbox.root().join("modules/sub/s.c");
+    let elsewhere = tempfile::tempdir().unwrap();
+    let outside = elsewhere.path().join("other.c");
+
+    let resolved = resolve(
+        sandbox.root(),
+        &[
+            "./src/../src/a.c",
+            absolute.to_str().unwrap(),
+            "modules/sub/",
+            outside.to_str().unwrap(),
+            "src/gone.c",
+        ],
+    );
+    assert_eq!(resolved[0].path, "src/a.c");
+    assert_eq!(resolved[0].status.as_deref(), Some(".M"));
+    assert_eq!(resolved[1].path, "modules/sub/s.c");
+    assert_eq!(resolved[1].repo.as_deref(), Some("modules/sub"));
+    assert_eq!(resolved[2].path, "modules/sub");
+    assert_eq!(resolved[2].repo_path.as_deref(), Some("."));
+
+    assert_eq!(resolved[3].ownership, PathOwnership::OutsideProject);
+    assert!(resolved[3].repo.is_none() && resolved[3].branch.is_none());
+    assert_eq!(Path::new(&resolved[3].path), outside);
+
+    assert_eq!(answer(&resolved[4]).0, ".");
+    assert_eq!(resolved[4].state, "not on disk");
+}
+
+#[test]
+fn paths_in_no_known_repository_are_unowned() {
+    if !has_git() {
+        return;
+    }
+    let sandbox = Sandbox::new();
+    let root = sandbox.root();
+    sandbox.write("contextkeeper.toml", "[git]\npaths = [\"app\"]\n");
+    sandbox.write("scratch/x.c", "int x;\n");
+    fs::create_dir_all(root.join("app")).unwrap();
+    git(&root.join("app"), &["init", "-q"]);
+    commit(&root.join("app"), "main.c", "int main;\n", "app");
+    // A repository git.paths doesn't list
+    fs::create_dir_all(root.join("docs")).unwrap();
+    git(&root.join("docs"), &["init", "-q"]);
+    commit(&root.join("docs"), "index.md", "# Docs\n", "docs");
+
+    let resolved = resolve(root, &["app/main.c", "scratch/x.c", "docs/index.md"]);
+    assert_eq!(resolved[0].ownership, PathOwnership::Owned);
+    assert_eq!(resolved[0].repo.as_deref(), Some("app"));
+    assert!(resolved[0].nested_in.is_none());
+    for entry in &resolved[1..] {
+        assert_eq!(entry.ownership, PathOwnership::Unowned, "{:?}", entry);
+        assert!(
+            entry.repo.is_none() && entry.branch.is_none(),
+            "{:?}",
+            entry
+        );
+    }
+}
+
+#[cfg(feature = "stdio-server")]
+#[test]
+fn answers_over_mcp() {
+    if !has_git() {
+        return;
+    }
+    let sandbox = Sandbox::new();
+    let upstream = tempfile::tempdir().unwrap();
+    superproject(sandbox.root(), upstream.path());
+
+    let mut client = common::mcp::McpClient::start(&sandbox, &[]);
+    let text = client.call_text(
+        "resolve_path",
+        serde_json::json!({"paths": ["vendor/lib/x.c", "./modules/sub/s.c", "/"]}),
+    );
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(
+        lines[0],
+        "- `vendor/lib/x.c`: repo `vendor/lib` inside `.` on `main`, staged (`A.`)"
+    );
+    assert!(
+        lines[1].starts_with(
+            "- `modules/sub/s.c` (given as `./modules/sub/s.c`): submodule `modules/sub` inside `.` on `("
+        ),
+        "{}",
+        text
+    );
+    assert_eq!(lines[2], "- `/`: outside project");
+
+    let response = client.call("resolve_path", serde_json::json!({"paths": []}));
+    assert_eq!(response["error"]["code"], -32602, "{}", response);
+    client.close();
+}
@@ -0,0 +1,5 @@
+set -gx CK_PROJECT_NAME 'o\'brien firmware'
+set -e CK_TARGET_CONTAINER  # target bare has no container
+set -e CK_TARGET_LUNCH  # target bare has no lunch target
+set -gx CK_DEVICE_SERIAL 'emulator-5554'
+set -gx CK_GIT_DIRTY_REPOS '. kernel wip'
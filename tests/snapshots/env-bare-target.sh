@@ -0,0 +1,5 @@
+export CK_PROJECT_NAME='o'\''brien firmware'
+unset CK_TARGET_CONTAINER  # target bare has no container
+unset CK_TARGET_LUNCH  # target bare has no lunch target
+export CK_DEVICE_SERIAL='emulator-5554'
+export CK_GIT_DIRTY_REPOS='. kernel wip'
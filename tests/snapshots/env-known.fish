@@ -0,0 +1,6 @@
+set -gx CK_PROJECT_NAME 'o\'brien firmware'
+set -gx CK_TARGET_CONTAINER 'it\'s a "builder" $HOME `id` \\n'
+set -gx CK_TARGET_LUNCH 'aosp_cf-userdebug
+second line'
+set -gx CK_DEVICE_SERIAL 'emulator-5554'
+set -gx CK_GIT_DIRTY_REPOS '. kernel wip'
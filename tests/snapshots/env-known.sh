@@ -0,0 +1,6 @@
+export CK_PROJECT_NAME='o'\''brien firmware'
+export CK_TARGET_CONTAINER='it'\''s a "builder" $HOME `id` \n'
+export CK_TARGET_LUNCH='aosp_cf-userdebug
+second line'
+export CK_DEVICE_SERIAL='emulator-5554'
+export CK_GIT_DIRTY_REPOS='. kernel wip'
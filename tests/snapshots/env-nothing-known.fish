@@ -0,0 +1,5 @@
+set -e CK_PROJECT_NAME  # no [project] name in the config
+set -e CK_TARGET_CONTAINER  # no --target given
+set -e CK_TARGET_LUNCH  # no --target given
+set -e CK_DEVICE_SERIAL  # no device connected
+set -e CK_GIT_DIRTY_REPOS  # no git repositories found
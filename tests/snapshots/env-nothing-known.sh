@@ -0,0 +1,5 @@
+unset CK_PROJECT_NAME  # no [project] name in the config
+unset CK_TARGET_CONTAINER  # no --target given
+unset CK_TARGET_LUNCH  # no --target given
+unset CK_DEVICE_SERIAL  # no device connected
+unset CK_GIT_DIRTY_REPOS  # no git repositories found
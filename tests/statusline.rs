@@ -0,0 +1,202 @@
+//! `context-keeper statusline` fed recorded statusline payloads
+
+mod common;
+
+use common::{stderr, stdout, Sandbox};
+use serde_json::json;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+const BOLD: &str = "\x1b[1m";
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// What Claude Code sends, with its paths pointing at the sandbox project
+fn recorded(sandbox: &Sandbox) -> String {
+    let root = sandbox.root().to_str().unwrap().replace('\\', "\\\\");
+    include_str!("fixtures/statusline_claude_code.json").replace("{root}", &root)
+}
+
+fn with_stdin(mut command: Command, payload: &str) -> Output {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(payload.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// `statusline` run from HOME, so only the payload points at the project
+fn statusline(sandbox: &Sandbox, payload: &str) -> String {
+    let mut command = sandbox.command(&["statusline"]);
+    command.current_dir(sandbox.home()).env_remove("NO_COLOR");
+    line(with_stdin(command, payload))
+}
+
+fn line(output: Output) -> String {
+    assert!(output.status.success(), "{}", stderr(&output));
+    let text = stdout(&output);
+    assert_eq!(text.lines().count(), 1, "{:?}", text);
+    text.trim_end().to_string()
+}
+
+fn project() -> Sandbox {
+    let sandbox = Sandbox::new();
+    sandbox.write("contextkeeper.toml", "[project]\nname = \"app\"\n");
+    sandbox.write("src/main.c", "int main(void) { return 0; }\n");
+    sandbox
+}
+
+/// The project's state directory, made by saving `task`
+fn save_task(sandbox: &Sandbox, task: &str) -> PathBuf {
+    let output = sandbox.run(&["save-state", task]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let projects = sandbox.home().join(".contextkeeper/projects");
+    std::fs::read_dir(projects)
+        .unwrap()
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|dir| dir.join("work-state.json").exists())
+        .expect("a project state directory")
+}
+
+/// Snapshots as the collectors save them, `minutes_ago` old
+fn write_snapshots(dir: &Path, minutes_ago: i64) {
+    let saved_at = (chrono::Utc::now() - chrono::Duration::minutes(minutes_ago)).to_rfc3339();
+    let repo = |path: &str, modified: usize, conflicted: usize| {
+        json!({
+            "repo_path": path, "branch": "main", "is_dirty": modified + conflicted > 0,
+            "modified_files": modified, "untracked_files": 0,
+            "conflicted_files": conflicted, "last_commit_short": "abc1234",
+        })
+    };
+    let build = |pid: u32, command: &str| {
+        json!({
+            "pid": pid, "command": command, "cmdline": command, "cwd": "out",
+            "started_at": saved_at, "cpu_percent": 180.0,
+        })
+    };
+    let device = |serial: &str| json!({"serial": serial, "state": "device", "device_type": "adb"});
+    for (file, value) in [
+        (
+            "git-status.json",
+            json!({"saved_at": saved_at, "repos": [
+                repo(".", 2, 0), repo("vendor/qcom", 1, 1), repo("kernel", 0, 0),
+            ]}),
+        ),
+        (
+            "processes.json",
+            json!({"saved_at": saved_at, "builds": [build(4100, "soong_ui"), build(4242, "ninja")]}),
+        ),
+        (
+            "devices.json",
+            json!({"saved_at": saved_at, "devices": [device("R58M"), device("emulator-5554")]}),
+        ),
+    ] {
+        std::fs::write(dir.join(file), value.to_string()).unwrap();
+    }
+}
+
+#[test]
+fn fallback_line_before_anything_is_cached() {
+    let sandbox = project();
+    assert_eq!(
+        statusline(&sandbox, &recorded(&sandbox)),
+        format!("{}app{} ▸ no context collected yet", BOLD, RESET)
+    );
+
+    // Without a payload, or with one that doesn't parse: the working
+    // directory, without colors
+    for payload in ["", "not json {", "[1, 2]"] {
+        let output = with_stdin(sandbox.command(&["statusline"]), payload);
+        assert_eq!(
+            line(output),
+            "app ▸ no context collected yet",
+            "{:?}",
+            payload
+        );
+    }
+}
+
+#[test]
+fn recorded_payload_with_cached_state() {
+    let sandbox = project();
+    let dir = save_task(
+        &sandbox,
+        "fix the flaky audio underrun test on the r58 board",
+    );
+    write_snapshots(&dir, 1);
+
+    assert_eq!(
+        statusline(&sandbox, &recorded(&sandbox)),
+        format!(
+            "{BOLD}app{RESET} ▸ {RED}⎇2 dirty ✖1 conflicts{RESET} ▸ {CYAN}⚙ soong_ui +1{RESET} \
+             ▸ 2 devices ▸ task: fix the flaky audio underrun test on th…"
+        )
+    );
+
+    // Unknown fields are ignored; colors only when asked for or from
+    // Claude Code
+    let plain = "app ▸ ⎇2 dirty ✖1 conflicts ▸ ⚙ soong_ui +1 ▸ 2 devices \
+                 ▸ task: fix the flaky audio underrun test on th…";
+    let mut payload: serde_json::Value = serde_json::from_str(&recorded(&sandbox)).unwrap();
+    payload["colors"] = json!(false);
+    assert_eq!(statusline(&sandbox, &payload.to_string()), plain);
+    payload["colors"] = json!(true);
+    let mut command = sandbox.command(&["statusline"]);
+    command.current_dir(sandbox.home()).env("NO_COLOR", "1");
+    assert_eq!(line(with_stdin(command, &payload.to_string())), plain);
+
+    let root = sandbox.root().to_str().unwrap();
+    let minimal = json!({"cwd": root, "extra": {"nested": [1, 2]}});
+    assert_eq!(statusline(&sandbox, &minimal.to_string()), plain);
+}
+
+#[test]
+fn old_snapshots_drop_builds_and_devices() {
+    let sandbox = project();
+    let dir = save_task(&sandbox, "flash");
+    write_snapshots(&dir, 60);
+    let payload = json!({
+        "workspace": {"project_dir": sandbox.root(), "current_dir": sandbox.home()},
+        "colors": false,
+    });
+    assert_eq!(
+        statusline(&sandbox, &payload.to_string()),
+        "app ▸ ⎇2 dirty ✖1 conflicts~ ▸ task: flash"
+    );
+
+    // Clean repos have nothing to mark
+    let clean = json!({"saved_at": "2026-01-01T00:00:00Z", "repos": []});
+    std::fs::write(dir.join("git-status.json"), clean.to_string()).unwrap();
+    assert_eq!(
+        statusline(&sandbox, &payload.to_string()),
+        "app ▸ task: flash"
+    );
+}
+
+#[test]
+fn yellow_without_conflicts() {
+    let sandbox = project();
+    let dir = save_task(&sandbox, "review");
+    let saved_at = chrono::Utc::now().to_rfc3339();
+    let snapshot = json!({"saved_at": saved_at, "repos": [{
+        "repo_path": ".", "branch": "main", "is_dirty": true, "modified_files": 1,
+        "untracked_files": 0, "conflicted_files": 0, "last_commit_short": "abc1234",
+    }]});
+    std::fs::write(dir.join("git-status.json"), snapshot.to_string()).unwrap();
+    assert_eq!(
+        statusline(&sandbox, &recorded(&sandbox)),
+        format!("{BOLD}app{RESET} ▸ {YELLOW}⎇1 dirty{RESET} ▸ task: review")
+    );
+}
@@ -0,0 +1,48 @@
+//! Over stdio, stdout is the protocol: logs at any verbosity go to stderr
+//! and the log file, never between the frames
+#![cfg(feature = "stdio-server")]
+
+mod common;
+
+use common::mcp::McpClient;
+use common::Sandbox;
+use serde_json::{json, Value};
+
+#[test]
+fn stdout_carries_only_frames_during_tool_calls() {
+    let sandbox = Sandbox::new();
+    sandbox.write(
+        "contextkeeper.toml",
+        "[project]\nname = \"frames\"\n\n[logging]\nfile = true\n",
+    );
+    let mut client = McpClient::start(&sandbox, &["-vvv"]);
+    client.call_text("get_dev_context", json!({ "level": "full" }));
+    client.call_text(
+        "save_work_state",
+        json!({ "task_summary": "check the frames" }),
+    );
+    client.call_text("get_dev_context", json!({ "format": "json" }));
+    let lines = client.lines.clone();
+    assert!(client.close().success());
+
+    for line in &lines {
+        let frame: Value = serde_json::from_str(line)
+            .unwrap_or_else(|e| panic!("not a frame ({}): {:?}", e, line));
+        assert_eq!(frame["jsonrpc"], "2.0", "{}", line);
+        assert!(line.ends_with('\n') && !line.trim_end().contains('\n'));
+    }
+
+    // The collection did log, just not there
+    let logs: String = std::fs::read_dir(sandbox.home().join(".contextkeeper"))
+        .unwrap()
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("context-keeper.log")
+        })
+        .map(|entry| std::fs::read_to_string(entry.path()).unwrap())
+        .collect();
+    assert!(logs.contains("DEBUG"), "{}", logs);
+}
@@ -0,0 +1,261 @@
+//! Target .conf files: shell assignments, quoting, `source` includes and
+//! the directories and patterns that select them
+
+use context_keeper::collectors::targets::{
+    collect_build_targets, parse_config_file, parse_shell_word, parse_var_assignment,
+};
+use context_keeper::collectors::{BuildTarget, COLLECTOR_STATS};
+use context_keeper::ProjectContext;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// The key and expanded value of one line, with the variables it could
+/// not expand
+fn assignment(line: &str) -> Option<(String, String, Vec<String>)> {
+    let (key, raw) = parse_var_assignment(line)?;
+    let mut lookup = |name: &str| match name {
+        "OUT_DIR" => Some("/work/out".to_string()),
+        "EMPTY" => Some(String::new()),
+        _ => None,
+    };
+    let mut unresolved = Vec::new();
+    let value = parse_shell_word(raw, &mut lookup, &mut unresolved);
+    Some((key.to_string(), value, unresolved))
+}
+
+#[test]
+fn shell_assignment_lines() {
+    for (line, value) in [
+        ("FOO=bar", "bar"),
+        ("export FOO=bar", "bar"),
+        ("readonly FOO=bar", "bar"),
+        ("FOO=", ""),
+        ("FOO=a b", "a"),
+        ("FOO=bar # trailing comment", "bar"),
+        (r#"FOO="a=b""#, "a=b"),
+        (r#"FOO="bar # kept""#, "bar # kept"),
+        (r#"FOO='it'\''s'"#, "it's"),
+        (r#"FOO="say \"hi\"""#, r#"say "hi""#),
+        (r#"FOO="back\\slash""#, r"back\slash"),
+        (r#"FOO="not \n an escape""#, r"not \n an escape"),
+        (r"FOO=a\ b", "a b"),
+        ("FOO=$OUT_DIR/img", "/work/out/img"),
+        ("FOO=${OUT_DIR}img", "/work/outimg"),
+        (r#"FOO="$OUT_DIR/with space""#, "/work/out/with space"),
+        ("FOO='${OUT_DIR}'", "${OUT_DIR}"),
+        (r#"FOO="\$OUT_DIR""#, "$OUT_DIR"),
+        ("FOO=${UNSET:-fallback}", "fallback"),
+        ("FOO=${EMPTY:-fallback}", "fallback"),
+        ("FOO=${EMPTY-fallback}", ""),
+        ("FOO=${UNSET-fallback}", "fallback"),
+        ("FOO=cost$", "cost$"),
+    ] {
+        let (key, parsed, unresolved) =
+            assignment(line).unwrap_or_else(|| panic!("not an assignment: {}", line));
+        assert_eq!(key, "FOO", "{}", line);
+        assert_eq!(parsed, value, "{}", line);
+        assert!(unresolved.is_empty(), "{}: {:?}", line, unresolved);
+    }
+}
+
+#[test]
+fn unexpanded_references_are_kept_and_reported() {
+    for (line, value, names) in [
+        ("FOO=$UNSET/x", "$UNSET/x", &["UNSET"][..]),
+        ("FOO=${UNSET}", "${UNSET}", &["UNSET"]),
+        ("FOO=${OUT_DIR:+set}", "${OUT_DIR:+set}", &["OUT_DIR"]),
+    ] {
+        let (_, parsed, unresolved) = assignment(line).unwrap();
+        assert_eq!(parsed, value, "{}", line);
+        assert_eq!(unresolved, names, "{}", line);
+    }
+}
+
+#[test]
+fn lines_that_are_not_assignments() {
+    for line in [
+        "echo TARGET_NAME=x",
+        "1FOO=bar",
+        "FOO-BAR=x",
+        "=value",
+        "if [ -n \"$X\" ]; then",
+    ] {
+        assert!(assignment(line).is_none(), "{}", line);
+    }
+}
+
+#[test]
+fn includes_continuations_and_cycles() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("common")).unwrap();
+    fs::write(
+        dir.path().join("common/base.sh"),
+        "CONTAINER_NAME=aosp-env\nTARGET_DESCRIPTION=\"from base\"\n\
+         # back to the including file, which is not read twice\n\
+         source ../pixel.conf\n",
+    )
+    .unwrap();
+    let conf = dir.path().join("pixel.conf");
+    fs::write(
+        &conf,
+        "#!/bin/bash\n. ./common/base.sh\nTARGET_NAME=pixel\n\
+         TARGET_DESCRIPTION=\"Pixel 7, \\\nuserdebug\"\n\
+         LUNCH_TARGET=${PRODUCT:-aosp_panther}-userdebug\nCAN_FLASH=true\n",
+    )
+    .unwrap();
+
+    let target = parse_config_file(&conf, &HashMap::new()).unwrap();
+    assert_eq!(target.name, "pixel");
+    assert_eq!(target.container_name, "aosp-env");
+    // Assigned after the include, then joined across the continuation
+    assert_eq!(target.description, "Pixel 7, userdebug");
+    assert_eq!(target.lunch_target, "aosp_panther-userdebug");
+    assert!(target.can_flash && !target.can_emulator);
+
+    let vars = HashMap::from([("PRODUCT".to_string(), "aosp_cheetah".to_string())]);
+    let target = parse_config_file(&conf, &vars).unwrap();
+    assert_eq!(target.lunch_target, "aosp_cheetah-userdebug");
+}
+
+#[test]
+fn name_falls_back_to_the_file_stem() {
+    let dir = tempfile::tempdir().unwrap();
+    let conf = dir.path().join("qcom-dev.conf");
+    fs::write(&conf, "CONTAINER_NAME=qcom\n").unwrap();
+    assert_eq!(
+        parse_config_file(&conf, &HashMap::new()).unwrap().name,
+        "qcom-dev"
+    );
+    assert!(parse_config_file(&dir.path().join("missing.conf"), &HashMap::new()).is_none());
+}
+
+/// A project with `toml` as its config and `files` (path, content) under it
+fn project_with(toml: &str, files: &[(&str, &str)]) -> (tempfile::TempDir, ProjectContext) {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("contextkeeper.toml"), toml).unwrap();
+    for (path, content) in files {
+        let path = dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    let project = ProjectContext::load(dir.path().to_path_buf(), None);
+    (dir, project)
+}
+
+/// The targets and the collector warnings, from a fresh thread so the
+/// warnings are only this collection's
+fn targets_of(project: ProjectContext) -> (Vec<BuildTarget>, Vec<String>) {
+    std::thread::spawn(move || {
+        let targets = collect_build_targets(&project);
+        let warnings = COLLECTOR_STATS.with(|stats| stats.borrow().warnings.clone());
+        (targets, warnings)
+    })
+    .join()
+    .unwrap()
+}
+
+/// Target names with the file each came from, relative to `root`
+fn sources(targets: &[BuildTarget], root: &Path) -> Vec<(String, String)> {
+    let root = root.canonicalize().unwrap();
+    targets
+        .iter()
+        .map(|t| {
+            let file = Path::new(&t.source_file).canonicalize().unwrap();
+            let file = file.strip_prefix(&root).unwrap().to_string_lossy();
+            (t.name.clone(), file.replace('\\', "/"))
+        })
+        .collect()
+}
+
+#[test]
+fn nested_tree_across_several_directories() {
+    let (dir, project) = project_with(
+        r#"
+        [scripts]
+        config_dir = ["configs/boards", "configs/variants/"]
+        config_pattern = "**/*.conf"
+        "#,
+        &[
+            ("configs/boards/rpi4.conf", "TARGET_NAME=rpi4\n"),
+            ("configs/boards/qcom/sm8550.conf", "TARGET_NAME=sm8550\n"),
+            (
+                "configs/boards/qcom/deep/er/imx8.conf",
+                "TARGET_NAME=imx8\nCONTAINER_NAME=old\n",
+            ),
+            ("configs/boards/README.md", "not a target"),
+            (
+                "configs/variants/imx8.conf",
+                "TARGET_NAME=imx8\nCONTAINER_NAME=new\n",
+            ),
+            (
+                "configs/variants/debug/rpi4-debug.conf",
+                "TARGET_NAME=rpi4-debug\n",
+            ),
+            // Outside both directories
+            ("configs/other/stray.conf", "TARGET_NAME=stray\n"),
+        ],
+    );
+    let (targets, warnings) = targets_of(project);
+
+    let mut found = sources(&targets, dir.path());
+    found.sort();
+    assert_eq!(
+        found,
+        [
+            ("imx8", "configs/variants/imx8.conf"),
+            ("rpi4", "configs/boards/rpi4.conf"),
+            ("rpi4-debug", "configs/variants/debug/rpi4-debug.conf"),
+            ("sm8550", "configs/boards/qcom/sm8550.conf"),
+        ]
+        .map(|(name, file)| (name.to_string(), file.to_string()))
+    );
+    // The later directory wins, and says so
+    let imx8 = targets.iter().find(|t| t.name == "imx8").unwrap();
+    assert_eq!(imx8.container_name, "new");
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert!(
+        warnings[0].starts_with("target imx8: ")
+            && warnings[0].contains("variants")
+            && warnings[0].contains(" overrides ")
+            && warnings[0].contains("deep/er"),
+        "{:?}",
+        warnings
+    );
+}
+
+#[test]
+fn single_star_stays_in_the_directory() {
+    let (dir, project) = project_with(
+        "[scripts]\nconfig_dir = \"configs\"\n",
+        &[
+            ("configs/top.conf", "TARGET_NAME=top\n"),
+            ("configs/nested/below.conf", "TARGET_NAME=below\n"),
+        ],
+    );
+    let (targets, _) = targets_of(project);
+    assert_eq!(
+        sources(&targets, dir.path()),
+        [("top".to_string(), "configs/top.conf".to_string())]
+    );
+}
+
+#[test]
+fn pattern_case_is_configurable() {
+    let files = [("configs/Board.CONF", "TARGET_NAME=board\n")];
+    let (_dir, project) = project_with("[scripts]\nconfig_dir = \"configs\"\n", &files);
+    let (targets, warnings) = targets_of(project);
+    assert!(targets.is_empty());
+    assert!(
+        warnings[0].ends_with("*.conf matched 0 files"),
+        "{:?}",
+        warnings
+    );
+
+    let (_dir, project) = project_with(
+        "[scripts]\nconfig_dir = \"configs\"\nconfig_pattern_ignore_case = true\n",
+        &files,
+    );
+    let (targets, _) = targets_of(project);
+    assert_eq!(targets.len(), 1);
+}
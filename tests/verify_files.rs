@@ -0,0 +1,309 @@
+//! `working_files` given to `save_work_state`: normalization, the nearest
+//! changed path suggested for one that isn't there, and the verdicts
+//! echoed back
+
+mod common;
+
+use common::git::{commit, git, has_git};
+use context_keeper::collectors::verify_files::{
+    format_file_verdicts, nearest_path, normalize_working_path, verify_working_files, FileVerdict,
+    FileVerdictState,
+};
+use context_keeper::format::format_work_state;
+use context_keeper::i18n::Language;
+use context_keeper::state::WorkState;
+use context_keeper::ProjectContext;
+use std::fs;
+
+fn paths(list: &[&str]) -> Vec<String> {
+    list.iter().map(|p| p.to_string()).collect()
+}
+
+#[test]
+fn nearest_by_edit_distance() {
+    let candidates = paths(&["src/hal/audio.cpp", "src/hal/video.cpp", "docs/audio.md"]);
+    let nearest = |path| nearest_path(path, &candidates);
+    assert_eq!(nearest("src/hall/audio.cpp"), Some("src/hal/audio.cpp"));
+    assert_eq!(nearest("src/hal/audoi.cpp"), Some("src/hal/audio.cpp"));
+    assert_eq!(nearest("src/hal/vidoe.cpp"), Some("src/hal/video.cpp"));
+    // Within a quarter of the length, and at least one edit
+    assert_eq!(nearest("lib/other/thing.c"), None);
+    assert_eq!(nearest_path("b.c", &paths(&["a.c"])), Some("a.c"));
+    assert_eq!(nearest_path("b.c", &paths(&["ab.cc"])), None);
+    // Never the path itself, and ties go to the earlier candidate
+    assert_eq!(
+        nearest_path("src/hal/audio.cpp", &paths(&["src/hal/audio.cpp"])),
+        None
+    );
+    assert_eq!(
+        nearest_path("src/c.c", &paths(&["src/a.c", "src/b.c"])),
+        Some("src/a.c")
+    );
+    assert_eq!(nearest_path("src/a.c", &[]), None);
+}
+
+#[test]
+fn nearest_ignores_case() {
+    let candidates = paths(&["src/Hal/Audio.cpp", "src/hal/audio.cp"]);
+    // Differing only in case beats a closer match that differs otherwise
+    assert_eq!(
+        nearest_path("src/hal/audio.cpp", &candidates),
+        Some("src/Hal/Audio.cpp")
+    );
+    // Case doesn't count as an edit
+    assert_eq!(
+        nearest_path("SRC/HALL/AUDIO.CP", &paths(&["src/hal/audio.cp"])),
+        Some("src/hal/audio.cp")
+    );
+    assert_eq!(
+        nearest_path("README.md", &paths(&["readme.md", "README.md"])),
+        Some("readme.md")
+    );
+}
+
+/// A project named `firmware` with `src/hal/audio.cpp` on disk
+fn project_dir() -> (tempfile::TempDir, std::path::PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("firmware");
+    fs::create_dir_all(root.join("src/hal")).unwrap();
+    fs::write(root.join("src/hal/audio.cpp"), "int a;\n").unwrap();
+    (dir, root)
+}
+
+#[test]
+fn the_usual_slips_are_normalized() {
+    let (_dir, root) = project_dir();
+    let absolute = root.join("src/hal/audio.cpp");
+    let file_url = format!("file://{}", absolute.display());
+    for input in [
+        "src/hal/audio.cpp",
+        "./src/../src/hal/audio.cpp",
+        "  src/hal/audio.cpp\n",
+        absolute.to_str().unwrap(),
+        &file_url,
+        "src/hal/audio.cpp:42",
+        "src/hal/audio.cpp:42:7",
+        "b/src/hal/audio.cpp",
+        "a/src/hal/audio.cpp",
+        "firmware/src/hal/audio.cpp",
+        "b/src/hal/audio.cpp:3",
+    ] {
+        assert_eq!(
+            normalize_working_path(&root, input),
+            "src/hal/audio.cpp",
+            "{:?}",
+            input
+        );
+    }
+
+    // Nothing on disk: the plain relative path
+    for (input, path) in [
+        ("src/hall/audio.cpp", "src/hall/audio.cpp"),
+        ("./src/new.c:12", "src/new.c:12"),
+        ("b/new.c", "b/new.c"),
+    ] {
+        assert_eq!(normalize_working_path(&root, input), path);
+    }
+    // A prefix is only stripped when what is left exists
+    fs::create_dir_all(root.join("b")).unwrap();
+    fs::write(root.join("b/src"), "").unwrap();
+    assert_eq!(normalize_working_path(&root, "b/src"), "b/src");
+
+    let outside = normalize_working_path(&root, "/elsewhere/x.c");
+    assert!(outside.ends_with(" [outside project]"), "{}", outside);
+}
+
+#[test]
+fn case_is_kept_as_given() {
+    let (_dir, root) = project_dir();
+    // On a case-sensitive filesystem the other case isn't the file, and
+    // the path isn't corrected to it
+    if root.join("SRC/HAL/AUDIO.CPP").exists() {
+        return;
+    }
+    assert_eq!(
+        normalize_working_path(&root, "SRC/HAL/AUDIO.CPP"),
+        "SRC/HAL/AUDIO.CPP"
+    );
+    assert_eq!(
+        normalize_working_path(&root, "b/SRC/hal/audio.cpp"),
+        "b/SRC/hal/audio.cpp"
+    );
+}
+
+fn verdict(verdict: &FileVerdict) -> (&str, FileVerdictState, Option<&str>) {
+    (
+        verdict.path.as_str(),
+        verdict.state,
+        verdict.suggestion.as_deref(),
+    )
+}
+
+#[test]
+fn verdicts_against_a_repository() {
+    if !has_git() {
+        return;
+    }
+    let (_dir, root) = project_dir();
+    git(&root, &["init", "-q"]);
+    commit(&root, "src/hal/video.cpp", "int v;\n", "init");
+    fs::write(root.join("src/hal/video.cpp"), "int v = 1;\n").unwrap();
+    fs::write(root.join("src/hal/new.cpp"), "int n;\n").unwrap();
+    let project = ProjectContext::load(root.clone(), None);
+
+    let case_sensitive = !root.join("SRC/HAL/VIDEO.CPP").exists();
+    let verdicts = verify_working_files(
+        &project,
+        &paths(&[
+            "b/src/hal/video.cpp",
+            "src/hal/new.cpp",
+            "src/hal/vidoe.cpp:10",
+            "src/hal/VIDEO.cpp",
+            "gone/made_up.rs",
+            "/elsewhere/x.c",
+        ]),
+    );
+    let answers: Vec<_> = verdicts.iter().map(verdict).collect();
+    assert_eq!(
+        answers[0],
+        ("src/hal/video.cpp", FileVerdictState::Tracked, None)
+    );
+    // On disk, never committed
+    assert_eq!(
+        answers[1],
+        ("src/hal/new.cpp", FileVerdictState::Untracked, None)
+    );
+    // Suggestions come from what git lists as changed
+    assert_eq!(
+        answers[2],
+        (
+            "src/hal/vidoe.cpp:10",
+            FileVerdictState::NotFound,
+            Some("src/hal/video.cpp")
+        )
+    );
+    if case_sensitive {
+        assert_eq!(
+            answers[3],
+            (
+                "src/hal/VIDEO.cpp",
+                FileVerdictState::NotFound,
+                Some("src/hal/video.cpp")
+            )
+        );
+    }
+    assert_eq!(
+        answers[4],
+        ("gone/made_up.rs", FileVerdictState::NotFound, None)
+    );
+    assert_eq!(answers[5].1, FileVerdictState::OutsideProject);
+    assert_eq!(verdicts[2].input, "src/hal/vidoe.cpp:10");
+
+    if case_sensitive {
+        let reply = format_file_verdicts(6, &verdicts);
+        assert_eq!(
+            reply,
+            format!(
+                "6 saved; 3 paths not found: src/hal/vidoe.cpp:10 — did you mean src/hal/video.cpp?, \
+                 src/hal/VIDEO.cpp — did you mean src/hal/video.cpp?, gone/made_up.rs; \
+                 1 path outside the project: {}; 1 path not tracked by git: src/hal/new.cpp",
+                verdicts[5].path
+            )
+        );
+    }
+}
+
+#[test]
+fn verdict_summaries() {
+    let file = |path: &str, state, suggestion: Option<&str>| FileVerdict {
+        input: path.to_string(),
+        path: path.to_string(),
+        state,
+        suggestion: suggestion.map(str::to_string),
+    };
+    assert_eq!(
+        format_file_verdicts(2, &[file("a.c", FileVerdictState::Tracked, None)]),
+        "2 saved"
+    );
+    assert_eq!(
+        format_file_verdicts(
+            5,
+            &[
+                file(
+                    "src/hall/audio.cpp",
+                    FileVerdictState::NotFound,
+                    Some("src/hal/audio.cpp")
+                ),
+                file("new.c", FileVerdictState::Untracked, None),
+                file("x.c", FileVerdictState::Tracked, None),
+            ]
+        ),
+        "5 saved; 1 path not found: src/hall/audio.cpp — did you mean src/hal/audio.cpp?; \
+         1 path not tracked by git: new.c"
+    );
+}
+
+#[test]
+fn unverified_files_are_marked() {
+    let state = WorkState {
+        task_summary: "audio".to_string(),
+        working_files: paths(&["src/hal/audio.cpp", "src/hall/audio.cpp"]),
+        unverified_files: paths(&["src/hall/audio.cpp"]),
+        ..WorkState::default()
+    };
+    let text = format_work_state(&state, &[], &[], Language::En);
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|line| line.contains("audio.cpp"))
+        .collect();
+    assert_eq!(lines.len(), 2, "{}", text);
+    assert!(!lines[0].contains("not found when saved"), "{}", text);
+    assert!(lines[1].contains("not found when saved"), "{}", text);
+}
+
+#[cfg(feature = "stdio-server")]
+#[test]
+fn save_work_state_echoes_the_verdicts() {
+    use common::mcp::McpClient;
+    use common::{stdout, Sandbox};
+    use serde_json::json;
+
+    if !has_git() {
+        return;
+    }
+    let sandbox = Sandbox::new();
+    git(sandbox.root(), &["init", "-q"]);
+    commit(sandbox.root(), "src/hal/audio.cpp", "int a;\n", "init");
+    sandbox.write("src/hal/audio.cpp", "int a = 1;\n");
+
+    let mut client = McpClient::start(&sandbox, &[]);
+    let reply = client.call_text(
+        "save_work_state",
+        json!({
+            "task_summary": "audio",
+            "working_files": ["b/src/hal/audio.cpp", "src/hall/audio.cpp", "src/hal/audio.cpp:12"],
+        }),
+    );
+    client.close();
+    assert!(
+        reply.contains(
+            "2 saved; 1 path not found: src/hall/audio.cpp — did you mean src/hal/audio.cpp?"
+        ),
+        "{}",
+        reply
+    );
+    let state = sandbox.work_state().unwrap();
+    assert_eq!(
+        state["working_files"],
+        json!(["src/hal/audio.cpp", "src/hall/audio.cpp"])
+    );
+    assert_eq!(state["unverified_files"], json!(["src/hall/audio.cpp"]));
+
+    let output = sandbox.run(&["context", "--level", "normal"]);
+    let text = stdout(&output);
+    assert!(
+        text.contains("  - src/hall/audio.cpp (not found when saved)\n"),
+        "{}",
+        text
+    );
+}
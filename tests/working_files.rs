@@ -0,0 +1,224 @@
+//! The working files save-state records, from fixture repositories with
+//! awkward file names, and what became of them after commits made since
+
+mod common;
+
+use common::git::{commit, git, has_git};
+use common::{stderr, stdout, Sandbox};
+use context_keeper::collectors::working_files::{
+    check_working_files, WorkingFileCheck, WorkingFileState,
+};
+use context_keeper::format::format_working_file_warnings;
+use context_keeper::state::WorkState;
+use context_keeper::ProjectContext;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+fn strings(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v.as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn spaces_and_unicode_in_file_names() {
+    if !has_git() {
+        return;
+    }
+    let sandbox = Sandbox::new();
+    sandbox.write("contextkeeper.toml", "[project]\nname = \"names\"\n");
+    let repo = sandbox.root().join("my repo");
+    fs::create_dir(&repo).unwrap();
+    git(&repo, &["init", "-q"]);
+    for file in ["src/with space.c", "ドキュメント/説明.md", "naïve.txt"] {
+        commit(&repo, file, "one\n", file);
+    }
+    fs::write(repo.join("src/with space.c"), "two\n").unwrap();
+    fs::write(repo.join("ドキュメント/説明.md"), "two\n").unwrap();
+    fs::write(repo.join("naïve.txt"), "two\n").unwrap();
+    git(&repo, &["add", "naïve.txt"]);
+
+    let output = sandbox.run(&["save-state", "names"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let state = sandbox.work_state().unwrap();
+
+    // Unstaged, then staged; as they are on disk, never quoted or escaped
+    let mut files = strings(&state["working_files"]);
+    let unstaged = files.drain(..2).collect::<Vec<_>>();
+    let mut sorted = unstaged.clone();
+    sorted.sort();
+    assert_eq!(
+        sorted,
+        ["my repo/src/with space.c", "my repo/ドキュメント/説明.md"]
+    );
+    assert_eq!(files, ["my repo/naïve.txt"]);
+    for file in unstaged.iter().chain(&files) {
+        assert!(sandbox.root().join(file).is_file(), "{}", file);
+    }
+
+    let repos = state["working_repos"].as_array().unwrap();
+    assert_eq!(repos.len(), 1);
+    assert_eq!(repos[0]["path"], "my repo");
+    assert_eq!(repos[0]["files"], 3);
+}
+
+#[test]
+fn the_cap_is_configurable() {
+    if !has_git() {
+        return;
+    }
+    let sandbox = Sandbox::new();
+    sandbox.write(
+        "contextkeeper.toml",
+        "[project]\nname = \"cap\"\n\n[git]\nmax_working_files = 2\n",
+    );
+    let root = sandbox.root();
+    git(root, &["init", "-q"]);
+    for n in 0..5 {
+        commit(root, &format!("file {}.txt", n), "one\n", "add");
+    }
+    for n in 0..5 {
+        fs::write(root.join(format!("file {}.txt", n)), "two\n").unwrap();
+    }
+
+    let output = sandbox.run(&["save-state", "cap"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let state = sandbox.work_state().unwrap();
+    let files = strings(&state["working_files"]);
+    assert_eq!(files.len(), 2, "{:?}", files);
+    assert!(files.iter().all(|f| f.starts_with("file ")), "{:?}", files);
+    assert_eq!(state["working_repos"][0]["path"], ".");
+}
+
+/// `app/` with a.c to d.c committed and then all four changed
+fn four_changed_files(sandbox: &Sandbox) -> std::path::PathBuf {
+    sandbox.write("contextkeeper.toml", "[project]\nname = \"since\"\n");
+    let repo = sandbox.root().join("app");
+    fs::create_dir(&repo).unwrap();
+    git(&repo, &["init", "-q"]);
+    for file in ["a.c", "b.c", "c.c", "d.c"] {
+        commit(&repo, file, "one\n", file);
+    }
+    for file in ["a.c", "b.c", "c.c", "d.c"] {
+        fs::write(repo.join(file), "two\n").unwrap();
+    }
+    repo
+}
+
+/// After the save: b.c committed, c.c deleted in a commit, d.c reverted,
+/// a.c left as it was. Returns the commits of b.c and c.c.
+fn work_since_the_save(repo: &Path) -> (String, String) {
+    let head = || {
+        git(repo, &["rev-parse", "--short", "HEAD"])
+            .trim()
+            .to_string()
+    };
+    git(repo, &["commit", "-qm", "Land b", "b.c"]);
+    let landed = head();
+    git(repo, &["rm", "-qf", "c.c"]);
+    git(repo, &["commit", "-qm", "Drop c", "c.c"]);
+    let dropped = head();
+    git(repo, &["checkout", "d.c"]);
+    (landed, dropped)
+}
+
+fn checks(sandbox: &Sandbox, state: &WorkState) -> Vec<WorkingFileCheck> {
+    let project = ProjectContext::load(sandbox.root().to_path_buf(), None);
+    check_working_files(&project, state)
+}
+
+fn summary(checks: &[WorkingFileCheck]) -> Vec<(&str, WorkingFileState, Option<&str>)> {
+    let mut summary: Vec<_> = checks
+        .iter()
+        .map(|c| (c.file.as_str(), c.state, c.commit.as_deref()))
+        .collect();
+    summary.sort_by_key(|(file, _, _)| *file);
+    summary
+}
+
+#[test]
+fn commits_between_save_and_load() {
+    if !has_git() {
+        return;
+    }
+    let sandbox = Sandbox::new();
+    let repo = four_changed_files(&sandbox);
+    let output = sandbox.run(&["save-state", "four files"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let state: WorkState = serde_json::from_value(sandbox.work_state().unwrap()).unwrap();
+    assert!(!state.working_repos[0].head.is_empty());
+
+    // Right after the save, all four still show in git status
+    let now = checks(&sandbox, &state);
+    assert!(
+        now.iter()
+            .all(|c| c.state == WorkingFileState::Modified && c.commit.is_none()),
+        "{:?}",
+        now
+    );
+
+    let (landed, dropped) = work_since_the_save(&repo);
+    let later = checks(&sandbox, &state);
+    assert_eq!(
+        summary(&later),
+        [
+            ("app/a.c", WorkingFileState::Modified, None),
+            ("app/b.c", WorkingFileState::Unchanged, Some(&*landed)),
+            ("app/c.c", WorkingFileState::Missing, Some(&*dropped)),
+            ("app/d.c", WorkingFileState::Unchanged, None),
+        ]
+    );
+
+    let warnings = format_working_file_warnings(&later);
+    assert_eq!(
+        warnings,
+        format!(
+            "⚠️ **2 of 4 saved files are no longer modified** — the work may already be \
+             committed; see commit {}\n⚠️ **1 of 4 saved files no longer exist:** app/c.c — \
+             see commit {}\n",
+            landed, dropped
+        )
+    );
+    let text = stdout(&sandbox.run(&["context"]));
+    assert!(text.contains(warnings.lines().next().unwrap()), "{}", text);
+}
+
+#[test]
+fn state_without_a_usable_head_goes_by_the_save_time() {
+    if !has_git() {
+        return;
+    }
+    let sandbox = Sandbox::new();
+    let repo = four_changed_files(&sandbox);
+    let output = sandbox.run(&["save-state", "four files"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let mut state: WorkState = serde_json::from_value(sandbox.work_state().unwrap()).unwrap();
+
+    // `git log --since` counts whole seconds: keep the commits before the
+    // save and the ones after it in different seconds
+    std::thread::sleep(Duration::from_millis(1100));
+    state.saved_at = chrono::Utc::now().to_rfc3339();
+    std::thread::sleep(Duration::from_millis(1100));
+    let (landed, dropped) = work_since_the_save(&repo);
+
+    // Saved before heads were recorded, and a head rebased away
+    for head in ["", "0000000"] {
+        state.working_repos[0].head = head.to_string();
+        assert_eq!(
+            summary(&checks(&sandbox, &state)),
+            [
+                ("app/a.c", WorkingFileState::Modified, None),
+                ("app/b.c", WorkingFileState::Unchanged, Some(&*landed)),
+                ("app/c.c", WorkingFileState::Missing, Some(&*dropped)),
+                ("app/d.c", WorkingFileState::Unchanged, None),
+            ],
+            "head {:?}",
+            head
+        );
+    }
+}